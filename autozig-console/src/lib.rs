@@ -35,7 +35,7 @@
 //! };
 //!
 //! #[no_mangle]
-//! pub extern "C" fn main() {
+//! pub extern "C" fn start() {
 //!     // Initialize panic hook (optional but recommended)
 //!     init_panic_hook();
 //!
@@ -181,7 +181,9 @@ macro_rules! console_error {
 /// Initialize panic hook to forward Rust panics to `console.error`.
 ///
 /// Call this function once when your WASM module is initialized to ensure
-/// that any Rust panics are properly logged to the browser console.
+/// that any Rust panics are properly logged to the browser console. A hook
+/// installed earlier (e.g. structured panic reporting) keeps running after
+/// the message is logged.
 ///
 /// # Examples
 ///
@@ -203,9 +205,36 @@ macro_rules! console_error {
 /// [AutoZig Error] RUST PANIC: panicked at 'index out of bounds: ...'
 /// ```
 pub fn init_panic_hook() {
-    std::panic::set_hook(Box::new(|info| {
-        let msg = info.to_string();
-        console_error!("RUST PANIC: {}", msg);
+    init_panic_hook_with(|info| format!("RUST PANIC: {}", info));
+}
+
+/// Initialize panic hook with a custom message format.
+///
+/// Like [`init_panic_hook`], but `formatter` turns the panic into the text
+/// passed to `console.error`. The previously installed hook is called after
+/// the message is logged.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use autozig_console::init_panic_hook_with;
+///
+/// init_panic_hook_with(|info| {
+///     let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+///     format!("[my-app] panic at {}", location)
+/// });
+/// ```
+// `PanicHookInfo` needs Rust 1.81; its deprecated `PanicInfo` alias keeps the
+// crate building on the declared MSRV
+#[allow(deprecated)]
+pub fn init_panic_hook_with<F>(formatter: F)
+where
+    F: Fn(&std::panic::PanicInfo<'_>) -> String + Send + Sync + 'static,
+{
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        console_error!("{}", formatter(info));
+        previous(info);
     }));
 }
 
@@ -0,0 +1,92 @@
+//! Print the Zig code a crate's build compiles
+//!
+//! The engine's scanner and code passes run as in the build script (error
+//! union and `f16` lowering, ABI wrappers, helper types, float and visibility
+//! settings), but nothing is compiled or written. Each file is printed after a
+//! `// ==== <name> ====` header: `generated_autozig.zig` in merged mode,
+//! `generated_main.zig` and the `include_zig!` files in the modular modes.
+//!
+//! Predicates of `#[cfg]`-gated signatures need cargo's variables and are
+//! treated as enabled.
+
+use std::path::PathBuf;
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_engine::{
+    AutoZigEngine,
+    CompilationMode,
+};
+use clap::{
+    Args,
+    ValueEnum,
+};
+
+/// Options of `autozig-cli emit-zig`
+#[derive(Args, Debug)]
+pub struct EmitZigArgs {
+    /// Crate directory containing Cargo.toml
+    #[arg(long = "crate", default_value = ".")]
+    crate_dir: PathBuf,
+    /// Source directory scanned for autozig code, relative to the crate
+    #[arg(long, default_value = "src")]
+    src: PathBuf,
+    /// Compilation mode (default: the blocks' `#![autozig(mode = ...)]`, else
+    /// merged)
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+    /// Rust target triple the code is generated for (affects visibility and
+    /// WASM-specific passes)
+    #[arg(long)]
+    target: Option<String>,
+}
+
+/// [`CompilationMode`] as spelled on the command line
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Mode {
+    Merged,
+    ModularImport,
+    ModularBuildzig,
+}
+
+impl From<Mode> for CompilationMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Merged => CompilationMode::Merged,
+            Mode::ModularImport => CompilationMode::ModularImport,
+            Mode::ModularBuildzig => CompilationMode::ModularBuildZig,
+        }
+    }
+}
+
+/// Print the generated Zig sources of the crate
+pub fn run(args: EmitZigArgs) -> Result<()> {
+    let crate_dir = args
+        .crate_dir
+        .canonicalize()
+        .with_context(|| format!("Crate directory {} not found", args.crate_dir.display()))?;
+    let src_dir = crate_dir.join(&args.src);
+
+    // Nothing is written to the output directory
+    let mut engine = match args.mode {
+        Some(mode) => AutoZigEngine::with_mode(src_dir, std::env::temp_dir(), mode.into()),
+        None => AutoZigEngine::new(src_dir, std::env::temp_dir()),
+    }
+    .with_manifest_dir(&crate_dir);
+    if let Some(target) = &args.target {
+        engine = engine.with_target(target);
+    }
+
+    let sources = engine.generated_sources()?;
+    if sources.is_empty() {
+        println!("// No Zig code found in {}", crate_dir.join(&args.src).display());
+    }
+    for (name, code) in sources {
+        println!("// ==== {name} ====");
+        println!("{}", code.trim_end());
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Show what the autozig macros generate for a Rust file
+//!
+//! Every `autozig!`, `include_zig!` and `bind_zig!` invocation of the file is
+//! expanded with the same code generator the proc macros use, and printed
+//! with its location:
+//!
+//! ```text
+//! // autozig! at src/lib.rs:12
+//! mod ffi {
+//!     use super::*;
+//!     extern "C" {
+//!         pub fn add(a: i32, b: i32) -> i32;
+//!     }
+//! }
+//! pub fn add(a: i32, b: i32) -> i32 { ... }
+//! ```
+//!
+//! Unlike `cargo expand`, nothing is compiled, and other macros are left
+//! alone.
+
+use std::{
+    fs,
+    path::PathBuf,
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use autozig_codegen::{
+    expand_autozig,
+    expand_external,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use clap::Args;
+use syn::visit::Visit;
+
+/// Options of `autozig-cli expand`
+#[derive(Args, Debug)]
+pub struct ExpandArgs {
+    /// Rust source file containing the macro invocations
+    file: PathBuf,
+}
+
+/// Print the expansion of every autozig macro in the file
+pub fn run(args: ExpandArgs) -> Result<()> {
+    let source = fs::read_to_string(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let expanded = expand_source(&source, &args.file.display().to_string())?;
+    if expanded.is_empty() {
+        bail!("No autozig!, include_zig! or bind_zig! invocation in {}", args.file.display());
+    }
+    print!("{expanded}");
+    Ok(())
+}
+
+/// Expansions of the macros in `source`, each preceded by a comment giving
+/// its location in `origin`
+fn expand_source(source: &str, origin: &str) -> Result<String> {
+    let file = syn::parse_file(source).with_context(|| format!("Failed to parse {origin}"))?;
+    let mut collector = Collector { origin, output: String::new() };
+    collector.visit_file(&file);
+    Ok(collector.output)
+}
+
+struct Collector<'a> {
+    origin: &'a str,
+    output: String,
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let Some(name) = node.path.get_ident().map(ToString::to_string) else {
+            return;
+        };
+        let expansion = match name.as_str() {
+            "autozig" => syn::parse2::<AutoZigConfig>(node.tokens.clone())
+                .map(|config| expand_autozig(&config)),
+            "include_zig" | "bind_zig" => {
+                syn::parse2::<IncludeZigConfig>(node.tokens.clone()).map(|config| {
+                    // Same marker as the proc macros emit
+                    let kind = if name == "include_zig" {
+                        "include"
+                    } else {
+                        "bind"
+                    };
+                    let marker = format!("// @autozig:{kind}:{}", config.file_path);
+                    expand_external(&config, &marker, &format!("{name}!"))
+                })
+            },
+            _ => return,
+        };
+
+        let line = node
+            .path
+            .get_ident()
+            .map_or(0, |ident| ident.span().start().line);
+        self.output
+            .push_str(&format!("// {name}! at {}:{line}\n", self.origin));
+        match expansion {
+            Ok(tokens) => match syn::parse2::<syn::File>(tokens.clone()) {
+                Ok(file) => self.output.push_str(&prettyplease::unparse(&file)),
+                Err(_) => self.output.push_str(&format!("{tokens}\n")),
+            },
+            Err(error) => self.output.push_str(&format!("// error: {error}\n")),
+        }
+        self.output.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_source() {
+        let source = r#"
+use autozig::autozig;
+
+autozig! {
+    export fn add(a: i32, b: i32) i32 { return a + b; }
+    ---
+    fn add(a: i32, b: i32) -> i32;
+}
+
+fn unrelated() {
+    println!("{}", add(1, 2));
+}
+"#;
+        let expanded = expand_source(source, "src/lib.rs").unwrap();
+        assert!(expanded.starts_with("// autozig! at src/lib.rs:4\n"));
+        assert!(expanded.contains("mod ffi {"));
+        assert!(expanded.contains("pub fn add(a: i32, b: i32) -> i32;"));
+        assert!(!expanded.contains("println"));
+        assert!(expand_source("fn main() {}", "src/main.rs")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+//! Generate cargo-fuzz targets for a crate's Zig functions
+//!
+//! Every safe wrapper taking a slice or a string gets a target in
+//! `fuzz/fuzz_targets/`, which feeds it arbitrary contents and lengths along
+//! with arbitrary scalar arguments:
+//!
+//! ```text
+//! // Generated by `autozig-cli fuzz` from src/lib.rs:12
+//! #![no_main]
+//!
+//! use libfuzzer_sys::fuzz_target;
+//!
+//! fuzz_target!(|input: (Vec<u8>, u32)| {
+//!     let (data, seed) = input;
+//!     let _ = my_crate::hash(&data, seed);
+//! });
+//! ```
+//!
+//! `fuzz/Cargo.toml` is created on the first run and only gains the
+//! `[[bin]]` entries of new targets afterwards. Run a target with
+//! `cargo +nightly fuzz run <name>`.
+//!
+//! Wrappers are found in the `autozig!`, `include_zig!` and `bind_zig!`
+//! invocations of the library's sources and called by their module path, so
+//! their modules must be public. Async, generic and `#[cfg]`-gated
+//! signatures, and those with other parameter types (structs, pointers,
+//! callbacks), are skipped.
+
+use std::{
+    fs,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+    RustFunctionSignature,
+};
+use clap::Args;
+use syn::visit::Visit;
+
+/// Options of `autozig-cli fuzz`
+#[derive(Args, Debug)]
+pub struct FuzzArgs {
+    /// Crate directory containing Cargo.toml
+    #[arg(long = "crate", default_value = ".")]
+    crate_dir: PathBuf,
+    /// Source directory scanned for autozig code, relative to the crate
+    #[arg(long, default_value = "src")]
+    src: PathBuf,
+    /// Fuzz crate directory, relative to the crate
+    #[arg(long, default_value = "fuzz")]
+    out: PathBuf,
+}
+
+/// Scalar types `arbitrary` can generate and the wrappers pass by value
+const SCALARS: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64", "bool",
+];
+
+/// How a fuzz target produces one argument of the wrapper
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Param {
+    /// `&[T]` or `&mut [T]`, from a `Vec<T>`
+    Slice { elem: String, mutable: bool },
+    /// `&str`, from a `String`
+    Str,
+    /// A scalar passed by value
+    Scalar(String),
+}
+
+impl Param {
+    fn from_type(ty: &syn::Type) -> Option<Param> {
+        match ty {
+            syn::Type::Reference(reference) => match &*reference.elem {
+                syn::Type::Slice(slice) => Some(Param::Slice {
+                    elem: scalar(&slice.elem)?,
+                    mutable: reference.mutability.is_some(),
+                }),
+                syn::Type::Path(path)
+                    if path.path.is_ident("str") && reference.mutability.is_none() =>
+                {
+                    Some(Param::Str)
+                },
+                _ => None,
+            },
+            _ => scalar(ty).map(Param::Scalar),
+        }
+    }
+
+    /// Type generated by `arbitrary`
+    fn input_type(&self) -> String {
+        match self {
+            Param::Slice { elem, .. } => format!("Vec<{elem}>"),
+            Param::Str => "String".to_string(),
+            Param::Scalar(ty) => ty.clone(),
+        }
+    }
+}
+
+fn scalar(ty: &syn::Type) -> Option<String> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = path.path.get_ident()?.to_string();
+    SCALARS.contains(&ident.as_str()).then_some(ident)
+}
+
+/// A fuzz target calling one wrapper
+#[derive(Debug)]
+struct Target {
+    /// Path of the wrapper below the crate root (`img::blur`)
+    path: Vec<String>,
+    /// Location of the macro invocation declaring it
+    origin: String,
+    params: Vec<(String, Param)>,
+}
+
+impl Target {
+    fn from_signature(
+        signature: &RustFunctionSignature,
+        module: &[String],
+        origin: &str,
+    ) -> Option<Target> {
+        let sig = &signature.sig;
+        if signature.is_async || !sig.generics.params.is_empty() || !signature.cfg_attrs.is_empty()
+        {
+            return None;
+        }
+        let mut params = Vec::new();
+        for (i, input) in sig.inputs.iter().enumerate() {
+            let syn::FnArg::Typed(arg) = input else {
+                return None;
+            };
+            let name = match &*arg.pat {
+                syn::Pat::Ident(pat) => pat.ident.to_string(),
+                _ => format!("arg{i}"),
+            };
+            params.push((name, Param::from_type(&arg.ty)?));
+        }
+        if !params
+            .iter()
+            .any(|(_, param)| !matches!(param, Param::Scalar(_)))
+        {
+            return None;
+        }
+        let mut path = module.to_vec();
+        path.push(sig.ident.to_string());
+        Some(Target { path, origin: origin.to_string(), params })
+    }
+
+    /// Name of the fuzz target and its file (`img_blur`)
+    fn name(&self) -> String {
+        self.path.join("_")
+    }
+
+    /// Source of `fuzz_targets/<name>.rs`, calling the wrapper in `lib`
+    fn render(&self, lib: &str) -> String {
+        let types: Vec<String> = self
+            .params
+            .iter()
+            .map(|(_, param)| param.input_type())
+            .collect();
+        let mut bindings: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, param)| match param {
+                Param::Slice { mutable: true, .. } => format!("mut {name}"),
+                _ => name.clone(),
+            })
+            .collect();
+        if bindings.len() == 1 {
+            bindings[0].push(',');
+        }
+        let args: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, param)| match param {
+                Param::Slice { mutable: true, .. } => format!("&mut {name}"),
+                Param::Slice { .. } | Param::Str => format!("&{name}"),
+                Param::Scalar(_) => name.clone(),
+            })
+            .collect();
+        let tuple = if types.len() == 1 {
+            format!("({},)", types[0])
+        } else {
+            format!("({})", types.join(", "))
+        };
+        format!(
+            "// Generated by `autozig-cli fuzz` from {origin}\n#![no_main]\n\nuse \
+             libfuzzer_sys::fuzz_target;\n\nfuzz_target!(|input: {tuple}| {{\n    let \
+             ({bindings}) = input;\n    let _ = {lib}::{path}({args});\n}});\n",
+            origin = self.origin,
+            bindings = bindings.join(", "),
+            path = self.path.join("::"),
+            args = args.join(", "),
+        )
+    }
+}
+
+/// Write a fuzz target for every fuzzable wrapper of the crate
+pub fn run(args: FuzzArgs) -> Result<()> {
+    let crate_dir = args
+        .crate_dir
+        .canonicalize()
+        .with_context(|| format!("Crate directory {} not found", args.crate_dir.display()))?;
+    let manifest = fs::read_to_string(crate_dir.join("Cargo.toml"))
+        .with_context(|| format!("No Cargo.toml in {}", crate_dir.display()))?;
+    let (package, lib) = crate_names(&manifest)?;
+    let src_dir = crate_dir.join(&args.src);
+    if !src_dir.join("lib.rs").exists() {
+        bail!(
+            "Fuzz targets call the library of the crate, but {} has no lib.rs",
+            src_dir.display()
+        );
+    }
+
+    let mut targets = Vec::new();
+    for file in rust_files(&src_dir)? {
+        let Some(module) = module_path(&src_dir, &file) else {
+            continue;
+        };
+        let source = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let origin = file.strip_prefix(&crate_dir).unwrap_or(&file);
+        targets.extend(collect_targets(&source, &origin.display().to_string(), module)?);
+    }
+    if targets.is_empty() {
+        bail!("No wrapper with slice or string parameters in {}", src_dir.display());
+    }
+
+    let out_dir = crate_dir.join(&args.out);
+    let targets_dir = out_dir.join("fuzz_targets");
+    fs::create_dir_all(&targets_dir)
+        .with_context(|| format!("Failed to create {}", targets_dir.display()))?;
+    let manifest_path = out_dir.join("Cargo.toml");
+    let mut fuzz_manifest = match fs::read_to_string(&manifest_path) {
+        Ok(existing) => existing,
+        Err(_) => fuzz_manifest(&package, &crate_path(&crate_dir, &args.out)),
+    };
+    for target in &targets {
+        let name = target.name();
+        fs::write(targets_dir.join(format!("{name}.rs")), target.render(&lib))
+            .with_context(|| format!("Failed to write fuzz target {name}"))?;
+        if !fuzz_manifest.contains(&format!("name = \"{name}\"")) {
+            fuzz_manifest.push_str(&format!(
+                "\n[[bin]]\nname = \"{name}\"\npath = \"fuzz_targets/{name}.rs\"\ntest = \
+                 false\ndoc = false\nbench = false\n"
+            ));
+        }
+        println!("{name}: {}::{}", lib, target.path.join("::"));
+    }
+    fs::write(&manifest_path, fuzz_manifest)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    let gitignore = out_dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, "target\ncorpus\nartifacts\ncoverage\n")?;
+    }
+    println!(
+        "Wrote {} fuzz target(s) to {}; run one with `cargo +nightly fuzz run <name>`",
+        targets.len(),
+        targets_dir.display()
+    );
+    Ok(())
+}
+
+/// Package name and library crate name from the crate's Cargo.toml
+fn crate_names(manifest: &str) -> Result<(String, String)> {
+    let manifest: toml::Value = toml::from_str(manifest).context("Failed to parse Cargo.toml")?;
+    let Some(package) = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+    else {
+        bail!("Cargo.toml has no package name");
+    };
+    let lib = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .and_then(toml::Value::as_str)
+        .map_or_else(|| package.replace('-', "_"), str::to_string);
+    Ok((package.to_string(), lib))
+}
+
+/// Path of the crate as seen from the fuzz crate
+fn crate_path(crate_dir: &Path, out: &Path) -> String {
+    if out.is_absolute() {
+        return crate_dir.display().to_string();
+    }
+    let depth = out
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .count();
+    vec![".."; depth].join("/")
+}
+
+fn fuzz_manifest(package: &str, crate_path: &str) -> String {
+    format!(
+        "[package]\nname = \"{package}-fuzz\"\nversion = \"0.0.0\"\npublish = false\nedition = \
+         \"2021\"\n\n[package.metadata]\ncargo-fuzz = true\n\n[dependencies]\nlibfuzzer-sys = \
+         \"0.4\"\n\n[dependencies.{package}]\npath = \"{crate_path}\"\n\n# Keep the fuzz crate \
+         out of the crate's workspace\n[workspace]\nmembers = [\".\"]\n"
+    )
+}
+
+/// `.rs` files below `dir`, sorted
+fn rust_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(rust_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Module path of a source file of the library: `[]` for `lib.rs`, `[a, b]`
+/// for `a/b.rs` or `a/b/mod.rs`; `None` for binaries
+fn module_path(src_dir: &Path, file: &Path) -> Option<Vec<String>> {
+    let relative = file.strip_prefix(src_dir).ok()?.with_extension("");
+    let mut module: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    match module.first().map(String::as_str) {
+        Some("main" | "bin") => return None,
+        Some("lib") if module.len() == 1 => module.clear(),
+        _ => {},
+    }
+    if module.last().is_some_and(|last| last == "mod") {
+        module.pop();
+    }
+    Some(module)
+}
+
+/// Fuzz targets for the macro invocations in `source`, a file of `module`
+fn collect_targets(source: &str, origin: &str, module: Vec<String>) -> Result<Vec<Target>> {
+    let file = syn::parse_file(source).with_context(|| format!("Failed to parse {origin}"))?;
+    let mut collector = Collector { origin, module, targets: Vec::new() };
+    collector.visit_file(&file);
+    Ok(collector.targets)
+}
+
+struct Collector<'a> {
+    origin: &'a str,
+    /// Module of the items being visited
+    module: Vec<String>,
+    targets: Vec<Target>,
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        self.module.push(node.ident.to_string());
+        syn::visit::visit_item_mod(self, node);
+        self.module.pop();
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let Some(name) = node.path.get_ident().map(ToString::to_string) else {
+            return;
+        };
+        let mut module = self.module.clone();
+        let signatures = match name.as_str() {
+            "autozig" => match syn::parse2::<AutoZigConfig>(node.tokens.clone()) {
+                Ok(config) => {
+                    module.extend(config.namespace.map(|namespace| namespace.to_string()));
+                    config.rust_signatures
+                },
+                Err(_) => return,
+            },
+            "include_zig" | "bind_zig" => {
+                match syn::parse2::<IncludeZigConfig>(node.tokens.clone()) {
+                    Ok(config) => config.rust_signatures,
+                    Err(_) => return,
+                }
+            },
+            _ => return,
+        };
+
+        let line = node
+            .path
+            .get_ident()
+            .map_or(0, |ident| ident.span().start().line);
+        let origin = format!("{}:{line}", self.origin);
+        self.targets.extend(
+            signatures
+                .iter()
+                .filter_map(|signature| Target::from_signature(signature, &module, &origin)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_targets() {
+        let source = r#"
+autozig! {
+    export fn hash(p: [*]const u8, n: usize, seed: u32) u32 { return 0; }
+    ---
+    fn hash(data: &[u8], seed: u32) -> u32;
+    fn add(a: i32, b: i32) -> i32;
+}
+
+pub mod text {
+    autozig!(mod = utf, {
+        export fn count(p: [*]const u8, n: usize) usize { return n; }
+        ---
+        fn count(s: &str) -> usize;
+        fn fill(out: &mut [f32]);
+    });
+}
+"#;
+        let targets = collect_targets(source, "src/lib.rs", Vec::new()).unwrap();
+        let names: Vec<String> = targets.iter().map(Target::name).collect();
+        assert_eq!(names, ["hash", "text_utf_count", "text_utf_fill"]);
+        assert_eq!(
+            targets[0].render("my_crate"),
+            "// Generated by `autozig-cli fuzz` from src/lib.rs:2\n#![no_main]\n\nuse \
+             libfuzzer_sys::fuzz_target;\n\nfuzz_target!(|input: (Vec<u8>, u32)| {\n    let \
+             (data, seed) = input;\n    let _ = my_crate::hash(&data, seed);\n});\n"
+        );
+        assert!(targets[2].render("my_crate").contains(
+            "|input: (Vec<f32>,)| {\n    let (mut out,) = input;\n    let _ = \
+             my_crate::text::utf::fill(&mut out);"
+        ));
+
+        let src = Path::new("src");
+        assert_eq!(module_path(src, Path::new("src/lib.rs")), Some(vec![]));
+        assert_eq!(module_path(src, Path::new("src/img/mod.rs")), Some(vec!["img".to_string()]));
+        assert_eq!(module_path(src, Path::new("src/main.rs")), None);
+        assert_eq!(crate_path(Path::new("/crate"), Path::new("fuzz")), "..");
+        assert_eq!(
+            crate_names("[package]\nname = \"img-kit\"\n").unwrap(),
+            ("img-kit".to_string(), "img_kit".to_string())
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Command-line tools for autozig projects
+//!
+//! `autozig-cli watch` keeps a WASM crate's Zig library and JS bindings up to
+//! date while editing (see [`watch`]). `autozig-cli expand <file>` shows the
+//! Rust the macros generate (see [`expand`]) and `autozig-cli emit-zig` the
+//! Zig the build compiles (see [`emit_zig`]). `autozig-cli fuzz` writes
+//! cargo-fuzz targets for the crate's wrappers (see [`fuzz`]).
+
+#![forbid(unsafe_code)]
+
+use anyhow::Result;
+use clap::{
+    Parser,
+    Subcommand,
+};
+
+mod emit_zig;
+mod expand;
+mod fuzz;
+mod watch;
+
+#[derive(Parser)]
+#[command(
+    name = "autozig-cli",
+    version,
+    about = "Command-line tools for autozig projects"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Rebuild the Zig library and JS bindings whenever a source file changes
+    Watch(watch::WatchArgs),
+    /// Show the extern blocks and wrappers the macros generate for a file
+    Expand(expand::ExpandArgs),
+    /// Print the Zig code the build compiles
+    EmitZig(emit_zig::EmitZigArgs),
+    /// Write cargo-fuzz targets for the wrappers taking slices or strings
+    Fuzz(fuzz::FuzzArgs),
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Watch(args) => watch::run(args),
+        Command::Expand(args) => expand::run(args),
+        Command::EmitZig(args) => emit_zig::run(args),
+        Command::Fuzz(args) => fuzz::run(args),
+    }
+}
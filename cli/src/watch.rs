@@ -0,0 +1,363 @@
+//! Watch mode for WASM crates
+//!
+//! Polls the crate's `.rs` and `.zig` files and, after every change:
+//!
+//! 1. re-runs only the Zig step through [`AutoZigEngine`], so Zig errors show
+//!    up without a cargo build,
+//! 2. copies the new static library over the one cargo links (found under
+//!    `<target-dir>/<triple>/<profile>/build/<package>-*/out`),
+//! 3. runs the bindings command (`wasm-pack build --target web --out-dir
+//!    www/pkg` by default).
+//!
+//! The library is compiled into `<target-dir>/<triple>/autozig-watch`, which
+//! shares the artifact cache with cargo's build directories, so when cargo
+//! re-runs the build script for step 3 it reuses the library instead of
+//! compiling it again. The engine runs with default
+//! [`BuildOptions`](autozig_engine::BuildOptions)
+//! (the blocks' `#![autozig(mode = ...)]`, else `AUTOZIG_MODE`, selects the
+//! compilation mode).
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+    thread,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use autozig_engine::AutoZigEngine;
+use clap::Args;
+
+/// Options of `autozig-cli watch`
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Crate directory containing Cargo.toml
+    #[arg(long = "crate", default_value = ".")]
+    crate_dir: PathBuf,
+    /// Source directory scanned for autozig code, relative to the crate
+    #[arg(long, default_value = "src")]
+    src: PathBuf,
+    /// Rust target triple the library is built for
+    #[arg(long, default_value = "wasm32-unknown-unknown")]
+    target: String,
+    /// Cargo profile directory whose library is replaced
+    #[arg(long, default_value = "release")]
+    profile: String,
+    /// Command regenerating the JS bindings, run in the crate directory
+    #[arg(long, default_value = "wasm-pack build --target web --out-dir www/pkg")]
+    bindgen: String,
+    /// Only rebuild the Zig library
+    #[arg(long)]
+    no_bindgen: bool,
+    /// Polling interval in milliseconds
+    #[arg(long, default_value_t = 300)]
+    interval_ms: u64,
+    /// Rebuild once and exit
+    #[arg(long)]
+    once: bool,
+}
+
+/// Crate being watched, as reported by `cargo metadata`
+struct Package {
+    name: String,
+    target_dir: PathBuf,
+}
+
+/// Run the watch loop until interrupted
+pub fn run(args: WatchArgs) -> Result<()> {
+    let crate_dir = args
+        .crate_dir
+        .canonicalize()
+        .with_context(|| format!("Crate directory {} not found", args.crate_dir.display()))?;
+    let package = locate_package(&crate_dir)?;
+    // The engine names the library after the package, like in build scripts
+    env::set_var("CARGO_PKG_NAME", &package.name);
+
+    let ignored = [package.target_dir.clone()];
+    let mut snapshot = source_snapshot(&crate_dir, &ignored);
+    if args.once {
+        return rebuild(&args, &crate_dir, &package);
+    }
+    report(rebuild(&args, &crate_dir, &package));
+
+    println!("[autozig] watching {} (Ctrl-C to stop)", crate_dir.display());
+    loop {
+        thread::sleep(Duration::from_millis(args.interval_ms));
+        let current = source_snapshot(&crate_dir, &ignored);
+        let changed = changed_files(&snapshot, &current);
+        if changed.is_empty() {
+            continue;
+        }
+        for path in &changed {
+            let path = path.strip_prefix(&crate_dir).unwrap_or(path);
+            println!("[autozig] changed: {}", path.display());
+        }
+        snapshot = current;
+        report(rebuild(&args, &crate_dir, &package));
+    }
+}
+
+/// One edit-compile round
+fn rebuild(args: &WatchArgs, crate_dir: &Path, package: &Package) -> Result<()> {
+    let started = Instant::now();
+    if let Some(lib) = compile_zig(args, crate_dir, package)? {
+        let swapped =
+            hot_swap(&lib, &package.target_dir, &args.target, &args.profile, &package.name)?;
+        println!(
+            "[autozig] replaced {swapped} linked cop{}",
+            if swapped == 1 { "y" } else { "ies" }
+        );
+    }
+    if !args.no_bindgen {
+        run_bindgen(&args.bindgen, crate_dir)?;
+    }
+    println!("[autozig] ready in {:.2}s", started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+/// Report a failed round; watching goes on with the last good build
+fn report(result: Result<()>) {
+    if let Err(err) = result {
+        eprintln!("[autozig] build failed: {err:#}");
+    }
+}
+
+/// Compile the Zig code, returning the static library (if any Zig code exists)
+fn compile_zig(args: &WatchArgs, crate_dir: &Path, package: &Package) -> Result<Option<PathBuf>> {
+    let out_dir = staging_dir(&package.target_dir, &args.target, &package.name);
+    fs::create_dir_all(&out_dir)
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+    let output = AutoZigEngine::new(crate_dir.join(&args.src), &out_dir)
+        .with_target(&args.target)
+        .with_manifest_dir(crate_dir)
+        .build()?;
+    Ok(output.lib_path)
+}
+
+/// Output directory of the watcher's own builds
+///
+/// Laid out like a cargo `OUT_DIR` so that [`autozig_engine::ArtifactCache`]
+/// resolves to the same `<target-dir>/<triple>/autozig-cache` as the build
+/// script does.
+fn staging_dir(target_dir: &Path, triple: &str, package: &str) -> PathBuf {
+    target_dir
+        .join(triple)
+        .join("autozig-watch")
+        .join("build")
+        .join(package)
+        .join("out")
+}
+
+/// Replace the library in every build script output directory of the
+/// package, returning how many were replaced
+fn hot_swap(
+    lib: &Path,
+    target_dir: &Path,
+    triple: &str,
+    profile: &str,
+    package: &str,
+) -> Result<usize> {
+    let file_name = lib.file_name().context("Library path has no file name")?;
+    let build_dir = target_dir.join(triple).join(profile).join("build");
+    let Ok(entries) = fs::read_dir(&build_dir) else {
+        // Nothing built by cargo yet; the bindings command will link the
+        // cached library
+        return Ok(0);
+    };
+
+    let prefix = format!("{package}-");
+    let mut swapped = 0;
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(&prefix) {
+            continue;
+        }
+        let dest = entry.path().join("out").join(file_name);
+        if !dest.exists() {
+            continue;
+        }
+        // Copy next to the destination and rename, so a concurrent link never
+        // sees a half-written archive
+        let tmp = dest.with_extension("a.tmp");
+        fs::copy(lib, &tmp).with_context(|| format!("Failed to copy to {}", tmp.display()))?;
+        fs::rename(&tmp, &dest).with_context(|| format!("Failed to replace {}", dest.display()))?;
+        swapped += 1;
+    }
+    Ok(swapped)
+}
+
+fn run_bindgen(command: &str, crate_dir: &Path) -> Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words.next().context("Empty bindings command")?;
+    let status = Command::new(program)
+        .args(words)
+        .current_dir(crate_dir)
+        .status()
+        .with_context(|| format!("Failed to run `{command}`"))?;
+    if !status.success() {
+        bail!("`{command}` failed with {status}");
+    }
+    Ok(())
+}
+
+/// Name and target directory of the package whose manifest is in `crate_dir`
+fn locate_package(crate_dir: &Path) -> Result<Package> {
+    let manifest = crate_dir.join("Cargo.toml");
+    let output = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["metadata", "--no-deps", "--format-version", "1", "--manifest-path"])
+        .arg(&manifest)
+        .output()
+        .context("Failed to run `cargo metadata`")?;
+    if !output.status.success() {
+        bail!("`cargo metadata` failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Invalid `cargo metadata` output")?;
+    let target_dir = metadata["target_directory"]
+        .as_str()
+        .context("`cargo metadata` reported no target directory")?;
+    let name = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|package| {
+            package["manifest_path"]
+                .as_str()
+                .is_some_and(|path| Path::new(path) == manifest)
+        })
+        .and_then(|package| package["name"].as_str())
+        .with_context(|| format!("No package with manifest {}", manifest.display()))?;
+
+    Ok(Package {
+        name: name.to_string(),
+        target_dir: PathBuf::from(target_dir),
+    })
+}
+
+/// Modification times of the `.rs` and `.zig` files under `dir`
+fn source_snapshot(dir: &Path, ignored: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    let mut files = BTreeMap::new();
+    collect_sources(dir, ignored, &mut files);
+    files
+}
+
+fn collect_sources(dir: &Path, ignored: &[PathBuf], files: &mut BTreeMap<PathBuf, SystemTime>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let skip = name.starts_with('.')
+                || name == "target"
+                || name == "node_modules"
+                || ignored.contains(&path);
+            if !skip {
+                collect_sources(&path, ignored, files);
+            }
+        } else if name.ends_with(".rs") || name.ends_with(".zig") {
+            if let Ok(modified) = entry.metadata().and_then(|meta| meta.modified()) {
+                files.insert(path, modified);
+            }
+        }
+    }
+}
+
+/// Files added, modified or removed between two snapshots
+fn changed_files(
+    before: &BTreeMap<PathBuf, SystemTime>,
+    after: &BTreeMap<PathBuf, SystemTime>,
+) -> Vec<PathBuf> {
+    let modified = after
+        .iter()
+        .filter(|(path, time)| before.get(*path) != Some(time))
+        .map(|(path, _)| path.clone());
+    let removed = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned();
+    modified.chain(removed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("autozig-cli-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_source_snapshot_changes() {
+        let dir = temp_dir("snapshot");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        fs::write(dir.join("src/kernel.zig"), "").unwrap();
+        fs::write(dir.join("src/notes.md"), "").unwrap();
+        fs::write(dir.join("target/debug/gen.rs"), "").unwrap();
+
+        let before = source_snapshot(&dir, &[]);
+        assert_eq!(
+            before.keys().cloned().collect::<Vec<_>>(),
+            vec![dir.join("src/kernel.zig"), dir.join("src/lib.rs")]
+        );
+
+        let mut after = before.clone();
+        after.insert(dir.join("src/kernel.zig"), SystemTime::UNIX_EPOCH);
+        after.remove(&dir.join("src/lib.rs"));
+        after.insert(dir.join("src/new.zig"), SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            changed_files(&before, &after),
+            vec![dir.join("src/kernel.zig"), dir.join("src/new.zig"), dir.join("src/lib.rs")]
+        );
+        assert!(changed_files(&before, &before).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hot_swap() {
+        let dir = temp_dir("swap");
+        let lib = staging_dir(&dir, "wasm32-unknown-unknown", "demo").join("libdemo.a");
+        fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        fs::write(&lib, "new").unwrap();
+
+        let build = dir.join("wasm32-unknown-unknown/release/build");
+        for out in ["demo-1234/out", "demo-5678/out", "other-1234/out"] {
+            fs::create_dir_all(build.join(out)).unwrap();
+        }
+        fs::write(build.join("demo-1234/out/libdemo.a"), "old").unwrap();
+        fs::write(build.join("other-1234/out/libdemo.a"), "old").unwrap();
+
+        let swapped = hot_swap(&lib, &dir, "wasm32-unknown-unknown", "release", "demo").unwrap();
+        assert_eq!(swapped, 1);
+        assert_eq!(fs::read_to_string(build.join("demo-1234/out/libdemo.a")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(build.join("other-1234/out/libdemo.a")).unwrap(), "old");
+        assert!(!build.join("demo-5678/out/libdemo.a").exists());
+        assert_eq!(hot_swap(&lib, &dir, "wasm32-unknown-unknown", "debug", "demo").unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,5471 @@
+//! Code generation behind the autozig! macros
+//!
+//! Turns parsed `autozig!`, `include_zig!` and `bind_zig!` input into the Rust
+//! items the macros expand to: raw `extern "C"` declarations, safe wrappers
+//! and trait implementations. It lives outside the proc-macro crate so tools
+//! can expand the macros without rustc (e.g. when vendoring a crate).
+
+#![forbid(unsafe_code)]
+
+use std::cell::Cell;
+
+use autozig_parser::{
+    namespaced_symbol,
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use quote::quote;
+
+mod napi;
+mod python;
+pub mod wit;
+
+thread_local! {
+    /// Whether wrappers may use std, see [`with_std`]
+    static STD: Cell<bool> = const { Cell::new(cfg!(feature = "std")) };
+}
+
+/// Run `f` generating wrappers with or without std, whatever the `std`
+/// feature says
+///
+/// The feature decides for the proc macros. Tools expanding the macros of a
+/// crate, such as vendoring, follow that crate's own `std` feature instead.
+pub fn with_std<R>(std: bool, f: impl FnOnce() -> R) -> R {
+    let previous = STD.with(|cell| cell.replace(std));
+    let result = f();
+    STD.with(|cell| cell.set(previous));
+    result
+}
+
+fn std_enabled() -> bool {
+    STD.with(Cell::get)
+}
+
+/// Expansion of an `autozig!` invocation: FFI declarations, safe wrappers
+/// and trait implementations
+pub fn expand_autozig(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    // Generate code with IDL-driven FFI
+    // No bindgen needed - we generate extern "C" directly from user signatures
+    let mod_name = syn::Ident::new(config.get_mod_name(), proc_macro2::Span::call_site());
+
+    if config.has_rust_signatures()
+        || !config.rust_structs.is_empty()
+        || !config.rust_enums.is_empty()
+        || !config.rust_trait_impls.is_empty()
+        || !config.rust_statics.is_empty()
+    {
+        // Generate enum definitions (must come before struct definitions)
+        let enum_defs = generate_enum_definitions(config);
+
+        // Generate struct definitions (must come before FFI declarations that use them)
+        let struct_defs = generate_struct_definitions(config);
+
+        // Generate trait impl target types (ZST structs for Phase 1)
+        let trait_impl_types = generate_trait_impl_types(config);
+
+        // Phase 3: Generate FFI declarations and wrappers with monomorphization and
+        // async support
+        let (ffi_decls, wrappers) = generate_with_monomorphization(config);
+
+        // Generate trait FFI declarations
+        let trait_ffi_decls = generate_trait_ffi_declarations(config);
+
+        // Generate trait implementations
+        let trait_impls = generate_trait_implementations(config);
+
+        // Statics bound to data exported by Zig
+        let (static_decls, static_accessors) =
+            generate_static_bindings(&config.rust_statics, &mod_name);
+
+        let ffi_items = prefix_link_names(
+            config.namespace.as_ref(),
+            symbol_mangling(),
+            quote! {
+                #ffi_decls
+                #trait_ffi_decls
+                #static_decls
+            },
+        );
+
+        let component_exports =
+            wit::component_exports(&config.rust_signatures, config.namespace.as_ref());
+        let napi_bindings = napi::napi_bindings(
+            &config.rust_signatures,
+            &config.rust_trait_impls,
+            config.namespace.as_ref(),
+        );
+        let python_bindings = python::python_bindings(
+            &config.rust_signatures,
+            &config.rust_trait_impls,
+            config.namespace.as_ref(),
+        );
+
+        let bindings = quote! {
+            // Enum definitions (visible at module level)
+            #enum_defs
+
+            // Struct definitions (visible at module level)
+            #struct_defs
+
+            // Trait impl target types (ZST structs)
+            #trait_impl_types
+
+            // Raw FFI module with extern "C" declarations
+            mod #mod_name {
+                use super::*;  // Import enums and structs from parent scope
+                #ffi_items
+            }
+
+            // Safe wrappers
+            #wrappers
+            #static_accessors
+            #component_exports
+            #napi_bindings
+            #python_bindings
+
+            // Trait implementations
+            #trait_impls
+        };
+
+        match &config.namespace {
+            Some(namespace) => quote! {
+                pub mod #namespace {
+                    use super::*;
+                    #bindings
+                }
+            },
+            None => bindings,
+        }
+    } else {
+        // No signatures provided - user must write their own FFI declarations
+        quote! {
+            // Note: No Rust signatures provided in autozig! macro
+            // You must manually declare extern "C" functions or provide signatures after ---
+            compile_error!("autozig! macro requires Rust function signatures after --- separator");
+        }
+    }
+}
+
+/// Prefix of mangled Zig exports and the names exempt from it, set by the
+/// build script when `BuildOptions::mangle_symbols` is enabled
+fn symbol_mangling() -> Option<(String, Vec<String>)> {
+    let prefix = std::env::var("AUTOZIG_SYMBOL_PREFIX")
+        .ok()
+        .filter(|prefix| !prefix.is_empty())?;
+    let stable = std::env::var("AUTOZIG_STABLE_SYMBOLS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some((prefix, stable))
+}
+
+/// Link the `extern` items against the symbols the engine exports
+///
+/// In a namespaced block, `fn resize` gets `#[link_name = "img__resize"]` and
+/// an existing `#[link_name = "dot"]` becomes `"img__dot"`. With symbol
+/// mangling, functions additionally get the crate's prefix
+/// (`az_1a2b3c__img__resize`), except for the stable names and the runtime's
+/// `autozig_*` helpers; statics keep their Zig names.
+fn prefix_link_names(
+    namespace: Option<&syn::Ident>,
+    mangling: Option<(String, Vec<String>)>,
+    ffi_items: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if namespace.is_none() && mangling.is_none() {
+        return ffi_items;
+    }
+    let Ok(mut file) = syn::parse2::<syn::File>(ffi_items.clone()) else {
+        return ffi_items;
+    };
+
+    for item in &mut file.items {
+        let syn::Item::ForeignMod(foreign) = item else {
+            continue;
+        };
+        for foreign_item in &mut foreign.items {
+            let (attrs, ident, is_fn) = match foreign_item {
+                syn::ForeignItem::Fn(item) => (&mut item.attrs, &item.sig.ident, true),
+                syn::ForeignItem::Static(item) => (&mut item.attrs, &item.ident, false),
+                _ => continue,
+            };
+            let link_name = attrs
+                .iter()
+                .position(|attr| attr.path().is_ident("link_name"));
+            let name = match link_name {
+                Some(index) => match &attrs.remove(index).meta {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(name), .. }),
+                        ..
+                    }) => name.value(),
+                    _ => ident.to_string(),
+                },
+                None => ident.to_string(),
+            };
+            let mut symbol = match namespace {
+                Some(namespace) => namespaced_symbol(&namespace.to_string(), &name),
+                None => name,
+            };
+            if let Some((prefix, stable)) = &mangling {
+                if is_fn && !symbol.starts_with("autozig_") && !stable.contains(&symbol) {
+                    symbol = format!("{prefix}{symbol}");
+                }
+            }
+            attrs.push(syn::parse_quote!(#[link_name = #symbol]));
+        }
+    }
+    quote! { #file }
+}
+
+/// Generate enum definitions from IDL
+fn generate_enum_definitions(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    let enums: Vec<_> = config.rust_enums.iter().map(|e| &e.item).collect();
+
+    quote! {
+        #(#enums)*
+    }
+}
+
+/// Generate struct definitions from IDL
+fn generate_struct_definitions(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    let structs: Vec<_> = config.rust_structs.iter().map(|s| &s.item).collect();
+    let instances = generate_struct_instances(&config.rust_structs);
+    let layout_checks = generate_layout_assertions(&config.rust_structs);
+
+    quote! {
+        #(#structs)*
+        #instances
+        #layout_checks
+    }
+}
+
+/// `type Matrix_f32 = Matrix<f32>;` for each `#[monomorphize]` instance of a
+/// generic struct, with the struct's visibility, named like its Zig
+/// counterpart `Matrix(f32)` is aliased by the engine
+fn generate_struct_instances(
+    structs: &[autozig_parser::RustStructDefinition],
+) -> proc_macro2::TokenStream {
+    let mut aliases = Vec::new();
+    for definition in structs {
+        let name = &definition.item.ident;
+        let vis = &definition.item.vis;
+        for (alias, ty) in struct_instances(definition) {
+            let doc =
+                format!(" `{name}` monomorphized for `{}` (generated by autozig)", quote! { #ty });
+            aliases.push(quote! {
+                #[doc = #doc]
+                #[allow(non_camel_case_types)]
+                #vis type #alias = #name<#ty>;
+            });
+        }
+    }
+    quote! { #(#aliases)* }
+}
+
+/// Alias names and type arguments of the `#[monomorphize]` instances of a
+/// generic struct
+fn struct_instances(
+    definition: &autozig_parser::RustStructDefinition,
+) -> Vec<(syn::Ident, syn::Type)> {
+    let name = definition.item.ident.to_string();
+    definition
+        .monomorphize_types
+        .iter()
+        .filter_map(|ty| {
+            let alias = quote::format_ident!("{name}_{}", autozig_parser::mangle_type(ty));
+            Some((alias, syn::parse_str(ty).ok()?))
+        })
+        .collect()
+}
+
+/// Compile-time assertions that `#[repr(C)]` structs match the layout Zig
+/// chose for the `extern struct` of the same name
+///
+/// The layouts are measured by the engine at build time and found through
+/// `AUTOZIG_LAYOUTS`; without it (e.g. no build script) nothing is checked.
+/// Generic structs are checked per `#[monomorphize]` instance, against the
+/// Zig alias of the same name (`Matrix_f32`).
+fn generate_layout_assertions(
+    structs: &[autozig_parser::RustStructDefinition],
+) -> proc_macro2::TokenStream {
+    let Ok(path) = std::env::var("AUTOZIG_LAYOUTS") else {
+        return quote! {};
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return quote! {};
+    };
+    let layouts = autozig_parser::layout::parse_layouts(&text);
+
+    let mut checks = Vec::new();
+    for definition in structs {
+        let item = &definition.item;
+        let is_repr_c = item.attrs.iter().any(|attr| {
+            attr.path().is_ident("repr")
+                && attr
+                    .parse_args_with(
+                        syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+                    )
+                    .is_ok_and(|reprs| reprs.iter().any(|repr| repr == "C"))
+        });
+        if !is_repr_c {
+            continue;
+        }
+        let ident = &item.ident;
+        let targets: Vec<(syn::Ident, proc_macro2::TokenStream)> =
+            if item.generics.params.is_empty() {
+                vec![(ident.clone(), quote! { #ident })]
+            } else {
+                struct_instances(definition)
+                    .into_iter()
+                    .map(|(alias, ty)| (alias, quote! { #ident<#ty> }))
+                    .collect()
+            };
+        for (name, ty) in &targets {
+            let Some(layout) = layouts.iter().find(|layout| *name == layout.name) else {
+                continue;
+            };
+
+            let size = layout.size as usize;
+            let align = layout.align as usize;
+            let size_msg =
+                format!("`{name}` differs in size from the Zig extern struct ({size} bytes)");
+            let align_msg =
+                format!("`{name}` differs in alignment from the Zig extern struct ({align} bytes)");
+            checks.push(quote! {
+                assert!(::core::mem::size_of::<#ty>() == #size, #size_msg);
+                assert!(::core::mem::align_of::<#ty>() == #align, #align_msg);
+            });
+
+            let mut rust_fields = Vec::new();
+            for field in &item.fields {
+                let Some(field_name) = &field.ident else {
+                    continue;
+                };
+                rust_fields.push(field_name.to_string());
+                match layout.offset_of(&field_name.to_string()) {
+                    Some(offset) => {
+                        let offset = offset as usize;
+                        let msg = format!(
+                            "`{name}.{field_name}` is at a different offset than in the Zig \
+                             extern struct ({offset})"
+                        );
+                        checks.push(quote! {
+                            assert!(::core::mem::offset_of!(#ty, #field_name) == #offset, #msg);
+                        });
+                    },
+                    None => checks.push(
+                        syn::Error::new_spanned(
+                            field_name,
+                            format!(
+                                "field `{field_name}` does not exist in the Zig extern struct \
+                                 `{name}`"
+                            ),
+                        )
+                        .to_compile_error(),
+                    ),
+                }
+            }
+            for (zig_field, _) in &layout.fields {
+                if !rust_fields.contains(zig_field) {
+                    checks.push(
+                        syn::Error::new_spanned(
+                            name,
+                            format!(
+                                "`{name}` is missing field `{zig_field}` of the Zig extern struct"
+                            ),
+                        )
+                        .to_compile_error(),
+                    );
+                }
+            }
+        }
+    }
+    if checks.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        // Rebuild when the measured layouts change
+        const _: &str = include_str!(#path);
+        const _: () = {
+            #(#checks)*
+        };
+    }
+}
+
+/// Check if a type is a reference to a slice or str
+fn is_slice_or_str_ref(ty: &syn::Type) -> Option<(bool, Option<syn::Type>)> {
+    if let syn::Type::Reference(type_ref) = ty {
+        let is_mut = type_ref.mutability.is_some();
+
+        // Check for &str or &mut str
+        if let syn::Type::Path(type_path) = &*type_ref.elem {
+            if type_path.path.is_ident("str") {
+                return Some((is_mut, None)); // str has no element type
+            }
+        }
+
+        // Check for &[T] or &mut [T]
+        if let syn::Type::Slice(type_slice) = &*type_ref.elem {
+            return Some((is_mut, Some((*type_slice.elem).clone())));
+        }
+    }
+    None
+}
+
+/// Check if a parameter is passed to Zig as pointer + length: `&[T]`, `&str`,
+/// or a shared `Bytes` / `Arc<[u8]>` buffer (as `*const u8`)
+fn ptr_len_param(ty: &syn::Type) -> Option<(bool, Option<syn::Type>)> {
+    if autozig_parser::is_shared_bytes(ty) {
+        return Some((false, Some(syn::parse_quote! { u8 })));
+    }
+    is_slice_or_str_ref(ty)
+}
+
+/// Check if a method returns a borrowed `&[T]` or `&str`
+/// Returns the element type (`None` for `str`); opaque type methods receive
+/// these from Zig as `autozig::ffi_types::ZigSliceRef<T>`
+fn is_borrowed_slice_return(output: &syn::ReturnType) -> Option<Option<syn::Type>> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    match is_slice_or_str_ref(ty)? {
+        (false, elem_type) => Some(elem_type),
+        (true, _) => None,
+    }
+}
+
+/// Check if a type is a fixed-size array [T; N]
+/// Returns Some((element_type, array_size_expr)) if it matches
+/// This enables automatic conversion of [T; N] to *const [N]T in FFI
+fn is_fixed_array(ty: &syn::Type) -> Option<(syn::Type, syn::Expr)> {
+    if let syn::Type::Array(type_array) = ty {
+        return Some(((*type_array.elem).clone(), type_array.len.clone()));
+    }
+    None
+}
+
+/// Check if a type is a mutable reference to a fixed-size array &mut [T; N]
+/// Returns Some((element_type, array_size_expr)) if it matches
+fn is_mut_fixed_array_ref(ty: &syn::Type) -> Option<(syn::Type, syn::Expr)> {
+    if let syn::Type::Reference(type_ref) = ty {
+        if type_ref.mutability.is_some() {
+            if let syn::Type::Array(type_array) = &*type_ref.elem {
+                return Some(((*type_array.elem).clone(), type_array.len.clone()));
+            }
+        }
+    }
+    None
+}
+
+/// Check if return type is a fixed-size array [T; N]
+/// Returns Some((element_type, array_size_expr)) if it matches
+fn is_array_return_type(output: &syn::ReturnType) -> Option<(syn::Type, syn::Expr)> {
+    if let syn::ReturnType::Type(_, ty) = output {
+        return is_fixed_array(ty);
+    }
+    None
+}
+
+/// Check if a type is a growable vector `&mut Vec<T>`
+/// Returns the element type; such parameters are lowered to
+/// `*mut autozig::ffi_types::ZigGrowable<T>` so Zig can append to them
+fn is_growable_vec_ref(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Reference(type_ref) = ty else {
+        return None;
+    };
+    type_ref.mutability?;
+    match generic_type_args(&type_ref.elem, "Vec")?.as_slice() {
+        [elem] => Some(elem),
+        _ => None,
+    }
+}
+
+/// Check if a type is `MapView<'a, K, V>`
+/// Such parameters are passed as `*mut MapView<'a, K, V>` for Zig to walk
+fn is_map_view(ty: &syn::Type) -> bool {
+    let syn::Type::Path(type_path) = ty else {
+        return false;
+    };
+    type_path.path.segments.last().is_some_and(|segment| {
+        segment.ident == "MapView"
+            && matches!(segment.arguments, syn::PathArguments::AngleBracketed(_))
+    })
+}
+
+/// Check if a return type is `MapBuilder<K, V>`
+/// Zig fills it through a trailing `*mut MapBuilder<K, V>` parameter
+fn is_map_builder_return_type(output: &syn::ReturnType) -> Option<&syn::Type> {
+    match output {
+        syn::ReturnType::Type(_, ty) if autozig_parser::is_map_builder(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+/// A Rust closure parameter that Zig can call back into
+///
+/// `on_chunk: impl FnMut(&[u8])` (or `&mut dyn FnMut(&[u8])` / `&dyn Fn(..)`)
+/// is lowered to a `(ctx, fn_ptr)` pair at the FFI boundary. On the Zig side
+/// this is `ctx: ?*anyopaque, cb: *const fn (?*anyopaque, [*]const u8, usize)
+/// callconv(.C) void`, and Zig invokes it as `cb(ctx, ptr, len)`.
+struct CallbackParam {
+    /// Closure argument types as declared by the user (e.g. `&[u8]`, `u64`)
+    args: Vec<syn::Type>,
+    /// Closure return type
+    output: syn::ReturnType,
+}
+
+/// Check if a type is a closure parameter (`impl Fn*`, `&dyn Fn*`,
+/// `&mut dyn Fn*`) Returns the closure's argument and return types
+fn is_callback_param(ty: &syn::Type) -> Option<CallbackParam> {
+    let bounds = match ty {
+        syn::Type::ImplTrait(impl_trait) => &impl_trait.bounds,
+        syn::Type::Reference(type_ref) => match &*type_ref.elem {
+            syn::Type::TraitObject(trait_object) => &trait_object.bounds,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    for bound in bounds {
+        if let syn::TypeParamBound::Trait(trait_bound) = bound {
+            let segment = trait_bound.path.segments.last()?;
+            // FnOnce cannot be called repeatedly from Zig, only Fn and FnMut are supported
+            if segment.ident != "Fn" && segment.ident != "FnMut" {
+                continue;
+            }
+            if let syn::PathArguments::Parenthesized(args) = &segment.arguments {
+                return Some(CallbackParam {
+                    args: args.inputs.iter().cloned().collect(),
+                    output: args.output.clone(),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Build the FFI parameters for a callback: `name_ctx` and `name_fn`
+fn callback_ffi_params(
+    param_name: &str,
+    callback: &CallbackParam,
+) -> Vec<proc_macro2::TokenStream> {
+    let ctx_name = quote::format_ident!("{}_ctx", param_name);
+    let fn_name = quote::format_ident!("{}_fn", param_name);
+    let output = &callback.output;
+
+    // Closure arguments are lowered the same way as function parameters:
+    // slices and strings become (ptr, len), everything else is passed through
+    let mut lowered_args = Vec::new();
+    for arg in &callback.args {
+        if let Some((is_mut, elem_type)) = is_slice_or_str_ref(arg) {
+            let elem = elem_type.map_or_else(|| quote! { u8 }, |elem| quote! { #elem });
+            if is_mut {
+                lowered_args.push(quote! { *mut #elem });
+            } else {
+                lowered_args.push(quote! { *const #elem });
+            }
+            lowered_args.push(quote! { usize });
+        } else {
+            lowered_args.push(quote! { #arg });
+        }
+    }
+
+    vec![
+        quote! { #ctx_name: *mut core::ffi::c_void },
+        quote! { #fn_name: extern "C" fn(*mut core::ffi::c_void, #(#lowered_args),*) #output },
+    ]
+}
+
+/// Build the wrapper-side glue for a callback parameter
+///
+/// Returns the statements that set up the trampoline (to be placed before the
+/// FFI call) and the two FFI arguments (context pointer, trampoline).
+fn callback_ffi_args(
+    param_name: &syn::Ident,
+    callback: &CallbackParam,
+) -> (proc_macro2::TokenStream, Vec<proc_macro2::TokenStream>) {
+    let dyn_name = quote::format_ident!("__autozig_{}_dyn", param_name);
+    let trampoline_name = quote::format_ident!("__autozig_{}_trampoline", param_name);
+    let args = &callback.args;
+    let output = &callback.output;
+
+    let mut trampoline_params = Vec::new();
+    let mut trampoline_setup = Vec::new();
+    let mut call_args = Vec::new();
+    for (idx, arg) in callback.args.iter().enumerate() {
+        let arg_name = quote::format_ident!("arg{}", idx);
+        if let Some((is_mut, elem_type)) = is_slice_or_str_ref(arg) {
+            let ptr_name = quote::format_ident!("arg{}_ptr", idx);
+            let len_name = quote::format_ident!("arg{}_len", idx);
+            let elem = elem_type
+                .as_ref()
+                .map_or_else(|| quote! { u8 }, |elem| quote! { #elem });
+            // Zig may pass an empty slice with a null or dangling pointer,
+            // which from_raw_parts rejects
+            let (ptr_type, slice_expr) = if is_mut {
+                (
+                    quote! { *mut #elem },
+                    quote! {
+                        if #len_name == 0 || #ptr_name.is_null() {
+                            &mut []
+                        } else {
+                            core::slice::from_raw_parts_mut(#ptr_name, #len_name)
+                        }
+                    },
+                )
+            } else {
+                (
+                    quote! { *const #elem },
+                    quote! {
+                        if #len_name == 0 || #ptr_name.is_null() {
+                            &[]
+                        } else {
+                            core::slice::from_raw_parts(#ptr_name, #len_name)
+                        }
+                    },
+                )
+            };
+            trampoline_params.push(quote! { #ptr_name: #ptr_type });
+            trampoline_params.push(quote! { #len_name: usize });
+            let context = format!("argument {idx} of the `{param_name}` callback");
+            trampoline_setup.push(quote! {
+                ::autozig::contracts::check_slice_parts(#context, #ptr_name as *const _, #len_name);
+            });
+            if elem_type.is_none() {
+                // &str: Zig must hand back valid UTF-8
+                trampoline_setup.push(quote! {
+                    let #arg_name = unsafe {
+                        core::str::from_utf8(#slice_expr)
+                            .expect("Zig passed invalid UTF-8 to a &str callback argument")
+                    };
+                });
+            } else {
+                trampoline_setup.push(quote! {
+                    let #arg_name = unsafe { #slice_expr };
+                });
+            }
+        } else {
+            trampoline_params.push(quote! { #arg_name: #arg });
+        }
+        call_args.push(quote! { #arg_name });
+    }
+
+    let prelude = quote! {
+        // Callback trampoline: Zig calls this with the context pointer we pass below
+        extern "C" fn #trampoline_name(
+            ctx: *mut core::ffi::c_void,
+            #(#trampoline_params),*
+        ) #output {
+            let callback = unsafe { &mut *(ctx as *mut &mut dyn FnMut(#(#args),*) #output) };
+            #(#trampoline_setup)*
+            callback(#(#call_args),*)
+        }
+        let mut #param_name = #param_name;
+        let mut #dyn_name: &mut dyn FnMut(#(#args),*) #output = &mut #param_name;
+    };
+
+    let ffi_args = vec![
+        quote! { &mut #dyn_name as *mut _ as *mut core::ffi::c_void },
+        quote! { #trampoline_name },
+    ];
+
+    (prelude, ffi_args)
+}
+
+/// Extract the generic arguments of a `Name<A, B, ..>` type path
+fn generic_type_args<'a>(ty: &'a syn::Type, name: &str) -> Option<Vec<&'a syn::Type>> {
+    let syn::Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    Some(
+        args.args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Check if a return type is `Result<T, E>` and extract `T` and `E`
+///
+/// Such functions are lowered through `autozig::ffi_types::ZigResult<T, E>`,
+/// which mirrors the Zig-side `AutoZigResult(T, E)` helper type.
+fn is_result_return_type(output: &syn::ReturnType) -> Option<(&syn::Type, &syn::Type)> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    match generic_type_args(ty, "Result")?.as_slice() {
+        [ok, err] => Some((ok, err)),
+        _ => None,
+    }
+}
+
+/// Check if a return type is `Option<T>` and extract `T`
+fn is_option_return_type(output: &syn::ReturnType) -> Option<&syn::Type> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    match generic_type_args(ty, "Option")?.as_slice() {
+        [inner] => Some(inner),
+        _ => None,
+    }
+}
+
+/// Check if a return type is `Option<*const T>` / `Option<*mut T>`
+///
+/// These are returned as a plain (nullable) pointer, with null mapped to
+/// `None`, so no ABI lowering is required.
+fn is_option_ptr_return_type(output: &syn::ReturnType) -> Option<&syn::Type> {
+    is_option_return_type(output).filter(|inner| matches!(inner, syn::Type::Ptr(_)))
+}
+
+/// Owned byte buffers that Zig can hand over to Rust
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OwnedBytes {
+    String,
+    Vec,
+    /// `autozig::ffi_types::ZigString`, which keeps the Zig allocation
+    ZigString,
+}
+
+/// Check if a return type is `String`, `Vec<u8>` or `ZigString`
+///
+/// All are returned from Zig as an `AutoZigBytes` buffer (layout of
+/// `autozig::ffi_types::ZigBuffer`) that is freed by its Zig allocator.
+fn is_owned_bytes_return_type(output: &syn::ReturnType) -> Option<OwnedBytes> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    if let syn::Type::Path(type_path) = &**ty {
+        if type_path.path.is_ident("String") {
+            return Some(OwnedBytes::String);
+        }
+        if type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "ZigString")
+        {
+            return Some(OwnedBytes::ZigString);
+        }
+    }
+    match generic_type_args(ty, "Vec")?.as_slice() {
+        [syn::Type::Path(elem)] if elem.path.is_ident("u8") => Some(OwnedBytes::Vec),
+        _ => None,
+    }
+}
+
+/// Check if a return type is `&'static str`
+///
+/// Zig returns these as an `AutoZigSlice(u8)` over static data (string
+/// literals, `@tagName`), received as `autozig::ffi_types::ZigSliceRef<u8>`.
+fn is_static_str_return_type(output: &syn::ReturnType) -> bool {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    let syn::Type::Reference(type_ref) = &**ty else {
+        return false;
+    };
+    type_ref.mutability.is_none()
+        && type_ref
+            .lifetime
+            .as_ref()
+            .is_some_and(|lifetime| lifetime.ident == "static")
+        && matches!(&*type_ref.elem, syn::Type::Path(path) if path.path.is_ident("str"))
+}
+
+/// FFI-level type of a lowered return value (the pointee of `__autozig_out`)
+fn lowered_return_type(ty: &syn::Type, output: &syn::ReturnType) -> proc_macro2::TokenStream {
+    if is_owned_bytes_return_type(output).is_some() {
+        quote! { ::autozig::ffi_types::ZigBuffer }
+    } else if is_static_str_return_type(output) {
+        quote! { ::autozig::ffi_types::ZigSliceRef<u8> }
+    } else if let Some((ok, err)) = is_result_return_type(output) {
+        quote! { ::autozig::ffi_types::ZigResult<#ok, #err> }
+    } else if let Some(inner) = is_option_return_type(output) {
+        quote! { ::autozig::ffi_types::ZigOption<#inner> }
+    } else {
+        quote! { #ty }
+    }
+}
+
+/// Method call converting a lowered return value back to the declared type
+fn lowered_return_conversion(output: &syn::ReturnType) -> proc_macro2::TokenStream {
+    match is_owned_bytes_return_type(output) {
+        Some(OwnedBytes::String) => quote! { .into_string() },
+        Some(OwnedBytes::Vec) => quote! { .into_bytes() },
+        Some(OwnedBytes::ZigString) => quote! { .into_zig_string() },
+        None if is_static_str_return_type(output) => quote! { .into_static_str() },
+        None if is_result_return_type(output).is_some() => quote! { .into_result() },
+        None if is_option_return_type(output).is_some() => quote! { .into_option() },
+        None => quote! {},
+    }
+}
+
+/// `autozig::pointer_log` records and `autozig::contracts` checks for the
+/// pointers a wrapper hands to Zig
+///
+/// They compile to nothing unless autozig's `pointer-log` or `ffi-contracts`
+/// feature is enabled.
+fn pointer_arg_hooks(
+    fn_name: &syn::Ident,
+    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::Token![,]>,
+) -> Vec<proc_macro2::TokenStream> {
+    let function = fn_name.to_string();
+    let mut records = Vec::new();
+    for input in inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            continue;
+        };
+        let param_name = &ident.ident;
+        let name = param_name.to_string();
+        let param_type = &*pat_type.ty;
+        if ptr_len_param(param_type).is_some() || is_mut_fixed_array_ref(param_type).is_some() {
+            records.push(quote! {
+                ::autozig::pointer_log::record_arg(#function, #name, #param_name.as_ptr(), #param_name.len());
+            });
+        } else if matches!(param_type, syn::Type::Ptr(_)) {
+            let context = format!("`{function}` argument `{name}`");
+            records.push(quote! {
+                ::autozig::pointer_log::record_arg(#function, #name, #param_name as *const _, 1);
+                ::autozig::contracts::check_arg_ptr(#context, #param_name as *const _);
+            });
+        }
+    }
+    records
+}
+
+/// `autozig::trace_ffi` span of a wrapper's FFI call, with the byte sizes of
+/// its slice and string arguments
+///
+/// It compiles to nothing unless autozig's `trace-ffi` feature is enabled.
+fn ffi_span(
+    fn_name: &syn::Ident,
+    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::Token![,]>,
+) -> proc_macro2::TokenStream {
+    let function = fn_name.to_string();
+    let sizes = inputs.iter().filter_map(|input| {
+        let syn::FnArg::Typed(pat_type) = input else {
+            return None;
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            return None;
+        };
+        let param_type = &*pat_type.ty;
+        if ptr_len_param(param_type).is_none() && is_mut_fixed_array_ref(param_type).is_none() {
+            return None;
+        }
+        let param_name = &ident.ident;
+        let name = param_name.to_string();
+        Some(quote! { (#name, ::core::mem::size_of_val(&*#param_name)) })
+    });
+    quote! { ::autozig::trace_ffi::span(#function, &[#(#sizes),*]) }
+}
+
+/// [`ffi_span`] and `autozig::metrics` timer covering the rest of a
+/// synchronous wrapper
+fn call_guards(
+    fn_name: &syn::Ident,
+    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::Token![,]>,
+) -> proc_macro2::TokenStream {
+    let span = ffi_span(fn_name, inputs);
+    let function = fn_name.to_string();
+    quote! {
+        let __autozig_span = #span.entered();
+        let __autozig_timer = ::autozig::metrics::start(#function);
+    }
+}
+
+/// Unsafe FFI `call` as a wrapper's tail expression, followed by
+/// `log_return` (from [`pointer_log_return`]) if non-empty
+fn logged_call(
+    call: proc_macro2::TokenStream,
+    log_return: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if log_return.is_empty() {
+        return quote! { unsafe { #call } };
+    }
+    quote! {
+        let ret = unsafe { #call };
+        #log_return
+        ret
+    }
+}
+
+/// Record `ret` in `autozig::pointer_log` if the function returns a raw pointer
+fn pointer_log_return(fn_name: &syn::Ident, output: &syn::ReturnType) -> proc_macro2::TokenStream {
+    let returns_ptr = match output {
+        syn::ReturnType::Type(_, ty) => matches!(**ty, syn::Type::Ptr(_)),
+        syn::ReturnType::Default => false,
+    };
+    if !returns_ptr && is_option_ptr_return_type(output).is_none() {
+        return quote! {};
+    }
+    let function = fn_name.to_string();
+    quote! {
+        ::autozig::pointer_log::record_return(#function, ret as *const _);
+    }
+}
+
+/// Check if a type is a struct type (non-primitive) that needs ABI-safe pointer
+/// passing Returns true for struct types, false for primitives
+fn is_struct_type(ty: &syn::Type) -> bool {
+    if let syn::Type::Path(type_path) = ty {
+        if let Some(ident) = type_path.path.get_ident() {
+            let ident_str = ident.to_string();
+            // Whitelist: Rust primitive types - these don't need pointer conversion
+            return !matches!(
+                ident_str.as_str(),
+                "i8" | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+                    | "f32"
+                    | "f64"
+                    | "bool"
+                    | "char"
+                    | "()"
+            );
+        }
+        // Complex path types (like generic types) - treat as struct type
+        true
+    } else if matches!(ty, syn::Type::Tuple(tuple) if tuple.elems.is_empty()) {
+        // Unit type () - not a struct
+        false
+    } else {
+        // Other types (arrays, references, etc.) - not struct types
+        // Arrays and references are already handled by other functions
+        false
+    }
+}
+
+
+/// Generate ZST struct types for trait implementations (Phase 1)
+/// Generate Opaque Pointer struct types for stateful trait implementations
+/// (Phase 2)
+fn generate_trait_impl_types(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    let mut type_defs = Vec::new();
+    let mut generated_types = std::collections::HashSet::new();
+
+    for trait_impl in &config.rust_trait_impls {
+        // Skip if we've already generated this type
+        if generated_types.contains(&trait_impl.target_type) {
+            continue;
+        }
+        generated_types.insert(trait_impl.target_type.clone());
+
+        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
+
+        if trait_impl.is_opaque {
+            // Phase 2: Generate opaque pointer struct. Default is only possible
+            // through a `new()` constructor taking no arguments.
+            let has_default_constructor = config
+                .rust_trait_impls
+                .iter()
+                .filter(|t| t.target_type == trait_impl.target_type)
+                .filter_map(|t| t.constructor.as_ref())
+                .any(|c| c.name == "new" && c.sig.inputs.is_empty());
+            let with_default = has_default_constructor && !trait_impl.no_default;
+            type_defs.push(generate_opaque_struct(&type_name, with_default, trait_impl));
+        } else if trait_impl.is_zst {
+            // Phase 1: Generate zero-sized type with Default derive
+            if trait_impl.no_default {
+                type_defs.push(quote! {
+                    #[derive(Debug, Clone, Copy)]
+                    pub struct #type_name;
+                });
+            } else {
+                type_defs.push(quote! {
+                    #[derive(Default, Debug, Clone, Copy)]
+                    pub struct #type_name;
+                });
+            }
+        }
+    }
+
+    quote! {
+        #(#type_defs)*
+    }
+}
+
+/// Generate an opaque pointer struct (Phase 2)
+///
+/// `with_default` adds a `Default` impl calling `new()`; it is left out when
+/// the user opted out with `#[autozig(no_default)]` or there is no such
+/// constructor.
+///
+/// Opaque types are `!Send` and `!Sync` (via `PhantomData<*mut ()>`) unless
+/// declared `struct T(opaque, send, sync);`, which asserts that the Zig
+/// implementation is thread-safe. `debug_lock` adds a mutex that `&self`
+/// methods hold in debug builds, to tell races inside Zig apart from other
+/// bugs.
+fn generate_opaque_struct(
+    type_name: &syn::Ident,
+    with_default: bool,
+    trait_impl: &autozig_parser::RustTraitImpl,
+) -> proc_macro2::TokenStream {
+    let default_impl = if with_default {
+        quote! {
+            // Implement Default by calling the constructor
+            impl Default for #type_name {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    if !std_enabled() && trait_impl.debug_lock {
+        return syn::Error::new(
+            type_name.span(),
+            "`debug_lock` needs std::sync::Mutex and is not available without the `std` feature",
+        )
+        .to_compile_error();
+    }
+    let lock_field = if trait_impl.debug_lock {
+        quote! {
+            #[cfg(debug_assertions)]
+            _lock: std::sync::Mutex<()>,
+        }
+    } else {
+        quote! {}
+    };
+    let send_impl = if trait_impl.send {
+        quote! {
+            // SAFETY: asserted by the `send` marker on the declaration
+            unsafe impl Send for #type_name {}
+        }
+    } else {
+        quote! {}
+    };
+    let sync_impl = if trait_impl.sync {
+        quote! {
+            // SAFETY: asserted by the `sync` marker on the declaration
+            unsafe impl Sync for #type_name {}
+        }
+    } else {
+        quote! {}
+    };
+
+    let pyclass = python::pyclass_attr(trait_impl);
+
+    quote! {
+        #pyclass
+        pub struct #type_name {
+            inner: core::ptr::NonNull<core::ffi::c_void>,
+            #lock_field
+            _marker: core::marker::PhantomData<*mut ()>,
+        }
+
+        #send_impl
+        #sync_impl
+        #default_impl
+    }
+}
+
+/// Statement taking the `debug_lock` mutex in a `&self` method
+fn debug_lock_guard(
+    trait_impl: &autozig_parser::RustTraitImpl,
+    sig: &syn::Signature,
+) -> proc_macro2::TokenStream {
+    let shared_self = sig
+        .receiver()
+        .is_some_and(|receiver| receiver.reference.is_some() && receiver.mutability.is_none());
+    if !(trait_impl.is_opaque && trait_impl.debug_lock && shared_self) {
+        return quote! {};
+    }
+    quote! {
+        #[cfg(debug_assertions)]
+        let _guard = self._lock.lock().unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// Generate trait implementations (Phase 1 & 2)
+fn generate_trait_implementations(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    let mut impls = Vec::new();
+    let mod_name = syn::Ident::new(config.get_mod_name(), proc_macro2::Span::call_site());
+
+    for trait_impl in &config.rust_trait_impls {
+        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
+
+        // Phase 2: Generate constructor if present
+        if let Some(constructor) = &trait_impl.constructor {
+            impls.push(generate_constructor(
+                &type_name,
+                constructor,
+                &mod_name,
+                trait_impl.debug_lock,
+            ));
+        }
+
+        // Phase 2: Generate Drop implementation if destructor present, unless
+        // the user provides their own (#[autozig(no_drop)])
+        if let Some(destructor) = trait_impl
+            .destructor
+            .as_ref()
+            .filter(|_| !trait_impl.no_drop)
+        {
+            impls.push(generate_drop_impl(&type_name, destructor, &mod_name));
+        }
+
+        // digest::Digest<N> on an opaque hasher expands to the RustCrypto traits
+        // behind digest's blanket `Digest` impl
+        if trait_impl.is_opaque && trait_impl.trait_name == "Digest" {
+            impls.push(generate_digest_impls(&type_name, trait_impl, &mod_name));
+            continue;
+        }
+
+        // Inherent impl methods (empty trait name) are public; trait methods
+        // take the trait's visibility
+        let is_inherent = trait_impl.trait_name.is_empty();
+        let vis = if is_inherent {
+            quote! { pub }
+        } else {
+            quote! {}
+        };
+
+        // Generate methods for the trait implementation
+        let mut methods = Vec::new();
+        for method in &trait_impl.methods {
+            if let Some(item) = opaque_iterator_item(trait_impl, method) {
+                impls.push(generate_opaque_iterator_impl(
+                    &type_name, trait_impl, method, item, &mod_name,
+                ));
+                continue;
+            }
+            let method_sig = &method.sig;
+            let method_name = &method_sig.ident;
+            let inputs = &method_sig.inputs;
+            let return_type = &method_sig.output;
+            let generated = methods.len();
+
+            // Phase 2: For opaque types, always generate FFI call (ignore user's simplified
+            // body) Phase 1: Use original method body if available (preserves
+            // user logic like Option wrapping)
+            let should_generate_ffi_call = trait_impl.is_opaque || method.body.is_none();
+
+            if !should_generate_ffi_call {
+                // Phase 1: Use the original body with unsafe wrapper (for ZST with complex
+                // logic)
+                if let Some(original_body) = &method.body {
+                    methods.push(quote! {
+                        #vis fn #method_name(#inputs) #return_type {
+                            unsafe #original_body
+                        }
+                    });
+                }
+            } else if let Some(elem_type) =
+                is_borrowed_slice_return(return_type).filter(|_| trait_impl.is_opaque)
+            {
+                methods.push(generate_borrowed_slice_method(
+                    method,
+                    elem_type,
+                    &vis,
+                    &mod_name,
+                    debug_lock_guard(trait_impl, method_sig),
+                ));
+            } else {
+                // Fallback: generate simple FFI call
+                let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
+
+                let mut ffi_args = Vec::new();
+
+                // Phase 2: Inject self pointer for opaque types
+                if trait_impl.is_opaque {
+                    ffi_args.push(inject_self_pointer(method_sig));
+                }
+
+                for input in &method_sig.inputs {
+                    if let syn::FnArg::Receiver(_) = input {
+                        // Skip self/&self/&mut self - already handled above
+                        continue;
+                    }
+
+                    if let syn::FnArg::Typed(pat_type) = input {
+                        if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                            let param_name = &ident.ident;
+
+                            if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(&pat_type.ty) {
+                                if is_mut {
+                                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                                } else {
+                                    ffi_args.push(quote! { #param_name.as_ptr() });
+                                }
+                                ffi_args.push(quote! { #param_name.len() });
+                            } else if is_mut_fixed_array_ref(&pat_type.ty).is_some() {
+                                // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
+                                ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                            } else if is_fixed_array(&pat_type.ty).is_some() {
+                                // Fixed array [T; N] -> pass &param
+                                ffi_args.push(quote! { &#param_name });
+                            } else {
+                                ffi_args.push(quote! { #param_name });
+                            }
+                        }
+                    }
+                }
+
+                let guard = debug_lock_guard(trait_impl, method_sig);
+                methods.push(quote! {
+                    #vis fn #method_name(#inputs) #return_type {
+                        #guard
+                        unsafe {
+                            #mod_name::#zig_fn(#(#ffi_args),*)
+                        }
+                    }
+                });
+            }
+
+            // Doc comments of the declared method
+            if let Some(generated) = methods.get_mut(generated) {
+                let docs = &method.doc_attrs;
+                *generated = quote! {
+                    #(#docs)*
+                    #generated
+                };
+            }
+        }
+
+        // Generate the complete impl block
+        if is_inherent {
+            if !methods.is_empty() {
+                impls.push(quote! {
+                    impl #type_name {
+                        #(#methods)*
+                    }
+                });
+            }
+        } else {
+            let trait_path = trait_impl_path(trait_impl);
+            let assoc_items = &trait_impl.assoc_items;
+            let rust_methods = &trait_impl.rust_methods;
+            impls.push(quote! {
+                impl #trait_path for #type_name {
+                    #(#assoc_items)*
+                    #(#methods)*
+                    #(#rust_methods)*
+                }
+            });
+        }
+    }
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// The trait as written in the impl (`std::hash::Hasher`, `Iterator`), so
+/// that traits sharing a name resolve to the right one
+fn trait_impl_path(trait_impl: &autozig_parser::RustTraitImpl) -> proc_macro2::TokenStream {
+    match &trait_impl.trait_path {
+        Some(path) => quote! { #path },
+        None => {
+            let trait_name =
+                syn::Ident::new(&trait_impl.trait_name, proc_macro2::Span::call_site());
+            quote! { #trait_name }
+        },
+    }
+}
+
+/// Item type of `fn next(&mut self) -> Option<T>` declared in an opaque
+/// type's inherent impl, which makes the type an `Iterator` over `T`
+///
+/// Zig exports it as `fn (self: *State, out: *T) bool`, writing the next item
+/// and returning `false` when exhausted.
+fn opaque_iterator_item<'a>(
+    trait_impl: &autozig_parser::RustTraitImpl,
+    method: &'a autozig_parser::TraitMethod,
+) -> Option<&'a syn::Type> {
+    let mut_self = method
+        .sig
+        .receiver()
+        .is_some_and(|receiver| receiver.reference.is_some() && receiver.mutability.is_some());
+    if !(trait_impl.is_opaque
+        && trait_impl.trait_name.is_empty()
+        && method.name == "next"
+        && mut_self
+        && method.sig.inputs.len() == 1)
+    {
+        return None;
+    }
+    is_option_return_type(&method.sig.output)
+}
+
+/// `Iterator` impl of an opaque type over its Zig `next` export
+///
+/// A `fn remaining(&self) -> usize` method in the same impl provides an exact
+/// `size_hint`.
+fn generate_opaque_iterator_impl(
+    type_name: &syn::Ident,
+    trait_impl: &autozig_parser::RustTraitImpl,
+    method: &autozig_parser::TraitMethod,
+    item: &syn::Type,
+    mod_name: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
+    let has_remaining = trait_impl.methods.iter().any(|method| {
+        method.name == "remaining"
+            && method.sig.inputs.len() == 1
+            && method
+                .sig
+                .receiver()
+                .is_some_and(|receiver| receiver.mutability.is_none())
+    });
+    let size_hint = has_remaining.then(|| {
+        quote! {
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let remaining = #type_name::remaining(self);
+                (remaining, Some(remaining))
+            }
+        }
+    });
+
+    quote! {
+        impl ::core::iter::Iterator for #type_name {
+            type Item = #item;
+
+            fn next(&mut self) -> Option<#item> {
+                let mut item = ::core::mem::MaybeUninit::<#item>::uninit();
+                if unsafe { #mod_name::#zig_fn(self.inner.as_ptr(), item.as_mut_ptr()) } {
+                    Some(unsafe { item.assume_init() })
+                } else {
+                    None
+                }
+            }
+
+            #size_hint
+        }
+    }
+}
+
+/// Generate an opaque type method returning a slice borrowed from Zig state
+///
+/// Zig returns an `AutoZigSlice(T)` (ptr, len) pair; the wrapper hands it out
+/// with the lifetime of the `&self` / `&mut self` borrow.
+fn generate_borrowed_slice_method(
+    method: &autozig_parser::TraitMethod,
+    elem_type: Option<syn::Type>,
+    vis: &proc_macro2::TokenStream,
+    mod_name: &syn::Ident,
+    guard: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let method_sig = &method.sig;
+    let method_name = &method_sig.ident;
+    let inputs = &method_sig.inputs;
+    let return_type = &method_sig.output;
+    let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
+
+    let borrows_self = method_sig
+        .receiver()
+        .is_some_and(|receiver| receiver.reference.is_some());
+    if !borrows_self {
+        return syn::Error::new_spanned(
+            method_sig,
+            "methods returning a slice borrowed from Zig state must take `&self` or `&mut self`",
+        )
+        .to_compile_error();
+    }
+
+    let mut ffi_args = vec![inject_self_pointer(method_sig)];
+    for input in inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(&pat_type.ty) {
+                    if is_mut {
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else {
+                        ffi_args.push(quote! { #param_name.as_ptr() });
+                    }
+                    ffi_args.push(quote! { #param_name.len() });
+                } else {
+                    ffi_args.push(quote! { #param_name });
+                }
+            }
+        }
+    }
+
+    let view = quote! {
+        // SAFETY: the buffer is owned by the Zig object, which stays alive and
+        // unmodified while `self` is borrowed
+        unsafe { #mod_name::#zig_fn(#(#ffi_args),*).as_slice() }
+    };
+    let body = if elem_type.is_some() {
+        view
+    } else {
+        quote! {
+            core::str::from_utf8(#view).expect("Zig returned invalid UTF-8 for a &str")
+        }
+    };
+
+    quote! {
+        #vis fn #method_name(#inputs) #return_type {
+            #guard
+            #body
+        }
+    }
+}
+
+/// Generate `digest` crate trait impls for an opaque Zig hasher
+///
+/// `impl digest::Digest<N> for T { update, finalize_into, reset }` becomes
+/// `Update` and `FixedOutput` with an `N`-byte output, plus `Reset` and
+/// `FixedOutputReset` when `reset` is declared. Requires autozig's `digest`
+/// feature.
+fn generate_digest_impls(
+    type_name: &syn::Ident,
+    trait_impl: &autozig_parser::RustTraitImpl,
+    mod_name: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let output_size =
+        trait_impl
+            .trait_path
+            .as_ref()
+            .and_then(|path| match &path.segments.last()?.arguments {
+                syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+                    syn::GenericArgument::Const(syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(size),
+                        ..
+                    })) => Some(quote::format_ident!("U{}", size.base10_digits())),
+                    _ => None,
+                },
+                _ => None,
+            });
+    let Some(output_size) = output_size else {
+        return syn::Error::new_spanned(
+            &trait_impl.trait_path,
+            "digest adapters need the output size in bytes, e.g. `impl digest::Digest<32> for \
+             ZigSha256`",
+        )
+        .to_compile_error();
+    };
+
+    let method = |name: &str| trait_impl.methods.iter().find(|m| m.name == name);
+    let takes = |method: &autozig_parser::TraitMethod, check: fn(&syn::Type) -> bool| {
+        let typed: Vec<_> = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(pat_type) => Some(&pat_type.ty),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        typed.len() == 1 && check(typed[0])
+    };
+    let (Some(update), Some(finalize)) = (method("update"), method("finalize_into")) else {
+        return syn::Error::new_spanned(
+            &trait_impl.trait_path,
+            "digest adapters need `fn update(&mut self, data: &[u8])` and `fn              \
+             finalize_into(&mut self, out: &mut [u8; N])`",
+        )
+        .to_compile_error();
+    };
+    if !takes(update, |ty| matches!(is_slice_or_str_ref(ty), Some((false, Some(_))))) {
+        return syn::Error::new_spanned(&update.sig, "`update` must take `data: &[u8]`")
+            .to_compile_error();
+    }
+    if !takes(finalize, |ty| is_mut_fixed_array_ref(ty).is_some()) {
+        return syn::Error::new_spanned(
+            &finalize.sig,
+            "`finalize_into` must take `out: &mut [u8; N]`",
+        )
+        .to_compile_error();
+    }
+
+    let update_fn = syn::Ident::new(&update.zig_function, proc_macro2::Span::call_site());
+    let finalize_fn = syn::Ident::new(&finalize.zig_function, proc_macro2::Span::call_site());
+
+    let reset_impls = method("reset").map(|reset| {
+        let reset_fn = syn::Ident::new(&reset.zig_function, proc_macro2::Span::call_site());
+        quote! {
+            impl ::autozig::digest::Reset for #type_name {
+                fn reset(&mut self) {
+                    unsafe { #mod_name::#reset_fn(self.inner.as_ptr()) }
+                }
+            }
+
+            impl ::autozig::digest::FixedOutputReset for #type_name {
+                fn finalize_into_reset(&mut self, out: &mut ::autozig::digest::Output<Self>) {
+                    unsafe {
+                        #mod_name::#finalize_fn(self.inner.as_ptr(), out.as_mut_ptr());
+                        #mod_name::#reset_fn(self.inner.as_ptr());
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl ::autozig::digest::HashMarker for #type_name {}
+
+        impl ::autozig::digest::OutputSizeUser for #type_name {
+            type OutputSize = ::autozig::digest::consts::#output_size;
+        }
+
+        impl ::autozig::digest::Update for #type_name {
+            fn update(&mut self, data: &[u8]) {
+                unsafe { #mod_name::#update_fn(self.inner.as_ptr(), data.as_ptr(), data.len()) }
+            }
+        }
+
+        impl ::autozig::digest::FixedOutput for #type_name {
+            fn finalize_into(self, out: &mut ::autozig::digest::Output<Self>) {
+                // `self` is dropped afterwards, freeing the Zig state
+                unsafe { #mod_name::#finalize_fn(self.inner.as_ptr(), out.as_mut_ptr()) }
+            }
+        }
+
+        #reset_impls
+    }
+}
+
+/// Generate constructor for opaque types (Phase 2)
+fn generate_constructor(
+    type_name: &syn::Ident,
+    constructor: &autozig_parser::TraitMethod,
+    mod_name: &syn::Ident,
+    debug_lock: bool,
+) -> proc_macro2::TokenStream {
+    let zig_fn = syn::Ident::new(&constructor.zig_function, proc_macro2::Span::call_site());
+    let method_name = syn::Ident::new(&constructor.name, proc_macro2::Span::call_site());
+    let lock_init = if debug_lock {
+        quote! {
+            #[cfg(debug_assertions)]
+            _lock: std::sync::Mutex::new(()),
+        }
+    } else {
+        quote! {}
+    };
+
+    // Get parameters (excluding self)
+    let params: Vec<_> = constructor
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|input| {
+            if let syn::FnArg::Typed(pat_type) = input {
+                Some(pat_type)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let param_names: Vec<_> = params
+        .iter()
+        .filter_map(|pat_type| {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                Some(&ident.ident)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let inputs = &constructor.sig.inputs;
+    let docs = &constructor.doc_attrs;
+
+    quote! {
+        impl #type_name {
+            #(#docs)*
+            pub fn #method_name(#inputs) -> Self {
+                unsafe {
+                    let ptr = #mod_name::#zig_fn(#(#param_names),*);
+                    core::ptr::NonNull::new(ptr as *mut core::ffi::c_void)
+                        .map(|inner| Self {
+                            inner,
+                            #lock_init
+                            _marker: core::marker::PhantomData,
+                        })
+                        .expect("Zig allocation failed (OOM)")
+                }
+            }
+        }
+    }
+}
+
+/// Generate Drop implementation for opaque types (Phase 2)
+fn generate_drop_impl(
+    type_name: &syn::Ident,
+    destructor: &autozig_parser::TraitMethod,
+    mod_name: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let zig_fn = syn::Ident::new(&destructor.zig_function, proc_macro2::Span::call_site());
+
+    quote! {
+        impl Drop for #type_name {
+            fn drop(&mut self) {
+                unsafe {
+                    #mod_name::#zig_fn(self.inner.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+/// Inject self pointer as first argument for opaque types (Phase 2)
+fn inject_self_pointer(sig: &syn::Signature) -> proc_macro2::TokenStream {
+    // Check receiver type: &self or &mut self
+    for input in &sig.inputs {
+        if let syn::FnArg::Receiver(receiver) = input {
+            if receiver.mutability.is_some() {
+                // &mut self -> *mut c_void
+                return quote! { self.inner.as_ptr() };
+            } else {
+                // &self -> *const c_void
+                return quote! { self.inner.as_ptr() as *const core::ffi::c_void };
+            }
+        }
+    }
+
+    // No receiver, shouldn't happen for trait methods
+    quote! {}
+}
+
+/// Generate FFI declarations for Zig functions used in trait implementations
+/// (Phase 1 & 2)
+fn generate_trait_ffi_declarations(config: &AutoZigConfig) -> proc_macro2::TokenStream {
+    let mut decls = Vec::new();
+
+    for trait_impl in &config.rust_trait_impls {
+        // Phase 2: Generate constructor FFI declaration
+        if let Some(constructor) = &trait_impl.constructor {
+            let zig_fn = syn::Ident::new(&constructor.zig_function, proc_macro2::Span::call_site());
+            let params: Vec<_> = constructor
+                .sig
+                .inputs
+                .iter()
+                .filter_map(|input| {
+                    if let syn::FnArg::Typed(pat_type) = input {
+                        let param_name = &pat_type.pat;
+                        let param_type = &pat_type.ty;
+                        Some(quote! { #param_name: #param_type })
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+
+            decls.push(quote! {
+                extern "C" {
+                    pub fn #zig_fn(#(#params),*) -> *mut core::ffi::c_void;
+                }
+            });
+        }
+
+        // Phase 2: Generate destructor FFI declaration
+        if let Some(destructor) = &trait_impl.destructor {
+            let zig_fn = syn::Ident::new(&destructor.zig_function, proc_macro2::Span::call_site());
+
+            decls.push(quote! {
+                extern "C" {
+                    pub fn #zig_fn(ptr: *mut core::ffi::c_void);
+                }
+            });
+        }
+
+        for method in &trait_impl.methods {
+            let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
+            let method_sig = &method.sig;
+
+            if let Some(item) = opaque_iterator_item(trait_impl, method) {
+                decls.push(quote! {
+                    extern "C" {
+                        pub fn #zig_fn(self_ptr: *mut core::ffi::c_void, out: *mut #item) -> bool;
+                    }
+                });
+                continue;
+            }
+
+            // Build FFI parameter list
+            let mut ffi_params = Vec::new();
+
+            // Phase 2: Add self pointer parameter for opaque types
+            if trait_impl.is_opaque {
+                let self_param = handle_receiver_type(method_sig);
+                if !self_param.is_empty() {
+                    ffi_params.push(self_param);
+                }
+            }
+
+            for input in &method_sig.inputs {
+                if let syn::FnArg::Receiver(_) = input {
+                    // Skip &self / &mut self for ZST (Phase 1)
+                    // For opaque types, already handled above
+                    continue;
+                }
+
+                if let syn::FnArg::Typed(pat_type) = input {
+                    let param_name = &pat_type.pat;
+                    let param_type = &pat_type.ty;
+
+                    // Check if this is a slice or str reference
+                    if let Some((is_mut, elem_type)) = is_slice_or_str_ref(param_type) {
+                        // Extract parameter name as string
+                        let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                            ident.ident.to_string()
+                        } else {
+                            continue;
+                        };
+
+                        // Lower to ptr + len
+                        let ptr_type = if let Some(elem) = elem_type {
+                            if is_mut {
+                                quote! { *mut #elem }
+                            } else {
+                                quote! { *const #elem }
+                            }
+                        } else if is_mut {
+                            quote! { *mut u8 }
+                        } else {
+                            quote! { *const u8 }
+                        };
+
+                        let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
+                        let len_name = quote::format_ident!("{}_len", param_name_str);
+
+                        ffi_params.push(quote! { #ptr_name: #ptr_type });
+                        ffi_params.push(quote! { #len_name: usize });
+                    } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type)
+                    {
+                        // NEW: Mutable array &mut [T; N] -> *mut T
+                        let ptr_type = quote! { *mut #elem_type };
+                        ffi_params.push(quote! { #param_name: #ptr_type });
+                    } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
+                        // Fixed array [T; N] -> *const [N]T
+                        let ptr_type = quote! { *const #param_type };
+                        ffi_params.push(quote! { #param_name: #ptr_type });
+                    } else {
+                        ffi_params.push(quote! { #param_name: #param_type });
+                    }
+                }
+            }
+
+            // Extract Zig function return type from Zig code
+            let zig_return_type = extract_zig_return_type(&config.zig_code, &method.zig_function);
+            let borrowed_slice =
+                is_borrowed_slice_return(&method_sig.output).filter(|_| trait_impl.is_opaque);
+            let return_type = if let Some(elem_type) = borrowed_slice {
+                // Borrowed slices come back as an AutoZigSlice(T) (ptr, len) pair
+                let elem = elem_type.map_or_else(|| quote! { u8 }, |elem| quote! { #elem });
+                syn::parse_quote! { -> ::autozig::ffi_types::ZigSliceRef<#elem> }
+            } else if let Some(zig_ret) = zig_return_type {
+                zig_ret
+            } else {
+                // Fallback: use method signature for most cases, but we need special handling
+                // If the method returns Option but doesn't have the Option in its body,
+                // it likely means the Zig function returns the unwrapped type
+                method_sig.output.clone()
+            };
+
+            decls.push(quote! {
+                extern "C" {
+                    pub fn #zig_fn(#(#ffi_params),*) #return_type;
+                }
+            });
+        }
+    }
+
+    quote! {
+        #(#decls)*
+    }
+}
+
+/// Extract return type from Zig function definition
+/// Looks for patterns like: `export fn function_name(...) TYPE {`
+fn extract_zig_return_type(zig_code: &str, fn_name: &str) -> Option<syn::ReturnType> {
+    // Simple string-based extraction
+    // Find "export fn function_name" - handle possible newline before function name
+    let search_pattern1 = format!("export fn {}", fn_name);
+    let search_pattern2 = format!("export fn\n{}", fn_name);
+
+    let start_pos = zig_code
+        .find(&search_pattern1)
+        .or_else(|| zig_code.find(&search_pattern2))?;
+
+    // Find the closing parenthesis of parameters
+    let after_fn = &zig_code[start_pos..];
+    let paren_start = after_fn.find('(')?;
+    let mut paren_count = 1;
+    let mut paren_end = paren_start + 1;
+
+    for (i, ch) in after_fn[paren_start + 1..].chars().enumerate() {
+        match ch {
+            '(' => paren_count += 1,
+            ')' => {
+                paren_count -= 1;
+                if paren_count == 0 {
+                    paren_end = paren_start + 1 + i;
+                    break;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Extract return type between ')' and '{'
+    let after_paren = &after_fn[paren_end + 1..];
+    let brace_pos = after_paren.find('{')?;
+    let return_type_str = after_paren[..brace_pos].trim();
+
+    // Map Zig types to Rust types
+    let rust_type = match return_type_str {
+        "i32" => quote! { -> i32 },
+        "u32" => quote! { -> u32 },
+        "i64" => quote! { -> i64 },
+        "u64" => quote! { -> u64 },
+        "f32" => quote! { -> f32 },
+        "f64" => quote! { -> f64 },
+        "bool" => quote! { -> bool },
+        "void" => quote! {},
+        _ => return None, // Unknown type, fall back to method signature
+    };
+
+    syn::parse2(rust_type).ok()
+}
+
+/// Handle receiver type for FFI parameter list (Phase 2)
+/// Returns the self pointer parameter for opaque types
+fn handle_receiver_type(sig: &syn::Signature) -> proc_macro2::TokenStream {
+    for input in &sig.inputs {
+        if let syn::FnArg::Receiver(receiver) = input {
+            if receiver.mutability.is_some() {
+                // &mut self -> *mut c_void
+                return quote! { self_ptr: *mut core::ffi::c_void };
+            } else {
+                // &self -> *const c_void
+                return quote! { self_ptr: *const core::ffi::c_void };
+            }
+        }
+    }
+    quote! {}
+}
+
+/// Expansion of an `include_zig!` or `bind_zig!` invocation: FFI
+/// declarations for code compiled elsewhere, plus the safe wrappers
+///
+/// `marker_code` is emitted as a doc attribute for the build script scanner.
+pub fn expand_external(
+    config: &IncludeZigConfig,
+    marker_code: &str,
+    macro_name: &str,
+) -> proc_macro2::TokenStream {
+    // Generate unique module name based on file path
+    // Convert "zig/math.zig" to "ffi_zig_math"
+    let mod_name = config.get_unique_mod_name();
+    let mod_name_ident = syn::Ident::new(&mod_name, proc_macro2::Span::call_site());
+
+    if config.has_rust_signatures()
+        || !config.rust_structs.is_empty()
+        || !config.rust_enums.is_empty()
+        || !config.rust_statics.is_empty()
+    {
+        // Generate enum definitions
+        let enum_defs = generate_enum_definitions_for_include(config);
+
+        // Generate struct definitions
+        let struct_defs = generate_struct_definitions_for_include(config);
+
+        // Phase 3: Use monomorphization-aware generation for include_zig! too
+        let (ffi_decls, wrappers) = generate_with_monomorphization_for_include(config);
+
+        let (static_decls, static_accessors) =
+            generate_static_bindings(&config.rust_statics, &mod_name_ident);
+
+        // Prebuilt libraries bound with bind_zig! are not mangled
+        let mangling = if macro_name == "include_zig!" {
+            symbol_mangling()
+        } else {
+            None
+        };
+        let ffi_items = prefix_link_names(
+            None,
+            mangling,
+            quote! {
+                #ffi_decls
+                #static_decls
+            },
+        );
+        let component_exports = wit::component_exports(&config.rust_signatures, None);
+        let napi_bindings = napi::napi_bindings(&config.rust_signatures, &[], None);
+        let python_bindings = python::python_bindings(&config.rust_signatures, &[], None);
+
+        quote! {
+            // Marker for scanner (will be removed in final output)
+            #[doc = #marker_code]
+
+            // Enum definitions (visible at module level)
+            #enum_defs
+
+            // Struct definitions (visible at module level)
+            #struct_defs
+
+            // Raw FFI module with extern "C" declarations (unique name per file)
+            mod #mod_name_ident {
+                use super::*;
+                #ffi_items
+            }
+
+            // Safe wrappers
+            #wrappers
+            #static_accessors
+            #component_exports
+            #napi_bindings
+            #python_bindings
+        }
+    } else {
+        let message = format!("{} macro requires Rust function signatures", macro_name);
+        quote! {
+            #[doc = #marker_code]
+            compile_error!(#message);
+        }
+    }
+}
+
+/// Helper functions for include_zig! - reuse the same logic as autozig!
+fn generate_enum_definitions_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
+    let enums: Vec<_> = config.rust_enums.iter().map(|e| &e.item).collect();
+    quote! {
+        #(#enums)*
+    }
+}
+
+fn generate_struct_definitions_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
+    let structs: Vec<_> = config.rust_structs.iter().map(|s| &s.item).collect();
+    let instances = generate_struct_instances(&config.rust_structs);
+    let layout_checks = generate_layout_assertions(&config.rust_structs);
+    quote! {
+        #(#structs)*
+        #instances
+        #layout_checks
+    }
+}
+
+#[allow(dead_code)]
+fn generate_ffi_declarations_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
+    let mut decls = Vec::new();
+
+    for rust_sig in &config.rust_signatures {
+        let sig = &rust_sig.sig;
+        let fn_name = &sig.ident;
+        let output = &sig.output;
+
+        let mut ffi_params = Vec::new();
+
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(pat_type) = input {
+                let param_type = &pat_type.ty;
+                let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                    ident.ident.to_string()
+                } else {
+                    continue;
+                };
+
+                if let Some((is_mut, elem_type)) = ptr_len_param(param_type) {
+                    let ptr_type = if let Some(elem) = elem_type {
+                        if is_mut {
+                            quote! { *mut #elem }
+                        } else {
+                            quote! { *const #elem }
+                        }
+                    } else if is_mut {
+                        quote! { *mut u8 }
+                    } else {
+                        quote! { *const u8 }
+                    };
+
+                    let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
+                    let len_name = quote::format_ident!("{}_len", param_name_str);
+
+                    ffi_params.push(quote! { #ptr_name: #ptr_type });
+                    ffi_params.push(quote! { #len_name: usize });
+                } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type) {
+                    // NEW: Mutable array &mut [T; N] -> *mut T
+                    let param_name = &pat_type.pat;
+                    let ptr_type = quote! { *mut #elem_type };
+                    ffi_params.push(quote! { #param_name: #ptr_type });
+                } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
+                    // NEW: Fixed array [T; N] -> *const [N]T
+                    let param_name = &pat_type.pat;
+                    let ptr_type = quote! { *const #param_type };
+                    ffi_params.push(quote! { #param_name: #ptr_type });
+                } else {
+                    let param_name = &pat_type.pat;
+                    ffi_params.push(quote! { #param_name: #param_type });
+                }
+            }
+        }
+
+        decls.push(quote! {
+            #[link(name = "autozig")]
+            extern "C" {
+                pub fn #fn_name(#(#ffi_params),*) #output;
+            }
+        });
+    }
+
+    quote! {
+        #(#decls)*
+    }
+}
+
+#[allow(dead_code)]
+fn generate_safe_wrappers_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
+    let mut wrappers = Vec::new();
+    let mod_name_str = config.get_unique_mod_name();
+    let mod_name = syn::Ident::new(&mod_name_str, proc_macro2::Span::call_site());
+
+    for rust_sig in &config.rust_signatures {
+        let sig = &rust_sig.sig;
+        let fn_name = &sig.ident;
+        let inputs = &sig.inputs;
+        let output = &sig.output;
+
+        let mut ffi_args = Vec::new();
+
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(pat_type) = input {
+                if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                    let param_name = &ident.ident;
+                    let param_type = &pat_type.ty;
+
+                    if let Some((is_mut, _elem_type)) = ptr_len_param(param_type) {
+                        if is_mut {
+                            ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                        } else {
+                            ffi_args.push(quote! { #param_name.as_ptr() });
+                        }
+                        ffi_args.push(quote! { #param_name.len() });
+                    } else if is_mut_fixed_array_ref(param_type).is_some() {
+                        // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else if is_fixed_array(param_type).is_some() {
+                        // NEW: Fixed array [T; N] -> pass &param
+                        ffi_args.push(quote! { &#param_name });
+                    } else {
+                        ffi_args.push(quote! { #param_name });
+                    }
+                }
+            }
+        }
+
+        let mut log_args = pointer_arg_hooks(fn_name, inputs);
+        log_args.push(call_guards(fn_name, inputs));
+        let call = logged_call(
+            quote! { #mod_name::#fn_name(#(#ffi_args),*) },
+            pointer_log_return(fn_name, output),
+        );
+        let wrapper = quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#log_args)*
+                #call
+            }
+        };
+
+        wrappers.push(wrapper);
+    }
+
+    quote! {
+        #(#wrappers)*
+    }
+}
+
+#[allow(dead_code)]
+fn generate_trait_impl_types_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
+    let mut type_defs = Vec::new();
+
+    for trait_impl in &config.rust_trait_impls {
+        if trait_impl.is_zst {
+            let type_name =
+                syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
+
+            type_defs.push(quote! {
+                #[derive(Default, Debug, Clone, Copy)]
+                pub struct #type_name;
+            });
+        }
+    }
+
+    quote! {
+        #(#type_defs)*
+    }
+}
+
+#[allow(dead_code)]
+fn generate_trait_implementations_for_include(
+    config: &IncludeZigConfig,
+) -> proc_macro2::TokenStream {
+    let mut impls = Vec::new();
+    let mod_name_str = config.get_unique_mod_name();
+    let mod_name = syn::Ident::new(&mod_name_str, proc_macro2::Span::call_site());
+
+    for trait_impl in &config.rust_trait_impls {
+        let trait_path = trait_impl_path(trait_impl);
+        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
+        let assoc_items = &trait_impl.assoc_items;
+        let rust_methods = &trait_impl.rust_methods;
+
+        let mut methods = Vec::new();
+        for method in &trait_impl.methods {
+            let method_sig = &method.sig;
+            let method_name = &method_sig.ident;
+            let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
+
+            let mut ffi_args = Vec::new();
+            for input in &method_sig.inputs {
+                if let syn::FnArg::Typed(pat_type) = input {
+                    if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                        let param_name = &ident.ident;
+
+                        if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(&pat_type.ty) {
+                            if is_mut {
+                                ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                            } else {
+                                ffi_args.push(quote! { #param_name.as_ptr() });
+                            }
+                            ffi_args.push(quote! { #param_name.len() });
+                        } else {
+                            ffi_args.push(quote! { #param_name });
+                        }
+                    }
+                }
+            }
+
+            let return_type = &method_sig.output;
+
+            methods.push(quote! {
+                fn #method_name(#method_sig) #return_type {
+                    unsafe {
+                        #mod_name::#zig_fn(#(#ffi_args),*)
+                    }
+                }
+            });
+        }
+
+        impls.push(quote! {
+            impl #trait_path for #type_name {
+                #(#assoc_items)*
+                #(#methods)*
+                #(#rust_methods)*
+            }
+        });
+    }
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// `extern` declarations (for the FFI module) and safe accessors for the
+/// `static NAME: T;` items bound to data exported by the Zig code
+///
+/// `CRC_TABLE` is read through `crc_table() -> &'static T`, a plain load with
+/// no call into Zig. Zig exports the data as `export const CRC_TABLE = ...;`,
+/// so it is immutable and `static mut` is rejected.
+fn generate_static_bindings(
+    statics: &[autozig_parser::RustStaticDefinition],
+    mod_name: &syn::Ident,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut decls = Vec::new();
+    let mut accessors = Vec::new();
+
+    for definition in statics {
+        let item = &definition.item;
+        if let syn::StaticMutability::Mut(_) = item.mutability {
+            accessors.push(
+                syn::Error::new_spanned(
+                    item,
+                    "Zig data is bound read-only; declare it as `static`, not `static mut`",
+                )
+                .to_compile_error(),
+            );
+            continue;
+        }
+
+        let name = &item.ident;
+        let ty = &item.ty;
+        let vis = &item.vis;
+        let cfg_attrs: Vec<_> = item
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .collect();
+        let accessor_attrs: Vec<_> = item
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg") || attr.path().is_ident("doc"))
+            .collect();
+        let accessor = syn::Ident::new(&name.to_string().to_lowercase(), name.span());
+
+        decls.push(quote! {
+            extern "C" {
+                #(#cfg_attrs)*
+                pub static #name: #ty;
+            }
+        });
+        accessors.push(quote! {
+            #(#accessor_attrs)*
+            #[inline]
+            #vis fn #accessor() -> &'static #ty {
+                // SAFETY: the Zig code exports the data as a `const`, so it is
+                // initialized before main and never written
+                unsafe { &#mod_name::#name }
+            }
+        });
+    }
+
+    (quote! { #(#decls)* }, quote! { #(#accessors)* })
+}
+
+// ============================================================================
+// Phase 3: Generics and Async Support
+// ============================================================================
+
+/// Phase 3: Generate FFI declarations and wrappers with monomorphization
+/// support
+fn generate_with_monomorphization(
+    config: &AutoZigConfig,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut all_ffi_decls = Vec::new();
+    let mut all_wrappers = Vec::new();
+    // Generated Zig error set enums: (name, errors, definition)
+    let mut error_enums = Vec::new();
+    let lowerings = load_type_lowerings();
+
+    for rust_sig in &config.rust_signatures {
+        let (ffi_mark, wrapper_mark) = (all_ffi_decls.len(), all_wrappers.len());
+        if rust_sig.binding_config.fallback_below.is_some()
+            && (!rust_sig.generic_params.is_empty()
+                || rust_sig.sig.generics.const_params().next().is_some()
+                || rust_sig.binding_config.time_sliced.is_some()
+                || rust_sig.is_async)
+        {
+            all_wrappers.push(
+                syn::Error::new_spanned(
+                    &rust_sig.sig,
+                    "`fallback_below` is not supported on generic, async or time-sliced functions",
+                )
+                .to_compile_error(),
+            );
+            continue;
+        }
+        if rust_sig.binding_config.isolated
+            && (!rust_sig.generic_params.is_empty()
+                || rust_sig.sig.generics.const_params().next().is_some()
+                || rust_sig.binding_config.time_sliced.is_some()
+                || rust_sig.is_async
+                || impl_trait_item(&rust_sig.sig.output, "Iterator").is_some()
+                || impl_trait_item(&rust_sig.sig.output, "Stream").is_some()
+                || passes_f16(&rust_sig.sig)
+                || autozig_parser::error_union::export_error_union(
+                    &config.zig_code,
+                    &rust_sig.sig.ident.to_string(),
+                )
+                .is_some())
+        {
+            all_wrappers.push(
+                syn::Error::new_spanned(
+                    &rust_sig.sig,
+                    "`isolated` is not supported on generic, async, time-sliced, iterator, \
+                     stream, f16 or error-union functions",
+                )
+                .to_compile_error(),
+            );
+            continue;
+        }
+        if unsupported_multiversion(rust_sig) {
+            all_wrappers.push(multiversion_error(rust_sig));
+            continue;
+        }
+        if let Some(error) = timeout_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = no_std_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = isr_safe_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = gpu_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if rust_sig.binding_config.gpu {
+            // SPIR-V module compiled by the engine, embedded instead of bound
+            all_wrappers.push(generate_gpu_kernel(rust_sig, config.namespace.as_ref()));
+        } else if rust_sig.sig.generics.const_params().next().is_some() {
+            // Const generic bound to one Zig specialization per value
+            let (const_ffi, const_wrappers) =
+                generate_const_monomorphized_versions(rust_sig, config.get_mod_name());
+            all_ffi_decls.push(const_ffi);
+            all_wrappers.push(const_wrappers);
+        } else if !rust_sig.generic_params.is_empty() && !rust_sig.monomorphize_types.is_empty() {
+            // Generic function with monomorphization attribute
+            let (mono_ffi, mono_wrappers) = generate_monomorphized_versions(
+                rust_sig,
+                config.get_mod_name(),
+                &declared_ffi_types(&config.rust_structs, &config.rust_enums),
+            );
+            all_ffi_decls.push(mono_ffi);
+            all_wrappers.push(mono_wrappers);
+        } else if let Some(budget) = rust_sig.binding_config.time_sliced {
+            // Long-running loop split into resumable steps
+            let (sliced_ffi, sliced_wrapper) =
+                generate_time_sliced_ffi_and_wrapper(rust_sig, config.get_mod_name(), budget);
+            all_ffi_decls.push(sliced_ffi);
+            all_wrappers.push(sliced_wrapper);
+        } else if let Some(item) = impl_trait_item(&rust_sig.sig.output, "Stream") {
+            // `-> impl Stream<Item = T>` fed by a Zig producer on a blocking thread
+            let (stream_ffi, stream_wrapper) =
+                generate_stream_ffi_and_wrapper(rust_sig, config.get_mod_name(), item);
+            all_ffi_decls.push(stream_ffi);
+            all_wrappers.push(stream_wrapper);
+        } else if rust_sig.is_async {
+            // Async function
+            let (async_ffi, async_wrapper) =
+                generate_async_ffi_and_wrapper(rust_sig, config.get_mod_name());
+            all_ffi_decls.push(async_ffi);
+            all_wrappers.push(async_wrapper);
+        } else if let Some((lowered_ffi, lowered_wrapper)) =
+            generate_type_lowering_ffi_and_wrapper(rust_sig, config.get_mod_name(), &lowerings)
+        {
+            // Domain types lowered by rules registered in build.rs
+            all_ffi_decls.push(lowered_ffi);
+            all_wrappers.push(lowered_wrapper);
+        } else if let Some(item) = impl_trait_item(&rust_sig.sig.output, "Iterator") {
+            // `-> impl Iterator<Item = T>` over a Zig cursor
+            let (iter_ffi, iter_wrapper) =
+                generate_iterator_ffi_and_wrapper(rust_sig, config.get_mod_name(), item);
+            all_ffi_decls.push(iter_ffi);
+            all_wrappers.push(iter_wrapper);
+        } else if let Some(error_union) = autozig_parser::error_union::export_error_union(
+            &config.zig_code,
+            &rust_sig.sig.ident.to_string(),
+        ) {
+            // Zig `E!T`, lowered by the engine to AutoZigResult(T, u16)
+            let (eu_ffi, eu_wrapper) = generate_error_union_ffi_and_wrapper(
+                rust_sig,
+                config,
+                error_union,
+                &mut error_enums,
+            );
+            all_ffi_decls.push(eu_ffi);
+            all_wrappers.push(eu_wrapper);
+        } else if passes_f16(&rust_sig.sig) {
+            // f16 by value, lowered by the engine to u16 bits
+            let (f16_ffi, f16_wrapper) =
+                generate_f16_ffi_and_wrapper(rust_sig, config.get_mod_name());
+            all_ffi_decls.push(f16_ffi);
+            all_wrappers.push(f16_wrapper);
+        } else {
+            // Regular function (non-generic, non-async)
+            let mut ffi_decl = generate_single_ffi_declaration(rust_sig);
+            if rust_sig.binding_config.multiversion {
+                ffi_decl = generate_multiversion_dispatch(ffi_decl);
+            }
+            let wrapper = generate_single_safe_wrapper(rust_sig, config.get_mod_name());
+            all_ffi_decls.push(ffi_decl);
+            all_wrappers.push(wrapper);
+        }
+        // A `#[cfg]` on the signature applies to everything generated for it
+        apply_cfg_attrs(&mut all_ffi_decls[ffi_mark..], &rust_sig.cfg_attrs);
+        apply_cfg_attrs(&mut all_wrappers[wrapper_mark..], &rust_sig.cfg_attrs);
+        apply_doc_attrs(&mut all_wrappers[wrapper_mark..], rust_sig);
+        apply_raw_pointer_unsafety(&mut all_wrappers[wrapper_mark..], rust_sig);
+        apply_isr_safe_attrs(&mut all_wrappers[wrapper_mark..], rust_sig);
+    }
+
+    let error_enums = error_enums.into_iter().map(|(_, _, tokens)| tokens);
+    let ffi_decls = quote! { #(#all_ffi_decls)* };
+    let wrappers = quote! {
+        #(#error_enums)*
+        #(#all_wrappers)*
+    };
+
+    (ffi_decls, wrappers)
+}
+
+/// Whether `#[autozig(multiversion)]` is set on a function that is not
+/// plain: only plain functions get a dispatching FFI declaration
+fn unsupported_multiversion(rust_sig: &autozig_parser::RustFunctionSignature) -> bool {
+    let config = &rust_sig.binding_config;
+    config.multiversion
+        && (!rust_sig.generic_params.is_empty()
+            || rust_sig.sig.generics.const_params().next().is_some()
+            || rust_sig.is_async
+            || rust_sig.needs_abi_lowering
+            || config.time_sliced.is_some()
+            || config.fallback_below.is_some()
+            || config.isolated
+            || impl_trait_item(&rust_sig.sig.output, "Iterator").is_some()
+            || impl_trait_item(&rust_sig.sig.output, "Stream").is_some()
+            || passes_f16(&rust_sig.sig))
+}
+
+/// Error for `#[autozig(timeout_ms = N)]` on anything but a plain async
+/// function run by tokio or the autozig pool
+fn timeout_error(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) -> Option<proc_macro2::TokenStream> {
+    let config = &rust_sig.binding_config;
+    config.timeout_ms?;
+    let supported = rust_sig.is_async
+        && rust_sig.sig.generics.params.is_empty()
+        && config.time_sliced.is_none()
+        && matches!(config.runtime.as_deref(), None | Some("tokio" | "autozig"));
+    (!supported).then(|| {
+        syn::Error::new_spanned(
+            &rust_sig.sig,
+            "`timeout_ms` is only supported on non-generic async functions using the tokio or \
+             autozig runtime, without `time_sliced`",
+        )
+        .to_compile_error()
+    })
+}
+
+/// Error for functions whose wrappers need std, without the `std` feature
+///
+/// Async functions must name their own spawner with `#[autozig(runtime =
+/// "path")]`, since tokio, async-std, smol and the autozig pool all need
+/// threads.
+fn no_std_error(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) -> Option<proc_macro2::TokenStream> {
+    if std_enabled() {
+        return None;
+    }
+    let config = &rust_sig.binding_config;
+    let reason = if rust_sig.is_async
+        && config.time_sliced.is_none()
+        && matches!(
+            config.runtime.as_deref(),
+            None | Some("tokio" | "autozig" | "async-std" | "smol")
+        ) {
+        "async functions need `#[autozig(runtime = \"path::to::spawn_blocking\")]` without the \
+         `std` feature"
+    } else if impl_trait_item(&rust_sig.sig.output, "Stream").is_some() {
+        "`impl Stream` results need tokio and are not available without the `std` feature"
+    } else if config.timeout_ms.is_some() || config.isolated || config.multiversion {
+        "`timeout_ms`, `isolated` and `multiversion` need std and are not available without the \
+         `std` feature"
+    } else {
+        return None;
+    };
+    Some(syn::Error::new_spanned(&rust_sig.sig, reason).to_compile_error())
+}
+
+/// Error for an `#[autozig(isr_safe)]` function whose wrapper would
+/// allocate or block, or for `link_section` without `isr_safe`
+fn isr_safe_error(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) -> Option<proc_macro2::TokenStream> {
+    let config = &rust_sig.binding_config;
+    if !config.isr_safe {
+        return config.link_section.as_ref().map(|_| {
+            syn::Error::new_spanned(&rust_sig.sig, "`link_section` requires `isr_safe`")
+                .to_compile_error()
+        });
+    }
+    if rust_sig.is_async
+        || config.time_sliced.is_some()
+        || config.timeout_ms.is_some()
+        || config.isolated
+        || config.multiversion
+        || impl_trait_item(&rust_sig.sig.output, "Iterator").is_some()
+        || impl_trait_item(&rust_sig.sig.output, "Stream").is_some()
+    {
+        return Some(
+            syn::Error::new_spanned(
+                &rust_sig.sig,
+                "`isr_safe` functions cannot be async, time-sliced, isolated or multiversion, or \
+                 return iterators or streams: their wrappers allocate or block",
+            )
+            .to_compile_error(),
+        );
+    }
+    let params = rust_sig.sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(pat_type) => Some(&*pat_type.ty),
+        syn::FnArg::Receiver(_) => None,
+    });
+    let output = match &rust_sig.sig.output {
+        syn::ReturnType::Type(_, ty) => Some(&**ty),
+        syn::ReturnType::Default => None,
+    };
+    params.chain(output).find_map(|ty| {
+        let name = allocating_type(ty)?;
+        Some(
+            syn::Error::new_spanned(
+                ty,
+                format!(
+                    "`isr_safe` functions cannot take or return `{name}`, which allocates; use \
+                     slices or fixed-size arrays"
+                ),
+            )
+            .to_compile_error(),
+        )
+    })
+}
+
+/// Owned types whose conversion or drop allocates or frees
+const ALLOCATING_TYPES: &[&str] = &[
+    "Vec",
+    "String",
+    "Box",
+    "HashMap",
+    "BTreeMap",
+    "ZigString",
+    "ZigBuffer",
+    "ZigBox",
+    "MapBuilder",
+    "ZeroCopyBuffer",
+    "RawVec",
+];
+
+/// First type in `ty` (looking through references, slices, arrays, tuples
+/// and generic arguments) in [`ALLOCATING_TYPES`]
+fn allocating_type(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Reference(reference) => allocating_type(&reference.elem),
+        syn::Type::Slice(slice) => allocating_type(&slice.elem),
+        syn::Type::Array(array) => allocating_type(&array.elem),
+        syn::Type::Paren(paren) => allocating_type(&paren.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().find_map(allocating_type),
+        syn::Type::Path(type_path) => {
+            let segment = type_path.path.segments.last()?;
+            let name = segment.ident.to_string();
+            if ALLOCATING_TYPES.contains(&name.as_str()) {
+                return Some(name);
+            }
+            let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return None;
+            };
+            args.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => allocating_type(ty),
+                _ => None,
+            })
+        },
+        _ => None,
+    }
+}
+
+/// Error for an `#[autozig(gpu)]` function that is not a plain kernel over
+/// storage buffers, or for `workgroup_size` without `gpu`
+fn gpu_error(rust_sig: &autozig_parser::RustFunctionSignature) -> Option<proc_macro2::TokenStream> {
+    let config = &rust_sig.binding_config;
+    if !config.gpu {
+        return config.workgroup_size.map(|_| {
+            syn::Error::new_spanned(&rust_sig.sig, "`workgroup_size` requires `gpu`")
+                .to_compile_error()
+        });
+    }
+    if config.workgroup_size == Some(0) {
+        return Some(
+            syn::Error::new_spanned(&rust_sig.sig, "`workgroup_size` must be at least 1")
+                .to_compile_error(),
+        );
+    }
+    if rust_sig.is_async
+        || !rust_sig.sig.generics.params.is_empty()
+        || config.time_sliced.is_some()
+        || config.fallback_below.is_some()
+        || config.timeout_ms.is_some()
+        || config.isolated
+        || config.multiversion
+        || config.isr_safe
+    {
+        return Some(
+            syn::Error::new_spanned(
+                &rust_sig.sig,
+                "`gpu` kernels cannot be async or generic, or combined with `time_sliced`, \
+                 `fallback_below`, `timeout_ms`, `isolated`, `multiversion` or `isr_safe`",
+            )
+            .to_compile_error(),
+        );
+    }
+    autozig_parser::gpu::storage_buffers(&rust_sig.sig)
+        .err()
+        .map(|error| error.to_compile_error())
+}
+
+/// `pub const <NAME>: autozig::gpu::ComputeKernel` embedding the SPIR-V
+/// module the engine compiled for an `#[autozig(gpu)]` function
+fn generate_gpu_kernel(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    namespace: Option<&syn::Ident>,
+) -> proc_macro2::TokenStream {
+    let name = rust_sig.sig.ident.to_string();
+    let symbol = match namespace {
+        Some(namespace) => namespaced_symbol(&namespace.to_string(), &name),
+        None => name.clone(),
+    };
+    let spirv = format!("/{}/{symbol}.spv", autozig_parser::gpu::SPIRV_DIR);
+    let workgroup_size = rust_sig
+        .binding_config
+        .workgroup_size
+        .unwrap_or(autozig_parser::gpu::DEFAULT_WORKGROUP_SIZE);
+    // Checked by `gpu_error`
+    let buffers = autozig_parser::gpu::storage_buffers(&rust_sig.sig).unwrap_or_default();
+    let bindings = buffers.iter().enumerate().map(|(index, buffer)| {
+        let buffer_name = buffer.name.to_string();
+        let binding = index as u32;
+        let read_only = buffer.read_only;
+        let element = &buffer.element;
+        quote! {
+            ::autozig::gpu::StorageBinding {
+                name: #buffer_name,
+                group: 0,
+                binding: #binding,
+                read_only: #read_only,
+                element_size: ::core::mem::size_of::<#element>(),
+            }
+        }
+    });
+    let const_name =
+        quote::format_ident!("{}", name.to_uppercase(), span = rust_sig.sig.ident.span());
+    let docs = &rust_sig.doc_attrs;
+
+    quote! {
+        #(#docs)*
+        pub const #const_name: ::autozig::gpu::ComputeKernel = ::autozig::gpu::ComputeKernel {
+            entry_point: #symbol,
+            spirv: include_bytes!(concat!(env!("OUT_DIR"), #spirv)),
+            workgroup_size: [#workgroup_size, 1, 1],
+            bindings: &[#(#bindings),*],
+        };
+    }
+}
+
+fn multiversion_error(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) -> proc_macro2::TokenStream {
+    syn::Error::new_spanned(
+        &rust_sig.sig,
+        "`multiversion` is only supported on non-generic, synchronous functions returning \
+         primitives, without `time_sliced`, `fallback_below` or `isolated`",
+    )
+    .to_compile_error()
+}
+
+/// Replace the `extern "C"` declaration of a `#[autozig(multiversion)]`
+/// function with one declaration per CPU variant and a function of the same
+/// name calling the best variant the running CPU supports
+///
+/// The variant is selected once, on the first call.
+fn generate_multiversion_dispatch(ffi_decl: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let Ok(foreign) = syn::parse2::<syn::ItemForeignMod>(ffi_decl.clone()) else {
+        return ffi_decl;
+    };
+    let Some(syn::ForeignItem::Fn(item)) = foreign.items.first() else {
+        return ffi_decl;
+    };
+    let fn_name = &item.sig.ident;
+    let inputs = &item.sig.inputs;
+    let output = &item.sig.output;
+    let (arg_names, arg_types): (Vec<_>, Vec<_>) = inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => Some((&pat_type.pat, &pat_type.ty)),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .unzip();
+
+    let name = fn_name.to_string();
+    let baseline = quote::format_ident!("{}__baseline", fn_name);
+    let mut variant_decls = Vec::new();
+    let mut selections = Vec::new();
+    for variant in autozig_parser::multiversion::CPU_VARIANTS {
+        let symbol =
+            quote::format_ident!("{}", autozig_parser::multiversion::symbol(&name, variant));
+        let arch = variant.arch;
+        let detected = variant.features.iter().map(|feature| match arch {
+            "aarch64" => quote! { ::std::arch::is_aarch64_feature_detected!(#feature) },
+            _ => quote! { ::std::is_x86_feature_detected!(#feature) },
+        });
+        variant_decls.push(quote! {
+            #[cfg(target_arch = #arch)]
+            fn #symbol(#inputs) #output;
+        });
+        selections.push(quote! {
+            #[cfg(target_arch = #arch)]
+            if #(#detected)&&* {
+                return #symbol as Variant;
+            }
+        });
+    }
+
+    quote! {
+        #[allow(non_snake_case)]
+        extern "C" {
+            #[link_name = #name]
+            fn #baseline(#inputs) #output;
+            #(#variant_decls)*
+        }
+
+        pub unsafe fn #fn_name(#inputs) #output {
+            type Variant = unsafe extern "C" fn(#(#arg_types),*) #output;
+            static SELECTED: ::std::sync::OnceLock<Variant> = ::std::sync::OnceLock::new();
+            let selected = *SELECTED.get_or_init(|| {
+                #(#selections)*
+                #baseline as Variant
+            });
+            unsafe { selected(#(#arg_names),*) }
+        }
+    }
+}
+
+/// Put `cfg_attrs` on every item in `items`
+fn apply_cfg_attrs(items: &mut [proc_macro2::TokenStream], cfg_attrs: &[syn::Attribute]) {
+    if cfg_attrs.is_empty() {
+        return;
+    }
+    for tokens in items {
+        let Ok(mut file) = syn::parse2::<syn::File>(tokens.clone()) else {
+            continue;
+        };
+        for item in &mut file.items {
+            let attrs = match item {
+                syn::Item::Const(item) => &mut item.attrs,
+                syn::Item::Enum(item) => &mut item.attrs,
+                syn::Item::Fn(item) => &mut item.attrs,
+                syn::Item::ForeignMod(item) => &mut item.attrs,
+                syn::Item::Impl(item) => &mut item.attrs,
+                syn::Item::Macro(item) => &mut item.attrs,
+                syn::Item::Mod(item) => &mut item.attrs,
+                syn::Item::Static(item) => &mut item.attrs,
+                syn::Item::Struct(item) => &mut item.attrs,
+                syn::Item::Trait(item) => &mut item.attrs,
+                syn::Item::Type(item) => &mut item.attrs,
+                syn::Item::Union(item) => &mut item.attrs,
+                syn::Item::Use(item) => &mut item.attrs,
+                _ => continue,
+            };
+            attrs.splice(0..0, cfg_attrs.iter().cloned());
+        }
+        *tokens = quote! { #file };
+    }
+}
+
+/// Put the doc comments of `rust_sig` on its wrapper in `items`, and on each
+/// monomorphized variant with a note naming the concrete type or value
+fn apply_doc_attrs(
+    items: &mut [proc_macro2::TokenStream],
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) {
+    if rust_sig.doc_attrs.is_empty() {
+        return;
+    }
+    let base = rust_sig.sig.ident.to_string();
+    let const_param = rust_sig.sig.generics.const_params().next();
+    let variants: Vec<(String, String)> = match const_param {
+        Some(param) => rust_sig
+            .monomorphize_const
+            .iter()
+            .map(|value| (format!("{base}_{value}"), format!("{} = {value}", param.ident)))
+            .collect(),
+        None => rust_sig
+            .monomorphize_instances()
+            .iter()
+            .map(|instance| {
+                let binding: Vec<_> = rust_sig
+                    .generic_params
+                    .iter()
+                    .zip(instance)
+                    .map(|(param, ty)| format!("{} = {ty}", param.name))
+                    .collect();
+                (rust_sig.monomorphized_name(instance), binding.join(", "))
+            })
+            .collect(),
+    };
+
+    for tokens in items {
+        let Ok(mut file) = syn::parse2::<syn::File>(tokens.clone()) else {
+            continue;
+        };
+        let mut documented = false;
+        for item in &mut file.items {
+            let syn::Item::Fn(item_fn) = item else {
+                continue;
+            };
+            let name = item_fn.sig.ident.to_string();
+            let mut docs = rust_sig.doc_attrs.clone();
+            if let Some((_, binding)) = variants.iter().find(|(variant, _)| *variant == name) {
+                let note = format!(" Monomorphized for `{binding}`.");
+                docs.push(syn::parse_quote!(#[doc = ""]));
+                docs.push(syn::parse_quote!(#[doc = #note]));
+                // Replaces the generic "Monomorphized wrapper" doc
+                item_fn.attrs.retain(|attr| !attr.path().is_ident("doc"));
+            } else if name != base {
+                continue;
+            }
+            item_fn.attrs.splice(0..0, docs);
+            documented = true;
+        }
+        if documented {
+            *tokens = quote! { #file };
+        }
+    }
+}
+
+/// Make the wrappers in `items` taking raw pointers `unsafe fn`s with a
+/// `# Safety` section, unless `#[autozig(assume_safe)]` keeps them safe
+///
+/// Zig dereferences the pointers, so a safe wrapper would let safe code pass
+/// dangling ones. Exports generated for a binding `strategy` are left alone.
+fn apply_raw_pointer_unsafety(
+    items: &mut [proc_macro2::TokenStream],
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) {
+    let config = &rust_sig.binding_config;
+    if config.assume_safe || config.strategy.is_some() {
+        return;
+    }
+    for tokens in items {
+        let Ok(mut file) = syn::parse2::<syn::File>(tokens.clone()) else {
+            continue;
+        };
+        let mut changed = false;
+        for item in &mut file.items {
+            let syn::Item::Fn(item_fn) = item else {
+                continue;
+            };
+            let takes_raw_pointer = item_fn.sig.inputs.iter().any(|input| {
+                matches!(input, syn::FnArg::Typed(pat_type) if has_raw_pointer(&pat_type.ty))
+            });
+            if !takes_raw_pointer || item_fn.sig.unsafety.is_some() {
+                continue;
+            }
+            item_fn.sig.unsafety = Some(Default::default());
+            // After the function's own docs, which may be empty
+            let at = item_fn
+                .attrs
+                .iter()
+                .rposition(|attr| attr.path().is_ident("doc"))
+                .map_or(0, |i| i + 1);
+            let safety: [syn::Attribute; 5] = [
+                syn::parse_quote!(#[doc = ""]),
+                syn::parse_quote!(#[doc = " # Safety"]),
+                syn::parse_quote!(#[doc = ""]),
+                syn::parse_quote!(#[doc = " Zig dereferences the raw pointer arguments: each must be valid for"]),
+                syn::parse_quote!(#[doc = " the accesses the Zig function makes, for the duration of the call."]),
+            ];
+            item_fn.attrs.splice(at..at, safety);
+            changed = true;
+        }
+        if changed {
+            *tokens = quote! { #file };
+        }
+    }
+}
+
+/// Prepare the wrappers in `items` of an `#[autozig(isr_safe)]` function for
+/// interrupt handlers
+///
+/// The `pointer-log`, `trace-ffi` and `metrics` hooks lock and allocate, so
+/// they are left out. Wrappers are inlined into the handler, or kept out of
+/// line in their `link_section` (e.g. RAM on flash-executing chips).
+fn apply_isr_safe_attrs(
+    items: &mut [proc_macro2::TokenStream],
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) {
+    let config = &rust_sig.binding_config;
+    if !config.isr_safe {
+        return;
+    }
+    let attrs: Vec<syn::Attribute> = match &config.link_section {
+        Some(section) => vec![
+            syn::parse_quote!(#[link_section = #section]),
+            syn::parse_quote!(#[inline(never)]),
+        ],
+        None => vec![syn::parse_quote!(#[inline(always)])],
+    };
+    for tokens in items {
+        let Ok(mut file) = syn::parse2::<syn::File>(tokens.clone()) else {
+            continue;
+        };
+        for item in &mut file.items {
+            if let syn::Item::Fn(item_fn) = item {
+                strip_call_hooks(&mut item_fn.block);
+                item_fn.attrs.extend(attrs.iter().cloned());
+            }
+        }
+        *tokens = quote! { #file };
+    }
+}
+
+/// Remove the `pointer-log`, `trace-ffi` and `metrics` statements from a
+/// wrapper body and the functions nested in it
+fn strip_call_hooks(block: &mut syn::Block) {
+    block.stmts.retain(|stmt| {
+        let stmt = quote!(#stmt).to_string();
+        !(stmt.starts_with(":: autozig :: pointer_log ::")
+            || stmt.starts_with("let __autozig_span")
+            || stmt.starts_with("let __autozig_timer"))
+    });
+    for stmt in &mut block.stmts {
+        if let syn::Stmt::Item(syn::Item::Fn(item_fn)) = stmt {
+            strip_call_hooks(&mut item_fn.block);
+        }
+    }
+}
+
+/// Whether `ty` is or contains a reference (`&str`, `Option<&T>`,
+/// `[&T; N]`); function pointers are not looked into
+fn has_reference(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(_) => true,
+        syn::Type::Array(array) => has_reference(&array.elem),
+        syn::Type::Slice(slice) => has_reference(&slice.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().any(has_reference),
+        syn::Type::Paren(paren) => has_reference(&paren.elem),
+        syn::Type::Group(group) => has_reference(&group.elem),
+        syn::Type::Path(path) => path.path.segments.iter().any(|segment| {
+            matches!(&segment.arguments, syn::PathArguments::AngleBracketed(args)
+                if args.args.iter().any(|arg| matches!(arg, syn::GenericArgument::Type(ty) if has_reference(ty))))
+        }),
+        _ => false,
+    }
+}
+
+/// Whether `ty` is or contains a raw pointer (`*const T`, `Option<*mut T>`,
+/// `[*mut T; N]`); function pointers and references are not looked into
+fn has_raw_pointer(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Ptr(_) => true,
+        syn::Type::Array(array) => has_raw_pointer(&array.elem),
+        syn::Type::Slice(slice) => has_raw_pointer(&slice.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().any(has_raw_pointer),
+        syn::Type::Paren(paren) => has_raw_pointer(&paren.elem),
+        syn::Type::Group(group) => has_raw_pointer(&group.elem),
+        syn::Type::Path(path) => path.path.segments.iter().any(|segment| {
+            matches!(&segment.arguments, syn::PathArguments::AngleBracketed(args)
+                if args.args.iter().any(|arg| matches!(arg, syn::GenericArgument::Type(ty) if has_raw_pointer(ty))))
+        }),
+        _ => false,
+    }
+}
+
+/// Bindings for a Zig export returning an error union `E!T`
+///
+/// The declared `Result<T, E>` (or `Result<T, Traced<E>>`, which adds the
+/// Zig stack trace) travels as `Result<T, u16>`; `E` is generated
+/// as an enum mirroring the Zig error set (once per name, collected in
+/// `error_enums`) unless the block declares it.
+fn generate_error_union_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    config: &AutoZigConfig,
+    error_union: Result<autozig_parser::error_union::ErrorUnion, String>,
+    error_enums: &mut Vec<(String, Vec<String>, proc_macro2::TokenStream)>,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    let error_union = match error_union {
+        Ok(error_union) => error_union,
+        Err(reason) => {
+            return (quote! {}, syn::Error::new_spanned(sig, reason).to_compile_error());
+        },
+    };
+    // `Traced<E>` attaches the Zig error return trace to `E`
+    let (err_ident, traced) = match is_result_return_type(&sig.output) {
+        Some((_, syn::Type::Path(err))) if err.path.get_ident().is_some() => {
+            (err.path.get_ident().cloned(), false)
+        },
+        Some((_, err)) => match traced_error(err) {
+            Some(inner) => (Some(inner), true),
+            None => (None, false),
+        },
+        _ => (None, false),
+    };
+    let Some(err_ident) = err_ident else {
+        return (
+            quote! {},
+            syn::Error::new_spanned(
+                sig,
+                format!(
+                    "`{}` returns a Zig error union; declare it as `-> Result<T, ErrorEnum>` or \
+                     `-> Result<T, Traced<ErrorEnum>>`",
+                    sig.ident
+                ),
+            )
+            .to_compile_error(),
+        );
+    };
+    let (ok, _) = is_result_return_type(&sig.output).expect("checked above");
+
+    let err_name = err_ident.to_string();
+    let user_declared = config.rust_enums.iter().any(|e| e.item.ident == err_ident);
+    match error_enums.iter().find(|(name, _, _)| *name == err_name) {
+        Some((_, errors, _)) if *errors != error_union.errors => {
+            return (
+                quote! {},
+                syn::Error::new_spanned(
+                    sig,
+                    format!("`{err_name}` is used for Zig error sets with different errors"),
+                )
+                .to_compile_error(),
+            );
+        },
+        Some(_) => {},
+        None if user_declared => {},
+        None => error_enums.push((
+            err_name,
+            error_union.errors.clone(),
+            generate_error_enum(&err_ident, &error_union.errors),
+        )),
+    }
+
+    // Bind the lowered export as `Result<T, u16>` and map the code back
+    let mut lowered_sig = rust_sig.clone();
+    lowered_sig.sig.output = syn::parse_quote! { -> Result<#ok, u16> };
+    let ffi_decl = generate_single_ffi_declaration(&lowered_sig);
+    let lowered_wrapper = generate_single_safe_wrapper(&lowered_sig, config.get_mod_name());
+
+    let fn_name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let params = inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+            syn::Pat::Ident(ident) => Some(&ident.ident),
+            _ => None,
+        },
+        syn::FnArg::Receiver(_) => None,
+    });
+    let map_err = if traced {
+        quote! { |code| ::autozig::zig_trace::Traced::new(#err_ident::from_code(code)) }
+    } else {
+        quote! { #err_ident::from_code }
+    };
+    let wrapper = quote! {
+        pub fn #fn_name(#inputs) #output {
+            #lowered_wrapper
+
+            #fn_name(#(#params),*).map_err(#map_err)
+        }
+    };
+    (ffi_decl, wrapper)
+}
+
+/// `E` of an error type written `Traced<E>` (any path ending in `Traced`)
+fn traced_error(ty: &syn::Type) -> Option<syn::Ident> {
+    match generic_type_args(ty, "Traced")?.as_slice() {
+        [syn::Type::Path(inner)] => inner.path.get_ident().cloned(),
+        _ => None,
+    }
+}
+
+/// Lowering rules registered in build.rs, found through `AUTOZIG_LOWERINGS`
+fn load_type_lowerings() -> Vec<autozig_parser::lowering::TypeLowering> {
+    std::env::var("AUTOZIG_LOWERINGS")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| autozig_parser::lowering::parse_lowerings(&text))
+        .unwrap_or_default()
+}
+
+/// Bindings for a function passing registered domain types by value
+///
+/// Returns `None` if no parameter or return type has a lowering rule. The
+/// Zig export is bound with the FFI types; the wrapper converts arguments
+/// with `to_ffi` and the result with `from_ffi`.
+fn generate_type_lowering_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+    lowerings: &[autozig_parser::lowering::TypeLowering],
+) -> Option<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let find = |ty: &syn::Type| {
+        let ty = quote!(#ty).to_string().replace(' ', "");
+        lowerings
+            .iter()
+            .find(|lowering| lowering.rust_type.replace(' ', "") == ty)
+    };
+    let sig = &rust_sig.sig;
+    let lowers_return = matches!(&sig.output, syn::ReturnType::Type(_, ty) if find(ty).is_some());
+    let lowers_param = sig
+        .inputs
+        .iter()
+        .any(|input| matches!(input, syn::FnArg::Typed(pat_type) if find(&pat_type.ty).is_some()));
+    if !lowers_return && !lowers_param {
+        return None;
+    }
+
+    let invalid = |lowering: &autozig_parser::lowering::TypeLowering| {
+        let message = format!(
+            "invalid lowering rule for `{}`: `{}` must be a type and `{}` / `{}` paths",
+            lowering.rust_type, lowering.ffi_type, lowering.to_ffi, lowering.from_ffi
+        );
+        Some((quote! {}, syn::Error::new_spanned(sig, message).to_compile_error()))
+    };
+
+    let fn_name = &sig.ident;
+    let mut lowered_sig = rust_sig.clone();
+    let mut args = Vec::new();
+    for input in &mut lowered_sig.sig.inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            continue;
+        };
+        let name = &ident.ident;
+        match find(&pat_type.ty) {
+            Some(lowering) => {
+                let (Ok(ffi_type), Ok(to_ffi)) = (
+                    syn::parse_str::<syn::Type>(&lowering.ffi_type),
+                    syn::parse_str::<syn::Path>(&lowering.to_ffi),
+                ) else {
+                    return invalid(lowering);
+                };
+                *pat_type.ty = ffi_type;
+                args.push(quote! { #to_ffi(#name) });
+            },
+            None => args.push(quote! { #name }),
+        }
+    }
+    let mut call = quote! { #fn_name(#(#args),*) };
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        if let Some(lowering) = find(ty) {
+            let (Ok(ffi_type), Ok(from_ffi)) = (
+                syn::parse_str::<syn::Type>(&lowering.ffi_type),
+                syn::parse_str::<syn::Path>(&lowering.from_ffi),
+            ) else {
+                return invalid(lowering);
+            };
+            lowered_sig.needs_abi_lowering = !autozig_parser::is_safe_primitive(&ffi_type);
+            lowered_sig.sig.output = syn::parse_quote! { -> #ffi_type };
+            call = quote! { #from_ffi(#call) };
+        }
+    }
+    let ffi_decl = generate_single_ffi_declaration(&lowered_sig);
+    let lowered_wrapper = generate_single_safe_wrapper(&lowered_sig, mod_name);
+
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let wrapper = quote! {
+        pub fn #fn_name(#inputs) #output {
+            #lowered_wrapper
+
+            #call
+        }
+    };
+    Some((ffi_decl, wrapper))
+}
+
+/// `T` of a `-> impl Trait<Item = T>` return type, e.g. for `Iterator`
+fn impl_trait_item<'a>(output: &'a syn::ReturnType, trait_name: &str) -> Option<&'a syn::Type> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    let syn::Type::ImplTrait(impl_trait) = &**ty else {
+        return None;
+    };
+    impl_trait.bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last()?;
+        if segment.ident != trait_name {
+            return None;
+        }
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::AssocType(assoc) if assoc.ident == "Item" => Some(&assoc.ty),
+            _ => None,
+        })
+    })
+}
+
+/// Bindings for `fn name(..) -> impl Iterator<Item = T>` over a Zig cursor
+///
+/// The Zig side provides three exports:
+///
+/// - `name(..) ?*anyopaque` creates the cursor,
+/// - `name_next(cursor: *anyopaque, out: *T) bool` writes the next item and
+///   returns `false` when exhausted,
+/// - `name_free(cursor: *anyopaque) void` releases it.
+///
+/// The wrapper returns a hidden cursor type implementing `Iterator` and
+/// `Drop`, borrowing the reference arguments for as long as it lives.
+fn generate_iterator_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+    item: &syn::Type,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    if !sig.generics.params.is_empty() {
+        return (
+            quote! {},
+            syn::Error::new_spanned(
+                &sig.generics,
+                "`impl Iterator` returns are not supported on generic functions",
+            )
+            .to_compile_error(),
+        );
+    }
+    let fn_name = &sig.ident;
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+    let next_fn = syn::Ident::new(&format!("{fn_name}_next"), fn_name.span());
+    let free_fn = syn::Ident::new(&format!("{fn_name}_free"), fn_name.span());
+
+    // The export creating the cursor, bound as returning a raw pointer
+    let mut lowered_sig = rust_sig.clone();
+    lowered_sig.sig.output = syn::parse_quote! { -> *mut ::core::ffi::c_void };
+    lowered_sig.needs_abi_lowering = false;
+    let create_decl = generate_single_ffi_declaration(&lowered_sig);
+    let lowered_wrapper = generate_single_safe_wrapper(&lowered_sig, mod_name);
+    let ffi_decl = quote! {
+        #create_decl
+        extern "C" {
+            pub fn #next_fn(cursor: *mut ::core::ffi::c_void, out: *mut #item) -> bool;
+            pub fn #free_fn(cursor: *mut ::core::ffi::c_void);
+        }
+    };
+
+    // Tie elided reference lifetimes to the returned iterator
+    let mut inputs = sig.inputs.clone();
+    for input in &mut inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Type::Reference(reference) = &mut *pat_type.ty {
+                if reference.lifetime.is_none() {
+                    reference.lifetime = Some(syn::parse_quote! { '__autozig });
+                }
+            }
+        }
+    }
+    let params = sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+            syn::Pat::Ident(ident) => Some(&ident.ident),
+            _ => None,
+        },
+        syn::FnArg::Receiver(_) => None,
+    });
+    let null_msg = format!("Zig returned a null cursor from `{fn_name}`");
+
+    let wrapper = quote! {
+        pub fn #fn_name<'__autozig>(#inputs) -> impl Iterator<Item = #item> + '__autozig {
+            #lowered_wrapper
+
+            struct Cursor<'a> {
+                ptr: ::core::ptr::NonNull<::core::ffi::c_void>,
+                _borrow: ::core::marker::PhantomData<&'a ()>,
+            }
+
+            impl Iterator for Cursor<'_> {
+                type Item = #item;
+
+                fn next(&mut self) -> Option<#item> {
+                    let mut item = ::core::mem::MaybeUninit::<#item>::uninit();
+                    if unsafe { #mod_ident::#next_fn(self.ptr.as_ptr(), item.as_mut_ptr()) } {
+                        Some(unsafe { item.assume_init() })
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl Drop for Cursor<'_> {
+                fn drop(&mut self) {
+                    unsafe { #mod_ident::#free_fn(self.ptr.as_ptr()) }
+                }
+            }
+
+            let ptr = ::core::ptr::NonNull::new(#fn_name(#(#params),*)).expect(#null_msg);
+            Cursor { ptr, _borrow: ::core::marker::PhantomData }
+        }
+    };
+    (ffi_decl, wrapper)
+}
+
+/// Bindings for `[async] fn name(..) -> impl Stream<Item = T>` over a Zig
+/// producer
+///
+/// Zig exports `name(.., emit_ctx: *anyopaque, emit_fn: *const fn
+/// (*anyopaque, ..) callconv(.c) bool) void` and calls `emit_fn` for every
+/// item until it returns `false` (the stream was dropped) or the producer is
+/// done. For `Item = Vec<E>` each call passes one chunk as `[*]const E,
+/// usize`; any other item type is passed by value. The wrapper runs the
+/// export on a blocking thread via `autozig::stream::from_producer`, copying
+/// borrowed arguments so the stream is `'static`.
+fn generate_stream_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+    item: &syn::Type,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    if !sig.generics.params.is_empty() {
+        return (
+            quote! {},
+            syn::Error::new_spanned(
+                &sig.generics,
+                "`impl Stream` returns are not supported on generic functions",
+            )
+            .to_compile_error(),
+        );
+    }
+    let fn_name = &sig.ident;
+
+    // Arguments outlive the call: references are copied into owned values
+    let mut captures = Vec::new();
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            continue;
+        };
+        let name = &ident.ident;
+        match &*pat_type.ty {
+            syn::Type::Reference(reference) if reference.mutability.is_some() => {
+                return (
+                    quote! {},
+                    syn::Error::new_spanned(
+                        input,
+                        "`impl Stream` functions cannot take `&mut` arguments; the producer \
+                         outlives the call",
+                    )
+                    .to_compile_error(),
+                );
+            },
+            syn::Type::Reference(_) => {
+                captures.push(quote! { let #name = ::std::borrow::ToOwned::to_owned(#name); });
+                args.push(quote! { &#name });
+            },
+            _ => args.push(quote! { #name }),
+        }
+    }
+
+    // Chunks of `Vec<E>` cross as slices, other items by value
+    let (emit_arg, forward) = match vec_elem_type(item) {
+        Some(elem) => {
+            (quote! { &[#elem] }, quote! { |chunk: &[#elem]| __autozig_emit(chunk.to_vec()) })
+        },
+        None => (quote! { #item }, quote! { |item: #item| __autozig_emit(item) }),
+    };
+
+    let mut producer_sig = rust_sig.clone();
+    producer_sig.sig.asyncness = None;
+    producer_sig
+        .sig
+        .inputs
+        .push(syn::parse_quote! { __autozig_emit: impl FnMut(#emit_arg) -> bool });
+    producer_sig.sig.output = syn::ReturnType::Default;
+    producer_sig.is_async = false;
+    producer_sig.needs_abi_lowering = false;
+    let ffi_decl = generate_single_ffi_declaration(&producer_sig);
+    let producer_wrapper = generate_single_safe_wrapper(&producer_sig, mod_name);
+
+    let inputs = &sig.inputs;
+    let wrapper = quote! {
+        pub fn #fn_name(#inputs) -> impl ::autozig::stream::Stream<Item = #item> + Send + 'static {
+            #producer_wrapper
+
+            #(#captures)*
+            ::autozig::stream::from_producer(
+                move |__autozig_emit: &mut dyn FnMut(#item) -> bool| {
+                    #fn_name(#(#args,)* #forward)
+                },
+            )
+        }
+    };
+    (ffi_decl, wrapper)
+}
+
+/// `E` of `Vec<E>`
+fn vec_elem_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Vec" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(elem) => Some(elem),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Slice Zig borrows from an owned `Vec<T>` (`&mut [T]`) or `String` (`&str`)
+/// argument of an async function, which the wrapper hands back after the call
+fn owned_buffer_borrow(ty: &syn::Type) -> Option<syn::Type> {
+    if let Some(elem) = vec_elem_type(ty) {
+        return Some(syn::parse_quote! { &mut [#elem] });
+    }
+    match ty {
+        syn::Type::Path(type_path)
+            if type_path
+                .path
+                .segments
+                .last()
+                .is_some_and(|s| s.ident == "String") =>
+        {
+            Some(syn::parse_quote! { &str })
+        },
+        _ => None,
+    }
+}
+
+/// `half::f16` (or the primitive `f16`) passed by value
+fn is_f16_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "f16" && segment.arguments.is_none()),
+        _ => false,
+    }
+}
+
+/// Whether a signature takes or returns `f16` by value
+fn passes_f16(sig: &syn::Signature) -> bool {
+    let returns_f16 = matches!(&sig.output, syn::ReturnType::Type(_, ty) if is_f16_type(ty));
+    returns_f16
+        || sig.inputs.iter().any(|input| match input {
+            syn::FnArg::Typed(pat_type) => is_f16_type(&pat_type.ty),
+            syn::FnArg::Receiver(_) => false,
+        })
+}
+
+/// Bindings for a Zig export taking or returning `f16` by value
+///
+/// The engine lowers the export to `u16` bit patterns (C ABIs pass `f16` in
+/// float registers, `half::f16` in integer ones); the wrapper converts with
+/// `to_bits` / `from_bits`.
+fn generate_f16_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let mut lowered_sig = rust_sig.clone();
+    let mut args = Vec::new();
+    for input in &mut lowered_sig.sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            let syn::Pat::Ident(ident) = &*pat_type.pat else {
+                continue;
+            };
+            let name = &ident.ident;
+            if is_f16_type(&pat_type.ty) {
+                *pat_type.ty = syn::parse_quote! { u16 };
+                args.push(quote! { #name.to_bits() });
+            } else {
+                args.push(quote! { #name });
+            }
+        }
+    }
+    let mut call = quote! { #fn_name(#(#args),*) };
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        if is_f16_type(ty) {
+            lowered_sig.sig.output = syn::parse_quote! { -> u16 };
+            call = quote! { <#ty>::from_bits(#call) };
+        }
+    }
+    let ffi_decl = generate_single_ffi_declaration(&lowered_sig);
+    let lowered_wrapper = generate_single_safe_wrapper(&lowered_sig, mod_name);
+
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let wrapper = quote! {
+        pub fn #fn_name(#inputs) #output {
+            #lowered_wrapper
+
+            #call
+        }
+    };
+    (ffi_decl, wrapper)
+}
+
+/// Rust enum mirroring a Zig error set, with the codes used by the engine
+fn generate_error_enum(name: &syn::Ident, errors: &[String]) -> proc_macro2::TokenStream {
+    let variants: Vec<syn::Ident> = errors
+        .iter()
+        .map(|error| syn::Ident::new(error, proc_macro2::Span::call_site()))
+        .collect();
+    let codes: Vec<u16> = (1..=errors.len() as u16).collect();
+    let doc = format!(" Errors of the Zig error set `error{{{}}}`", errors.join(", "));
+    // `core::error::Error` is newer than the crate's MSRV, so std crates keep
+    // the std trait
+    let error_impl = if !std_enabled() {
+        quote! { impl ::core::error::Error for #name {} }
+    } else {
+        quote! { impl ::std::error::Error for #name {} }
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u16)]
+        pub enum #name {
+            #(#variants = #codes,)*
+        }
+
+        impl #name {
+            /// Error for a code returned by the lowered Zig function
+            pub fn from_code(code: u16) -> Self {
+                match code {
+                    #(#codes => Self::#variants,)*
+                    _ => unreachable!("unknown Zig error code {}", code),
+                }
+            }
+
+            /// Name of the error in Zig (without `error.`)
+            pub fn name(self) -> &'static str {
+                match self {
+                    #(Self::#variants => #errors,)*
+                }
+            }
+        }
+
+        impl ::core::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                write!(f, "error.{}", self.name())
+            }
+        }
+
+        #error_impl
+    }
+}
+
+/// Wrapper running the call in a forked child process
+/// (`#[autozig(isolated)]`)
+///
+/// The declared return type `T` becomes `Result<T, Crashed>`. Writes through
+/// `&mut` arguments and closure calls would happen in the child only, and
+/// references or pointers returned by the child would dangle in the parent,
+/// so all are rejected. The hooks lock and allocate, so they run in the
+/// parent and the child calls a wrapper without them.
+fn generate_isolated_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> proc_macro2::TokenStream {
+    let sig = &rust_sig.sig;
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            let is_mut_ref =
+                matches!(&*pat_type.ty, syn::Type::Reference(r) if r.mutability.is_some());
+            if is_mut_ref || is_callback_param(&pat_type.ty).is_some() {
+                return syn::Error::new_spanned(
+                    input,
+                    "`#[autozig(isolated)]` functions run in a child process and cannot write \
+                     through `&mut` arguments or call closures",
+                )
+                .to_compile_error();
+            }
+        }
+    }
+
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        if has_raw_pointer(ty) || has_reference(ty) {
+            return syn::Error::new_spanned(
+                ty,
+                "`#[autozig(isolated)]` functions cannot return references or pointers, which \
+                 would point into the child process",
+            )
+            .to_compile_error();
+        }
+    }
+
+    let mut inner_sig = rust_sig.clone();
+    inner_sig.binding_config.isolated = false;
+    let inner_wrapper = generate_single_safe_wrapper(&inner_sig, mod_name);
+    let inner_wrapper = match syn::parse2::<syn::ItemFn>(inner_wrapper.clone()) {
+        Ok(mut inner_fn) => {
+            strip_call_hooks(&mut inner_fn.block);
+            quote! { #inner_fn }
+        },
+        Err(_) => inner_wrapper,
+    };
+
+    let fn_name = &sig.ident;
+    let inputs = &sig.inputs;
+    let hooks = pointer_arg_hooks(fn_name, inputs);
+    let guards = call_guards(fn_name, inputs);
+    let ret = match &sig.output {
+        syn::ReturnType::Default => quote! { () },
+        syn::ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    let params = inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+            syn::Pat::Ident(ident) => Some(&ident.ident),
+            _ => None,
+        },
+        syn::FnArg::Receiver(_) => None,
+    });
+    quote! {
+        pub fn #fn_name(#inputs) -> ::core::result::Result<#ret, ::autozig::isolate::Crashed> {
+            #inner_wrapper
+
+            #(#hooks)*
+            #guards
+            // SAFETY: the return type holds no references or pointers, and the
+            // child calls Zig without the hooks
+            unsafe { ::autozig::isolate::run(|| #fn_name(#(#params),*)) }
+        }
+    }
+}
+
+/// Generate single FFI declaration for regular (non-generic) function
+fn generate_single_ffi_declaration(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+) -> proc_macro2::TokenStream {
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let output = &sig.output;
+
+    // Check if this function returns an array - if so, parameters need pointer
+    // conversion
+    let has_array_return = is_array_return_type(output).is_some();
+
+    let mut ffi_params = Vec::new();
+
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            let param_type = &pat_type.ty;
+            let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                ident.ident.to_string()
+            } else {
+                continue;
+            };
+
+            if let Some(callback) = is_callback_param(param_type) {
+                // Closure -> (ctx, trampoline) pair
+                ffi_params.extend(callback_ffi_params(&param_name_str, &callback));
+            } else if let Some(elem) = is_growable_vec_ref(param_type) {
+                let param_name = &pat_type.pat;
+                ffi_params
+                    .push(quote! { #param_name: *mut ::autozig::ffi_types::ZigGrowable<#elem> });
+            } else if is_map_view(param_type) {
+                let param_name = &pat_type.pat;
+                ffi_params.push(quote! { #param_name: *mut #param_type });
+            } else if let Some((is_mut, elem_type)) = ptr_len_param(param_type) {
+                let ptr_type = if let Some(elem) = elem_type {
+                    if is_mut {
+                        quote! { *mut #elem }
+                    } else {
+                        quote! { *const #elem }
+                    }
+                } else if is_mut {
+                    quote! { *mut u8 }
+                } else {
+                    quote! { *const u8 }
+                };
+
+                let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
+                let len_name = quote::format_ident!("{}_len", param_name_str);
+
+                ffi_params.push(quote! { #ptr_name: #ptr_type });
+                ffi_params.push(quote! { #len_name: usize });
+            } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type) {
+                // NEW: Mutable array &mut [T; N] -> *mut T
+                let param_name = &pat_type.pat;
+                let ptr_type = quote! { *mut #elem_type };
+                ffi_params.push(quote! { #param_name: #ptr_type });
+            } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
+                // NEW: Fixed array [T; N] -> *const [N]T
+                // This is backward compatible - only triggers for [T; N] types
+                let param_name = &pat_type.pat;
+                let ptr_type = quote! { *const #param_type };
+                ffi_params.push(quote! { #param_name: #ptr_type });
+            } else if has_array_return && is_struct_type(param_type) {
+                // CRITICAL FIX: For array returns, Engine converts struct params to pointers
+                // This matches Engine's convert_params_to_ptrs behavior
+                let param_name = &pat_type.pat;
+                let ptr_type = quote! { *const #param_type };
+                ffi_params.push(quote! { #param_name: #ptr_type });
+            } else {
+                let param_name = &pat_type.pat;
+                ffi_params.push(quote! { #param_name: #param_type });
+            }
+        }
+    }
+
+    // Check if this function needs ABI lowering
+    if rust_sig.needs_abi_lowering {
+        // Generate FFI declaration for the out-pointer version
+        let out_fn_name =
+            syn::Ident::new(&format!("{}__autozig_out", fn_name), proc_macro2::Span::call_site());
+
+        // Rebuild FFI params with struct types converted to pointers
+        let mut abi_ffi_params = Vec::new();
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(pat_type) = input {
+                let param_name = &pat_type.pat;
+                let param_type = &pat_type.ty;
+
+                if let Some(callback) = is_callback_param(param_type) {
+                    if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                        abi_ffi_params
+                            .extend(callback_ffi_params(&ident.ident.to_string(), &callback));
+                    }
+                } else if let Some(elem) = is_growable_vec_ref(param_type) {
+                    abi_ffi_params.push(
+                        quote! { #param_name: *mut ::autozig::ffi_types::ZigGrowable<#elem> },
+                    );
+                } else if is_map_view(param_type) {
+                    abi_ffi_params.push(quote! { #param_name: *mut #param_type });
+                } else if let Some((is_mut, elem_type)) = ptr_len_param(param_type) {
+                    // Slices and strings are passed as ptr + len, same as the wrapper args
+                    let elem = elem_type.map_or_else(|| quote! { u8 }, |elem| quote! { #elem });
+                    let ptr_type = if is_mut {
+                        quote! { *mut #elem }
+                    } else {
+                        quote! { *const #elem }
+                    };
+                    if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                        let ptr_name = quote::format_ident!("{}_ptr", ident.ident);
+                        let len_name = quote::format_ident!("{}_len", ident.ident);
+                        abi_ffi_params.push(quote! { #ptr_name: #ptr_type });
+                        abi_ffi_params.push(quote! { #len_name: usize });
+                    }
+                } else if is_struct_type(param_type) {
+                    // Convert struct types to *const StructType
+                    abi_ffi_params.push(quote! { #param_name: *const #param_type });
+                } else {
+                    abi_ffi_params.push(quote! { #param_name: #param_type });
+                }
+            }
+        }
+
+        // Zig writes the return value through a trailing *mut ReturnType
+        // (Result<T, E> -> *mut ZigResult<T, E>)
+        if let syn::ReturnType::Type(_, ty) = output {
+            let lowered = lowered_return_type(ty, output);
+            abi_ffi_params.push(quote! { __autozig_out: *mut #lowered });
+        }
+
+        return quote! {
+            extern "C" {
+                pub fn #out_fn_name(#(#abi_ffi_params),*);
+            }
+        };
+    }
+
+    // Check if return type is an array - FFI should return pointer
+    let ffi_output = if let Some(builder) = is_map_builder_return_type(output) {
+        // Map built by Zig through an out parameter
+        ffi_params.push(quote! { __autozig_out: *mut #builder });
+        syn::ReturnType::Default
+    } else if let Some(ptr_type) = is_option_ptr_return_type(output) {
+        // Option<*mut T>: FFI returns the nullable pointer itself
+        syn::parse_quote! { -> #ptr_type }
+    } else if let Some((_elem_type, _size_expr)) = is_array_return_type(output) {
+        // Array return: FFI returns *const [T; N]
+        if let syn::ReturnType::Type(arrow, ty) = output {
+            syn::ReturnType::Type(
+                *arrow,
+                Box::new(syn::Type::Ptr(syn::TypePtr {
+                    star_token: syn::Token![*](proc_macro2::Span::call_site()),
+                    const_token: Some(syn::Token![const](proc_macro2::Span::call_site())),
+                    mutability: None,
+                    elem: ty.clone(),
+                })),
+            )
+        } else {
+            output.clone()
+        }
+    } else {
+        output.clone()
+    };
+
+    quote! {
+        extern "C" {
+            pub fn #fn_name(#(#ffi_params),*) #ffi_output;
+        }
+    }
+}
+
+/// Generate single safe wrapper for regular (non-generic) function
+fn generate_single_safe_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> proc_macro2::TokenStream {
+    // Check if this function has AutoZig binding configuration
+    let config = &rust_sig.binding_config;
+
+    // If there's binding config, generate dual exports (wasm-bindgen + C-style)
+    if config.strategy.is_some() {
+        return generate_dual_binding_wrappers(rust_sig, mod_name);
+    }
+
+    // Zig keeps the arguments: document the contract on the wrapper and reject
+    // closures, whose trampoline context only lives for the duration of the call
+    if config.retains {
+        if let Some(callback) = rust_sig.sig.inputs.iter().find(|input| {
+            matches!(input, syn::FnArg::Typed(pat_type) if is_callback_param(&pat_type.ty).is_some())
+        }) {
+            return syn::Error::new_spanned(
+                callback,
+                "closure callbacks cannot be passed to a `#[autozig(retains)]` function; use an \
+                 `extern \"C\" fn` instead",
+            )
+            .to_compile_error();
+        }
+
+        let mut plain_sig = rust_sig.clone();
+        plain_sig.binding_config.retains = false;
+        let wrapper = generate_single_safe_wrapper(&plain_sig, mod_name);
+        return quote! {
+            #[doc = ""]
+            #[doc = " # Safety contract"]
+            #[doc = ""]
+            #[doc = " Zig retains pointers passed to this function after it returns. Every"]
+            #[doc = " borrowed argument must outlive all later calls into the library; never pass"]
+            #[doc = " references to temporaries."]
+            #wrapper
+        };
+    }
+
+    // The call runs in a forked child; a crash there becomes `Err(Crashed)`
+    if config.isolated {
+        return generate_isolated_wrapper(rust_sig, mod_name);
+    }
+
+    // Small inputs run the Rust body, larger ones the Zig kernel
+    if let Some(threshold) = config.fallback_below {
+        let mut zig_sig = rust_sig.clone();
+        zig_sig.binding_config.fallback_below = None;
+        let zig_wrapper = generate_single_safe_wrapper(&zig_sig, mod_name);
+        return generate_adaptive_wrapper(rust_sig, threshold, zig_wrapper);
+    }
+
+    // Otherwise, use original single wrapper generation
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+
+    // Check if this function returns an array - if so, struct params need pointer
+    // conversion
+    let has_array_return = is_array_return_type(output).is_some();
+
+    let mut ffi_args = Vec::new();
+    // Trampolines, closure bindings and growable views that must precede the FFI
+    // call
+    let mut callback_preludes = pointer_arg_hooks(fn_name, inputs);
+    callback_preludes.push(call_guards(fn_name, inputs));
+    let log_return = pointer_log_return(fn_name, output);
+
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                let param_type = &pat_type.ty;
+
+                if let Some(callback) = is_callback_param(param_type) {
+                    let (prelude, args) = callback_ffi_args(param_name, &callback);
+                    callback_preludes.push(prelude);
+                    ffi_args.extend(args);
+                } else if is_growable_vec_ref(param_type).is_some() {
+                    // The view commits Zig's appends to the Vec when dropped
+                    let growable_name = quote::format_ident!("__autozig_{}_growable", param_name);
+                    callback_preludes.push(quote! {
+                        let mut #growable_name = ::autozig::ffi_types::ZigGrowable::new(#param_name);
+                    });
+                    ffi_args.push(quote! { &mut #growable_name });
+                } else if is_map_view(param_type) {
+                    let view_name = quote::format_ident!("__autozig_{}_view", param_name);
+                    callback_preludes.push(quote! {
+                        let mut #view_name = #param_name;
+                    });
+                    ffi_args.push(quote! { &mut #view_name });
+                } else if let Some((is_mut, _elem_type)) = ptr_len_param(param_type) {
+                    if is_mut {
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else {
+                        ffi_args.push(quote! { #param_name.as_ptr() });
+                    }
+                    ffi_args.push(quote! { #param_name.len() });
+                } else if is_mut_fixed_array_ref(param_type).is_some() {
+                    // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
+                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                } else if is_fixed_array(param_type).is_some() {
+                    // NEW: Fixed array [T; N] -> pass &param
+                    // This is backward compatible - only triggers for [T; N] types
+                    ffi_args.push(quote! { &#param_name });
+                } else if has_array_return && is_struct_type(param_type) {
+                    // CRITICAL FIX: For array returns, pass struct params as pointers
+                    // This matches Engine's behavior where struct params become *const StructType
+                    ffi_args.push(quote! { &#param_name });
+                } else {
+                    ffi_args.push(quote! { #param_name });
+                }
+            }
+        }
+    }
+
+    // Check if this function needs ABI lowering (struct return)
+    if rust_sig.needs_abi_lowering {
+        // Rebuild ffi_args with ONLY struct types converted to pointers
+        // Other types (arrays, slices, primitives) keep their original handling
+        let mut abi_ffi_args = Vec::new();
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(pat_type) = input {
+                if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                    let param_name = &ident.ident;
+                    let param_type = &pat_type.ty;
+
+                    // Only struct types get pointer conversion
+                    // Arrays, slices, and primitives use original handling
+                    if let Some(callback) = is_callback_param(param_type) {
+                        // Preludes were already collected in the first pass
+                        let (_, args) = callback_ffi_args(param_name, &callback);
+                        abi_ffi_args.extend(args);
+                    } else if is_growable_vec_ref(param_type).is_some() {
+                        let growable_name =
+                            quote::format_ident!("__autozig_{}_growable", param_name);
+                        abi_ffi_args.push(quote! { &mut #growable_name });
+                    } else if is_map_view(param_type) {
+                        let view_name = quote::format_ident!("__autozig_{}_view", param_name);
+                        abi_ffi_args.push(quote! { &mut #view_name });
+                    } else if is_struct_type(param_type) && is_fixed_array(param_type).is_none() {
+                        // Pass struct by pointer: &param
+                        abi_ffi_args.push(quote! { &#param_name });
+                    } else if let Some((is_mut, _elem_type)) = ptr_len_param(param_type) {
+                        if is_mut {
+                            abi_ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                        } else {
+                            abi_ffi_args.push(quote! { #param_name.as_ptr() });
+                        }
+                        abi_ffi_args.push(quote! { #param_name.len() });
+                    } else if is_mut_fixed_array_ref(param_type).is_some() {
+                        abi_ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else if is_fixed_array(param_type).is_some() {
+                        // Fixed arrays: pass as pointer reference for FFI (same as normal path)
+                        abi_ffi_args.push(quote! { &#param_name });
+                    } else {
+                        abi_ffi_args.push(quote! { #param_name });
+                    }
+                }
+            }
+        }
+        // Generate ABI-safe wrapper using pointer-based call
+        return generate_abi_lowered_wrapper(
+            fn_name,
+            inputs,
+            output,
+            &abi_ffi_args,
+            &callback_preludes,
+            &mod_ident,
+        );
+    }
+
+    // Check if return type is an array
+    let wrapper_body = if let Some(builder) = is_map_builder_return_type(output) {
+        // Zig inserts the entries through an out parameter
+        quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#callback_preludes)*
+                let mut builder = <#builder>::new();
+                unsafe { #mod_ident::#fn_name(#(#ffi_args,)* &mut builder) };
+                builder
+            }
+        }
+    } else if let Some((_elem_type, _size_expr)) = is_array_return_type(output) {
+        // Array return: need to dereference pointer and read value
+        quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#callback_preludes)*
+                unsafe {
+                    let ptr = #mod_ident::#fn_name(#(#ffi_args),*);
+                    // Dereference the pointer to get the array value
+                    *ptr
+                }
+            }
+        }
+    } else if is_option_ptr_return_type(output).is_some() {
+        // Nullable pointer return: null -> None
+        quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#callback_preludes)*
+                let ret = unsafe { #mod_ident::#fn_name(#(#ffi_args),*) };
+                #log_return
+                if ret.is_null() {
+                    None
+                } else {
+                    Some(ret)
+                }
+            }
+        }
+    } else if !log_return.is_empty() {
+        // Raw pointer return
+        quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#callback_preludes)*
+                let ret = unsafe { #mod_ident::#fn_name(#(#ffi_args),*) };
+                #log_return
+                ret
+            }
+        }
+    } else {
+        // Normal return
+        quote! {
+            pub fn #fn_name(#inputs) #output {
+                #(#callback_preludes)*
+                unsafe {
+                    #mod_ident::#fn_name(#(#ffi_args),*)
+                }
+            }
+        }
+    };
+
+    wrapper_body
+}
+
+/// Wrap `zig_wrapper` so inputs shorter than `threshold` run the function's
+/// Rust body instead
+///
+/// The input size is the length of the longest slice or string argument.
+/// The Zig wrapper becomes an item inside the dispatching function, which
+/// calls it under the same name.
+fn generate_adaptive_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    threshold: usize,
+    zig_wrapper: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+
+    let Some(body) = &rust_sig.rust_fallback else {
+        return syn::Error::new_spanned(
+            sig,
+            "`fallback_below` needs a Rust implementation: give the function a body instead of `;`",
+        )
+        .to_compile_error();
+    };
+
+    let mut params = Vec::new();
+    let mut lengths = Vec::new();
+    for input in inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                params.push(param_name);
+                if ptr_len_param(&pat_type.ty).is_some() {
+                    lengths.push(quote! { #param_name.len() });
+                }
+            }
+        }
+    }
+    let Some(first_len) = lengths.first() else {
+        return syn::Error::new_spanned(
+            sig,
+            "`fallback_below` measures the input by its slice or string arguments, but this \
+             function has none",
+        )
+        .to_compile_error();
+    };
+    let other_lens = &lengths[1..];
+
+    quote! {
+        pub fn #fn_name(#inputs) #output {
+            #zig_wrapper
+
+            let __autozig_input_len = #first_len #(.max(#other_lens))*;
+            if __autozig_input_len < #threshold #body else { #fn_name(#(#params),*) }
+        }
+    }
+}
+
+/// Generate dual binding wrappers (wasm-bindgen + C-style export)
+/// This is the core of the AutoZig dual export feature
+fn generate_dual_binding_wrappers(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> proc_macro2::TokenStream {
+    let config = &rust_sig.binding_config;
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+
+    // Get strategy (default: "dual")
+    let strategy = config.strategy.as_deref().unwrap_or("dual");
+
+    // Get prefixes
+    let prefix_bindgen = config.prefix_bindgen.as_deref().unwrap_or("wasm_");
+    let prefix_c = config.prefix_c.as_deref().unwrap_or("wasm64_");
+
+    // Build FFI call arguments
+    let mut ffi_args = Vec::new();
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                let param_type = &pat_type.ty;
+
+                if let Some((is_mut, _elem_type)) = ptr_len_param(param_type) {
+                    if is_mut {
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else {
+                        ffi_args.push(quote! { #param_name.as_ptr() });
+                    }
+                    ffi_args.push(quote! { #param_name.len() });
+                } else if is_mut_fixed_array_ref(param_type).is_some() {
+                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                } else if is_fixed_array(param_type).is_some() {
+                    ffi_args.push(quote! { &#param_name });
+                } else {
+                    ffi_args.push(quote! { #param_name });
+                }
+            }
+        }
+    }
+
+    let mut wrappers = Vec::new();
+
+    // Generate wasm-bindgen wrapper
+    if strategy == "dual" || strategy == "bindgen" {
+        let export_name = quote::format_ident!("{}{}", prefix_bindgen, fn_name);
+        wrappers.push(quote! {
+            #[cfg(not(target_family = "wasm"))]
+            #[wasm_bindgen::prelude::wasm_bindgen]
+            pub fn #export_name(#inputs) #output {
+                unsafe {
+                    #mod_ident::#fn_name(#(#ffi_args),*)
+                }
+            }
+        });
+    }
+
+    // Generate C-style export wrapper
+    if strategy == "dual" || strategy == "c_only" {
+        let export_name = quote::format_ident!("{}{}", prefix_c, fn_name);
+
+        // Handle return type conversion if specified
+        let (ret_type, body) = if let (Some(c_ret), Some(map_fn)) = (&config.c_ret, &config.map_fn)
+        {
+            // With type mapping
+            (
+                quote! { -> #c_ret },
+                quote! {
+                    let res = unsafe { #mod_ident::#fn_name(#(#ffi_args),*) };
+                    let mapper = #map_fn;
+                    mapper(res)
+                },
+            )
+        } else {
+            // Direct pass-through
+            (
+                quote! { #output },
+                quote! {
+                    unsafe { #mod_ident::#fn_name(#(#ffi_args),*) }
+                },
+            )
+        };
+
+        wrappers.push(quote! {
+            #[cfg(not(target_family = "wasm"))]
+            #[no_mangle]
+            pub extern "C" fn #export_name(#inputs) #ret_type {
+                #body
+            }
+        });
+    }
+
+    quote! {
+        #(#wrappers)*
+    }
+}
+
+/// Generate ABI-lowered wrapper using MaybeUninit + out-pointer call
+/// This ensures cross-platform ABI compatibility for struct returns
+fn generate_abi_lowered_wrapper(
+    fn_name: &syn::Ident,
+    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
+    output: &syn::ReturnType,
+    ffi_args: &[proc_macro2::TokenStream],
+    callback_preludes: &[proc_macro2::TokenStream],
+    mod_ident: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    // Extract return type
+    let return_type = match output {
+        syn::ReturnType::Type(_, ty) => ty,
+        syn::ReturnType::Default => {
+            // Should not happen for needs_abi_lowering, but fallback
+            return quote! {
+                pub fn #fn_name(#inputs) #output {
+                    #(#callback_preludes)*
+                    unsafe {
+                        #mod_ident::#fn_name(#(#ffi_args),*)
+                    }
+                }
+            };
+        },
+    };
+
+    // Generate wrapper name for the out-pointer version
+    let out_fn_name =
+        syn::Ident::new(&format!("{}__autozig_out", fn_name), proc_macro2::Span::call_site());
+
+    // Result<T, E> travels as ZigResult<T, E> and is converted back on return
+    let ffi_return_type = lowered_return_type(return_type, output);
+    let convert = lowered_return_conversion(output);
+
+    quote! {
+        pub fn #fn_name(#inputs) #output {
+            #(#callback_preludes)*
+            unsafe {
+                // Use MaybeUninit for uninitialized stack allocation
+                let mut result = core::mem::MaybeUninit::<#ffi_return_type>::uninit();
+
+                // Zig writes the result straight into our stack allocation
+                #mod_ident::#out_fn_name(#(#ffi_args,)* result.as_mut_ptr());
+
+                // Assume initialized and return
+                result.assume_init()#convert
+            }
+        }
+    }
+}
+
+/// Names of the `#[repr(..)]` structs and enums declared in a block, which
+/// `#[monomorphize]` accepts besides primitives
+fn declared_ffi_types(
+    structs: &[autozig_parser::RustStructDefinition],
+    enums: &[autozig_parser::RustEnumDefinition],
+) -> Vec<String> {
+    let has_repr = |attrs: &[syn::Attribute]| attrs.iter().any(|attr| attr.path().is_ident("repr"));
+    structs
+        .iter()
+        .filter(|s| has_repr(&s.item.attrs))
+        .map(|s| s.item.ident.to_string())
+        .chain(
+            enums
+                .iter()
+                .filter(|e| has_repr(&e.item.attrs))
+                .map(|e| e.item.ident.to_string()),
+        )
+        .collect()
+}
+
+/// Whether `ty` is a primitive passed to Zig as is (`i32`, `f64`, `bool`,
+/// `c_int`, `core::ffi::c_int`, ...)
+fn is_ffi_primitive(ty: &syn::Type) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+        "f32", "f64", "bool",
+    ];
+    const C_TYPES: &[&str] = &[
+        "c_char",
+        "c_schar",
+        "c_uchar",
+        "c_short",
+        "c_ushort",
+        "c_int",
+        "c_uint",
+        "c_long",
+        "c_ulong",
+        "c_longlong",
+        "c_ulonglong",
+        "c_float",
+        "c_double",
+    ];
+    const C_TYPE_MODULES: &[&str] = &["", "core::ffi", "std::ffi", "std::os::raw", "libc"];
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+    if path.qself.is_some() {
+        return false;
+    }
+    let full = path_string(&path.path);
+    let (module, name) = full.rsplit_once("::").unwrap_or(("", &full));
+    (module.is_empty() && PRIMITIVES.contains(&name))
+        || (C_TYPES.contains(&name) && C_TYPE_MODULES.contains(&module))
+}
+
+/// Segments of `path` joined by `::`, without a leading `::` or generic
+/// arguments (`::core::ffi::c_int` -> `core::ffi::c_int`)
+fn path_string(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Phase 3: Generate monomorphized versions for a generic function
+///
+/// Every concrete type must be FFI-safe: a primitive, one of the `#[repr]`
+/// types in `declared` (generic ones instantiated with FFI-safe types), or,
+/// when its parameter is bounded by `ZigPod`, a type implementing
+/// `autozig::ZigPod`. The declared bounds are checked for each type at
+/// compile time. With several type parameters each instance lists one type
+/// per parameter and is named after all of them (`convert_u8_f32`).
+fn generate_monomorphized_versions(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+    declared: &[String],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut ffi_decls = Vec::new();
+    let mut wrappers = Vec::new();
+
+    let error = |message: String| syn::Error::new_spanned(&rust_sig.sig.generics, message);
+    let type_params: Vec<_> = rust_sig.sig.generics.type_params().collect();
+    let is_zig_pod = |path: &syn::Path| {
+        ["ZigPod", "autozig::ZigPod", "autozig::ffi_types::ZigPod"]
+            .contains(&path_string(path).as_str())
+    };
+    // Trait bounds of each parameter, inline and in the where clause
+    let param_bounds: Vec<Vec<&syn::Path>> = type_params
+        .iter()
+        .map(|param| {
+            let where_bounds = rust_sig
+                .sig
+                .generics
+                .where_clause
+                .iter()
+                .flat_map(|clause| &clause.predicates)
+                .filter_map(move |predicate| match predicate {
+                    syn::WherePredicate::Type(predicate)
+                        if matches!(&predicate.bounded_ty, syn::Type::Path(ty)
+                            if ty.qself.is_none() && ty.path.is_ident(&param.ident)) =>
+                    {
+                        Some(&predicate.bounds)
+                    },
+                    _ => None,
+                })
+                .flatten();
+            param
+                .bounds
+                .iter()
+                .chain(where_bounds)
+                .filter_map(|bound| match bound {
+                    syn::TypeParamBound::Trait(bound) => Some(&bound.path),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let instances = rust_sig.monomorphize_instances();
+
+    // Every type of every instance is checked, and all errors are reported
+    let mut errors = Vec::new();
+    for instance in &instances {
+        if instance.len() != type_params.len() {
+            errors.push(error(format!(
+                "`#[monomorphize]` instance `({})` needs one type for each of the {} type \
+                 parameters",
+                instance.join(", "),
+                type_params.len()
+            )));
+            continue;
+        }
+        for ((mono_type, type_param), bounds) in
+            instance.iter().zip(&type_params).zip(&param_bounds)
+        {
+            let param_name = &type_param.ident;
+            let pod_bound = bounds.iter().any(|path| is_zig_pod(path));
+
+            let Ok(ty) = syn::parse_str::<syn::Type>(mono_type) else {
+                errors.push(error(format!(
+                    "`#[monomorphize]` type `{mono_type}` is not a Rust type"
+                )));
+                continue;
+            };
+            let allowed = is_monomorphizable(&ty, declared);
+            if !allowed && !pod_bound {
+                errors.push(error(format!(
+                    "`#[monomorphize]` type `{mono_type}` is not FFI-safe: use a primitive or a \
+                     #[repr(C)] type declared in this block, or bound `{param_name}: ZigPod` and \
+                     implement `autozig::ZigPod` for it"
+                )));
+                continue;
+            }
+            for bound in bounds {
+                // Allowlisted types need no ZigPod impl
+                let bound = match (is_zig_pod(bound), allowed) {
+                    (true, true) => continue,
+                    (true, false) => quote! { ::autozig::ZigPod },
+                    (false, _) => quote! { #bound },
+                };
+                wrappers.push(quote! {
+                    const _: () = {
+                        fn assert_bound<T: #bound + ?Sized>() {}
+                        let _ = assert_bound::<#ty>;
+                    };
+                });
+            }
+        }
+    }
+    if !errors.is_empty() {
+        let errors = errors.iter().map(syn::Error::to_compile_error);
+        return (quote! {}, quote! { #(#errors)* });
+    }
+
+    for instance in &instances {
+        // Generate mangled name: convert<Src, Dst> + (u8, f32) -> convert_u8_f32
+        let mono_name =
+            syn::Ident::new(&rust_sig.monomorphized_name(instance), proc_macro2::Span::call_site());
+
+        // Substitute the type parameters with the concrete types
+        let bindings: Vec<_> = type_params
+            .iter()
+            .map(|param| param.ident.to_string())
+            .zip(instance.iter().map(String::as_str))
+            .collect();
+        let mono_sig = substitute_generic_types(&rust_sig.sig, &bindings);
+
+        // Generate FFI declaration for this monomorphized version
+        let ffi_decl = generate_ffi_declaration_from_sig(&mono_name, &mono_sig);
+        ffi_decls.push(ffi_decl);
+
+        // Generate safe wrapper for this monomorphized version
+        let wrapper = generate_wrapper_from_sig(&mono_name, &mono_sig, mod_name);
+        wrappers.push(wrapper);
+    }
+
+    let ffi_output = quote! { #(#ffi_decls)* };
+    let wrapper_output = quote! { #(#wrappers)* };
+
+    (ffi_output, wrapper_output)
+}
+
+/// Whether `ty` can be monomorphized without a `ZigPod` bound: a primitive,
+/// or a type declared in the block whose generic arguments are such types
+/// (`Matrix<f32>`)
+fn is_monomorphizable(ty: &syn::Type, declared: &[String]) -> bool {
+    if is_ffi_primitive(ty) {
+        return true;
+    }
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+    // Declared in this block: `Point`, not `other::Point`
+    let [segment] = path.path.segments.iter().collect::<Vec<_>>()[..] else {
+        return false;
+    };
+    if path.qself.is_some()
+        || path.path.leading_colon.is_some()
+        || !declared.contains(&segment.ident.to_string())
+    {
+        return false;
+    }
+    match &segment.arguments {
+        syn::PathArguments::None => true,
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().all(|arg| match arg {
+            syn::GenericArgument::Type(ty) => is_monomorphizable(ty, declared),
+            syn::GenericArgument::Const(_) => true,
+            _ => false,
+        }),
+        syn::PathArguments::Parenthesized(_) => false,
+    }
+}
+
+/// Bindings for a const generic function with `#[monomorphize_const(..)]`
+///
+/// Each value gets a regular binding `name_value` for the Zig export the
+/// engine generates, and `name::<VALUE>` dispatches to it. Values without a
+/// binding fail to compile when the function is instantiated with them.
+fn generate_const_monomorphized_versions(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    let const_params: Vec<_> = sig.generics.const_params().collect();
+    let error =
+        |message: &str| (quote! {}, syn::Error::new_spanned(sig, message).to_compile_error());
+    if const_params.len() != 1 || sig.generics.type_params().next().is_some() {
+        return error(
+            "`monomorphize_const` supports functions with exactly one const generic parameter",
+        );
+    }
+    if rust_sig.is_async {
+        return error("`monomorphize_const` is not supported on async functions");
+    }
+    if rust_sig.monomorphize_const.is_empty() {
+        return error(
+            "const generic functions need #[monomorphize_const(...)] listing the values to bind",
+        );
+    }
+    let const_ident = &const_params[0].ident;
+
+    let mut ffi_decls = Vec::new();
+    let mut wrappers = Vec::new();
+    let mut arms = Vec::new();
+    let mut values = Vec::new();
+    for value in &rust_sig.monomorphize_const {
+        let lit = match syn::parse_str::<syn::Lit>(value) {
+            Ok(lit @ (syn::Lit::Int(_) | syn::Lit::Bool(_))) => lit,
+            _ => {
+                return error(&format!(
+                    "`monomorphize_const` value `{value}` is not an integer or bool literal"
+                ))
+            },
+        };
+        let name = quote::format_ident!("{}_{}", sig.ident, value);
+
+        let mut non_generic = sig.clone();
+        non_generic.ident = name.clone();
+        non_generic.generics = syn::Generics::default();
+        let mut specialized = rust_sig.clone();
+        specialized.sig =
+            match syn::parse2(substitute_const(quote! { #non_generic }, const_ident, &lit)) {
+                Ok(sig) => sig,
+                Err(err) => return (quote! {}, err.to_compile_error()),
+            };
+        specialized.monomorphize_const.clear();
+        ffi_decls.push(generate_single_ffi_declaration(&specialized));
+        wrappers.push(generate_single_safe_wrapper(&specialized, mod_name));
+
+        // Types mentioning the const differ from the specialization's only
+        // to the type checker: inside this arm they are the same type
+        let args = sig.inputs.iter().filter_map(|input| match input {
+            syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(ident) => {
+                    let ident = &ident.ident;
+                    Some(if mentions_ident(quote! { #pat_type }, const_ident) {
+                        same_type_cast(quote! { #ident })
+                    } else {
+                        quote! { #ident }
+                    })
+                },
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        });
+        let call = quote! { #name(#(#args),*) };
+        let call = match &sig.output {
+            syn::ReturnType::Type(_, ty) if mentions_ident(quote! { #ty }, const_ident) => {
+                same_type_cast(call)
+            },
+            _ => call,
+        };
+        arms.push(quote! { #lit => #call });
+        values.push(lit);
+    }
+
+    let fn_name = &sig.ident;
+    let generics = &sig.generics;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let const_ty = &const_params[0].ty;
+    let message = syn::LitStr::new(
+        &format!(
+            "`{fn_name}` is only bound for {const_ident} in [{}] (see #[monomorphize_const])",
+            rust_sig.monomorphize_const.join(", ")
+        ),
+        proc_macro2::Span::call_site(),
+    );
+    let dispatcher = quote! {
+        pub fn #fn_name #generics(#inputs) #output {
+            struct __AutozigBound<const #const_ident: #const_ty>;
+            impl<const #const_ident: #const_ty> __AutozigBound<#const_ident> {
+                const CHECK: () = assert!(#(#const_ident == #values)||*, #message);
+            }
+            let () = __AutozigBound::<#const_ident>::CHECK;
+            match #const_ident {
+                #(#arms,)*
+                _ => unreachable!(),
+            }
+        }
+    };
+
+    (
+        quote! { #(#ffi_decls)* },
+        quote! {
+            #(#wrappers)*
+            #dispatcher
+        },
+    )
+}
+
+/// Whether `tokens` contain the identifier `name`
+fn mentions_ident(tokens: proc_macro2::TokenStream, name: &syn::Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => ident == *name,
+        proc_macro2::TokenTree::Group(group) => mentions_ident(group.stream(), name),
+        _ => false,
+    })
+}
+
+/// Convert `expr` between two spellings of the same type, e.g. `[u8; N]` and
+/// `[u8; 4]` where `N == 4`
+fn same_type_cast(expr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote! {
+        unsafe { ::core::mem::transmute_copy(&::core::mem::ManuallyDrop::new(#expr)) }
+    }
+}
+
+/// Replace the const generic `name` by `value` in `tokens`
+fn substitute_const(
+    tokens: proc_macro2::TokenStream,
+    name: &syn::Ident,
+    value: &syn::Lit,
+) -> proc_macro2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if ident == *name => {
+                quote! { #value }
+            },
+            proc_macro2::TokenTree::Group(group) => {
+                let mut substituted = proc_macro2::Group::new(
+                    group.delimiter(),
+                    substitute_const(group.stream(), name, value),
+                );
+                substituted.set_span(group.span());
+                quote! { #substituted }
+            },
+            token => quote! { #token },
+        })
+        .collect()
+}
+
+/// Substitute the generic type parameters with concrete types, given as
+/// `(parameter, type)` pairs
+fn substitute_generic_types(sig: &syn::Signature, bindings: &[(String, &str)]) -> syn::Signature {
+    let mut new_sig = sig.clone();
+
+    // Parse concrete types
+    let bindings: Vec<(&str, syn::Type)> = bindings
+        .iter()
+        .map(|(name, ty)| {
+            let concrete_ty: syn::Type =
+                syn::parse_str(ty).unwrap_or_else(|_| panic!("Invalid type: {}", ty));
+            (name.as_str(), concrete_ty)
+        })
+        .collect();
+
+    // Remove generics from signature
+    new_sig.generics = syn::Generics::default();
+
+    // Substitute type in parameters
+    for input in &mut new_sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            *pat_type.ty = substitute_type_recursive(&pat_type.ty, &bindings);
+        }
+    }
+
+    // Substitute type in return type
+    if let syn::ReturnType::Type(_, ret_ty) = &mut new_sig.output {
+        **ret_ty = substitute_type_recursive(ret_ty, &bindings);
+    }
+
+    new_sig
+}
+
+/// Recursively substitute generic types in a type expression
+fn substitute_type_recursive(ty: &syn::Type, bindings: &[(&str, syn::Type)]) -> syn::Type {
+    match ty {
+        syn::Type::Path(type_path) => {
+            // Check if this is a generic parameter
+            if let Some((_, concrete_ty)) = bindings
+                .iter()
+                .find(|(name, _)| type_path.path.is_ident(name))
+            {
+                return concrete_ty.clone();
+            }
+            // Substitute inside generic arguments, e.g. `Matrix<T>`
+            let mut new_path = type_path.clone();
+            for segment in &mut new_path.path.segments {
+                if let syn::PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                    for arg in &mut args.args {
+                        if let syn::GenericArgument::Type(arg_ty) = arg {
+                            *arg_ty = substitute_type_recursive(arg_ty, bindings);
+                        }
+                    }
+                }
+            }
+            syn::Type::Path(new_path)
+        },
+        syn::Type::Reference(type_ref) => {
+            let mut new_ref = type_ref.clone();
+            *new_ref.elem = substitute_type_recursive(&type_ref.elem, bindings);
+            syn::Type::Reference(new_ref)
+        },
+        syn::Type::Slice(type_slice) => {
+            let mut new_slice = type_slice.clone();
+            *new_slice.elem = substitute_type_recursive(&type_slice.elem, bindings);
+            syn::Type::Slice(new_slice)
+        },
+        syn::Type::Array(type_array) => {
+            let mut new_array = type_array.clone();
+            *new_array.elem = substitute_type_recursive(&type_array.elem, bindings);
+            syn::Type::Array(new_array)
+        },
+        syn::Type::Ptr(type_ptr) => {
+            let mut new_ptr = type_ptr.clone();
+            *new_ptr.elem = substitute_type_recursive(&type_ptr.elem, bindings);
+            syn::Type::Ptr(new_ptr)
+        },
+        _ => ty.clone(),
+    }
+}
+
+/// Generate FFI declaration from signature with specific name
+fn generate_ffi_declaration_from_sig(
+    fn_name: &syn::Ident,
+    sig: &syn::Signature,
+) -> proc_macro2::TokenStream {
+    let output = &sig.output;
+
+    let mut ffi_params = Vec::new();
+
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            let param_type = &pat_type.ty;
+            let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                ident.ident.to_string()
+            } else {
+                continue;
+            };
+
+            if let Some((is_mut, elem_type)) = ptr_len_param(param_type) {
+                let ptr_type = if let Some(elem) = elem_type {
+                    if is_mut {
+                        quote! { *mut #elem }
+                    } else {
+                        quote! { *const #elem }
+                    }
+                } else if is_mut {
+                    quote! { *mut u8 }
+                } else {
+                    quote! { *const u8 }
+                };
+
+                let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
+                let len_name = quote::format_ident!("{}_len", param_name_str);
+
+                ffi_params.push(quote! { #ptr_name: #ptr_type });
+                ffi_params.push(quote! { #len_name: usize });
+            } else {
+                let param_name = &pat_type.pat;
+                ffi_params.push(quote! { #param_name: #param_type });
+            }
+        }
+    }
+
+    quote! {
+        extern "C" {
+            pub fn #fn_name(#(#ffi_params),*) #output;
+        }
+    }
+}
+
+/// Generate safe wrapper from signature with specific name
+fn generate_wrapper_from_sig(
+    fn_name: &syn::Ident,
+    sig: &syn::Signature,
+    mod_name: &str,
+) -> proc_macro2::TokenStream {
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+
+    let mut ffi_args = Vec::new();
+
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                let param_type = &pat_type.ty;
+
+                if let Some((is_mut, _elem_type)) = ptr_len_param(param_type) {
+                    if is_mut {
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else {
+                        ffi_args.push(quote! { #param_name.as_ptr() });
+                    }
+                    ffi_args.push(quote! { #param_name.len() });
+                } else {
+                    ffi_args.push(quote! { #param_name });
+                }
+            }
+        }
+    }
+
+    let mut log_args = pointer_arg_hooks(fn_name, inputs);
+    log_args.push(call_guards(fn_name, inputs));
+    let call = logged_call(
+        quote! { #mod_ident::#fn_name(#(#ffi_args),*) },
+        pointer_log_return(fn_name, output),
+    );
+    quote! {
+        /// Monomorphized wrapper (generated by autozig)
+        pub fn #fn_name(#inputs) #output {
+            #(#log_args)*
+            #call
+        }
+    }
+}
+
+/// Phase 3.2: Generate async FFI and wrapper using spawn_blocking pattern
+/// Architecture: "Rust Async Wrapper, Zig Sync Execution"
+/// - Zig writes normal synchronous code (no async/await needed in Zig)
+/// - Rust async fn uses the runtime's spawn_blocking (tokio by default, see
+///   `spawn_blocking_call`)
+/// - This prevents blocking the async runtime while maintaining async interface
+fn generate_async_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let fn_name = &rust_sig.sig.ident;
+    let sig = &rust_sig.sig;
+
+    // Generate standard synchronous FFI declaration
+    // Zig side is always synchronous - no async/await needed!
+    // Owned buffers are declared as the slices Zig sees
+    let mut ffi_sig = sig.clone();
+    for input in &mut ffi_sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let Some(borrowed) = owned_buffer_borrow(&pat_type.ty) {
+                *pat_type.ty = borrowed;
+            }
+        }
+    }
+    let ffi_decl = generate_ffi_declaration_from_sig(fn_name, &ffi_sig);
+
+    // Build wrapper parameters and FFI call arguments
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+
+    let mut ffi_args = Vec::new();
+    let mut param_captures = Vec::new();
+    // Owned buffers handed back to the caller, with their types
+    let mut returned = Vec::new();
+
+    for input in &sig.inputs {
+        if let syn::FnArg::Typed(pat_type) = input {
+            if let syn::Pat::Ident(ident) = &*pat_type.pat {
+                let param_name = &ident.ident;
+                let param_type = &pat_type.ty;
+
+                // For async, we need to move parameters into the closure
+                // For slices/strings, we need to convert to owned data
+                if let Some(borrowed) = owned_buffer_borrow(param_type) {
+                    // Zig borrows the buffer on the blocking thread, then it
+                    // moves back out of the task
+                    if matches!(is_slice_or_str_ref(&borrowed), Some((true, _))) {
+                        param_captures.push(quote! {
+                            let mut #param_name = #param_name;
+                        });
+                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
+                    } else {
+                        ffi_args.push(quote! { #param_name.as_ptr() });
+                    }
+                    ffi_args.push(quote! { #param_name.len() });
+                    returned.push((param_name, param_type));
+                } else if autozig_parser::is_shared_bytes(param_type) {
+                    // The refcounted owner moves into the closure as is, so the
+                    // buffer outlives the blocking call without a copy
+                    ffi_args.push(quote! { #param_name.as_ptr() });
+                    ffi_args.push(quote! { #param_name.len() });
+                } else if let Some((_is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
+                    // Convert slice to Vec to own the data
+                    param_captures.push(quote! {
+                        let #param_name = #param_name.to_vec();
+                    });
+
+                    ffi_args.push(quote! { #param_name.as_ptr() });
+                    ffi_args.push(quote! { #param_name.len() });
+                } else {
+                    // For Copy types, just capture them
+                    ffi_args.push(quote! { #param_name });
+                }
+            }
+        }
+    }
+
+    // Measured before the slices are copied, entered on the blocking thread
+    let span = ffi_span(fn_name, inputs);
+    let function = fn_name.to_string();
+    let call = quote! { unsafe { #mod_ident::#fn_name(#(#ffi_args),*) } };
+    // The declared result comes first, then the owned buffers in parameter
+    // order; without a declared result a single buffer is returned alone
+    let names = returned.iter().map(|(name, _)| name);
+    let types = returned.iter().map(|(_, ty)| ty);
+    let (result, output) = match (output, returned.len()) {
+        (_, 0) => (call, output.clone()),
+        (syn::ReturnType::Default, 1) => {
+            let (name, ty) = returned[0];
+            (quote! { #call; #name }, syn::parse_quote! { -> #ty })
+        },
+        (syn::ReturnType::Default, _) => {
+            (quote! { #call; (#(#names),*) }, syn::parse_quote! { -> (#(#types),*) })
+        },
+        (syn::ReturnType::Type(_, ty), _) => {
+            (quote! { (#call, #(#names),*) }, syn::parse_quote! { -> (#ty, #(#types),*) })
+        },
+    };
+    let blocking_call = quote! {
+        move || {
+            let __autozig_span = __autozig_span.entered();
+            let __autozig_timer = ::autozig::metrics::start(#function);
+            #result
+        }
+    };
+    let spawn = match spawn_blocking_call(rust_sig, blocking_call) {
+        Ok(spawn) => spawn,
+        Err(err) => return (ffi_decl, err.to_compile_error()),
+    };
+    let (spawn, output) = match rust_sig.binding_config.timeout_ms {
+        // Stop waiting after the time limit; the blocking call keeps running
+        Some(timeout_ms) => {
+            let ret = match &output {
+                syn::ReturnType::Default => quote! { () },
+                syn::ReturnType::Type(_, ty) => quote! { #ty },
+            };
+            let spawn = quote! {
+                tokio::time::timeout(
+                    ::core::time::Duration::from_millis(#timeout_ms),
+                    async { #spawn },
+                )
+                .await
+                .map_err(|_| ::autozig::AutoZigTimeout {
+                    function: #function,
+                    timeout: ::core::time::Duration::from_millis(#timeout_ms),
+                })
+            };
+            (
+                spawn,
+                syn::parse_quote! { -> ::core::result::Result<#ret, ::autozig::AutoZigTimeout> },
+            )
+        },
+        None => (spawn, output),
+    };
+
+    // Generate async wrapper using spawn_blocking
+    let wrapper = quote! {
+        /// Async wrapper (auto-generated by AutoZig Phase 3.2)
+        ///
+        /// This function uses the runtime's spawn_blocking to offload the
+        /// synchronous Zig FFI call to a dedicated thread pool, preventing
+        /// blocking of the async runtime.
+        ///
+        /// Zig side: Write normal synchronous code, no async/await needed!
+        pub async fn #fn_name(#inputs) #output {
+            let __autozig_span = #span;
+            // Capture parameters (convert slices to owned Vec)
+            #(#param_captures)*
+
+            // Offload to blocking thread pool
+            #spawn
+        }
+    };
+
+    (ffi_decl, wrapper)
+}
+
+/// Await `call` on the blocking thread pool of the function's runtime
+/// (`#[autozig(runtime = "...")]`, tokio by default)
+///
+/// Tokio functions use `autozig::runtime`'s pool instead once it is
+/// configured, and `"autozig"` always does. Any runtime other than `tokio`,
+/// `autozig`, `async-std` and `smol` names a function
+/// `fn(impl FnOnce() -> T + Send + 'static) -> impl Future<Output = T>`.
+fn spawn_blocking_call(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    call: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    Ok(match rust_sig.binding_config.runtime.as_deref() {
+        None | Some("tokio") => quote! {
+            match ::autozig::runtime::try_spawn(#call) {
+                Ok(task) => task.await,
+                Err(call) => tokio::task::spawn_blocking(call)
+                    .await
+                    .expect("Zig task panicked or was cancelled"),
+            }
+        },
+        Some("autozig") => quote! { ::autozig::runtime::spawn(#call).await },
+        Some("async-std") => quote! { async_std::task::spawn_blocking(#call).await },
+        Some("smol") => quote! { smol::unblock(#call).await },
+        Some(custom) => {
+            let spawner: syn::Path = syn::parse_str(custom).map_err(|_| {
+                syn::Error::new_spanned(
+                    &rust_sig.sig,
+                    format!(
+                        "unknown runtime `{custom}`: use \"tokio\", \"autozig\", \"async-std\", \
+                         \"smol\" or the path of a spawn_blocking function"
+                    ),
+                )
+            })?;
+            quote! { #spawner(#call).await }
+        },
+    })
+}
+
+/// Generate the task type and blocking wrapper of a
+/// `#[autozig(time_sliced = N)]` function
+///
+/// Zig implements the function as three exports sharing a state handle:
+/// `<name>_begin(args) ?*anyopaque` sets up the loop, `<name>_step(state,
+/// budget) bool` runs at most `budget` iterations and returns `true` once
+/// done, and `<name>_finish(state) R` frees the state and returns the result.
+fn generate_time_sliced_ffi_and_wrapper(
+    rust_sig: &autozig_parser::RustFunctionSignature,
+    mod_name: &str,
+    budget: u32,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let sig = &rust_sig.sig;
+    let fn_name = &sig.ident;
+    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+
+    // The arguments are consumed by `_begin`, while the loop runs over several
+    // calls: anything borrowed would have to outlive the task
+    let borrowed = sig.inputs.iter().find(|input| match input {
+        syn::FnArg::Typed(pat_type) => matches!(&*pat_type.ty, syn::Type::Reference(_)),
+        syn::FnArg::Receiver(_) => true,
+    });
+    if let Some(input) = borrowed {
+        let error = syn::Error::new_spanned(
+            input,
+            "time-sliced functions take parameters by value; copy buffers into the Zig state in \
+             `_begin`",
+        )
+        .to_compile_error();
+        return (quote! {}, error);
+    }
+    if rust_sig.needs_abi_lowering {
+        let error = syn::Error::new_spanned(
+            output,
+            "time-sliced functions must return a primitive type or nothing",
+        )
+        .to_compile_error();
+        return (quote! {}, error);
+    }
+
+    let begin_fn = quote::format_ident!("{}_begin", fn_name);
+    let step_fn = quote::format_ident!("{}_step", fn_name);
+    let finish_fn = quote::format_ident!("{}_finish", fn_name);
+    let task_name = quote::format_ident!("{}Task", pascal_case(&fn_name.to_string()));
+    let result_type = match output {
+        syn::ReturnType::Default => quote! { () },
+        syn::ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    let args: Vec<_> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(pat_type) => Some(&pat_type.pat),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    let ffi_decl = quote! {
+        extern "C" {
+            pub fn #begin_fn(#inputs) -> *mut ::core::ffi::c_void;
+            pub fn #step_fn(state: *mut ::core::ffi::c_void, budget: u32) -> bool;
+            pub fn #finish_fn(state: *mut ::core::ffi::c_void) #output;
+        }
+    };
+
+    let fn_doc = format!(" Runs [`{}`] to completion on the calling thread", task_name);
+    let task_doc = format!(
+        " Resumable execution of `{}`: each step runs a bounded number of loop iterations in Zig",
+        fn_name
+    );
+
+    let wrapper = quote! {
+        #[doc = #task_doc]
+        ///
+        /// Drive it with [`step`](Self::step) from a frame callback, or `.await` it
+        /// to yield to the executor between steps.
+        pub struct #task_name {
+            state: ::core::option::Option<::core::ptr::NonNull<::core::ffi::c_void>>,
+            done: bool,
+        }
+
+        impl #task_name {
+            /// Iterations per step used by the blocking wrapper and `.await`
+            pub const BUDGET: u32 = #budget;
+
+            pub fn new(#inputs) -> Self {
+                let state = unsafe { #mod_ident::#begin_fn(#(#args),*) };
+                let state = ::core::ptr::NonNull::new(state).expect("Zig allocation failed (OOM)");
+                Self { state: ::core::option::Option::Some(state), done: false }
+            }
+
+            /// Run at most `budget` iterations; returns `true` once finished
+            pub fn step(&mut self, budget: u32) -> bool {
+                if !self.done {
+                    if let ::core::option::Option::Some(state) = self.state {
+                        self.done = unsafe { #mod_ident::#step_fn(state.as_ptr(), budget) };
+                    }
+                }
+                self.done
+            }
+
+            /// Free the Zig state and return the result
+            ///
+            /// # Panics
+            ///
+            /// If [`step`](Self::step) has not returned `true` yet.
+            pub fn finish(mut self) -> #result_type {
+                assert!(self.done, "time-sliced task finished before its last step");
+                self.release()
+            }
+
+            fn release(&mut self) -> #result_type {
+                let state = self.state.take().expect("time-sliced task already finished");
+                unsafe { #mod_ident::#finish_fn(state.as_ptr()) }
+            }
+        }
+
+        impl ::core::future::Future for #task_name {
+            type Output = #result_type;
+
+            fn poll(
+                self: ::core::pin::Pin<&mut Self>,
+                cx: &mut ::core::task::Context<'_>,
+            ) -> ::core::task::Poll<Self::Output> {
+                let this = self.get_mut();
+                if this.step(Self::BUDGET) {
+                    ::core::task::Poll::Ready(this.release())
+                } else {
+                    cx.waker().wake_by_ref();
+                    ::core::task::Poll::Pending
+                }
+            }
+        }
+
+        impl Drop for #task_name {
+            fn drop(&mut self) {
+                if let ::core::option::Option::Some(state) = self.state.take() {
+                    unsafe {
+                        #mod_ident::#finish_fn(state.as_ptr());
+                    }
+                }
+            }
+        }
+
+        #[doc = #fn_doc]
+        pub fn #fn_name(#inputs) #output {
+            let mut task = #task_name::new(#(#args),*);
+            while !task.step(#task_name::BUDGET) {}
+            task.finish()
+        }
+    };
+
+    (ffi_decl, wrapper)
+}
+
+/// `slow_sum` -> `SlowSum`
+fn pascal_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Phase 3: Generate FFI declarations and wrappers with monomorphization
+/// support for include_zig!
+///
+/// NOTE: For include_zig!, we DISABLE ABI lowering because external Zig files
+/// are expected to use `extern struct` declarations which are already
+/// ABI-compatible. The engine generates wrappers only for autozig! embedded
+/// code.
+fn generate_with_monomorphization_for_include(
+    config: &IncludeZigConfig,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut all_ffi_decls = Vec::new();
+    let mut all_wrappers = Vec::new();
+    let mod_name = config.get_unique_mod_name();
+    let lowerings = load_type_lowerings();
+
+    for rust_sig in &config.rust_signatures {
+        let (ffi_mark, wrapper_mark) = (all_ffi_decls.len(), all_wrappers.len());
+        // For include_zig!, external Zig files should handle ABI themselves
+        // by using `extern struct`. We disable ABI lowering here.
+        let mut sig_no_abi_lowering = rust_sig.clone();
+        sig_no_abi_lowering.needs_abi_lowering = false;
+
+        if rust_sig.binding_config.isolated
+            && (!rust_sig.generic_params.is_empty()
+                || rust_sig.sig.generics.const_params().next().is_some()
+                || rust_sig.binding_config.time_sliced.is_some()
+                || rust_sig.is_async
+                || impl_trait_item(&rust_sig.sig.output, "Iterator").is_some()
+                || impl_trait_item(&rust_sig.sig.output, "Stream").is_some()
+                || passes_f16(&rust_sig.sig))
+        {
+            all_wrappers.push(
+                syn::Error::new_spanned(
+                    &rust_sig.sig,
+                    "`isolated` is not supported on generic, async, time-sliced, iterator, stream \
+                     or f16 functions",
+                )
+                .to_compile_error(),
+            );
+            continue;
+        }
+        if unsupported_multiversion(&sig_no_abi_lowering) {
+            all_wrappers.push(multiversion_error(rust_sig));
+            continue;
+        }
+        if let Some(error) = timeout_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = no_std_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = isr_safe_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if let Some(error) = gpu_error(rust_sig) {
+            all_wrappers.push(error);
+            continue;
+        }
+        if rust_sig.binding_config.gpu {
+            // SPIR-V module compiled by the engine, embedded instead of bound
+            all_wrappers.push(generate_gpu_kernel(rust_sig, None));
+        } else if rust_sig.sig.generics.const_params().next().is_some() {
+            // Const generic bound to one Zig specialization per value
+            let (const_ffi, const_wrappers) =
+                generate_const_monomorphized_versions(&sig_no_abi_lowering, &mod_name);
+            all_ffi_decls.push(const_ffi);
+            all_wrappers.push(const_wrappers);
+        } else if !rust_sig.generic_params.is_empty() && !rust_sig.monomorphize_types.is_empty() {
+            // Generic function with monomorphization attribute
+            let (mono_ffi, mono_wrappers) = generate_monomorphized_versions(
+                &sig_no_abi_lowering,
+                &mod_name,
+                &declared_ffi_types(&config.rust_structs, &config.rust_enums),
+            );
+            all_ffi_decls.push(mono_ffi);
+            all_wrappers.push(mono_wrappers);
+        } else if let Some(budget) = rust_sig.binding_config.time_sliced {
+            // Long-running loop split into resumable steps
+            let (sliced_ffi, sliced_wrapper) =
+                generate_time_sliced_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name, budget);
+            all_ffi_decls.push(sliced_ffi);
+            all_wrappers.push(sliced_wrapper);
+        } else if let Some(item) = impl_trait_item(&rust_sig.sig.output, "Stream") {
+            // `-> impl Stream<Item = T>` fed by a Zig producer on a blocking thread
+            let (stream_ffi, stream_wrapper) =
+                generate_stream_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name, item);
+            all_ffi_decls.push(stream_ffi);
+            all_wrappers.push(stream_wrapper);
+        } else if rust_sig.is_async {
+            // Async function
+            let (async_ffi, async_wrapper) =
+                generate_async_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name);
+            all_ffi_decls.push(async_ffi);
+            all_wrappers.push(async_wrapper);
+        } else if let Some((lowered_ffi, lowered_wrapper)) =
+            generate_type_lowering_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name, &lowerings)
+        {
+            // Domain types lowered by rules registered in build.rs
+            all_ffi_decls.push(lowered_ffi);
+            all_wrappers.push(lowered_wrapper);
+        } else if let Some(item) = impl_trait_item(&rust_sig.sig.output, "Iterator") {
+            // `-> impl Iterator<Item = T>` over a Zig cursor
+            let (iter_ffi, iter_wrapper) =
+                generate_iterator_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name, item);
+            all_ffi_decls.push(iter_ffi);
+            all_wrappers.push(iter_wrapper);
+        } else if passes_f16(&rust_sig.sig) {
+            // f16 by value, lowered by the engine to u16 bits
+            let (f16_ffi, f16_wrapper) =
+                generate_f16_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name);
+            all_ffi_decls.push(f16_ffi);
+            all_wrappers.push(f16_wrapper);
+        } else {
+            // Regular function (non-generic, non-async)
+            let mut ffi_decl = generate_single_ffi_declaration(&sig_no_abi_lowering);
+            if rust_sig.binding_config.multiversion {
+                ffi_decl = generate_multiversion_dispatch(ffi_decl);
+            }
+            let wrapper = generate_single_safe_wrapper(&sig_no_abi_lowering, &mod_name);
+            all_ffi_decls.push(ffi_decl);
+            all_wrappers.push(wrapper);
+        }
+        // A `#[cfg]` on the signature applies to everything generated for it
+        apply_cfg_attrs(&mut all_ffi_decls[ffi_mark..], &rust_sig.cfg_attrs);
+        apply_cfg_attrs(&mut all_wrappers[wrapper_mark..], &rust_sig.cfg_attrs);
+        apply_doc_attrs(&mut all_wrappers[wrapper_mark..], rust_sig);
+        apply_raw_pointer_unsafety(&mut all_wrappers[wrapper_mark..], rust_sig);
+        apply_isr_safe_attrs(&mut all_wrappers[wrapper_mark..], rust_sig);
+    }
+
+    let ffi_decls = quote! { #(#all_ffi_decls)* };
+    let wrappers = quote! { #(#all_wrappers)* };
+
+    (ffi_decls, wrappers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> String {
+        let config: AutoZigConfig = syn::parse_str(input).unwrap();
+        expand_autozig(&config).to_string()
+    }
+
+    #[test]
+    fn test_digest_without_output_size() {
+        let expanded = expand(
+            "export fn sha_new() ?*anyopaque {}\n---\nstruct ZigSha256(opaque);\nimpl \
+             digest::Digest for ZigSha256 {\n    fn update(&mut self, data: &[u8]) { \
+             sha_update(data) }\n}",
+        );
+        assert!(expanded.contains(
+            "\"digest adapters need the output size in bytes, e.g. `impl digest::Digest<32> for \
+             ZigSha256`\""
+        ));
+    }
+
+    #[test]
+    fn test_callback_trampoline() {
+        let expanded = expand(
+            "export fn for_each(ctx: ?*anyopaque, cb: *const fn (?*anyopaque, [*]const u8, usize) \
+             callconv(.C) void) void {}\n---\nfn for_each(cb: impl FnMut(&[u8]));",
+        );
+        // Zig receives the closure as a context pointer and a C function
+        assert!(expanded.contains(
+            "pub fn for_each (cb_ctx : * mut core :: ffi :: c_void , cb_fn : extern \"C\" fn (* \
+             mut core :: ffi :: c_void , * const u8 , usize)) ;"
+        ));
+        assert!(expanded.contains("pub fn for_each (cb : impl FnMut (& [u8]))"));
+        assert!(expanded.contains(
+            "extern \"C\" fn __autozig_cb_trampoline (ctx : * mut core :: ffi :: c_void , \
+             arg0_ptr : * const u8 , arg0_len : usize)"
+        ));
+        assert!(expanded.contains("if arg0_len == 0 || arg0_ptr . is_null () { & [] }"));
+        assert!(expanded.contains(
+            "ffi :: for_each (& mut __autozig_cb_dyn as * mut _ as * mut core :: ffi :: c_void , \
+             __autozig_cb_trampoline)"
+        ));
+    }
+
+    #[test]
+    fn test_isolated_rejects_borrowed_returns() {
+        with_std(true, || {
+            let expanded = expand(
+                "export fn name(code: u8) AutoZigOption(AutoZigSlice(u8)) \
+                 {}\n---\n#[autozig(isolated)]\nfn name(code: u8) -> Option<&'static str>;",
+            );
+            assert!(expanded.contains("cannot return references or pointers"));
+            let expanded = expand(
+                "export fn add(a: u32) u32 {}\n---\n#[autozig(isolated)]\nfn add(a: u32) -> u32;",
+            );
+            assert!(expanded.contains(":: autozig :: isolate :: run"));
+        });
+    }
+
+    #[test]
+    fn test_with_std() {
+        let input =
+            "export fn add(a: u32) u32 {}\n---\n#[autozig(isolated)]\nfn add(a: u32) -> u32;";
+        assert!(with_std(false, || expand(input)).contains(
+            "\"`timeout_ms`, `isolated` and `multiversion` need std and are not available without \
+             the `std` feature\""
+        ));
+        assert!(!with_std(true, || expand(input)).contains("compile_error"));
+        assert_eq!(std_enabled(), cfg!(feature = "std"));
+    }
+
+    #[test]
+    fn test_monomorphize_checks_full_paths() {
+        let accepted = expand(
+            "export fn id_u8(a: u8) u8 {}\n---\n#[repr(C)]\npub struct Point { pub x: f32 \
+             }\n#[monomorphize(u8, core::ffi::c_int, Point)]\nfn id<T>(a: T) -> T;",
+        );
+        assert!(!accepted.contains("compile_error"));
+        assert!(accepted.contains("pub fn id_u8"));
+
+        let rejected = expand(
+            "export fn id_u8(a: u8) u8 {}\n---\n#[repr(C)]\npub struct Point { pub x: f32 \
+             }\n#[monomorphize(other::Point, my::u8)]\nfn id<T: my::ZigPod>(a: T) -> T;",
+        );
+        assert!(rejected.contains("`#[monomorphize]` type `other :: Point` is not FFI-safe"));
+        assert!(rejected.contains("`#[monomorphize]` type `my :: u8` is not FFI-safe"));
+    }
+
+    #[test]
+    fn test_monomorphize_reports_every_parameter() {
+        let expanded = expand(
+            "export fn convert_u8_f32(a: u8) f32 {}\n---\n#[monomorphize((String, u8), (u8, \
+             Vec<u8>))]\nfn convert<Src, Dst>(a: Src) -> Dst;",
+        );
+        assert!(expanded.contains(
+            "\"`#[monomorphize]` type `String` is not FFI-safe: use a primitive or a #[repr(C)] \
+             type declared in this block, or bound `Src: ZigPod` and implement `autozig::ZigPod` \
+             for it\""
+        ));
+        assert!(expanded.contains("`#[monomorphize]` type `Vec < u8 >` is not FFI-safe"));
+        assert!(expanded.contains("bound `Dst: ZigPod`"));
+        assert!(!expanded.contains("pub fn convert"));
+    }
+
+    #[test]
+    fn test_struct_instances_keep_visibility() {
+        let expanded = expand(
+            "export fn scale(m: Matrix_f32) void \
+             {}\n---\n#[repr(C)]\n#[monomorphize(f32)]\nstruct Matrix<T> { data: [T; 4] \
+             }\n#[repr(C)]\n#[monomorphize(f64)]\npub struct Grid<T> { cells: [T; 4] }",
+        );
+        assert!(expanded.contains("type Matrix_f32 = Matrix < f32 > ;"));
+        assert!(!expanded.contains("pub type Matrix_f32"));
+        assert!(expanded.contains("pub type Grid_f64 = Grid < f64 > ;"));
+    }
+
+    #[test]
+    fn test_fallback_below_errors() {
+        let without_body = expand(
+            "export fn sum(ptr: [*]const u8, len: usize) u32 {}\n---\n#[autozig(fallback_below = \
+             64)]\nfn sum(data: &[u8]) -> u32;",
+        );
+        assert!(without_body.contains(
+            "\"`fallback_below` needs a Rust implementation: give the function a body instead of \
+             `;`\""
+        ));
+
+        let without_slices = expand(
+            "export fn add(a: u32) u32 {}\n---\n#[autozig(fallback_below = 64)]\nfn add(a: u32) \
+             -> u32 { a }",
+        );
+        assert!(without_slices.contains(
+            "\"`fallback_below` measures the input by its slice or string arguments, but this \
+             function has none\""
+        ));
+    }
+}
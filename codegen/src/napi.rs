@@ -0,0 +1,364 @@
+//! Node.js addon functions for the safe wrappers (`napi` feature)
+//!
+//! With the feature, every wrapper whose types have a JS equivalent also gets
+//! a napi-rs function, registered under its camelCase name when the crate is
+//! built as a Node addon (a `cdylib` depending on `napi` and `napi-derive`):
+//!
+//! - `&[u8]`, `&mut [u8]` and `Vec<u8>` are `Buffer`s, slices and vectors of
+//!   other numbers typed arrays (`Float32Array`, `Int32Array`, ...);
+//! - `&str` and `String` are JS strings;
+//! - opaque types are `External` objects, whose finalizer drops them and so
+//!   calls the Zig destructor. Their constructor and methods are exported as
+//!   functions taking the object first: `zigHasherNew()`,
+//!   `zigHasherUpdate(hasher, data)`.
+//!
+//! Numbers napi cannot take directly are converted: `f32` arrives as a JS
+//! number, and `u64`, `usize` and `isize` cross as `i64` both ways. Functions
+//! using other types are not exported.
+
+use autozig_parser::{
+    RustFunctionSignature,
+    RustTraitImpl,
+};
+use quote::{
+    format_ident,
+    quote,
+};
+
+/// Scalars napi converts as they are
+const NATIVE: &[&str] = &["bool", "u8", "i8", "u16", "i16", "u32", "i32", "i64", "f64"];
+
+/// Scalars passed to napi as another type: (Rust, JS side)
+const WIDENED: &[(&str, &str)] =
+    &[("f32", "f64"), ("u64", "i64"), ("usize", "i64"), ("isize", "i64")];
+
+/// JS arrays of slice elements
+const ARRAYS: &[(&str, &str)] = &[
+    ("u8", "Buffer"),
+    ("i8", "Int8Array"),
+    ("u16", "Uint16Array"),
+    ("i16", "Int16Array"),
+    ("u32", "Uint32Array"),
+    ("i32", "Int32Array"),
+    ("u64", "BigUint64Array"),
+    ("i64", "BigInt64Array"),
+    ("f32", "Float32Array"),
+    ("f64", "Float64Array"),
+];
+
+/// How one parameter reaches the wrapper
+struct Param {
+    /// Type of the napi function's parameter
+    js_type: proc_macro2::TokenStream,
+    /// Argument passed to the wrapper
+    arg: proc_macro2::TokenStream,
+    /// Whether the wrapper mutates the value
+    mutable: bool,
+}
+
+fn ident_of(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.get_ident().map(ToString::to_string),
+        _ => None,
+    }
+}
+
+fn array_type(elem: &syn::Type) -> Option<proc_macro2::TokenStream> {
+    let elem = ident_of(elem)?;
+    let (_, array) = ARRAYS.iter().find(|(rust, _)| *rust == elem)?;
+    let array = format_ident!("{}", array);
+    Some(quote! { ::napi::bindgen_prelude::#array })
+}
+
+fn param(name: &syn::Ident, ty: &syn::Type, opaque: &[String]) -> Option<Param> {
+    if let syn::Type::Reference(reference) = ty {
+        let mutable = reference.mutability.is_some();
+        let (js_type, arg) = match &*reference.elem {
+            syn::Type::Slice(slice) => (array_type(&slice.elem)?, name_ref(name, mutable, false)),
+            syn::Type::Path(path) if path.path.is_ident("str") && !mutable => {
+                (quote! { String }, quote! { &#name })
+            },
+            elem => {
+                let ty_name = ident_of(elem).filter(|ty_name| opaque.contains(ty_name))?;
+                let ty_ident = format_ident!("{}", ty_name);
+                (
+                    quote! { ::napi::bindgen_prelude::External<#ty_ident> },
+                    name_ref(name, mutable, true),
+                )
+            },
+        };
+        return Some(Param { js_type, arg, mutable });
+    }
+
+    let scalar = ident_of(ty)?;
+    if NATIVE.contains(&scalar.as_str()) {
+        return Some(Param {
+            js_type: quote! { #ty },
+            arg: quote! { #name },
+            mutable: false,
+        });
+    }
+    let (_, js) = WIDENED.iter().find(|(rust, _)| *rust == scalar)?;
+    let js = format_ident!("{}", js);
+    Some(Param {
+        js_type: quote! { #js },
+        arg: quote! { #name as #ty },
+        mutable: false,
+    })
+}
+
+fn name_ref(name: &syn::Ident, mutable: bool, deref: bool) -> proc_macro2::TokenStream {
+    match (mutable, deref) {
+        (true, true) => quote! { &mut *#name },
+        (false, true) => quote! { &*#name },
+        (true, false) => quote! { &mut #name },
+        (false, false) => quote! { &#name },
+    }
+}
+
+/// JS return type and the conversion of the wrapper's result; `self_type`
+/// stands for `Self`
+fn result(
+    output: &syn::ReturnType,
+    opaque: &[String],
+    self_type: Option<&str>,
+) -> Option<(
+    proc_macro2::TokenStream,
+    Box<dyn Fn(proc_macro2::TokenStream) -> proc_macro2::TokenStream>,
+)> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return Some((quote! {}, Box::new(|call| quote! { #call; })));
+    };
+    let syn::Type::Path(path) = &**ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let name = segment.ident.to_string();
+    if path.path.segments.len() == 1 && segment.arguments.is_none() {
+        if NATIVE.contains(&name.as_str()) || ["f32", "String"].contains(&name.as_str()) {
+            return Some((quote! { -> #ty }, Box::new(|call| call)));
+        }
+        if ["u64", "usize", "isize"].contains(&name.as_str()) {
+            return Some((quote! { -> i64 }, Box::new(|call| quote! { #call as i64 })));
+        }
+        let opaque_type = match name.as_str() {
+            "Self" => self_type,
+            _ => opaque.iter().map(String::as_str).find(|ty| *ty == name),
+        };
+        if let Some(opaque_type) = opaque_type {
+            let ty = format_ident!("{}", opaque_type);
+            return Some((
+                quote! { -> ::napi::bindgen_prelude::External<#ty> },
+                Box::new(|call| quote! { ::napi::bindgen_prelude::External::new(#call) }),
+            ));
+        }
+    }
+    if name == "Vec" {
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        let Some(syn::GenericArgument::Type(elem)) = args.args.first() else {
+            return None;
+        };
+        let array = array_type(elem)?;
+        let convert = if ident_of(elem).as_deref() == Some("u8") {
+            quote! { from }
+        } else {
+            quote! { new }
+        };
+        let returned = array.clone();
+        return Some((
+            quote! { -> #returned },
+            Box::new(move |call| quote! { #array::#convert(#call) }),
+        ));
+    }
+    None
+}
+
+/// camelCase JS name of `parts` joined with `_` (`ZigHasher_update` is
+/// `zigHasherUpdate`)
+fn js_name(parts: &[String]) -> String {
+    let joined = parts.join("_");
+    let mut name = String::new();
+    for (i, segment) in joined.split('_').filter(|s| !s.is_empty()).enumerate() {
+        let mut chars = segment.chars();
+        let first = chars.next().unwrap_or_default();
+        if i == 0 {
+            name.extend(first.to_lowercase());
+        } else {
+            name.extend(first.to_uppercase());
+        }
+        name.push_str(chars.as_str());
+    }
+    name
+}
+
+/// A napi function named `js_name`, converting `params` for `call`
+fn napi_fn(
+    js_name: &str,
+    params: Vec<(syn::Ident, Param)>,
+    call: proc_macro2::TokenStream,
+    output: (
+        proc_macro2::TokenStream,
+        Box<dyn Fn(proc_macro2::TokenStream) -> proc_macro2::TokenStream>,
+    ),
+) -> proc_macro2::TokenStream {
+    let fn_name = format_ident!("__autozig_napi_{}", js_name);
+    let inputs = params.iter().map(|(name, param)| {
+        let js_type = &param.js_type;
+        quote! { #name: #js_type }
+    });
+    let rebinds = params
+        .iter()
+        .filter(|(_, param)| param.mutable)
+        .map(|(name, _)| quote! { let mut #name = #name; });
+    let args = params.iter().map(|(_, param)| &param.arg);
+    let (ret, convert) = output;
+    let body = convert(quote! { #call(#(#args),*) });
+    quote! {
+        #[cfg(not(target_family = "wasm"))]
+        #[allow(non_snake_case)]
+        #[::napi_derive::napi(js_name = #js_name)]
+        fn #fn_name(#(#inputs),*) #ret {
+            #(#rebinds)*
+            #body
+        }
+    }
+}
+
+/// The napi function of a wrapper, if its types have JS equivalents
+fn function(
+    signature: &RustFunctionSignature,
+    namespace: Option<&syn::Ident>,
+    opaque: &[String],
+) -> Option<proc_macro2::TokenStream> {
+    let sig = &signature.sig;
+    if signature.is_async
+        || !sig.generics.params.is_empty()
+        || signature.binding_config.strategy.is_some()
+    {
+        return None;
+    }
+    let mut params = Vec::new();
+    for input in &sig.inputs {
+        let syn::FnArg::Typed(arg) = input else {
+            return None;
+        };
+        let syn::Pat::Ident(pat) = &*arg.pat else {
+            return None;
+        };
+        params.push((pat.ident.clone(), param(&pat.ident, &arg.ty, opaque)?));
+    }
+    let output = result(&sig.output, opaque, None)?;
+    let parts: Vec<String> = namespace
+        .map(ToString::to_string)
+        .into_iter()
+        .chain([sig.ident.to_string()])
+        .collect();
+    let wrapper = &sig.ident;
+    let cfg_attrs = &signature.cfg_attrs;
+    let function = napi_fn(&js_name(&parts), params, quote! { #wrapper }, output);
+    Some(quote! {
+        #(#cfg_attrs)*
+        #function
+    })
+}
+
+/// napi functions for the constructor and methods of an opaque type
+fn opaque_functions(
+    trait_impl: &RustTraitImpl,
+    namespace: Option<&syn::Ident>,
+    opaque: &[String],
+) -> Vec<proc_macro2::TokenStream> {
+    let type_name = &trait_impl.target_type;
+    let type_ident = format_ident!("{}", type_name);
+    let path = match &trait_impl.trait_path {
+        Some(trait_path) => quote! { <#type_ident as #trait_path> },
+        None => quote! { #type_ident },
+    };
+    let handle = format_ident!("handle");
+
+    let mut functions = Vec::new();
+    let methods = trait_impl
+        .constructor
+        .iter()
+        .map(|constructor| (constructor, quote! { #type_ident }))
+        .chain(
+            trait_impl
+                .methods
+                .iter()
+                .map(|method| (method, path.clone())),
+        );
+    for (method, path) in methods {
+        let sig = &method.sig;
+        if !sig.generics.params.is_empty() {
+            continue;
+        }
+        let mut params = Vec::new();
+        let mut supported = true;
+        for input in &sig.inputs {
+            match input {
+                syn::FnArg::Receiver(receiver) if receiver.reference.is_some() => {
+                    let mutable = receiver.mutability.is_some();
+                    params.push((
+                        handle.clone(),
+                        Param {
+                            js_type: quote! { ::napi::bindgen_prelude::External<#type_ident> },
+                            arg: name_ref(&handle, mutable, true),
+                            mutable,
+                        },
+                    ));
+                },
+                syn::FnArg::Typed(arg) => match &*arg.pat {
+                    syn::Pat::Ident(pat) => match param(&pat.ident, &arg.ty, opaque) {
+                        Some(param) => params.push((pat.ident.clone(), param)),
+                        None => supported = false,
+                    },
+                    _ => supported = false,
+                },
+                // Consuming methods would leave the External dangling
+                syn::FnArg::Receiver(_) => supported = false,
+            }
+        }
+        let Some(output) = result(&sig.output, opaque, Some(type_name)).filter(|_| supported)
+        else {
+            continue;
+        };
+        let parts: Vec<String> = namespace
+            .map(ToString::to_string)
+            .into_iter()
+            .chain([type_name.clone(), method.name.clone()])
+            .collect();
+        let method_ident = &sig.ident;
+        functions.push(napi_fn(&js_name(&parts), params, quote! { #path::#method_ident }, output));
+    }
+    functions
+}
+
+/// napi functions for the wrappers and opaque types of a block, if the
+/// feature is enabled
+pub fn napi_bindings(
+    signatures: &[RustFunctionSignature],
+    trait_impls: &[RustTraitImpl],
+    namespace: Option<&syn::Ident>,
+) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "napi") {
+        return quote! {};
+    }
+    let opaque: Vec<String> = trait_impls
+        .iter()
+        .filter(|trait_impl| trait_impl.is_opaque)
+        .map(|trait_impl| trait_impl.target_type.clone())
+        .collect();
+    let functions = signatures
+        .iter()
+        .filter_map(|signature| function(signature, namespace, &opaque));
+    let methods = trait_impls
+        .iter()
+        .filter(|trait_impl| trait_impl.is_opaque && trait_impl.trait_name != "Digest")
+        .flat_map(|trait_impl| opaque_functions(trait_impl, namespace, &opaque));
+    quote! {
+        #(#functions)*
+        #(#methods)*
+    }
+}
@@ -0,0 +1,328 @@
+//! Python bindings of `#[autozig(python)]` items (`python` feature)
+//!
+//! Marked functions get a pyo3 `#[pyfunction]`, and marked opaque types
+//! become `#[pyclass]`es whose constructor and methods are `#[pymethods]`;
+//! Python releasing an object drops it, which calls the Zig destructor. Each
+//! block registers its functions and classes with `autozig::python`, and
+//! `autozig::python_module!` adds them all to the extension module:
+//!
+//! - `&[u8]` arrives from `bytes` without copying, other slices as lists;
+//! - `&str` and `String` are `str`, `Vec<T>` results lists;
+//! - `&O` and `&mut O` of an exported opaque type borrow the Python object.
+//!
+//! A marked function whose types have no Python equivalent is a compile
+//! error; methods of an exported type that have none are left out.
+
+use autozig_parser::{
+    RustFunctionSignature,
+    RustTraitImpl,
+};
+use quote::{
+    format_ident,
+    quote,
+};
+
+/// Scalars pyo3 converts as they are
+const SCALARS: &[&str] = &[
+    "bool", "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "usize", "isize", "f32", "f64",
+];
+
+fn ident_of(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.get_ident().map(ToString::to_string),
+        _ => None,
+    }
+}
+
+fn is_scalar(ty: &syn::Type) -> bool {
+    ident_of(ty).is_some_and(|name| SCALARS.contains(&name.as_str()))
+}
+
+/// Python-side type of a parameter and the argument passed on, or `None` if
+/// it has no Python equivalent
+fn param(
+    name: &syn::Ident,
+    ty: &syn::Type,
+    classes: &[String],
+) -> Option<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let syn::Type::Reference(reference) = ty else {
+        return is_scalar(ty).then(|| (quote! { #ty }, quote! { #name }));
+    };
+    let mutable = reference.mutability.is_some();
+    match &*reference.elem {
+        syn::Type::Slice(slice) if !mutable && ident_of(&slice.elem).as_deref() == Some("u8") => {
+            Some((quote! { &[u8] }, quote! { #name }))
+        },
+        syn::Type::Slice(slice) if !mutable && is_scalar(&slice.elem) => {
+            let elem = &slice.elem;
+            Some((quote! { Vec<#elem> }, quote! { &#name }))
+        },
+        syn::Type::Path(path) if !mutable && path.path.is_ident("str") => {
+            Some((quote! { String }, quote! { &#name }))
+        },
+        elem => {
+            let class = ident_of(elem).filter(|class| classes.contains(class))?;
+            let class = format_ident!("{}", class);
+            Some(if mutable {
+                (quote! { ::pyo3::PyRefMut<'_, #class> }, quote! { &mut *#name })
+            } else {
+                (quote! { ::pyo3::PyRef<'_, #class> }, quote! { &*#name })
+            })
+        },
+    }
+}
+
+/// Whether a result converts to Python; `Self` is one of the classes
+fn result_supported(output: &syn::ReturnType, classes: &[String], in_class: bool) -> bool {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return true;
+    };
+    let syn::Type::Path(path) = &**ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    match &segment.arguments {
+        syn::PathArguments::None => {
+            let name = segment.ident.to_string();
+            is_scalar(ty)
+                || name == "String"
+                || (name == "Self" && in_class)
+                || classes.contains(&name)
+        },
+        syn::PathArguments::AngleBracketed(args) if segment.ident == "Vec" => {
+            matches!(args.args.first(), Some(syn::GenericArgument::Type(elem)) if is_scalar(elem))
+        },
+        _ => false,
+    }
+}
+
+/// Parameters of a function or method, or `None` if one has no Python
+/// equivalent (or is a consuming `self`)
+#[allow(clippy::type_complexity)]
+fn params(
+    sig: &syn::Signature,
+    classes: &[String],
+) -> Option<(Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>)> {
+    let mut inputs = Vec::new();
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            syn::FnArg::Receiver(receiver) if receiver.reference.is_some() => {
+                inputs.push(quote! { #receiver });
+                args.push(quote! { self });
+            },
+            syn::FnArg::Typed(arg) => {
+                let syn::Pat::Ident(pat) = &*arg.pat else {
+                    return None;
+                };
+                let name = &pat.ident;
+                let (ty, passed) = param(name, &arg.ty, classes)?;
+                inputs.push(quote! { #name: #ty });
+                args.push(passed);
+            },
+            syn::FnArg::Receiver(_) => return None,
+        }
+    }
+    Some((inputs, args))
+}
+
+/// `#[pyfunction]` of a marked wrapper, or a compile error if its signature
+/// has no Python equivalent
+fn function(
+    signature: &RustFunctionSignature,
+    namespace: Option<&syn::Ident>,
+    classes: &[String],
+) -> Result<(syn::Ident, proc_macro2::TokenStream), syn::Error> {
+    let sig = &signature.sig;
+    let unsupported = |what: &str| {
+        syn::Error::new_spanned(
+            &sig.ident,
+            format!("`{}` cannot be exported to Python: {what}", sig.ident),
+        )
+    };
+    if signature.is_async
+        || !sig.generics.params.is_empty()
+        || signature.binding_config.strategy.is_some()
+    {
+        return Err(unsupported("async, generic and strategy functions are not supported"));
+    }
+    let (inputs, args) =
+        params(sig, classes).ok_or_else(|| unsupported("a parameter has no Python equivalent"))?;
+    if !result_supported(&sig.output, classes, false) {
+        return Err(unsupported("the result has no Python equivalent"));
+    }
+
+    let wrapper = &sig.ident;
+    let py_name = match namespace {
+        Some(namespace) => format!("{namespace}_{wrapper}"),
+        None => wrapper.to_string(),
+    };
+    let fn_name = format_ident!("__autozig_py_{}", py_name);
+    let output = &sig.output;
+    let cfg_attrs = &signature.cfg_attrs;
+    let doc_attrs = &signature.doc_attrs;
+    Ok((
+        fn_name.clone(),
+        quote! {
+            #(#cfg_attrs)*
+            #(#doc_attrs)*
+            #[::pyo3::pyfunction]
+            #[pyo3(name = #py_name)]
+            fn #fn_name(#(#inputs),*) #output {
+                #wrapper(#(#args),*)
+            }
+        },
+    ))
+}
+
+/// `#[pymethods]` of an exported opaque type, from all its impl blocks
+fn class_methods(
+    type_name: &str,
+    impls: &[&RustTraitImpl],
+    classes: &[String],
+) -> proc_macro2::TokenStream {
+    let type_ident = format_ident!("{}", type_name);
+    let mut methods = Vec::new();
+    for trait_impl in impls {
+        let path = match &trait_impl.trait_path {
+            Some(trait_path) => quote! { <#type_ident as #trait_path> },
+            None => quote! { #type_ident },
+        };
+        if let Some(constructor) = &trait_impl.constructor {
+            if let Some((inputs, args)) = params(&constructor.sig, classes) {
+                let name = &constructor.sig.ident;
+                let doc_attrs = &constructor.doc_attrs;
+                methods.push(quote! {
+                    #(#doc_attrs)*
+                    #[new]
+                    fn __autozig_py_new(#(#inputs),*) -> Self {
+                        #type_ident::#name(#(#args),*)
+                    }
+                });
+            }
+        }
+        for method in &trait_impl.methods {
+            let sig = &method.sig;
+            if !sig.generics.params.is_empty() || !result_supported(&sig.output, classes, true) {
+                continue;
+            }
+            let Some((inputs, args)) = params(sig, classes) else {
+                continue;
+            };
+            let name = &sig.ident;
+            let py_name = name.to_string();
+            let fn_name = format_ident!("__autozig_py_{}", py_name);
+            let static_attr = if sig.receiver().is_none() {
+                quote! { #[staticmethod] }
+            } else {
+                quote! {}
+            };
+            let output = &sig.output;
+            let doc_attrs = &method.doc_attrs;
+            methods.push(quote! {
+                #(#doc_attrs)*
+                #static_attr
+                #[pyo3(name = #py_name)]
+                fn #fn_name(#(#inputs),*) #output {
+                    #path::#name(#(#args),*)
+                }
+            });
+        }
+    }
+    quote! {
+        #[::pyo3::pymethods]
+        impl #type_ident {
+            #(#methods)*
+        }
+    }
+}
+
+/// `#[pyclass]` attribute of an exported opaque type; classes not declared
+/// `send, sync` stay on the thread that created them
+pub fn pyclass_attr(trait_impl: &RustTraitImpl) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "python") || !trait_impl.python {
+        return quote! {};
+    }
+    let name = &trait_impl.target_type;
+    if trait_impl.send && trait_impl.sync {
+        quote! { #[::pyo3::pyclass(name = #name)] }
+    } else {
+        quote! { #[::pyo3::pyclass(name = #name, unsendable)] }
+    }
+}
+
+/// Python functions and classes of the marked items of a block, registered
+/// with `autozig::python`
+pub fn python_bindings(
+    signatures: &[RustFunctionSignature],
+    trait_impls: &[RustTraitImpl],
+    namespace: Option<&syn::Ident>,
+) -> proc_macro2::TokenStream {
+    let marked: Vec<&RustFunctionSignature> = signatures
+        .iter()
+        .filter(|signature| signature.binding_config.python)
+        .collect();
+    let mut classes: Vec<String> = Vec::new();
+    for trait_impl in trait_impls {
+        if trait_impl.is_opaque && trait_impl.python && !classes.contains(&trait_impl.target_type) {
+            classes.push(trait_impl.target_type.clone());
+        }
+    }
+    if marked.is_empty() && classes.is_empty() {
+        return quote! {};
+    }
+    if !cfg!(feature = "python") {
+        let message = "`#[autozig(python)]` needs the `python` feature of autozig";
+        return quote! { compile_error!(#message); };
+    }
+
+    let mut functions = Vec::new();
+    let mut function_names = Vec::new();
+    for signature in marked {
+        match function(signature, namespace, &classes) {
+            Ok((name, function)) => {
+                let cfg_attrs = &signature.cfg_attrs;
+                function_names.push(quote! {
+                    #(#cfg_attrs)*
+                    module.add_function(::pyo3::wrap_pyfunction!(#name, module)?)?;
+                });
+                functions.push(function);
+            },
+            Err(err) => return err.to_compile_error(),
+        }
+    }
+    let methods = classes.iter().map(|class| {
+        let impls: Vec<&RustTraitImpl> = trait_impls
+            .iter()
+            .filter(|trait_impl| &trait_impl.target_type == class)
+            .collect();
+        class_methods(class, &impls, &classes)
+    });
+    let class_idents = classes.iter().map(|class| format_ident!("{}", class));
+
+    quote! {
+        const _: () = {
+            #(#functions)*
+            #(#methods)*
+
+            unsafe fn register(
+                module: *const ::std::ffi::c_void,
+            ) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::any::Any + Send>> {
+                let module = &*module.cast::<::pyo3::Bound<'_, ::pyo3::types::PyModule>>();
+                let added = (|| -> ::pyo3::PyResult<()> {
+                    use ::pyo3::types::PyModuleMethods;
+                    #(#function_names)*
+                    #(module.add_class::<#class_idents>()?;)*
+                    Ok(())
+                })();
+                added.map_err(|err| ::std::boxed::Box::new(err) as _)
+            }
+
+            ::autozig::python::inventory::submit! {
+                ::autozig::python::Registration { register }
+            }
+        };
+    }
+}
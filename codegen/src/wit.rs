@@ -0,0 +1,360 @@
+//! WASM component model exports of the safe wrappers
+//!
+//! With `BuildOptions::wit` the build script sets `AUTOZIG_WIT=1`, and every
+//! wrapper whose signature has a WIT equivalent gets a core export following
+//! the canonical ABI on `wasm32`: strings and lists arrive as pointer/length
+//! pairs allocated through `cabi_realloc`, and returned ones are passed back
+//! in a return area, freed by the matching `cabi_post_<name>` export. The
+//! runtime side lives in `autozig::component` (`component` feature).
+//!
+//! The engine renders the WIT world from the same [`WitFunction`]s, so the
+//! export names and types always agree with it.
+
+use autozig_parser::RustFunctionSignature;
+use quote::{
+    format_ident,
+    quote,
+};
+
+/// Whether the build asked for component exports
+pub fn enabled() -> bool {
+    std::env::var("AUTOZIG_WIT").is_ok_and(|v| v == "1")
+}
+
+/// Scalar types with a WIT equivalent, as (Rust, WIT) names; `usize` and
+/// `isize` are 32-bit like the component model's memory
+const SCALARS: &[(&str, &str)] = &[
+    ("bool", "bool"),
+    ("u8", "u8"),
+    ("u16", "u16"),
+    ("u32", "u32"),
+    ("u64", "u64"),
+    ("usize", "u32"),
+    ("i8", "s8"),
+    ("i16", "s16"),
+    ("i32", "s32"),
+    ("i64", "s64"),
+    ("isize", "s32"),
+    ("f32", "f32"),
+    ("f64", "f64"),
+];
+
+/// WIT keywords, which need a `%` to be used as names
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "bool",
+    "borrow",
+    "char",
+    "constructor",
+    "enum",
+    "export",
+    "f32",
+    "f64",
+    "flags",
+    "from",
+    "func",
+    "future",
+    "import",
+    "include",
+    "interface",
+    "list",
+    "option",
+    "own",
+    "package",
+    "record",
+    "resource",
+    "result",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "static",
+    "stream",
+    "string",
+    "tuple",
+    "type",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+    "use",
+    "variant",
+    "with",
+    "world",
+];
+
+/// Type of a parameter or result crossing the component boundary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitType {
+    /// A scalar passed by value, with its Rust type
+    Scalar(syn::Ident),
+    /// `&str` parameters and `String` results
+    String,
+    /// `&[T]` parameters and `Vec<T>` results of a scalar `T`
+    List(syn::Ident),
+}
+
+impl WitType {
+    fn param(ty: &syn::Type) -> Option<WitType> {
+        match ty {
+            syn::Type::Reference(reference) if reference.mutability.is_none() => {
+                match &*reference.elem {
+                    syn::Type::Path(path) if path.path.is_ident("str") => Some(WitType::String),
+                    syn::Type::Slice(slice) => scalar(&slice.elem).map(WitType::List),
+                    _ => None,
+                }
+            },
+            _ => scalar(ty).map(WitType::Scalar),
+        }
+    }
+
+    fn result(ty: &syn::Type) -> Option<WitType> {
+        let syn::Type::Path(path) = ty else {
+            return None;
+        };
+        let segment = path.path.segments.last()?;
+        match segment.ident.to_string().as_str() {
+            "String" if segment.arguments.is_none() => Some(WitType::String),
+            "Vec" => match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match &args.args[0] {
+                        syn::GenericArgument::Type(elem) => scalar(elem).map(WitType::List),
+                        _ => None,
+                    }
+                },
+                _ => None,
+            },
+            _ => scalar(ty).map(WitType::Scalar),
+        }
+    }
+
+    /// The type as written in WIT
+    pub fn wit(&self) -> String {
+        match self {
+            WitType::Scalar(ty) => wit_scalar(ty).to_string(),
+            WitType::String => "string".to_string(),
+            WitType::List(elem) => format!("list<{}>", wit_scalar(elem)),
+        }
+    }
+
+    /// Core wasm parameters it flattens to
+    fn flat_count(&self) -> usize {
+        match self {
+            WitType::Scalar(_) => 1,
+            WitType::String | WitType::List(_) => 2,
+        }
+    }
+}
+
+fn scalar(ty: &syn::Type) -> Option<syn::Ident> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = path.path.get_ident()?;
+    SCALARS
+        .iter()
+        .any(|(rust, _)| ident == rust)
+        .then(|| ident.clone())
+}
+
+fn wit_scalar(ty: &syn::Ident) -> &'static str {
+    SCALARS
+        .iter()
+        .find(|(rust, _)| ty == rust)
+        .map_or("u8", |(_, wit)| wit)
+}
+
+/// Kebab-case WIT name of a snake_case Rust name; segments starting with a
+/// digit are joined to the previous one (`blur_2d` is `blur2d`)
+pub fn kebab(name: &str) -> String {
+    let mut segments: Vec<String> = Vec::new();
+    for segment in name.split('_').filter(|segment| !segment.is_empty()) {
+        let segment = segment.to_lowercase();
+        match segments.last_mut() {
+            Some(last) if segment.starts_with(|c: char| c.is_ascii_digit()) => {
+                last.push_str(&segment)
+            },
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("-")
+}
+
+/// `name` as a WIT identifier, escaped if it is a keyword
+pub fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("%{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// A wrapper exported to the component model
+#[derive(Debug, Clone)]
+pub struct WitFunction {
+    /// Export name, kebab-case and prefixed with the block's namespace
+    pub name: String,
+    /// Name of the safe wrapper
+    pub rust_name: syn::Ident,
+    /// Parameter names (Rust) and types
+    pub params: Vec<(syn::Ident, WitType)>,
+    /// Result type; `None` for functions returning `()`
+    pub result: Option<WitType>,
+}
+
+impl WitFunction {
+    /// The component export of a wrapper, or `None` if its signature has no
+    /// WIT equivalent
+    ///
+    /// Async, generic, `#[autozig(strategy)]` and `#[cfg]`-gated functions
+    /// are not exported, nor are those whose parameters flatten to more than
+    /// 16 core values.
+    pub fn from_signature(
+        signature: &RustFunctionSignature,
+        namespace: Option<&syn::Ident>,
+    ) -> Option<WitFunction> {
+        let sig = &signature.sig;
+        if signature.is_async
+            || !sig.generics.params.is_empty()
+            || signature.binding_config.strategy.is_some()
+            || !signature.cfg_attrs.is_empty()
+        {
+            return None;
+        }
+        let mut params = Vec::new();
+        for input in &sig.inputs {
+            let syn::FnArg::Typed(arg) = input else {
+                return None;
+            };
+            let syn::Pat::Ident(pat) = &*arg.pat else {
+                return None;
+            };
+            params.push((pat.ident.clone(), WitType::param(&arg.ty)?));
+        }
+        if params.iter().map(|(_, ty)| ty.flat_count()).sum::<usize>() > 16 {
+            return None;
+        }
+        let result = match &sig.output {
+            syn::ReturnType::Default => None,
+            syn::ReturnType::Type(_, ty) => Some(WitType::result(ty)?),
+        };
+        let rust_name = sig.ident.to_string();
+        let name = match namespace {
+            Some(namespace) => kebab(&format!("{namespace}_{rust_name}")),
+            None => kebab(&rust_name),
+        };
+        Some(WitFunction {
+            name,
+            rust_name: sig.ident.clone(),
+            params,
+            result,
+        })
+    }
+
+    /// Declaration in a WIT world, e.g. `export blur: func(px: list<u8>) ->
+    /// u32;`
+    pub fn declaration(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", identifier(&kebab(&name.to_string())), ty.wit()))
+            .collect();
+        let result = self
+            .result
+            .as_ref()
+            .map(|ty| format!(" -> {}", ty.wit()))
+            .unwrap_or_default();
+        format!("export {}: func({}){result};", identifier(&self.name), params.join(", "))
+    }
+
+    /// Core exports calling the wrapper with the canonical ABI
+    fn exports(&self) -> proc_macro2::TokenStream {
+        let wrapper = &self.rust_name;
+        let export_name = &self.name;
+        let mut core_params = Vec::new();
+        let mut lifts = Vec::new();
+        let mut args = Vec::new();
+        for (name, ty) in &self.params {
+            match ty {
+                WitType::Scalar(rust) if rust == "bool" => {
+                    core_params.push(quote! { #name: i32 });
+                    args.push(quote! { #name != 0 });
+                },
+                WitType::Scalar(rust) => {
+                    core_params.push(quote! { #name: #rust });
+                    args.push(quote! { #name });
+                },
+                WitType::String | WitType::List(_) => {
+                    let ptr = format_ident!("{}_ptr", name);
+                    let len = format_ident!("{}_len", name);
+                    let elem = match ty {
+                        WitType::List(elem) => quote! { #elem },
+                        _ => quote! { u8 },
+                    };
+                    core_params.push(quote! { #ptr: *mut #elem, #len: usize });
+                    lifts.push(if *ty == WitType::String {
+                        quote! { let #name = ::autozig::component::take_string(#ptr, #len); }
+                    } else {
+                        quote! { let #name = ::autozig::component::take_list(#ptr, #len); }
+                    });
+                    args.push(quote! { &#name });
+                },
+            }
+        }
+
+        let call = quote! { #wrapper(#(#args),*) };
+        let (ret, body, post) = match &self.result {
+            None => (quote! {}, quote! { #call; }, None),
+            Some(WitType::Scalar(rust)) if rust == "bool" => {
+                (quote! { -> i32 }, quote! { i32::from(#call) }, None)
+            },
+            Some(WitType::Scalar(rust)) => (quote! { -> #rust }, call, None),
+            Some(list) => {
+                let (value, elem) = match list {
+                    WitType::List(elem) => (call, quote! { #elem }),
+                    _ => (quote! { #call.into_bytes() }, quote! { u8 }),
+                };
+                let post_name = format!("cabi_post_{export_name}");
+                (
+                    quote! { -> *mut usize },
+                    quote! { ::autozig::component::return_list(#value) },
+                    Some(quote! {
+                        #[export_name = #post_name]
+                        unsafe extern "C" fn __autozig_component_post(ret: *mut usize) {
+                            ::autozig::component::free_list::<#elem>(ret)
+                        }
+                    }),
+                )
+            },
+        };
+
+        quote! {
+            #[cfg(target_arch = "wasm32")]
+            const _: () = {
+                #[export_name = #export_name]
+                unsafe extern "C" fn __autozig_component_export(#(#core_params),*) #ret {
+                    #(#lifts)*
+                    #body
+                }
+                #post
+            };
+        }
+    }
+}
+
+/// Component exports of the wrappers of `signatures`, if the build asked for
+/// them
+pub fn component_exports(
+    signatures: &[RustFunctionSignature],
+    namespace: Option<&syn::Ident>,
+) -> proc_macro2::TokenStream {
+    if !enabled() {
+        return quote! {};
+    }
+    let exports = signatures
+        .iter()
+        .filter_map(|signature| WitFunction::from_signature(signature, namespace))
+        .map(|function| function.exports());
+    quote! { #(#exports)* }
+}
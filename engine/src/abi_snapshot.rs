@@ -0,0 +1,142 @@
+//! ABI snapshots for golden-file tests
+//!
+//! A snapshot lists what the generated bindings expect from the compiled Zig
+//! library: every lowered `extern "C"` declaration and every `#[repr]` type
+//! crossing the boundary, as the macros expand them for the current target.
+//! Comparing it against a committed copy per target (see
+//! `autozig::abi_snapshot_test!`) turns unintended ABI changes, e.g. from an
+//! autozig or Zig upgrade, into a failing test instead of memory corruption.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use walkdir::WalkDir;
+
+use crate::vendor;
+
+/// Set to `1` to overwrite the committed snapshots with the current ABI
+pub const UPDATE_ENV: &str = "AUTOZIG_UPDATE_ABI_SNAPSHOTS";
+
+/// ABI snapshot of the autozig macros in the `.rs` files under `src_dir`
+///
+/// Files are listed in path order, each under a `// <path>` header.
+pub fn snapshot_dir(src_dir: &Path) -> Result<String> {
+    let mut files: Vec<PathBuf> = WalkDir::new(src_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+    files.sort();
+
+    let mut snapshot = String::new();
+    for path in files {
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let abi = snapshot_source(&source)
+            .with_context(|| format!("Failed to snapshot the ABI of {}", path.display()))?;
+        if abi.is_empty() {
+            continue;
+        }
+        let relative = path.strip_prefix(src_dir).unwrap_or(&path);
+        snapshot.push_str(&format!("// {}\n", relative.display().to_string().replace('\\', "/")));
+        snapshot.push_str(&abi);
+        snapshot.push('\n');
+    }
+    Ok(snapshot)
+}
+
+/// ABI snapshot of the autozig macros in one source file (empty if none)
+pub fn snapshot_source(source: &str) -> Result<String> {
+    let mut items = Vec::new();
+    for expansion in vendor::expansions(source)? {
+        collect_abi_items(expansion.items, &mut items);
+    }
+    if items.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(prettyplease::unparse(&syn::File { shebang: None, attrs: Vec::new(), items }))
+}
+
+/// Foreign declarations and `#[repr]` types, without docs or derives
+fn collect_abi_items(items: Vec<syn::Item>, abi: &mut Vec<syn::Item>) {
+    for item in items {
+        match item {
+            syn::Item::ForeignMod(mut foreign) => {
+                foreign.attrs.clear();
+                for foreign_item in &mut foreign.items {
+                    if let syn::ForeignItem::Fn(function) = foreign_item {
+                        function.attrs.clear();
+                    }
+                }
+                abi.push(syn::Item::ForeignMod(foreign));
+            },
+            syn::Item::Mod(module) => {
+                if let Some((_, content)) = module.content {
+                    collect_abi_items(content, abi);
+                }
+            },
+            syn::Item::Struct(mut item) if has_repr(&item.attrs) => {
+                item.attrs.retain(|attr| attr.path().is_ident("repr"));
+                for field in &mut item.fields {
+                    field.attrs.clear();
+                }
+                abi.push(syn::Item::Struct(item));
+            },
+            syn::Item::Enum(mut item) if has_repr(&item.attrs) => {
+                item.attrs.retain(|attr| attr.path().is_ident("repr"));
+                for variant in &mut item.variants {
+                    variant.attrs.clear();
+                }
+                abi.push(syn::Item::Enum(item));
+            },
+            _ => {},
+        }
+    }
+}
+
+fn has_repr(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("repr"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_source() {
+        let source = r#"
+use autozig::autozig;
+
+fn unrelated() {}
+
+autozig! {
+    export fn scale(p: Point, k: f32) Point { return .{ .x = p.x * k, .y = p.y * k }; }
+    export fn checksum(ptr: [*]const u8, len: usize) u32 { return 0; }
+    ---
+    /// A point
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Point { x: f32, y: f32 }
+
+    fn checksum(data: &[u8]) -> u32;
+}
+"#;
+        let snapshot = snapshot_source(source).unwrap();
+        assert!(snapshot.contains("#[repr(C)]\nstruct Point {\n    x: f32,\n    y: f32,\n}"));
+        assert!(snapshot.contains("fn checksum(data_ptr: *const u8, data_len: usize) -> u32;"));
+        assert!(!snapshot.contains("derive"));
+        assert!(!snapshot.contains("unrelated"));
+
+        assert_eq!(snapshot_source("fn main() {}").unwrap(), "");
+    }
+}
@@ -0,0 +1,268 @@
+//! Deterministic static archives for reproducible builds
+//!
+//! `ar` archives record a modification time, owner and mode for every
+//! member, and Zig adds objects in whatever order its parallel compilation
+//! finishes. [`normalize`] zeroes the timestamps and owners, fixes the mode
+//! to `644` and sorts the members by name (then contents), rewriting the
+//! offsets of the GNU symbol table (`/` or `/SYM64/`) to match. Archives with
+//! a BSD symbol table (`__.SYMDEF`) keep their member order.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+
+const MAGIC: &[u8] = b"!<arch>\n";
+const HEADER_LEN: usize = 60;
+
+struct Member<'a> {
+    /// Offset of the member's header in the original archive
+    offset: usize,
+    /// Raw 16-byte name field
+    name: &'a [u8],
+    data: &'a [u8],
+}
+
+impl Member<'_> {
+    fn name_field(&self) -> &str {
+        std::str::from_utf8(self.name)
+            .unwrap_or_default()
+            .trim_end()
+    }
+
+    fn is_symbol_table(&self) -> bool {
+        matches!(self.name_field(), "/" | "/SYM64/")
+    }
+
+    fn is_bsd_symbol_table(&self) -> bool {
+        self.name_field().starts_with("__.SYMDEF") || self.data.starts_with(b"__.SYMDEF")
+    }
+
+    fn is_special(&self) -> bool {
+        self.is_symbol_table() || self.name_field() == "//" || self.is_bsd_symbol_table()
+    }
+
+    /// Member name, resolving GNU (`/123`) and BSD (`#1/20`) long names
+    fn resolved_name<'a>(&'a self, long_names: &'a [u8]) -> &'a [u8] {
+        let field = self.name_field();
+        if let Some(len) = field
+            .strip_prefix("#1/")
+            .and_then(|len| len.parse::<usize>().ok())
+        {
+            return &self.data[..len.min(self.data.len())];
+        }
+        if let Some(start) = field
+            .strip_prefix('/')
+            .and_then(|start| start.parse::<usize>().ok())
+        {
+            let rest = long_names.get(start..).unwrap_or_default();
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            return rest[..end].strip_suffix(b"/").unwrap_or(&rest[..end]);
+        }
+        field.strip_suffix('/').unwrap_or(field).as_bytes()
+    }
+}
+
+fn parse(archive: &[u8]) -> Result<Vec<Member<'_>>> {
+    if !archive.starts_with(MAGIC) {
+        anyhow::bail!("Not an ar archive");
+    }
+    let mut members = Vec::new();
+    let mut pos = MAGIC.len();
+    while pos + HEADER_LEN <= archive.len() {
+        let header = &archive[pos..pos + HEADER_LEN];
+        if &header[58..] != b"`\n" {
+            anyhow::bail!("Malformed ar member header at offset {pos}");
+        }
+        let size: usize = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|size| size.trim().parse().ok())
+            .with_context(|| format!("Malformed ar member size at offset {pos}"))?;
+        let start = pos + HEADER_LEN;
+        let data = archive
+            .get(start..start + size)
+            .with_context(|| format!("Truncated ar member at offset {pos}"))?;
+        members.push(Member { offset: pos, name: &header[..16], data });
+        pos = start + size + size % 2;
+    }
+    Ok(members)
+}
+
+fn write_header(out: &mut Vec<u8>, name: &[u8], size: usize) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(format!("{:<12}{:<6}{:<6}{:<8}{:<10}`\n", 0, 0, 0, 644, size).as_bytes());
+}
+
+/// GNU symbol table with its member offsets moved to `new_offsets` and its
+/// entries ordered by member, then name
+fn rewrite_symbol_table(data: &[u8], width: usize, new_offsets: &HashMap<usize, usize>) -> Vec<u8> {
+    let read = |pos: usize| -> Option<usize> {
+        let bytes = data.get(pos..pos + width)?;
+        Some(bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize))
+    };
+    let Some(count) = read(0) else {
+        return data.to_vec();
+    };
+    let names_start = width * (count + 1);
+    let Some(names) = data.get(names_start..) else {
+        return data.to_vec();
+    };
+    let mut entries: Vec<(usize, &[u8])> = names
+        .split(|&b| b == 0)
+        .take(count)
+        .enumerate()
+        .filter_map(|(idx, name)| {
+            let offset = read(width * (idx + 1))?;
+            Some((*new_offsets.get(&offset).unwrap_or(&offset), name))
+        })
+        .collect();
+    if entries.len() != count {
+        return data.to_vec();
+    }
+    entries.sort();
+
+    let mut table = Vec::with_capacity(data.len());
+    table.extend_from_slice(&count.to_be_bytes()[usize::BITS as usize / 8 - width..]);
+    for (offset, _) in &entries {
+        table.extend_from_slice(&offset.to_be_bytes()[usize::BITS as usize / 8 - width..]);
+    }
+    for (_, name) in &entries {
+        table.extend_from_slice(name);
+        table.push(0);
+    }
+    table.resize(data.len(), 0);
+    table
+}
+
+/// `archive` with zeroed timestamps and owners and sorted members
+pub fn normalize_bytes(archive: &[u8]) -> Result<Vec<u8>> {
+    let members = parse(archive)?;
+    let long_names = members
+        .iter()
+        .find(|member| member.name_field() == "//")
+        .map_or(&[][..], |member| member.data);
+
+    let (special, mut objects): (Vec<&Member>, Vec<&Member>) =
+        members.iter().partition(|member| member.is_special());
+    if !members.iter().any(Member::is_bsd_symbol_table) {
+        objects.sort_by(|a, b| {
+            match a.resolved_name(long_names).cmp(b.resolved_name(long_names)) {
+                Ordering::Equal => a.data.cmp(b.data),
+                order => order,
+            }
+        });
+    }
+
+    let ordered: Vec<&Member> = special.into_iter().chain(objects).collect();
+    let mut new_offsets = HashMap::new();
+    let mut pos = MAGIC.len();
+    for member in &ordered {
+        new_offsets.insert(member.offset, pos);
+        pos += HEADER_LEN + member.data.len() + member.data.len() % 2;
+    }
+
+    let mut out = Vec::with_capacity(archive.len());
+    out.extend_from_slice(MAGIC);
+    for member in ordered {
+        let data = match member.name_field() {
+            "/" => rewrite_symbol_table(member.data, 4, &new_offsets),
+            "/SYM64/" => rewrite_symbol_table(member.data, 8, &new_offsets),
+            _ => member.data.to_vec(),
+        };
+        write_header(&mut out, member.name, data.len());
+        out.extend_from_slice(&data);
+        if data.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+    }
+    Ok(out)
+}
+
+/// Make the archive at `path` deterministic (see the module documentation)
+pub fn normalize(path: &Path) -> Result<()> {
+    let archive = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let normalized = normalize_bytes(&archive)
+        .with_context(|| format!("Failed to normalize {}", path.display()))?;
+    if normalized != archive {
+        fs::write(path, normalized)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{
+        Digest,
+        Sha256,
+    };
+
+    use super::*;
+
+    /// GNU archive of `objects` in the given order, with a symbol table
+    /// naming `sym_<object>` for each and a build timestamp
+    fn archive(objects: &[(&str, &[u8])], timestamp: u64) -> Vec<u8> {
+        let header = |name: &str, size: usize| {
+            format!("{name:<16}{timestamp:<12}{:<6}{:<6}{:<8}{size:<10}`\n", 1000, 1000, 100664)
+        };
+        let mut names = Vec::new();
+        for (name, _) in objects {
+            names.extend_from_slice(format!("sym_{name}").as_bytes());
+            names.push(0);
+        }
+        let table_len = 4 * (objects.len() + 1) + names.len();
+
+        let mut pos = MAGIC.len() + HEADER_LEN + table_len + table_len % 2;
+        let mut table = (objects.len() as u32).to_be_bytes().to_vec();
+        for (_, data) in objects {
+            table.extend_from_slice(&(pos as u32).to_be_bytes());
+            pos += HEADER_LEN + data.len() + data.len() % 2;
+        }
+        table.extend_from_slice(&names);
+
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(header("/", table.len()).as_bytes());
+        out.extend_from_slice(&table);
+        if table.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+        for (name, data) in objects {
+            out.extend_from_slice(header(&format!("{name}/"), data.len()).as_bytes());
+            out.extend_from_slice(data);
+            if data.len() % 2 == 1 {
+                out.push(b'\n');
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_reproducible_archives() {
+        let first = archive(&[("math.o", b"math code"), ("autozig.o", b"main")], 1_700_000_000);
+        let second = archive(&[("autozig.o", b"main"), ("math.o", b"math code")], 1_800_000_000);
+        assert_ne!(Sha256::digest(&first), Sha256::digest(&second));
+
+        let first = normalize_bytes(&first).unwrap();
+        let second = normalize_bytes(&second).unwrap();
+        assert_eq!(Sha256::digest(&first), Sha256::digest(&second));
+        assert_eq!(normalize_bytes(&first).unwrap(), first);
+
+        // The symbol table follows the members it points to
+        let members = parse(&first).unwrap();
+        assert_eq!(members[1].name_field(), "autozig.o/");
+        assert_eq!(members[2].name_field(), "math.o/");
+        assert_eq!(&first[8 + 16..8 + 40], b"0           0     0     ");
+        let table = members[0].data;
+        assert_eq!(&table[4..8], &(members[1].offset as u32).to_be_bytes());
+        assert_eq!(&table[8..12], &(members[2].offset as u32).to_be_bytes());
+        assert!(table.ends_with(b"sym_autozig.o\0sym_math.o\0"));
+        assert!(first.windows(12).all(|w| w != b"1700000000  "));
+    }
+}
@@ -0,0 +1,180 @@
+//! Zig structs generated from `#[autozig(bridge)]` Rust structs
+//!
+//! Marking a `#[repr(C)]` struct of an `autozig!` block with
+//! `#[autozig(bridge)]` makes it the single source of truth: the engine adds
+//! the matching `extern struct` to the block's Zig code instead of the Zig
+//! code repeating every field.
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     export fn length(v: Vec3) f32 { return @sqrt(v.x * v.x + v.y * v.y + v.z * v.z); }
+//!     ---
+//!     #[autozig(bridge)]
+//!     #[repr(C)]
+//!     #[derive(Clone, Copy)]
+//!     pub struct Vec3 { pub x: f32, pub y: f32, pub z: f32 }
+//!     fn length(v: Vec3) -> f32;
+//! }
+//! ```
+//!
+//! gives Zig `pub const Vec3 = extern struct { x: f32, y: f32, z: f32 };`.
+//! Field types must have a C layout: primitives, arrays, raw pointers and
+//! other `#[repr(C)]` types by name. A Zig declaration of the same name takes
+//! precedence.
+
+use syn::{
+    ItemStruct,
+    Type,
+};
+
+use crate::zig_enums::{
+    declares,
+    zig_ident,
+};
+
+/// Zig declarations for the `structs` that `zig_code` does not declare
+/// itself
+pub fn inject(zig_code: &str, structs: &[ItemStruct]) -> String {
+    let mut declarations = String::new();
+    for item in structs {
+        if declares(zig_code, &item.ident.to_string()) {
+            continue;
+        }
+        match zig_struct(item) {
+            Ok(declaration) => declarations.push_str(&declaration),
+            Err(message) => declarations
+                .push_str(&format!("comptime {{\n    @compileError(\"{message}\");\n}}\n")),
+        }
+    }
+    if declarations.is_empty() {
+        return zig_code.to_string();
+    }
+    format!("{zig_code}\n\n// Structs generated from their Rust definitions\n{declarations}")
+}
+
+/// `extern struct` declaration of a `#[repr(C)]` Rust struct
+fn zig_struct(item: &ItemStruct) -> Result<String, String> {
+    let name = &item.ident;
+    if !is_repr_c(item) {
+        return Err(format!("{name} must be #[repr(C)] to be bridged to Zig"));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(format!("{name} is generic and cannot be bridged to Zig"));
+    }
+    let syn::Fields::Named(fields) = &item.fields else {
+        return Err(format!("{name} must have named fields to be bridged to Zig"));
+    };
+
+    let mut zig_fields = String::new();
+    for field in &fields.named {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        if is_phantom(&field.ty) {
+            continue;
+        }
+        let ident = ident.to_string();
+        let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+        let Some(ty) = zig_type(&field.ty) else {
+            return Err(format!("{name}.{ident} has no C-compatible Zig equivalent"));
+        };
+        zig_fields.push_str(&format!("    {}: {ty},\n", zig_ident(ident)));
+    }
+    let visibility = if matches!(item.vis, syn::Visibility::Inherited) {
+        ""
+    } else {
+        "pub "
+    };
+    Ok(format!("{visibility}const {name} = extern struct {{\n{zig_fields}}};\n"))
+}
+
+fn is_repr_c(item: &ItemStruct) -> bool {
+    item.attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+        .any(|attr| {
+            let mut repr_c = false;
+            let _ = attr.parse_nested_meta(|meta| {
+                repr_c |= meta.path.is_ident("C");
+                Ok(())
+            });
+            repr_c
+        })
+}
+
+/// `PhantomData` fields take no space and have no Zig counterpart
+fn is_phantom(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == "PhantomData"))
+}
+
+/// Zig spelling of a field type
+fn zig_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+            if !matches!(segment.arguments, syn::PathArguments::None) {
+                return None;
+            }
+            let ident = segment.ident.to_string();
+            Some(
+                match ident.as_str() {
+                    "c_float" => "f32",
+                    "c_double" => "f64",
+                    "c_void" => "anyopaque",
+                    "char" => "u32",
+                    ident => ident,
+                }
+                .to_string(),
+            )
+        },
+        Type::Array(array) => {
+            let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(len), .. }) = &array.len else {
+                return None;
+            };
+            Some(format!("[{}]{}", len.base10_digits(), zig_type(&array.elem)?))
+        },
+        // Raw pointers may be null
+        Type::Ptr(ptr) => {
+            let constness = if ptr.mutability.is_some() {
+                ""
+            } else {
+                "const "
+            };
+            Some(format!("?*{constness}{}", zig_type(&ptr.elem)?))
+        },
+        Type::Paren(paren) => zig_type(&paren.elem),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_bridge_structs() {
+        let structs: Vec<ItemStruct> = [
+            "#[repr(C)] pub struct Vec3 { pub x: f32, pub y: f32, pub z: f32 }",
+            "#[repr(C)] struct Mesh { origin: Vec3, ids: [u16; 4], data: *const \
+             core::ffi::c_void, next: *mut Mesh, error: u8, marker: PhantomData<u8> }",
+            "#[repr(C)] struct Declared { a: i32 }",
+            "struct Loose { a: i32 }",
+            "#[repr(C)] struct Owned { name: Vec<u8> }",
+        ]
+        .iter()
+        .map(|source| syn::parse_str(source).unwrap())
+        .collect();
+
+        let zig = inject("const Declared = extern struct { a: i32 };", &structs);
+        assert!(zig.contains(
+            "pub const Vec3 = extern struct {\n    x: f32,\n    y: f32,\n    z: f32,\n};"
+        ));
+        assert!(zig.contains(
+            "const Mesh = extern struct {\n    origin: Vec3,\n    ids: [4]u16,\n    data: ?*const \
+             anyopaque,\n    next: ?*Mesh,\n    @\"error\": u8,\n};"
+        ));
+        assert!(!zig.contains("const Declared = extern struct {\n"));
+        assert!(zig.contains("@compileError(\"Loose must be #[repr(C)]"));
+        assert!(zig.contains("@compileError(\"Owned.name has no C-compatible Zig equivalent"));
+    }
+}
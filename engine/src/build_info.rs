@@ -0,0 +1,100 @@
+//! Build metadata exposed to the compiled crate
+//!
+//! Every build emits `AUTOZIG_ZIG_VERSION`, `AUTOZIG_TARGET` and
+//! `AUTOZIG_OPT_LEVEL` to the crate and writes the full record to
+//! `OUT_DIR/autozig_build_info.json` (exposed as `AUTOZIG_BUILD_INFO`).
+//! `autozig::build_info!()` turns them into an `autozig::BuildInfo` for logs
+//! and bug reports.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+
+use crate::manifest::json_string;
+
+/// How the crate's Zig code was compiled
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Version of the engine that ran the build
+    pub autozig_version: String,
+    /// Output of `zig version`, or `unknown` if Zig could not be run
+    pub zig_version: String,
+    /// Rust target triple
+    pub target: String,
+    /// Zig target triple (`native` for the host)
+    pub zig_target: String,
+    /// Zig optimization mode (`Debug`, `ReleaseFast`, ...)
+    pub opt_level: String,
+    /// Other code generation flags passed to Zig
+    pub flags: Vec<String>,
+    /// Compilation mode (`Merged`, `ModularImport`, `ModularBuildZig`)
+    pub mode: String,
+    /// Library kind (`static` or `dynamic`)
+    pub artifact: String,
+}
+
+impl BuildInfo {
+    /// The record as a JSON object
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        for (key, value) in [
+            ("autozigVersion", &self.autozig_version),
+            ("zigVersion", &self.zig_version),
+            ("target", &self.target),
+            ("zigTarget", &self.zig_target),
+            ("optLevel", &self.opt_level),
+            ("mode", &self.mode),
+            ("artifact", &self.artifact),
+        ] {
+            let _ = writeln!(out, "  \"{key}\": {},", json_string(value));
+        }
+        let flags: Vec<String> = self.flags.iter().map(|flag| json_string(flag)).collect();
+        let _ = writeln!(out, "  \"flags\": [{}]", flags.join(", "));
+        out.push_str("}\n");
+        out
+    }
+
+    /// Write the JSON record to `path` and expose it and the summary
+    /// variables to the crate being built
+    pub fn emit(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()).context("Failed to write build info")?;
+        println!("cargo:rustc-env=AUTOZIG_ZIG_VERSION={}", self.zig_version);
+        println!("cargo:rustc-env=AUTOZIG_TARGET={}", self.target);
+        println!("cargo:rustc-env=AUTOZIG_OPT_LEVEL={}", self.opt_level);
+        println!("cargo:rustc-env=AUTOZIG_BUILD_INFO={}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_json() {
+        let info = BuildInfo {
+            autozig_version: "0.1.2".to_string(),
+            zig_version: "0.15.2".to_string(),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            zig_target: "x86_64-linux-gnu".to_string(),
+            opt_level: "ReleaseFast".to_string(),
+            flags: vec!["-mcpu=baseline".to_string(), "-fstrip".to_string()],
+            mode: "ModularBuildZig".to_string(),
+            artifact: "static".to_string(),
+        };
+        assert_eq!(
+            info.to_json(),
+            "{\n  \"autozigVersion\": \"0.1.2\",\n  \"zigVersion\": \"0.15.2\",\n  \"target\": \
+             \"x86_64-unknown-linux-gnu\",\n  \"zigTarget\": \"x86_64-linux-gnu\",\n  \
+             \"optLevel\": \"ReleaseFast\",\n  \"mode\": \"ModularBuildZig\",\n  \"artifact\": \
+             \"static\",\n  \"flags\": [\"-mcpu=baseline\", \"-fstrip\"]\n}\n"
+        );
+    }
+}
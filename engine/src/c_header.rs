@@ -0,0 +1,205 @@
+//! C header for the exported Zig functions
+//!
+//! The static library built for Rust can be linked from C or C++ as well.
+//! The header declares every container-level `extern struct` and every
+//! `export fn` of the compiled sources, after autozig's own ABI lowering, so
+//! it describes exactly what the library exports. Declarations using types
+//! without a C equivalent are listed in a comment instead.
+
+use autozig_parser::type_mapper::zig_exports;
+
+use crate::layout_probe::extern_structs;
+
+/// Render a header declaring the exports and `extern struct`s of
+/// `zig_sources`
+///
+/// `guard` is the include guard macro, e.g. `MYZIG_H`.
+pub fn render(guard: &str, zig_sources: &[String]) -> String {
+    let mut structs = Vec::new();
+    let mut struct_names: Vec<String> = Vec::new();
+    let mut functions = Vec::new();
+    let mut skipped = Vec::new();
+
+    for source in zig_sources {
+        for s in extern_structs(source) {
+            if !struct_names.contains(&s.name) {
+                struct_names.push(s.name.clone());
+                structs.push(s);
+            }
+        }
+    }
+    for source in zig_sources {
+        for export in zig_exports(source) {
+            if functions
+                .iter()
+                .any(|(name, _): &(String, String)| *name == export.name)
+            {
+                continue;
+            }
+            let params: Option<Vec<String>> = export
+                .params
+                .iter()
+                .map(|(name, ty)| Some(format!("{} {name}", c_type(ty, &struct_names)?)))
+                .collect();
+            let ret = c_type(&export.return_type, &struct_names);
+            match (params, ret) {
+                (Some(params), Some(ret)) => {
+                    let params = if params.is_empty() {
+                        "void".to_string()
+                    } else {
+                        params.join(", ")
+                    };
+                    functions
+                        .push((export.name.clone(), format!("{ret} {}({params});", export.name)));
+                },
+                _ => skipped.push(export.name.clone()),
+            }
+        }
+    }
+
+    let mut header = String::new();
+    header.push_str("/* Generated by autozig from the crate's Zig sources. Do not edit. */\n");
+    header.push_str(&format!("#ifndef {guard}\n#define {guard}\n\n"));
+    header.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n\n");
+    header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+    for s in &structs {
+        let fields: Option<Vec<String>> = s
+            .fields
+            .iter()
+            .map(|(name, ty)| c_field(name, ty, &struct_names))
+            .collect();
+        match fields {
+            Some(fields) => {
+                header.push_str(&format!("typedef struct {} {{\n", s.name));
+                for field in fields {
+                    header.push_str(&format!("    {field};\n"));
+                }
+                header.push_str(&format!("}} {};\n\n", s.name));
+            },
+            None => skipped.push(s.name.clone()),
+        }
+    }
+    for (_, declaration) in &functions {
+        header.push_str(declaration);
+        header.push('\n');
+    }
+    if !skipped.is_empty() {
+        header.push_str(&format!(
+            "\n/* Not declared (no C equivalent for some types): {} */\n",
+            skipped.join(", ")
+        ));
+    }
+
+    header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    header.push_str(&format!("#endif /* {guard} */\n"));
+    header
+}
+
+/// Include guard for a header file name: `myzig.h` -> `MYZIG_H`
+pub fn guard_for(file_name: &str) -> String {
+    file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// C declaration of a struct field; arrays put their length after the name
+fn c_field(name: &str, ty: &str, structs: &[String]) -> Option<String> {
+    if let Some((len, elem)) = ty.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        if !len.is_empty() && len.chars().all(|c| c.is_ascii_digit()) {
+            return c_field(&format!("{name}[{len}]"), elem.trim(), structs);
+        }
+    }
+    Some(format!("{} {name}", c_type(ty, structs)?))
+}
+
+/// C spelling of a Zig type passed by value, if it has one
+fn c_type(ty: &str, structs: &[String]) -> Option<String> {
+    let ty = ty.trim();
+    let scalar = match ty {
+        "void" => "void",
+        "bool" => "bool",
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "isize" => "intptr_t",
+        "usize" => "size_t",
+        "f32" => "float",
+        "f64" => "double",
+        "c_char" => "char",
+        "c_short" => "short",
+        "c_ushort" => "unsigned short",
+        "c_int" => "int",
+        "c_uint" => "unsigned int",
+        "c_long" => "long",
+        "c_ulong" => "unsigned long",
+        "c_longlong" => "long long",
+        "c_ulonglong" => "unsigned long long",
+        "anyopaque" => "void",
+        _ => "",
+    };
+    if !scalar.is_empty() {
+        return Some(scalar.to_string());
+    }
+    if structs.iter().any(|s| s == ty) {
+        return Some(ty.to_string());
+    }
+
+    // Pointers: `*T`, `[*]T`, `[*c]T`, optionally `?` and `const`
+    let pointer = ty.strip_prefix('?').unwrap_or(ty).trim_start();
+    let pointee = ["[*c]", "[*]", "*"]
+        .iter()
+        .find_map(|prefix| pointer.strip_prefix(prefix))?
+        .trim_start();
+    let (is_const, pointee) = match pointee.strip_prefix("const ") {
+        Some(pointee) => (true, pointee),
+        None => (false, pointee),
+    };
+    let pointee = c_type(pointee, structs)?;
+    Some(if is_const {
+        format!("const {pointee}*")
+    } else {
+        format!("{pointee}*")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_header() {
+        let code = r#"
+const Point = extern struct { x: f32, y: f32, tags: [4]u8 };
+export fn dot(a: Point, b: Point) f32 { return a.x * b.x + a.y * b.y; }
+export fn checksum(ptr: [*]const u8, len: usize) u32 { _ = ptr; _ = len; return 0; }
+export fn reset() void {}
+export fn handle(h: ?*anyopaque) void { _ = h; }
+export fn wide(x: i128) i128 { return x; }
+"#;
+        let header = render(&guard_for("myzig.h"), &[code.to_string()]);
+        assert!(header.starts_with("/* Generated by autozig"));
+        assert!(header.contains("#ifndef MYZIG_H\n#define MYZIG_H\n"));
+        assert!(header.contains(
+            "typedef struct Point {\n    float x;\n    float y;\n    uint8_t tags[4];\n} Point;"
+        ));
+        assert!(header.contains("float dot(Point a, Point b);"));
+        assert!(header.contains("uint32_t checksum(const uint8_t* ptr, size_t len);"));
+        assert!(header.contains("void reset(void);"));
+        assert!(header.contains("void handle(void* h);"));
+        assert!(header.contains("no C equivalent for some types): wide"));
+        assert!(header.ends_with("#endif /* MYZIG_H */\n"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Content-addressed cache of compiled Zig libraries shared across crates
+//!
+//! Every crate normally compiles its Zig code into its own `OUT_DIR`. In a
+//! workspace where several crates (or several profiles of the same crate)
+//! produce identical Zig sources, the resulting static library is identical
+//! too, so it is stored once under `target/autozig-cache/`, keyed by the
+//! source contents, the Zig target and the Zig compiler version.
+//!
+//! Concurrent build scripts coordinate through a per-key lock file: the first
+//! one compiles and stores the library while the others wait and then reuse it.
+
+use std::{
+    env,
+    fs,
+    io::ErrorKind,
+    path::{
+        Path,
+        PathBuf,
+    },
+    thread,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+/// A lock older than this is assumed to belong to a killed build script
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shared cache directory of compiled Zig libraries
+#[derive(Debug, Clone)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+impl ArtifactCache {
+    /// Use `dir` as the cache directory
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Locate the cache for a build script running with `out_dir`
+    ///
+    /// `AUTOZIG_CACHE_DIR` overrides the location, then `CARGO_TARGET_DIR`.
+    /// Otherwise the target directory is derived from `OUT_DIR`
+    /// (`<target>/<profile>/build/<pkg>-<hash>/out`).
+    pub fn locate(out_dir: &Path) -> Option<Self> {
+        if let Ok(dir) = env::var("AUTOZIG_CACHE_DIR") {
+            return Some(Self::new(dir));
+        }
+        Some(Self::new(target_dir(out_dir)?.join("autozig-cache")))
+    }
+
+    /// Cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cache key for a library compiled from `sources` for `zig_target`
+    ///
+    /// `mode` distinguishes compilation pipelines that could produce
+    /// different libraries from the same sources.
+    pub fn key(
+        mode: &str,
+        zig_target: &str,
+        zig_version: &str,
+        sources: &[&Path],
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        for part in [mode, zig_target, zig_version] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for source in sources {
+            let data =
+                fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+            // File names matter for @import and build.zig references
+            hasher.update(source.file_name().unwrap_or_default().as_encoded_bytes());
+            hasher.update([0]);
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Path of the cached library for `key`
+    pub fn artifact_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.a"))
+    }
+
+    /// Copy the cached library for `key` to `dest`, returning whether it was
+    /// cached
+    pub fn fetch(&self, key: &str, dest: &Path) -> Result<bool> {
+        let artifact = self.artifact_path(key);
+        match fs::copy(&artifact, dest) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to copy {}", artifact.display())),
+        }
+    }
+
+    /// Store the library at `src` under `key`
+    ///
+    /// The library is written to a temporary file first and renamed into
+    /// place, so readers never observe a partial artifact.
+    pub fn store(&self, key: &str, src: &Path) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let tmp = self.dir.join(format!("{key}.a.{}.tmp", std::process::id()));
+        fs::copy(src, &tmp).with_context(|| format!("Failed to copy {}", src.display()))?;
+        fs::rename(&tmp, self.artifact_path(key)).context("Failed to store cached library")?;
+        Ok(())
+    }
+
+    /// Take the lock for `key`, waiting for other build scripts holding it
+    pub fn lock(&self, key: &str) -> Result<CacheLock> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let path = self.dir.join(format!("{key}.lock"));
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(CacheLock { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if is_stale(&path) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    thread::sleep(LOCK_POLL_INTERVAL);
+                },
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to create lock {}", path.display()))
+                },
+            }
+        }
+    }
+}
+
+/// Exclusive access to one cache key, released on drop
+#[derive(Debug)]
+pub struct CacheLock {
+    path: PathBuf,
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Zig's local cache directory for a build script running with `out_dir`
+///
+/// `ZIG_LOCAL_CACHE_DIR` wins, as it does for Zig itself. Otherwise every
+/// build script of the project shares `<target>/zig-cache`, instead of each
+/// `OUT_DIR` filling a cache of its own.
+pub fn zig_cache_dir(out_dir: &Path) -> Option<PathBuf> {
+    if let Ok(dir) = env::var("ZIG_LOCAL_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    Some(target_dir(out_dir)?.join("zig-cache"))
+}
+
+/// Cargo's target directory: `CARGO_TARGET_DIR`, else derived from `OUT_DIR`
+/// (`<target>/<profile>/build/<pkg>-<hash>/out`)
+fn target_dir(out_dir: &Path) -> Option<PathBuf> {
+    if let Ok(dir) = env::var("CARGO_TARGET_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let build_dir = out_dir
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == "build"))?;
+    Some(build_dir.parent()?.parent()?.to_path_buf())
+}
+
+fn is_stale(lock: &Path) -> bool {
+    fs::metadata(lock)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("autozig-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_locate_from_out_dir() {
+        if env::var("AUTOZIG_CACHE_DIR").is_ok() || env::var("CARGO_TARGET_DIR").is_ok() {
+            return;
+        }
+        let out_dir = Path::new("/work/target/debug/build/demo-0123/out");
+        let cache = ArtifactCache::locate(out_dir).unwrap();
+        assert_eq!(cache.dir(), Path::new("/work/target/autozig-cache"));
+    }
+
+    #[test]
+    fn test_zig_cache_dir_from_out_dir() {
+        if env::var("ZIG_LOCAL_CACHE_DIR").is_ok() || env::var("CARGO_TARGET_DIR").is_ok() {
+            return;
+        }
+        let out_dir = Path::new("/work/target/release/build/demo-0123/out");
+        assert_eq!(zig_cache_dir(out_dir).unwrap(), Path::new("/work/target/zig-cache"));
+    }
+
+    #[test]
+    fn test_key_depends_on_inputs() {
+        let dir = scratch_dir("key");
+        let source = dir.join("main.zig");
+        fs::write(&source, "export fn a() void {}").unwrap();
+
+        let key = ArtifactCache::key("merged", "native", "0.15.2", &[&source]).unwrap();
+        let same = ArtifactCache::key("merged", "native", "0.15.2", &[&source]).unwrap();
+        let other_target =
+            ArtifactCache::key("merged", "wasm32-freestanding", "0.15.2", &[&source]).unwrap();
+        assert_eq!(key, same);
+        assert_ne!(key, other_target);
+
+        fs::write(&source, "export fn b() void {}").unwrap();
+        let changed = ArtifactCache::key("merged", "native", "0.15.2", &[&source]).unwrap();
+        assert_ne!(key, changed);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_and_fetch() {
+        let dir = scratch_dir("store");
+        let cache = ArtifactCache::new(dir.join("cache"));
+        let lib = dir.join("libdemo.a");
+        let dest = dir.join("libcopy.a");
+        fs::write(&lib, b"!<arch>\n").unwrap();
+
+        let lock = cache.lock("abc").unwrap();
+        assert!(!cache.fetch("abc", &dest).unwrap());
+        cache.store("abc", &lib).unwrap();
+        drop(lock);
+
+        assert!(!dir.join("cache/abc.lock").exists());
+        assert!(cache.fetch("abc", &dest).unwrap());
+        assert_eq!(fs::read(&dest).unwrap(), b"!<arch>\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
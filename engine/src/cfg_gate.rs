@@ -0,0 +1,190 @@
+//! `#[cfg]` gating of Zig exports
+//!
+//! A signature declared with `#[cfg(...)]` in an `autozig!` block only exists
+//! on matching targets: the macros copy the attribute onto its bindings, and
+//! the engine evaluates it against the `CARGO_CFG_*`/`CARGO_FEATURE_*`
+//! variables cargo passes to build scripts. On other targets the Zig function
+//! loses its `export`, so Zig neither analyzes nor emits it:
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     export fn dot_avx2(a: [*]const f32, b: [*]const f32, len: usize) f32 { ... }
+//!     ---
+//!     #[cfg(target_arch = "x86_64")]
+//!     fn dot_avx2(a: &[f32], b: &[f32]) -> f32;
+//! }
+//! ```
+//!
+//! Predicates a build script cannot decide (`test`, `doc`, or any cfg when
+//! the engine runs outside cargo) keep the export.
+
+use std::env;
+
+use syn::{
+    punctuated::Punctuated,
+    Meta,
+    Token,
+};
+
+/// cfg names whose value differs between the build script and the crate
+const UNDECIDABLE: &[&str] = &["test", "doc", "doctest", "miri"];
+
+/// Whether the bindings carrying `cfg_attrs` exist on the target being built,
+/// or `None` if that cannot be decided
+pub fn is_enabled(cfg_attrs: &[syn::Attribute]) -> Option<bool> {
+    // Not running under cargo
+    env::var_os("CARGO_CFG_TARGET_ARCH")?;
+    all_enabled(cfg_attrs, &|key| env::var(key).ok())
+}
+
+/// [`is_enabled`] with cargo's variables looked up through `lookup`
+fn all_enabled(
+    cfg_attrs: &[syn::Attribute],
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Option<bool> {
+    all(cfg_attrs.iter().map(|attr| {
+        attr.parse_args::<Meta>()
+            .ok()
+            .and_then(|meta| evaluate(&meta, lookup))
+    }))
+}
+
+/// Evaluate one cfg predicate
+fn evaluate(meta: &Meta, lookup: &dyn Fn(&str) -> Option<String>) -> Option<bool> {
+    let name = meta.path().get_ident()?.to_string();
+    match meta {
+        Meta::Path(_) if UNDECIDABLE.contains(&name.as_str()) => None,
+        Meta::Path(_) => Some(lookup(&cfg_var(&name)).is_some()),
+        Meta::NameValue(name_value) => {
+            let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(value), .. }) = &name_value.value
+            else {
+                return None;
+            };
+            let value = value.value();
+            if name == "feature" {
+                let var = format!("CARGO_FEATURE_{}", value.to_uppercase().replace('-', "_"));
+                return Some(lookup(&var).is_some());
+            }
+            Some(
+                lookup(&cfg_var(&name))
+                    .is_some_and(|values| values.split(',').any(|item| item == value)),
+            )
+        },
+        Meta::List(list) => {
+            let nested = list
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok()?;
+            let results = nested.iter().map(|meta| evaluate(meta, lookup));
+            match name.as_str() {
+                "all" => all(results),
+                "any" => any(results),
+                "not" if nested.len() == 1 => evaluate(&nested[0], lookup).map(|value| !value),
+                _ => None,
+            }
+        },
+    }
+}
+
+/// Environment variable cargo sets for the cfg `name`
+fn cfg_var(name: &str) -> String {
+    format!("CARGO_CFG_{}", name.to_uppercase())
+}
+
+/// Three-valued AND: false wins over undecided
+fn all(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut decided = true;
+    for result in results {
+        match result {
+            Some(false) => return Some(false),
+            None => decided = false,
+            Some(true) => {},
+        }
+    }
+    decided.then_some(true)
+}
+
+/// Three-valued OR: true wins over undecided
+fn any(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut decided = true;
+    for result in results {
+        match result {
+            Some(true) => return Some(true),
+            None => decided = false,
+            Some(false) => {},
+        }
+    }
+    decided.then_some(false)
+}
+
+/// Turn `export fn <name>` into a plain `fn` for each of `names`
+pub fn unexport(zig_code: &str, names: &[String]) -> String {
+    let mut result = String::with_capacity(zig_code.len());
+    let mut rest = zig_code;
+
+    while let Some(pos) = rest.find("export fn ") {
+        let after = &rest[pos + "export fn ".len()..];
+        let name_end = after
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..name_end];
+        let is_keyword = !rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+        if is_keyword && names.iter().any(|n| n == name) {
+            result.push_str(&rest[..pos]);
+            result.push_str("fn ");
+        } else {
+            result.push_str(&rest[..pos + "export fn ".len()]);
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(tokens: &str) -> Vec<syn::Attribute> {
+        let item: syn::ItemFn = syn::parse_str(&format!("{tokens} fn f() {{}}")).unwrap();
+        item.attrs
+    }
+
+    #[test]
+    fn test_evaluate_cfg() {
+        let lookup = |key: &str| match key {
+            "CARGO_CFG_TARGET_ARCH" => Some("x86_64".to_string()),
+            "CARGO_CFG_TARGET_FEATURE" => Some("fxsr,sse,sse2".to_string()),
+            "CARGO_CFG_UNIX" => Some(String::new()),
+            "CARGO_FEATURE_FAST_MATH" => Some("1".to_string()),
+            _ => None,
+        };
+        let enabled = |tokens: &str| all_enabled(&cfg(tokens), &lookup);
+
+        assert_eq!(enabled(r#"#[cfg(target_arch = "x86_64")]"#), Some(true));
+        assert_eq!(enabled(r#"#[cfg(target_arch = "aarch64")]"#), Some(false));
+        assert_eq!(enabled(r#"#[cfg(target_feature = "sse2")]"#), Some(true));
+        assert_eq!(enabled(r#"#[cfg(all(unix, not(target_feature = "avx2")))]"#), Some(true));
+        assert_eq!(enabled(r#"#[cfg(any(windows, feature = "fast-math"))]"#), Some(true));
+        assert_eq!(enabled("#[cfg(windows)]"), Some(false));
+        assert_eq!(enabled("#[cfg(test)]"), None);
+        assert_eq!(enabled("#[cfg(all(test, windows))]"), Some(false));
+        assert_eq!(enabled("#[cfg(any(test, windows))]"), None);
+        assert_eq!(enabled("#[cfg(unix)] #[cfg(windows)]"), Some(false));
+    }
+
+    #[test]
+    fn test_unexport() {
+        let code = "export fn dot(a: f32) f32 { return a; }\npub export fn dot_avx2(a: f32) f32 { \
+                    return a; }\nexport fn dot_avx2x(a: f32) f32 { return a; }\n// reexport fn \
+                    dot_avx2\n";
+        assert_eq!(
+            unexport(code, &["dot_avx2".to_string()]),
+            "export fn dot(a: f32) f32 { return a; }\npub fn dot_avx2(a: f32) f32 { return a; \
+             }\nexport fn dot_avx2x(a: f32) f32 { return a; }\n// reexport fn dot_avx2\n"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Zig specializations for `#[monomorphize_const(...)]`
+//!
+//! A signature `fn blur<const RADIUS: usize>(img: &mut [u8])` with
+//! `#[monomorphize_const(1, 3, 5)]` binds one export per value: `blur_1`,
+//! `blur_3` and `blur_5`. The Zig side implements `blur` once, taking a
+//! `comptime` parameter named like the const generic, and the engine
+//! generates the exports calling it:
+//!
+//! ```zig
+//! fn blur(comptime RADIUS: usize, img_ptr: [*]u8, img_len: usize) void { ... }
+//!
+//! // generated
+//! export fn blur_3(img_ptr: [*]u8, img_len: usize) void {
+//!     return blur(3, img_ptr, img_len);
+//! }
+//! ```
+
+use autozig_parser::type_mapper::zig_function;
+
+/// Exports specializing the Zig function `name` for each of `values` of its
+/// comptime parameter `param`
+///
+/// A missing function or parameter becomes a `@compileError`, so the Zig
+/// build reports it.
+pub fn specialize(zig_code: &str, name: &str, param: &str, values: &[String]) -> String {
+    let Some(function) = zig_function(zig_code, name) else {
+        return compile_error(&format!(
+            "#[monomorphize_const] on `{name}` needs a Zig `fn {name}(comptime {param}: ..)`"
+        ));
+    };
+    let Some(position) = function.params.iter().position(|(p, _)| p == param) else {
+        return compile_error(&format!(
+            "Zig `fn {name}` has no comptime parameter `{param}` for #[monomorphize_const]"
+        ));
+    };
+
+    let params: Vec<String> = function
+        .params
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != position)
+        .map(|(_, (p, ty))| format!("{p}: {ty}"))
+        .collect();
+    let mut exports = String::from("\n// Specializations for #[monomorphize_const]\n");
+    for value in values {
+        let args: Vec<&str> = function
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| {
+                if i == position {
+                    value.as_str()
+                } else {
+                    p.as_str()
+                }
+            })
+            .collect();
+        exports.push_str(&format!(
+            "export fn {name}_{value}({}) {} {{\n    return {name}({});\n}}\n",
+            params.join(", "),
+            function.return_type,
+            args.join(", ")
+        ));
+    }
+    exports
+}
+
+fn compile_error(message: &str) -> String {
+    format!("\ncomptime {{\n    @compileError(\"{message}\");\n}}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specialize() {
+        let code = "fn blur(img_ptr: [*]u8, comptime RADIUS: usize, img_len: usize) void {}";
+        let values = ["1".to_string(), "3".to_string()];
+        assert_eq!(
+            specialize(code, "blur", "RADIUS", &values),
+            "\n// Specializations for #[monomorphize_const]\nexport fn blur_1(img_ptr: [*]u8, \
+             img_len: usize) void {\n    return blur(img_ptr, 1, img_len);\n}\nexport fn \
+             blur_3(img_ptr: [*]u8, img_len: usize) void {\n    return blur(img_ptr, 3, \
+             img_len);\n}\n"
+        );
+        assert!(specialize(code, "blur", "R", &values).contains("@compileError"));
+        assert!(specialize(code, "sharpen", "RADIUS", &values).contains("@compileError"));
+    }
+}
@@ -0,0 +1,125 @@
+//! Files a build depends on, for precise `cargo:rerun-if-changed` lines
+//!
+//! Watching the whole source directory reruns the build script for every
+//! Rust edit, yet misses Zig files imported from outside it. Instead, the
+//! engine starts from the crate's Rust, Zig, C and C++ sources and follows
+//! `@import("file.zig")`, `@cInclude("file.h")` and `#include "file.h"`
+//! relative to the including file, listing every local file reached.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+
+/// `roots` and every local file they import or include, transitively
+///
+/// Only existing files are listed; package imports such as
+/// `@import("std")` and system headers (`#include <stdio.h>`) are not files
+/// of the crate.
+pub fn closure(roots: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut seen = BTreeSet::new();
+    let mut pending: Vec<PathBuf> = roots.into_iter().map(|root| normalize(&root)).collect();
+    while let Some(path) = pending.pop() {
+        if !path.is_file() || !seen.insert(path.clone()) {
+            continue;
+        }
+        let is_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                matches!(ext, "zig" | "c" | "h" | "cpp" | "cc" | "cxx" | "hpp" | "hh")
+            });
+        let (Some(dir), true) = (path.parent(), is_source) else {
+            continue;
+        };
+        let Ok(code) = fs::read_to_string(&path) else {
+            continue;
+        };
+        for import in local_imports(&code) {
+            pending.push(normalize(&dir.join(import)));
+        }
+    }
+    seen.into_iter().collect()
+}
+
+/// `path` with `.` and `..` components resolved lexically, so that a file
+/// reached along two paths is listed (and read) once
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Relative paths imported by Zig or C `code`
+fn local_imports(code: &str) -> Vec<&str> {
+    let mut imports = Vec::new();
+    for marker in ["@import(\"", "@cInclude(\"", "#include \""] {
+        let mut rest = code;
+        while let Some(pos) = rest.find(marker) {
+            rest = &rest[pos + marker.len()..];
+            let Some(end) = rest.find('"') else {
+                break;
+            };
+            let path = &rest[..end];
+            let is_file = Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    matches!(ext, "zig" | "zon" | "c" | "h" | "cpp" | "cc" | "cxx" | "hpp" | "hh")
+                });
+            if is_file && !Path::new(path).is_absolute() {
+                imports.push(path);
+            }
+        }
+    }
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_closure_follows_imports() {
+        let dir = env::temp_dir().join(format!("autozig-deps-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("zig/util")).unwrap();
+        fs::write(
+            dir.join("zig/math.zig"),
+            "const std = @import(\"std\");\nconst v = @import(\"util/vec.zig\");\nconst c = \
+             @cImport(@cInclude(\"fast.h\"));\n",
+        )
+        .unwrap();
+        fs::write(dir.join("zig/util/vec.zig"), "const m = @import(\"../math.zig\");\n").unwrap();
+        fs::write(dir.join("zig/fast.h"), "#include <stdint.h>\n#include \"missing.h\"\n").unwrap();
+        fs::write(dir.join("src/lib.rs"), "include_zig!(\"zig/math.zig\", {});\n").unwrap();
+
+        let deps = closure([dir.join("src/lib.rs"), dir.join("zig/math.zig")]);
+        let names: Vec<_> = deps
+            .iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&PathBuf::from("zig/fast.h")));
+        assert!(names.contains(&PathBuf::from("zig/util/vec.zig")));
+        assert!(names.contains(&PathBuf::from("zig/math.zig")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+//! Map Zig compiler diagnostics back to the Rust files they came from
+//!
+//! Embedded Zig is copied verbatim from each `autozig!` block and wrapped in
+//! origin markers recording the Rust file and line it starts at:
+//!
+//! ```text
+//! // @autozig:origin src/lib.rs:12
+//! ...zig code...
+//! // @autozig:origin end
+//! ```
+//!
+//! When Zig reports `generated_autozig.zig:15:9: error: ...`, the nearest
+//! marker above line 15 turns that into `src/lib.rs:14:9: error: ...`.
+
+use std::{
+    collections::HashMap,
+    fs,
+};
+
+/// Prefix of the comment lines delimiting embedded code
+pub const ORIGIN_MARKER: &str = "// @autozig:origin ";
+
+/// A position in a Rust source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustLocation {
+    pub file: String,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+}
+
+/// Wrap embedded Zig `code` starting at `line` of `file` in origin markers
+pub fn wrap_with_origin(code: &str, file: &str, line: usize) -> String {
+    format!("{ORIGIN_MARKER}{file}:{line}\n{code}\n{ORIGIN_MARKER}end")
+}
+
+/// Rust location of `line`/`column` (1-based) in a generated Zig file
+pub fn map_location(generated: &str, line: usize, column: usize) -> Option<RustLocation> {
+    let lines: Vec<&str> = generated.lines().collect();
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+
+    // Nearest marker above the diagnostic decides which block it belongs to
+    let (marker_idx, origin) = lines[..line - 1]
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(idx, text)| Some((idx, text.trim().strip_prefix(ORIGIN_MARKER)?)))?;
+    if origin == "end" {
+        return None;
+    }
+
+    let (file, start_line) = origin.rsplit_once(':')?;
+    let start_line: usize = start_line.parse().ok()?;
+    Some(RustLocation {
+        file: file.to_string(),
+        line: start_line + (line - marker_idx - 2),
+        column,
+    })
+}
+
+/// Rewrite `path.zig:line:col:` prefixes in compiler output to the Rust
+/// locations they map to, leaving other lines untouched
+pub fn remap_diagnostics(output: &str) -> String {
+    let mut files: HashMap<String, Option<String>> = HashMap::new();
+    let mut result = String::with_capacity(output.len());
+
+    for line in output.lines() {
+        let remapped = parse_zig_location(line).and_then(|(path, row, col, rest)| {
+            let generated = files
+                .entry(path.to_string())
+                .or_insert_with(|| fs::read_to_string(path).ok())
+                .as_deref()?;
+            let loc = map_location(generated, row, col)?;
+            Some(format!("{}:{}:{}:{}", loc.file, loc.line, loc.column, rest))
+        });
+        result.push_str(remapped.as_deref().unwrap_or(line));
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Split `path.zig:line:col:rest` into its parts
+fn parse_zig_location(line: &str) -> Option<(&str, usize, usize, &str)> {
+    let path_end = line.find(".zig:")? + ".zig".len();
+    let path = &line[..path_end];
+    let mut parts = line[path_end + 1..].splitn(3, ':');
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    let rest = parts.next()?;
+    Some((path, row, col, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENERATED: &str = concat!(
+        "const std = @import(\"std\");\n",
+        "// @autozig:origin src/lib.rs:10\n",
+        "export fn add(a: i32, b: i32) i32 {\n",
+        "    return a + b\n",
+        "}\n",
+        "// @autozig:origin end\n",
+        "fn wrapper() void {}\n",
+    );
+
+    #[test]
+    fn test_map_location() {
+        assert_eq!(
+            map_location(GENERATED, 4, 17),
+            Some(RustLocation {
+                file: "src/lib.rs".to_string(),
+                line: 11,
+                column: 17
+            })
+        );
+        // Outside any block
+        assert_eq!(map_location(GENERATED, 1, 1), None);
+        assert_eq!(map_location(GENERATED, 7, 1), None);
+    }
+
+    #[test]
+    fn test_remap_diagnostics() {
+        let dir = std::env::temp_dir().join(format!("autozig-diag-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let zig_file = dir.join("generated_autozig.zig");
+        fs::write(&zig_file, GENERATED).unwrap();
+
+        let output = format!(
+            "{}:4:17: error: expected ';' after statement\n    return a + b\n",
+            zig_file.display()
+        );
+        let remapped = remap_diagnostics(&output);
+        assert!(remapped.starts_with("src/lib.rs:11:17: error: expected ';' after statement\n"));
+        assert!(remapped.contains("    return a + b"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Lowering of exported Zig functions that return error unions
+//!
+//! `export fn parse(..) ParseError!i32` is renamed to the internal
+//! `parse__autozig_impl`, and a C-ABI export with the original name returns
+//! `AutoZigResult(i32, u16)` instead, carrying the error as its code in the
+//! declared error set (see [`autozig_parser::error_union`]). The regular ABI
+//! lowering then treats it like any other `Result` return.
+//!
+//! With stack traces enabled, the export first hands the error return trace
+//! to Rust (see `autozig::zig_trace`).
+
+use anyhow::{
+    bail,
+    Result,
+};
+use autozig_parser::{
+    error_union::{
+        export_error_union,
+        ErrorUnion,
+    },
+    type_mapper::zig_exports,
+};
+
+/// Rewrite every error-union `export fn` in `zig_code` into a C-ABI export,
+/// recording error return traces if `trace_errors`
+pub fn lower_error_unions(zig_code: &str, trace_errors: bool) -> Result<String> {
+    let mut lowered = zig_code.to_string();
+    let mut wrappers = String::new();
+    for export in zig_exports(zig_code) {
+        let Some(error_union) = export_error_union(zig_code, &export.name) else {
+            continue;
+        };
+        let error_union = match error_union {
+            Ok(error_union) => error_union,
+            Err(reason) => bail!("{reason}"),
+        };
+
+        lowered = rename_export(&lowered, &export.name, "__autozig_impl");
+        let params: Vec<String> = export
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect();
+        let args: Vec<&str> = export
+            .params
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        wrappers.push_str(&wrapper(
+            &export.name,
+            &params.join(", "),
+            &args.join(", "),
+            &error_union,
+            trace_errors,
+        ));
+    }
+
+    if wrappers.is_empty() {
+        return Ok(lowered);
+    }
+    lowered.push_str("\n\n// Error unions lowered to AutoZigResult(T, u16)\n");
+    lowered.push_str(&wrappers);
+    Ok(lowered)
+}
+
+/// `export fn name(` -> `fn name<suffix>(`
+pub(crate) fn rename_export(code: &str, name: &str, suffix: &str) -> String {
+    let mut result = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(pos) = rest.find("export") {
+        let after = &rest[pos + "export".len()..];
+        let decl = after.trim_start().strip_prefix("fn").map(str::trim_start);
+        let matches = decl
+            .and_then(|decl| decl.strip_prefix(name))
+            .is_some_and(|tail| tail.trim_start().starts_with('('));
+        if matches {
+            let decl = decl.unwrap_or_default();
+            result.push_str(&rest[..pos]);
+            result.push_str(&format!("fn {name}{suffix}"));
+            rest = &decl[name.len()..];
+        } else {
+            result.push_str(&rest[..pos + "export".len()]);
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn wrapper(
+    name: &str,
+    params: &str,
+    args: &str,
+    error_union: &ErrorUnion,
+    trace_errors: bool,
+) -> String {
+    // `void` cannot be a field of an extern struct; `u8` has the same layout
+    // as the `()` payload on the Rust side here
+    let payload = match error_union.payload.as_str() {
+        "void" => "u8",
+        payload => payload,
+    };
+    let result = format!("AutoZigResult({payload}, u16)");
+
+    let mut codes = String::new();
+    for (index, error) in error_union.errors.iter().enumerate() {
+        codes.push_str(&format!("        error.{error} => {},\n", index + 1));
+    }
+    let call = if trace_errors {
+        format!(
+            "{name}__autozig_impl({args}) catch |err| {{\n        \
+             autozig_trace.recordError(@errorReturnTrace());\n        return {result}.err(switch \
+             (err) {{\n{codes}        }});\n    }}"
+        )
+    } else {
+        format!(
+            "{name}__autozig_impl({args}) catch |err| return {result}.err(switch (err) \
+             {{\n{codes}    }})"
+        )
+    };
+    let body = if error_union.payload == "void" {
+        format!("    {call};\n    return {result}.ok(0);\n")
+    } else {
+        format!("    const value = {call};\n    return {result}.ok(value);\n")
+    };
+    format!("export fn {name}({params}) {result} {{\n{body}}}\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_error_unions() {
+        let code = r#"const ParseError = error{ InvalidDigit, Overflow };
+export fn parse(ptr: [*]const u8, len: usize) ParseError!i32 { _ = ptr; _ = len; return 0; }
+export fn reset() error{Busy}!void {}
+export fn add(a: i32, b: i32) i32 { return a + b; }
+"#;
+        let lowered = lower_error_unions(code, false).unwrap();
+        assert!(lowered.contains("fn parse__autozig_impl(ptr: [*]const u8"));
+        assert!(lowered.contains("fn reset__autozig_impl()"));
+        assert!(lowered.contains("export fn add(a: i32, b: i32) i32"));
+        assert!(lowered.contains(
+            "export fn parse(ptr: [*]const u8, len: usize) AutoZigResult(i32, u16) {\n    const \
+             value = parse__autozig_impl(ptr, len) catch |err| return AutoZigResult(i32, \
+             u16).err(switch (err) {\n        error.InvalidDigit => 1,\n        error.Overflow => \
+             2,\n    });\n    return AutoZigResult(i32, u16).ok(value);\n}"
+        ));
+        assert!(lowered.contains("return AutoZigResult(u8, u16).ok(0);"));
+
+        assert_eq!(
+            lower_error_unions("export fn f() u8 { return 1; }", false).unwrap(),
+            "export fn f() u8 { return 1; }"
+        );
+        let err = lower_error_unions("export fn f() !u8 { return 1; }", false).unwrap_err();
+        assert!(err.to_string().contains("inferred"));
+
+        let traced = lower_error_unions(code, true).unwrap();
+        assert!(traced.contains(
+            "catch |err| {\n        autozig_trace.recordError(@errorReturnTrace());\n        \
+             return AutoZigResult(u8, u16).err(switch (err) {\n        error.Busy => 1,\n        \
+             });\n    };\n    return AutoZigResult(u8, u16).ok(0);"
+        ));
+    }
+}
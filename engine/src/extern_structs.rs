@@ -0,0 +1,155 @@
+//! C layout for Zig structs crossing the FFI boundary
+//!
+//! A plain Zig `struct` has no defined layout: Zig may reorder and pad its
+//! fields as it likes. Zig rejects one in an `export fn` signature, but the ABI
+//! lowering hands structs over through pointers (`name__autozig_out`), which
+//! Zig accepts for any struct, so a plain struct would silently disagree with
+//! its `#[repr(C)]` Rust mirror.
+//!
+//! Plain structs that an export passes by value, in an array or behind a
+//! many-item pointer (`[*]T`) are therefore made `extern struct`, together
+//! with the plain structs nested in their fields. Single-item pointers (`*T`,
+//! `?*T`) are opaque handles on the Rust side and leave the struct alone.
+
+use std::collections::BTreeSet;
+
+use autozig_parser::type_mapper::zig_exports;
+
+use crate::layout_probe::{
+    declared_name,
+    fields,
+    matching_brace,
+};
+
+/// A `const Name = struct { ... }` definition without `extern` or `packed`
+struct PlainStruct {
+    name: String,
+    /// Byte offset of the `struct` keyword
+    keyword: usize,
+    /// Field types
+    fields: Vec<String>,
+}
+
+/// Make every plain struct whose layout Rust depends on an `extern struct`
+///
+/// Returns the rewritten code and the names of the structs that changed.
+pub fn require_extern(zig_code: &str) -> (String, Vec<String>) {
+    let plain = plain_structs(zig_code);
+    if plain.is_empty() {
+        return (zig_code.to_string(), Vec::new());
+    }
+
+    let mut pending: Vec<String> = zig_exports(zig_code)
+        .iter()
+        .flat_map(|export| {
+            export
+                .params
+                .iter()
+                .map(|(_, ty)| ty.as_str())
+                .chain([export.return_type.as_str()])
+                .flat_map(layout_idents)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let mut required = BTreeSet::new();
+    while let Some(name) = pending.pop() {
+        let Some(plain_struct) = plain.iter().find(|s| s.name == name) else {
+            continue;
+        };
+        if required.insert(name) {
+            pending.extend(plain_struct.fields.iter().flat_map(|ty| layout_idents(ty)));
+        }
+    }
+
+    let mut code = zig_code.to_string();
+    for plain_struct in plain.iter().rev() {
+        if required.contains(&plain_struct.name) {
+            code.insert_str(plain_struct.keyword, "extern ");
+        }
+    }
+    (code, required.into_iter().collect())
+}
+
+/// Identifiers in `ty` whose layout is visible to Rust
+fn layout_idents(ty: &str) -> Vec<String> {
+    let ty = ty.trim();
+    if ty.starts_with('*') || ty.starts_with("?*") {
+        return Vec::new();
+    }
+    ty.split(|c: char| !is_ident_char(c))
+        .filter(|ident| ident.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Plain struct definitions in `zig_code`, in source order
+fn plain_structs(zig_code: &str) -> Vec<PlainStruct> {
+    let mut structs = Vec::new();
+    let mut search_from = 0;
+    while let Some(offset) = zig_code[search_from..].find("struct") {
+        let keyword = search_from + offset;
+        search_from = keyword + "struct".len();
+
+        let before = &zig_code[..keyword];
+        let after = &zig_code[search_from..];
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        if before[line_start..].contains("//") || after.starts_with(is_ident_char) {
+            continue;
+        }
+        // `extern struct` / `packed struct` end in a keyword instead of `=`
+        let Some(name) = declared_name(before) else {
+            continue;
+        };
+        let Some(body) = after.trim_start().strip_prefix('{') else {
+            continue;
+        };
+        let Some(body_len) = matching_brace(body) else {
+            continue;
+        };
+        structs.push(PlainStruct {
+            name,
+            keyword,
+            fields: fields(&body[..body_len])
+                .into_iter()
+                .map(|(_, ty)| ty)
+                .collect(),
+        });
+    }
+    structs
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_extern() {
+        let code = r#"pub const Vec3 = struct { x: f32, y: f32, z: f32 };
+const Transform = struct {
+    position: Vec3,
+    scale: f32 = 1.0,
+
+    pub fn identity() Transform { return .{ .position = .{ .x = 0, .y = 0, .z = 0 } }; }
+};
+const Handle = struct { items: std.ArrayList(u8) };
+const Pixel = packed struct { r: u8, g: u8, b: u8, a: u8 };
+const Color = extern struct { r: f32, g: f32, b: f32 };
+// const Transform = struct {
+export fn make(scale: f32) Transform { return .{ .position = undefined, .scale = scale }; }
+export fn open() ?*Handle { return null; }
+export fn fill(out: [*]Pixel, len: usize, c: Color) void { _ = out; _ = len; _ = c; }
+"#;
+        let (code, changed) = require_extern(code);
+        assert_eq!(changed, ["Transform", "Vec3"]);
+        assert!(code.starts_with("pub const Vec3 = extern struct { x: f32"));
+        assert!(code.contains("\nconst Transform = extern struct {\n"));
+        assert!(code.contains("const Handle = struct {"));
+        assert!(code.contains("const Pixel = packed struct {"));
+        assert!(code.contains("const Color = extern struct {"));
+        assert!(code.contains("// const Transform = struct {"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Passing Zig `f16` by value as its bit pattern
+//!
+//! C ABIs pass `_Float16` in floating-point registers, while Rust's
+//! `half::f16` is a `u16` newtype passed like an integer. An `export fn`
+//! taking or returning `f16` by value is renamed to `name__autozig_f16` and
+//! wrapped by a C-ABI export with the original name that exchanges `u16`
+//! bits instead; the macros convert with `f16::to_bits` / `f16::from_bits`.
+//! `f16` behind pointers and in slices has the same layout on both sides
+//! and is left alone.
+
+use autozig_parser::type_mapper::zig_exports;
+
+use crate::error_union::rename_export;
+
+/// Rewrite every `export fn` passing `f16` by value in `zig_code`
+pub fn lower_f16_exports(zig_code: &str) -> String {
+    let mut lowered = zig_code.to_string();
+    let mut wrappers = String::new();
+    for export in zig_exports(zig_code) {
+        let returns_f16 = export.return_type == "f16";
+        if !returns_f16 && !export.params.iter().any(|(_, ty)| ty == "f16") {
+            continue;
+        }
+
+        lowered = rename_export(&lowered, &export.name, "__autozig_f16");
+        let mut params = Vec::new();
+        let mut args = Vec::new();
+        for (name, ty) in &export.params {
+            if ty == "f16" {
+                params.push(format!("{name}: u16"));
+                args.push(format!("@as(f16, @bitCast({name}))"));
+            } else {
+                params.push(format!("{name}: {ty}"));
+                args.push(name.clone());
+            }
+        }
+        let call = format!("{}__autozig_f16({})", export.name, args.join(", "));
+        let (return_type, body) = if returns_f16 {
+            ("u16", format!("return @bitCast({call});"))
+        } else {
+            (export.return_type.as_str(), format!("return {call};"))
+        };
+        wrappers.push_str(&format!(
+            "export fn {}({}) {return_type} {{\n    {body}\n}}\n\n",
+            export.name,
+            params.join(", ")
+        ));
+    }
+
+    if wrappers.is_empty() {
+        return lowered;
+    }
+    lowered.push_str("\n\n// f16 passed by value lowered to u16 bits\n");
+    lowered.push_str(&wrappers);
+    lowered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_f16_exports() {
+        let code = r#"export fn mix(a: f16, b: f16, t: f32) f16 { return a + (b - a) * @as(f16, @floatCast(t)); }
+export fn sum(ptr: [*]const f16, len: usize) f32 { _ = ptr; _ = len; return 0; }
+export fn store(out: *f16, v: f16) void { out.* = v; }
+"#;
+        let lowered = lower_f16_exports(code);
+        assert!(lowered.contains("fn mix__autozig_f16(a: f16, b: f16, t: f32) f16"));
+        assert!(lowered.contains(
+            "export fn mix(a: u16, b: u16, t: f32) u16 {\n    return \
+             @bitCast(mix__autozig_f16(@as(f16, @bitCast(a)), @as(f16, @bitCast(b)), t));\n}"
+        ));
+        assert!(lowered.contains(
+            "export fn store(out: *f16, v: u16) void {\n    return store__autozig_f16(out, \
+             @as(f16, @bitCast(v)));\n}"
+        ));
+        assert!(lowered.contains("export fn sum(ptr: [*]const f16, len: usize) f32 {"));
+        assert!(!lowered.contains("sum__autozig_f16"));
+    }
+}
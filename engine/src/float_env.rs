@@ -0,0 +1,159 @@
+//! Floating-point semantics of compiled Zig modules
+//!
+//! Zig code is IEEE 754 strict by default: no reassociation and no
+//! contraction of `a * b + c` into a fused multiply-add. Modules can opt into
+//! fast-math with [`FloatMode::Optimized`], which the engine applies as a
+//! module-level `@setFloatMode`. Denormal flushing is a property of the
+//! calling thread's floating-point environment, so it is exposed to Zig code
+//! as the `autozig_fenv` helper rather than as a compiler flag.
+//!
+//! Strict determinism mode (for lockstep simulations that need bit-identical
+//! results on every platform) rejects anything that could make results
+//! differ between targets.
+
+use anyhow::{
+    bail,
+    Result,
+};
+
+/// Floating-point optimization mode of a Zig module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatMode {
+    /// IEEE 754 semantics; FMA only where the code calls `@mulAdd` (default)
+    #[default]
+    Strict,
+    /// Fast-math: operations may be reassociated, approximated and contracted
+    /// into FMA instructions when the target has them
+    Optimized,
+}
+
+/// Make `zig_code` compile with `mode`
+///
+/// A `comptime` block at container level sets the mode for every function
+/// in the module.
+pub fn apply_float_mode(zig_code: String, mode: FloatMode) -> String {
+    match mode {
+        FloatMode::Strict => zig_code,
+        FloatMode::Optimized => format!(
+            "// Fast-math requested by autozig_build::Builder\ncomptime {{\n    \
+             @setFloatMode(.optimized);\n}}\n\n{}",
+            zig_code
+        ),
+    }
+}
+
+/// Fail if `zig_code` (from `module`) opts into fast-math itself
+///
+/// Used in strict determinism mode.
+pub fn check_deterministic(module: &str, zig_code: &str) -> Result<()> {
+    let compact: String = zig_code.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.contains("@setFloatMode(.optimized)")
+        || compact.contains("@setFloatMode(.Optimized)")
+    {
+        bail!(
+            "{} sets @setFloatMode(.optimized), which is not allowed with \
+             strict_float_determinism: fast-math results differ between targets",
+            module
+        );
+    }
+    Ok(())
+}
+
+/// `autozig_fenv` helper: scoped flush-to-zero of denormals for the calling
+/// thread
+pub(crate) const AUTOZIG_FENV_ZIG: &str = r#"
+/// Floating-point environment of the calling thread
+pub const autozig_fenv = struct {
+    const arch = @import("builtin").cpu.arch;
+
+    /// Flush denormal inputs and results to zero until `restore(saved)`.
+    /// The setting is per thread and also applies to Rust code on this thread,
+    /// so always restore it before returning. No-op where unsupported (WASM).
+    pub fn enterFlushToZero() usize {
+        const saved = read();
+        switch (arch) {
+            .x86_64 => write(saved | 0x8040), // MXCSR FTZ | DAZ
+            .aarch64 => write(saved | (1 << 24)), // FPCR FZ
+            else => {},
+        }
+        return saved;
+    }
+
+    pub fn restore(saved: usize) void {
+        write(saved);
+    }
+
+    fn read() usize {
+        switch (arch) {
+            .x86_64 => {
+                var csr: u32 = 0;
+                asm volatile ("stmxcsr (%[ptr])"
+                    :
+                    : [ptr] "r" (&csr),
+                    : .{ .memory = true });
+                return csr;
+            },
+            .aarch64 => return asm volatile ("mrs %[ret], fpcr"
+                : [ret] "=r" (-> usize),
+            ),
+            else => return 0,
+        }
+    }
+
+    fn write(value: usize) void {
+        switch (arch) {
+            .x86_64 => {
+                const csr: u32 = @truncate(value);
+                asm volatile ("ldmxcsr (%[ptr])"
+                    :
+                    : [ptr] "r" (&csr),
+                    : .{ .memory = true });
+            },
+            .aarch64 => asm volatile ("msr fpcr, %[value]"
+                :
+                : [value] "r" (value),
+            ),
+            else => {},
+        }
+    }
+};
+"#;
+
+/// `autozig_fenv` for strict determinism builds, where flushing denormals
+/// is rejected because not every target supports it
+pub(crate) const AUTOZIG_FENV_DETERMINISTIC_ZIG: &str = r#"
+/// Floating-point environment of the calling thread
+pub const autozig_fenv = struct {
+    pub fn enterFlushToZero() usize {
+        @compileError("autozig_fenv.enterFlushToZero is not available with strict_float_determinism: WASM cannot flush denormals, so results would differ between targets");
+    }
+
+    pub fn restore(saved: usize) void {
+        _ = saved;
+    }
+};
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_float_mode() {
+        let code = "export fn dot() f32 { return 0; }".to_string();
+        assert_eq!(apply_float_mode(code.clone(), FloatMode::Strict), code);
+
+        let fast = apply_float_mode(code, FloatMode::Optimized);
+        assert!(fast.contains("@setFloatMode(.optimized);"));
+        assert!(fast.ends_with("export fn dot() f32 { return 0; }"));
+    }
+
+    #[test]
+    fn test_check_deterministic() {
+        assert!(check_deterministic("a.zig", "export fn f() void {}").is_ok());
+
+        let fast = "export fn f() void { @setFloatMode( .optimized ); }";
+        let err = check_deterministic("physics.zig", fast).unwrap_err();
+        assert!(err.to_string().starts_with("physics.zig sets"));
+    }
+}
@@ -0,0 +1,82 @@
+//! Zig sources of the SPIR-V compute kernels of `#[autozig(gpu)]` functions
+//!
+//! Each kernel is compiled into a SPIR-V module of its own (see
+//! [`autozig_parser::gpu`]). Its source is the crate's Zig code with every
+//! export removed except the kernel, followed by a `comptime` block that
+//! sets the kernel's workgroup size and binds each storage buffer global to
+//! descriptor set 0 in argument order. The host library keeps the kernels
+//! unexported, so their GPU-only code is never analyzed for the CPU.
+
+use crate::{
+    cfg_gate,
+    targets::{
+        TargetSdk,
+        ZigTarget,
+    },
+    visibility,
+};
+
+/// Zig target of the kernels: logical SPIR-V for Vulkan 1.2
+pub const SPIRV_TARGET: ZigTarget = ZigTarget {
+    triple: "spirv64-vulkan",
+    cpu: Some("vulkan_v1_2"),
+    sdk: TargetSdk::None,
+};
+
+/// A function bound with `#[autozig(gpu)]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuKernel {
+    /// Exported name of the kernel, namespaced like other exports
+    pub symbol: String,
+    /// Storage buffer globals, in binding order
+    pub buffers: Vec<String>,
+    /// Invocations per workgroup
+    pub workgroup_size: u32,
+}
+
+/// Source of the SPIR-V module of `kernel`, from the crate's Zig code
+pub fn kernel_source(zig_code: &str, kernel: &GpuKernel) -> String {
+    let others: Vec<String> = visibility::exported_function_names(zig_code)
+        .into_iter()
+        .filter(|name| *name != kernel.symbol)
+        .collect();
+    let code = cfg_gate::unexport(zig_code, &others);
+
+    let bindings: String = kernel
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| format!("    gpu.binding(&{buffer}, 0, {index});\n"))
+        .collect();
+    format!(
+        "{code}\n\n// Entry point and storage buffers of the `{symbol}` kernel\ncomptime {{\n    \
+         const gpu = @import(\"std\").gpu;\n    gpu.executionMode({symbol}, .{{ .local_size = .{{ \
+         .x = {size}, .y = 1, .z = 1 }} }});\n{bindings}}}\n",
+        symbol = kernel.symbol,
+        size = kernel.workgroup_size,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_source() {
+        let code = "extern var x: [64]f32 addrspace(.storage_buffer);\nexport fn saxpy() \
+                    callconv(.kernel) void {}\nexport fn dot(a: f32) f32 { return a; }\n";
+        let kernel = GpuKernel {
+            symbol: "saxpy".to_string(),
+            buffers: vec!["x".to_string(), "y".to_string()],
+            workgroup_size: 256,
+        };
+        assert_eq!(
+            kernel_source(code, &kernel),
+            "extern var x: [64]f32 addrspace(.storage_buffer);\nexport fn saxpy() \
+             callconv(.kernel) void {}\nfn dot(a: f32) f32 { return a; }\n\n\n// Entry point and \
+             storage buffers of the `saxpy` kernel\ncomptime {\n    const gpu = \
+             @import(\"std\").gpu;\n    gpu.executionMode(saxpy, .{ .local_size = .{ .x = 256, .y \
+             = 1, .z = 1 } });\n    gpu.binding(&x, 0, 0);\n    gpu.binding(&y, 0, 1);\n}\n"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Skipping the Zig invocation when nothing it compiles has changed
+//!
+//! Cargo reruns the build script for any change to the watched files, most
+//! of which leave the generated Zig untouched (e.g. a Rust-only edit). The
+//! engine stamps each successful build with a SHA-256 over the files handed
+//! to Zig (generated main module, copied `include_zig!` modules, C sources,
+//! build.zig) and the build settings, stored as `OUT_DIR/.zig_code_hash`.
+//! A rebuild with the same stamp and the library still present reuses it.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+/// Content hash of one build's inputs, recorded once the build succeeded
+pub struct BuildStamp {
+    path: PathBuf,
+    hash: String,
+}
+
+impl BuildStamp {
+    /// Stamp over `settings` (compilation mode, target, compiler flags) and
+    /// the names and contents of `sources`
+    pub fn new(out_dir: &Path, settings: &str, sources: &[&Path]) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(settings.as_bytes());
+        hasher.update([0]);
+        for source in sources {
+            let data =
+                fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+            // File names matter for @import and build.zig references
+            hasher.update(source.file_name().unwrap_or_default().as_encoded_bytes());
+            hasher.update([0]);
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+        Ok(Self {
+            path: out_dir.join(".zig_code_hash"),
+            hash: format!("{:x}", hasher.finalize()),
+        })
+    }
+
+    /// Whether the last successful build had the same inputs and its library
+    /// `lib_path` is still there
+    pub fn is_fresh(&self, lib_path: &Path) -> bool {
+        lib_path.exists() && fs::read_to_string(&self.path).is_ok_and(|hash| hash == self.hash)
+    }
+
+    /// Record the stamp after a successful build
+    pub fn record(&self) -> Result<()> {
+        fs::write(&self.path, &self.hash).context("Failed to write hash file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_build_stamp() {
+        let dir = env::temp_dir().join(format!("autozig-stamp-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("generated_main.zig");
+        let lib = dir.join("libdemo.a");
+        fs::write(&main, "export fn add(a: i32, b: i32) i32 { return a + b; }").unwrap();
+
+        let stamp = BuildStamp::new(&dir, "ModularBuildZig", &[&main]).unwrap();
+        assert!(!stamp.is_fresh(&lib));
+        fs::write(&lib, "!<arch>\n").unwrap();
+        stamp.record().unwrap();
+        assert!(BuildStamp::new(&dir, "ModularBuildZig", &[&main])
+            .unwrap()
+            .is_fresh(&lib));
+        assert!(!BuildStamp::new(&dir, "ModularImport", &[&main])
+            .unwrap()
+            .is_fresh(&lib));
+
+        fs::write(&main, "export fn add(a: i32, b: i32) i32 { return b + a; }").unwrap();
+        assert!(!BuildStamp::new(&dir, "ModularBuildZig", &[&main])
+            .unwrap()
+            .is_fresh(&lib));
+        fs::remove_file(&lib).unwrap();
+        assert!(!stamp.is_fresh(&lib));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,374 @@
+//! Measuring the layout of Zig `extern struct`s
+//!
+//! Field order and padding of a Zig `extern struct` and the `#[repr(C)]` Rust
+//! struct mirroring it can silently diverge. The engine appends exported
+//! `@sizeOf`/`@alignOf`/`@offsetOf` constants to a copy of each Zig source,
+//! builds it as an object for the target and reads the values back from the
+//! object's data, so no probe has to run on the build host. The macros then
+//! assert the Rust layouts against them (see
+//! [`autozig_parser::layout`]).
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_parser::layout::StructLayout;
+use object::{
+    Object,
+    ObjectSection,
+    ObjectSymbol,
+};
+
+/// A container-level `extern struct` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZigStruct {
+    pub name: String,
+    /// Field names with their Zig types, in declaration order
+    pub fields: Vec<(String, String)>,
+}
+
+/// Container-level `extern struct` declarations in `zig_code`
+pub fn extern_structs(zig_code: &str) -> Vec<ZigStruct> {
+    let code: String = zig_code
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut structs = Vec::new();
+    let mut depth = 0usize;
+    let mut search_from = 0;
+    while let Some(found) = code[search_from..].find("extern struct") {
+        let start = search_from + found;
+        for c in code[search_from..start].chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {},
+            }
+        }
+        search_from = start + "extern struct".len();
+        if depth != 0 {
+            continue;
+        }
+
+        let Some(name) = declared_name(&code[..start]) else {
+            continue;
+        };
+        let Some(open) = code[search_from..].find('{') else {
+            break;
+        };
+        let body_start = search_from + open + 1;
+        let Some(body_len) = matching_brace(&code[body_start..]) else {
+            break;
+        };
+        let fields = fields(&code[body_start..body_start + body_len]);
+        if !fields.is_empty() {
+            structs.push(ZigStruct { name, fields });
+        }
+        // Continue after the struct body at depth 0
+        search_from = body_start + body_len + 1;
+    }
+    structs
+}
+
+/// Aliases of generic `extern struct` instances, `const Matrix_f32 =
+/// Matrix(f32);` for a `fn Matrix(comptime T: type) type` returning an
+/// `extern struct`, with the fields of that struct
+pub fn generic_instances(zig_code: &str) -> Vec<ZigStruct> {
+    let code: String = zig_code
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut instances = Vec::new();
+    for statement in code.split(';') {
+        // Declarations follow the previous statement or a closing brace
+        let statement = statement
+            .rsplit(['{', '}'])
+            .next()
+            .unwrap_or_default()
+            .trim();
+        let statement = statement.strip_prefix("pub ").unwrap_or(statement);
+        let Some((name, value)) = statement
+            .strip_prefix("const ")
+            .and_then(|rest| rest.split_once('='))
+        else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        let Some((function, _)) = value.split_once('(') else {
+            continue;
+        };
+        let function = function.trim();
+        if !value.ends_with(')')
+            || !function.chars().all(|c| c.is_alphanumeric() || c == '_')
+            || !name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        {
+            continue;
+        }
+        let Some(fields) = type_function_fields(&code, function) else {
+            continue;
+        };
+        instances.push(ZigStruct { name: name.to_string(), fields });
+    }
+    instances
+}
+
+/// Fields of the `extern struct` returned by `fn name(..) type`
+fn type_function_fields(code: &str, name: &str) -> Option<Vec<(String, String)>> {
+    let function = autozig_parser::type_mapper::zig_function(code, name)?;
+    if function.return_type != "type" {
+        return None;
+    }
+    let start = code.find(&format!("fn {name}"))?;
+    let body = &code[start..];
+    let open = body.find('{')?;
+    let body = &body[open + 1..];
+    let body = &body[..matching_brace(body)?];
+    let returned = &body[body.find("extern struct")?..];
+    let struct_open = returned.find('{')? + 1;
+    let struct_len = matching_brace(&returned[struct_open..])?;
+    let fields = fields(&returned[struct_open..struct_open + struct_len]);
+    (!fields.is_empty()).then_some(fields)
+}
+
+/// `Name` in `[pub] const Name =` right before `extern struct` / `struct`
+pub(crate) fn declared_name(before: &str) -> Option<String> {
+    let before = before.trim_end().strip_suffix('=')?.trim_end();
+    let name_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    let name = &before[name_start..];
+    let keyword = before[..name_start].trim_end();
+    if name.is_empty() || !keyword.ends_with("const") {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Length of the text up to the `}` closing an already opened brace
+pub(crate) fn matching_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Fields of a struct body with their types, skipping declarations and
+/// methods
+pub(crate) fn fields(body: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut segment = String::new();
+    // Braces enclose methods and nested declarations; brackets and parens
+    // belong to field types
+    let mut braces = 0usize;
+    let mut groups = 0usize;
+    let mut finish = |segment: &mut String| {
+        let text = segment.trim();
+        if let Some((name, ty)) = text.split_once(':') {
+            let name = name.trim();
+            // Drop the default value
+            let ty = ty.split('=').next().unwrap_or_default().trim();
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                fields.push((name.to_string(), ty.to_string()));
+            }
+        }
+        segment.clear();
+    };
+    for c in body.chars() {
+        match c {
+            '{' => braces += 1,
+            '}' => {
+                braces = braces.saturating_sub(1);
+                if braces == 0 {
+                    // End of a method or nested declaration
+                    segment.clear();
+                }
+                continue;
+            },
+            '(' | '[' if braces == 0 => groups += 1,
+            ')' | ']' if braces == 0 => groups = groups.saturating_sub(1),
+            ',' | ';' if braces == 0 && groups == 0 => {
+                finish(&mut segment);
+                continue;
+            },
+            _ => {},
+        }
+        if braces == 0 {
+            segment.push(c);
+        }
+    }
+    finish(&mut segment);
+    fields
+}
+
+fn symbol_name(structure: &str, item: &str) -> String {
+    format!("autozig_layout__{structure}__{item}")
+}
+
+/// `zig_code` with the layout of `structs` exported as `u64` constants
+pub fn probe_source(zig_code: &str, structs: &[ZigStruct]) -> String {
+    let mut probe = format!("{zig_code}\n\n// AutoZig layout probe\n");
+    for s in structs {
+        let name = &s.name;
+        probe.push_str(&format!(
+            "export const {}: u64 = @sizeOf({name});\n",
+            symbol_name(name, "size")
+        ));
+        probe.push_str(&format!(
+            "export const {}: u64 = @alignOf({name});\n",
+            symbol_name(name, "align")
+        ));
+        for (field, _) in &s.fields {
+            probe.push_str(&format!(
+                "export const {}: u64 = @offsetOf({name}, \"{field}\");\n",
+                symbol_name(name, &format!("f_{field}"))
+            ));
+        }
+    }
+    probe
+}
+
+/// Read the layouts of `structs` from a probe object built from
+/// [`probe_source`]
+///
+/// Structs whose constants are missing (e.g. on formats without data
+/// symbols) are left out.
+pub fn read_layouts(obj_path: &Path, structs: &[ZigStruct]) -> Result<Vec<StructLayout>> {
+    let data =
+        fs::read(obj_path).with_context(|| format!("Failed to read {}", obj_path.display()))?;
+    let file = object::File::parse(&*data)
+        .with_context(|| format!("Unsupported object format: {}", obj_path.display()))?;
+
+    let read = |name: String| -> Option<u64> {
+        let symbol = file.symbols().find(|symbol| {
+            symbol
+                .name()
+                .is_ok_and(|n| n == name || n.strip_prefix('_') == Some(&name))
+        })?;
+        let section = file.section_by_index(symbol.section_index()?).ok()?;
+        let offset = usize::try_from(symbol.address().checked_sub(section.address())?).ok()?;
+        let bytes: [u8; 8] = section
+            .data()
+            .ok()?
+            .get(offset..offset + 8)?
+            .try_into()
+            .ok()?;
+        Some(if file.is_little_endian() {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    };
+
+    let mut layouts = Vec::new();
+    for s in structs {
+        let (Some(size), Some(align)) =
+            (read(symbol_name(&s.name, "size")), read(symbol_name(&s.name, "align")))
+        else {
+            continue;
+        };
+        let fields = s
+            .fields
+            .iter()
+            .filter_map(|(field, _)| {
+                let offset = read(symbol_name(&s.name, &format!("f_{field}")))?;
+                Some((field.clone(), offset))
+            })
+            .collect();
+        layouts.push(StructLayout {
+            name: s.name.clone(),
+            size,
+            align,
+            fields,
+        });
+    }
+    Ok(layouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extern_structs() {
+        let code = r#"
+const std = @import("std");
+
+pub const Point = extern struct {
+    x: f32, // horizontal {
+    y: f32 = 0,
+
+    pub fn len(self: Point, scale: f32) f32 {
+        const tmp = extern struct { a: u8 };
+        _ = tmp;
+        return (self.x + self.y) * scale;
+    }
+};
+
+const Rgb=extern struct { r: u8, g: u8, b: [3]u8 };
+
+fn Pair(comptime T: type) type {
+    return extern struct { a: T, b: T };
+}
+
+const Packed = packed struct { a: u4, b: u4 };
+"#;
+        assert_eq!(
+            extern_structs(code),
+            vec![
+                ZigStruct {
+                    name: "Point".to_string(),
+                    fields: vec![("x".into(), "f32".into()), ("y".into(), "f32".into())]
+                },
+                ZigStruct {
+                    name: "Rgb".to_string(),
+                    fields: vec![
+                        ("r".into(), "u8".into()),
+                        ("g".into(), "u8".into()),
+                        ("b".into(), "[3]u8".into())
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_instances() {
+        let code = "pub fn Matrix(comptime T: type) type {\n    return extern struct { data: \
+                    [4]T, scale: T };\n}\npub const Matrix_f32 = Matrix(f32);\nconst n = add(1, \
+                    2);";
+        assert_eq!(
+            generic_instances(code),
+            vec![ZigStruct {
+                name: "Matrix_f32".to_string(),
+                fields: vec![("data".into(), "[4]T".into()), ("scale".into(), "T".into())],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_probe_source() {
+        let structs = vec![ZigStruct {
+            name: "Point".to_string(),
+            fields: vec![("x".into(), "f32".into()), ("y".into(), "f32".into())],
+        }];
+        let probe = probe_source("const Point = extern struct { x: f32, y: f32 };", &structs);
+        assert!(probe.contains("export const autozig_layout__Point__size: u64 = @sizeOf(Point);"));
+        assert!(probe
+            .contains("export const autozig_layout__Point__f_y: u64 = @offsetOf(Point, \"y\");"));
+    }
+}
@@ -21,24 +21,207 @@ use anyhow::{
     Context,
     Result,
 };
+use autozig_parser::layout::StructLayout;
 use sha2::{
     Digest,
     Sha256,
 };
 
+pub mod abi_snapshot;
+pub mod archive;
+pub mod bridge_structs;
+pub mod build_info;
+pub mod c_header;
+pub mod cache;
+pub mod cfg_gate;
+pub mod const_generics;
+pub mod deps;
+pub mod diagnostics;
+pub mod error_union;
+pub mod extern_structs;
+pub mod float16;
+pub mod float_env;
+pub mod gpu;
+pub mod incremental;
+pub mod layout_probe;
+pub mod lints;
+pub mod manifest;
+pub mod multiversion;
+pub mod namespace;
 pub mod scanner;
+pub mod shared_modules;
+pub mod stack_report;
+pub mod static_str;
+pub mod targets;
+pub mod toolchain;
 pub mod ts_generator;
-pub mod type_mapper;
+pub mod type_generics;
+pub mod udl;
+pub mod unused_exports;
+pub mod vendor;
+pub mod visibility;
+pub mod wit;
+pub mod workspace;
 pub mod zig_compiler;
-
+pub mod zig_enums;
+
+pub use autozig_parser::lowering::TypeLowering;
+/// Zig <-> Rust type mapping, shared with the macro parser
+pub use autozig_parser::type_mapper;
+pub use build_info::BuildInfo;
+pub use cache::ArtifactCache;
+pub use float_env::FloatMode;
+pub use incremental::BuildStamp;
+pub use manifest::SourceManifest;
 pub use scanner::{
     CompilationMode,
+    ForeignSource,
+    PrebuiltLibrary,
     ScanResult,
+    SourceKind,
     ZigCodeScanner,
+    MODE_FILE,
+};
+pub use shared_modules::ModuleIndex;
+pub use stack_report::{
+    StackReport,
+    StackUsage,
 };
-pub use zig_compiler::ZigCompiler;
+pub use targets::ZigTarget;
+pub use unused_exports::UnusedExports;
+pub use visibility::SymbolVisibility;
+pub use zig_compiler::{
+    ArtifactKind,
+    CodegenFlags,
+    Sanitizer,
+    WasiExecModel,
+    ZigCompiler,
+    ZigOptimize,
+};
+
+/// Optional build settings layered on top of the compilation mode
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Write a per-export stack usage report to `OUT_DIR`
+    pub stack_report: bool,
+    /// Fail the build if an exported function needs more stack than this
+    /// (implies `stack_report`)
+    pub max_stack: Option<u64>,
+    /// Use the deterministic `autozig_runtime` shims (fixed-buffer allocator,
+    /// seedable PRNG) for reproducible tests
+    pub test_shims: bool,
+    /// Visibility of Zig exports on native targets
+    pub visibility: SymbolVisibility,
+    /// Exports that stay public when `visibility` is `Hidden`, and are never
+    /// reported or stripped as unused
+    pub public_symbols: Vec<String>,
+    /// Report or strip Zig exports that no Rust declaration binds (see
+    /// [`unused_exports`])
+    pub unused_exports: UnusedExports,
+    /// Export Zig functions as `az_<hash>__<name>`, with a hash unique to the
+    /// crate, so two crates exporting the same name link together; the Rust
+    /// wrappers keep their names. `public_symbols` and WASM exports keep
+    /// their plain names.
+    pub mangle_symbols: bool,
+    /// Expose the source manifest to the crate as `AUTOZIG_SOURCE_MANIFEST`
+    /// so `autozig::source_manifest!()` can embed it
+    pub embed_manifest: bool,
+    /// Reuse compiled libraries from the shared `target/autozig-cache/`
+    pub artifact_cache: bool,
+    /// Zig's local cache directory, relative to the crate's manifest
+    /// directory; `None` shares `target/zig-cache/` across the project (see
+    /// [`cache::zig_cache_dir`])
+    pub zig_cache_dir: Option<PathBuf>,
+    /// Report misuse of generated bindings (see [`lints`]) as cargo warnings
+    pub safety_lints: bool,
+    /// Floating-point mode of every Zig module without an override
+    pub float_mode: FloatMode,
+    /// Per-module float modes, keyed by `include_zig!` file name
+    pub module_float_modes: Vec<(String, FloatMode)>,
+    /// Extra compiler flags of C and C++ sources, keyed by file name (used
+    /// by the generated build.zig)
+    pub source_flags: Vec<(String, Vec<String>)>,
+    /// System libraries the Zig and C code calls into (e.g. `"z"`), linked
+    /// by Zig and by the final Rust link on native targets
+    pub system_libs: Vec<String>,
+    /// Header search paths for C sources and `@cImport`, relative to the
+    /// crate's manifest directory
+    pub include_dirs: Vec<PathBuf>,
+    /// Reject fast-math and denormal flushing so results are bit-identical
+    /// across targets
+    pub strict_float_determinism: bool,
+    /// Compare the crate's ABI against `tests/abi_snapshots/<target>.txt`
+    /// (see [`abi_snapshot`])
+    pub abi_snapshot: bool,
+    /// Measure Zig `extern struct` layouts so the macros can assert that the
+    /// `#[repr(C)]` Rust structs match (see [`layout_probe`])
+    pub layout_check: bool,
+    /// Compile self-contained `include_zig!` modules once per workspace into
+    /// shared libraries (see [`shared_modules`])
+    pub share_modules: bool,
+    /// Write a C header declaring the Zig exports to this path, relative to
+    /// the crate's manifest directory (see [`c_header`])
+    pub c_header: Option<PathBuf>,
+    /// Write a WIT world describing the crate's functions to this path,
+    /// relative to the crate's manifest directory, and export them to the
+    /// WASM component model (see [`wit`])
+    pub wit: Option<PathBuf>,
+    /// Write a UniFFI UDL file describing the crate's items to this path,
+    /// relative to the crate's manifest directory, and the matching Rust
+    /// glue to `OUT_DIR/autozig_uniffi.rs` (see [`udl`])
+    pub udl: Option<PathBuf>,
+    /// User-registered lowering rules for domain types, applied by the macros
+    /// (see [`autozig_parser::lowering`])
+    pub type_lowerings: Vec<TypeLowering>,
+    /// Optimization mode, stripping and threading of the Zig library
+    pub codegen: CodegenFlags,
+    /// Compile the Zig code into a static archive linked into the crate
+    /// (default), or into a shared library exposed as `AUTOZIG_CDYLIB` for
+    /// loading at runtime
+    pub artifact: ArtifactKind,
+    /// Exact Zig version the build requires; `None` falls back to
+    /// `[package.metadata.autozig] zig-version`, if any (see [`toolchain`])
+    pub zig_version: Option<String>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            stack_report: false,
+            max_stack: None,
+            // AUTOZIG_TEST_SHIMS=1 enables the deterministic shims without
+            // touching build.rs (e.g. in CI test jobs)
+            test_shims: env::var("AUTOZIG_TEST_SHIMS").is_ok_and(|v| v == "1"),
+            visibility: SymbolVisibility::default(),
+            public_symbols: Vec::new(),
+            unused_exports: UnusedExports::default(),
+            mangle_symbols: false,
+            embed_manifest: false,
+            artifact_cache: true,
+            zig_cache_dir: None,
+            safety_lints: true,
+            float_mode: FloatMode::default(),
+            module_float_modes: Vec::new(),
+            source_flags: Vec::new(),
+            system_libs: Vec::new(),
+            include_dirs: Vec::new(),
+            strict_float_determinism: false,
+            abi_snapshot: false,
+            layout_check: true,
+            share_modules: false,
+            c_header: None,
+            wit: None,
+            udl: None,
+            type_lowerings: Vec::new(),
+            codegen: CodegenFlags::default(),
+            artifact: ArtifactKind::default(),
+            zig_version: None,
+        }
+    }
+}
 
 /// Main engine for processing autozig! macros during build
+#[derive(Debug, Clone)]
 pub struct AutoZigEngine {
     /// Output directory (usually OUT_DIR from build.rs)
     out_dir: PathBuf,
@@ -46,12 +229,29 @@ pub struct AutoZigEngine {
     src_dir: PathBuf,
     /// Compilation mode
     mode: CompilationMode,
+    /// Whether `mode` was chosen by the build script, which
+    /// `#![autozig(mode = ...)]` of a block does not override (the deprecated
+    /// `AUTOZIG_MODE` does not count)
+    mode_explicit: bool,
+    /// Additional build settings
+    options: BuildOptions,
+    /// Rust target triple overriding cargo's `TARGET`
+    target: Option<String>,
+    /// Directory `include_zig!`/`bind_zig!` paths are relative to, overriding
+    /// `CARGO_MANIFEST_DIR`
+    manifest_dir: Option<PathBuf>,
 }
 
 impl AutoZigEngine {
-    /// Create a new AutoZig engine with default mode
+    /// Create a new AutoZig engine whose mode the crate's blocks choose
+    /// (`#![autozig(mode = ...)]`), else the deprecated `AUTOZIG_MODE`,
+    /// else merged
     pub fn new(src_dir: impl AsRef<Path>, out_dir: impl AsRef<Path>) -> Self {
-        Self::with_mode(src_dir, out_dir, CompilationMode::default())
+        let mode = CompilationMode::from_env().unwrap_or_default();
+        Self {
+            mode_explicit: false,
+            ..Self::with_mode(src_dir, out_dir, mode)
+        }
     }
 
     /// Create engine with specific compilation mode
@@ -64,11 +264,185 @@ impl AutoZigEngine {
             src_dir: src_dir.as_ref().to_path_buf(),
             out_dir: out_dir.as_ref().to_path_buf(),
             mode,
+            mode_explicit: true,
+            options: BuildOptions::default(),
+            target: None,
+            manifest_dir: None,
+        }
+    }
+
+    /// Apply additional build settings
+    pub fn with_options(mut self, options: BuildOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Compile for `rust_target` instead of the `TARGET` cargo passes to
+    /// build scripts (for tools running outside of cargo)
+    pub fn with_target(mut self, rust_target: impl Into<String>) -> Self {
+        self.target = Some(rust_target.into());
+        self
+    }
+
+    /// Resolve `include_zig!`/`bind_zig!` paths against `dir` instead of
+    /// `CARGO_MANIFEST_DIR`
+    pub fn with_manifest_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.manifest_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Rust target triple being compiled for
+    fn rust_target(&self) -> Option<String> {
+        self.target.clone().or_else(|| env::var("TARGET").ok())
+    }
+
+    /// Zig target for the Rust target being compiled for
+    ///
+    /// Fails for targets without a Zig equivalent instead of silently
+    /// building for the host.
+    fn zig_target(&self) -> Result<ZigTarget> {
+        let rust_target = self.rust_target().unwrap_or_else(|| "native".to_string());
+        let host = env::var("HOST").ok();
+        let zig_target = ZigTarget::from_rust(&rust_target, host.as_deref())?;
+        for var in zig_target.sdk_env_vars() {
+            println!("cargo:rerun-if-env-changed={}", var);
+        }
+        Ok(zig_target)
+    }
+
+    /// Whether the Zig code is built without libc: for a `no_std` crate
+    /// or a bare-metal target
+    fn freestanding(&self) -> bool {
+        self.options.codegen.freestanding
+            || self
+                .rust_target()
+                .and_then(|target| ZigTarget::from_rust(&target, None).ok())
+                .is_some_and(|target| target.is_bare_metal())
+    }
+
+    /// Compiler for `zig_target`, using the configured Zig cache
+    fn compiler(&self, zig_target: &ZigTarget) -> Result<ZigCompiler> {
+        let compiler = ZigCompiler::for_target(zig_target, &self.out_dir)?
+            .with_system_deps(&self.options.system_libs, &self.include_dirs())
+            .with_artifact(self.options.artifact);
+        let cache_dir = match &self.options.zig_cache_dir {
+            Some(dir) => Some(self.scanner(self.mode).manifest_dir().join(dir)),
+            None => cache::zig_cache_dir(&self.out_dir),
+        };
+        Ok(match cache_dir {
+            Some(dir) => compiler.with_cache_dir(dir),
+            None => compiler,
+        })
+    }
+
+    /// `include_dirs`, resolved against the manifest directory
+    fn include_dirs(&self) -> Vec<PathBuf> {
+        let manifest_dir = self.scanner(self.mode).manifest_dir().to_path_buf();
+        self.options
+            .include_dirs
+            .iter()
+            .map(|dir| manifest_dir.join(dir))
+            .collect()
+    }
+
+    /// Scanner over the source directory
+    fn scanner(&self, mode: CompilationMode) -> ZigCodeScanner {
+        let scanner = ZigCodeScanner::with_mode(&self.src_dir, mode);
+        match &self.manifest_dir {
+            Some(dir) => scanner.with_manifest_dir(dir),
+            None => scanner,
+        }
+    }
+
+    /// This engine with the `mode` and `optimize` asked for by
+    /// `#![autozig(...)]` headers of the crate's blocks, if they change it
+    ///
+    /// A mode set by the build script and an optimization mode set in
+    /// `BuildOptions::codegen` take precedence; the headers in turn override
+    /// the deprecated `AUTOZIG_MODE`. Blocks asking for different settings
+    /// fail the build.
+    fn with_block_settings(&self) -> Result<Option<AutoZigEngine>> {
+        let zig_code = self.scanner(CompilationMode::Merged).scan()?;
+        let mut mode: Option<String> = None;
+        let mut optimize: Option<String> = None;
+        for header in zig_code
+            .lines()
+            .filter_map(autozig_parser::header::BlockHeader::from_marker)
+        {
+            for (setting, value, what) in
+                [(&mut mode, header.mode, "mode"), (&mut optimize, header.optimize, "optimize")]
+            {
+                match (setting.as_ref(), value) {
+                    (Some(current), Some(value)) if *current != value => anyhow::bail!(
+                        "autozig! blocks ask for different {what} settings: `{current}` and \
+                         `{value}`"
+                    ),
+                    (None, value) => *setting = value,
+                    _ => {},
+                }
+            }
+        }
+
+        let mut engine = self.clone();
+        if let Some(mode) = mode.filter(|_| !self.mode_explicit) {
+            engine.mode = CompilationMode::from_name(&mode)
+                .ok_or_else(|| anyhow::anyhow!("unknown compilation mode `{mode}`"))?;
+        }
+        if let Some(optimize) = optimize.filter(|_| self.options.codegen.optimize.is_none()) {
+            engine.options.codegen.optimize = Some(
+                ZigOptimize::from_name(&optimize)
+                    .ok_or_else(|| anyhow::anyhow!("unknown optimize mode `{optimize}`"))?,
+            );
+        }
+        if engine.mode == self.mode
+            && engine.options.codegen.optimize == self.options.codegen.optimize
+        {
+            return Ok(None);
         }
+        engine.mode_explicit = true;
+        Ok(Some(engine))
     }
 
     /// Run the complete build pipeline with incremental compilation
     pub fn build(&self) -> Result<BuildOutput> {
+        if let Some(engine) = self.with_block_settings()? {
+            return engine.build();
+        }
+        if env::var_os("AUTOZIG_MODE").is_some() {
+            println!(
+                "cargo:warning=AUTOZIG_MODE is deprecated; choose the mode with \
+                 autozig_build::Builder::mode or #![autozig(mode = ...)]"
+            );
+        }
+        println!("cargo:rerun-if-env-changed=AUTOZIG_TEST_SHIMS");
+        println!("cargo:rerun-if-env-changed=AUTOZIG_CACHE_DIR");
+        println!("cargo:rerun-if-env-changed=ZIG_PATH");
+        println!("cargo:rerun-if-env-changed=ZIG_LOCAL_CACHE_DIR");
+        self.emit_rerun_if_changed()?;
+        self.use_pinned_toolchain()?;
+        self.check_float_settings()?;
+        self.check_wasm_threads();
+        self.check_artifact()?;
+        self.check_sanitizer()?;
+        self.check_stack_traces()?;
+        self.report_unused_exports()?;
+        self.export_symbol_prefix();
+        self.link_prebuilt_libraries()?;
+        write_log_module(&self.out_dir)?;
+        self.build_multiversion()?;
+        self.build_gpu_kernels()?;
+        self.write_source_manifest()?;
+        self.write_build_info()?;
+        self.mode.record(&self.out_dir)?;
+        self.write_type_lowerings()?;
+        self.write_wit()?;
+        self.write_udl()?;
+        if self.options.abi_snapshot {
+            self.write_abi_snapshot()?;
+        }
+        if self.options.safety_lints {
+            self.run_safety_lints()?;
+        }
         match self.mode {
             CompilationMode::Merged => self.build_merged(),
             CompilationMode::ModularImport => self.build_modular_import(),
@@ -76,22 +450,208 @@ impl AutoZigEngine {
         }
     }
 
+    /// Zig sources the build compiles, as `(file name, contents)`, without
+    /// compiling or writing anything
+    ///
+    /// Merged mode yields `generated_autozig.zig`; the modular modes yield
+    /// `generated_main.zig` followed by the `include_zig!` files it imports.
+    /// CPU variants of multiversioned functions are not included.
+    pub fn generated_sources(&self) -> Result<Vec<(String, String)>> {
+        if let Some(engine) = self.with_block_settings()? {
+            return engine.generated_sources();
+        }
+        if self.mode == CompilationMode::Merged {
+            let code = self.merged_source()?;
+            if code.is_empty() {
+                return Ok(Vec::new());
+            }
+            return Ok(vec![("generated_autozig.zig".to_string(), self.apply_visibility(code))]);
+        }
+
+        let ScanResult::Modular { embedded_code, external_files, .. } =
+            self.scanner(self.mode).scan_modular()?
+        else {
+            return Err(anyhow::anyhow!("Expected modular scan result"));
+        };
+        if embedded_code.is_empty() && external_files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let main_zig = self.generate_main_module_with_files(&embedded_code, &external_files)?;
+        let main_zig = self.apply_float_settings("generated_main.zig", main_zig)?;
+        let mut sources = vec![("generated_main.zig".to_string(), self.apply_visibility(main_zig))];
+        for file in &external_files {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            sources.push((name.into_owned(), self.zig_file_source(file)?));
+        }
+        Ok(sources)
+    }
+
+    /// Compile and link the CPU variants of `#[autozig(multiversion)]`
+    /// functions, one library per variant of the target architecture
+    ///
+    /// The variants are built from the merged Zig code whatever the
+    /// compilation mode, since they only need the multiversioned functions
+    /// and what they call.
+    fn build_multiversion(&self) -> Result<()> {
+        let functions = self.scanner(self.mode).scan_multiversion()?;
+        if functions.is_empty() {
+            return Ok(());
+        }
+        let rust_target = self.rust_target().unwrap_or_default();
+        let arch = rust_target.split('-').next().unwrap_or_default();
+        let variants: Vec<_> = autozig_parser::multiversion::variants_for(arch).collect();
+        if variants.is_empty() {
+            return Ok(());
+        }
+
+        let zig_code = self.strip_unused_exports(self.scanner(CompilationMode::Merged).scan()?)?;
+        let mut zig_code = lower_exports(&zig_code, self.options.codegen.stack_traces)?;
+        let helpers = self.helper_code_for(&zig_code);
+        if !helpers.is_empty() {
+            zig_code.push_str("\n// AutoZig helper types\n");
+            zig_code.push_str(&helpers);
+        }
+        let zig_code = self.apply_float_settings("generated_autozig.zig", zig_code)?;
+
+        // A directory of their own, so no C sources are compiled in again
+        let dir = self.out_dir.join("autozig_multiversion");
+        fs::create_dir_all(&dir).context("Failed to create multiversion directory")?;
+        write_log_module(&dir)?;
+        let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let zig_target = self.zig_target()?;
+        for variant in variants {
+            let source = dir.join(format!("autozig_{}.zig", variant.suffix));
+            let variant_code = multiversion::variant_source(&zig_code, &functions, variant);
+            fs::write(&source, self.apply_visibility(variant_code))
+                .with_context(|| format!("Failed to write {}", source.display()))?;
+
+            let variant_target = ZigTarget {
+                cpu: Some(variant.zig_cpu),
+                ..zig_target.clone()
+            };
+            let lib_name = format!("{}_{}", pkg_name.replace('-', "_"), variant.suffix);
+            let lib_path = self.out_dir.join(format!("lib{lib_name}.a"));
+            let compiler = self
+                .compiler(&variant_target)?
+                .with_codegen(self.options.codegen);
+            self.compile_cached(&[&source], &variant_target, &lib_path, || {
+                compiler.compile_with_target(&source, &lib_path, variant_target.triple)
+            })?;
+            println!("cargo:rustc-link-search=native={}", self.out_dir.display());
+            println!("cargo:rustc-link-lib=static={lib_name}");
+        }
+        Ok(())
+    }
+
+    /// Compile the `#[autozig(gpu)]` kernels into SPIR-V modules in
+    /// `OUT_DIR/autozig_gpu`, which the generated bindings embed
+    ///
+    /// Like CPU variants, kernels are built from the merged Zig code whatever
+    /// the compilation mode.
+    fn build_gpu_kernels(&self) -> Result<()> {
+        let kernels = self.scanner(self.mode).scan_gpu_kernels()?;
+        if kernels.is_empty() {
+            return Ok(());
+        }
+
+        let zig_code = self.scanner(CompilationMode::Merged).scan()?;
+        let dir = self.out_dir.join(autozig_parser::gpu::SPIRV_DIR);
+        fs::create_dir_all(&dir).context("Failed to create GPU kernel directory")?;
+        let compiler = self
+            .compiler(&gpu::SPIRV_TARGET)?
+            .with_codegen(self.options.codegen);
+        for kernel in &kernels {
+            let source = dir.join(format!("{}.zig", kernel.symbol));
+            fs::write(&source, gpu::kernel_source(&zig_code, kernel))
+                .with_context(|| format!("Failed to write {}", source.display()))?;
+            let spirv = dir.join(format!("{}.spv", kernel.symbol));
+            self.compile_cached(&[&source], &gpu::SPIRV_TARGET, &spirv, || {
+                compiler.compile_spirv(&source, &spirv, gpu::SPIRV_TARGET.triple)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Rerun the build script only when a file it reads, the compilation mode
+    /// or the target changes
+    fn emit_rerun_if_changed(&self) -> Result<()> {
+        println!("cargo:rerun-if-env-changed=AUTOZIG_MODE");
+        println!("cargo:rerun-if-env-changed=TARGET");
+        for path in self.scanner(self.mode).dependencies()? {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Switch to the pinned Zig version, if the crate pins one
+    fn use_pinned_toolchain(&self) -> Result<()> {
+        let version = match &self.options.zig_version {
+            Some(version) => Some(version.clone()),
+            None => toolchain::manifest_zig_version()?,
+        };
+        match version {
+            Some(version) => toolchain::ensure(&version),
+            None => Ok(()),
+        }
+    }
+
     /// Legacy merged compilation mode
     fn build_merged(&self) -> Result<BuildOutput> {
-        println!("cargo:rerun-if-changed={}", self.src_dir.display());
         println!("cargo:warning=Using MERGED compilation mode (legacy)");
 
-        let scanner = ZigCodeScanner::with_mode(&self.src_dir, CompilationMode::Merged);
-        let zig_code = scanner.scan()?;
-
-        if zig_code.is_empty() {
+        let complete_code = self.merged_source()?;
+        if complete_code.is_empty() {
             // No Zig code found, nothing to do
             return Ok(BuildOutput { lib_path: None });
         }
+        self.write_c_header(std::slice::from_ref(&complete_code))?;
+        let complete_code = self.apply_visibility(complete_code);
+
+        let lib_path = self.lib_path();
+
+        let zig_file = self.out_dir.join("generated_autozig.zig");
+        fs::write(&zig_file, &complete_code).context("Failed to write Zig source file")?;
+
+        let zig_target = self.zig_target()?;
+        self.compile_incremental(&[&zig_file], &zig_target, &lib_path, || {
+            let compiler = self
+                .compiler(&zig_target)?
+                .with_codegen(self.options.codegen);
+            self.compile_cached(&[&zig_file], &zig_target, &lib_path, || {
+                compiler.compile_with_target_and_src(
+                    &zig_file,
+                    &lib_path,
+                    zig_target.triple,
+                    &self.src_dir,
+                )
+            })?;
+            self.check_stack_usage(&zig_file, &zig_target)?;
+            self.verify_visibility(&zig_file, &lib_path)?;
+            self.probe_layouts(&[&zig_file], &zig_target)
+        })?;
+
+        self.link_library(&lib_path);
+
+        Ok(BuildOutput { lib_path: Some(lib_path) })
+    }
+
+    /// Merged Zig source with ABI wrappers, helpers and float settings
+    /// applied, but not visibility; empty when the crate has no Zig code
+    fn merged_source(&self) -> Result<String> {
+        let scanner = self.scanner(CompilationMode::Merged);
+        let zig_code = lower_exports(
+            &self.strip_unused_exports(scanner.scan()?)?,
+            self.options.codegen.stack_traces,
+        )?;
+
+        if zig_code.is_empty() {
+            return Ok(String::new());
+        }
 
         // Generate ABI lowering wrappers and modify original code
         let (modified_code, abi_wrappers) =
-            self.generate_abi_lowering_with_modified_code(&[zig_code.clone()]);
+            self.generate_abi_lowering_with_modified_code(std::slice::from_ref(&zig_code));
 
         // Combine modified code with ABI wrappers
         let mut complete_code = if modified_code.is_empty() {
@@ -100,6 +660,12 @@ impl AutoZigEngine {
             modified_code
         };
 
+        let helpers = self.helper_code_for(&complete_code);
+        if !helpers.is_empty() {
+            complete_code.push_str("\n// AutoZig helper types\n");
+            complete_code.push_str(&helpers);
+        }
+
         if !abi_wrappers.is_empty() {
             complete_code.push_str("\n\n");
             complete_code.push_str("// ABI Lowering: Pointer-based wrappers for struct returns\n");
@@ -107,53 +673,23 @@ impl AutoZigEngine {
             complete_code.push_str(&abi_wrappers);
         }
 
-        let code_hash = format!("{:x}", Sha256::digest(&complete_code));
-        let hash_file = self.out_dir.join(".zig_code_hash");
-
-        let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
-        let lib_name = pkg_name.replace("-", "_");
-        let lib_path = self.out_dir.join(format!("lib{}.a", lib_name));
-
-        if hash_file.exists() && lib_path.exists() {
-            if let Ok(old_hash) = fs::read_to_string(&hash_file) {
-                if old_hash == code_hash {
-                    println!("cargo:warning=Zig code unchanged, skipping compilation");
-                    self.link_library();
-                    return Ok(BuildOutput { lib_path: Some(lib_path) });
-                }
-            }
-        }
-
-        let zig_file = self.out_dir.join("generated_autozig.zig");
-        fs::write(&zig_file, &complete_code).context("Failed to write Zig source file")?;
-
-        let rust_target = env::var("TARGET").unwrap_or_else(|_| "native".to_string());
-        let zig_target = rust_to_zig_target(&rust_target);
-
-        let compiler = ZigCompiler::new();
-        compiler.compile_with_target_and_src(&zig_file, &lib_path, zig_target, &self.src_dir)?;
-
-        fs::write(&hash_file, &code_hash).context("Failed to write hash file")?;
-        self.link_library();
-
-        Ok(BuildOutput { lib_path: Some(lib_path) })
+        self.apply_float_settings("generated_autozig.zig", complete_code)
     }
 
     /// Modular compilation with main module + @import
     fn build_modular_import(&self) -> Result<BuildOutput> {
-        println!("cargo:rerun-if-changed={}", self.src_dir.display());
         println!("cargo:warning=Using MODULAR_IMPORT compilation mode");
 
-        let scanner = ZigCodeScanner::with_mode(&self.src_dir, CompilationMode::ModularImport);
+        let scanner = self.scanner(CompilationMode::ModularImport);
         let scan_result = scanner.scan_modular()?;
 
-        let (embedded_code, external_files, all_zig_files) = match scan_result {
+        let (embedded_code, external_files) = match scan_result {
             ScanResult::Modular {
                 embedded_code,
                 external_files,
-                all_zig_files,
+                all_zig_files: _,
                 c_source_files: _,
-            } => (embedded_code, external_files, all_zig_files),
+            } => (embedded_code, external_files),
             _ => return Err(anyhow::anyhow!("Expected modular scan result")),
         };
 
@@ -166,38 +702,56 @@ impl AutoZigEngine {
         for file in &external_files {
             if let Some(file_name) = file.file_name() {
                 let dest = self.out_dir.join(file_name);
-                fs::copy(file, &dest)
-                    .with_context(|| format!("Failed to copy {}", file.display()))?;
+                self.copy_zig_file(file, &dest)?;
                 copied_files.push(dest);
             }
         }
 
+        let zig_target = self.zig_target()?;
+        let own_files = self.share_modules(&copied_files, &embedded_code, &zig_target)?;
+
         // Generate main module with @import statements using actual copied file names
-        let main_zig = self.generate_main_module_with_files(&embedded_code, &copied_files)?;
+        let main_zig = self.generate_main_module_with_files(&embedded_code, &own_files)?;
+        let main_zig = self.apply_float_settings("generated_main.zig", main_zig)?;
+        self.write_modular_c_header(&main_zig, &external_files)?;
+        let main_zig = self.apply_visibility(main_zig);
         let main_file = self.out_dir.join("generated_main.zig");
         fs::write(&main_file, &main_zig).context("Failed to write main module")?;
 
         // Compile main module
 
-        let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
-        let lib_name = pkg_name.replace("-", "_");
-        let lib_path = self.out_dir.join(format!("lib{}.a", lib_name));
-        let rust_target = env::var("TARGET").unwrap_or_else(|_| "native".to_string());
-        let zig_target = rust_to_zig_target(&rust_target);
-
-        let compiler = ZigCompiler::new();
-        compiler.compile_with_target_and_src(&main_file, &lib_path, zig_target, &self.src_dir)?;
-
-        self.link_library();
+        let lib_path = self.lib_path();
+
+        let mut sources = vec![main_file.as_path()];
+        sources.extend(own_files.iter().map(PathBuf::as_path));
+        let mut probed = vec![main_file.as_path()];
+        probed.extend(copied_files.iter().map(PathBuf::as_path));
+        self.compile_incremental(&probed, &zig_target, &lib_path, || {
+            let compiler = self
+                .compiler(&zig_target)?
+                .with_codegen(self.options.codegen);
+            self.compile_cached(&sources, &zig_target, &lib_path, || {
+                compiler.compile_with_target_and_src(
+                    &main_file,
+                    &lib_path,
+                    zig_target.triple,
+                    &self.src_dir,
+                )
+            })?;
+            self.check_stack_usage(&main_file, &zig_target)?;
+            self.verify_visibility(&main_file, &lib_path)?;
+            self.probe_layouts(&probed, &zig_target)
+        })?;
+
+        self.link_library(&lib_path);
         Ok(BuildOutput { lib_path: Some(lib_path) })
     }
 
     /// Modular compilation with build.zig (recommended)
     fn build_modular_buildzig(&self) -> Result<BuildOutput> {
-        println!("cargo:rerun-if-changed={}", self.src_dir.display());
         println!("cargo:warning=Using MODULAR_BUILDZIG compilation mode (recommended)");
 
-        let scanner = ZigCodeScanner::with_mode(&self.src_dir, CompilationMode::ModularBuildZig);
+        let scanner = self.scanner(CompilationMode::ModularBuildZig);
         let scan_result = scanner.scan_modular()?;
 
         let (embedded_code, external_files, _all_zig_files, c_source_files) = match scan_result {
@@ -220,7 +774,7 @@ impl AutoZigEngine {
         for file in &external_files {
             let file_name = file.file_name().unwrap_or_default();
             let dest = self.out_dir.join(file_name);
-            fs::copy(file, &dest).with_context(|| format!("Failed to copy {}", file.display()))?;
+            self.copy_zig_file(file, &dest)?;
             copied_files.push(dest);
         }
 
@@ -234,27 +788,51 @@ impl AutoZigEngine {
             copied_c_files.push(dest);
         }
 
+        let zig_target = self.zig_target()?;
+        let own_files = self.share_modules(&copied_files, &embedded_code, &zig_target)?;
+
         // Generate main module using copied file paths (now files are in place)
-        let main_zig = self.generate_main_module_with_files(&embedded_code, &copied_files)?;
+        let main_zig = self.generate_main_module_with_files(&embedded_code, &own_files)?;
+        let main_zig = self.apply_float_settings("generated_main.zig", main_zig)?;
+        self.write_modular_c_header(&main_zig, &external_files)?;
+        let main_zig = self.apply_visibility(main_zig);
         let main_file = self.out_dir.join("generated_main.zig");
         fs::write(&main_file, &main_zig).context("Failed to write main module")?;
 
         // Generate build.zig file with C file support
-        let build_zig =
-            self.generate_build_zig_with_c(&embedded_code, &copied_files, &copied_c_files)?;
+        let build_zig = self.generate_build_zig_with_c(
+            &zig_target,
+            &embedded_code,
+            &own_files,
+            &copied_c_files,
+        )?;
         let build_file = self.out_dir.join("build.zig");
         fs::write(&build_file, &build_zig).context("Failed to write build.zig")?;
 
         // Compile using build.zig
 
-        let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
-        let lib_name = pkg_name.replace("-", "_");
-        let lib_path = self.out_dir.join(format!("lib{}.a", lib_name));
-        let compiler = ZigCompiler::new();
-        compiler.compile_with_buildzig(&build_file, &self.out_dir, &lib_path)?;
+        let lib_path = self.lib_path();
+        let rust_target = self.rust_target().unwrap_or_else(|| "native".to_string());
+        let mut sources = vec![build_file.as_path(), main_file.as_path()];
+        sources.extend(own_files.iter().map(PathBuf::as_path));
+        sources.extend(copied_c_files.iter().map(PathBuf::as_path));
+        let mut probed = vec![main_file.as_path()];
+        probed.extend(copied_files.iter().map(PathBuf::as_path));
+        let mut stamped = sources.clone();
+        stamped.extend(copied_files.iter().map(PathBuf::as_path));
+        self.compile_incremental(&stamped, &zig_target, &lib_path, || {
+            let compiler = self
+                .compiler(&zig_target)?
+                .with_codegen(self.options.codegen);
+            self.compile_cached(&sources, &zig_target, &lib_path, || {
+                compiler.compile_with_buildzig(&build_file, &self.out_dir, &lib_path)
+            })?;
+            self.check_stack_usage(&main_file, &zig_target)?;
+            self.verify_visibility(&main_file, &lib_path)?;
+            self.probe_layouts(&probed, &zig_target)
+        })?;
 
         // Generate TypeScript bindings for WASM targets
-        let rust_target = env::var("TARGET").unwrap_or_else(|_| "native".to_string());
         if rust_target.contains("wasm") {
             // Force export of Zig functions for WASM targets
             // This is critical because we use +whole-archive but without explicit exports,
@@ -270,10 +848,720 @@ impl AutoZigEngine {
             self.generate_typescript_bindings_for_rust_exports()?;
         }
 
-        self.link_library();
+        self.link_library(&lib_path);
+        // Zig compiles C++ against LLVM's libc++, which the static library
+        // does not contain
+        let has_cpp = copied_c_files
+            .iter()
+            .any(|file| scanner::is_cpp_source(file));
+        if has_cpp
+            && !rust_target.contains("wasm")
+            && self.options.artifact == ArtifactKind::Staticlib
+        {
+            println!("cargo:rustc-link-lib=c++");
+        }
         Ok(BuildOutput { lib_path: Some(lib_path) })
     }
 
+    /// Run `compile` (the Zig invocation and the checks of its output) unless
+    /// `sources` and the build settings are unchanged since the build that
+    /// produced `lib_path` (see [`incremental`])
+    fn compile_incremental(
+        &self,
+        sources: &[&Path],
+        zig_target: &ZigTarget,
+        lib_path: &Path,
+        compile: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        let settings = format!("{:?} {zig_target} {:?}", self.mode, self.options);
+        let stamp = BuildStamp::new(&self.out_dir, &settings, sources)?;
+        if stamp.is_fresh(lib_path) {
+            println!("cargo:warning=Zig code unchanged, skipping compilation");
+            let layouts_path = self.out_dir.join("autozig_layouts.txt");
+            if self.options.layout_check && layouts_path.exists() {
+                println!("cargo:rustc-env=AUTOZIG_LAYOUTS={}", layouts_path.display());
+            }
+            return Ok(());
+        }
+        compile()?;
+        stamp.record()
+    }
+
+    /// Run `compile` unless the shared cache already holds a library built
+    /// from the same `sources`, target and Zig version
+    fn compile_cached(
+        &self,
+        sources: &[&Path],
+        zig_target: &ZigTarget,
+        lib_path: &Path,
+        compile: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        let cache = match ArtifactCache::locate(&self.out_dir) {
+            Some(cache) if self.options.artifact_cache => cache,
+            _ => return compile(),
+        };
+
+        let zig_version = ZigCompiler::new().check_version()?;
+        let mode = format!(
+            "{:?} {:?} {:?} {:?} {:?}",
+            self.mode,
+            self.options.codegen,
+            self.options.system_libs,
+            self.options.include_dirs,
+            self.options.artifact
+        );
+        let key = ArtifactCache::key(&mode, &zig_target.to_string(), &zig_version, sources)?;
+
+        // Holding the lock while compiling makes concurrent builds of the same
+        // code wait for this one instead of compiling it again
+        let _lock = cache.lock(&key)?;
+        if cache.fetch(&key, lib_path)? {
+            println!("cargo:warning=Reusing cached Zig library ({})", &key[..16]);
+            return Ok(());
+        }
+
+        compile()?;
+        cache.store(&key, lib_path)
+    }
+
+    /// Compile self-contained modules among `modules` into shared libraries
+    /// and link them, returning the modules left for the crate's own library
+    ///
+    /// A module stays with the crate if it depends on it (see
+    /// [`shared_modules::is_standalone`]) or is imported by embedded code or
+    /// another module. WASM targets link the crate's library as a whole
+    /// archive and keep every module.
+    fn share_modules(
+        &self,
+        modules: &[PathBuf],
+        embedded_code: &[String],
+        zig_target: &ZigTarget,
+    ) -> Result<Vec<PathBuf>> {
+        let Some(cache) = self.shared_module_cache() else {
+            return Ok(modules.to_vec());
+        };
+        let (own, shared) =
+            self.compile_shared_modules(modules, embedded_code, zig_target, &cache)?;
+
+        let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let index = ModuleIndex::new(cache.clone());
+        for module in shared {
+            let (file_name, lib_name) = (&module.file_name, shared_modules::lib_name(&module.key));
+            if module.reused {
+                println!("cargo:warning=Reusing shared Zig module {file_name} ({lib_name})");
+            }
+            let others = index.record(&module.key, &package, file_name)?;
+            if !others.is_empty() {
+                println!(
+                    "cargo:warning=Zig module {file_name} is shared with {}",
+                    others.join(", ")
+                );
+            }
+            println!("cargo:rustc-link-search=native={}", cache.dir().display());
+            // Not bundled into the rlib, so crates sharing the module link
+            // one copy of its exports
+            println!("cargo:rustc-link-lib=static:-bundle={lib_name}");
+        }
+        Ok(own)
+    }
+
+    /// Cache holding shared modules, if module sharing applies to this build
+    fn shared_module_cache(&self) -> Option<ArtifactCache> {
+        let target = self.rust_target().unwrap_or_default();
+        ArtifactCache::locate(&self.out_dir).filter(|_| {
+            self.options.share_modules
+                && !target.contains("wasm")
+                && self.options.artifact == ArtifactKind::Staticlib
+        })
+    }
+
+    /// Compile the self-contained modules among `modules` into `cache`
+    /// (unless already there), returning the modules left for the crate's
+    /// own library and the shared ones
+    fn compile_shared_modules(
+        &self,
+        modules: &[PathBuf],
+        embedded_code: &[String],
+        zig_target: &ZigTarget,
+        cache: &ArtifactCache,
+    ) -> Result<(Vec<PathBuf>, Vec<shared_modules::SharedModule>)> {
+        let codes = modules
+            .iter()
+            .map(|module| {
+                fs::read_to_string(module)
+                    .with_context(|| format!("Failed to read {}", module.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let zig_version = ZigCompiler::new().check_version()?;
+        let compiler = self
+            .compiler(zig_target)?
+            .with_codegen(self.options.codegen);
+        let pipeline = format!("shared-module {:?}", self.options.codegen);
+
+        let mut own = Vec::new();
+        let mut shared = Vec::new();
+        for (idx, (module, code)) in modules.iter().zip(&codes).enumerate() {
+            let file_name = module.file_name().unwrap_or_default().to_string_lossy();
+            let import = format!("@import(\"{file_name}\")");
+            let imported = embedded_code
+                .iter()
+                .chain(
+                    codes
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| *i != idx)
+                        .map(|(_, c)| c),
+                )
+                .any(|other| other.contains(&import));
+            if imported || !shared_modules::is_standalone(code) {
+                own.push(module.clone());
+                continue;
+            }
+
+            let key =
+                ArtifactCache::key(&pipeline, &zig_target.to_string(), &zig_version, &[module])?;
+            let lib_name = shared_modules::lib_name(&key);
+            let shared_lib = shared_modules::lib_path(cache, &key);
+            let _lock = cache.lock(&key)?;
+            let reused = shared_lib.exists();
+            if !reused {
+                let source = shared_modules::isolate(module, &self.out_dir)?;
+                let built = self.out_dir.join(format!("lib{lib_name}.a"));
+                compiler.compile_with_target(&source, &built, zig_target.triple)?;
+                let tmp = shared_lib.with_extension(format!("a.{}.tmp", std::process::id()));
+                fs::copy(&built, &tmp)
+                    .with_context(|| format!("Failed to copy {}", built.display()))?;
+                fs::rename(&tmp, &shared_lib).context("Failed to store shared module")?;
+            }
+            shared.push(shared_modules::SharedModule {
+                key,
+                file_name: file_name.into_owned(),
+                reused,
+            });
+        }
+        Ok((own, shared))
+    }
+
+    /// Build the crate after compiling the shared Zig modules of every
+    /// workspace member using autozig, in parallel and each one once
+    ///
+    /// Identical self-contained `include_zig!` modules of all members (see
+    /// [`workspace::members`]) are compiled into the shared
+    /// `target/autozig-cache/` before the crate itself is built. The crate
+    /// links the shared modules it includes; the other members find theirs
+    /// already compiled when their build scripts run. Implies
+    /// `BuildOptions::share_modules`, and only the modular compilation modes
+    /// share modules.
+    pub fn build_workspace(&self) -> Result<BuildOutput> {
+        let mut engine = self.clone();
+        engine.options.share_modules = true;
+        let manifest_dir = self.scanner(self.mode).manifest_dir().to_path_buf();
+        let (Some(cache), Some(root)) =
+            (engine.shared_module_cache(), workspace::root(&manifest_dir))
+        else {
+            return engine.build();
+        };
+        let zig_target = self.zig_target()?;
+        let members = workspace::members(&root)?;
+
+        // One build script compiles for the whole workspace while the others
+        // wait, then find their modules in the cache
+        {
+            let _lock = cache.lock(&format!("workspace-{}", zig_target.triple))?;
+            std::thread::scope(|scope| {
+                let builds: Vec<_> = members
+                    .iter()
+                    .filter(|member| member.dir != manifest_dir)
+                    .map(|member| {
+                        let member_engine = AutoZigEngine {
+                            src_dir: member.dir.join("src"),
+                            out_dir: self.out_dir.join("autozig_workspace").join(&member.name),
+                            manifest_dir: Some(member.dir.clone()),
+                            ..engine.clone()
+                        };
+                        let zig_target = &zig_target;
+                        scope.spawn(move || {
+                            member_engine
+                                .prebuild_shared_modules(zig_target)
+                                .with_context(|| {
+                                    format!("Failed to build the Zig modules of {}", member.name)
+                                })
+                        })
+                    })
+                    .collect();
+                builds.into_iter().try_for_each(|build| {
+                    build
+                        .join()
+                        .map_err(|_| anyhow::anyhow!("Workspace module build panicked"))?
+                })
+            })?;
+        }
+        engine.build()
+    }
+
+    /// Compile the crate's self-contained `include_zig!` modules into the
+    /// shared cache without linking them
+    fn prebuild_shared_modules(&self, zig_target: &ZigTarget) -> Result<()> {
+        let Some(cache) = self.shared_module_cache() else {
+            return Ok(());
+        };
+        let ScanResult::Modular { embedded_code, external_files, .. } = self
+            .scanner(CompilationMode::ModularBuildZig)
+            .scan_modular()?
+        else {
+            return Err(anyhow::anyhow!("Expected modular scan result"));
+        };
+        if external_files.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.out_dir)
+            .with_context(|| format!("Failed to create {}", self.out_dir.display()))?;
+        let mut copied_files = Vec::new();
+        for file in &external_files {
+            let dest = self.out_dir.join(file.file_name().unwrap_or_default());
+            self.copy_zig_file(file, &dest)?;
+            copied_files.push(dest);
+        }
+        self.compile_shared_modules(&copied_files, &embedded_code, zig_target, &cache)?;
+        Ok(())
+    }
+
+    /// Report stack usage of exported functions and enforce `max_stack`
+    fn check_stack_usage(&self, source: &Path, zig_target: &ZigTarget) -> Result<()> {
+        if !self.options.stack_report && self.options.max_stack.is_none() {
+            return Ok(());
+        }
+
+        let exports = self.export_names_near(source)?;
+
+        let compiler = self.compiler(zig_target)?;
+        let obj_path = self.out_dir.join("autozig_stack_report.o");
+        let output = compiler.stack_usage(source, &obj_path, zig_target.triple)?;
+
+        let mut report = StackReport::parse(&output);
+        report.retain_exports(&exports);
+
+        let report_path = self.out_dir.join("autozig_stack_report.txt");
+        fs::write(&report_path, report.render()).context("Failed to write stack report")?;
+        println!("cargo:warning=Stack usage report: {}", report_path.display());
+        for entry in &report.entries {
+            println!("cargo:warning=  {}: {} bytes", entry.function, entry.bytes);
+        }
+
+        if let Some(limit) = self.options.max_stack {
+            report.check_limit(limit)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the layouts of the `extern struct`s declared in `sources` to
+    /// `OUT_DIR/autozig_layouts.txt` and expose it as `AUTOZIG_LAYOUTS`
+    ///
+    /// A source the probe cannot be built for is skipped with a warning; its
+    /// structs are then not checked.
+    fn probe_layouts(&self, sources: &[&Path], zig_target: &ZigTarget) -> Result<()> {
+        if !self.options.layout_check {
+            return Ok(());
+        }
+
+        let compiler = self.compiler(zig_target)?;
+        let mut layouts: Vec<StructLayout> = Vec::new();
+        for (idx, source) in sources.iter().enumerate() {
+            if source.extension().and_then(|e| e.to_str()) != Some("zig") {
+                continue;
+            }
+            let code = fs::read_to_string(source)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            let mut structs = layout_probe::extern_structs(&code);
+            structs.extend(layout_probe::generic_instances(&code));
+            if structs.is_empty() {
+                continue;
+            }
+
+            // The probe must live next to the source for its @imports to resolve
+            let probe_file = self.out_dir.join(format!("autozig_layout_probe_{idx}.zig"));
+            let probe_obj = self.out_dir.join(format!("autozig_layout_probe_{idx}.o"));
+            fs::write(&probe_file, layout_probe::probe_source(&code, &structs))
+                .context("Failed to write layout probe")?;
+            let measured = compiler
+                .build_object(&probe_file, &probe_obj, zig_target.triple)
+                .and_then(|()| layout_probe::read_layouts(&probe_obj, &structs));
+            let _ = fs::remove_file(&probe_file);
+            let _ = fs::remove_file(&probe_obj);
+            match measured {
+                Ok(measured) => {
+                    for layout in measured {
+                        if !layouts.iter().any(|known| known.name == layout.name) {
+                            layouts.push(layout);
+                        }
+                    }
+                },
+                Err(e) => {
+                    println!("cargo:warning=Skipping layout check of {}: {:#}", source.display(), e)
+                },
+            }
+        }
+
+        let layouts_path = self.out_dir.join("autozig_layouts.txt");
+        let text: String = layouts
+            .iter()
+            .map(|layout| layout.render() + "\n")
+            .collect();
+        fs::write(&layouts_path, text).context("Failed to write struct layouts")?;
+        println!("cargo:rustc-env=AUTOZIG_LAYOUTS={}", layouts_path.display());
+        Ok(())
+    }
+
+    /// Hand the registered type lowerings to the macros via
+    /// `AUTOZIG_LOWERINGS`
+    fn write_type_lowerings(&self) -> Result<()> {
+        if self.options.type_lowerings.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.out_dir)?;
+        let path = self.out_dir.join("autozig_lowerings.txt");
+        let text: String = self
+            .options
+            .type_lowerings
+            .iter()
+            .map(|lowering| lowering.render() + "\n")
+            .collect();
+        fs::write(&path, text).context("Failed to write type lowerings")?;
+        println!("cargo:rustc-env=AUTOZIG_LOWERINGS={}", path.display());
+        Ok(())
+    }
+
+    /// Helper types and runtime shims referenced by `zig_code`
+    fn helper_code_for(&self, zig_code: &str) -> String {
+        let mut helpers = helper_types_for(zig_code);
+        if zig_code.contains("autozig_runtime.") && !zig_code.contains("const autozig_runtime") {
+            if self.options.test_shims {
+                helpers.push_str(AUTOZIG_RUNTIME_DETERMINISTIC_ZIG);
+            } else {
+                helpers.push_str(AUTOZIG_RUNTIME_ZIG);
+            }
+        }
+        if self.options.codegen.stack_traces && !zig_code.contains("const autozig_trace") {
+            helpers.push_str(AUTOZIG_STACK_TRACE_ZIG);
+            if !zig_code.contains("pub const panic") && !zig_code.contains("pub fn panic") {
+                helpers.push_str(AUTOZIG_PANIC_ZIG);
+            }
+        }
+        // Without libc, AutoZigBytes allocates through g_allocator
+        if self.freestanding()
+            && zig_code.contains("AutoZigBytes")
+            && !zig_code.contains("g_allocator")
+        {
+            helpers.push_str(AUTOZIG_ALLOCATOR_ZIG);
+        }
+        if zig_code.contains("autozig_fenv.") && !zig_code.contains("const autozig_fenv") {
+            if self.options.strict_float_determinism {
+                helpers.push_str(float_env::AUTOZIG_FENV_DETERMINISTIC_ZIG);
+            } else {
+                helpers.push_str(float_env::AUTOZIG_FENV_ZIG);
+            }
+        }
+        helpers
+    }
+
+    /// Reject float settings that contradict strict determinism
+    fn check_float_settings(&self) -> Result<()> {
+        if !self.options.strict_float_determinism {
+            return Ok(());
+        }
+        let fast_module = self
+            .options
+            .module_float_modes
+            .iter()
+            .find(|(_, mode)| *mode == FloatMode::Optimized)
+            .map(|(module, _)| module.as_str());
+        if self.options.float_mode == FloatMode::Optimized || fast_module.is_some() {
+            anyhow::bail!(
+                "FloatMode::Optimized ({}) cannot be combined with strict_float_determinism",
+                fast_module.unwrap_or("all modules")
+            );
+        }
+        Ok(())
+    }
+
+    /// Reject shared Zig libraries on WASM, which has no dynamic loading
+    fn check_artifact(&self) -> Result<()> {
+        let is_wasm = self
+            .rust_target()
+            .is_some_and(|target| target.starts_with("wasm"));
+        if is_wasm && self.options.artifact == ArtifactKind::Cdylib {
+            anyhow::bail!(
+                "ArtifactKind::Cdylib is not supported for WASM targets; build the crate itself \
+                 as a cdylib instead"
+            );
+        }
+        Ok(())
+    }
+
+    /// Reject stack traces on WASM, which cannot walk its own stack, and
+    /// together with stripping, which removes the symbols they need
+    fn check_stack_traces(&self) -> Result<()> {
+        let codegen = self.options.codegen;
+        if !codegen.stack_traces {
+            return Ok(());
+        }
+        let is_wasm = self
+            .rust_target()
+            .is_some_and(|target| target.starts_with("wasm"));
+        if is_wasm {
+            anyhow::bail!("Zig stack traces are not supported for WASM targets");
+        }
+        if codegen.strip {
+            anyhow::bail!("Zig stack traces need debug information; do not strip the Zig library");
+        }
+        if codegen.reproducible {
+            anyhow::bail!(
+                "Zig stack traces need debug information, which reproducible builds strip; \
+                 disable one of them"
+            );
+        }
+        if self.freestanding() {
+            anyhow::bail!(
+                "Zig stack traces need an operating system; freestanding builds have none"
+            );
+        }
+        Ok(())
+    }
+
+    /// Reject sanitizers on WASM, and warn when the Rust code is built with a
+    /// different sanitizer than the Zig code
+    fn check_sanitizer(&self) -> Result<()> {
+        println!("cargo:rerun-if-env-changed=CARGO_ENCODED_RUSTFLAGS");
+        let is_wasm = self
+            .rust_target()
+            .is_some_and(|target| target.starts_with("wasm"));
+        let sanitizer = self.options.codegen.sanitizer;
+        if let (true, Some(sanitizer)) = (is_wasm, sanitizer) {
+            anyhow::bail!("Sanitizer::{sanitizer:?} is not supported for WASM targets");
+        }
+        let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+        if let Some(rust) = Sanitizer::from_rustflags(&rustflags) {
+            if sanitizer != Some(rust) {
+                println!(
+                    "cargo:warning=Rust code is built with -Zsanitizer={} but the Zig code is \
+                     not; add Builder::sanitizer(Sanitizer::{rust:?}) to instrument it too",
+                    rust.as_str()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Path of the library the Zig code is compiled into, named after the
+    /// package: `libmy_crate.a`, or `libmy_crate.so` (`.dylib`, `.dll`) for
+    /// [`ArtifactKind::Cdylib`]
+    fn lib_path(&self) -> PathBuf {
+        let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let lib_name = pkg_name.replace("-", "_");
+        let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_else(|_| env::consts::OS.to_string());
+        self.out_dir
+            .join(self.options.artifact.file_name(&lib_name, &os))
+    }
+
+    /// Warn when Zig code is built for shared memory but the Rust code is not:
+    /// the final module would fail to instantiate with a shared memory
+    fn check_wasm_threads(&self) {
+        let is_wasm = self
+            .rust_target()
+            .is_some_and(|target| target.starts_with("wasm"));
+        if !is_wasm || !self.options.codegen.wasm_threads {
+            return;
+        }
+        let features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+        if !features.split(',').any(|feature| feature == "atomics") {
+            println!(
+                "cargo:warning=wasm_threads needs the Rust code built with atomics: \
+                 RUSTFLAGS=\"-C target-feature=+atomics,+bulk-memory -C link-arg=--shared-memory \
+                 -C link-arg=--import-memory\" and -Z build-std"
+            );
+        }
+    }
+
+    /// Apply the float mode configured for `module` (a Zig file name)
+    fn apply_float_settings(&self, module: &str, zig_code: String) -> Result<String> {
+        if self.options.strict_float_determinism {
+            float_env::check_deterministic(module, &zig_code)?;
+        }
+        let mode = self
+            .options
+            .module_float_modes
+            .iter()
+            .find(|(name, _)| name == module)
+            .map_or(self.options.float_mode, |(_, mode)| *mode);
+        Ok(float_env::apply_float_mode(zig_code, mode))
+    }
+
+    /// Names of functions exported by `source` and the modules it imports
+    fn export_names_near(&self, source: &Path) -> Result<Vec<String>> {
+        // Exports may live in imported modules next to the main source
+        let mut exports = Vec::new();
+        if let Some(dir) = source.parent() {
+            for entry in fs::read_dir(dir)?.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) == Some("zig") {
+                    let code = fs::read_to_string(&path).unwrap_or_default();
+                    exports.extend(visibility::exported_function_names(&code));
+                }
+            }
+        }
+        Ok(exports)
+    }
+
+    /// Zig exports of the crate that no Rust declaration binds
+    fn unused_zig_exports(&self) -> Result<Vec<String>> {
+        let declared = unused_exports::declared_symbols(&self.src_dir)?;
+        let zig_code = self.scanner(CompilationMode::Merged).scan()?;
+        Ok(unused_exports::unused(&zig_code, &declared, &self.options.public_symbols))
+    }
+
+    /// Warn about Zig exports the Rust side never binds
+    fn report_unused_exports(&self) -> Result<()> {
+        match self.options.unused_exports {
+            UnusedExports::Ignore => {},
+            UnusedExports::Warn => {
+                for name in self.unused_zig_exports()? {
+                    println!(
+                        "cargo:warning=autozig: Zig export `{name}` is not declared on the Rust                          side; declare it, or strip unused exports with `UnusedExports::Strip`"
+                    );
+                }
+            },
+            UnusedExports::Strip => {
+                let unused = self.unused_zig_exports()?;
+                if !unused.is_empty() {
+                    println!(
+                        "cargo:warning=autozig: stripping {} unused Zig export(s): {}",
+                        unused.len(),
+                        unused.join(", ")
+                    );
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Drop the `export` of unused functions when stripping is enabled
+    fn strip_unused_exports(&self, zig_code: String) -> Result<String> {
+        // GPU kernels only go into their SPIR-V modules
+        let mut names: Vec<String> = self
+            .scanner(self.mode)
+            .scan_gpu_kernels()?
+            .into_iter()
+            .map(|kernel| kernel.symbol)
+            .collect();
+        if self.options.unused_exports == UnusedExports::Strip {
+            names.extend(self.unused_zig_exports()?);
+        }
+        if names.is_empty() {
+            return Ok(zig_code);
+        }
+        Ok(cfg_gate::unexport(&zig_code, &names))
+    }
+
+    /// Whether Zig exports should be rewritten to hidden visibility (never
+    /// in a shared library, whose exports are its interface)
+    fn hides_exports(&self) -> bool {
+        let target = self.rust_target().unwrap_or_default();
+        self.options.visibility == SymbolVisibility::Hidden
+            && !target.contains("wasm")
+            && self.options.artifact == ArtifactKind::Staticlib
+    }
+
+    /// Prefix of mangled export symbols (`az_<hash>__`), or `None` when Zig
+    /// functions are exported under their own names
+    ///
+    /// The hash covers the package name and version, so two versions of the
+    /// same crate in one binary do not collide either. WASM exports are the
+    /// module's public interface and are never mangled.
+    fn symbol_prefix(&self) -> Option<String> {
+        let target = self.rust_target().unwrap_or_default();
+        if !self.options.mangle_symbols || target.contains("wasm") {
+            return None;
+        }
+        let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let version = env::var("CARGO_PKG_VERSION").unwrap_or_default();
+        let hash = Sha256::digest(format!("{package}@{version}"));
+        Some(format!("az_{:02x}{:02x}{:02x}__", hash[0], hash[1], hash[2]))
+    }
+
+    /// Symbol name of the Zig export `name`
+    ///
+    /// `public_symbols` and the runtime's `autozig_*` helpers keep their
+    /// names; the macros apply the same rule to their `link_name`s.
+    fn export_symbol(&self, prefix: Option<&str>, name: &str) -> String {
+        match prefix {
+            Some(prefix)
+                if !name.starts_with("autozig_")
+                    && !self.options.public_symbols.iter().any(|p| p == name) =>
+            {
+                format!("{prefix}{name}")
+            },
+            _ => name.to_string(),
+        }
+    }
+
+    /// Tell the macros to link against the mangled symbols
+    fn export_symbol_prefix(&self) {
+        if let Some(prefix) = self.symbol_prefix() {
+            println!("cargo:rustc-env=AUTOZIG_SYMBOL_PREFIX={prefix}");
+            println!(
+                "cargo:rustc-env=AUTOZIG_STABLE_SYMBOLS={}",
+                self.options.public_symbols.join(",")
+            );
+        }
+    }
+
+    /// Apply symbol visibility and mangling settings to generated Zig source
+    fn apply_visibility(&self, zig_code: String) -> String {
+        let hidden = self.hides_exports();
+        let prefix = self.symbol_prefix();
+        if !hidden && prefix.is_none() {
+            return zig_code;
+        }
+        visibility::rewrite_exports(&zig_code, |name| {
+            let public = self.options.public_symbols.iter().any(|p| p == name);
+            (self.export_symbol(prefix.as_deref(), name), hidden && !public)
+        })
+    }
+
+    /// Copy an external Zig file into OUT_DIR, applying float and visibility
+    /// settings
+    fn copy_zig_file(&self, src: &Path, dest: &Path) -> Result<()> {
+        fs::write(dest, self.zig_file_source(src)?)
+            .with_context(|| format!("Failed to copy {}", src.display()))
+    }
+
+    /// Contents of an external Zig file as compiled, with float and
+    /// visibility settings applied
+    fn zig_file_source(&self, src: &Path) -> Result<String> {
+        let code =
+            fs::read_to_string(src).with_context(|| format!("Failed to copy {}", src.display()))?;
+        let module = src.file_name().unwrap_or_default().to_string_lossy();
+        let code = self.strip_unused_exports(code)?;
+        let code = self.apply_float_settings(&module, float16::lower_f16_exports(&code))?;
+        Ok(self.apply_visibility(code))
+    }
+
+    /// Check that only intended Zig exports are dynamically visible
+    fn verify_visibility(&self, source: &Path, lib_path: &Path) -> Result<()> {
+        if !self.hides_exports() {
+            return Ok(());
+        }
+
+        let prefix = self.symbol_prefix();
+        let exports: Vec<_> = self
+            .export_names_near(source)?
+            .iter()
+            .map(|name| self.export_symbol(prefix.as_deref(), name))
+            .collect();
+        visibility::verify_exported_symbols(lib_path, &exports, &self.options.public_symbols)
+    }
+
     /// Force export of Zig functions for WASM targets
     fn force_wasm_exports(&self) -> Result<()> {
         use ts_generator::FunctionSignature;
@@ -289,8 +1577,10 @@ impl AutoZigEngine {
         for decl in function_decls {
             if let Some(sig) = FunctionSignature::parse(&decl) {
                 // Emit linker argument to force export
-                println!("cargo:rustc-link-arg=--export={}", sig.name);
-                export_count += 1;
+                for name in sig.export_names() {
+                    println!("cargo:rustc-link-arg=--export={}", name);
+                    export_count += 1;
+                }
             }
         }
 
@@ -301,21 +1591,22 @@ impl AutoZigEngine {
         Ok(())
     }
 
-    /// Generate main module with @import statements
-    fn generate_main_module(
-        &self,
-        embedded_code: &[String],
-        all_zig_files: &[PathBuf],
-    ) -> Result<String> {
-        self.generate_main_module_with_files(embedded_code, all_zig_files)
-    }
-
     /// Generate main module with @import statements using specific file list
     fn generate_main_module_with_files(
         &self,
         embedded_code: &[String],
         zig_files: &[PathBuf],
     ) -> Result<String> {
+        let embedded_code = embedded_code
+            .iter()
+            .map(|code| {
+                lower_exports(
+                    &self.strip_unused_exports(code.clone())?,
+                    self.options.codegen.stack_traces,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let embedded_code = embedded_code.as_slice();
         let mut main = String::new();
 
         // Check if embedded code already contains std import to avoid duplication
@@ -345,7 +1636,7 @@ impl AutoZigEngine {
             }
         }
         if !zig_files.is_empty() {
-            main.push_str("\n");
+            main.push('\n');
             main.push_str("// Force exported symbols from imported modules to be included\n");
             main.push_str("comptime {\n");
             for (idx, _) in zig_files.iter().enumerate() {
@@ -363,13 +1654,26 @@ impl AutoZigEngine {
             }
         }
 
+        // Helper types are declared in the root module so that imported files can
+        // reach them via @import("root")
+        let mut used_code = embedded_code.concat();
+        for file in zig_files {
+            used_code.push_str(&fs::read_to_string(file).unwrap_or_default());
+        }
+        let helpers = self.helper_code_for(&used_code);
+        if !helpers.is_empty() {
+            main.push_str("// AutoZig helper types\n");
+            main.push_str(&helpers);
+            main.push('\n');
+        }
+
         // Generate ABI lowering wrappers for struct returns
         let abi_wrappers = self.generate_abi_lowering_wrappers(embedded_code);
         if !abi_wrappers.is_empty() {
             main.push_str("// ABI Lowering: Pointer-based wrappers for struct returns\n");
             main.push_str("// These wrappers ensure cross-platform ABI compatibility\n");
             main.push_str(&abi_wrappers);
-            main.push_str("\n");
+            main.push('\n');
         }
 
         Ok(main)
@@ -377,7 +1681,7 @@ impl AutoZigEngine {
 
     /// Generate ABI lowering wrappers for functions returning structs
     /// Transforms: export fn foo() -> StructType
-    /// Into: export fn foo__autozig_ptr() -> *const StructType
+    /// Into: export fn foo__autozig_out(__autozig_out: *StructType) void
     fn generate_abi_lowering_wrappers(&self, embedded_code: &[String]) -> String {
         let mut wrappers = String::new();
 
@@ -389,7 +1693,7 @@ impl AutoZigEngine {
                 if needs_abi_wrapper(&export_fn.return_type) {
                     let wrapper = generate_ptr_wrapper(&export_fn);
                     wrappers.push_str(&wrapper);
-                    wrappers.push_str("\n");
+                    wrappers.push('\n');
                 }
             }
         }
@@ -402,7 +1706,7 @@ impl AutoZigEngine {
     /// CRITICAL FIX:
     /// - Arrays: Rename impl to _impl, generate export wrapper returning
     ///   pointer (macro expects this)
-    /// - Structs: Keep export AND add __autozig_ptr wrapper (dual export for
+    /// - Structs: Keep export AND add __autozig_out wrapper (dual export for
     ///   compatibility)
     fn generate_abi_lowering_with_modified_code(
         &self,
@@ -424,12 +1728,12 @@ impl AutoZigEngine {
                         functions_to_rename.push(export_fn.name.clone());
                         let wrapper = generate_array_pointer_wrapper(&export_fn);
                         wrappers.push_str(&wrapper);
-                        wrappers.push_str("\n");
+                        wrappers.push('\n');
                     } else {
-                        // Structs: keep export, add __autozig_ptr wrapper
+                        // Structs: keep export, add __autozig_out wrapper
                         let wrapper = generate_ptr_wrapper(&export_fn);
                         wrappers.push_str(&wrapper);
-                        wrappers.push_str("\n");
+                        wrappers.push('\n');
                     }
                 }
             }
@@ -448,15 +1752,16 @@ impl AutoZigEngine {
     /// Generate build.zig file with C source file support
     fn generate_build_zig_with_c(
         &self,
+        zig_target: &ZigTarget,
         _embedded_code: &[String],
-        all_zig_files: &[PathBuf],
+        _all_zig_files: &[PathBuf],
         c_source_files: &[PathBuf],
     ) -> Result<String> {
-        let rust_target = env::var("TARGET").unwrap_or_else(|_| "native".to_string());
-        let zig_target = rust_to_zig_target(&rust_target);
-        let is_wasm32 = zig_target.contains("wasm32");
-        let is_wasm64 = zig_target.contains("wasm64");
+        let is_wasm32 = zig_target.triple.contains("wasm32");
+        let is_wasm64 = zig_target.triple.contains("wasm64");
         let is_wasm = is_wasm32 || is_wasm64;
+        let is_wasi = zig_target.triple.contains("wasi");
+        let freestanding = self.options.codegen.freestanding || zig_target.is_bare_metal();
 
         let mut build = String::new();
         build.push_str("const std = @import(\"std\");\n\n");
@@ -465,54 +1770,31 @@ impl AutoZigEngine {
         // Target configuration with BASELINE CPU to match zig build-lib behavior
         // This fixes the "incompatible with elf64-x86-64" linking error
         build.push_str("    // Force baseline CPU model to match Rust's expectations\n");
-        build.push_str("    const target = b.resolveTargetQuery(.{\n");
-        build.push_str("        .cpu_model = .baseline,  // Critical: use baseline, not native\n");
-
-        if is_wasm64 {
-            build.push_str("        .cpu_arch = .wasm64,\n");
-            build.push_str("        .os_tag = .freestanding,\n");
-        } else if is_wasm32 {
-            build.push_str("        .cpu_arch = .wasm32,\n");
-            build.push_str("        .os_tag = .freestanding,\n");
-        } else if zig_target.contains("x86_64") {
-            build.push_str("        .cpu_arch = .x86_64,\n");
-            if zig_target.contains("linux") {
-                build.push_str("        .os_tag = .linux,\n");
-                if zig_target.contains("musl") {
-                    build.push_str("        .abi = .musl,\n");
-                } else {
-                    build.push_str("        .abi = .gnu,\n");
-                }
-            } else if zig_target.contains("macos") {
-                build.push_str("        .os_tag = .macos,\n");
-            } else if zig_target.contains("windows") {
-                build.push_str("        .os_tag = .windows,\n");
-                if zig_target.contains("gnu") {
-                    build.push_str("        .abi = .gnu,\n");
-                } else {
-                    build.push_str("        .abi = .msvc,\n");
-                }
-            }
-        } else if zig_target.contains("aarch64") {
-            build.push_str("        .cpu_arch = .aarch64,\n");
-            if zig_target.contains("linux") {
-                build.push_str("        .os_tag = .linux,\n");
-                build.push_str("        .abi = .gnu,\n");
-            } else if zig_target.contains("macos") {
-                build.push_str("        .os_tag = .macos,\n");
-            }
-        }
-
-        build.push_str("    });\n");
+        build.push_str("    const query = std.Target.Query.parse(.{\n");
+        build.push_str(&format!("        .arch_os_abi = \"{}\",\n", zig_target.triple));
+        let cpu_features = if is_wasm && self.options.codegen.wasm_threads {
+            self.options.codegen.wasm_cpu()
+        } else {
+            zig_target.mcpu()
+        };
+        build.push_str(&format!("        .cpu_features = \"{}\",\n", cpu_features));
+        build.push_str("    }) catch @panic(\"invalid autozig target\");\n");
+        build.push_str("    const target = b.resolveTargetQuery(query);\n");
 
         // WASM64 FIX: Force ReleaseFast for WASM to avoid Thread/POSIX errors
         // In Debug mode, std.ArrayList and std.AutoHashMap use Thread.getCurrentId()
         // and POSIX calls which are unavailable in freestanding WASM
         // environment
-        if is_wasm {
+        let codegen = self.options.codegen;
+        if codegen.optimize.is_some() || codegen.sanitizer.is_some() || codegen.stack_traces {
+            build.push_str(&format!(
+                "    const optimize = std.builtin.OptimizeMode.{};\n\n",
+                codegen.optimize_or(ZigOptimize::ReleaseFast).as_str()
+            ));
+        } else if is_wasm || freestanding {
             build.push_str(
-                "    // Force ReleaseFast for WASM to bypass Debug-mode Thread/POSIX \
-                 requirements\n",
+                "    // Force ReleaseFast for WASM and bare metal to bypass Debug-mode \
+                 Thread/POSIX requirements\n",
             );
             build.push_str("    const optimize = std.builtin.OptimizeMode.ReleaseFast;\n\n");
         } else {
@@ -524,17 +1806,35 @@ impl AutoZigEngine {
         build.push_str("        .root_source_file = b.path(\"generated_main.zig\"),\n");
         build.push_str("        .target = target,\n");
         build.push_str("        .optimize = optimize,\n");
+        if codegen.strip || codegen.reproducible {
+            build.push_str("        .strip = true,\n");
+        }
+        if codegen.single_threaded {
+            build.push_str("        .single_threaded = true,\n");
+        }
+        if codegen.stack_traces || codegen.sanitizer.is_some() {
+            build.push_str("        .omit_frame_pointer = false,\n");
+        }
+        if codegen.stack_traces {
+            build.push_str("        .error_tracing = true,\n");
+        }
+        if let Some(sanitizer) = codegen.sanitizer {
+            build.push_str(match sanitizer {
+                Sanitizer::Thread => "        .sanitize_thread = true,\n",
+                Sanitizer::Address | Sanitizer::Undefined => "        .sanitize_c = .full,\n",
+            });
+        }
         build.push_str("    });\n\n");
 
         // Create static library using addLibrary (Zig 0.15.2 API)
         build.push_str("    const lib = b.addLibrary(.{\n");
         build.push_str("        .name = \"autozig\",\n");
         build.push_str("        .root_module = mod,\n");
-        build.push_str("        .linkage = .static,\n");
+        build.push_str(&format!("        .linkage = .{},\n", self.options.artifact.linkage()));
         build.push_str("    });\n\n");
 
         // Enable PIC (Position Independent Code) for compatibility with Rust
-        if !is_wasm {
+        if !is_wasm && !freestanding {
             build.push_str("    // Enable PIC for Rust FFI compatibility\n");
             build.push_str("    lib.root_module.pic = true;\n\n");
         }
@@ -544,24 +1844,62 @@ impl AutoZigEngine {
             build.push_str("    // WASM-specific configuration\n");
             build.push_str("    lib.root_module.stack_protector = false;\n");
             build.push_str("    lib.root_module.red_zone = false;\n");
+            if is_wasi {
+                // Zig's std.fs goes through wasi-libc, like Rust's std, so
+                // both resolve paths against the same preopened directories
+                build.push_str("    lib.linkLibC();\n");
+                build.push_str(&format!(
+                    "    lib.wasi_exec_model = .{};\n",
+                    codegen.wasi_exec_model.as_str()
+                ));
+            }
+        } else if freestanding {
+            build.push_str("    // Freestanding: no libc, no stack protector runtime\n");
+            build.push_str("    lib.root_module.stack_protector = false;\n");
+            for system_lib in &self.options.system_libs {
+                build.push_str(&format!("    lib.linkSystemLibrary({system_lib:?});\n"));
+            }
         } else {
             build.push_str("    // Link with libc\n");
             build.push_str("    lib.linkLibC();\n");
+            for system_lib in &self.options.system_libs {
+                build.push_str(&format!("    lib.linkSystemLibrary({system_lib:?});\n"));
+            }
+        }
+        for dir in self.include_dirs() {
+            build.push_str(&format!(
+                "    lib.addIncludePath(.{{ .cwd_relative = {:?} }});\n",
+                dir.display().to_string()
+            ));
         }
 
-        // Add C source files if present
+        // Add C and C++ source files if present
         if !c_source_files.is_empty() {
-            build.push_str("\n    // Add C source files\n");
+            build.push_str("\n    // Add C and C++ source files\n");
             for c_file in c_source_files {
                 if let Some(file_name) = c_file.file_name() {
+                    let file_name = file_name.to_string_lossy();
+                    let flags = self
+                        .source_flags(c_file)
+                        .iter()
+                        .map(|flag| format!("{flag:?}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
                     build.push_str(&format!(
-                        "    lib.addCSourceFile(.{{ .file = b.path(\"{}\"), .flags = \
-                         &.{{\"-fno-sanitize=undefined\"}} }});\n",
-                        file_name.to_string_lossy()
+                        "    lib.addCSourceFile(.{{ .file = b.path(\"{file_name}\"), .flags = \
+                         &.{{{flags}}} }});\n"
                     ));
                 }
             }
         }
+        if c_source_files
+            .iter()
+            .any(|file| scanner::is_cpp_source(file))
+            && (!is_wasm || is_wasi)
+            && !freestanding
+        {
+            build.push_str("    lib.linkLibCpp();\n");
+        }
 
         build.push_str("\n    b.installArtifact(lib);\n");
         build.push_str("}\n");
@@ -569,35 +1907,287 @@ impl AutoZigEngine {
         Ok(build)
     }
 
-    /// Generate build.zig file for modular compilation (Zig 0.15.2 compatible)
-    /// Legacy version without C file support
-    fn generate_build_zig(
-        &self,
-        embedded_code: &[String],
-        all_zig_files: &[PathBuf],
-    ) -> Result<String> {
-        // Delegate to version with empty C files
-        self.generate_build_zig_with_c(embedded_code, all_zig_files, &[])
+    /// Compiler flags of a C or C++ source in the generated build.zig
+    ///
+    /// C++ sources default to C++17 unless `source_flags` picks a standard.
+    fn source_flags(&self, file: &Path) -> Vec<String> {
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+        let extra = self
+            .options
+            .source_flags
+            .iter()
+            .filter(|(name, _)| *name == file_name)
+            .flat_map(|(_, flags)| flags.iter().cloned());
+        // UBSan stays off unless a sanitizer was asked for: Zig enables it for
+        // C code by default, and its traps would abort code built for gcc
+        let mut flags = match self.options.codegen.sanitizer {
+            None => vec!["-fno-sanitize=undefined".to_string()],
+            Some(Sanitizer::Address) => vec!["-fsanitize=address".to_string()],
+            Some(Sanitizer::Thread | Sanitizer::Undefined) => Vec::new(),
+        };
+        if self.options.codegen.reproducible {
+            flags.push(format!("-ffile-prefix-map={}=.", self.out_dir.display()));
+        }
+        flags.extend(extra);
+        if scanner::is_cpp_source(file) && !flags.iter().any(|flag| flag.starts_with("-std=")) {
+            flags.insert(0, "-std=c++17".to_string());
+        }
+        flags
+    }
+
+    /// Link the static library, and the system libraries it calls into
+    fn link_library(&self, lib_path: &Path) {
+        if self.options.artifact == ArtifactKind::Cdylib {
+            // Loaded at runtime by the host, not linked
+            println!("cargo:rustc-env=AUTOZIG_CDYLIB={}", lib_path.display());
+            return;
+        }
+        println!("cargo:rustc-link-search=native={}", self.out_dir.display());
+
+        // For WASM targets, use +whole-archive to force inclusion of all symbols
+        // Without this, wasm-ld only includes referenced symbols, but extern "C"
+        // declarations become imports instead of references
+        let target = self.rust_target().unwrap_or_default();
+        if target.contains("wasm") {
+            // Use +whole-archive modifier (Cargo 1.61+)
+            let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+            let lib_name = pkg_name.replace("-", "_");
+            println!("cargo:rustc-link-lib=static:+whole-archive={}", lib_name);
+        } else {
+            let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+            let lib_name = pkg_name.replace("-", "_");
+            println!("cargo:rustc-link-lib=static={}", lib_name);
+            // After the archive, which refers to them
+            for system_lib in &self.options.system_libs {
+                println!("cargo:rustc-link-lib={system_lib}");
+            }
+        }
+    }
+
+    /// Link archives bound with `bind_zig!` without compiling anything,
+    /// after checking they define every declared symbol
+    fn link_prebuilt_libraries(&self) -> Result<()> {
+        let scanner = self.scanner(self.mode);
+        for library in scanner.scan_prebuilt()? {
+            let path = &library.path;
+            println!("cargo:rerun-if-changed={}", path.display());
+
+            if !path.exists() {
+                anyhow::bail!("Prebuilt library not found: {}", path.display());
+            }
+            let lib_name = static_lib_name(path).with_context(|| {
+                format!("Expected a static library (.a or .lib): {}", path.display())
+            })?;
+
+            visibility::verify_defined_symbols(path, &library.symbols)?;
+
+            if let Some(dir) = path.parent() {
+                println!("cargo:rustc-link-search=native={}", dir.display());
+            }
+            println!("cargo:rustc-link-lib=static={}", lib_name);
+        }
+        Ok(())
+    }
+
+    /// Write `OUT_DIR/autozig_manifest.json` listing every linked foreign
+    /// source with its hash and declared license
+    fn write_source_manifest(&self) -> Result<()> {
+        let scanner = self.scanner(self.mode);
+        let sources = scanner.foreign_sources()?;
+
+        let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let version = env::var("CARGO_PKG_VERSION").unwrap_or_default();
+        let manifest =
+            SourceManifest::collect(&package, &version, &sources, scanner.manifest_dir())?;
+
+        let manifest_path = self.out_dir.join("autozig_manifest.json");
+        fs::write(&manifest_path, manifest.to_json()).context("Failed to write source manifest")?;
+
+        if self.options.embed_manifest {
+            println!("cargo:rustc-env=AUTOZIG_SOURCE_MANIFEST={}", manifest_path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Write `OUT_DIR/autozig_build_info.json` and expose the Zig version,
+    /// target and optimization mode to the crate (see [`build_info`])
+    fn write_build_info(&self) -> Result<()> {
+        let zig_target = self.zig_target()?;
+        let is_wasm = zig_target.triple.contains("wasm");
+        let codegen = self.options.codegen;
+        // Mirrors the defaults documented on `CodegenFlags::optimize`
+        let opt_level = match (codegen.optimize, codegen.sanitizer) {
+            (None, None)
+                if self.mode == CompilationMode::ModularBuildZig
+                    && !is_wasm
+                    && !codegen.stack_traces =>
+            {
+                "Debug"
+            },
+            _ => codegen.optimize_or(ZigOptimize::ReleaseFast).as_str(),
+        };
+        let mut flags = vec![format!(
+            "-mcpu={}",
+            if is_wasm {
+                codegen.wasm_cpu()
+            } else {
+                zig_target.mcpu()
+            }
+        )];
+        if codegen.strip || codegen.reproducible {
+            flags.push("-fstrip".to_string());
+        }
+        if codegen.single_threaded {
+            flags.push("-fsingle-threaded".to_string());
+        }
+        if codegen.stack_traces {
+            flags.push("-ferror-tracing".to_string());
+        }
+        if let Some(sanitizer) = codegen.sanitizer {
+            flags.push(format!("-fsanitize={}", sanitizer.as_str()));
+        }
+
+        let info = BuildInfo {
+            autozig_version: env!("CARGO_PKG_VERSION").to_string(),
+            zig_version: ZigCompiler::new()
+                .check_version()
+                .unwrap_or_else(|_| "unknown".to_string()),
+            target: self.rust_target().unwrap_or_else(|| "native".to_string()),
+            zig_target: zig_target.triple.to_string(),
+            opt_level: opt_level.to_string(),
+            flags,
+            mode: format!("{:?}", self.mode),
+            artifact: self.options.artifact.linkage().to_string(),
+        };
+        info.emit(&self.out_dir.join("autozig_build_info.json"))
+    }
+
+    /// Write the current ABI snapshot to `OUT_DIR/autozig_abi_snapshot.txt`
+    /// and create the committed per-target copy if it is missing
+    ///
+    /// Both paths are exposed to the crate for `autozig::abi_snapshot_test!`.
+    fn write_abi_snapshot(&self) -> Result<()> {
+        println!("cargo:rerun-if-env-changed={}", abi_snapshot::UPDATE_ENV);
+        // Expand the macros as the crate's own `std` feature does
+        let std = !self.options.codegen.freestanding;
+        let snapshot =
+            autozig_codegen::with_std(std, || abi_snapshot::snapshot_dir(&self.src_dir))?;
+
+        let current = self.out_dir.join("autozig_abi_snapshot.txt");
+        fs::write(&current, &snapshot).context("Failed to write ABI snapshot")?;
+
+        let target = self.rust_target().unwrap_or_else(|| "native".to_string());
+        let golden = self
+            .scanner(self.mode)
+            .manifest_dir()
+            .join("tests")
+            .join("abi_snapshots")
+            .join(format!("{target}.txt"));
+        let update = env::var(abi_snapshot::UPDATE_ENV).is_ok_and(|v| v == "1");
+        if update || !golden.exists() {
+            if let Some(dir) = golden.parent() {
+                fs::create_dir_all(dir).context("Failed to create ABI snapshot directory")?;
+            }
+            fs::write(&golden, &snapshot).context("Failed to write ABI snapshot")?;
+            println!("cargo:warning=Wrote ABI snapshot {}; commit it", golden.display());
+        }
+
+        println!("cargo:rustc-env=AUTOZIG_ABI_SNAPSHOT={}", current.display());
+        println!("cargo:rustc-env=AUTOZIG_ABI_GOLDEN={}", golden.display());
+        Ok(())
+    }
+
+    /// Write the header requested with `c_header`, declaring the exports of
+    /// `zig_sources` (before symbol visibility is applied)
+    fn write_c_header(&self, zig_sources: &[String]) -> Result<()> {
+        let Some(header) = &self.options.c_header else {
+            return Ok(());
+        };
+        let path = self.scanner(self.mode).manifest_dir().join(header);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let text = c_header::render(&c_header::guard_for(&file_name), zig_sources);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        // Leave the file untouched when unchanged, so C builds don't rebuild
+        if fs::read_to_string(&path).is_ok_and(|old| old == text) {
+            return Ok(());
+        }
+        fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Write the WIT world requested with `wit` and have the macros generate
+    /// the component exports it describes
+    fn write_wit(&self) -> Result<()> {
+        let Some(wit_path) = &self.options.wit else {
+            return Ok(());
+        };
+        let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
+        let text = wit::world_for_dir(&self.src_dir, &package)?;
+        let path = self.scanner(self.mode).manifest_dir().join(wit_path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        if !fs::read_to_string(&path).is_ok_and(|old| old == text) {
+            fs::write(&path, text)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        println!("cargo:rustc-env=AUTOZIG_WIT=1");
+        Ok(())
+    }
+
+    /// Write the UniFFI UDL file and glue, if requested
+    fn write_udl(&self) -> Result<()> {
+        let Some(udl_path) = &self.options.udl else {
+            return Ok(());
+        };
+        let namespace = env::var("CARGO_PKG_NAME")
+            .map(|name| name.replace('-', "_"))
+            .unwrap_or_else(|_| "autozig".to_string());
+        let udl_name = udl_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| namespace.clone());
+        let uniffi = udl::uniffi_for_dir(&self.src_dir, &namespace, &udl_name)?;
+        let path = self.scanner(self.mode).manifest_dir().join(udl_path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        if !fs::read_to_string(&path).is_ok_and(|old| old == uniffi.udl) {
+            fs::write(&path, uniffi.udl)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        let glue = self.out_dir.join("autozig_uniffi.rs");
+        fs::write(&glue, uniffi.glue)
+            .with_context(|| format!("Failed to write {}", glue.display()))?;
+        Ok(())
+    }
+
+    /// [`Self::write_c_header`] for the main module and the `include_zig!`
+    /// files of the modular modes
+    fn write_modular_c_header(&self, main_zig: &str, external_files: &[PathBuf]) -> Result<()> {
+        if self.options.c_header.is_none() {
+            return Ok(());
+        }
+        let mut sources = vec![main_zig.to_string()];
+        for file in external_files {
+            let code = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            sources.push(float16::lower_f16_exports(&code));
+        }
+        self.write_c_header(&sources)
     }
 
-    /// Link the static library
-    fn link_library(&self) {
-        println!("cargo:rustc-link-search=native={}", self.out_dir.display());
-
-        // For WASM targets, use +whole-archive to force inclusion of all symbols
-        // Without this, wasm-ld only includes referenced symbols, but extern "C"
-        // declarations become imports instead of references
-        let target = env::var("TARGET").unwrap_or_default();
-        if target.contains("wasm") {
-            // Use +whole-archive modifier (Cargo 1.61+)
-            let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
-            let lib_name = pkg_name.replace("-", "_");
-            println!("cargo:rustc-link-lib=static:+whole-archive={}", lib_name);
-        } else {
-            let pkg_name = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "autozig".to_string());
-            let lib_name = pkg_name.replace("-", "_");
-            println!("cargo:rustc-link-lib=static={}", lib_name);
+    /// Report misuse of generated bindings found in the crate's sources
+    fn run_safety_lints(&self) -> Result<()> {
+        for warning in lints::check_dir(&self.src_dir)? {
+            println!("cargo:warning={warning}");
         }
+        Ok(())
     }
 
     /// Generate TypeScript bindings (.d.ts and .js files) for WASM modules
@@ -664,7 +2254,7 @@ impl AutoZigEngine {
         for entry in walkdir::WalkDir::new(&self.src_dir)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "rs"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
         {
             let content = fs::read_to_string(entry.path())?;
 
@@ -783,7 +2373,7 @@ impl AutoZigEngine {
     /// Public method to generate TypeScript bindings from #[autozig_export]
     /// functions This is called from build.rs via autozig_build crate
     pub fn generate_typescript_bindings_for_rust_exports(&self) -> Result<()> {
-        let rust_target = env::var("TARGET").unwrap_or_else(|_| "native".to_string());
+        let rust_target = self.rust_target().unwrap_or_else(|| "native".to_string());
 
         // Only generate bindings for WASM targets
         if !rust_target.contains("wasm") {
@@ -796,7 +2386,7 @@ impl AutoZigEngine {
         for entry in walkdir::WalkDir::new(&self.src_dir)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "rs"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
         {
             let content = fs::read_to_string(entry.path())?;
             self.extract_autozig_export_functions(&content, &mut declarations);
@@ -854,36 +2444,779 @@ impl AutoZigEngine {
     }
 }
 
-/// Map Rust target triple to Zig target
-fn rust_to_zig_target(rust_target: &str) -> &str {
-    match rust_target {
-        // Linux targets
-        "x86_64-unknown-linux-gnu" => "x86_64-linux-gnu",
-        "x86_64-unknown-linux-musl" => "x86_64-linux-musl",
-        "aarch64-unknown-linux-gnu" => "aarch64-linux-gnu",
-        "aarch64-unknown-linux-musl" => "aarch64-linux-musl",
-        "arm-unknown-linux-gnueabihf" => "arm-linux-gnueabihf",
-        "i686-unknown-linux-gnu" => "i386-linux-gnu",
-
-        // macOS targets
-        "x86_64-apple-darwin" => "x86_64-macos",
-        "aarch64-apple-darwin" => "aarch64-macos",
-
-        // Windows targets
-        "x86_64-pc-windows-msvc" => "x86_64-windows",
-        "x86_64-pc-windows-gnu" => "x86_64-windows-gnu",
-        "i686-pc-windows-msvc" => "i386-windows",
-        "aarch64-pc-windows-msvc" => "aarch64-windows",
-
-        // WebAssembly
-        "wasm32-unknown-unknown" => "wasm32-freestanding",
-        "wasm32-wasi" => "wasm32-wasi",
-        "wasm64-unknown-unknown" => "wasm64-freestanding",
-        "wasm64-wasi" => "wasm64-wasi",
-
-        // Default to native
-        _ => "native",
+/// Rewrite the exports of embedded Zig code into their C-ABI form;
+/// `trace_errors` reports the error return traces of error unions to Rust
+fn lower_exports(zig_code: &str, trace_errors: bool) -> Result<String> {
+    let (zig_code, made_extern) = extern_structs::require_extern(zig_code);
+    for name in made_extern {
+        println!(
+            "cargo:warning=autozig: `{name}` crosses the FFI boundary, so it was made an `extern \
+             struct`; declare it `extern struct` in the Zig code"
+        );
+    }
+    let zig_code = error_union::lower_error_unions(&zig_code, trace_errors)?;
+    Ok(float16::lower_f16_exports(&static_str::lower_str_exports(&zig_code)))
+}
+
+/// Name to pass to `rustc-link-lib` for a static archive path
+/// (`vendor/libfoo.a` -> `foo`, `foo.lib` -> `foo`)
+fn static_lib_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    if let Some(stem) = file_name.strip_suffix(".a") {
+        Some(stem.strip_prefix("lib").unwrap_or(stem).to_string())
+    } else {
+        file_name.strip_suffix(".lib").map(str::to_string)
+    }
+}
+
+/// Zig-side counterpart of `autozig::ffi_types::ZigResult`, used for
+/// functions declared as returning `Result<T, E>` on the Rust side
+const AUTOZIG_RESULT_ZIG: &str = r#"
+/// Tagged result returned to Rust as `Result<T, E>`
+pub fn AutoZigResult(comptime T: type, comptime E: type) type {
+    return extern struct {
+        is_ok: u8,
+        ok_value: T,
+        err_value: E,
+
+        pub fn ok(value: T) @This() {
+            return .{ .is_ok = 1, .ok_value = value, .err_value = undefined };
+        }
+
+        pub fn err(e: E) @This() {
+            return .{ .is_ok = 0, .ok_value = undefined, .err_value = e };
+        }
+    };
+}
+"#;
+
+/// Zig-side counterpart of `autozig::ffi_types::ZigOption`, used for
+/// functions declared as returning `Option<T>` for value types
+const AUTOZIG_OPTION_ZIG: &str = r#"
+/// Optional value returned to Rust as `Option<T>`
+pub fn AutoZigOption(comptime T: type) type {
+    return extern struct {
+        is_some: u8,
+        value: T,
+
+        pub fn some(value: T) @This() {
+            return .{ .is_some = 1, .value = value };
+        }
+
+        pub fn none() @This() {
+            return .{ .is_some = 0, .value = undefined };
+        }
+    };
+}
+"#;
+
+/// Zig-side counterpart of `autozig::ffi_types::ZigBuffer`, used for
+/// functions declared as returning `String`, `Vec<u8>` or `ZigString`
+const AUTOZIG_BYTES_ZIG: &str = r#"
+/// Owned byte buffer returned to Rust as `String` / `Vec<u8>` / `ZigString`
+pub const AutoZigBytes = extern struct {
+    ptr: ?[*]u8,
+    len: usize,
+    cap: usize,
+    free_fn: ?*const fn (?[*]u8, usize, usize) callconv(.c) void,
+
+    /// Allocator backing buffers handed over to Rust
+    pub const allocator = if (@import("builtin").target.cpu.arch.isWasm())
+        @import("std").heap.wasm_allocator
+    else if (@import("builtin").link_libc)
+        @import("std").heap.c_allocator
+    else
+        g_allocator;
+
+    /// Copy `bytes` into a new buffer owned by Rust
+    pub fn fromSlice(bytes: []const u8) AutoZigBytes {
+        const buf = allocator.dupe(u8, bytes) catch return empty();
+        return fromOwned(buf);
+    }
+
+    /// Hand over a buffer allocated with `AutoZigBytes.allocator`
+    pub fn fromOwned(buf: []u8) AutoZigBytes {
+        if (buf.len == 0) {
+            allocator.free(buf);
+            return empty();
+        }
+        return .{ .ptr = buf.ptr, .len = buf.len, .cap = buf.len, .free_fn = &release };
+    }
+
+    pub fn empty() AutoZigBytes {
+        return .{ .ptr = null, .len = 0, .cap = 0, .free_fn = null };
+    }
+
+    fn release(ptr: ?[*]u8, len: usize, cap: usize) callconv(.c) void {
+        _ = len;
+        if (ptr) |p| allocator.free(p[0..cap]);
+    }
+};
+"#;
+
+/// Zig-side counterpart of `autozig::ffi_types::ZigSliceRef`, used for
+/// opaque type methods declared as returning `&[T]` or `&str`
+const AUTOZIG_SLICE_ZIG: &str = r#"
+/// Slice of Zig-owned memory borrowed by Rust as `&[T]` / `&str`
+pub fn AutoZigSlice(comptime T: type) type {
+    return extern struct {
+        ptr: ?[*]const T,
+        len: usize,
+
+        pub fn fromSlice(items: []const T) @This() {
+            return .{ .ptr = items.ptr, .len = items.len };
+        }
+    };
+}
+"#;
+
+/// Bounds-checked reads over a `(ptr, len)` byte view, e.g. an
+/// `autozig::zero_copy::MappedBuffer` passed as `&[u8]`
+const AUTOZIG_MAPPED_ZIG: &str = r#"
+/// Bounds-checked view of bytes borrowed from Rust; reads past the end give null
+pub const AutoZigMapped = struct {
+    bytes: []const u8,
+
+    pub fn init(ptr: [*]const u8, len: usize) AutoZigMapped {
+        return .{ .bytes = ptr[0..len] };
+    }
+
+    pub fn len(self: AutoZigMapped) usize {
+        return self.bytes.len;
+    }
+
+    pub fn get(self: AutoZigMapped, index: usize) ?u8 {
+        if (index >= self.bytes.len) return null;
+        return self.bytes[index];
+    }
+
+    /// `count` bytes starting at `offset`
+    pub fn slice(self: AutoZigMapped, offset: usize, count: usize) ?[]const u8 {
+        if (offset > self.bytes.len or count > self.bytes.len - offset) return null;
+        return self.bytes[offset..][0..count];
+    }
+
+    /// Integer stored at `offset` (no alignment required)
+    pub fn readInt(self: AutoZigMapped, comptime T: type, offset: usize, endian: @import("std").builtin.Endian) ?T {
+        const raw = self.slice(offset, @sizeOf(T)) orelse return null;
+        return @import("std").mem.readInt(T, raw[0..@sizeOf(T)], endian);
+    }
+};
+"#;
+
+/// Zig-side counterpart of `autozig::ffi_types::ZigGrowable`, used for
+/// parameters declared as `&mut Vec<T>` on the Rust side
+const AUTOZIG_GROWABLE_ZIG: &str = r#"
+/// Rust `Vec(T)` that Zig can append to; growth goes through Rust's allocator
+pub fn AutoZigGrowable(comptime T: type) type {
+    return extern struct {
+        ptr: [*]T,
+        len: usize,
+        cap: usize,
+        ctx: ?*anyopaque,
+        grow_fn: *const fn (*@This(), usize) callconv(.c) bool,
+
+        pub fn items(self: *@This()) []T {
+            return self.ptr[0..self.len];
+        }
+
+        /// Make room for at least `additional` more items
+        pub fn ensureUnusedCapacity(self: *@This(), additional: usize) error{OutOfMemory}!void {
+            const needed = @import("std").math.add(usize, self.len, additional) catch return error.OutOfMemory;
+            if (needed <= self.cap) return;
+            if (!self.grow_fn(self, needed)) return error.OutOfMemory;
+        }
+
+        pub fn append(self: *@This(), item: T) error{OutOfMemory}!void {
+            try self.ensureUnusedCapacity(1);
+            self.ptr[self.len] = item;
+            self.len += 1;
+        }
+
+        pub fn appendSlice(self: *@This(), new_items: []const T) error{OutOfMemory}!void {
+            try self.ensureUnusedCapacity(new_items.len);
+            @memcpy(self.ptr[self.len..][0..new_items.len], new_items);
+            self.len += new_items.len;
+        }
+    };
+}
+"#;
+
+/// Zig-side counterparts of `autozig::ffi_types::MapView<K, V>` and
+/// `MapBuilder<K, V>`
+const AUTOZIG_MAP_ZIG: &str = r#"
+/// Rust map (`MapView<K, V>`) that Zig walks entry by entry, once
+pub fn AutoZigMapView(comptime K: type, comptime V: type) type {
+    return extern struct {
+        len: usize,
+        ctx: ?*anyopaque,
+        next_fn: *const fn (*@This(), *K, *V) callconv(.c) bool,
+
+        pub const Entry = struct { key: K, value: V };
+
+        /// Next entry in the map's order, or null after the last one
+        pub fn next(self: *@This()) ?Entry {
+            var entry: Entry = undefined;
+            if (!self.next_fn(self, &entry.key, &entry.value)) return null;
+            return entry;
+        }
+    };
+}
+
+/// Entries returned to Rust as a map (`MapBuilder<K, V>`)
+pub fn AutoZigMapBuilder(comptime K: type, comptime V: type) type {
+    return extern struct {
+        ctx: ?*anyopaque,
+        insert_fn: *const fn (*@This(), *const K, *const V) callconv(.c) bool,
+
+        pub fn put(self: *@This(), key: K, value: V) error{OutOfMemory}!void {
+            if (!self.insert_fn(self, &key, &value)) return error.OutOfMemory;
+        }
+    };
+}
+"#;
+
+/// Zig-side counterparts of `autozig::ffi_types::MatView<T>` and
+/// `MatViewMut<T>`
+const AUTOZIG_MATVIEW_ZIG: &str = r#"
+/// Row-major matrix borrowed from Rust (`MatView<T>`); element `(r, c)` is
+/// at `ptr[r * row_stride + c]`
+pub fn AutoZigMatView(comptime T: type) type {
+    return extern struct {
+        ptr: [*]const T,
+        rows: usize,
+        cols: usize,
+        row_stride: usize,
+
+        /// Element at row `r`, column `c`
+        pub fn at(self: @This(), r: usize, c: usize) T {
+            return self.ptr[autoZigMatIndex(self, r, c)];
+        }
+
+        /// Row `r` as a slice of `cols` elements
+        pub fn row(self: @This(), r: usize) []const T {
+            if (r >= self.rows) @panic("AutoZigMatView row out of bounds");
+            return self.ptr[r * self.row_stride ..][0..self.cols];
+        }
+    };
+}
+
+/// Row-major matrix Zig writes into (`MatViewMut<T>`)
+pub fn AutoZigMatViewMut(comptime T: type) type {
+    return extern struct {
+        ptr: [*]T,
+        rows: usize,
+        cols: usize,
+        row_stride: usize,
+
+        /// Element at row `r`, column `c`
+        pub fn at(self: @This(), r: usize, c: usize) T {
+            return self.ptr[autoZigMatIndex(self, r, c)];
+        }
+
+        /// Pointer to the element at row `r`, column `c`
+        pub fn ptrAt(self: @This(), r: usize, c: usize) *T {
+            return &self.ptr[autoZigMatIndex(self, r, c)];
+        }
+
+        pub fn set(self: @This(), r: usize, c: usize, value: T) void {
+            self.ptr[autoZigMatIndex(self, r, c)] = value;
+        }
+
+        /// Row `r` as a slice of `cols` elements
+        pub fn row(self: @This(), r: usize) []T {
+            if (r >= self.rows) @panic("AutoZigMatViewMut row out of bounds");
+            return self.ptr[r * self.row_stride ..][0..self.cols];
+        }
+
+        pub fn asConst(self: @This()) AutoZigMatView(T) {
+            return .{ .ptr = self.ptr, .rows = self.rows, .cols = self.cols, .row_stride = self.row_stride };
+        }
+    };
+}
+
+fn autoZigMatIndex(view: anytype, r: usize, c: usize) usize {
+    if (r >= view.rows or c >= view.cols) @panic("AutoZigMatView index out of bounds");
+    return r * view.row_stride + c;
+}
+"#;
+
+/// Zig-side counterparts of `autozig::image::ImageBuffer` and `PixelFormat`
+const AUTOZIG_IMAGE_ZIG: &str = r#"
+/// Byte layout of the pixels of an `AutoZigImage` (Rust `PixelFormat`)
+pub const AutoZigPixelFormat = enum(u32) {
+    rgba8 = 0,
+    bgra8 = 1,
+    rgb8 = 2,
+    gray8 = 3,
+
+    pub fn bytesPerPixel(self: AutoZigPixelFormat) usize {
+        return switch (self) {
+            .rgba8, .bgra8 => 4,
+            .rgb8 => 3,
+            .gray8 => 1,
+        };
+    }
+};
+
+/// Pixels borrowed from Rust (`ImageBuffer`), rows packed top to bottom
+pub const AutoZigImage = extern struct {
+    ptr: [*]u8,
+    width: u32,
+    height: u32,
+    format: AutoZigPixelFormat,
+
+    /// Every byte of the image
+    pub fn bytes(self: AutoZigImage) []u8 {
+        return self.ptr[0 .. @as(usize, self.width) * self.height * self.format.bytesPerPixel()];
+    }
+
+    /// Bytes of the pixel at column `x`, row `y`
+    pub fn pixel(self: AutoZigImage, x: usize, y: usize) []u8 {
+        if (x >= self.width or y >= self.height) @panic("AutoZigImage pixel out of bounds");
+        const size = self.format.bytesPerPixel();
+        return self.ptr[(y * self.width + x) * size ..][0..size];
+    }
+
+    /// Pixel at `(x, y)` as RGBA, whatever the format (opaque without alpha)
+    pub fn getRgba(self: AutoZigImage, x: usize, y: usize) [4]u8 {
+        const p = self.pixel(x, y);
+        return switch (self.format) {
+            .rgba8 => .{ p[0], p[1], p[2], p[3] },
+            .bgra8 => .{ p[2], p[1], p[0], p[3] },
+            .rgb8 => .{ p[0], p[1], p[2], 255 },
+            .gray8 => .{ p[0], p[0], p[0], 255 },
+        };
+    }
+
+    /// Store an RGBA color at `(x, y)`; gray images keep its luma, formats
+    /// without alpha drop the alpha
+    pub fn setRgba(self: AutoZigImage, x: usize, y: usize, rgba: [4]u8) void {
+        const p = self.pixel(x, y);
+        switch (self.format) {
+            .rgba8 => @memcpy(p, &rgba),
+            .bgra8 => @memcpy(p, &[4]u8{ rgba[2], rgba[1], rgba[0], rgba[3] }),
+            .rgb8 => @memcpy(p, rgba[0..3]),
+            .gray8 => p[0] = autoZigLuma(rgba),
+        }
+    }
+
+    /// Luma (BT.601) of the pixel at `(x, y)`
+    pub fn luma(self: AutoZigImage, x: usize, y: usize) u8 {
+        return autoZigLuma(self.getRgba(x, y));
+    }
+};
+
+fn autoZigLuma(rgba: [4]u8) u8 {
+    const r: u32 = rgba[0];
+    const g: u32 = rgba[1];
+    const b: u32 = rgba[2];
+    return @intCast((r * 299 + g * 587 + b * 114) / 1000);
+}
+"#;
+
+/// Zig-side counterparts of `autozig::audio::RingBuffer<T>` and its
+/// `Consumer<T>`/`Producer<T>` ends, following its memory ordering contract
+const AUTOZIG_RING_ZIG: &str = r#"
+/// State of a Rust `audio::RingBuffer<T>`: free-running `write` and `read`
+/// indices, each stored (with release ordering) by one end only
+pub fn AutoZigRing(comptime T: type) type {
+    return extern struct {
+        write: usize,
+        read: usize,
+        capacity: usize,
+        data: [*]T,
+        handles: usize,
+    };
+}
+
+/// Consumer end of a ring (`&mut audio::Consumer<T>`)
+pub fn AutoZigRingReader(comptime T: type) type {
+    return extern struct {
+        ring: *AutoZigRing(T),
+
+        /// Samples ready to read
+        pub fn available(self: *const @This()) usize {
+            const ring = self.ring;
+            return @atomicLoad(usize, &ring.write, .acquire) -% @atomicLoad(usize, &ring.read, .monotonic);
+        }
+
+        /// Move up to `out.len` samples into `out`, returning how many were read
+        pub fn readBlock(self: *@This(), out: []T) usize {
+            const ring = self.ring;
+            const read = @atomicLoad(usize, &ring.read, .monotonic);
+            const ready = @atomicLoad(usize, &ring.write, .acquire) -% read;
+            const len = @min(out.len, ready);
+            const start = read & (ring.capacity - 1);
+            const first = @min(len, ring.capacity - start);
+            @memcpy(out[0..first], ring.data[start..][0..first]);
+            @memcpy(out[first..len], ring.data[0 .. len - first]);
+            @atomicStore(usize, &ring.read, read +% len, .release);
+            return len;
+        }
+    };
+}
+
+/// Producer end of a ring (`&mut audio::Producer<T>`)
+pub fn AutoZigRingWriter(comptime T: type) type {
+    return extern struct {
+        ring: *AutoZigRing(T),
+
+        /// Slots free for writing
+        pub fn freeSpace(self: *const @This()) usize {
+            const ring = self.ring;
+            const write = @atomicLoad(usize, &ring.write, .monotonic);
+            return ring.capacity - (write -% @atomicLoad(usize, &ring.read, .acquire));
+        }
+
+        /// Append as many of `samples` as fit, returning how many were written
+        pub fn writeBlock(self: *@This(), samples: []const T) usize {
+            const ring = self.ring;
+            const write = @atomicLoad(usize, &ring.write, .monotonic);
+            const free = ring.capacity - (write -% @atomicLoad(usize, &ring.read, .acquire));
+            const len = @min(samples.len, free);
+            const start = write & (ring.capacity - 1);
+            const first = @min(len, ring.capacity - start);
+            @memcpy(ring.data[start..][0..first], samples[0..first]);
+            @memcpy(ring.data[0 .. len - first], samples[first..len]);
+            @atomicStore(usize, &ring.write, write +% len, .release);
+            return len;
+        }
+    };
+}
+"#;
+
+/// Zig-side counterpart of `autozig::ffi_types::Fixed<I, F>`, which crosses
+/// the boundary as the bare integer
+const AUTOZIG_FIXED_ZIG: &str = r#"
+/// Fixed-point helpers on the raw integer of Rust's `Fixed<I, frac_bits>`
+pub fn AutoZigFixed(comptime I: type, comptime frac_bits: comptime_int) type {
+    return struct {
+        const info = @typeInfo(I).int;
+        const Wide = @import("std").meta.Int(info.signedness, info.bits * 2);
+        const scale: f64 = @floatFromInt(@as(u128, 1) << frac_bits);
+
+        pub fn fromFloat(value: f64) I {
+            return @intFromFloat(@round(value * scale));
+        }
+
+        pub fn toFloat(value: I) f64 {
+            return @as(f64, @floatFromInt(value)) / scale;
+        }
+
+        pub fn mul(a: I, b: I) I {
+            return @truncate((@as(Wide, a) * @as(Wide, b)) >> frac_bits);
+        }
+
+        pub fn div(a: I, b: I) I {
+            return @truncate(@divTrunc(@as(Wide, a) << frac_bits, @as(Wide, b)));
+        }
+    };
+}
+"#;
+
+/// `g_allocator`: Zig allocations through Rust's global allocator once
+/// `autozig::allocator::install()` registered its shims, `c_allocator`
+/// before (`wasm_allocator` on WASM, and failed allocations without libc)
+const AUTOZIG_ALLOCATOR_ZIG: &str = r#"
+/// Allocator backed by Rust's global allocator after `autozig::allocator::install()`
+pub const g_allocator: @import("std").mem.Allocator = .{
+    .ptr = undefined,
+    .vtable = &autozig_allocator.vtable,
+};
+
+const autozig_allocator = struct {
+    const std = @import("std");
+    const Alignment = std.mem.Alignment;
+    pub const AllocFn = *const fn (usize, usize) callconv(.c) ?[*]u8;
+    pub const ReallocFn = *const fn ([*]u8, usize, usize, usize) callconv(.c) ?[*]u8;
+    pub const FreeFn = *const fn ([*]u8, usize, usize) callconv(.c) void;
+
+    var rust_alloc: ?AllocFn = null;
+    var rust_realloc: ?ReallocFn = null;
+    var rust_free: ?FreeFn = null;
+    /// Set by the first fallback allocation; the shims can no longer be installed
+    var fallback_used = std.atomic.Value(bool).init(false);
+
+    const vtable: std.mem.Allocator.VTable = .{
+        .alloc = alloc,
+        .resize = resize,
+        .remap = remap,
+        .free = free,
+    };
+
+    fn fallback() ?std.mem.Allocator {
+        fallback_used.store(true, .release);
+        if (@import("builtin").link_libc) return std.heap.c_allocator;
+        if (@import("builtin").target.cpu.arch.isWasm()) return std.heap.wasm_allocator;
+        return null;
+    }
+
+    fn alloc(_: *anyopaque, len: usize, alignment: Alignment, ret_addr: usize) ?[*]u8 {
+        if (rust_alloc) |f| return f(len, alignment.toByteUnits());
+        const c = fallback() orelse return null;
+        return c.vtable.alloc(c.ptr, len, alignment, ret_addr);
+    }
+
+    fn resize(_: *anyopaque, memory: []u8, alignment: Alignment, new_len: usize, ret_addr: usize) bool {
+        // Rust frees with the allocated size, so it cannot change in place
+        if (rust_alloc != null) return new_len == memory.len;
+        const c = fallback() orelse return false;
+        return c.vtable.resize(c.ptr, memory, alignment, new_len, ret_addr);
+    }
+
+    fn remap(_: *anyopaque, memory: []u8, alignment: Alignment, new_len: usize, ret_addr: usize) ?[*]u8 {
+        if (rust_realloc) |f| return f(memory.ptr, memory.len, alignment.toByteUnits(), new_len);
+        const c = fallback() orelse return null;
+        return c.vtable.remap(c.ptr, memory, alignment, new_len, ret_addr);
+    }
+
+    fn free(_: *anyopaque, memory: []u8, alignment: Alignment, ret_addr: usize) void {
+        if (rust_free) |f| return f(memory.ptr, memory.len, alignment.toByteUnits());
+        const c = fallback() orelse return;
+        c.vtable.free(c.ptr, memory, alignment, ret_addr);
+    }
+};
+
+export fn autozig_install_allocator(
+    alloc: autozig_allocator.AllocFn,
+    realloc: autozig_allocator.ReallocFn,
+    free: autozig_allocator.FreeFn,
+) bool {
+    if (autozig_allocator.fallback_used.load(.acquire)) return false;
+    autozig_allocator.rust_alloc = alloc;
+    autozig_allocator.rust_realloc = realloc;
+    autozig_allocator.rust_free = free;
+    return true;
+}
+"#;
+
+/// Panic handler and error hook of `CodegenFlags::stack_traces`: Zig
+/// formats its stack trace and hands it to `autozig::zig_trace`
+const AUTOZIG_STACK_TRACE_ZIG: &str = r#"
+const autozig_trace = struct {
+    const std = @import("std");
+
+    extern fn autozig_zig_panic(msg: [*]const u8, msg_len: usize, trace: [*]const u8, trace_len: usize) noreturn;
+    extern fn autozig_zig_error_trace(trace: [*]const u8, trace_len: usize) void;
+
+    /// Longest trace passed on; longer ones are truncated
+    const max_len = 16 * 1024;
+
+    /// Symbolized frames of `trace`, or their addresses without debug information
+    fn format(buf: []u8, trace: std.builtin.StackTrace) []const u8 {
+        var writer = std.Io.Writer.fixed(buf);
+        if (std.debug.getSelfDebugInfo()) |debug_info| {
+            std.debug.writeStackTrace(trace, &writer, debug_info, .no_color) catch {};
+        } else |_| {
+            const count = @min(trace.index, trace.instruction_addresses.len);
+            for (trace.instruction_addresses[0..count]) |address| {
+                writer.print("0x{x}\n", .{address}) catch break;
+            }
+        }
+        return writer.buffered();
+    }
+
+    fn onPanic(msg: []const u8, first_trace_addr: ?usize) noreturn {
+        var addresses: [32]usize = undefined;
+        var trace: std.builtin.StackTrace = .{ .index = 0, .instruction_addresses = &addresses };
+        std.debug.captureStackTrace(first_trace_addr orelse @returnAddress(), &trace);
+        var buf: [max_len]u8 = undefined;
+        const text = format(&buf, trace);
+        autozig_zig_panic(msg.ptr, msg.len, text.ptr, text.len);
+    }
+
+    /// Hand the error return trace of an error about to be returned to Rust
+    pub fn recordError(error_trace: ?*std.builtin.StackTrace) void {
+        const trace = error_trace orelse return;
+        var buf: [max_len]u8 = undefined;
+        const text = format(&buf, trace.*);
+        autozig_zig_error_trace(text.ptr, text.len);
+    }
+};
+"#;
+
+/// Root panic handler of `CodegenFlags::stack_traces`, unless the Zig code
+/// declares its own
+const AUTOZIG_PANIC_ZIG: &str = r#"
+/// Zig panics reported to Rust with their stack trace
+pub const panic = @import("std").debug.FullPanic(autozig_trace.onPanic);
+"#;
+
+/// `autozig_runtime` shims for regular builds: process allocator and a
+/// CSPRNG-backed random source
+const AUTOZIG_RUNTIME_ZIG: &str = r#"
+/// Allocator and randomness used by AutoZig kernels
+pub const autozig_runtime = struct {
+    pub fn allocator() @import("std").mem.Allocator {
+        return @import("std").heap.page_allocator;
+    }
+
+    pub fn random() @import("std").Random {
+        return @import("std").crypto.random;
+    }
+};
+"#;
+
+/// `autozig_runtime` shims for test builds: allocations come from a fixed
+/// buffer (stable addresses relative to its base) and randomness from a
+/// PRNG seeded through `autozig_init_rng`
+const AUTOZIG_RUNTIME_DETERMINISTIC_ZIG: &str = r#"
+/// Deterministic allocator and randomness for reproducible tests
+pub const autozig_runtime = struct {
+    var heap_buffer: [16 * 1024 * 1024]u8 align(16) = undefined;
+    pub var fba = @import("std").heap.FixedBufferAllocator.init(&heap_buffer);
+    pub var prng = @import("std").Random.DefaultPrng.init(0);
+
+    pub fn allocator() @import("std").mem.Allocator {
+        return fba.allocator();
+    }
+
+    pub fn random() @import("std").Random {
+        return prng.random();
+    }
+};
+
+export fn autozig_init_rng(seed: u64) void {
+    autozig_runtime.prng = @import("std").Random.DefaultPrng.init(seed);
+}
+
+export fn autozig_reset_allocator() void {
+    autozig_runtime.fba.reset();
+}
+"#;
+
+/// `az_log`: log records handed to the Rust host, implemented by
+/// `autozig::logging` (`log` or `tracing` feature)
+const AUTOZIG_LOG_ZIG: &str = r#"
+/// Log `ptr[0..len]` on the Rust side; levels 1 to 5 are error, warn, info, debug, trace
+extern fn az_log(level: u8, ptr: [*]const u8, len: usize) void;
+"#;
+
+/// `autozig_log.zig`, written next to the generated sources so Zig code can
+/// `@import` it
+const AUTOZIG_LOG_MODULE_ZIG: &str = r#"//! Logging into the Rust host's `log`/`tracing` stack (see `autozig::logging`)
+//!
+//!     const zlog = @import("autozig_log.zig");
+//!     zlog.info("decoded {d} frames", .{count});
+//!
+//! `std.log` can be routed the same way from the root source file:
+//!
+//!     pub const std_options: std.Options = .{ .logFn = zlog.logFn };
+
+const std = @import("std");
+
+extern fn az_log(level: u8, ptr: [*]const u8, len: usize) void;
+
+pub const Level = enum(u8) { err = 1, warn = 2, info = 3, debug = 4, trace = 5 };
+
+/// Longest message passed on; longer ones are truncated
+pub const max_len = 1024;
+
+pub fn log(level: Level, comptime format: []const u8, args: anytype) void {
+    var buf: [max_len]u8 = undefined;
+    const msg = std.fmt.bufPrint(&buf, format, args) catch &buf;
+    az_log(@intFromEnum(level), msg.ptr, msg.len);
+}
+
+pub fn err(comptime format: []const u8, args: anytype) void {
+    log(.err, format, args);
+}
+
+pub fn warn(comptime format: []const u8, args: anytype) void {
+    log(.warn, format, args);
+}
+
+pub fn info(comptime format: []const u8, args: anytype) void {
+    log(.info, format, args);
+}
+
+pub fn debug(comptime format: []const u8, args: anytype) void {
+    log(.debug, format, args);
+}
+
+pub fn trace(comptime format: []const u8, args: anytype) void {
+    log(.trace, format, args);
+}
+
+/// `std.Options.logFn` sending `std.log` records to the host, prefixed with
+/// their scope
+pub fn logFn(
+    comptime level: std.log.Level,
+    comptime scope: @Type(.enum_literal),
+    comptime format: []const u8,
+    args: anytype,
+) void {
+    const prefix = if (scope == .default) "" else "(" ++ @tagName(scope) ++ ") ";
+    const host_level: Level = switch (level) {
+        .err => .err,
+        .warn => .warn,
+        .info => .info,
+        .debug => .debug,
+    };
+    log(host_level, prefix ++ format, args);
+}
+"#;
+
+/// Write `autozig_log.zig` into `dir`, unless it is already up to date
+fn write_log_module(dir: &Path) -> Result<()> {
+    let path = dir.join("autozig_log.zig");
+    if !fs::read_to_string(&path).is_ok_and(|old| old == AUTOZIG_LOG_MODULE_ZIG) {
+        fs::write(&path, AUTOZIG_LOG_MODULE_ZIG)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Collect AutoZig helper types that `zig_code` uses but does not define
+fn helper_types_for(zig_code: &str) -> String {
+    let mut helpers = String::new();
+
+    if zig_code.contains("AutoZigResult(") && !zig_code.contains("fn AutoZigResult(") {
+        helpers.push_str(AUTOZIG_RESULT_ZIG);
+    }
+    if zig_code.contains("AutoZigOption(") && !zig_code.contains("fn AutoZigOption(") {
+        helpers.push_str(AUTOZIG_OPTION_ZIG);
     }
+    if zig_code.contains("AutoZigBytes") && !zig_code.contains("const AutoZigBytes") {
+        helpers.push_str(AUTOZIG_BYTES_ZIG);
+    }
+    if zig_code.contains("AutoZigSlice(") && !zig_code.contains("fn AutoZigSlice(") {
+        helpers.push_str(AUTOZIG_SLICE_ZIG);
+    }
+    if zig_code.contains("AutoZigGrowable(") && !zig_code.contains("fn AutoZigGrowable(") {
+        helpers.push_str(AUTOZIG_GROWABLE_ZIG);
+    }
+    if (zig_code.contains("AutoZigMapView(") || zig_code.contains("AutoZigMapBuilder("))
+        && !zig_code.contains("fn AutoZigMapView(")
+    {
+        helpers.push_str(AUTOZIG_MAP_ZIG);
+    }
+    if zig_code.contains("AutoZigMatView") && !zig_code.contains("fn AutoZigMatView(") {
+        helpers.push_str(AUTOZIG_MATVIEW_ZIG);
+    }
+    if (zig_code.contains("AutoZigImage") || zig_code.contains("AutoZigPixelFormat"))
+        && !zig_code.contains("const AutoZigImage =")
+    {
+        helpers.push_str(AUTOZIG_IMAGE_ZIG);
+    }
+    if zig_code.contains("AutoZigRing") && !zig_code.contains("fn AutoZigRing(") {
+        helpers.push_str(AUTOZIG_RING_ZIG);
+    }
+    if zig_code.contains("AutoZigMapped") && !zig_code.contains("const AutoZigMapped") {
+        helpers.push_str(AUTOZIG_MAPPED_ZIG);
+    }
+    if zig_code.contains("AutoZigFixed(") && !zig_code.contains("fn AutoZigFixed(") {
+        helpers.push_str(AUTOZIG_FIXED_ZIG);
+    }
+    if zig_code.contains("g_allocator") && !zig_code.contains("const g_allocator") {
+        helpers.push_str(AUTOZIG_ALLOCATOR_ZIG);
+    }
+    if zig_code.contains("az_log(") && !zig_code.contains("fn az_log(") {
+        helpers.push_str(AUTOZIG_LOG_ZIG);
+    }
+
+    helpers
 }
 
 /// Representation of an exported Zig function
@@ -898,7 +3231,6 @@ struct ExportFunction {
 fn extract_export_functions(zig_code: &str) -> Vec<ExportFunction> {
     let mut functions = Vec::new();
 
-    // Scanner removes newlines, so code is all on one line
     // Search for all occurrences of "export fn"
     let mut pos = 0;
     while let Some(start) = zig_code[pos..].find("export fn ") {
@@ -936,7 +3268,7 @@ fn parse_export_function(line: &str, _lines: &[&str], _idx: usize) -> Option<Exp
     let mut paren_count = 1;
     let mut params_end = 0;
 
-    for (i, ch) in after_paren_start.chars().enumerate() {
+    for (i, ch) in after_paren_start.char_indices() {
         match ch {
             '(' => paren_count += 1,
             ')' => {
@@ -950,16 +3282,23 @@ fn parse_export_function(line: &str, _lines: &[&str], _idx: usize) -> Option<Exp
         }
     }
 
-    let params = after_paren_start[..params_end].trim().to_string();
+    // Embedded code keeps its original formatting, so declarations may span
+    // several lines
+    let params = normalize_whitespace(&after_paren_start[..params_end]);
 
     // Extract return type (between ) and {)
     let after_params = &after_paren_start[params_end + 1..];
     let brace_pos = after_params.find('{')?;
-    let return_type = after_params[..brace_pos].trim().to_string();
+    let return_type = normalize_whitespace(&after_params[..brace_pos]);
 
     Some(ExportFunction { name, params, return_type })
 }
 
+/// Collapse runs of whitespace (including newlines) into single spaces
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 /// Check if a Zig type needs ABI wrapper (not a primitive)
 /// All non-primitive types (structs, enums, etc.) need ABI wrappers for
 /// cross-platform compatibility
@@ -1024,20 +3363,24 @@ fn must_use_wrapper(zig_type: &str) -> bool {
     false
 }
 
-/// Generate pointer-based wrapper for a function returning struct
+/// Generate out-pointer wrapper for a function returning struct
 fn generate_ptr_wrapper(func: &ExportFunction) -> String {
-    let wrapper_name = format!("{}__autozig_ptr", func.name);
-    let return_ptr_type = format!("*const {}", func.return_type);
+    let wrapper_name = format!("{}__autozig_out", func.name);
 
-    // Convert struct parameters to pointers
+    // Convert struct parameters to pointers, then append the out pointer
     let (wrapper_params, forwarding_args) = convert_params_to_ptrs(&func.params);
-
-    // Generate static storage for the return value
+    let out_param = format!("__autozig_out : *{}", func.return_type);
+    let wrapper_params = if wrapper_params.is_empty() {
+        out_param
+    } else {
+        format!("{} , {}", wrapper_params, out_param)
+    };
+
+    // The caller owns the result slot, so concurrent calls never share it
     format!(
-        "export fn {}({}) {} {{\n    // ABI-safe wrapper: returns pointer instead of struct by \
-         value\n    const static = struct {{\n        var result: {} = undefined;\n    }};\n    \
-         static.result = {}({});\n    return &static.result;\n}}",
-        wrapper_name, wrapper_params, return_ptr_type, func.return_type, func.name, forwarding_args
+        "export fn {}({}) void {{\n    // ABI-safe wrapper: writes the struct through the \
+         caller's pointer instead of returning it by value\n    __autozig_out.* = {}({});\n}}",
+        wrapper_name, wrapper_params, func.name, forwarding_args
     )
 }
 
@@ -1063,7 +3406,11 @@ fn convert_params_to_ptrs(params: &str) -> (String, String) {
             let param_type = type_part.trim();
 
             // Check if parameter type needs ABI wrapping (is a struct)
-            if needs_abi_wrapper(param_type) {
+            // Pointers ([*]T, *T, ?*T) are passed through; only values are boxed
+            let is_pointer = param_type.starts_with("[*")
+                || param_type.starts_with('*')
+                || param_type.starts_with("?*");
+            if needs_abi_wrapper(param_type) && !is_pointer {
                 // Convert to pointer: "name: Type" -> "name: *const Type"
                 wrapper_params.push(format!("{} : *const {}", name, param_type));
                 // Dereference when forwarding: "name" -> "name.*"
@@ -1079,22 +3426,6 @@ fn convert_params_to_ptrs(params: &str) -> (String, String) {
     (wrapper_params.join(" , "), forwarding_args.join(", "))
 }
 
-/// Extract parameter names from parameter list for forwarding
-fn extract_param_names(params: &str) -> String {
-    if params.trim().is_empty() {
-        return String::new();
-    }
-
-    params
-        .split(',')
-        .filter_map(|param| {
-            let param = param.trim();
-            // Pattern: "name: Type" -> extract "name"
-            param.split(':').next().map(|s| s.trim())
-        })
-        .collect::<Vec<_>>()
-        .join(", ")
-}
 /// Rename functions to _impl variants (for array-returning functions)
 /// Pattern: "export fn function_name(" -> "fn function_name_impl("
 fn rename_functions_to_impl(code: &str, function_names: &[String]) -> String {
@@ -1136,33 +3467,12 @@ fn generate_array_pointer_wrapper(func: &ExportFunction) -> String {
     )
 }
 
-/// Remove export keyword from specified functions in Zig code
-fn remove_export_from_functions(code: &str, function_names: &[String]) -> String {
-    let mut result = code.to_string();
-
-    for fn_name in function_names {
-        // Pattern: "export fn function_name(" -> "fn function_name("
-        // Note: Zig code may have spaces compressed, so match both with/without space
-        let pattern_with_space = format!("export fn {} (", fn_name);
-        let pattern_no_space = format!("export fn {}(", fn_name);
-        let replacement_with_space = format!("fn {} (", fn_name);
-        let replacement_no_space = format!("fn {}(", fn_name);
-
-        if result.contains(&pattern_with_space) {
-            result = result.replace(&pattern_with_space, &replacement_with_space);
-        } else {
-            result = result.replace(&pattern_no_space, &replacement_no_space);
-        }
-    }
-
-    result
-}
-
 
 /// Output from the build process
 #[derive(Debug)]
 pub struct BuildOutput {
-    /// Path to the generated static library
+    /// Path to the generated library: the static archive linked into the
+    /// crate, or the shared library to load with [`ArtifactKind::Cdylib`]
     pub lib_path: Option<PathBuf>,
 }
 
@@ -1178,11 +3488,283 @@ mod tests {
     }
 
     #[test]
-    fn test_target_mapping() {
-        assert_eq!(rust_to_zig_target("x86_64-unknown-linux-gnu"), "x86_64-linux-gnu");
-        assert_eq!(rust_to_zig_target("aarch64-apple-darwin"), "aarch64-macos");
-        assert_eq!(rust_to_zig_target("x86_64-pc-windows-msvc"), "x86_64-windows");
-        assert_eq!(rust_to_zig_target("wasm32-wasi"), "wasm32-wasi");
-        assert_eq!(rust_to_zig_target("unknown-target"), "native");
+    fn test_ptr_wrapper_writes_through_out_pointer() {
+        let code = "export fn make(p: Point, n: i32) Point { return p; }";
+        let wrapper = generate_ptr_wrapper(&extract_export_functions(code)[0]);
+        assert!(wrapper.starts_with(
+            "export fn make__autozig_out(p : *const Point , n : i32 , __autozig_out : *Point) void"
+        ));
+        assert!(wrapper.contains("__autozig_out.* = make(p.*, n);"));
+        assert!(!wrapper.contains("var result"));
+    }
+
+    #[test]
+    fn test_result_helper_injection() {
+        let code = "export fn divide(a: i32, b: i32) AutoZigResult(i32, u8) { _ = a; _ = b; }";
+        assert!(helper_types_for(code).contains("pub fn AutoZigResult"));
+        assert!(helper_types_for("export fn add(a: i32) i32 { return a; }").is_empty());
+        assert!(helper_types_for("export fn f() AutoZigOption(u8) {}").contains("fn AutoZigOption"));
+        assert!(helper_types_for("export fn greet() AutoZigBytes {}").contains("free_fn"));
+        assert!(
+            helper_types_for("export fn f(out: *AutoZigGrowable(u8)) void {}").contains("grow_fn")
+        );
+        assert!(helper_types_for("const Q16 = AutoZigFixed(i32, 16);").contains("fn mul(a: I"));
+        assert!(helper_types_for("export fn f(out: *AutoZigMapBuilder(u8, u32)) void {}")
+            .contains("insert_fn"));
+        assert!(helper_types_for("export fn f(out: AutoZigMatViewMut(u8)) void {}")
+            .contains("pub fn AutoZigMatView(comptime T: type)"));
+        assert!(helper_types_for("export fn f(image: AutoZigImage) void {}")
+            .contains("pub fn setRgba("));
+        assert!(helper_types_for("export fn f(input: *AutoZigRingReader(f32)) void {}")
+            .contains("pub fn writeBlock("));
+        assert!(helper_types_for("const view = AutoZigMapped.init(ptr, len);")
+            .contains("pub fn readInt(self: AutoZigMapped"));
+        let allocator = helper_types_for("const buf = try g_allocator.alloc(u8, 4);");
+        assert!(allocator.contains("pub const g_allocator"));
+        assert!(allocator.contains("export fn autozig_install_allocator("));
+        assert!(helper_types_for("az_log(3, msg.ptr, msg.len);").contains("extern fn az_log("));
+
+        let funcs = extract_export_functions(code);
+        assert_eq!(funcs[0].return_type, "AutoZigResult(i32, u8)");
+        assert!(needs_abi_wrapper(&funcs[0].return_type));
+    }
+
+    #[test]
+    fn test_runtime_shims() {
+        let code = "export fn roll() u32 { return autozig_runtime.random().int(u32); }";
+        let engine = AutoZigEngine::new("src", "target");
+        assert!(engine
+            .helper_code_for(code)
+            .contains("std\").crypto.random"));
+
+        let engine = engine.with_options(BuildOptions { test_shims: true, ..Default::default() });
+        let shims = engine.helper_code_for(code);
+        assert!(shims.contains("export fn autozig_init_rng(seed: u64)"));
+        assert!(engine.helper_code_for("export fn f() void {}").is_empty());
+    }
+
+    #[test]
+    fn test_block_settings() {
+        let dir = env::temp_dir().join(format!("autozig-block-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let block = |optimize: &str| {
+            format!(
+                "autozig! {{\n    #![autozig(mode = \"modular\", optimize = \"{optimize}\")]\n    \
+                 export fn f() void {{}}\n}}\n"
+            )
+        };
+        fs::write(dir.join("lib.rs"), block("ReleaseSmall")).unwrap();
+
+        let engine = AutoZigEngine::with_mode(&dir, "target", CompilationMode::Merged)
+            .with_block_settings()
+            .unwrap()
+            .unwrap();
+        assert_eq!(engine.mode, CompilationMode::Merged);
+        assert_eq!(engine.options.codegen.optimize, Some(ZigOptimize::ReleaseSmall));
+        assert!(engine.with_block_settings().unwrap().is_none());
+
+        // `new` leaves the mode to the headers, even with `AUTOZIG_MODE` set
+        let engine = AutoZigEngine::new(&dir, "target");
+        assert!(!engine.mode_explicit);
+        let engine = engine.with_block_settings().unwrap().unwrap();
+        assert_eq!(engine.mode, CompilationMode::ModularBuildZig);
+
+        fs::write(dir.join("other.rs"), block("Debug")).unwrap();
+        let err = engine.with_block_settings().unwrap_err();
+        assert!(err.to_string().contains("different optimize settings"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_float_settings() {
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            module_float_modes: vec![("physics.zig".to_string(), FloatMode::Optimized)],
+            ..Default::default()
+        });
+        let code = "export fn f() f32 { return 0; }".to_string();
+        assert_eq!(
+            engine
+                .apply_float_settings("generated_main.zig", code.clone())
+                .unwrap(),
+            code
+        );
+        assert!(engine
+            .apply_float_settings("physics.zig", code)
+            .unwrap()
+            .contains("@setFloatMode(.optimized)"));
+
+        let engine = engine.with_options(BuildOptions {
+            module_float_modes: vec![("physics.zig".to_string(), FloatMode::Optimized)],
+            strict_float_determinism: true,
+            ..Default::default()
+        });
+        assert!(engine.check_float_settings().is_err());
+        assert!(engine
+            .helper_code_for("fn f() void { _ = autozig_fenv.enterFlushToZero(); }")
+            .contains("@compileError"));
+    }
+
+    #[test]
+    fn test_build_zig_codegen_flags() {
+        let wasm = ZigTarget::from_rust("wasm32-unknown-unknown", None).unwrap();
+        let engine = AutoZigEngine::new("src", "target");
+        let build = engine
+            .generate_build_zig_with_c(&wasm, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains("const optimize = std.builtin.OptimizeMode.ReleaseFast;"));
+        assert!(!build.contains(".strip"));
+
+        let engine = engine.with_options(BuildOptions {
+            codegen: CodegenFlags {
+                optimize: Some(ZigOptimize::ReleaseSmall),
+                strip: true,
+                single_threaded: true,
+                wasi_exec_model: WasiExecModel::Reactor,
+                wasm_threads: true,
+                sanitizer: None,
+                stack_traces: false,
+                reproducible: false,
+                freestanding: false,
+            },
+            ..Default::default()
+        });
+        let build = engine
+            .generate_build_zig_with_c(&wasm, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains("const optimize = std.builtin.OptimizeMode.ReleaseSmall;"));
+        assert!(build.contains("        .strip = true,\n        .single_threaded = true,\n"));
+        assert!(!build.contains("wasi_exec_model"));
+        assert!(build.contains(".cpu_features = \"mvp+simd128+atomics+bulk_memory\""));
+
+        let wasi = ZigTarget::from_rust("wasm32-wasip1", None).unwrap();
+        let build = engine
+            .generate_build_zig_with_c(&wasi, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains(".arch_os_abi = \"wasm32-wasi\""));
+        assert!(build.contains("    lib.linkLibC();\n    lib.wasi_exec_model = .reactor;\n"));
+
+        let linux = ZigTarget::from_rust("x86_64-unknown-linux-gnu", None).unwrap();
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            codegen: CodegenFlags {
+                sanitizer: Some(Sanitizer::Address),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &[PathBuf::from("out/wrap.c")])
+            .unwrap();
+        assert!(build.contains("const optimize = std.builtin.OptimizeMode.ReleaseSafe;"));
+        assert!(
+            build.contains("        .omit_frame_pointer = false,\n        .sanitize_c = .full,\n")
+        );
+        assert!(build.contains(".flags = &.{\"-fsanitize=address\"} });"));
+
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            codegen: CodegenFlags { stack_traces: true, ..Default::default() },
+            ..Default::default()
+        });
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains("const optimize = std.builtin.OptimizeMode.ReleaseSafe;"));
+        assert!(build
+            .contains("        .omit_frame_pointer = false,\n        .error_tracing = true,\n"));
+        let helpers = engine.helper_code_for("export fn f() void {}");
+        assert!(helpers.contains("pub const panic = @import(\"std\").debug.FullPanic("));
+        assert!(!engine
+            .helper_code_for("pub const panic = std.debug.simple_panic;")
+            .contains("FullPanic"));
+
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            codegen: CodegenFlags { reproducible: true, ..Default::default() },
+            ..Default::default()
+        });
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &[PathBuf::from("target/wrap.c")])
+            .unwrap();
+        assert!(build.contains("        .strip = true,\n"));
+        assert!(build.contains(
+            ".flags = &.{\"-fno-sanitize=undefined\", \"-ffile-prefix-map=target=.\"} });"
+        ));
+
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            codegen: CodegenFlags { freestanding: true, ..Default::default() },
+            ..Default::default()
+        });
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &[PathBuf::from("out/dsp.cpp")])
+            .unwrap();
+        assert!(build.contains("    lib.root_module.stack_protector = false;\n"));
+        assert!(!build.contains("pic = true") && !build.contains("linkLibC"));
+        assert!(!build.contains("linkLibCpp"));
+        let helpers = engine.helper_code_for("export fn f() AutoZigBytes {}");
+        assert!(helpers.contains("export fn autozig_install_allocator("));
+        assert!(!AutoZigEngine::new("src", "target")
+            .helper_code_for("export fn f() AutoZigBytes {}")
+            .contains("autozig_install_allocator"));
+
+        let cortex_m = ZigTarget::from_rust("thumbv7em-none-eabihf", None).unwrap();
+        let engine = AutoZigEngine::new("src", "target").with_target("thumbv7em-none-eabihf");
+        let build = engine
+            .generate_build_zig_with_c(&cortex_m, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains(".arch_os_abi = \"thumb-freestanding-eabihf\""));
+        assert!(build.contains(".cpu_features = \"cortex_m4\""));
+        assert!(build.contains("const optimize = std.builtin.OptimizeMode.ReleaseFast;"));
+        assert!(!build.contains("pic = true") && !build.contains("linkLibC"));
+        assert!(engine
+            .helper_code_for("export fn f() AutoZigBytes {}")
+            .contains("export fn autozig_install_allocator("));
+    }
+
+    #[test]
+    fn test_build_zig_cpp_sources() {
+        let linux = ZigTarget::from_rust("x86_64-unknown-linux-gnu", None).unwrap();
+        let engine = AutoZigEngine::new("src", "target").with_options(BuildOptions {
+            source_flags: vec![("dsp.cpp".to_string(), vec!["-std=c++20".to_string()])],
+            ..Default::default()
+        });
+        let sources = [PathBuf::from("out/wrap.c"), PathBuf::from("out/dsp.cpp")];
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &sources)
+            .unwrap();
+        assert!(build.contains(
+            "lib.addCSourceFile(.{ .file = b.path(\"wrap.c\"), .flags = \
+             &.{\"-fno-sanitize=undefined\"} });"
+        ));
+        assert!(build.contains(
+            "lib.addCSourceFile(.{ .file = b.path(\"dsp.cpp\"), .flags = \
+             &.{\"-fno-sanitize=undefined\", \"-std=c++20\"} });"
+        ));
+        assert!(build.contains("    lib.linkLibCpp();\n"));
+
+        let engine = AutoZigEngine::new("src", "target")
+            .with_manifest_dir("/crate")
+            .with_options(BuildOptions {
+                system_libs: vec!["z".to_string()],
+                include_dirs: vec![PathBuf::from("vendor/include")],
+                artifact: ArtifactKind::Cdylib,
+                ..Default::default()
+            });
+        let build = engine
+            .generate_build_zig_with_c(&linux, &[], &[], &[])
+            .unwrap();
+        assert!(build.contains("        .linkage = .dynamic,\n"));
+        assert!(build.contains("    lib.linkLibC();\n    lib.linkSystemLibrary(\"z\");\n"));
+        assert!(build
+            .contains("    lib.addIncludePath(.{ .cwd_relative = \"/crate/vendor/include\" });\n"));
+        assert_eq!(
+            engine.source_flags(Path::new("other.cc")),
+            ["-std=c++17", "-fno-sanitize=undefined"]
+        );
+    }
+
+    #[test]
+    fn test_static_lib_name() {
+        assert_eq!(static_lib_name(Path::new("vendor/libfoo.a")).as_deref(), Some("foo"));
+        assert_eq!(static_lib_name(Path::new("zstd.lib")).as_deref(), Some("zstd"));
+        assert_eq!(static_lib_name(Path::new("libfoo.so")), None);
     }
 }
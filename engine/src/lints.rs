@@ -0,0 +1,479 @@
+//! Build-time checks for common misuse of generated AutoZig APIs
+//!
+//! The borrow checker cannot see what Zig does with the memory it is handed,
+//! so a few patterns compile fine but are unsound or fragile. The build script
+//! scans the crate's sources and reports them as cargo warnings:
+//!
+//! - `zero_copy_across_await`: a slice borrowed from a `ZeroCopyBuffer` is
+//!   still used after an `.await`
+//! - `temporary_into_retaining_fn`: a reference to a temporary is passed to a
+//!   function declared `#[autozig(retains)]`, so Zig keeps a dangling pointer
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use syn::{
+    spanned::Spanned,
+    visit::Visit,
+};
+use walkdir::WalkDir;
+
+/// Methods whose result is an owned value, i.e. a temporary when borrowed
+const OWNED_METHODS: &[&str] = &[
+    "to_string",
+    "to_owned",
+    "to_vec",
+    "clone",
+    "collect",
+    "concat",
+    "join",
+    "repeat",
+    "into",
+];
+
+/// Methods borrowing from their receiver
+const BORROWING_METHODS: &[&str] = &["as_ptr", "as_str", "as_slice", "as_bytes", "as_ref"];
+
+/// A misuse found in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// Lint name, e.g. `zero_copy_across_await`
+    pub lint: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: autozig::{}: {}",
+            self.file, self.line, self.column, self.lint, self.message
+        )
+    }
+}
+
+/// Check every Rust file under `src_dir`
+pub fn check_dir(src_dir: &Path) -> Result<Vec<LintWarning>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(src_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "rs") {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            files.push((path.display().to_string(), content));
+        }
+    }
+
+    // Retaining functions may be declared in a different file than their callers
+    let mut retaining = HashSet::new();
+    for (_, content) in &files {
+        retaining.extend(retaining_functions(content));
+    }
+
+    let mut warnings = Vec::new();
+    for (path, content) in &files {
+        warnings.extend(check_source(path, content, &retaining));
+    }
+    Ok(warnings)
+}
+
+/// Names of functions declared `#[autozig(retains)]` in `source`
+pub fn retaining_functions(source: &str) -> Vec<String> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+
+    #[derive(Default)]
+    struct Collector(Vec<String>);
+
+    impl<'ast> Visit<'ast> for Collector {
+        fn visit_macro(&mut self, node: &'ast syn::Macro) {
+            // Parsing IDL is comparatively expensive; most blocks don't use it
+            if !node.tokens.to_string().contains("retains") {
+                return;
+            }
+            let signatures = if node.path.is_ident("autozig") {
+                syn::parse2::<AutoZigConfig>(node.tokens.clone())
+                    .map(|config| config.rust_signatures)
+                    .unwrap_or_default()
+            } else if node.path.is_ident("include_zig") || node.path.is_ident("bind_zig") {
+                syn::parse2::<IncludeZigConfig>(node.tokens.clone())
+                    .map(|config| config.rust_signatures)
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            self.0.extend(
+                signatures
+                    .iter()
+                    .filter(|sig| sig.binding_config.retains)
+                    .map(|sig| sig.sig.ident.to_string()),
+            );
+        }
+    }
+
+    let mut collector = Collector::default();
+    collector.visit_file(&file);
+    collector.0
+}
+
+/// Check one source file
+pub fn check_source(file: &str, source: &str, retaining: &HashSet<String>) -> Vec<LintWarning> {
+    let Ok(parsed) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+
+    let mut checker = Checker { file, retaining, warnings: Vec::new() };
+    checker.visit_file(&parsed);
+    checker.warnings
+}
+
+struct Checker<'a> {
+    file: &'a str,
+    retaining: &'a HashSet<String>,
+    warnings: Vec<LintWarning>,
+}
+
+impl Checker<'_> {
+    fn warn(&mut self, span: proc_macro2::Span, lint: &'static str, message: String) {
+        let start = span.start();
+        self.warnings.push(LintWarning {
+            file: self.file.to_string(),
+            line: start.line,
+            column: start.column + 1,
+            lint,
+            message,
+        });
+    }
+
+    /// Walk the statements of an async body in order, tracking slices
+    /// borrowed from zero-copy buffers and whether an `.await` separated the
+    /// borrow from a later use
+    fn check_async_body(
+        &mut self,
+        inputs: Option<&syn::punctuated::Punctuated<syn::FnArg, syn::Token![,]>>,
+        block: &syn::Block,
+    ) {
+        let mut buffers: HashSet<String> = HashSet::new();
+        if let Some(inputs) = inputs {
+            for input in inputs {
+                if let syn::FnArg::Typed(pat_type) = input {
+                    if let (syn::Pat::Ident(ident), true) =
+                        (&*pat_type.pat, mentions_zero_copy(&pat_type.ty))
+                    {
+                        buffers.insert(ident.ident.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut slices: Vec<BorrowedSlice> = Vec::new();
+        for stmt in &block.stmts {
+            for slice in slices.iter_mut().filter(|s| s.crossed_await && !s.reported) {
+                if let Some(span) = find_use(stmt, &slice.ident) {
+                    self.warn(
+                        span,
+                        "zero_copy_across_await",
+                        format!(
+                            "slice `{}` borrowed from ZeroCopyBuffer `{}` is held across an \
+                             `.await`; re-borrow it after awaiting",
+                            slice.ident, slice.buffer
+                        ),
+                    );
+                    slice.reported = true;
+                }
+            }
+
+            if let syn::Stmt::Local(local) = stmt {
+                let name = local_name(&local.pat);
+                if let (Some(name), Some(init)) = (name, &local.init) {
+                    if let syn::Pat::Type(pat_type) = &local.pat {
+                        if mentions_zero_copy(&pat_type.ty) {
+                            buffers.insert(name.to_string());
+                        }
+                    }
+                    if expr_mentions_zero_copy(&init.expr) {
+                        buffers.insert(name.to_string());
+                    } else if let Some(buffer) = slice_of_buffer(&init.expr, &buffers) {
+                        slices.push(BorrowedSlice {
+                            ident: name.clone(),
+                            buffer,
+                            crossed_await: false,
+                            reported: false,
+                        });
+                    }
+                }
+            }
+
+            if contains_await(stmt) {
+                for slice in &mut slices {
+                    slice.crossed_await = true;
+                }
+            }
+        }
+    }
+}
+
+/// A local slice borrowed from a zero-copy buffer
+struct BorrowedSlice {
+    ident: syn::Ident,
+    buffer: String,
+    crossed_await: bool,
+    reported: bool,
+}
+
+impl<'ast> Visit<'ast> for Checker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        if node.sig.asyncness.is_some() {
+            self.check_async_body(Some(&node.sig.inputs), &node.block);
+        }
+        syn::visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        if node.sig.asyncness.is_some() {
+            self.check_async_body(Some(&node.sig.inputs), &node.block);
+        }
+        syn::visit::visit_impl_item_fn(self, node);
+    }
+
+    fn visit_expr_async(&mut self, node: &'ast syn::ExprAsync) {
+        self.check_async_body(None, &node.block);
+        syn::visit::visit_expr_async(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(func) = &*node.func {
+            let name = func
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string());
+            if let Some(name) = name.filter(|name| self.retaining.contains(name)) {
+                for arg in &node.args {
+                    if borrows_temporary(arg) {
+                        self.warn(
+                            arg.span(),
+                            "temporary_into_retaining_fn",
+                            format!(
+                                "`{name}` retains its arguments, but this borrows a temporary \
+                                 that is dropped at the end of the statement"
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+}
+
+fn local_name(pat: &syn::Pat) -> Option<&syn::Ident> {
+    match pat {
+        syn::Pat::Ident(ident) => Some(&ident.ident),
+        syn::Pat::Type(pat_type) => local_name(&pat_type.pat),
+        _ => None,
+    }
+}
+
+fn mentions_zero_copy(ty: &syn::Type) -> bool {
+    quote::quote!(#ty).to_string().contains("ZeroCopyBuffer")
+}
+
+fn expr_mentions_zero_copy(expr: &syn::Expr) -> bool {
+    // `ZeroCopyBuffer::from_zig_vec(..)` and friends
+    let mut expr = expr;
+    while let syn::Expr::MethodCall(call) = expr {
+        expr = &call.receiver;
+    }
+    matches!(expr, syn::Expr::Call(call)
+        if quote::quote!(#call).to_string().starts_with("ZeroCopyBuffer"))
+}
+
+/// Buffer name if `expr` borrows a slice from one of `buffers`
+fn slice_of_buffer(expr: &syn::Expr, buffers: &HashSet<String>) -> Option<String> {
+    let receiver = match expr {
+        syn::Expr::MethodCall(call) if call.method == "as_slice" || call.method == "as_ref" => {
+            &*call.receiver
+        },
+        syn::Expr::Reference(reference) => match &*reference.expr {
+            syn::Expr::Index(index) => &*index.expr,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match receiver {
+        syn::Expr::Path(path) => path
+            .path
+            .get_ident()
+            .map(|ident| ident.to_string())
+            .filter(|name| buffers.contains(name)),
+        _ => None,
+    }
+}
+
+/// Whether `expr` is a borrow of a value that only lives until the end of the
+/// enclosing statement
+fn borrows_temporary(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Reference(reference) => is_temporary(&reference.expr),
+        syn::Expr::MethodCall(call)
+            if BORROWING_METHODS.contains(&call.method.to_string().as_str()) =>
+        {
+            is_temporary(&call.receiver)
+        },
+        syn::Expr::Paren(paren) => borrows_temporary(&paren.expr),
+        _ => false,
+    }
+}
+
+fn is_temporary(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Call(_) | syn::Expr::Macro(_) => true,
+        syn::Expr::MethodCall(call) => OWNED_METHODS.contains(&call.method.to_string().as_str()),
+        syn::Expr::Paren(paren) => is_temporary(&paren.expr),
+        _ => false,
+    }
+}
+
+fn contains_await(stmt: &syn::Stmt) -> bool {
+    #[derive(Default)]
+    struct AwaitFinder(bool);
+
+    impl<'ast> Visit<'ast> for AwaitFinder {
+        fn visit_expr_await(&mut self, _: &'ast syn::ExprAwait) {
+            self.0 = true;
+        }
+
+        // Awaits inside nested async blocks or closures don't suspend this body
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+    }
+
+    let mut finder = AwaitFinder::default();
+    finder.visit_stmt(stmt);
+    finder.0
+}
+
+/// Span of the first use of `ident` in `stmt`
+fn find_use(stmt: &syn::Stmt, ident: &syn::Ident) -> Option<proc_macro2::Span> {
+    struct UseFinder<'a> {
+        ident: &'a syn::Ident,
+        found: Option<proc_macro2::Span>,
+    }
+
+    impl<'ast> Visit<'ast> for UseFinder<'_> {
+        fn visit_expr_path(&mut self, node: &'ast syn::ExprPath) {
+            if self.found.is_none() && node.path.is_ident(self.ident) {
+                self.found = Some(node.span());
+            }
+        }
+
+        // Macro arguments (`println!("{}", data[0])`) are plain tokens
+        fn visit_macro(&mut self, node: &'ast syn::Macro) {
+            if self.found.is_none() {
+                self.found = find_ident(node.tokens.clone(), self.ident);
+            }
+        }
+    }
+
+    fn find_ident(
+        tokens: proc_macro2::TokenStream,
+        ident: &syn::Ident,
+    ) -> Option<proc_macro2::Span> {
+        tokens.into_iter().find_map(|tree| match tree {
+            proc_macro2::TokenTree::Ident(candidate) if candidate == *ident => {
+                Some(candidate.span())
+            },
+            proc_macro2::TokenTree::Group(group) => find_ident(group.stream(), ident),
+            _ => None,
+        })
+    }
+
+    let mut finder = UseFinder { ident, found: None };
+    finder.visit_stmt(stmt);
+    finder.found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_copy_across_await() {
+        let source = r#"
+            async fn process(buffer: ZeroCopyBuffer<u8>) {
+                let data = buffer.as_slice();
+                let n = data.len();
+                tick().await;
+                println!("{}", data[0]);
+            }
+        "#;
+        let warnings = check_source("src/lib.rs", source, &HashSet::new());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, "zero_copy_across_await");
+        assert_eq!(warnings[0].line, 6);
+    }
+
+    #[test]
+    fn test_slice_reborrowed_after_await_is_fine() {
+        let source = r#"
+            async fn process() {
+                let buffer = ZeroCopyBuffer::from_zig_vec(make());
+                tick().await;
+                let data = buffer.as_slice();
+                println!("{}", data[0]);
+            }
+        "#;
+        assert!(check_source("src/lib.rs", source, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_temporary_into_retaining_fn() {
+        let idl = r#"
+            autozig! {
+                export fn set_name(ptr: [*]const u8, len: usize) void {}
+                ---
+                #[autozig(retains)]
+                fn set_name(name: &str);
+            }
+        "#;
+        let retaining: HashSet<String> = retaining_functions(idl).into_iter().collect();
+        assert!(retaining.contains("set_name"));
+
+        let source = r#"
+            fn setup(name: &str) {
+                set_name(&format!("{}-worker", name));
+                set_name(name);
+                set_name(name.to_string().as_str());
+            }
+        "#;
+        let warnings = check_source("src/main.rs", source, &retaining);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings
+            .iter()
+            .all(|w| w.lint == "temporary_into_retaining_fn"));
+        assert_eq!(warnings[0].line, 3);
+        assert_eq!(warnings[1].line, 5);
+    }
+}
@@ -0,0 +1,211 @@
+//! License and provenance manifest for linked foreign code
+//!
+//! Every Zig/C source (and prebuilt archive) that contributes code to the
+//! build is listed with its SHA-256 and the license it declares through an
+//! `SPDX-License-Identifier` header, so compliance tooling can trace exactly
+//! which foreign code ended up in the binary.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+use crate::scanner::{
+    ForeignSource,
+    SourceKind,
+};
+
+/// Only the leading comment block is searched for license headers
+const HEADER_LINES: usize = 30;
+
+/// One linked source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest directory when possible
+    pub path: String,
+    pub kind: SourceKind,
+    /// Hex-encoded SHA-256 of the file contents
+    pub sha256: String,
+    /// SPDX license expression declared in the file header
+    pub license: Option<String>,
+    /// Copyright line declared in the file header
+    pub copyright: Option<String>,
+}
+
+/// Provenance of all foreign code linked into a crate
+#[derive(Debug, Clone, Default)]
+pub struct SourceManifest {
+    /// Crate the sources were compiled for
+    pub package: String,
+    pub version: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl SourceManifest {
+    /// Hash and inspect `sources`, recording paths relative to `root`
+    pub fn collect(
+        package: &str,
+        version: &str,
+        sources: &[ForeignSource],
+        root: &Path,
+    ) -> Result<Self> {
+        let mut entries = Vec::with_capacity(sources.len());
+        for source in sources {
+            let data = fs::read(&source.path)
+                .with_context(|| format!("Failed to read {}", source.path.display()))?;
+
+            let (license, copyright) = if source.kind == SourceKind::Archive {
+                (None, None)
+            } else {
+                let text = String::from_utf8_lossy(&data);
+                (detect_license(&text), detect_copyright(&text))
+            };
+
+            let path = source.path.strip_prefix(root).unwrap_or(&source.path);
+            entries.push(ManifestEntry {
+                path: path.to_string_lossy().replace('\\', "/"),
+                kind: source.kind,
+                sha256: format!("{:x}", Sha256::digest(&data)),
+                license,
+                copyright,
+            });
+        }
+
+        Ok(Self {
+            package: package.to_string(),
+            version: version.to_string(),
+            entries,
+        })
+    }
+
+    /// Render as SPDX-flavoured JSON
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        let _ = writeln!(out, "  \"package\": {},", json_string(&self.package));
+        let _ = writeln!(out, "  \"version\": {},", json_string(&self.version));
+        out.push_str("  \"files\": [");
+        for (i, entry) in self.entries.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            let _ = write!(
+                out,
+                "    {{ \"fileName\": {}, \"kind\": {}, \"sha256\": {}, \"licenseDeclared\": {}, \
+                 \"copyrightText\": {} }}",
+                json_string(&entry.path),
+                json_string(entry.kind.as_str()),
+                json_string(&entry.sha256),
+                json_optional(entry.license.as_deref()),
+                json_optional(entry.copyright.as_deref()),
+            );
+        }
+        if !self.entries.is_empty() {
+            out.push_str("\n  ");
+        }
+        out.push_str("]\n}\n");
+        out
+    }
+}
+
+/// SPDX license expression from a `SPDX-License-Identifier:` header
+pub fn detect_license(content: &str) -> Option<String> {
+    header_value(content, "SPDX-License-Identifier:")
+}
+
+/// First `Copyright` line of the file header
+pub fn detect_copyright(content: &str) -> Option<String> {
+    content
+        .lines()
+        .take(HEADER_LINES)
+        .map(strip_comment)
+        .find(|line| line.starts_with("Copyright") || line.starts_with("SPDX-FileCopyrightText:"))
+        .map(|line| {
+            line.strip_prefix("SPDX-FileCopyrightText:")
+                .unwrap_or(line)
+                .trim()
+                .to_string()
+        })
+}
+
+fn header_value(content: &str, key: &str) -> Option<String> {
+    content.lines().take(HEADER_LINES).find_map(|line| {
+        let line = strip_comment(line);
+        let value = line[line.find(key)? + key.len()..].trim();
+        (!value.is_empty()).then(|| value.trim_end_matches("*/").trim().to_string())
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.trim().trim_start_matches(['/', '*', '!', '#']).trim()
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_optional(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), json_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_license_headers() {
+        let zig = "// SPDX-License-Identifier: MIT OR Apache-2.0\n// Copyright (c) 2024 Foo \
+                   Ltd\nconst std = @import(\"std\");";
+        assert_eq!(detect_license(zig).as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(detect_copyright(zig).as_deref(), Some("Copyright (c) 2024 Foo Ltd"));
+
+        let c = "/* SPDX-License-Identifier: BSD-3-Clause */\nint x;";
+        assert_eq!(detect_license(c).as_deref(), Some("BSD-3-Clause"));
+        assert_eq!(detect_license("export fn f() void {}"), None);
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = SourceManifest {
+            package: "demo".to_string(),
+            version: "0.1.0".to_string(),
+            entries: vec![ManifestEntry {
+                path: "src/\"odd\".zig".to_string(),
+                kind: SourceKind::Zig,
+                sha256: "ab".to_string(),
+                license: Some("MIT".to_string()),
+                copyright: None,
+            }],
+        };
+        let json = manifest.to_json();
+        assert!(json.contains("\"fileName\": \"src/\\\"odd\\\".zig\""));
+        assert!(json.contains("\"licenseDeclared\": \"MIT\""));
+        assert!(json.contains("\"copyrightText\": null"));
+
+        let empty = SourceManifest::default().to_json();
+        assert!(empty.contains("\"files\": []"));
+    }
+}
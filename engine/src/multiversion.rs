@@ -0,0 +1,83 @@
+//! Zig sources of the CPU variants of `#[autozig(multiversion)]` functions
+//!
+//! Each variant is compiled into its own library with a newer `-mcpu` (see
+//! [`autozig_parser::multiversion`]). Its source is the crate's Zig code
+//! with every export removed except the multiversioned functions, which are
+//! renamed to `<name>__<suffix>`; the variant libraries then define no
+//! symbol twice. A `const <name> = <name>__<suffix>;` alias keeps calls from
+//! other Zig functions working.
+
+use autozig_parser::multiversion::{
+    self,
+    CpuVariant,
+};
+
+use crate::{
+    cfg_gate,
+    visibility,
+};
+
+/// Source of the `variant` library of `functions`, from the crate's Zig code
+pub fn variant_source(zig_code: &str, functions: &[String], variant: &CpuVariant) -> String {
+    let others: Vec<String> = visibility::exported_function_names(zig_code)
+        .into_iter()
+        .filter(|name| !functions.contains(name))
+        .collect();
+    let mut code = cfg_gate::unexport(zig_code, &others);
+
+    let mut aliases = String::new();
+    for name in functions {
+        let symbol = multiversion::symbol(name, variant);
+        let renamed = rename_export(&code, name, &symbol);
+        if renamed != code {
+            code = renamed;
+            aliases.push_str(&format!("const {name} = {symbol};\n"));
+        }
+    }
+    format!(
+        "{code}\n\n// {} variant of the multiversioned functions\n{aliases}",
+        variant.suffix
+    )
+}
+
+/// Rename the function declared as `export fn <name>` to `to`
+fn rename_export(zig_code: &str, name: &str, to: &str) -> String {
+    let marker = format!("export fn {name}(");
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find(&marker) {
+        let boundary = !rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if boundary {
+            let start = zig_code.len() - rest.len() + pos;
+            return format!(
+                "{}export fn {to}({}",
+                &zig_code[..start],
+                &zig_code[start + marker.len()..]
+            );
+        }
+        rest = &rest[pos + marker.len()..];
+    }
+    zig_code.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_source() {
+        let code = "export fn dot(a: f32) f32 { return a; }\nexport fn sum(a: f32) f32 { return \
+                    dot(a); }\nfn helper() void {}\n";
+        let avx2 = multiversion::variants_for("x86_64")
+            .find(|variant| variant.suffix == "avx2")
+            .unwrap();
+        assert_eq!(
+            variant_source(code, &["dot".to_string()], avx2),
+            "export fn dot__avx2(a: f32) f32 { return a; }\nfn sum(a: f32) f32 { return dot(a); \
+             }\nfn helper() void {}\n\n\n// avx2 variant of the multiversioned functions\nconst \
+             dot = dot__avx2;\n"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Prefixed exports of namespaced `autozig!` blocks
+//!
+//! `autozig!(mod = img, { ... })` generates its bindings in `pub mod img`, so
+//! two blocks can both bind a `resize`. Their Zig exports would still clash
+//! at link time (and in the merged Zig file), so every export of the block is
+//! renamed to `img__resize`, along with the references to it in the block.
+//! The macro links the bindings against the prefixed symbols.
+
+use autozig_parser::namespaced_symbol;
+
+/// Rename every `export fn`/`export const`/`export var` of `zig_code` to
+/// `<namespace>__<name>`, and the uses of these names in `zig_code`
+///
+/// Field accesses (`.name`), strings and comments are left alone.
+pub fn prefix_exports(zig_code: &str, namespace: &str) -> String {
+    let exports = exported_names(zig_code);
+    if exports.is_empty() {
+        return zig_code.to_string();
+    }
+
+    let mut result = String::with_capacity(zig_code.len());
+    let mut chars = zig_code.char_indices().peekable();
+    let mut previous = ' ';
+    while let Some((start, c)) = chars.next() {
+        if c == '"' || c == '\'' {
+            // String or character literal
+            result.push(c);
+            let mut escaped = false;
+            for (_, next) in chars.by_ref() {
+                result.push(next);
+                if next == '\n' || (next == c && !escaped) {
+                    break;
+                }
+                escaped = next == '\\' && !escaped;
+            }
+        } else if (c == '/' || c == '\\') && chars.peek().is_some_and(|(_, next)| *next == c) {
+            // Comment or multiline string line
+            let end = zig_code[start..]
+                .find('\n')
+                .map_or(zig_code.len(), |len| start + len);
+            result.push_str(&zig_code[start..end]);
+            while chars.peek().is_some_and(|(index, _)| *index < end) {
+                chars.next();
+            }
+        } else if is_ident_start(c) && !is_ident_char(previous) {
+            let end = zig_code[start..]
+                .find(|c: char| !is_ident_char(c))
+                .map_or(zig_code.len(), |len| start + len);
+            let ident = &zig_code[start..end];
+            if previous != '.' && exports.iter().any(|name| name == ident) {
+                result.push_str(&namespaced_symbol(namespace, ident));
+            } else {
+                result.push_str(ident);
+            }
+            while chars.peek().is_some_and(|(index, _)| *index < end) {
+                chars.next();
+            }
+        } else {
+            result.push(c);
+        }
+        previous = result.chars().next_back().unwrap_or(' ');
+    }
+    result
+}
+
+/// Names declared with `export fn`, `export const` or `export var`
+fn exported_names(zig_code: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find("export ") {
+        let boundary = !rest[..pos].chars().next_back().is_some_and(is_ident_char);
+        let after = &rest[pos + "export ".len()..];
+        rest = after;
+        if !boundary {
+            continue;
+        }
+        let Some(decl) = ["fn ", "const ", "var "]
+            .iter()
+            .find_map(|keyword| after.trim_start().strip_prefix(keyword))
+        else {
+            continue;
+        };
+        let decl = decl.trim_start();
+        let end = decl.find(|c: char| !is_ident_char(c)).unwrap_or(decl.len());
+        if end > 0 && !names.iter().any(|name| name == &decl[..end]) {
+            names.push(decl[..end].to_string());
+        }
+    }
+    names
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_exports() {
+        let code = "const std = @import(\"std\");\nexport const LUT: [2]u8 = .{ 1, 2 };\nfn \
+                    scale(w: u32) u32 { return w * LUT[1]; }\nexport fn resize(w: u32) u32 {\n    \
+                    // resize keeps .resize fields\n    std.debug.print(\"resize\", .{});\n    \
+                    return scale(w) + cfg.resize + resized(w);\n}\nexport fn resized(w: u32) u32 \
+                    { return w; }\n";
+        assert_eq!(
+            prefix_exports(code, "img"),
+            "const std = @import(\"std\");\nexport const img__LUT: [2]u8 = .{ 1, 2 };\nfn \
+             scale(w: u32) u32 { return w * img__LUT[1]; }\nexport fn img__resize(w: u32) u32 \
+             {\n    // resize keeps .resize fields\n    std.debug.print(\"resize\", .{});\n    \
+             return scale(w) + cfg.resize + img__resized(w);\n}\nexport fn img__resized(w: u32) \
+             u32 { return w; }\n"
+        );
+        assert_eq!(prefix_exports("fn helper() void {}", "img"), "fn helper() void {}");
+    }
+}
@@ -14,17 +14,52 @@ use anyhow::{
     Context,
     Result,
 };
+use autozig_parser::{
+    header::BlockHeader,
+    AutoZigConfig,
+    IncludeZigConfig,
+    RustFunctionSignature,
+};
+use proc_macro2::{
+    Delimiter,
+    Group,
+    Spacing,
+    Span,
+    TokenStream,
+    TokenTree,
+};
 use syn::{
     visit::Visit,
     Macro,
+    MacroDelimiter,
 };
 use walkdir::WalkDir;
 
+use crate::{
+    bridge_structs,
+    cfg_gate,
+    const_generics,
+    diagnostics,
+    gpu::GpuKernel,
+    namespace,
+    type_generics,
+    zig_enums,
+};
+
+/// File in `OUT_DIR` recording the compilation mode of the last build
+pub const MODE_FILE: &str = "autozig_mode";
+
 /// Compilation mode for Zig code
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// The build script chooses it (`autozig_build::Builder::mode`, else the
+/// `#![autozig(mode = ...)]` header of a block) and the engine records it in
+/// [`MODE_FILE`], where [`ZigCodeScanner::new`] reads it back. The
+/// `AUTOZIG_MODE` environment variable is a deprecated fallback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum CompilationMode {
     /// Legacy mode: merge all Zig code into one file (default for backward
     /// compatibility)
+    #[default]
     Merged,
     /// Modular mode with main module + @import (Solution 1)
     ModularImport,
@@ -32,20 +67,47 @@ pub enum CompilationMode {
     ModularBuildZig,
 }
 
-impl Default for CompilationMode {
-    fn default() -> Self {
-        // Read from AUTOZIG_MODE environment variable
-        // Valid values: "merged", "modular_import", "modular_buildzig"
-        match std::env::var("AUTOZIG_MODE").as_deref() {
-            Ok("merged") => CompilationMode::Merged,
-            Ok("modular_import") => CompilationMode::ModularImport,
-            Ok("modular_buildzig") => CompilationMode::ModularBuildZig,
-            _ => {
-                // Default to Merged if not set or invalid
-                CompilationMode::Merged
-            },
+impl CompilationMode {
+    /// Mode named as in `AUTOZIG_MODE`: "merged", "modular_import" or
+    /// "modular_buildzig"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "merged" => Some(CompilationMode::Merged),
+            "modular_import" => Some(CompilationMode::ModularImport),
+            "modular_buildzig" => Some(CompilationMode::ModularBuildZig),
+            _ => None,
+        }
+    }
+
+    /// Name accepted by [`CompilationMode::from_name`]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CompilationMode::Merged => "merged",
+            CompilationMode::ModularImport => "modular_import",
+            CompilationMode::ModularBuildZig => "modular_buildzig",
         }
     }
+
+    /// Mode of the deprecated `AUTOZIG_MODE` environment variable, if set to
+    /// a valid name
+    pub fn from_env() -> Option<Self> {
+        std::env::var("AUTOZIG_MODE")
+            .ok()
+            .and_then(|name| CompilationMode::from_name(&name))
+    }
+
+    /// Mode the last build recorded in `out_dir`
+    pub fn recorded(out_dir: &Path) -> Option<Self> {
+        let name = fs::read_to_string(out_dir.join(MODE_FILE)).ok()?;
+        CompilationMode::from_name(name.trim())
+    }
+
+    /// Record the mode in `out_dir` for [`CompilationMode::recorded`]
+    pub fn record(self, out_dir: &Path) -> Result<()> {
+        let path = out_dir.join(MODE_FILE);
+        fs::write(&path, self.as_str())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
 }
 
 /// Result of scanning, containing either merged code or modular file
@@ -62,11 +124,62 @@ pub enum ScanResult {
         external_files: Vec<PathBuf>,
         /// All unique Zig files to be compiled
         all_zig_files: Vec<PathBuf>,
-        /// C source files to be compiled and linked
+        /// C and C++ source files to be compiled and linked
         c_source_files: Vec<PathBuf>,
     },
 }
 
+/// A prebuilt static library referenced by `bind_zig!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrebuiltLibrary {
+    /// Archive path, resolved against the manifest directory
+    pub path: PathBuf,
+    /// Symbols the declared signatures link against
+    pub symbols: Vec<String>,
+}
+
+/// Kind of foreign source that ends up linked into the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// Rust file containing embedded Zig in `autozig!`
+    EmbeddedZig,
+    /// Standalone or `include_zig!` Zig file
+    Zig,
+    /// C source compiled alongside the Zig code
+    C,
+    /// C++ source compiled alongside the Zig code (`ModularBuildZig` mode)
+    Cpp,
+    /// Prebuilt archive linked with `bind_zig!`
+    Archive,
+}
+
+impl SourceKind {
+    /// Short lowercase name used in reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceKind::EmbeddedZig => "embedded-zig",
+            SourceKind::Zig => "zig",
+            SourceKind::C => "c",
+            SourceKind::Cpp => "c++",
+            SourceKind::Archive => "archive",
+        }
+    }
+}
+
+/// A foreign source file that contributes code to the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignSource {
+    pub path: PathBuf,
+    pub kind: SourceKind,
+}
+
+/// Whether `path` is a C++ source file (`.cpp`, `.cc` or `.cxx`)
+pub fn is_cpp_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext, "cpp" | "cc" | "cxx"))
+}
+
 /// Scanner for extracting Zig code from Rust source files
 pub struct ZigCodeScanner {
     src_dir: std::path::PathBuf,
@@ -75,8 +188,15 @@ pub struct ZigCodeScanner {
 }
 
 impl ZigCodeScanner {
+    /// Create scanner with the mode recorded in `OUT_DIR` by the build
+    /// script, which already follows the blocks' headers, else
+    /// `AUTOZIG_MODE` (deprecated), else merged
     pub fn new(src_dir: impl AsRef<Path>) -> Self {
-        Self::with_mode(src_dir, CompilationMode::default())
+        let mode = std::env::var_os("OUT_DIR")
+            .and_then(|out_dir| CompilationMode::recorded(Path::new(&out_dir)))
+            .or_else(CompilationMode::from_env)
+            .unwrap_or_default();
+        Self::with_mode(src_dir, mode)
     }
 
     /// Create scanner with specific compilation mode
@@ -100,11 +220,22 @@ impl ZigCodeScanner {
         }
     }
 
+    /// Resolve `include_zig!`/`bind_zig!` paths against `dir`
+    pub fn with_manifest_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.manifest_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
     /// Get the compilation mode
     pub fn mode(&self) -> CompilationMode {
         self.mode
     }
 
+    /// Directory that `include_zig!`/`bind_zig!` paths are relative to
+    pub fn manifest_dir(&self) -> &Path {
+        &self.manifest_dir
+    }
+
     /// Scan all .rs files and extract Zig code using AST parsing
     /// Returns merged code string for backward compatibility
     pub fn scan(&self) -> Result<String> {
@@ -130,14 +261,15 @@ impl ZigCodeScanner {
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
-            if path.extension().map_or(false, |ext| ext == "rs") {
+            if path.extension().is_some_and(|ext| ext == "rs") {
                 let content = fs::read_to_string(path)
                     .with_context(|| format!("Failed to read {}", path.display()))?;
 
                 // Parse the Rust file into an AST
                 match syn::parse_file(&content) {
                     Ok(file) => {
-                        let mut visitor = AutozigVisitor::default();
+                        let origin = path.display().to_string();
+                        let mut visitor = AutozigVisitor::with_source(&content, &origin);
                         visitor.visit_file(&file);
 
                         // Collect embedded Zig code
@@ -164,7 +296,7 @@ impl ZigCodeScanner {
             }
         }
 
-        // Also scan for standalone .zig and .c files in src directory
+        // Also scan for standalone .zig, C and C++ files in src directory
         for entry in WalkDir::new(&self.src_dir)
             .into_iter()
             .filter_map(|e| e.ok())
@@ -173,8 +305,8 @@ impl ZigCodeScanner {
             if let Some(ext) = path.extension() {
                 if ext == "zig" {
                     all_zig_files.insert(path.to_path_buf());
-                } else if ext == "c" {
-                    // Collect C source files for build.zig compilation
+                } else if ext == "c" || is_cpp_source(path) {
+                    // Collect C and C++ source files for build.zig compilation
                     c_source_files.insert(path.to_path_buf());
                 }
             }
@@ -199,6 +331,204 @@ impl ZigCodeScanner {
         }
     }
 
+    /// Collect the prebuilt libraries bound with `bind_zig!`
+    ///
+    /// Declarations of the same archive in several places are merged.
+    pub fn scan_prebuilt(&self) -> Result<Vec<PrebuiltLibrary>> {
+        let mut libraries: Vec<PrebuiltLibrary> = Vec::new();
+
+        for entry in WalkDir::new(&self.src_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                continue;
+            }
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let Ok(file) = syn::parse_file(&content) else {
+                continue;
+            };
+
+            let mut visitor = AutozigVisitor::default();
+            visitor.visit_file(&file);
+
+            for (lib_path, symbols) in visitor.bound_libraries {
+                let lib_path = self.manifest_dir.join(lib_path);
+                match libraries.iter_mut().find(|lib| lib.path == lib_path) {
+                    Some(existing) => {
+                        for symbol in symbols {
+                            if !existing.symbols.contains(&symbol) {
+                                existing.symbols.push(symbol);
+                            }
+                        }
+                    },
+                    None => libraries.push(PrebuiltLibrary { path: lib_path, symbols }),
+                }
+            }
+        }
+
+        Ok(libraries)
+    }
+
+    /// Names of the Zig functions bound with `#[autozig(multiversion)]`,
+    /// except those excluded by their `#[cfg]`
+    pub fn scan_multiversion(&self) -> Result<Vec<String>> {
+        let mut functions = Vec::new();
+        for visitor in self.visit_files_mentioning("multiversion")? {
+            for name in visitor.multiversion {
+                if !functions.contains(&name) {
+                    functions.push(name);
+                }
+            }
+        }
+        Ok(functions)
+    }
+
+    /// Compute kernels bound with `#[autozig(gpu)]`, except those excluded
+    /// by their `#[cfg]`
+    pub fn scan_gpu_kernels(&self) -> Result<Vec<GpuKernel>> {
+        let mut kernels: Vec<GpuKernel> = Vec::new();
+        for visitor in self.visit_files_mentioning("gpu")? {
+            for kernel in visitor.gpu {
+                if !kernels.iter().any(|known| known.symbol == kernel.symbol) {
+                    kernels.push(kernel);
+                }
+            }
+        }
+        Ok(kernels)
+    }
+
+    /// Visit the Rust files of the crate that mention `keyword`, in file
+    /// name order
+    fn visit_files_mentioning(&self, keyword: &str) -> Result<Vec<AutozigVisitor<'static>>> {
+        let mut visitors = Vec::new();
+
+        for entry in WalkDir::new(&self.src_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                continue;
+            }
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if !content.contains(keyword) {
+                continue;
+            }
+            let Ok(file) = syn::parse_file(&content) else {
+                continue;
+            };
+
+            let mut visitor = AutozigVisitor::default();
+            visitor.visit_file(&file);
+            visitors.push(visitor);
+        }
+
+        Ok(visitors)
+    }
+
+    /// List every foreign source that contributes code to the build, in a
+    /// stable order
+    pub fn foreign_sources(&self) -> Result<Vec<ForeignSource>> {
+        let mut sources = Vec::new();
+
+        for entry in WalkDir::new(&self.src_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            let kind = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("zig") => SourceKind::Zig,
+                Some("c") => SourceKind::C,
+                Some(_) if is_cpp_source(path) => SourceKind::Cpp,
+                Some("rs") => {
+                    let content = fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    let Ok(file) = syn::parse_file(&content) else {
+                        continue;
+                    };
+                    let mut visitor = AutozigVisitor::default();
+                    visitor.visit_file(&file);
+
+                    // Missing include_zig! files are reported by scan_modular
+                    for external_file in visitor.external_files {
+                        let external_path = self.manifest_dir.join(external_file);
+                        if external_path.exists() {
+                            sources.push(ForeignSource {
+                                path: external_path,
+                                kind: SourceKind::Zig,
+                            });
+                        }
+                    }
+                    for (lib_path, _) in visitor.bound_libraries {
+                        sources.push(ForeignSource {
+                            path: self.manifest_dir.join(lib_path),
+                            kind: SourceKind::Archive,
+                        });
+                    }
+                    if visitor.zig_code.is_empty() {
+                        continue;
+                    }
+                    SourceKind::EmbeddedZig
+                },
+                _ => continue,
+            };
+            sources.push(ForeignSource { path: path.to_path_buf(), kind });
+        }
+
+        // include_zig! files usually live under src/ as well
+        let mut seen = HashSet::new();
+        sources.retain(|source| {
+            let key = source
+                .path
+                .canonicalize()
+                .unwrap_or_else(|_| source.path.clone());
+            seen.insert(key)
+        });
+
+        Ok(sources)
+    }
+
+    /// Every file the build reads: the Rust, Zig, C and header files under
+    /// the source directory, the `include_zig!` files, and the local files
+    /// they import or include (see [`crate::deps`])
+    pub fn dependencies(&self) -> Result<Vec<PathBuf>> {
+        let mut roots = Vec::new();
+        for entry in WalkDir::new(&self.src_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("zig" | "c" | "h" | "cpp" | "cc" | "cxx" | "hpp" | "hh") => {
+                    roots.push(path.to_path_buf())
+                },
+                Some("rs") => {
+                    roots.push(path.to_path_buf());
+                    let content = fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    if let Ok(file) = syn::parse_file(&content) {
+                        let mut visitor = AutozigVisitor::default();
+                        visitor.visit_file(&file);
+                        roots.extend(
+                            visitor
+                                .external_files
+                                .iter()
+                                .map(|external_file| self.manifest_dir.join(external_file)),
+                        );
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(crate::deps::closure(roots))
+    }
+
     /// Merge code for legacy mode
     fn merge_code(&self, embedded: &[String], external: &[PathBuf]) -> Result<String> {
         let mut consolidated_zig = String::new();
@@ -238,24 +568,218 @@ impl ZigCodeScanner {
     }
 }
 
-/// AST visitor to extract autozig! and include_zig! macro contents
+/// AST visitor to extract autozig!, include_zig! and bind_zig! macro contents
 #[derive(Default)]
-struct AutozigVisitor {
+struct AutozigVisitor<'s> {
+    /// Text of the file being visited, to copy embedded Zig verbatim
+    source: &'s str,
+    /// Display path of the file being visited, for origin markers
+    origin: &'s str,
     zig_code: Vec<String>,
     external_files: Vec<String>,
+    /// (archive path, declared symbols) per bind_zig! invocation
+    bound_libraries: Vec<(String, Vec<String>)>,
+    /// Functions bound with `#[autozig(multiversion)]`
+    multiversion: Vec<String>,
+    /// Functions bound with `#[autozig(gpu)]`
+    gpu: Vec<GpuKernel>,
+}
+
+impl<'s> AutozigVisitor<'s> {
+    fn with_source(source: &'s str, origin: &'s str) -> Self {
+        Self { source, origin, ..Default::default() }
+    }
+
+    /// Zig section of an autozig! invocation copied verbatim from the source
+    /// file, with the Rust line it starts at
+    ///
+    /// Returns `None` when the macro has no usable source location (e.g. it
+    /// was not parsed from `self.source`).
+    fn raw_zig_section(&self, node: &Macro) -> Option<(String, usize)> {
+        let (open, close, tokens) = match namespaced_block(&node.tokens) {
+            Some((_, block)) => (block.span_open(), block.span_close(), block.stream()),
+            None => {
+                let delim = match &node.delimiter {
+                    MacroDelimiter::Paren(paren) => paren.span,
+                    MacroDelimiter::Brace(brace) => brace.span,
+                    MacroDelimiter::Bracket(bracket) => bracket.span,
+                };
+                (delim.open(), delim.close(), node.tokens.clone())
+            },
+        };
+        let (body_start, start_line) = match inner_attributes_end(&tokens) {
+            Some(attrs_end) => (attrs_end.byte_range().end, attrs_end.end().line),
+            None => (open.byte_range().end, open.start().line),
+        };
+        let body_end = separator_start(&tokens).unwrap_or_else(|| close.byte_range().start);
+        if body_start >= body_end {
+            return None;
+        }
+        let raw = self.source.get(body_start..body_end)?;
+
+        // Drop leading blank lines but keep the indentation of the first line
+        // so that Zig's columns match the Rust file
+        let leading = &raw[..raw.len() - raw.trim_start().len()];
+        let skip = leading.rfind('\n').map_or(0, |idx| idx + 1);
+        let line = start_line + leading[..skip].matches('\n').count();
+
+        Some((convert_to_extern_struct(raw[skip..].trim_end()), line))
+    }
+}
+
+/// Namespace and braced body of an `autozig!(mod = img, { ... })` invocation
+fn namespaced_block(tokens: &TokenStream) -> Option<(String, Group)> {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(keyword), TokenTree::Punct(eq), name, TokenTree::Punct(comma), TokenTree::Group(block), ..]
+            if keyword == "mod"
+                && eq.as_char() == '='
+                && comma.as_char() == ','
+                && block.delimiter() == Delimiter::Brace =>
+        {
+            let name = name.to_string();
+            Some((name.trim_matches('"').to_string(), block.clone()))
+        },
+        _ => None,
+    }
+}
+
+/// Closing bracket of the leading `#![...]` attributes (e.g. `#![infer]`),
+/// which are not Zig code
+fn inner_attributes_end(tokens: &TokenStream) -> Option<Span> {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    let mut end = None;
+    for attr in tokens.chunks(3) {
+        match attr {
+            [TokenTree::Punct(hash), TokenTree::Punct(bang), TokenTree::Group(group)]
+                if hash.as_char() == '#'
+                    && bang.as_char() == '!'
+                    && group.delimiter() == Delimiter::Bracket =>
+            {
+                end = Some(group.span_close());
+            },
+            _ => break,
+        }
+    }
+    end
+}
+
+/// Byte offset of the `---` separating Zig code from Rust signatures
+fn separator_start(tokens: &TokenStream) -> Option<usize> {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    tokens.windows(3).find_map(|window| match window {
+        [TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Punct(c)]
+            if a.as_char() == '-'
+                && b.as_char() == '-'
+                && c.as_char() == '-'
+                && a.spacing() == Spacing::Joint
+                && b.spacing() == Spacing::Joint =>
+        {
+            Some(a.span().byte_range().start)
+        },
+        _ => None,
+    })
 }
 
-impl<'ast> Visit<'ast> for AutozigVisitor {
+impl<'ast> Visit<'ast> for AutozigVisitor<'_> {
     fn visit_macro(&mut self, node: &'ast Macro) {
+        if (node.path.is_ident("autozig") || node.path.is_ident("include_zig"))
+            && ["multiversion", "gpu"]
+                .iter()
+                .any(|keyword| node.tokens.to_string().contains(keyword))
+        {
+            let (signatures, namespace) = if node.path.is_ident("autozig") {
+                syn::parse2::<AutoZigConfig>(node.tokens.clone())
+                    .map(|config| (config.rust_signatures, config.namespace))
+                    .unwrap_or_default()
+            } else {
+                let signatures = syn::parse2::<IncludeZigConfig>(node.tokens.clone())
+                    .map(|config| config.rust_signatures);
+                (signatures.unwrap_or_default(), None)
+            };
+            let symbol = |sig: &RustFunctionSignature| match &namespace {
+                Some(namespace) => autozig_parser::namespaced_symbol(
+                    &namespace.to_string(),
+                    &sig.sig.ident.to_string(),
+                ),
+                None => sig.sig.ident.to_string(),
+            };
+            let enabled = signatures
+                .iter()
+                .filter(|sig| cfg_gate::is_enabled(&sig.cfg_attrs) != Some(false));
+            for sig in enabled {
+                if sig.binding_config.multiversion {
+                    self.multiversion.push(symbol(sig));
+                }
+                if sig.binding_config.gpu {
+                    // Invalid kernels are reported by the macro
+                    let Ok(buffers) = autozig_parser::gpu::storage_buffers(&sig.sig) else {
+                        continue;
+                    };
+                    self.gpu.push(GpuKernel {
+                        symbol: symbol(sig),
+                        buffers: buffers
+                            .iter()
+                            .map(|buffer| buffer.name.to_string())
+                            .collect(),
+                        workgroup_size: sig
+                            .binding_config
+                            .workgroup_size
+                            .unwrap_or(autozig_parser::gpu::DEFAULT_WORKGROUP_SIZE),
+                    });
+                }
+            }
+        }
+
         // Check if this is an autozig! macro
         if node.path.is_ident("autozig") {
-            // Extract the token stream and convert to string
-            let tokens = node.tokens.to_string();
-
-            // The tokens will be in the format: { ... }
-            // We need to extract the content and split by ---
-            if let Some(zig_code) = extract_zig_from_tokens(&tokens) {
-                self.zig_code.push(zig_code);
+            let block = namespaced_block(&node.tokens);
+            let header = BlockHeader::of_tokens(match &block {
+                Some((_, block)) => block.stream(),
+                None => node.tokens.clone(),
+            })
+            .unwrap_or_default();
+            let namespace = match block {
+                Some((name, _)) => Some(name),
+                None => header.namespace.as_ref().map(ToString::to_string),
+            };
+            let apply_namespace = |zig_code: String| match &namespace {
+                Some(namespace) => namespace::prefix_exports(&zig_code, namespace),
+                None => zig_code,
+            };
+            // `mode` and `optimize` of `#![autozig(...)]` travel to the engine
+            // as a comment above the block
+            let with_marker = |zig_code: String| match header.marker() {
+                Some(marker) => format!("{marker}\n{zig_code}"),
+                None => zig_code,
+            };
+            if let Some((zig_code, line)) = self.raw_zig_section(node) {
+                let zig_code = apply_namespace(apply_signature_attributes(node, zig_code));
+                // Copy the Zig code verbatim and record where it came from so
+                // that compiler errors can be mapped back to the Rust file
+                if !zig_code.trim().is_empty() {
+                    self.zig_code
+                        .push(with_marker(diagnostics::wrap_with_origin(
+                            &zig_code,
+                            self.origin,
+                            line,
+                        )));
+                }
+            } else {
+                // Extract the token stream and convert to string
+                let tokens = match namespaced_block(&node.tokens) {
+                    Some((_, block)) => block.to_string(),
+                    None => node.tokens.to_string(),
+                };
+
+                // The tokens will be in the format: { ... }
+                // We need to extract the content and split by ---
+                if let Some(zig_code) = extract_zig_from_tokens(&tokens) {
+                    self.zig_code
+                        .push(with_marker(apply_namespace(apply_signature_attributes(
+                            node, zig_code,
+                        ))));
+                }
             }
         }
         // Check if this is an include_zig! macro
@@ -267,6 +791,14 @@ impl<'ast> Visit<'ast> for AutozigVisitor {
                 self.external_files.push(file_path);
             }
         }
+        // Check if this is a bind_zig! macro (prebuilt archive, nothing to
+        // compile)
+        else if node.path.is_ident("bind_zig") {
+            if let Ok(config) = syn::parse2::<IncludeZigConfig>(node.tokens.clone()) {
+                let symbols = config.symbol_names();
+                self.bound_libraries.push((config.file_path, symbols));
+            }
+        }
 
         // Continue visiting nested items
         syn::visit::visit_macro(self, node);
@@ -278,6 +810,83 @@ impl<'ast> Visit<'ast> for AutozigVisitor {
     }
 }
 
+/// Apply the attributes of an autozig! invocation's Rust signatures that
+/// change its Zig code: `#[cfg]`-excluded exports are dropped (see
+/// [`cfg_gate`]), `#[monomorphize_const]` and `#[monomorphize]`
+/// specializations are added (see [`const_generics`] and [`type_generics`])
+/// and Rust enums and bridged structs get their Zig declaration (see
+/// [`zig_enums`] and [`bridge_structs`])
+fn apply_signature_attributes(node: &Macro, zig_code: String) -> String {
+    // Skip parsing the signatures of the (many) blocks without any of them
+    let tokens = node.tokens.to_string();
+    if !["cfg", "monomorphize", "enum", "bridge"]
+        .iter()
+        .any(|keyword| tokens.contains(keyword))
+    {
+        return zig_code;
+    }
+    let Ok(config) = syn::parse2::<AutoZigConfig>(node.tokens.clone()) else {
+        return zig_code;
+    };
+
+    let disabled: Vec<String> = config
+        .rust_signatures
+        .iter()
+        .filter(|sig| cfg_gate::is_enabled(&sig.cfg_attrs) == Some(false))
+        .map(|sig| sig.sig.ident.to_string())
+        .collect();
+    let mut zig_code = cfg_gate::unexport(&zig_code, &disabled);
+
+    for sig in &config.rust_signatures {
+        let name = sig.sig.ident.to_string();
+        let Some(param) = sig.sig.generics.const_params().next() else {
+            continue;
+        };
+        if sig.monomorphize_const.is_empty() || disabled.contains(&name) {
+            continue;
+        }
+        let specializations = const_generics::specialize(
+            &zig_code,
+            &name,
+            &param.ident.to_string(),
+            &sig.monomorphize_const,
+        );
+        zig_code.push_str(&specializations);
+    }
+
+    for sig in &config.rust_signatures {
+        let name = sig.sig.ident.to_string();
+        if sig.generic_params.is_empty()
+            || sig.monomorphize_types.is_empty()
+            || disabled.contains(&name)
+        {
+            continue;
+        }
+        let params: Vec<String> = sig.generic_params.iter().map(|p| p.name.clone()).collect();
+        let specializations =
+            type_generics::specialize(&zig_code, &name, &params, &sig.monomorphize_instances());
+        zig_code.push_str(&specializations);
+    }
+    for definition in &config.rust_structs {
+        let aliases = type_generics::instance_aliases(
+            &zig_code,
+            &definition.item.ident.to_string(),
+            &definition.monomorphize_types,
+        );
+        zig_code.push_str(&aliases);
+    }
+
+    let enums: Vec<_> = config.rust_enums.into_iter().map(|e| e.item).collect();
+    let structs: Vec<_> = config
+        .rust_structs
+        .into_iter()
+        .filter(|s| s.bridge)
+        .map(|s| s.item)
+        .collect();
+    let zig_code = zig_enums::inject(&zig_code, &enums);
+    bridge_structs::inject(&zig_code, &structs)
+}
+
 /// Extract file path from include_zig! macro tokens
 /// Expected format: ("path/to/file.zig", { ... }) or just ("path/to/file.zig")
 fn extract_file_path_from_tokens(tokens: &str) -> Option<String> {
@@ -313,6 +922,14 @@ fn extract_zig_from_tokens(tokens: &str) -> Option<String> {
     } else {
         content
     };
+    // Leading `#![infer]` / `#![autozig(...)]` attributes are not Zig code
+    let mut content = content;
+    while let Some(attr) = content.trim_start().strip_prefix("# ! [") {
+        match attr.find(']') {
+            Some(end) => content = &attr[end + 1..],
+            None => break,
+        }
+    }
 
     // Split by --- separator (Zig code comes before ---)
     // Only take the first part (before ---)
@@ -485,6 +1102,21 @@ fn find_struct_keyword_end(code: &str) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_recorded_mode() {
+        let dir = std::env::temp_dir().join(format!("autozig-mode-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(CompilationMode::recorded(&dir), None);
+
+        CompilationMode::ModularImport.record(&dir).unwrap();
+        assert_eq!(CompilationMode::recorded(&dir), Some(CompilationMode::ModularImport));
+        for mode in [CompilationMode::Merged, CompilationMode::ModularBuildZig] {
+            assert_eq!(CompilationMode::from_name(mode.as_str()), Some(mode));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn test_extract_zig_from_tokens() {
         let tokens = r#"{
@@ -585,4 +1217,161 @@ export fn test() void {}
         assert!(result.contains("extern struct"));
         assert!(result.contains("export fn create_color"));
     }
+
+    #[test]
+    fn test_embedded_code_keeps_source_lines() {
+        let source = concat!(
+            "use autozig::autozig;\n",
+            "\n",
+            "autozig! {\n",
+            "    // Add two numbers\n",
+            "    export fn add(a: i32, b: i32) i32 {\n",
+            "        return a + b;\n",
+            "    }\n",
+            "    ---\n",
+            "    fn add(a: i32, b: i32) -> i32;\n",
+            "}\n",
+        );
+        let file = syn::parse_file(source).unwrap();
+        let mut visitor = AutozigVisitor::with_source(source, "src/lib.rs");
+        visitor.visit_file(&file);
+
+        assert_eq!(visitor.zig_code.len(), 1);
+        let code = &visitor.zig_code[0];
+        assert!(code.starts_with("// @autozig:origin src/lib.rs:4\n    // Add two numbers\n"));
+        assert!(code.ends_with("    }\n// @autozig:origin end"));
+        assert!(!code.contains("fn add(a: i32, b: i32) -> i32;"));
+
+        let loc = diagnostics::map_location(code, 4, 9).unwrap();
+        assert_eq!((loc.file.as_str(), loc.line, loc.column), ("src/lib.rs", 6, 9));
+    }
+
+    #[test]
+    fn test_embedded_code_skips_infer_attribute() {
+        let source = concat!(
+            "autozig! {\n",
+            "    #![infer]\n",
+            "    export fn add(a: i32, b: i32) i32 {\n",
+            "        return a + b;\n",
+            "    }\n",
+            "}\n",
+        );
+        let file = syn::parse_file(source).unwrap();
+        let mut visitor = AutozigVisitor::with_source(source, "src/lib.rs");
+        visitor.visit_file(&file);
+
+        let code = &visitor.zig_code[0];
+        assert!(code.starts_with("// @autozig:origin src/lib.rs:3\n    export fn add"));
+    }
+
+    #[test]
+    fn test_embedded_code_of_configured_block() {
+        let source = concat!(
+            "autozig! {\n",
+            "    #![autozig(mode = \"modular\", mod = \"img\", optimize = \"ReleaseFast\")]\n",
+            "    export fn resize(w: u32) u32 {\n",
+            "        return w;\n",
+            "    }\n",
+            "}\n",
+        );
+        let file = syn::parse_file(source).unwrap();
+        let mut visitor = AutozigVisitor::with_source(source, "src/lib.rs");
+        visitor.visit_file(&file);
+
+        let code = &visitor.zig_code[0];
+        assert!(code.starts_with(
+            "// @autozig:config mode=modular_buildzig optimize=ReleaseFast\n// @autozig:origin \
+             src/lib.rs:3\n    export fn img__resize("
+        ));
+
+        let tokens =
+            "# ! [autozig (optimize = \"ReleaseFast\")] # ! [infer] export fn f () void { }";
+        assert_eq!(extract_zig_from_tokens(tokens).unwrap(), "export fn f () void { }");
+    }
+
+    #[test]
+    fn test_embedded_code_of_namespaced_block() {
+        let source = concat!(
+            "autozig!(mod = img, {\n",
+            "    export fn resize(w: u32) u32 {\n",
+            "        return w;\n",
+            "    }\n",
+            "    ---\n",
+            "    fn resize(w: u32) -> u32;\n",
+            "});\n",
+        );
+        let file = syn::parse_file(source).unwrap();
+        let mut visitor = AutozigVisitor::with_source(source, "src/lib.rs");
+        visitor.visit_file(&file);
+
+        let code = &visitor.zig_code[0];
+        assert!(code.starts_with("// @autozig:origin src/lib.rs:2\n    export fn img__resize("));
+        assert!(!code.contains("mod = img"));
+    }
+
+    #[test]
+    fn test_visit_bind_zig() {
+        let file: syn::File = syn::parse_quote! {
+            bind_zig!("vendor/libfoo.a", {
+                fn foo_checksum(data: &[u8]) -> u32;
+            });
+        };
+        let mut visitor = AutozigVisitor::default();
+        visitor.visit_file(&file);
+
+        assert!(visitor.zig_code.is_empty());
+        assert!(visitor.external_files.is_empty());
+        assert_eq!(
+            visitor.bound_libraries,
+            vec![("vendor/libfoo.a".to_string(), vec!["foo_checksum".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_visit_multiversion() {
+        let file: syn::File = syn::parse_quote! {
+            autozig! {
+                export fn dot(a: [*]const f32, b: [*]const f32, len: usize) f32 { return 0; }
+                ---
+                #[autozig(multiversion)]
+                fn dot(a: &[f32], b: &[f32]) -> f32;
+                fn other(x: i32) -> i32;
+            }
+            include_zig!("src/blur.zig", {
+                #[autozig(multiversion)]
+                fn blur(pixels: &mut [u8]);
+            });
+        };
+        let mut visitor = AutozigVisitor::default();
+        visitor.visit_file(&file);
+
+        assert_eq!(visitor.multiversion, ["dot", "blur"]);
+    }
+
+    #[test]
+    fn test_visit_gpu() {
+        let file: syn::File = syn::parse_quote! {
+            autozig! {
+                mod = compute, {
+                    export fn saxpy() callconv(.kernel) void {}
+                    ---
+                    #[autozig(gpu, workgroup_size = 256)]
+                    fn saxpy(x: &[f32], y: &mut [f32]);
+                    #[autozig(gpu)]
+                    fn invalid(x: &[f32], a: f32);
+                }
+            }
+        };
+        let mut visitor = AutozigVisitor::default();
+        visitor.visit_file(&file);
+
+        assert_eq!(
+            visitor.gpu,
+            [GpuKernel {
+                symbol: "compute__saxpy".to_string(),
+                buffers: vec!["x".to_string(), "y".to_string()],
+                workgroup_size: 256,
+            }]
+        );
+    }
 }
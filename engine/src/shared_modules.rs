@@ -0,0 +1,197 @@
+//! Compiling identical Zig modules once per workspace
+//!
+//! Crates of a workspace often `include_zig!` the same file (e.g. a shared
+//! `zig/math.zig`). With module sharing enabled, every self-contained module
+//! is compiled on its own into `lib<autozig_mod_HASH>.a` in the shared
+//! [`ArtifactCache`] directory, keyed by its contents, the Zig target and the
+//! Zig version. Each crate links that archive instead of compiling the module
+//! into its own library, so the module is built once and its exports exist
+//! in one archive only.
+//!
+//! The cache directory also holds `modules.index`, recording which packages
+//! link which shared module (`<key> <package> <file name>` per line).
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+
+use crate::ArtifactCache;
+
+const INDEX_FILE: &str = "modules.index";
+/// Cache lock key guarding `modules.index`
+const INDEX_LOCK: &str = "modules-index";
+
+/// Whether a Zig module can be compiled on its own
+///
+/// Modules importing the root module, other local files or the generated
+/// helper types depend on the crate they are compiled into.
+pub fn is_standalone(zig_code: &str) -> bool {
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find("@import(\"") {
+        rest = &rest[pos + "@import(\"".len()..];
+        let name = rest.split('"').next().unwrap_or_default();
+        if name != "std" && name != "builtin" {
+            return false;
+        }
+    }
+    !zig_code.contains("AutoZig") && !zig_code.contains("autozig_runtime")
+}
+
+/// Name of the static library holding the shared module with `key`
+pub fn lib_name(key: &str) -> String {
+    format!("autozig_mod_{}", &key[..16.min(key.len())])
+}
+
+/// Path of the shared module library for `key` in `cache`
+pub fn lib_path(cache: &ArtifactCache, key: &str) -> PathBuf {
+    cache.dir().join(format!("lib{}.a", lib_name(key)))
+}
+
+/// One line of `modules.index`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub key: String,
+    pub package: String,
+    pub file_name: String,
+}
+
+/// A module compiled into the shared cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedModule {
+    /// Cache key of the module's library (see [`lib_name`])
+    pub key: String,
+    /// File name of the module
+    pub file_name: String,
+    /// Whether the library was already in the cache
+    pub reused: bool,
+}
+
+/// Record of the packages linking each shared module
+#[derive(Debug, Clone)]
+pub struct ModuleIndex {
+    cache: ArtifactCache,
+}
+
+impl ModuleIndex {
+    /// Index stored in `cache`
+    pub fn new(cache: ArtifactCache) -> Self {
+        Self { cache }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.cache.dir().join(INDEX_FILE)
+    }
+
+    /// All recorded entries, skipping malformed lines
+    pub fn entries(&self) -> Result<Vec<IndexEntry>> {
+        let path = self.path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(text
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                Some(IndexEntry {
+                    key: parts.next()?.to_string(),
+                    package: parts.next()?.to_string(),
+                    file_name: parts.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Record that `package` links the shared module `key`
+    ///
+    /// Returns the other packages already linking it.
+    pub fn record(&self, key: &str, package: &str, file_name: &str) -> Result<Vec<String>> {
+        let _lock = self.cache.lock(INDEX_LOCK)?;
+        let mut entries = self.entries()?;
+        let others: Vec<String> = entries
+            .iter()
+            .filter(|entry| entry.key == key && entry.package != package)
+            .map(|entry| entry.package.clone())
+            .collect();
+
+        if !entries
+            .iter()
+            .any(|entry| entry.key == key && entry.package == package)
+        {
+            entries.push(IndexEntry {
+                key: key.to_string(),
+                package: package.to_string(),
+                file_name: file_name.to_string(),
+            });
+            let text: String = entries
+                .iter()
+                .map(|entry| format!("{} {} {}\n", entry.key, entry.package, entry.file_name))
+                .collect();
+            let tmp = self
+                .cache
+                .dir()
+                .join(format!("{INDEX_FILE}.{}.tmp", std::process::id()));
+            fs::write(&tmp, text).context("Failed to write module index")?;
+            fs::rename(&tmp, self.path()).context("Failed to write module index")?;
+        }
+        Ok(others)
+    }
+}
+
+/// Copy `module` into its own directory under `out_dir`, so compiling it
+/// picks up no other sources
+pub fn isolate(module: &Path, out_dir: &Path) -> Result<PathBuf> {
+    let dir = out_dir.join("autozig_shared");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let dest = dir.join(module.file_name().unwrap_or_default());
+    fs::copy(module, &dest).with_context(|| format!("Failed to copy {}", module.display()))?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_is_standalone() {
+        assert!(is_standalone(
+            "const std = @import(\"std\");\nexport fn add(a: i32, b: i32) i32 { return a + b; }"
+        ));
+        assert!(!is_standalone("const root = @import(\"root\");"));
+        assert!(!is_standalone("const util = @import(\"util.zig\");"));
+        assert!(!is_standalone("export fn f() AutoZigSlice(u8) { return undefined; }"));
+    }
+
+    #[test]
+    fn test_module_index() {
+        let dir = env::temp_dir().join(format!("autozig-modules-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let index = ModuleIndex::new(ArtifactCache::new(&dir));
+
+        assert!(index
+            .record("abc", "crate_a", "math.zig")
+            .unwrap()
+            .is_empty());
+        assert!(index
+            .record("abc", "crate_a", "math.zig")
+            .unwrap()
+            .is_empty());
+        assert_eq!(index.record("abc", "crate_b", "math.zig").unwrap(), vec!["crate_a"]);
+        assert_eq!(index.entries().unwrap().len(), 2);
+        assert_eq!(lib_name("0123456789abcdef0123"), "autozig_mod_0123456789abcdef");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
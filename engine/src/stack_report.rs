@@ -0,0 +1,180 @@
+//! Stack usage reporting for exported Zig functions
+//!
+//! Zig forwards LLVM's stack size diagnostics when invoked with
+//! `-fstack-report`. This module parses that output, narrows it down to the
+//! functions exported across the FFI boundary, and checks it against an
+//! optional per-function budget (e.g. for code running on small fibers or
+//! embedded stacks).
+
+use std::fmt::Write as _;
+
+use anyhow::Result;
+
+/// Stack usage of a single function as reported by the compiler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackUsage {
+    /// Symbol name of the function
+    pub function: String,
+    /// Stack frame size in bytes
+    pub bytes: u64,
+    /// `false` when the frame size depends on runtime values (alloca, VLAs)
+    pub bounded: bool,
+}
+
+/// Per-function stack usage collected from a Zig compilation
+#[derive(Debug, Clone, Default)]
+pub struct StackReport {
+    /// One entry per function, in compiler output order
+    pub entries: Vec<StackUsage>,
+}
+
+impl StackReport {
+    /// Parse compiler output into a report
+    ///
+    /// Two formats are understood:
+    /// - `.su` style lines: `file.zig:12:5:name\t48\tstatic`
+    /// - LLVM diagnostics: `stack frame size (48) exceeds limit (0) in function
+    ///   'name'`
+    pub fn parse(output: &str) -> Self {
+        let mut report = StackReport::default();
+
+        for line in output.lines() {
+            if let Some(usage) = parse_su_line(line).or_else(|| parse_llvm_diagnostic(line)) {
+                report.record(usage);
+            }
+        }
+
+        report
+    }
+
+    /// Keep only the given (exported) functions
+    pub fn retain_exports(&mut self, exports: &[String]) {
+        self.entries
+            .retain(|entry| exports.iter().any(|name| name == &entry.function));
+    }
+
+    /// Largest stack frame in the report
+    pub fn max(&self) -> Option<&StackUsage> {
+        self.entries.iter().max_by_key(|entry| entry.bytes)
+    }
+
+    /// Fail when any function exceeds `limit` bytes or has an unbounded frame
+    pub fn check_limit(&self, limit: u64) -> Result<()> {
+        let offenders: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.bounded || entry.bytes > limit)
+            .map(|entry| {
+                if entry.bounded {
+                    format!("{} uses {} bytes", entry.function, entry.bytes)
+                } else {
+                    format!("{} has an unbounded stack frame", entry.function)
+                }
+            })
+            .collect();
+
+        if !offenders.is_empty() {
+            anyhow::bail!(
+                "Stack usage exceeds max_stack ({} bytes):\n  {}",
+                limit,
+                offenders.join("\n  ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Render the report as a human-readable table
+    pub fn render(&self) -> String {
+        let mut out = String::from("function\tbytes\tkind\n");
+        for entry in &self.entries {
+            let kind = if entry.bounded { "static" } else { "dynamic" };
+            let _ = writeln!(out, "{}\t{}\t{}", entry.function, entry.bytes, kind);
+        }
+        out
+    }
+
+    /// Insert or update an entry, keeping the larger frame for duplicates
+    fn record(&mut self, usage: StackUsage) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.function == usage.function)
+        {
+            Some(existing) => {
+                existing.bytes = existing.bytes.max(usage.bytes);
+                existing.bounded &= usage.bounded;
+            },
+            None => self.entries.push(usage),
+        }
+    }
+}
+
+/// Parse a `.su` style line: `location:name<TAB>bytes<TAB>qualifier`
+fn parse_su_line(line: &str) -> Option<StackUsage> {
+    let mut parts = line.trim().split('\t');
+    let location = parts.next()?;
+    let bytes = parts.next()?.trim().parse().ok()?;
+    let qualifier = parts.next().unwrap_or("static").trim();
+
+    let function = location.rsplit(':').next()?.trim().to_string();
+    if function.is_empty() {
+        return None;
+    }
+
+    Some(StackUsage {
+        function,
+        bytes,
+        bounded: qualifier == "static" || qualifier.contains("bounded"),
+    })
+}
+
+/// Parse an LLVM `stack frame size (N) ... in function 'name'` diagnostic
+fn parse_llvm_diagnostic(line: &str) -> Option<StackUsage> {
+    let after_size = &line[line.find("stack frame size (")? + "stack frame size (".len()..];
+    let bytes = after_size[..after_size.find(')')?].trim().parse().ok()?;
+
+    let after_fn = &line[line.find("in function '")? + "in function '".len()..];
+    let function = after_fn[..after_fn.find('\'')?].to_string();
+
+    Some(StackUsage { function, bytes, bounded: true })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_su_format() {
+        let output = "main.zig:3:5:add\t16\tstatic\nmain.zig:9:5:scratch\t64\tdynamic\n";
+        let report = StackReport::parse(output);
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(
+            report.entries[0],
+            StackUsage {
+                function: "add".to_string(),
+                bytes: 16,
+                bounded: true
+            }
+        );
+        assert!(!report.entries[1].bounded);
+    }
+
+    #[test]
+    fn test_parse_llvm_diagnostic() {
+        let output = "warning: stack frame size (4112) exceeds limit (0) in function 'big_buf'";
+        let report = StackReport::parse(output);
+        assert_eq!(report.max().unwrap().function, "big_buf");
+        assert_eq!(report.max().unwrap().bytes, 4112);
+    }
+
+    #[test]
+    fn test_check_limit() {
+        let mut report =
+            StackReport::parse("a.zig:1:1:small\t32\tstatic\na.zig:2:1:big\t8192\tstatic");
+        assert!(report.check_limit(1024).is_err());
+
+        report.retain_exports(&["small".to_string()]);
+        assert!(report.check_limit(1024).is_ok());
+    }
+}
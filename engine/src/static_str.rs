@@ -0,0 +1,78 @@
+//! Lowering of exported Zig functions that return static strings
+//!
+//! `export fn version() []const u8` cannot use the C calling convention, so
+//! it is renamed to `version__autozig_str` and a C-ABI export with the
+//! original name returns the slice as `AutoZigSlice(u8)` instead. The Rust
+//! side declares `fn version() -> &'static str`; the slice must point to
+//! data that lives for the whole program (string literals, `@tagName`,
+//! globals).
+
+use autozig_parser::type_mapper::zig_exports;
+
+use crate::error_union::rename_export;
+
+/// Zig return types lowered to `AutoZigSlice(u8)`
+const STRING_TYPES: &[&str] = &["[]const u8", "[:0]const u8"];
+
+/// Rewrite every `export fn` returning a string slice in `zig_code`
+pub fn lower_str_exports(zig_code: &str) -> String {
+    let mut lowered = zig_code.to_string();
+    let mut wrappers = String::new();
+    for export in zig_exports(zig_code) {
+        if !STRING_TYPES.contains(&export.return_type.as_str()) {
+            continue;
+        }
+
+        lowered = rename_export(&lowered, &export.name, "__autozig_str");
+        let params: Vec<String> = export
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect();
+        let args: Vec<&str> = export
+            .params
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        wrappers.push_str(&format!(
+            "export fn {name}({}) AutoZigSlice(u8) {{\n    return \
+             AutoZigSlice(u8).fromSlice({name}__autozig_str({}));\n}}\n\n",
+            params.join(", "),
+            args.join(", "),
+            name = export.name,
+        ));
+    }
+
+    if wrappers.is_empty() {
+        return lowered;
+    }
+    lowered.push_str("\n\n// String slices lowered to AutoZigSlice(u8)\n");
+    lowered.push_str(&wrappers);
+    lowered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_str_exports() {
+        let code = r#"const Color = enum { red, green };
+export fn version() []const u8 { return "1.4.0"; }
+export fn color_name(color: u8) [:0]const u8 { return @tagName(@as(Color, @enumFromInt(color))); }
+export fn add(a: i32, b: i32) i32 { return a + b; }
+"#;
+        let lowered = lower_str_exports(code);
+        assert!(lowered.contains("fn version__autozig_str() []const u8"));
+        assert!(lowered.contains(
+            "export fn version() AutoZigSlice(u8) {\n    return \
+             AutoZigSlice(u8).fromSlice(version__autozig_str());\n}"
+        ));
+        assert!(lowered.contains(
+            "export fn color_name(color: u8) AutoZigSlice(u8) {\n    return \
+             AutoZigSlice(u8).fromSlice(color_name__autozig_str(color));\n}"
+        ));
+        assert!(lowered.contains("export fn add(a: i32, b: i32) i32 {"));
+        assert_eq!(lower_str_exports("export fn add() i32 {}"), "export fn add() i32 {}");
+    }
+}
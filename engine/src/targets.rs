@@ -0,0 +1,437 @@
+//! Rust target triple to Zig target mapping
+//!
+//! Zig and Rust spell target triples differently (`riscv64gc-unknown-linux-gnu`
+//! is `riscv64-linux-gnu`, `aarch64-apple-ios-sim` is
+//! `aarch64-ios-simulator`), and a few Rust targets imply CPU features that
+//! Zig's baseline for the same triple lacks. Mobile targets also need the
+//! platform SDK, which Zig does not ship: the Android NDK sysroot and the
+//! iOS SDK from Xcode are located here and passed to every Zig invocation.
+//!
+//! Bare-metal targets (Cortex-M, the RISC-V ESP32 chips) map to Zig's
+//! `freestanding` OS with the CPU Rust assumes, so the float ABI and
+//! instruction set match the firmware linking the library.
+//!
+//! Unknown triples are an error unless they are the host: guessing would
+//! produce a library for the wrong architecture that only fails at link time.
+
+use std::{
+    env,
+    fmt,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+
+/// Android API level used for the NDK libraries when `ANDROID_API_LEVEL` is
+/// unset (the lowest level Rust's Android targets support)
+const DEFAULT_ANDROID_API_LEVEL: &str = "21";
+
+/// Platform SDK a target links against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetSdk {
+    /// Zig provides everything (libc headers and startup files)
+    None,
+    /// Android NDK sysroot; the string is the NDK's directory for the triple
+    AndroidNdk(&'static str),
+    /// Apple SDK, named as `xcrun --sdk` expects it
+    Apple(&'static str),
+}
+
+/// Zig target for a Rust target triple
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZigTarget {
+    /// Zig target triple, or `native`
+    pub triple: &'static str,
+    /// CPU model and features for `-mcpu` (`None`: Zig's baseline CPU)
+    pub cpu: Option<&'static str>,
+    /// SDK required to link C code and libc
+    pub sdk: TargetSdk,
+}
+
+impl ZigTarget {
+    /// The host, with a baseline CPU
+    pub const NATIVE: ZigTarget = ZigTarget {
+        triple: "native",
+        cpu: None,
+        sdk: TargetSdk::None,
+    };
+
+    const fn plain(triple: &'static str) -> Self {
+        Self { triple, cpu: None, sdk: TargetSdk::None }
+    }
+
+    /// Map a Rust target triple
+    ///
+    /// `host` is the triple of the machine running the build (cargo's
+    /// `HOST`); a target missing from the table still builds when it is the
+    /// host, as `native`.
+    pub fn from_rust(rust_target: &str, host: Option<&str>) -> Result<Self> {
+        if let Some(target) = Self::lookup(rust_target) {
+            return Ok(target);
+        }
+        if rust_target == "native" || host == Some(rust_target) {
+            return Ok(Self::NATIVE);
+        }
+        if rust_target.starts_with("xtensa-") {
+            bail!(
+                "autozig does not support the Rust target `{}`: Zig cannot generate Xtensa code. \
+                 The RISC-V ESP32 chips (ESP32-C3, -C6, -H2) are supported",
+                rust_target
+            )
+        }
+        bail!(
+            "autozig does not support the Rust target `{}`: there is no known Zig equivalent. \
+             Supported targets: {}",
+            rust_target,
+            SUPPORTED_RUST_TARGETS.join(", ")
+        )
+    }
+
+    fn lookup(rust_target: &str) -> Option<Self> {
+        const ARMV7: &str = "generic+v7a+vfp3d16+thumb2";
+        // Rust's soft-float Cortex-M4/M7 target has no FPU at all
+        const CORTEX_M4_SOFT: &str = "cortex_m4-vfp4d16sp";
+        const RV32IMC: &str = "generic_rv32+m+c";
+        const RV32IMAC: &str = "generic_rv32+m+a+c";
+
+        let target = match rust_target {
+            // Linux targets
+            "x86_64-unknown-linux-gnu" => Self::plain("x86_64-linux-gnu"),
+            "x86_64-unknown-linux-musl" => Self::plain("x86_64-linux-musl"),
+            "aarch64-unknown-linux-gnu" => Self::plain("aarch64-linux-gnu"),
+            "aarch64-unknown-linux-musl" => Self::plain("aarch64-linux-musl"),
+            "arm-unknown-linux-gnueabihf" => Self::plain("arm-linux-gnueabihf"),
+            "armv7-unknown-linux-gnueabihf" => Self {
+                cpu: Some(ARMV7),
+                ..Self::plain("arm-linux-gnueabihf")
+            },
+            "armv7-unknown-linux-musleabihf" => Self {
+                cpu: Some(ARMV7),
+                ..Self::plain("arm-linux-musleabihf")
+            },
+            "i686-unknown-linux-gnu" => Self::plain("x86-linux-gnu"),
+            "i686-unknown-linux-musl" => Self::plain("x86-linux-musl"),
+            "riscv64gc-unknown-linux-gnu" => Self::plain("riscv64-linux-gnu"),
+            "riscv64gc-unknown-linux-musl" => Self::plain("riscv64-linux-musl"),
+            "loongarch64-unknown-linux-gnu" => Self::plain("loongarch64-linux-gnu"),
+            "powerpc64le-unknown-linux-gnu" => Self::plain("powerpc64le-linux-gnu"),
+            "s390x-unknown-linux-gnu" => Self::plain("s390x-linux-gnu"),
+
+            // Android targets
+            "aarch64-linux-android" => Self {
+                sdk: TargetSdk::AndroidNdk("aarch64-linux-android"),
+                ..Self::plain("aarch64-linux-android")
+            },
+            "armv7-linux-androideabi" => Self {
+                cpu: Some(ARMV7),
+                sdk: TargetSdk::AndroidNdk("arm-linux-androideabi"),
+                ..Self::plain("arm-linux-androideabi")
+            },
+            "x86_64-linux-android" => Self {
+                sdk: TargetSdk::AndroidNdk("x86_64-linux-android"),
+                ..Self::plain("x86_64-linux-android")
+            },
+            "i686-linux-android" => Self {
+                sdk: TargetSdk::AndroidNdk("i686-linux-android"),
+                ..Self::plain("x86-linux-android")
+            },
+
+            // macOS targets
+            "x86_64-apple-darwin" => Self::plain("x86_64-macos"),
+            "aarch64-apple-darwin" => Self::plain("aarch64-macos"),
+
+            // iOS targets
+            "aarch64-apple-ios" => Self {
+                sdk: TargetSdk::Apple("iphoneos"),
+                ..Self::plain("aarch64-ios")
+            },
+            "aarch64-apple-ios-sim" => Self {
+                sdk: TargetSdk::Apple("iphonesimulator"),
+                ..Self::plain("aarch64-ios-simulator")
+            },
+            "x86_64-apple-ios" => Self {
+                sdk: TargetSdk::Apple("iphonesimulator"),
+                ..Self::plain("x86_64-ios-simulator")
+            },
+
+            // Windows targets
+            "x86_64-pc-windows-msvc" => Self::plain("x86_64-windows"),
+            "x86_64-pc-windows-gnu" => Self::plain("x86_64-windows-gnu"),
+            "i686-pc-windows-msvc" => Self::plain("x86-windows"),
+            "i686-pc-windows-gnu" => Self::plain("x86-windows-gnu"),
+            "aarch64-pc-windows-msvc" => Self::plain("aarch64-windows"),
+
+            // WebAssembly
+            "wasm32-unknown-unknown" => Self::plain("wasm32-freestanding"),
+            "wasm32-wasi" | "wasm32-wasip1" | "wasm32-wasip1-threads" => Self::plain("wasm32-wasi"),
+            "wasm64-unknown-unknown" => Self::plain("wasm64-freestanding"),
+            "wasm64-wasi" => Self::plain("wasm64-wasi"),
+
+            // Bare metal: Cortex-M
+            "thumbv6m-none-eabi" => Self {
+                cpu: Some("cortex_m0plus"),
+                ..Self::plain("thumb-freestanding-eabi")
+            },
+            "thumbv7m-none-eabi" => Self {
+                cpu: Some("cortex_m3"),
+                ..Self::plain("thumb-freestanding-eabi")
+            },
+            "thumbv7em-none-eabi" => Self {
+                cpu: Some(CORTEX_M4_SOFT),
+                ..Self::plain("thumb-freestanding-eabi")
+            },
+            "thumbv7em-none-eabihf" => Self {
+                cpu: Some("cortex_m4"),
+                ..Self::plain("thumb-freestanding-eabihf")
+            },
+            "thumbv8m.main-none-eabihf" => Self {
+                cpu: Some("cortex_m33"),
+                ..Self::plain("thumb-freestanding-eabihf")
+            },
+
+            // Bare metal: RISC-V (ESP32-C3 is imc, ESP32-C6/H2 imac), soft float
+            "riscv32imc-unknown-none-elf" | "riscv32imc-esp-espidf" => Self {
+                cpu: Some(RV32IMC),
+                ..Self::plain("riscv32-freestanding-none")
+            },
+            "riscv32imac-unknown-none-elf" | "riscv32imac-esp-espidf" => Self {
+                cpu: Some(RV32IMAC),
+                ..Self::plain("riscv32-freestanding-none")
+            },
+
+            _ => return None,
+        };
+        Some(target)
+    }
+
+    /// Whether the target has no operating system (WASM aside): the Zig
+    /// code is built without libc or PIC, as with `CodegenFlags::freestanding`
+    pub fn is_bare_metal(&self) -> bool {
+        self.triple.contains("-freestanding") && !self.triple.starts_with("wasm")
+    }
+
+    /// `-mcpu` value for `zig build-lib` and friends
+    pub fn mcpu(&self) -> &'static str {
+        self.cpu.unwrap_or("baseline")
+    }
+
+    /// Environment variables the SDK lookup reads, for
+    /// `cargo:rerun-if-env-changed`
+    pub fn sdk_env_vars(&self) -> &'static [&'static str] {
+        match self.sdk {
+            TargetSdk::None => &[],
+            TargetSdk::AndroidNdk(_) => {
+                &["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT", "ANDROID_API_LEVEL"]
+            },
+            TargetSdk::Apple(_) => &["SDKROOT"],
+        }
+    }
+
+    /// Arguments locating the target's SDK, valid for both `zig build-lib`
+    /// and `zig build`
+    ///
+    /// Android gets a libc description file written to `work_dir`; iOS gets
+    /// `--sysroot`. Fails with instructions when the SDK cannot be found.
+    pub fn sdk_args(&self, work_dir: &Path) -> Result<Vec<String>> {
+        match self.sdk {
+            TargetSdk::None => Ok(Vec::new()),
+            TargetSdk::AndroidNdk(ndk_triple) => {
+                let sysroot = android_sysroot()
+                    .with_context(|| format!("cannot build Zig code for {}", self.triple))?;
+                let api_level = env::var("ANDROID_API_LEVEL")
+                    .unwrap_or_else(|_| DEFAULT_ANDROID_API_LEVEL.to_string());
+                let libc_file = work_dir.join(format!("autozig-libc-{}.txt", ndk_triple));
+                fs::write(&libc_file, android_libc_file(&sysroot, ndk_triple, &api_level))
+                    .context("Failed to write Zig libc file for the Android NDK")?;
+                Ok(vec!["--libc".to_string(), libc_file.display().to_string()])
+            },
+            TargetSdk::Apple(sdk) => {
+                let sysroot = apple_sysroot(sdk)
+                    .with_context(|| format!("cannot build Zig code for {}", self.triple))?;
+                Ok(vec!["--sysroot".to_string(), sysroot.display().to_string()])
+            },
+        }
+    }
+}
+
+impl fmt::Display for ZigTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cpu {
+            Some(cpu) => write!(f, "{} ({})", self.triple, cpu),
+            None => f.write_str(self.triple),
+        }
+    }
+}
+
+/// Rust targets with a Zig mapping, for error messages
+const SUPPORTED_RUST_TARGETS: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "x86_64-unknown-linux-musl",
+    "aarch64-unknown-linux-gnu",
+    "aarch64-unknown-linux-musl",
+    "arm-unknown-linux-gnueabihf",
+    "armv7-unknown-linux-gnueabihf",
+    "armv7-unknown-linux-musleabihf",
+    "i686-unknown-linux-gnu",
+    "i686-unknown-linux-musl",
+    "riscv64gc-unknown-linux-gnu",
+    "riscv64gc-unknown-linux-musl",
+    "loongarch64-unknown-linux-gnu",
+    "powerpc64le-unknown-linux-gnu",
+    "s390x-unknown-linux-gnu",
+    "aarch64-linux-android",
+    "armv7-linux-androideabi",
+    "x86_64-linux-android",
+    "i686-linux-android",
+    "x86_64-apple-darwin",
+    "aarch64-apple-darwin",
+    "aarch64-apple-ios",
+    "aarch64-apple-ios-sim",
+    "x86_64-apple-ios",
+    "x86_64-pc-windows-msvc",
+    "x86_64-pc-windows-gnu",
+    "i686-pc-windows-msvc",
+    "i686-pc-windows-gnu",
+    "aarch64-pc-windows-msvc",
+    "wasm32-unknown-unknown",
+    "wasm32-wasi",
+    "wasm32-wasip1",
+    "wasm32-wasip1-threads",
+    "wasm64-unknown-unknown",
+    "wasm64-wasi",
+    "thumbv6m-none-eabi",
+    "thumbv7m-none-eabi",
+    "thumbv7em-none-eabi",
+    "thumbv7em-none-eabihf",
+    "thumbv8m.main-none-eabihf",
+    "riscv32imc-unknown-none-elf",
+    "riscv32imc-esp-espidf",
+    "riscv32imac-unknown-none-elf",
+    "riscv32imac-esp-espidf",
+];
+
+/// Sysroot of the Android NDK named by `ANDROID_NDK_HOME` or
+/// `ANDROID_NDK_ROOT`
+fn android_sysroot() -> Result<PathBuf> {
+    let ndk = env::var_os("ANDROID_NDK_HOME")
+        .or_else(|| env::var_os("ANDROID_NDK_ROOT"))
+        .map(PathBuf::from)
+        .context("the Android NDK was not found: set ANDROID_NDK_HOME to its directory")?;
+
+    let prebuilt = ndk.join("toolchains").join("llvm").join("prebuilt");
+    let host_tag = ["linux-x86_64", "darwin-x86_64", "windows-x86_64"]
+        .into_iter()
+        .find(|tag| prebuilt.join(tag).join("sysroot").is_dir())
+        .with_context(|| {
+            format!("{} is not an Android NDK (no LLVM toolchain sysroot)", ndk.display())
+        })?;
+    Ok(prebuilt.join(host_tag).join("sysroot"))
+}
+
+/// Zig `--libc` file pointing at the NDK's headers and libraries
+fn android_libc_file(sysroot: &Path, ndk_triple: &str, api_level: &str) -> String {
+    let usr = sysroot.join("usr");
+    format!(
+        "include_dir={}\nsys_include_dir={}\ncrt_dir={}\nmsvc_lib_dir=\nkernel32_lib_dir=\\
+         ngcc_dir=\n",
+        usr.join("include").display(),
+        usr.join("include").join(ndk_triple).display(),
+        usr.join("lib").join(ndk_triple).join(api_level).display(),
+    )
+}
+
+/// Path of the Apple SDK `sdk`, from `SDKROOT` or `xcrun`
+fn apple_sysroot(sdk: &str) -> Result<PathBuf> {
+    if let Some(root) = env::var_os("SDKROOT") {
+        return Ok(PathBuf::from(root));
+    }
+
+    let output = Command::new("xcrun")
+        .args(["--sdk", sdk, "--show-sdk-path"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .with_context(|| {
+            format!("the {} SDK was not found: install Xcode or set SDKROOT to the SDK path", sdk)
+        })?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_mapping() {
+        let zig = |rust| ZigTarget::from_rust(rust, None).unwrap().triple;
+        assert_eq!(zig("x86_64-unknown-linux-gnu"), "x86_64-linux-gnu");
+        assert_eq!(zig("aarch64-apple-darwin"), "aarch64-macos");
+        assert_eq!(zig("x86_64-pc-windows-msvc"), "x86_64-windows");
+        assert_eq!(zig("wasm32-wasi"), "wasm32-wasi");
+        assert_eq!(zig("wasm32-wasip1"), "wasm32-wasi");
+        assert_eq!(zig("wasm32-wasip1-threads"), "wasm32-wasi");
+        assert_eq!(zig("i686-unknown-linux-gnu"), "x86-linux-gnu");
+        assert_eq!(zig("riscv64gc-unknown-linux-gnu"), "riscv64-linux-gnu");
+        assert_eq!(zig("aarch64-apple-ios-sim"), "aarch64-ios-simulator");
+        assert_eq!(zig("native"), "native");
+    }
+
+    #[test]
+    fn test_mobile_targets_need_sdk() {
+        let android = ZigTarget::from_rust("armv7-linux-androideabi", None).unwrap();
+        assert_eq!(android.triple, "arm-linux-androideabi");
+        assert_eq!(android.mcpu(), "generic+v7a+vfp3d16+thumb2");
+        assert_eq!(android.sdk, TargetSdk::AndroidNdk("arm-linux-androideabi"));
+
+        let ios = ZigTarget::from_rust("aarch64-apple-ios", None).unwrap();
+        assert_eq!(ios.sdk, TargetSdk::Apple("iphoneos"));
+        assert_eq!(ios.sdk_env_vars(), ["SDKROOT"]);
+    }
+
+    #[test]
+    fn test_bare_metal_targets() {
+        let m4f = ZigTarget::from_rust("thumbv7em-none-eabihf", None).unwrap();
+        assert_eq!(m4f.triple, "thumb-freestanding-eabihf");
+        assert_eq!(m4f.mcpu(), "cortex_m4");
+        assert!(m4f.is_bare_metal());
+        let m4 = ZigTarget::from_rust("thumbv7em-none-eabi", None).unwrap();
+        assert_eq!((m4.triple, m4.mcpu()), ("thumb-freestanding-eabi", "cortex_m4-vfp4d16sp"));
+
+        let esp32c6 = ZigTarget::from_rust("riscv32imac-unknown-none-elf", None).unwrap();
+        assert_eq!(esp32c6.triple, "riscv32-freestanding-none");
+        assert_eq!(esp32c6.mcpu(), "generic_rv32+m+a+c");
+        assert!(esp32c6.is_bare_metal());
+        assert!(!ZigTarget::from_rust("wasm32-unknown-unknown", None)
+            .unwrap()
+            .is_bare_metal());
+
+        let err = ZigTarget::from_rust("xtensa-esp32-none-elf", None).unwrap_err();
+        assert!(err.to_string().contains("Xtensa"));
+    }
+
+    #[test]
+    fn test_unsupported_target() {
+        let err = ZigTarget::from_rust("sparc64-unknown-netbsd", None).unwrap_err();
+        assert!(err.to_string().contains("`sparc64-unknown-netbsd`"));
+
+        // The host is always buildable
+        let host = ZigTarget::from_rust("sparc64-unknown-netbsd", Some("sparc64-unknown-netbsd"));
+        assert_eq!(host.unwrap(), ZigTarget::NATIVE);
+    }
+
+    #[test]
+    fn test_android_libc_file() {
+        let file = android_libc_file(Path::new("/ndk/sysroot"), "aarch64-linux-android", "24");
+        assert!(file.contains("sys_include_dir=/ndk/sysroot/usr/include/aarch64-linux-android\n"));
+        assert!(file.contains("crt_dir=/ndk/sysroot/usr/lib/aarch64-linux-android/24\n"));
+    }
+}
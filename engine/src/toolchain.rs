@@ -0,0 +1,232 @@
+//! Pinned Zig toolchain
+//!
+//! A crate can require an exact Zig version, either in its manifest or with
+//! `Builder::zig_version`:
+//!
+//! ```toml
+//! [package.metadata.autozig]
+//! zig-version = "0.15.2"
+//! ```
+//!
+//! The build then uses the `zig` on `PATH` (or `ZIG_PATH`) if it has that
+//! version, else a copy cached in `~/.cache/autozig/zig-<version>/`. With the
+//! `download` feature, a missing copy is fetched from ziglang.org and its
+//! checksum verified; without it the build fails and names the version to
+//! install.
+
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+
+use crate::zig_compiler::{
+    self,
+    ZigCompiler,
+};
+
+/// Make every [`ZigCompiler`] of this build use Zig `version`
+pub fn ensure(version: &str) -> Result<()> {
+    let installed = ZigCompiler::new().check_version().ok();
+    if installed.as_deref() == Some(version) {
+        return Ok(());
+    }
+
+    let dir = toolchain_dir(version)?;
+    let zig = dir.join(if cfg!(windows) { "zig.exe" } else { "zig" });
+    if !zig.is_file() {
+        #[cfg(feature = "download")]
+        download::fetch(version, &dir)?;
+        #[cfg(not(feature = "download"))]
+        bail!(
+            "Zig {version} is required, but {}: install it, point ZIG_PATH at it, or enable the \
+             `download` feature of autozig-build to fetch it into {}",
+            installed.map_or("no Zig was found".to_string(), |v| format!("Zig {v} is installed")),
+            dir.display()
+        );
+    }
+
+    let zig = zig.to_string_lossy().into_owned();
+    zig_compiler::use_zig(zig.clone());
+    let cached = ZigCompiler::new().check_version()?;
+    if cached != version {
+        bail!("{zig} reports version {cached}, expected {version}");
+    }
+    println!("cargo:warning=Using pinned Zig {version} from {zig}");
+    Ok(())
+}
+
+/// `zig-version` from `[package.metadata.autozig]` of the crate being built
+pub fn manifest_zig_version() -> Result<Option<String>> {
+    let Some(manifest_dir) = env::var_os("CARGO_MANIFEST_DIR") else {
+        return Ok(None);
+    };
+    let manifest = Path::new(&manifest_dir).join("Cargo.toml");
+    let text = fs::read_to_string(&manifest)
+        .with_context(|| format!("Failed to read {}", manifest.display()))?;
+    zig_version_from_manifest(&text).with_context(|| format!("Invalid {}", manifest.display()))
+}
+
+fn zig_version_from_manifest(text: &str) -> Result<Option<String>> {
+    let manifest: toml::Table = toml::from_str(text)?;
+    let Some(value) = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("autozig"))
+        .and_then(|autozig| autozig.get("zig-version"))
+    else {
+        return Ok(None);
+    };
+    match value.as_str() {
+        Some(version) => Ok(Some(version.to_string())),
+        None => bail!("package.metadata.autozig.zig-version must be a string"),
+    }
+}
+
+/// Where the toolchain for `version` is cached
+fn toolchain_dir(version: &str) -> Result<PathBuf> {
+    let base = if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        PathBuf::from(dir)
+    } else if let Some(home) = env::var_os("HOME") {
+        Path::new(&home).join(".cache")
+    } else {
+        bail!("Cannot locate a cache directory for the Zig toolchain: HOME is not set");
+    };
+    Ok(base.join("autozig").join(format!("zig-{version}")))
+}
+
+/// Key of the build host in ziglang.org's download index, e.g.
+/// `x86_64-linux`
+#[cfg_attr(not(feature = "download"), allow(dead_code))]
+fn host_key() -> Result<String> {
+    let os = match env::consts::OS {
+        "macos" => "macos",
+        "linux" => "linux",
+        "windows" => "windows",
+        "freebsd" => "freebsd",
+        os => bail!("No prebuilt Zig toolchain for {os}"),
+    };
+    Ok(format!("{}-{os}", env::consts::ARCH))
+}
+
+#[cfg(feature = "download")]
+mod download {
+    use std::{
+        fs,
+        path::Path,
+        process::Command,
+    };
+
+    use anyhow::{
+        bail,
+        Context,
+        Result,
+    };
+    use sha2::{
+        Digest,
+        Sha256,
+    };
+
+    const INDEX_URL: &str = "https://ziglang.org/download/index.json";
+
+    /// Download and unpack Zig `version` into `dir`
+    pub(super) fn fetch(version: &str, dir: &Path) -> Result<()> {
+        let host = super::host_key()?;
+        let parent = dir.parent().context("Invalid toolchain directory")?;
+        fs::create_dir_all(parent)?;
+
+        println!("cargo:warning=Downloading Zig {version} for {host}");
+        let index_path = parent.join("index.json");
+        curl(INDEX_URL, &index_path)?;
+        let index: serde_json::Value = serde_json::from_slice(&fs::read(&index_path)?)
+            .context("Invalid Zig download index")?;
+        let release = &index[version][&host];
+        let (Some(tarball), Some(shasum)) =
+            (release["tarball"].as_str(), release["shasum"].as_str())
+        else {
+            bail!("ziglang.org has no Zig {version} build for {host}");
+        };
+
+        let archive = parent.join(tarball.rsplit('/').next().unwrap_or("zig.tar.xz"));
+        curl(tarball, &archive)?;
+        let digest = format!("{:x}", Sha256::digest(fs::read(&archive)?));
+        if digest != shasum {
+            fs::remove_file(&archive)?;
+            bail!("Checksum mismatch for {tarball}: expected {shasum}, got {digest}");
+        }
+
+        // Unpack next to the destination, then move into place, so an
+        // interrupted download never leaves a partial toolchain behind
+        let staging = parent.join(format!(".zig-{version}-{}", std::process::id()));
+        fs::create_dir_all(&staging)?;
+        run(Command::new("tar")
+            .arg("-xf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&staging))?;
+        let unpacked = fs::read_dir(&staging)?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.path().is_dir())
+            .context("The Zig archive contains no directory")?;
+        if !dir.exists() {
+            fs::rename(unpacked.path(), dir)?;
+        }
+        fs::remove_dir_all(&staging)?;
+        fs::remove_file(&archive)?;
+        Ok(())
+    }
+
+    fn curl(url: &str, output: &Path) -> Result<()> {
+        run(Command::new("curl")
+            .args(["-sSfL", "-o"])
+            .arg(output)
+            .arg(url))
+    }
+
+    fn run(cmd: &mut Command) -> Result<()> {
+        let status = cmd
+            .status()
+            .with_context(|| format!("Failed to run {:?}", cmd.get_program()))?;
+        if !status.success() {
+            bail!("{cmd:?} failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zig_version_from_manifest() {
+        let manifest = r#"
+[package]
+name = "demo"
+
+[package.metadata.autozig]
+zig-version = "0.15.2"
+"#;
+        assert_eq!(zig_version_from_manifest(manifest).unwrap().as_deref(), Some("0.15.2"));
+        assert_eq!(zig_version_from_manifest("[package]\nname = \"demo\"").unwrap(), None);
+        assert!(zig_version_from_manifest("[package.metadata.autozig]\nzig-version = 15").is_err());
+    }
+
+    #[test]
+    fn test_host_key() {
+        if let Ok(key) = host_key() {
+            assert!(key.starts_with(env::consts::ARCH));
+        }
+    }
+}
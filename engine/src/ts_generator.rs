@@ -28,6 +28,7 @@ pub enum RustType {
 
 impl RustType {
     /// Parse from Rust type string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s.trim() {
             "u8" => RustType::U8,
@@ -92,6 +93,8 @@ pub struct FunctionSignature {
     pub return_type: RustType,
     /// Optional documentation comment
     pub doc: Option<String>,
+    /// Iterations per step of a `#[autozig(time_sliced = N)]` function
+    pub time_sliced: Option<u32>,
 }
 
 impl FunctionSignature {
@@ -102,8 +105,9 @@ impl FunctionSignature {
 
         // Remove leading attributes like #[autozig(...)]
         // Handle complex attributes with nested brackets/strings
-        let decl = Self::strip_attributes(decl);
-        let decl = decl.trim();
+        let stripped = Self::strip_attributes(decl);
+        let time_sliced = Self::parse_time_sliced(&decl[..decl.len() - stripped.len()]);
+        let decl = stripped.trim();
 
         // Must start with "fn " now
         let decl = decl.strip_prefix("fn ")?.trim();
@@ -135,7 +139,43 @@ impl FunctionSignature {
             RustType::Void
         };
 
-        Some(FunctionSignature { name, params, return_type, doc: None })
+        Some(FunctionSignature {
+            name,
+            params,
+            return_type,
+            doc: None,
+            time_sliced,
+        })
+    }
+
+    /// Symbols the Zig library exports for this function
+    ///
+    /// A time-sliced function is implemented as `_begin`, `_step` and
+    /// `_finish` exports rather than a single function.
+    pub fn export_names(&self) -> Vec<String> {
+        match self.time_sliced {
+            Some(_) => ["begin", "step", "finish"]
+                .iter()
+                .map(|suffix| format!("{}_{}", self.name, suffix))
+                .collect(),
+            None => vec![self.name.clone()],
+        }
+    }
+
+    /// Budget of a `time_sliced = N` argument in the attributes
+    fn parse_time_sliced(attrs: &str) -> Option<u32> {
+        let value = attrs
+            .split("time_sliced")
+            .nth(1)?
+            .trim_start()
+            .strip_prefix('=')?;
+        let digits: String = value
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '_')
+            .filter(|c| *c != '_')
+            .collect();
+        digits.parse().ok()
     }
 
     /// Strip all #[...] attributes from the beginning of a declaration
@@ -281,6 +321,16 @@ impl TsGenerator {
 
             let ret = func.return_type.to_typescript(self.config.is_wasm64);
 
+            if func.time_sliced.is_some() {
+                writeln!(
+                    output,
+                    "  /** Runs across animation frames to keep the page responsive */"
+                )
+                .unwrap();
+                writeln!(output, "  {}({}): Promise<{}>;", func.name, params, ret).unwrap();
+                continue;
+            }
+
             writeln!(output, "  {}({}): {};", func.name, params, ret).unwrap();
         }
 
@@ -306,6 +356,13 @@ impl TsGenerator {
         )
         .unwrap();
 
+        if self.functions.iter().any(|func| func.time_sliced.is_some()) {
+            writeln!(output).unwrap();
+            writeln!(output, "/** Milliseconds per frame spent in time-sliced Zig functions */")
+                .unwrap();
+            writeln!(output, "export let timeSliceMs: number;").unwrap();
+        }
+
         output
     }
 
@@ -349,6 +406,11 @@ impl TsGenerator {
         writeln!(output, "}}").unwrap();
         writeln!(output).unwrap();
 
+        if self.functions.iter().any(|func| func.time_sliced.is_some()) {
+            output.push_str(TIME_SLICED_JS);
+            writeln!(output).unwrap();
+        }
+
         // Wrapper function
         writeln!(output, "/**").unwrap();
         writeln!(output, " * Wrap raw WASM exports with type conversions").unwrap();
@@ -365,6 +427,38 @@ impl TsGenerator {
                 ""
             };
 
+            if let Some(budget) = func.time_sliced {
+                let params: Vec<_> = func.params.iter().map(|(n, _)| n.as_str()).collect();
+                let args: Vec<_> = func
+                    .params
+                    .iter()
+                    .map(|(name, ty)| {
+                        if ty.needs_bigint(self.config.is_wasm64) {
+                            format!("BigInt({})", name)
+                        } else {
+                            name.clone()
+                        }
+                    })
+                    .collect();
+                let convert = match func.return_type {
+                    RustType::Bool => ".then((result) => !!result)",
+                    _ => "",
+                };
+                writeln!(
+                    output,
+                    "    {}: ({}) => runTimeSliced(raw, \"{}\", [{}], {}){}{}",
+                    func.name,
+                    params.join(", "),
+                    func.name,
+                    args.join(", "),
+                    budget,
+                    convert,
+                    trailing_comma
+                )
+                .unwrap();
+                continue;
+            }
+
             // Check if any params need BigInt conversion
             let needs_param_conversion = func
                 .params
@@ -420,6 +514,38 @@ impl TsGenerator {
     }
 }
 
+/// Scheduler for time-sliced functions: steps the Zig loop from
+/// `requestAnimationFrame`, spending at most a few milliseconds per frame
+const TIME_SLICED_JS: &str = r#"/** Milliseconds per frame spent in time-sliced Zig functions */
+export let timeSliceMs = 8;
+
+/**
+ * Run a time-sliced Zig function (`_begin`/`_step`/`_finish` exports),
+ * yielding to the browser between animation frames
+ * @returns {Promise<any>} Resolves with the result of `_finish`
+ */
+function runTimeSliced(raw, name, args, budget) {
+  return new Promise((resolve, reject) => {
+    const state = raw[name + "_begin"](...args);
+    if (!state) {
+      reject(new Error(name + ": Zig could not allocate its state"));
+      return;
+    }
+    const frame = () => {
+      const start = performance.now();
+      do {
+        if (raw[name + "_step"](state, budget)) {
+          resolve(raw[name + "_finish"](state));
+          return;
+        }
+      } while (performance.now() - start < timeSliceMs);
+      requestAnimationFrame(frame);
+    };
+    requestAnimationFrame(frame);
+  });
+}
+"#;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -451,6 +577,25 @@ mod tests {
         assert_eq!(sig.return_type, RustType::U32);
     }
 
+    #[test]
+    fn test_time_sliced_bindings() {
+        let sig =
+            FunctionSignature::parse("#[autozig(time_sliced = 10_000)] fn slow_sum(n: u64) -> u64")
+                .unwrap();
+        assert_eq!(sig.time_sliced, Some(10_000));
+        assert_eq!(sig.export_names(), ["slow_sum_begin", "slow_sum_step", "slow_sum_finish"]);
+
+        let generator = TsGenerator::new(vec![sig], TsConfig::default());
+        assert!(generator
+            .generate_dts()
+            .contains("slow_sum(n: bigint): Promise<bigint>;"));
+        let js = generator.generate_js_loader();
+        assert!(js.contains("function runTimeSliced("));
+        assert!(
+            js.contains("slow_sum: (n) => runTimeSliced(raw, \"slow_sum\", [BigInt(n)], 10000)")
+        );
+    }
+
     #[test]
     fn test_type_to_typescript() {
         assert_eq!(RustType::U32.to_typescript(false), "number");
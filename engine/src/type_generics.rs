@@ -0,0 +1,212 @@
+//! Zig specializations for `#[monomorphize(...)]` over types
+//!
+//! A signature `fn convert<Src, Dst>(input: &[Src], output: &mut [Dst])`
+//! with `#[monomorphize((u8, f32), (i16, f32))]` binds one export per
+//! instance, named after its types: `convert_u8_f32` and `convert_i16_f32`.
+//! Exports the Zig code does not define are generated from a generic Zig
+//! function taking a `comptime` type parameter named like each Rust one:
+//!
+//! ```zig
+//! fn convert(comptime Src: type, comptime Dst: type, input_ptr: [*]const Src,
+//!     input_len: usize, output_ptr: [*]Dst, output_len: usize) void { ... }
+//!
+//! // generated
+//! export fn convert_u8_f32(input_ptr: [*]const u8, input_len: usize,
+//!     output_ptr: [*]f32, output_len: usize) void {
+//!     return convert(u8, f32, input_ptr, input_len, output_ptr, output_len);
+//! }
+//! ```
+//!
+//! A generic `#[repr(C)] struct Matrix<T>` with `#[monomorphize(f32)]`
+//! mirrors a Zig `fn Matrix(comptime T: type) type` returning an
+//! `extern struct`; each instance gets an alias `pub const Matrix_f32 =
+//! Matrix(f32);`, the name its layout is checked under.
+
+use autozig_parser::{
+    mangle_type,
+    type_mapper::{
+        zig_exports,
+        zig_function,
+    },
+};
+
+/// Exports specializing the Zig function `name` over its comptime type
+/// parameters `params` for each of `instances` (one Rust type per
+/// parameter), skipping the exports `zig_code` already defines
+///
+/// A missing function or parameter becomes a `@compileError`, so the Zig
+/// build reports it.
+pub fn specialize(
+    zig_code: &str,
+    name: &str,
+    params: &[String],
+    instances: &[Vec<String>],
+) -> String {
+    let defined: Vec<String> = zig_exports(zig_code)
+        .into_iter()
+        .map(|export| export.name)
+        .collect();
+    let missing: Vec<&Vec<String>> = instances
+        .iter()
+        .filter(|instance| !defined.contains(&export_name(name, instance)))
+        .collect();
+    if missing.is_empty() {
+        return String::new();
+    }
+
+    let comptime_params = params.join(", ");
+    let Some(function) = zig_function(zig_code, name) else {
+        return compile_error(&format!(
+            "#[monomorphize] on `{name}` needs `export fn {}` or a Zig `fn {name}(comptime \
+             {comptime_params}: type, ..)`",
+            export_name(name, missing[0])
+        ));
+    };
+    if let Some(param) = params
+        .iter()
+        .find(|param| !function.params.iter().any(|(p, _)| p == *param))
+    {
+        return compile_error(&format!(
+            "Zig `fn {name}` has no comptime parameter `{param}` for #[monomorphize]"
+        ));
+    }
+
+    let mut exports = String::from("\n// Specializations for #[monomorphize]\n");
+    for instance in missing {
+        let bindings: Vec<(&str, String)> = params
+            .iter()
+            .map(String::as_str)
+            .zip(instance.iter().map(|ty| zig_type(ty)))
+            .collect();
+        let mut export_params = Vec::new();
+        let mut args = Vec::new();
+        for (param, ty) in &function.params {
+            match bindings.iter().find(|(name, _)| name == param) {
+                Some((_, zig_ty)) => args.push(zig_ty.clone()),
+                None => {
+                    export_params.push(format!("{param}: {}", substitute(ty, &bindings)));
+                    args.push(param.clone());
+                },
+            }
+        }
+        exports.push_str(&format!(
+            "export fn {}({}) {} {{\n    return {name}({});\n}}\n",
+            export_name(name, instance),
+            export_params.join(", "),
+            substitute(&function.return_type, &bindings),
+            args.join(", ")
+        ));
+    }
+    exports
+}
+
+/// `pub const Matrix_f32 = Matrix(f32);` for each instance of the Zig type
+/// function `name`, unless `zig_code` declares the alias or lacks `fn name`
+pub fn instance_aliases(zig_code: &str, name: &str, types: &[String]) -> String {
+    if zig_function(zig_code, name).is_none() {
+        return String::new();
+    }
+    let mut aliases = String::new();
+    for ty in types {
+        let alias = format!("{name}_{}", mangle_type(ty));
+        if !zig_code.contains(&format!("const {alias} "))
+            && !zig_code.contains(&format!("const {alias}="))
+        {
+            aliases.push_str(&format!("pub const {alias} = {name}({});\n", zig_type(ty)));
+        }
+    }
+    if aliases.is_empty() {
+        return aliases;
+    }
+    format!("\n// Instances for #[monomorphize]\n{aliases}")
+}
+
+fn export_name(name: &str, instance: &[String]) -> String {
+    let mut export = name.to_string();
+    for ty in instance {
+        export.push('_');
+        export.push_str(&mangle_type(ty));
+    }
+    export
+}
+
+/// Zig spelling of a Rust type: primitives and declared types keep their
+/// name, paths their last segment, and `Matrix<f32>` becomes `Matrix(f32)`
+fn zig_type(ty: &str) -> String {
+    let Ok(syn::Type::Path(path)) = syn::parse_str::<syn::Type>(ty) else {
+        return mangle_type(ty);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return mangle_type(ty);
+    };
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => {
+            let args: Vec<String> = args
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => zig_type(&quote::quote!(#ty).to_string()),
+                    arg => quote::quote!(#arg).to_string(),
+                })
+                .collect();
+            format!("{}({})", segment.ident, args.join(", "))
+        },
+        _ => segment.ident.to_string(),
+    }
+}
+
+/// `zig_ty` with the identifiers bound in `bindings` replaced
+fn substitute(zig_ty: &str, bindings: &[(&str, String)]) -> String {
+    let mut substituted = String::new();
+    let mut ident = String::new();
+    let flush = |ident: &mut String, substituted: &mut String| {
+        match bindings.iter().find(|(name, _)| name == ident) {
+            Some((_, ty)) => substituted.push_str(ty),
+            None => substituted.push_str(ident),
+        }
+        ident.clear();
+    };
+    for c in zig_ty.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else {
+            flush(&mut ident, &mut substituted);
+            substituted.push(c);
+        }
+    }
+    flush(&mut ident, &mut substituted);
+    substituted
+}
+
+fn compile_error(message: &str) -> String {
+    format!("\ncomptime {{\n    @compileError(\"{message}\");\n}}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specialize_types() {
+        let code = "pub fn Matrix(comptime T: type) type {\n    return extern struct { data: [4]T \
+                    };\n}\nfn convert(comptime Src: type, comptime Dst: type, in_ptr: [*]const \
+                    Src, out: *Matrix(Dst)) void {}\nexport fn convert_u8_f32(in_ptr: [*]const \
+                    u8, out: *Matrix(f32)) void {}";
+        let params = ["Src".to_string(), "Dst".to_string()];
+        let instances = [
+            vec!["u8".to_string(), "f32".to_string()],
+            vec!["i16".to_string(), "Matrix < f64 >".to_string()],
+        ];
+        assert_eq!(
+            specialize(code, "convert", &params, &instances),
+            "\n// Specializations for #[monomorphize]\nexport fn convert_i16_Matrix_f64(in_ptr: \
+             [*]const i16, out: *Matrix(Matrix(f64))) void {\n    return convert(i16, \
+             Matrix(f64), in_ptr, out);\n}\n"
+        );
+        assert!(specialize(code, "scale", &params, &instances).contains("@compileError"));
+        assert_eq!(
+            instance_aliases(code, "Matrix", &["f32".to_string()]),
+            "\n// Instances for #[monomorphize]\npub const Matrix_f32 = Matrix(f32);\n"
+        );
+    }
+}
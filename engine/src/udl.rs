@@ -0,0 +1,779 @@
+//! UniFFI interface definition and glue for Kotlin and Swift
+//!
+//! With `BuildOptions::udl`, the crate's `autozig!`, `include_zig!` and
+//! `bind_zig!` items are described in a UniFFI UDL file, and
+//! `OUT_DIR/autozig_uniffi.rs` holds the Rust side UniFFI expects. The crate
+//! then only needs `uniffi::generate_scaffolding` in its build script, after
+//! the autozig build, and the glue included at its root:
+//!
+//! ```rust,ignore
+//! include!(concat!(env!("OUT_DIR"), "/autozig_uniffi.rs"));
+//! ```
+//!
+//! - wrappers become namespace functions, prefixed with their block's
+//!   namespace; `&[u8]` and `Vec<u8>` are `bytes`, other slices and vectors
+//!   `sequence<T>`, `&str` and `String` `string`, and `usize`/`isize` cross as
+//!   `u64`/`i64`;
+//! - structs with public scalar fields become dictionaries, and fieldless enums
+//!   enums;
+//! - opaque types declared `send` and having a constructor become interfaces.
+//!   UniFFI shares objects between threads, so the glue keeps them behind a
+//!   mutex; the foreign object releasing the last reference runs the Zig
+//!   destructor.
+//!
+//! The glue calls the wrappers by their module path, so their modules must
+//! be reachable from the crate root. Items the UDL cannot describe are listed
+//! in a comment and not exported.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+    RustEnumDefinition,
+    RustFunctionSignature,
+    RustStructDefinition,
+    RustTraitImpl,
+};
+use syn::visit::Visit;
+use walkdir::WalkDir;
+
+/// Scalar types with a UDL equivalent, as (Rust, UDL) names
+const SCALARS: &[(&str, &str)] = &[
+    ("bool", "boolean"),
+    ("u8", "u8"),
+    ("u16", "u16"),
+    ("u32", "u32"),
+    ("u64", "u64"),
+    ("usize", "u64"),
+    ("i8", "i8"),
+    ("i16", "i16"),
+    ("i32", "i32"),
+    ("i64", "i64"),
+    ("isize", "i64"),
+    ("f32", "float"),
+    ("f64", "double"),
+];
+
+/// UDL file and Rust glue of a crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uniffi {
+    /// Contents of the UDL file
+    pub udl: String,
+    /// Contents of `autozig_uniffi.rs`
+    pub glue: String,
+}
+
+/// UDL and glue for the items in the `.rs` files under `src_dir`
+///
+/// `namespace` is the UDL namespace (the library's crate name) and
+/// `udl_name` the file name of the UDL without extension, as passed to
+/// `uniffi::include_scaffolding!`.
+pub fn uniffi_for_dir(src_dir: &Path, namespace: &str, udl_name: &str) -> Result<Uniffi> {
+    let mut collector = Collector::default();
+    for entry in WalkDir::new(src_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        let Some(module) = module_path(src_dir, path) else {
+            continue;
+        };
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if let Ok(file) = syn::parse_file(&content) {
+            collector.module = module;
+            collector.visit_file(&file);
+        }
+    }
+    Ok(collector.interface.render(namespace, udl_name))
+}
+
+/// Module path of a source file of the library: `[]` for `lib.rs`, `[a, b]`
+/// for `a/b.rs` or `a/b/mod.rs`; `None` for binaries
+fn module_path(src_dir: &Path, file: &Path) -> Option<Vec<String>> {
+    let relative = file.strip_prefix(src_dir).ok()?.with_extension("");
+    let mut module: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    match module.first().map(String::as_str) {
+        Some("main" | "bin" | "build") => return None,
+        Some("lib") if module.len() == 1 => module.clear(),
+        _ => {},
+    }
+    if module.last().is_some_and(|last| last == "mod") {
+        module.pop();
+    }
+    Some(module)
+}
+
+fn scalar(ty: &syn::Type) -> Option<String> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = path.path.get_ident()?.to_string();
+    SCALARS
+        .iter()
+        .any(|(rust, _)| *rust == ident)
+        .then_some(ident)
+}
+
+fn udl_scalar(rust: &str) -> &'static str {
+    SCALARS
+        .iter()
+        .find(|(name, _)| *name == rust)
+        .map_or("u8", |(_, udl)| udl)
+}
+
+/// Scalars UniFFI converts as they are (not `usize`/`isize`)
+fn is_native_scalar(rust: &str) -> bool {
+    rust != "usize" && rust != "isize"
+}
+
+/// Type of a parameter, result or field crossing to the foreign language
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UdlType {
+    Scalar(String),
+    String,
+    Bytes,
+    Sequence(String),
+    Record(String),
+    Enum(String),
+    Object { name: String, mutable: bool },
+}
+
+impl UdlType {
+    fn udl(&self) -> String {
+        match self {
+            UdlType::Scalar(rust) => udl_scalar(rust).to_string(),
+            UdlType::String => "string".to_string(),
+            UdlType::Bytes => "bytes".to_string(),
+            UdlType::Sequence(elem) => format!("sequence<{}>", udl_scalar(elem)),
+            UdlType::Record(name) | UdlType::Enum(name) | UdlType::Object { name, .. } => {
+                name.clone()
+            },
+        }
+    }
+
+    /// Type of the glue's parameter and the argument it passes on
+    fn glue_param(&self, name: &str) -> (String, String) {
+        match self {
+            UdlType::Scalar(rust) if is_native_scalar(rust) => (rust.clone(), name.to_string()),
+            UdlType::Scalar(rust) => (udl_scalar(rust).to_string(), format!("{name} as {rust}")),
+            UdlType::String => ("String".to_string(), format!("&{name}")),
+            UdlType::Bytes => ("Vec<u8>".to_string(), format!("&{name}")),
+            UdlType::Sequence(elem) => (format!("Vec<{elem}>"), format!("&{name}")),
+            UdlType::Record(ty) | UdlType::Enum(ty) => (ty.clone(), name.to_string()),
+            UdlType::Object { name: ty, mutable } => (
+                format!("std::sync::Arc<{ty}>"),
+                format!("&{}*{name}.0.lock().unwrap()", if *mutable { "mut " } else { "" }),
+            ),
+        }
+    }
+
+    /// Return type of the glue and the conversion of the wrapper's result
+    fn glue_result(&self, call: &str) -> (String, String) {
+        match self {
+            UdlType::Scalar(rust) if is_native_scalar(rust) => (rust.clone(), call.to_string()),
+            UdlType::Scalar(rust) => {
+                let udl = udl_scalar(rust);
+                (udl.to_string(), format!("{call} as {udl}"))
+            },
+            UdlType::String => ("String".to_string(), call.to_string()),
+            UdlType::Bytes => ("Vec<u8>".to_string(), call.to_string()),
+            UdlType::Sequence(elem) => (format!("Vec<{elem}>"), call.to_string()),
+            UdlType::Record(ty) | UdlType::Enum(ty) => (ty.clone(), call.to_string()),
+            UdlType::Object { name, .. } => (
+                format!("std::sync::Arc<{name}>"),
+                format!("std::sync::Arc::new({name}(std::sync::Mutex::new({call})))"),
+            ),
+        }
+    }
+}
+
+/// Records, enums and objects of one macro invocation
+#[derive(Default)]
+struct Scope {
+    records: Vec<String>,
+    enums: Vec<String>,
+    objects: Vec<String>,
+}
+
+impl Scope {
+    fn param(&self, ty: &syn::Type) -> Option<UdlType> {
+        if let syn::Type::Reference(reference) = ty {
+            let mutable = reference.mutability.is_some();
+            return match &*reference.elem {
+                syn::Type::Slice(slice) if !mutable => match scalar(&slice.elem)?.as_str() {
+                    "u8" => Some(UdlType::Bytes),
+                    elem if is_native_scalar(elem) => Some(UdlType::Sequence(elem.to_string())),
+                    _ => None,
+                },
+                syn::Type::Path(path) if !mutable && path.path.is_ident("str") => {
+                    Some(UdlType::String)
+                },
+                syn::Type::Path(path) => {
+                    let name = path.path.get_ident()?.to_string();
+                    self.objects
+                        .contains(&name)
+                        .then_some(UdlType::Object { name, mutable })
+                },
+                _ => None,
+            };
+        }
+        self.value(ty)
+    }
+
+    fn result(&self, ty: &syn::Type, self_type: Option<&str>) -> Option<UdlType> {
+        let syn::Type::Path(path) = ty else {
+            return None;
+        };
+        let segment = path.path.segments.last()?;
+        if segment.ident == "Vec" {
+            let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return None;
+            };
+            let Some(syn::GenericArgument::Type(elem)) = args.args.first() else {
+                return None;
+            };
+            return match scalar(elem)?.as_str() {
+                "u8" => Some(UdlType::Bytes),
+                elem if is_native_scalar(elem) => Some(UdlType::Sequence(elem.to_string())),
+                _ => None,
+            };
+        }
+        let name = path.path.get_ident()?.to_string();
+        if name == "String" {
+            return Some(UdlType::String);
+        }
+        let object = match (name.as_str(), self_type) {
+            ("Self", Some(self_type)) => Some(self_type.to_string()),
+            _ => self.objects.contains(&name).then_some(name),
+        };
+        match object {
+            Some(name) => Some(UdlType::Object { name, mutable: false }),
+            None => self.value(ty),
+        }
+    }
+
+    /// Scalars, records and enums passed by value
+    fn value(&self, ty: &syn::Type) -> Option<UdlType> {
+        if let Some(rust) = scalar(ty) {
+            return Some(UdlType::Scalar(rust));
+        }
+        let syn::Type::Path(path) = ty else {
+            return None;
+        };
+        let name = path.path.get_ident()?.to_string();
+        if self.records.contains(&name) {
+            Some(UdlType::Record(name))
+        } else if self.enums.contains(&name) {
+            Some(UdlType::Enum(name))
+        } else {
+            None
+        }
+    }
+}
+
+/// A function or method call exported through the glue
+struct Function {
+    /// UDL name
+    name: String,
+    /// Path the glue calls
+    target: String,
+    /// Parameter names and types, without the receiver
+    params: Vec<(String, UdlType)>,
+    /// Locking receiver: `Some(true)` for `&mut self`
+    receiver: Option<bool>,
+    result: Option<UdlType>,
+}
+
+impl Function {
+    fn from_signature(
+        sig: &syn::Signature,
+        scope: &Scope,
+        self_type: Option<&str>,
+    ) -> Option<(Vec<(String, UdlType)>, Option<bool>, Option<UdlType>)> {
+        if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+            return None;
+        }
+        let mut params = Vec::new();
+        let mut receiver = None;
+        for input in &sig.inputs {
+            match input {
+                syn::FnArg::Receiver(recv) if recv.reference.is_some() => {
+                    receiver = Some(recv.mutability.is_some());
+                },
+                syn::FnArg::Receiver(_) => return None,
+                syn::FnArg::Typed(arg) => {
+                    let syn::Pat::Ident(pat) = &*arg.pat else {
+                        return None;
+                    };
+                    params.push((pat.ident.to_string(), scope.param(&arg.ty)?));
+                },
+            }
+        }
+        let result = match &sig.output {
+            syn::ReturnType::Default => None,
+            syn::ReturnType::Type(_, ty) => Some(scope.result(ty, self_type)?),
+        };
+        Some((params, receiver, result))
+    }
+
+    fn declaration(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{} {name}", ty.udl()))
+            .collect();
+        let result = self
+            .result
+            .as_ref()
+            .map_or("void".to_string(), UdlType::udl);
+        format!("{result} {}({});", self.name, params.join(", "))
+    }
+
+    /// Glue function or method, indented by `indent`
+    fn glue(&self, indent: &str) -> String {
+        let mut inputs = Vec::new();
+        let mut args = Vec::new();
+        if let Some(mutable) = self.receiver {
+            inputs.push("&self".to_string());
+            args.push(format!("&{}*self.0.lock().unwrap()", if mutable { "mut " } else { "" }));
+        }
+        for (name, ty) in &self.params {
+            let (input, arg) = ty.glue_param(name);
+            inputs.push(format!("{name}: {input}"));
+            args.push(arg);
+        }
+        let call = format!("{}({})", self.target, args.join(", "));
+        let (ret, body) = match &self.result {
+            Some(ty) => {
+                let (ret, body) = ty.glue_result(&call);
+                (format!(" -> {ret}"), body)
+            },
+            None => (String::new(), format!("{call};")),
+        };
+        format!(
+            "{indent}pub fn {}({}){ret} {{\n{indent}    {body}\n{indent}}}\n",
+            self.name,
+            inputs.join(", ")
+        )
+    }
+}
+
+/// An opaque type exported as an interface
+struct Object {
+    name: String,
+    /// Path of the opaque type
+    path: String,
+    /// Name and parameters of the constructor
+    constructor: (String, Vec<(String, UdlType)>),
+    methods: Vec<Function>,
+}
+
+/// Everything the UDL describes
+#[derive(Default)]
+struct Interface {
+    functions: Vec<Function>,
+    /// Dictionary name, path and fields
+    records: Vec<(String, String, Vec<(String, String)>)>,
+    /// Enum name, path and variants
+    enums: Vec<(String, String, Vec<String>)>,
+    objects: Vec<Object>,
+    skipped: Vec<String>,
+}
+
+impl Interface {
+    fn add_block(
+        &mut self,
+        module: &[String],
+        namespace: Option<&str>,
+        signatures: &[RustFunctionSignature],
+        structs: &[RustStructDefinition],
+        enums: &[RustEnumDefinition],
+        trait_impls: &[RustTraitImpl],
+    ) {
+        let item_path = |name: &str| {
+            let mut path = vec!["crate".to_string()];
+            path.extend(module.iter().cloned());
+            path.push(name.to_string());
+            path.join("::")
+        };
+        let mut scope = Scope::default();
+
+        for definition in enums {
+            let item = &definition.item;
+            let name = item.ident.to_string();
+            if !item.generics.params.is_empty()
+                || !item
+                    .variants
+                    .iter()
+                    .all(|v| matches!(v.fields, syn::Fields::Unit))
+            {
+                self.skipped.push(name);
+                continue;
+            }
+            let variants = item.variants.iter().map(|v| v.ident.to_string()).collect();
+            self.enums.push((name.clone(), item_path(&name), variants));
+            scope.enums.push(name);
+        }
+
+        for definition in structs {
+            let item = &definition.item;
+            let name = item.ident.to_string();
+            let mut fields = Vec::new();
+            let supported = item.generics.params.is_empty()
+                && definition.monomorphize_types.is_empty()
+                && matches!(item.fields, syn::Fields::Named(_))
+                && item.fields.iter().all(|field| {
+                    let ty = scope.value(&field.ty).filter(|ty| match ty {
+                        UdlType::Scalar(rust) => is_native_scalar(rust),
+                        _ => true,
+                    });
+                    match (ty, &field.ident, &field.vis) {
+                        (Some(ty), Some(ident), syn::Visibility::Public(_)) => {
+                            fields.push((ident.to_string(), ty.udl()));
+                            true
+                        },
+                        _ => false,
+                    }
+                });
+            if !supported {
+                self.skipped.push(name);
+                continue;
+            }
+            self.records.push((name.clone(), item_path(&name), fields));
+            scope.records.push(name);
+        }
+
+        // Objects need `Send` to live behind the glue's mutex
+        let mut candidates: Vec<&str> = Vec::new();
+        for trait_impl in trait_impls.iter().filter(|t| t.is_opaque) {
+            if !candidates.contains(&trait_impl.target_type.as_str()) {
+                candidates.push(&trait_impl.target_type);
+            }
+        }
+        for candidate in &candidates {
+            let impls = || trait_impls.iter().filter(|t| t.target_type == *candidate);
+            if impls().any(|t| t.send) && impls().any(|t| t.constructor.is_some()) {
+                scope.objects.push(candidate.to_string());
+            } else {
+                self.skipped.push(candidate.to_string());
+            }
+        }
+        for name in scope.objects.clone() {
+            let path = item_path(&name);
+            let mut constructor = None;
+            let mut methods = Vec::new();
+            for trait_impl in trait_impls.iter().filter(|t| t.target_type == name) {
+                if let Some(ctor) = &trait_impl.constructor {
+                    match Function::from_signature(&ctor.sig, &scope, Some(&name)) {
+                        Some((params, None, _)) => constructor = Some((ctor.name.clone(), params)),
+                        _ => self.skipped.push(format!("{name}::{}", ctor.name)),
+                    }
+                }
+                let target = match &trait_impl.trait_path {
+                    Some(trait_path) => match trait_string(trait_path, module) {
+                        Some(trait_path) => format!("<{path} as {trait_path}>"),
+                        None => {
+                            self.skipped.extend(
+                                trait_impl
+                                    .methods
+                                    .iter()
+                                    .map(|m| format!("{name}::{}", m.name)),
+                            );
+                            continue;
+                        },
+                    },
+                    None => path.clone(),
+                };
+                for method in &trait_impl.methods {
+                    match Function::from_signature(&method.sig, &scope, Some(&name)) {
+                        Some((params, receiver @ Some(_), result)) => methods.push(Function {
+                            name: method.name.clone(),
+                            target: format!("{target}::{}", method.name),
+                            params,
+                            receiver,
+                            result,
+                        }),
+                        _ => self.skipped.push(format!("{name}::{}", method.name)),
+                    }
+                }
+            }
+            match constructor {
+                Some(constructor) => self
+                    .objects
+                    .push(Object { name, path, constructor, methods }),
+                None => self.skipped.push(name),
+            }
+        }
+
+        for signature in signatures {
+            let sig = &signature.sig;
+            let rust_name = sig.ident.to_string();
+            let exported = signature.cfg_attrs.is_empty()
+                && !signature.is_async
+                && signature.binding_config.strategy.is_none();
+            match Function::from_signature(sig, &scope, None).filter(|_| exported) {
+                Some((params, None, result)) => self.functions.push(Function {
+                    name: match namespace {
+                        Some(namespace) => format!("{namespace}_{rust_name}"),
+                        None => rust_name.clone(),
+                    },
+                    target: item_path(&rust_name),
+                    params,
+                    receiver: None,
+                    result,
+                }),
+                _ => self.skipped.push(rust_name),
+            }
+        }
+    }
+
+    fn render(&self, namespace: &str, udl_name: &str) -> Uniffi {
+        const HEADER: &str = "// Generated by autozig from the crate's signatures. Do not edit.\n";
+
+        // weedle rejects comments after the last definition
+        let mut udl = HEADER.to_string();
+        if !self.skipped.is_empty() {
+            udl.push_str(&format!(
+                "// Not exported (no UniFFI equivalent for some types): {}\n",
+                self.skipped.join(", ")
+            ));
+        }
+        udl.push_str(&format!("namespace {namespace} {{\n"));
+        for function in &self.functions {
+            udl.push_str(&format!("  {}\n", function.declaration()));
+        }
+        udl.push_str("};\n");
+        for (name, _, fields) in &self.records {
+            udl.push_str(&format!("\ndictionary {name} {{\n"));
+            for (field, ty) in fields {
+                udl.push_str(&format!("  {ty} {field};\n"));
+            }
+            udl.push_str("};\n");
+        }
+        for (name, _, variants) in &self.enums {
+            let variants: Vec<String> = variants.iter().map(|v| format!("  \"{v}\"")).collect();
+            udl.push_str(&format!("\nenum {name} {{\n{}\n}};\n", variants.join(",\n")));
+        }
+        for object in &self.objects {
+            let (ctor_name, params) = &object.constructor;
+            let params: Vec<String> = params
+                .iter()
+                .map(|(name, ty)| format!("{} {name}", ty.udl()))
+                .collect();
+            let rename = if ctor_name == "new" {
+                String::new()
+            } else {
+                format!("[Name={ctor_name}] ")
+            };
+            udl.push_str(&format!(
+                "\ninterface {} {{\n  {rename}constructor({});\n",
+                object.name,
+                params.join(", ")
+            ));
+            for method in &object.methods {
+                udl.push_str(&format!("  {}\n", method.declaration()));
+            }
+            udl.push_str("};\n");
+        }
+
+        let mut glue = format!(
+            "{HEADER}// Include it at the crate root with\n// include!(concat!(env!(\"OUT_DIR\"), \
+             \"/autozig_uniffi.rs\"));\n\nmod autozig_uniffi {{\n"
+        );
+        let named = self.records.iter().map(|(_, path, _)| path);
+        for path in named.chain(self.enums.iter().map(|(_, path, _)| path)) {
+            glue.push_str(&format!("    use {path};\n"));
+        }
+        for function in &self.functions {
+            glue.push('\n');
+            glue.push_str(&function.glue("    "));
+        }
+        for object in &self.objects {
+            let (ctor_name, params) = &object.constructor;
+            let mut inputs = Vec::new();
+            let mut args = Vec::new();
+            for (name, ty) in params {
+                let (input, arg) = ty.glue_param(name);
+                inputs.push(format!("{name}: {input}"));
+                args.push(arg);
+            }
+            glue.push_str(&format!(
+                "\n    pub struct {name}(std::sync::Mutex<{path}>);\n\n    impl {name} {{\n        \
+                 pub fn {ctor_name}({inputs}) -> Self {{\n            \
+                 Self(std::sync::Mutex::new({path}::{ctor_name}({args})))\n        }}\n",
+                name = object.name,
+                path = object.path,
+                inputs = inputs.join(", "),
+                args = args.join(", "),
+            ));
+            for method in &object.methods {
+                glue.push('\n');
+                glue.push_str(&method.glue("        "));
+            }
+            glue.push_str("    }\n");
+        }
+        glue.push_str(&format!(
+            "\n    ::uniffi::include_scaffolding!(\"{udl_name}\");\n}}\n\nuse \
+             autozig_uniffi::UniFfiTag;\n"
+        ));
+        Uniffi { udl, glue }
+    }
+}
+
+/// Trait path as seen from the crate root; single-segment paths are taken
+/// to be declared in the block's module. `None` for generic traits.
+fn trait_string(path: &syn::Path, module: &[String]) -> Option<String> {
+    if path
+        .segments
+        .iter()
+        .any(|segment| !segment.arguments.is_none())
+    {
+        return None;
+    }
+    let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    let mut full = Vec::new();
+    if path.leading_colon.is_some() {
+        full.push(String::new());
+    } else if segments.len() == 1 {
+        full.push("crate".to_string());
+        full.extend(module.iter().cloned());
+    }
+    full.extend(segments);
+    Some(full.join("::"))
+}
+
+#[derive(Default)]
+struct Collector {
+    /// Module of the items being visited
+    module: Vec<String>,
+    interface: Interface,
+}
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        self.module.push(node.ident.to_string());
+        syn::visit::visit_item_mod(self, node);
+        self.module.pop();
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let mut module = self.module.clone();
+        if node.path.is_ident("autozig") {
+            let Ok(config) = syn::parse2::<AutoZigConfig>(node.tokens.clone()) else {
+                return;
+            };
+            let namespace = config.namespace.as_ref().map(ToString::to_string);
+            module.extend(namespace.clone());
+            self.interface.add_block(
+                &module,
+                namespace.as_deref(),
+                &config.rust_signatures,
+                &config.rust_structs,
+                &config.rust_enums,
+                &config.rust_trait_impls,
+            );
+        } else if node.path.is_ident("include_zig") || node.path.is_ident("bind_zig") {
+            let Ok(config) = syn::parse2::<IncludeZigConfig>(node.tokens.clone()) else {
+                return;
+            };
+            self.interface.add_block(
+                &module,
+                None,
+                &config.rust_signatures,
+                &config.rust_structs,
+                &config.rust_enums,
+                &config.rust_trait_impls,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniffi_interface() {
+        let file = syn::parse_file(
+            r#"
+            autozig! {
+                export fn blur(p: [*]const u8, n: usize, w: u32) void {}
+                ---
+                #[repr(C)]
+                pub struct Point { pub x: f32, pub y: f64 }
+                #[repr(u32)]
+                pub enum Mode { Fast = 0, Slow = 1 }
+                fn blur(px: &[u8], width: u32) -> Vec<u8>;
+                fn shift(p: Point, mode: Mode) -> Point;
+                fn fill(out: &mut [f32]);
+
+                struct ZigHasher(opaque, send);
+                impl ZigHasher {
+                    #[constructor]
+                    fn new(seed: u64) -> Self { hasher_new() }
+                    #[destructor]
+                    fn drop(&mut self) { hasher_free() }
+                }
+                impl std::hash::Hasher for ZigHasher {
+                    fn write(&mut self, bytes: &[u8]) { hasher_write() }
+                    fn finish(&self) -> u64 { hasher_finish() }
+                }
+            }
+            pub mod text {
+                autozig!(mod = utf, {
+                    export fn count(p: [*]const u8, n: usize) usize { return n; }
+                    ---
+                    fn count(s: &str) -> usize;
+                });
+            }
+            "#,
+        )
+        .unwrap();
+        let mut collector = Collector::default();
+        collector.visit_file(&file);
+        let uniffi = collector.interface.render("img_kit", "img_kit");
+        assert_eq!(
+            uniffi.udl,
+            "// Generated by autozig from the crate's signatures. Do not edit.\n// Not exported \
+             (no UniFFI equivalent for some types): fill\nnamespace img_kit {\n  bytes blur(bytes \
+             px, u32 width);\n  Point shift(Point p, Mode mode);\n  u64 utf_count(string \
+             s);\n};\n\ndictionary Point {\n  float x;\n  double y;\n};\n\nenum Mode {\n  \
+             \"Fast\",\n  \"Slow\"\n};\n\ninterface ZigHasher {\n  constructor(u64 seed);\n  void \
+             write(bytes bytes);\n  u64 finish();\n};\n"
+        );
+        for line in [
+            "    use crate::Point;\n",
+            "    pub fn utf_count(s: String) -> u64 {\n        crate::text::utf::count(&s) as \
+             u64\n    }\n",
+            "    pub struct ZigHasher(std::sync::Mutex<crate::ZigHasher>);\n",
+            "            Self(std::sync::Mutex::new(crate::ZigHasher::new(seed)))\n",
+            "        pub fn write(&self, bytes: Vec<u8>) {\n            <crate::ZigHasher as \
+             std::hash::Hasher>::write(&mut *self.0.lock().unwrap(), &bytes);\n        }\n",
+            "    ::uniffi::include_scaffolding!(\"img_kit\");\n}\n\nuse \
+             autozig_uniffi::UniFfiTag;\n",
+        ] {
+            assert!(uniffi.glue.contains(line), "{line}\n---\n{}", uniffi.glue);
+        }
+
+        assert_eq!(
+            module_path(Path::new("src"), Path::new("src/a/mod.rs")),
+            Some(vec!["a".to_string()])
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Zig exports the Rust side never binds
+//!
+//! Large Zig modules often export more functions than the crate declares.
+//! Every export is a root for Zig's analysis, so each of them ends up in the
+//! static library (and the WASM binary) whether Rust calls it or not. The
+//! engine diffs the exports against the symbols of the crate's `autozig!`,
+//! `include_zig!` and `bind_zig!` signatures, plus any hand-written
+//! `extern "C"` declarations, and either reports the rest as cargo warnings
+//! or drops their `export` so Zig leaves them out:
+//!
+//! ```rust,ignore
+//! let options = BuildOptions {
+//!     unused_exports: UnusedExports::Strip,
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! Exports called from C sources rather than Rust must be listed in
+//! `public_symbols` to be kept.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use syn::visit::Visit;
+use walkdir::WalkDir;
+
+use crate::visibility;
+
+/// What to do with Zig exports that no Rust declaration refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnusedExports {
+    /// Compile every export without comment
+    Ignore,
+    /// Report each unused export as a cargo warning (default)
+    #[default]
+    Warn,
+    /// Turn unused exports into plain functions so Zig does not emit them
+    Strip,
+}
+
+/// Symbols the Rust sources under `src_dir` bind to
+pub fn declared_symbols(src_dir: &Path) -> Result<HashSet<String>> {
+    let mut collector = Collector::default();
+    for entry in WalkDir::new(src_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if let Ok(file) = syn::parse_file(&content) {
+            collector.visit_file(&file);
+        }
+    }
+    Ok(collector.0)
+}
+
+/// Functions exported by `zig_code` that are neither in `declared` nor in
+/// `keep`, in order of appearance
+pub fn unused(zig_code: &str, declared: &HashSet<String>, keep: &[String]) -> Vec<String> {
+    visibility::exported_function_names(zig_code)
+        .into_iter()
+        .filter(|name| !declared.contains(name) && !keep.contains(name))
+        .collect()
+}
+
+#[derive(Default)]
+struct Collector(HashSet<String>);
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let symbols = if node.path.is_ident("autozig") {
+            syn::parse2::<AutoZigConfig>(node.tokens.clone()).map(|config| config.symbol_names())
+        } else if node.path.is_ident("include_zig") || node.path.is_ident("bind_zig") {
+            syn::parse2::<IncludeZigConfig>(node.tokens.clone()).map(|config| config.symbol_names())
+        } else {
+            Ok(Vec::new())
+        };
+        self.0.extend(symbols.unwrap_or_default());
+        syn::visit::visit_macro(self, node);
+    }
+
+    fn visit_foreign_item_fn(&mut self, node: &'ast syn::ForeignItemFn) {
+        self.0.insert(node.sig.ident.to_string());
+    }
+
+    fn visit_foreign_item_static(&mut self, node: &'ast syn::ForeignItemStatic) {
+        self.0.insert(node.ident.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused_exports() {
+        let file = syn::parse_file(
+            r#"
+            autozig! {
+                export fn add(a: i32, b: i32) i32 { return a + b; }
+                export fn sub(a: i32, b: i32) i32 { return a - b; }
+                export fn debug_dump() void {}
+                ---
+                fn add(a: i32, b: i32) -> i32;
+            }
+            extern "C" {
+                fn sub(a: i32, b: i32) -> i32;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut collector = Collector::default();
+        collector.visit_file(&file);
+
+        let declared = collector.0;
+        let zig_code = "export fn add(a: i32, b: i32) i32 { return a + b; }\nexport fn sub(a: \
+                        i32, b: i32) i32 { return a - b; }\nexport fn debug_dump() void \
+                        {}\nexport fn c_entry() void {}\n";
+        assert_eq!(unused(zig_code, &declared, &["c_entry".to_string()]), ["debug_dump"]);
+    }
+}
@@ -0,0 +1,613 @@
+//! Eject a crate from the autozig macros
+//!
+//! Vendoring copies a crate, compiles its Zig code once per target into
+//! `vendor/autozig/<target>/`, replaces every `autozig!`, `include_zig!` and
+//! `bind_zig!` invocation with the Rust code it expands to, and swaps the
+//! build script for one that only links the prebuilt libraries. The result
+//! builds without a Zig toolchain, and its Rust side can be audited as plain
+//! source.
+//!
+//! ```rust,no_run
+//! use autozig_engine::vendor::Vendor;
+//!
+//! Vendor::new(".", "../mycrate-vendored")
+//!     .target("x86_64-unknown-linux-gnu")
+//!     .target("aarch64-apple-darwin")
+//!     .run()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use quote::ToTokens;
+use syn::{
+    visit::Visit,
+    MacroDelimiter,
+};
+use walkdir::WalkDir;
+
+use crate::{
+    AutoZigEngine,
+    BuildOptions,
+    CompilationMode,
+    ZigCodeScanner,
+};
+
+/// Directory (relative to the crate root) holding the prebuilt libraries
+pub const VENDOR_DIR: &str = "vendor/autozig";
+
+/// Library name the compiled Zig code is linked as
+const VENDOR_LIB: &str = "autozig_vendor";
+
+/// Build-script marker passed to the expansion and stripped from the output
+const VENDOR_MARKER: &str = "@autozig:vendored";
+
+/// Directories never copied into the vendored crate
+const SKIPPED_DIRS: &[&str] = &["target", ".git"];
+
+/// Features of the `autozig` crate that turn on its `std` feature
+const STD_FEATURES: &[&str] = &[
+    "std",
+    "stream",
+    "pointer-log",
+    "trace-ffi",
+    "metrics",
+    "isolation",
+    "bench",
+    "mmap",
+    "plugin",
+    "python",
+    "log",
+    "tracing",
+];
+
+/// Vendoring of one crate
+#[derive(Debug, Clone)]
+pub struct Vendor {
+    crate_dir: PathBuf,
+    output_dir: PathBuf,
+    mode: CompilationMode,
+    targets: Vec<String>,
+    std: Option<bool>,
+}
+
+/// What a vendoring run produced
+#[derive(Debug, Clone, Default)]
+pub struct VendorReport {
+    /// Prebuilt Zig library per target
+    pub libraries: Vec<PathBuf>,
+    /// Source files whose macros were expanded
+    pub expanded_files: Vec<PathBuf>,
+}
+
+impl Vendor {
+    /// Vendor the crate at `crate_dir` into `output_dir`
+    pub fn new(crate_dir: impl AsRef<Path>, output_dir: impl AsRef<Path>) -> Self {
+        Self {
+            crate_dir: crate_dir.as_ref().to_path_buf(),
+            output_dir: output_dir.as_ref().to_path_buf(),
+            mode: CompilationMode::from_env().unwrap_or_default(),
+            targets: Vec::new(),
+            std: None,
+        }
+    }
+
+    /// Compilation mode used to build the Zig code
+    pub fn mode(mut self, mode: CompilationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Add a Rust target triple to prebuild the Zig library for
+    pub fn target(mut self, rust_target: impl Into<String>) -> Self {
+        self.targets.push(rust_target.into());
+        self
+    }
+
+    /// Whether the crate uses autozig's `std` feature
+    ///
+    /// Defaults to what the crate's Cargo.toml asks of its `autozig`
+    /// dependency. Without std, the Zig code is built freestanding and the
+    /// macros expand to core/alloc-only wrappers, like
+    /// `autozig_build::Builder::no_std` does.
+    pub fn std(mut self, enabled: bool) -> Self {
+        self.std = Some(enabled);
+        self
+    }
+
+    /// Copy, compile and rewrite the crate
+    pub fn run(&self) -> Result<VendorReport> {
+        if self.targets.is_empty() {
+            anyhow::bail!("No targets to vendor; add at least one with Vendor::target");
+        }
+
+        copy_crate(&self.crate_dir, &self.output_dir)?;
+        let src_dir = self.output_dir.join("src");
+        let std = match self.std {
+            Some(std) => std,
+            None => {
+                let manifest = self.output_dir.join("Cargo.toml");
+                let text = fs::read_to_string(&manifest)
+                    .with_context(|| format!("Failed to read {}", manifest.display()))?;
+                manifest_uses_std(&text)
+                    .with_context(|| format!("Failed to parse {}", manifest.display()))?
+            },
+        };
+        let mut report = VendorReport::default();
+
+        // Compile before the macros holding the Zig code are expanded away
+        for target in &self.targets {
+            let work_dir = self.output_dir.join("target/autozig-vendor").join(target);
+            fs::create_dir_all(&work_dir)
+                .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+
+            let mut options = BuildOptions {
+                safety_lints: false,
+                ..BuildOptions::default()
+            };
+            options.codegen.freestanding = !std;
+            let output = AutoZigEngine::with_mode(&src_dir, &work_dir, self.mode)
+                .with_target(target)
+                .with_manifest_dir(&self.output_dir)
+                .with_options(options)
+                .build()
+                .with_context(|| format!("Failed to compile Zig code for {}", target))?;
+
+            if let Some(lib_path) = output.lib_path {
+                let dest_dir = self.output_dir.join(VENDOR_DIR).join(target);
+                fs::create_dir_all(&dest_dir)
+                    .with_context(|| format!("Failed to create {}", dest_dir.display()))?;
+                let dest = dest_dir.join(format!("lib{}.a", VENDOR_LIB));
+                fs::copy(&lib_path, &dest)
+                    .with_context(|| format!("Failed to copy {}", lib_path.display()))?;
+                report.libraries.push(dest);
+            }
+        }
+
+        let prebuilt = ZigCodeScanner::with_mode(&src_dir, self.mode)
+            .with_manifest_dir(&self.output_dir)
+            .scan_prebuilt()?;
+        let archives: Vec<PathBuf> = prebuilt
+            .into_iter()
+            .map(|lib| {
+                lib.path
+                    .strip_prefix(&self.output_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(lib.path)
+            })
+            .collect();
+
+        for entry in WalkDir::new(&src_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                continue;
+            }
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let expanded = autozig_codegen::with_std(std, || expand_source(&source))
+                .with_context(|| format!("Failed to expand macros in {}", path.display()))?;
+            if let Some(expanded) = expanded {
+                fs::write(path, expanded)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                report.expanded_files.push(path.to_path_buf());
+            }
+        }
+
+        // Keep the original build script around for reference
+        let build_rs = self.output_dir.join("build.rs");
+        if build_rs.exists() {
+            let kept = self.output_dir.join(VENDOR_DIR).join("build.rs.orig");
+            fs::create_dir_all(self.output_dir.join(VENDOR_DIR))?;
+            fs::rename(&build_rs, &kept).context("Failed to move the original build.rs")?;
+        }
+        let targets: Vec<&str> = if report.libraries.is_empty() {
+            Vec::new()
+        } else {
+            self.targets.iter().map(String::as_str).collect()
+        };
+        fs::write(&build_rs, render_build_script(&targets, &archives))
+            .context("Failed to write build.rs")?;
+
+        Ok(report)
+    }
+}
+
+/// Whether the crate with Cargo.toml `manifest` turns on autozig's `std`
+/// feature
+///
+/// It does unless its `autozig` dependency sets `default-features = false`
+/// and asks for none of the [`STD_FEATURES`].
+fn manifest_uses_std(manifest: &str) -> Result<bool> {
+    let manifest: toml::Table = toml::from_str(manifest)?;
+    let Some(toml::Value::Table(dep)) = manifest
+        .get("dependencies")
+        .and_then(|deps| deps.get("autozig"))
+    else {
+        return Ok(true);
+    };
+    let default_features = dep
+        .get("default-features")
+        .or_else(|| dep.get("default_features"))
+        .and_then(toml::Value::as_bool)
+        .unwrap_or(true);
+    let mut features = dep
+        .get("features")
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_str);
+    Ok(default_features || features.any(|feature| STD_FEATURES.contains(&feature)))
+}
+
+/// Replace the autozig macro invocations in `source` with their expansion
+///
+/// Returns `None` if the file contains no such macro.
+pub fn expand_source(source: &str) -> Result<Option<String>> {
+    let file = syn::parse_file(source).context("Failed to parse Rust source")?;
+
+    let mut finder = MacroFinder::default();
+    finder.visit_file(&file);
+    if finder.macros.is_empty() {
+        return Ok(None);
+    }
+
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for found in &finder.macros {
+        let mut end = found.end;
+        if found.needs_semi {
+            let rest = &source[end..];
+            if rest.trim_start().starts_with(';') {
+                end += rest.len() - rest.trim_start().len() + 1;
+            }
+        }
+
+        let indent = " ".repeat(found.column);
+        let mut replacement = format!("// Expanded from {}! by autozig vendor\n", found.name);
+        for line in render_expansion(found)?.lines() {
+            if !line.is_empty() {
+                replacement.push_str(&indent);
+                replacement.push_str(line);
+            }
+            replacement.push('\n');
+        }
+        edits.push((found.start, end, replacement.trim_end().to_string()));
+    }
+    // Imports of the expanded macros would be unused
+    for &(start, end) in &finder.macro_imports {
+        let end = if source[end..].starts_with('\n') {
+            end + 1
+        } else {
+            end
+        };
+        edits.push((start, end, String::new()));
+    }
+
+    // Back to front, so earlier byte ranges stay valid
+    edits.sort_by_key(|&(start, ..)| std::cmp::Reverse(start));
+    let mut result = source.to_string();
+    for (start, end, replacement) in edits {
+        result.replace_range(start..end, &replacement);
+    }
+
+    Ok(Some(result))
+}
+
+/// An autozig macro invocation located in a source file
+struct FoundMacro {
+    name: String,
+    tokens: proc_macro2::TokenStream,
+    /// Byte range of the invocation, from the macro name to the closing
+    /// delimiter
+    start: usize,
+    end: usize,
+    /// Column the invocation starts at, for indenting the expansion
+    column: usize,
+    /// Parenthesized/bracketed invocations are followed by `;`
+    needs_semi: bool,
+}
+
+/// Macros replaced by their expansion
+const EXPANDED_MACROS: &[&str] = &["autozig", "include_zig", "bind_zig"];
+
+#[derive(Default)]
+struct MacroFinder {
+    macros: Vec<FoundMacro>,
+    /// Byte ranges of `use autozig::<macro>;` items
+    macro_imports: Vec<(usize, usize)>,
+}
+
+impl<'ast> Visit<'ast> for MacroFinder {
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let name = match node.path.get_ident() {
+            Some(ident) if EXPANDED_MACROS.iter().any(|name| ident == name) => ident.to_string(),
+            _ => return,
+        };
+        let (close, needs_semi) = match &node.delimiter {
+            MacroDelimiter::Paren(paren) => (paren.span.close(), true),
+            MacroDelimiter::Bracket(bracket) => (bracket.span.close(), true),
+            MacroDelimiter::Brace(brace) => (brace.span.close(), false),
+        };
+        let name_span = node.path.segments[0].ident.span();
+        self.macros.push(FoundMacro {
+            name,
+            tokens: node.tokens.clone(),
+            start: name_span.byte_range().start,
+            end: close.byte_range().end,
+            column: name_span.start().column,
+            needs_semi,
+        });
+    }
+
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        let imports_macro = match &node.tree {
+            syn::UseTree::Path(path) if path.ident == "autozig" => matches!(
+                &*path.tree,
+                syn::UseTree::Name(name) if EXPANDED_MACROS.iter().any(|m| name.ident == m)
+            ),
+            _ => false,
+        };
+        if imports_macro && node.attrs.is_empty() {
+            let start = node
+                .vis
+                .to_token_stream()
+                .into_iter()
+                .next()
+                .map_or(node.use_token.span, |token| token.span());
+            self.macro_imports
+                .push((start.byte_range().start, node.semi_token.span.byte_range().end));
+        }
+    }
+}
+
+/// Expansion of every autozig macro invocation in `source`, in source order
+pub(crate) fn expansions(source: &str) -> Result<Vec<syn::File>> {
+    let file = syn::parse_file(source).context("Failed to parse Rust source")?;
+    let mut finder = MacroFinder::default();
+    finder.visit_file(&file);
+    finder.macros.iter().map(expand).collect()
+}
+
+/// Expansion of one invocation
+fn expand(found: &FoundMacro) -> Result<syn::File> {
+    let tokens = if found.name == "autozig" {
+        let config: AutoZigConfig = syn::parse2(found.tokens.clone())?;
+        autozig_codegen::expand_autozig(&config)
+    } else {
+        let config: IncludeZigConfig = syn::parse2(found.tokens.clone())?;
+        autozig_codegen::expand_external(&config, VENDOR_MARKER, &format!("{}!", found.name))
+    };
+
+    syn::parse2(tokens).context("Failed to parse macro expansion")
+}
+
+/// Pretty-printed expansion of one invocation
+fn render_expansion(found: &FoundMacro) -> Result<String> {
+    let mut file = expand(found)?;
+    for item in &mut file.items {
+        // Glob imports in the generated FFI module only serve some signatures
+        if let syn::Item::Mod(module) = item {
+            module
+                .attrs
+                .push(syn::parse_quote!(#[allow(unused_imports)]));
+        }
+        // The build-script marker is meaningless once the macro is gone
+        if let Some(attrs) = item_attrs(item) {
+            attrs.retain(|attr| !is_vendor_marker(attr));
+        }
+    }
+    Ok(prettyplease::unparse(&file))
+}
+
+fn item_attrs(item: &mut syn::Item) -> Option<&mut Vec<syn::Attribute>> {
+    match item {
+        syn::Item::Enum(item) => Some(&mut item.attrs),
+        syn::Item::Struct(item) => Some(&mut item.attrs),
+        syn::Item::Mod(item) => Some(&mut item.attrs),
+        syn::Item::Macro(item) => Some(&mut item.attrs),
+        syn::Item::Fn(item) => Some(&mut item.attrs),
+        _ => None,
+    }
+}
+
+fn is_vendor_marker(attr: &syn::Attribute) -> bool {
+    match &attr.meta {
+        syn::Meta::NameValue(meta) if meta.path.is_ident("doc") => matches!(
+            &meta.value,
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) if s.value() == VENDOR_MARKER
+        ),
+        _ => false,
+    }
+}
+
+/// Build script linking the prebuilt libraries for `targets` and the
+/// `bind_zig!` archives
+fn render_build_script(targets: &[&str], archives: &[PathBuf]) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "//! Generated by autozig vendor: links the prebuilt Zig libraries instead of\n//! \
+         compiling Zig code. The original build script is kept as\n",
+    );
+    let _ = writeln!(out, "//! {}/build.rs.orig.", VENDOR_DIR);
+    out.push_str("\nuse std::{\n    env,\n    path::Path,\n};\n\n");
+    let _ = writeln!(out, "const TARGETS: &[&str] = &{:?};\n", targets);
+    out.push_str("fn main() {\n");
+    out.push_str("    let manifest_dir = env::var(\"CARGO_MANIFEST_DIR\").unwrap();\n");
+    out.push_str("    let manifest_dir = Path::new(&manifest_dir);\n");
+    let _ = writeln!(out, "    println!(\"cargo:rerun-if-changed={}\");", VENDOR_DIR);
+
+    if !targets.is_empty() {
+        out.push_str("\n    let target = env::var(\"TARGET\").unwrap();\n");
+        out.push_str("    if !TARGETS.contains(&target.as_str()) {\n");
+        out.push_str(
+            "        panic!(\"No prebuilt Zig library for {target}; vendored targets: \
+             {TARGETS:?}\");\n",
+        );
+        out.push_str("    }\n");
+        let _ =
+            writeln!(out, "    let lib_dir = manifest_dir.join({:?}).join(&target);", VENDOR_DIR);
+        out.push_str("    println!(\"cargo:rustc-link-search=native={}\", lib_dir.display());\n");
+        out.push_str("    if target.contains(\"wasm\") {\n");
+        let _ = writeln!(
+            out,
+            "        println!(\"cargo:rustc-link-lib=static:+whole-archive={}\");",
+            VENDOR_LIB
+        );
+        out.push_str("    } else {\n");
+        let _ = writeln!(out, "        println!(\"cargo:rustc-link-lib=static={}\");", VENDOR_LIB);
+        out.push_str("    }\n");
+    }
+
+    for archive in archives {
+        let Some(name) = crate::static_lib_name(archive) else {
+            continue;
+        };
+        let dir = archive.parent().unwrap_or(Path::new(""));
+        let dir = dir.to_string_lossy().replace('\\', "/");
+        out.push('\n');
+        let _ = writeln!(
+            out,
+            "    println!(\"cargo:rustc-link-search=native={{}}\", \
+             manifest_dir.join({:?}).display());",
+            dir
+        );
+        let _ = writeln!(out, "    println!(\"cargo:rustc-link-lib=static={}\");", name);
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Copy `crate_dir` to `output_dir`, skipping build output and VCS data
+fn copy_crate(crate_dir: &Path, output_dir: &Path) -> Result<()> {
+    let output_abs = output_dir.canonicalize().ok();
+    let walker = WalkDir::new(crate_dir).into_iter().filter_entry(|entry| {
+        let skipped = entry.depth() == 1
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+        // The output may live inside the crate being vendored
+        let is_output = output_abs
+            .as_ref()
+            .is_some_and(|out| entry.path().canonicalize().is_ok_and(|p| &p == out));
+        !skipped && !is_output
+    });
+
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(crate_dir)?;
+        let dest = output_dir.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)
+                .with_context(|| format!("Failed to create {}", dest.display()))?;
+        } else {
+            fs::copy(entry.path(), &dest)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_source() {
+        let source = concat!(
+            "use autozig::prelude::*;\n",
+            "use autozig::include_zig;\n",
+            "\n",
+            "mod math {\n",
+            "    autozig! {\n",
+            "        export fn add(a: i32, b: i32) i32 {\n",
+            "            return a + b;\n",
+            "        }\n",
+            "        ---\n",
+            "        fn add(a: i32, b: i32) -> i32;\n",
+            "    }\n",
+            "}\n",
+            "\n",
+            "include_zig!(\"zig/extra.zig\", {\n",
+            "    fn checksum(data: &[u8]) -> u32;\n",
+            "});\n",
+            "\n",
+            "fn main() {}\n",
+        );
+
+        let expanded = expand_source(source).unwrap().unwrap();
+        assert!(!expanded.contains("autozig! {"));
+        assert!(!expanded.contains("include_zig!("));
+        assert!(!expanded.contains("@autozig:include"));
+        assert!(!expanded.contains("use autozig::include_zig;"));
+        assert!(expanded.contains("use autozig::prelude::*;"));
+        assert!(expanded.contains("    // Expanded from autozig! by autozig vendor\n"));
+        assert!(expanded.contains("mod ffi {"));
+        assert!(expanded.contains("mod ffi_zig_extra {"));
+        assert!(expanded.contains("fn checksum("));
+        syn::parse_file(&expanded).unwrap();
+
+        assert!(expand_source("fn main() {}").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_manifest_uses_std() {
+        assert!(manifest_uses_std("[dependencies]\nautozig = \"0.1\"\n").unwrap());
+        assert!(manifest_uses_std("[package]\nname = \"plain\"\n").unwrap());
+        assert!(!manifest_uses_std(
+            "[dependencies]\nautozig = { version = \"0.1\", default-features = false }\n"
+        )
+        .unwrap());
+        assert!(manifest_uses_std(
+            "[dependencies.autozig]\nversion = \"0.1\"\ndefault-features = false\nfeatures = \
+             [\"stream\"]\n"
+        )
+        .unwrap());
+
+        let source = concat!(
+            "autozig! {\n",
+            "    export fn add(a: u32) u32 { return a; }\n",
+            "    ---\n",
+            "    #[autozig(isolated)]\n",
+            "    fn add(a: u32) -> u32;\n",
+            "}\n",
+        );
+        let expanded = autozig_codegen::with_std(false, || expand_source(source));
+        assert!(expanded
+            .unwrap()
+            .unwrap()
+            .contains("without the `std` feature"));
+        let expanded = autozig_codegen::with_std(true, || expand_source(source));
+        assert!(expanded.unwrap().unwrap().contains("isolate::run"));
+    }
+
+    #[test]
+    fn test_render_build_script() {
+        let script =
+            render_build_script(&["x86_64-unknown-linux-gnu"], &[PathBuf::from("vendor/libfoo.a")]);
+        syn::parse_file(&script).unwrap();
+        assert!(script.contains("const TARGETS: &[&str] = &[\"x86_64-unknown-linux-gnu\"];"));
+        assert!(script.contains("cargo:rustc-link-lib=static=autozig_vendor"));
+        assert!(script.contains("manifest_dir.join(\"vendor\")"));
+        assert!(script.contains("cargo:rustc-link-lib=static=foo"));
+    }
+}
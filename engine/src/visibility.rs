@@ -0,0 +1,309 @@
+//! Symbol visibility control for Zig exports
+//!
+//! Zig's `export fn` always produces default-visibility symbols, which leak
+//! into the dynamic symbol table of shared libraries that link the generated
+//! static library. On native targets the engine rewrites exports to
+//! `@export(.., .{ .visibility = .hidden })` so they still link statically but
+//! are not re-exported, unless the symbol was explicitly marked public.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use object::{
+    read::archive::ArchiveFile,
+    Object,
+    ObjectSymbol,
+    SymbolScope,
+};
+
+/// Visibility applied to Zig exports in the generated static library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymbolVisibility {
+    /// Exports link statically but are not visible from shared libraries
+    /// (default on native targets)
+    #[default]
+    Hidden,
+    /// Keep Zig's default visibility for every export
+    Public,
+}
+
+/// Rewrite `export fn` declarations to hidden `@export`s
+///
+/// `export fn name(..) T { .. }` becomes `fn name(..) callconv(.c) T { .. }`
+/// followed by a `comptime` block exporting it with hidden visibility.
+/// Functions listed in `public` are left untouched.
+pub fn apply_hidden_visibility(zig_code: &str, public: &[String]) -> String {
+    rewrite_exports(zig_code, |name| (name.to_string(), !public.iter().any(|p| p == name)))
+}
+
+/// Rewrite `export fn` declarations to `@export`s
+///
+/// `export_as` gives the symbol name of each function and whether it is
+/// hidden. Functions exported under their own name with default visibility
+/// are left untouched; the others become plain `callconv(.c)` functions
+/// exported from a `comptime` block, so Zig code keeps calling them by name.
+pub fn rewrite_exports(zig_code: &str, export_as: impl Fn(&str) -> (String, bool)) -> String {
+    let mut result = String::with_capacity(zig_code.len());
+    let mut exports = Vec::new();
+    let mut rest = zig_code;
+
+    while let Some(pos) = rest.find("export fn ") {
+        let after = &rest[pos + "export fn ".len()..];
+        let name_end = after
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..name_end];
+
+        // Only rewrite real declarations (`export` not part of another word)
+        let is_keyword = !rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let (symbol, hidden) = export_as(name);
+
+        if !is_keyword || name.is_empty() || (symbol == name && !hidden) {
+            result.push_str(&rest[..pos + "export fn ".len()]);
+            rest = after;
+            continue;
+        }
+
+        result.push_str(&rest[..pos]);
+        result.push_str("fn ");
+        result.push_str(name);
+
+        // Copy the parameter list, then add the C calling convention that
+        // `export` used to imply
+        let params = &after[name_end..];
+        let Some(params_len) = matching_paren_end(params) else {
+            result.push_str(params);
+            rest = "";
+            break;
+        };
+        result.push_str(&params[..params_len]);
+        let tail = &params[params_len..];
+        if !tail.trim_start().starts_with("callconv") {
+            result.push_str(" callconv(.c)");
+        }
+
+        exports.push((name.to_string(), symbol, hidden));
+        rest = tail;
+    }
+    result.push_str(rest);
+
+    if !exports.is_empty() {
+        result.push_str("\n\n// AutoZig: rewritten exports\ncomptime {\n");
+        for (name, symbol, hidden) in &exports {
+            let visibility = if *hidden { "hidden" } else { "default" };
+            result.push_str(&format!(
+                "    @export(&{name}, .{{ .name = \"{symbol}\", .visibility = .{visibility} }});\n"
+            ));
+        }
+        result.push_str("}\n");
+    }
+
+    result
+}
+
+/// Names of all functions exported from `zig_code`, whether declared with
+/// `export fn` or rewritten to a hidden `@export`
+pub fn exported_function_names(zig_code: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for marker in ["export fn ", "@export(&"] {
+        let mut rest = zig_code;
+        while let Some(pos) = rest.find(marker) {
+            rest = &rest[pos + marker.len()..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end > 0 && !names.iter().any(|n| n == &rest[..end]) {
+                names.push(rest[..end].to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Length of a leading `( .. )` group including both parentheses
+fn matching_paren_end(code: &str) -> Option<usize> {
+    if !code.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0usize;
+    for (i, ch) in code.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// List symbols that a library makes visible to dynamic linking
+///
+/// Works on shared libraries (`.so`, `.dylib`, `.dll`) and static archives
+/// (`.a`, `.lib`). For archives every member object is inspected.
+pub fn exported_symbols(lib_path: &Path) -> Result<Vec<String>> {
+    collect_symbols(lib_path, |scope| scope == SymbolScope::Dynamic)
+}
+
+/// List global symbols a library defines, regardless of their visibility
+pub fn defined_symbols(lib_path: &Path) -> Result<Vec<String>> {
+    collect_symbols(lib_path, |scope| matches!(scope, SymbolScope::Linkage | SymbolScope::Dynamic))
+}
+
+fn collect_symbols(lib_path: &Path, keep: impl Fn(SymbolScope) -> bool) -> Result<Vec<String>> {
+    let data =
+        fs::read(lib_path).with_context(|| format!("Failed to read {}", lib_path.display()))?;
+
+    let mut symbols = Vec::new();
+    if let Ok(archive) = ArchiveFile::parse(&*data) {
+        for member in archive.members() {
+            let member = member.context("Failed to read archive member")?;
+            let member_data = member
+                .data(&*data)
+                .context("Failed to read archive member")?;
+            // Skip non-object members (symbol tables, etc.)
+            if let Ok(file) = object::File::parse(member_data) {
+                collect_defined(&file, &keep, &mut symbols);
+            }
+        }
+    } else {
+        let file = object::File::parse(&*data)
+            .with_context(|| format!("Unsupported library format: {}", lib_path.display()))?;
+        collect_defined(&file, &keep, &mut symbols);
+    }
+
+    symbols.sort();
+    symbols.dedup();
+    Ok(symbols)
+}
+
+fn collect_defined(
+    file: &object::File,
+    keep: &impl Fn(SymbolScope) -> bool,
+    symbols: &mut Vec<String>,
+) {
+    let strip_underscore = file.format() == object::BinaryFormat::MachO;
+    for symbol in file.symbols() {
+        if symbol.is_undefined() || !keep(symbol.scope()) {
+            continue;
+        }
+        if let Ok(name) = symbol.name() {
+            let name = if strip_underscore {
+                name.strip_prefix('_').unwrap_or(name)
+            } else {
+                name
+            };
+            symbols.push(name.to_string());
+        }
+    }
+}
+
+/// Fail if `lib_path` does not define every symbol in `expected`
+pub fn verify_defined_symbols(lib_path: &Path, expected: &[String]) -> Result<()> {
+    let defined = defined_symbols(lib_path)?;
+    let missing: Vec<&str> = expected
+        .iter()
+        .filter(|name| !defined.contains(name))
+        .map(|s| s.as_str())
+        .collect();
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "{} does not define the declared symbols:\n  {}",
+            lib_path.display(),
+            missing.join("\n  ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Fail if any of `candidates` is dynamically visible in `lib_path` without
+/// being listed in `public`
+pub fn verify_exported_symbols(
+    lib_path: &Path,
+    candidates: &[String],
+    public: &[String],
+) -> Result<()> {
+    let exported = exported_symbols(lib_path)?;
+    let leaked: Vec<&String> = candidates
+        .iter()
+        .filter(|name| exported.contains(name) && !public.contains(name))
+        .collect();
+
+    if !leaked.is_empty() {
+        anyhow::bail!(
+            "Unintended public symbols in {}:\n  {}",
+            lib_path.display(),
+            leaked
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_hidden_visibility() {
+        let code = "export fn add(a: i32, b: i32) i32 { return a + b; }";
+        let hidden = apply_hidden_visibility(code, &[]);
+        assert!(hidden.starts_with("fn add(a: i32, b: i32) callconv(.c) i32 {"));
+        assert!(hidden.contains("@export(&add, .{ .name = \"add\", .visibility = .hidden });"));
+    }
+
+    #[test]
+    fn test_public_opt_out() {
+        let code = "pub export fn keep() void {} export fn hide(f: fn () void) void { f(); }";
+        let hidden = apply_hidden_visibility(code, &["keep".to_string()]);
+        assert!(hidden.contains("pub export fn keep() void {}"));
+        assert!(hidden.contains("fn hide(f: fn () void) callconv(.c) void"));
+        assert!(!hidden.contains("@export(&keep"));
+    }
+
+    #[test]
+    fn test_exported_function_names() {
+        let code = "export fn a() void {} export fn b() void {}";
+        let hidden = apply_hidden_visibility(code, &["a".to_string()]);
+        assert_eq!(exported_function_names(&hidden), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_rewrite_exports_renames_symbols() {
+        let code = "export fn add(a: i32) i32 { return a; }\nexport fn keep() void {}";
+        let rewritten = rewrite_exports(code, |name| match name {
+            "keep" => (name.to_string(), false),
+            _ => (format!("az_0a1b2c__{name}"), false),
+        });
+        assert!(rewritten.starts_with("fn add(a: i32) callconv(.c) i32"));
+        assert!(rewritten.contains("export fn keep() void {}"));
+        assert!(rewritten
+            .contains("@export(&add, .{ .name = \"az_0a1b2c__add\", .visibility = .default });"));
+    }
+
+    #[test]
+    fn test_existing_callconv_preserved() {
+        let code = "export fn cb() callconv(.c) void {}";
+        let hidden = apply_hidden_visibility(code, &[]);
+        assert!(hidden.starts_with("fn cb() callconv(.c) void {}"));
+    }
+}
@@ -0,0 +1,149 @@
+//! WIT world for the WASM component model
+//!
+//! With `BuildOptions::wit`, the crate's `autozig!`, `include_zig!` and
+//! `bind_zig!` signatures are described as a WIT world, and the macros export
+//! the matching wrappers with the canonical ABI (see
+//! [`autozig_codegen::wit`]). The core module built for `wasm32` then only
+//! needs `wasm-tools component new` to become a component usable from
+//! WASI-preview2 hosts:
+//!
+//! ```wit
+//! // Generated by autozig from the crate's signatures. Do not edit.
+//! package local:img-kit;
+//!
+//! world img-kit {
+//!   export blur: func(px: list<u8>, width: u32) -> list<u8>;
+//!   export img-count: func(s: string) -> u32;
+//! }
+//! ```
+//!
+//! Signatures with types the world cannot describe are listed in a comment
+//! and not exported.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use autozig_codegen::wit::{
+    kebab,
+    WitFunction,
+};
+use autozig_parser::{
+    AutoZigConfig,
+    IncludeZigConfig,
+};
+use syn::visit::Visit;
+use walkdir::WalkDir;
+
+/// WIT world of the signatures in the `.rs` files under `src_dir`, for the
+/// package `package` (a crate name)
+pub fn world_for_dir(src_dir: &Path, package: &str) -> Result<String> {
+    let mut collector = Collector::default();
+    for entry in WalkDir::new(src_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if let Ok(file) = syn::parse_file(&content) {
+            collector.visit_file(&file);
+        }
+    }
+    Ok(render(package, &collector.functions, &collector.skipped))
+}
+
+/// Render the world `package` exporting `functions`
+pub fn render(package: &str, functions: &[WitFunction], skipped: &[String]) -> String {
+    let name = kebab(package);
+    let mut wit =
+        String::from("// Generated by autozig from the crate's signatures. Do not edit.\n");
+    wit.push_str(&format!("package local:{name};\n\nworld {name} {{\n"));
+    for function in functions {
+        wit.push_str(&format!("  {}\n", function.declaration()));
+    }
+    wit.push_str("}\n");
+    if !skipped.is_empty() {
+        wit.push_str(&format!(
+            "\n// Not exported (no WIT equivalent for some types): {}\n",
+            skipped.join(", ")
+        ));
+    }
+    wit
+}
+
+#[derive(Default)]
+struct Collector {
+    functions: Vec<WitFunction>,
+    skipped: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let (signatures, namespace) = if node.path.is_ident("autozig") {
+            match syn::parse2::<AutoZigConfig>(node.tokens.clone()) {
+                Ok(config) => (config.rust_signatures, config.namespace),
+                Err(_) => return,
+            }
+        } else if node.path.is_ident("include_zig") || node.path.is_ident("bind_zig") {
+            match syn::parse2::<IncludeZigConfig>(node.tokens.clone()) {
+                Ok(config) => (config.rust_signatures, None),
+                Err(_) => return,
+            }
+        } else {
+            return;
+        };
+        for signature in &signatures {
+            match WitFunction::from_signature(signature, namespace.as_ref()) {
+                Some(function) => self.functions.push(function),
+                None => self.skipped.push(signature.sig.ident.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wit_world() {
+        let file = syn::parse_file(
+            r#"
+            autozig! {
+                export fn blur_2d(p: [*]const u8, n: usize, w: u32) void {}
+                ---
+                fn blur_2d(px: &[u8], width: u32) -> Vec<u8>;
+                fn is_ready(flag: bool) -> bool;
+                fn describe(list: &str) -> String;
+                fn fill(out: &mut [f32]);
+            }
+            autozig!(mod = img, {
+                export fn count(p: [*]const u8, n: usize) usize { return n; }
+                ---
+                fn count(s: &str) -> usize;
+            });
+            "#,
+        )
+        .unwrap();
+        let mut collector = Collector::default();
+        collector.visit_file(&file);
+        assert_eq!(
+            render("img_kit", &collector.functions, &collector.skipped),
+            "// Generated by autozig from the crate's signatures. Do not edit.\npackage \
+             local:img-kit;\n\nworld img-kit {\n  export blur2d: func(px: list<u8>, width: u32) \
+             -> list<u8>;\n  export is-ready: func(flag: bool) -> bool;\n  export describe: \
+             func(%list: string) -> string;\n  export img-count: func(s: string) -> u32;\n}\n\n// \
+             Not exported (no WIT equivalent for some types): fill\n"
+        );
+    }
+}
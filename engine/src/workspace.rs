@@ -0,0 +1,139 @@
+//! Workspace members using autozig
+//!
+//! [`AutoZigEngine::build_workspace`](crate::AutoZigEngine::build_workspace)
+//! compiles the shared Zig modules of every member in one pass. Members are
+//! the packages listed in `[workspace] members` of the workspace manifest
+//! (trailing `/*` globs included, `exclude` honored) that have
+//! `autozig-build` among their build dependencies.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+
+/// A workspace member using autozig
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// Package name
+    pub name: String,
+    /// Directory of the member's Cargo.toml
+    pub dir: PathBuf,
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Table> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Directory of the workspace manifest above `manifest_dir` (itself
+/// included), if the crate belongs to a workspace
+pub fn root(manifest_dir: &Path) -> Option<PathBuf> {
+    manifest_dir
+        .ancestors()
+        .find(|dir| {
+            read_manifest(&dir.join("Cargo.toml"))
+                .is_ok_and(|manifest| manifest.contains_key("workspace"))
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Members of the workspace at `root` using autozig, sorted by name
+pub fn members(root: &Path) -> Result<Vec<Member>> {
+    let manifest = read_manifest(&root.join("Cargo.toml"))?;
+    let workspace = manifest.get("workspace").and_then(|w| w.as_table());
+    let paths = |key: &str| -> Vec<String> {
+        workspace
+            .and_then(|w| w.get(key))
+            .and_then(|list| list.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.as_str().map(str::to_string))
+            .collect()
+    };
+    let excluded: Vec<PathBuf> = paths("exclude")
+        .iter()
+        .map(|path| root.join(path))
+        .collect();
+
+    let mut dirs = Vec::new();
+    for path in paths("members") {
+        match path.strip_suffix("/*") {
+            Some(parent) => {
+                let Ok(entries) = fs::read_dir(root.join(parent)) else {
+                    continue;
+                };
+                dirs.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()));
+            },
+            None => dirs.push(root.join(path)),
+        }
+    }
+    if manifest.contains_key("package") {
+        dirs.push(root.to_path_buf());
+    }
+
+    let mut members = Vec::new();
+    for dir in dirs {
+        if excluded.contains(&dir) {
+            continue;
+        }
+        let Ok(manifest) = read_manifest(&dir.join("Cargo.toml")) else {
+            continue;
+        };
+        let uses_autozig = manifest
+            .get("build-dependencies")
+            .and_then(|deps| deps.as_table())
+            .is_some_and(|deps| deps.contains_key("autozig-build"));
+        let name = manifest
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str());
+        if let (true, Some(name)) = (uses_autozig, name) {
+            members.push(Member { name: name.to_string(), dir });
+        }
+    }
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    members.dedup();
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_members() {
+        let dir = env::temp_dir().join(format!("autozig-workspace-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let write = |path: &str, text: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"img\", \"crates/*\"]\nexclude = [\"crates/old\"]\n",
+        );
+        let uses = "[build-dependencies]\nautozig-build = \"0.1\"\n";
+        write("img/Cargo.toml", &format!("[package]\nname = \"img\"\n{uses}"));
+        write("crates/audio/Cargo.toml", &format!("[package]\nname = \"audio\"\n{uses}"));
+        write("crates/old/Cargo.toml", &format!("[package]\nname = \"old\"\n{uses}"));
+        write("crates/cli/Cargo.toml", "[package]\nname = \"cli\"\n");
+
+        assert_eq!(root(&dir.join("crates/audio")).unwrap(), dir);
+        let names: Vec<String> = members(&dir).unwrap().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, vec!["audio", "img"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,17 @@
 //! Zig compiler wrapper with target support
 
 use std::{
-    path::Path,
-    process::Command,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Command,
+        Output,
+    },
+    sync::OnceLock,
+    thread,
+    time::Duration,
 };
 
 use anyhow::{
@@ -10,17 +19,416 @@ use anyhow::{
     Result,
 };
 
+use crate::{
+    diagnostics,
+    targets::ZigTarget,
+};
+
+/// Zig optimization mode (`-O`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZigOptimize {
+    /// No optimizations, all safety checks
+    Debug,
+    /// Optimized with safety checks
+    ReleaseSafe,
+    /// Optimized for speed, no safety checks
+    ReleaseFast,
+    /// Optimized for size, no safety checks
+    ReleaseSmall,
+}
+
+impl ZigOptimize {
+    /// Name as accepted by `-O` and `std.builtin.OptimizeMode`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ZigOptimize::Debug => "Debug",
+            ZigOptimize::ReleaseSafe => "ReleaseSafe",
+            ZigOptimize::ReleaseFast => "ReleaseFast",
+            ZigOptimize::ReleaseSmall => "ReleaseSmall",
+        }
+    }
+
+    /// Mode named as in `-O`
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ZigOptimize::Debug,
+            ZigOptimize::ReleaseSafe,
+            ZigOptimize::ReleaseFast,
+            ZigOptimize::ReleaseSmall,
+        ]
+        .into_iter()
+        .find(|optimize| optimize.as_str() == name)
+    }
+}
+
+/// WASI execution model of the final module
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WasiExecModel {
+    /// A program: the host runs `_start`, which calls `main` and exits
+    #[default]
+    Command,
+    /// A library: the host calls `_initialize` once, then any export
+    Reactor,
+}
+
+impl WasiExecModel {
+    /// Name as accepted by `-mexec-model` and `std.builtin.WasiExecModel`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WasiExecModel::Command => "command",
+            WasiExecModel::Reactor => "reactor",
+        }
+    }
+}
+
+/// Kind of library the crate's Zig code is compiled into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// A static archive, linked into the crate
+    #[default]
+    Staticlib,
+    /// A shared library (`.so`, `.dylib`, `.dll`), not linked into the crate
+    /// but loaded at runtime (e.g. with `libloading`)
+    Cdylib,
+}
+
+impl ArtifactKind {
+    /// Flag selecting the kind for `zig build-lib`
+    fn flag(self) -> &'static str {
+        match self {
+            ArtifactKind::Staticlib => "-static",
+            ArtifactKind::Cdylib => "-dynamic",
+        }
+    }
+
+    /// Name as accepted by `linkage` in build.zig
+    pub fn linkage(self) -> &'static str {
+        match self {
+            ArtifactKind::Staticlib => "static",
+            ArtifactKind::Cdylib => "dynamic",
+        }
+    }
+
+    /// File name of the library `name` on the target OS `os` (as in
+    /// `CARGO_CFG_TARGET_OS`)
+    pub fn file_name(self, name: &str, os: &str) -> String {
+        match self {
+            ArtifactKind::Staticlib => format!("lib{name}.a"),
+            ArtifactKind::Cdylib if os == "windows" => format!("{name}.dll"),
+            ArtifactKind::Cdylib if matches!(os, "macos" | "ios" | "tvos" | "watchos") => {
+                format!("lib{name}.dylib")
+            },
+            ArtifactKind::Cdylib => format!("lib{name}.so"),
+        }
+    }
+}
+
+/// Runtime instrumentation of the crate's Zig and C code, matching Rust's
+/// `-Zsanitizer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    /// AddressSanitizer: C sources are compiled with `-fsanitize=address`.
+    /// Zig has no ASan instrumentation of its own, so Zig code keeps its
+    /// runtime safety checks (bounds, overflow, null) and its allocations go
+    /// through the instrumented `malloc`.
+    Address,
+    /// ThreadSanitizer (`-fsanitize-thread`), for Zig and C code
+    Thread,
+    /// UndefinedBehaviorSanitizer for C code (`-fsanitize-c`)
+    Undefined,
+}
+
+impl Sanitizer {
+    /// Name as accepted by `-Zsanitizer` and `-fsanitize`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Undefined => "undefined",
+        }
+    }
+
+    /// The sanitizer Rust code is built with, from `CARGO_ENCODED_RUSTFLAGS`
+    /// (`-Zsanitizer=<name>` or `-Z sanitizer=<name>`)
+    pub fn from_rustflags(encoded: &str) -> Option<Sanitizer> {
+        let flags: Vec<&str> = encoded.split('\x1f').collect();
+        flags.iter().enumerate().find_map(|(i, flag)| {
+            let name = match flag.strip_prefix("-Zsanitizer=") {
+                Some(name) => name,
+                None if *flag == "-Z" => flags.get(i + 1)?.strip_prefix("sanitizer=")?,
+                None => return None,
+            };
+            match name {
+                "address" => Some(Sanitizer::Address),
+                "thread" => Some(Sanitizer::Thread),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Code generation flags of the crate's Zig library, applied to both
+/// `zig build-lib` and generated build.zig files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodegenFlags {
+    /// `None` keeps the defaults: `ReleaseFast`, except build.zig builds for
+    /// native targets, which use `zig build`'s standard option (Debug).
+    /// Debug builds of WASM code cannot use std containers, which need
+    /// thread and POSIX support in Debug mode.
+    pub optimize: Option<ZigOptimize>,
+    /// Omit debug information (`-fstrip`)
+    pub strip: bool,
+    /// Assume a single thread (`-fsingle-threaded`): no atomics or locks in
+    /// the standard library
+    pub single_threaded: bool,
+    /// Execution model for WASI targets, visible to Zig code as
+    /// `builtin.wasi_exec_model`
+    pub wasi_exec_model: WasiExecModel,
+    /// Compile WASM code with atomics and bulk memory, so it can run on a
+    /// shared memory across Web Workers
+    pub wasm_threads: bool,
+    /// Instrument the code with a sanitizer; the default optimization mode
+    /// becomes `ReleaseSafe`, which keeps Zig's safety checks
+    pub sanitizer: Option<Sanitizer>,
+    /// Keep frame pointers and error return traces, so Zig panics and
+    /// errors reach Rust with their stack traces (native targets only); the
+    /// default optimization mode becomes `ReleaseSafe`
+    pub stack_traces: bool,
+    /// Produce byte-identical libraries from the same sources: Zig code
+    /// without debug information (Zig has no prefix map for the paths it
+    /// embeds), C sources with their build directory mapped to `.`
+    /// (`-ffile-prefix-map`), and archives normalized by
+    /// [`archive::normalize`](crate::archive::normalize)
+    pub reproducible: bool,
+    /// Build the Zig code for a `#![no_std]` crate: no libc, position
+    /// dependent code and no stack protector on native targets. Always set
+    /// for bare-metal targets. `AutoZigBytes`
+    /// and `g_allocator` then allocate through Rust's global allocator once
+    /// `autozig::allocator::install()` ran, and fail before.
+    pub freestanding: bool,
+}
+
+impl CodegenFlags {
+    /// Optimization mode when [`optimize`](Self::optimize) is not set:
+    /// `ReleaseSafe` under a sanitizer or with stack traces, `default`
+    /// otherwise
+    pub fn optimize_or(&self, default: ZigOptimize) -> ZigOptimize {
+        match (self.optimize, self.sanitizer) {
+            (Some(optimize), _) => optimize,
+            (None, Some(_)) => ZigOptimize::ReleaseSafe,
+            (None, None) if self.stack_traces => ZigOptimize::ReleaseSafe,
+            (None, None) => default,
+        }
+    }
+
+    /// Arguments for `zig build-lib`, ending with the flags of the C sources
+    /// that follow them
+    fn args(&self) -> Vec<&'static str> {
+        let optimize = self.optimize_or(ZigOptimize::ReleaseFast);
+        let mut args = vec!["-O", optimize.as_str()];
+        if self.strip || self.reproducible {
+            args.push("-fstrip");
+        }
+        if self.single_threaded {
+            args.push("-fsingle-threaded");
+        }
+        if self.stack_traces {
+            args.extend(["-fno-omit-frame-pointer", "-ferror-tracing"]);
+        }
+        if let Some(sanitizer) = self.sanitizer {
+            if !self.stack_traces {
+                args.push("-fno-omit-frame-pointer");
+            }
+            args.push(match sanitizer {
+                Sanitizer::Thread => "-fsanitize-thread",
+                Sanitizer::Address | Sanitizer::Undefined => "-fsanitize-c",
+            });
+        }
+        args
+    }
+
+    /// `-cflags ... --` for the C sources following them, mapping the
+    /// directories in `source_dirs` to `.` for reproducible builds
+    fn c_args(&self, source_dirs: &[&Path]) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.sanitizer == Some(Sanitizer::Address) {
+            flags.push("-fsanitize=address".to_string());
+        }
+        if self.reproducible {
+            flags.extend(
+                source_dirs
+                    .iter()
+                    .map(|dir| format!("-ffile-prefix-map={}=.", dir.display())),
+            );
+        }
+        if flags.is_empty() {
+            return flags;
+        }
+        flags.insert(0, "-cflags".to_string());
+        flags.push("--".to_string());
+        flags
+    }
+
+    /// Arguments for `zig build-lib` on native targets: PIC and libc, or
+    /// neither for freestanding builds
+    fn native_args(&self) -> &'static [&'static str] {
+        if self.freestanding {
+            &["-fno-stack-protector"]
+        } else {
+            &["-fPIC", "-lc"]
+        }
+    }
+
+    /// CPU features for WASM targets: SIMD128, plus atomics and bulk memory
+    /// for shared memory
+    pub fn wasm_cpu(&self) -> &'static str {
+        if self.wasm_threads {
+            "mvp+simd128+atomics+bulk_memory"
+        } else {
+            "mvp+simd128"
+        }
+    }
+
+    /// Additional arguments for `zig build-lib` on WASI targets
+    ///
+    /// Zig code links wasi-libc, as Rust's std does, so `std.fs` resolves
+    /// paths against the preopened directories the same way `std::fs` does.
+    fn wasi_args(&self) -> [String; 2] {
+        ["-lc".to_string(), format!("-mexec-model={}", self.wasi_exec_model.as_str())]
+    }
+}
+
+/// Zig executable selected by [`crate::toolchain`], overriding `ZIG_PATH`
+static PINNED_ZIG: OnceLock<String> = OnceLock::new();
+
+/// Run every compiler created from now on with the Zig at `path`
+pub(crate) fn use_zig(path: String) {
+    // A build pins at most one version, so a second call has the same path
+    let _ = PINNED_ZIG.set(path);
+}
+
 /// Wrapper for invoking the Zig compiler
 pub struct ZigCompiler {
     zig_path: String,
+    /// `-mcpu` for direct compiler invocations (`zig build` takes the CPU
+    /// from build.zig)
+    cpu: Option<String>,
+    /// Arguments locating the target's SDK, passed to every invocation
+    sdk_args: Vec<String>,
+    /// Optimization and code generation flags for `build-lib`
+    codegen: CodegenFlags,
+    /// Local cache directory (`--cache-dir`); `None` leaves the choice to Zig
+    cache_dir: Option<PathBuf>,
+    /// System libraries linked into `build-lib` outputs (`-l<name>`)
+    system_libs: Vec<String>,
+    /// Header search paths (`-I<dir>`)
+    include_dirs: Vec<PathBuf>,
+    /// Static or shared library output of `build-lib`
+    artifact: ArtifactKind,
 }
 
 impl ZigCompiler {
     /// Create a new Zig compiler wrapper
     pub fn new() -> Self {
-        // Check for ZIG_PATH environment variable, otherwise use "zig"
-        let zig_path = std::env::var("ZIG_PATH").unwrap_or_else(|_| "zig".to_string());
-        Self { zig_path }
+        // Pinned toolchain, then the ZIG_PATH environment variable, otherwise "zig"
+        let zig_path = PINNED_ZIG
+            .get()
+            .cloned()
+            .or_else(|| std::env::var("ZIG_PATH").ok())
+            .unwrap_or_else(|| "zig".to_string());
+        Self {
+            zig_path,
+            cpu: None,
+            sdk_args: Vec::new(),
+            codegen: CodegenFlags::default(),
+            cache_dir: None,
+            system_libs: Vec::new(),
+            include_dirs: Vec::new(),
+            artifact: ArtifactKind::default(),
+        }
+    }
+
+    /// Create a wrapper that compiles for `target`
+    ///
+    /// Fails if the target needs a platform SDK that cannot be found; files
+    /// describing the SDK to Zig are written to `work_dir`.
+    pub fn for_target(target: &ZigTarget, work_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            cpu: target.cpu.map(str::to_string),
+            sdk_args: target.sdk_args(work_dir)?,
+            codegen: CodegenFlags {
+                freestanding: target.is_bare_metal(),
+                ..CodegenFlags::default()
+            },
+            ..Self::new()
+        })
+    }
+
+    /// Compile libraries with `codegen` instead of the default flags
+    ///
+    /// Libraries for bare-metal targets stay freestanding.
+    pub fn with_codegen(mut self, codegen: CodegenFlags) -> Self {
+        self.codegen = CodegenFlags {
+            freestanding: codegen.freestanding || self.codegen.freestanding,
+            ..codegen
+        };
+        self
+    }
+
+    /// Keep Zig's local cache in `dir` instead of next to the build
+    ///
+    /// The global cache (`ZIG_GLOBAL_CACHE_DIR`, else `~/.cache/zig`) is
+    /// left to Zig.
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Link the system libraries `libs` and search `include_dirs` for the
+    /// headers of C code and `@cImport`
+    pub fn with_system_deps(mut self, libs: &[String], include_dirs: &[PathBuf]) -> Self {
+        self.system_libs = libs.to_vec();
+        self.include_dirs = include_dirs.to_vec();
+        self
+    }
+
+    /// Build shared instead of static libraries with `build-lib`
+    pub fn with_artifact(mut self, artifact: ArtifactKind) -> Self {
+        self.artifact = artifact;
+        self
+    }
+
+    /// Add the system libraries to a `zig build-lib`
+    fn library_args(&self, cmd: &mut Command) {
+        for lib in &self.system_libs {
+            cmd.arg(format!("-l{lib}"));
+        }
+    }
+
+    /// Make a built static archive deterministic in reproducible builds
+    fn normalize_archive(&self, output_lib: &Path) -> Result<()> {
+        if self.codegen.reproducible && self.artifact == ArtifactKind::Staticlib {
+            crate::archive::normalize(output_lib)?;
+        }
+        Ok(())
+    }
+
+    /// Add `--cache-dir` to a compiler or `zig build` invocation
+    fn cache_args(&self, cmd: &mut Command) {
+        if let Some(dir) = &self.cache_dir {
+            cmd.arg("--cache-dir").arg(dir);
+        }
+    }
+
+    /// Add the target CPU and SDK arguments to a `zig build-lib`/`build-obj`
+    fn target_args(&self, cmd: &mut Command) {
+        if let Some(cpu) = &self.cpu {
+            cmd.arg(format!("-mcpu={}", cpu));
+        }
+        cmd.args(&self.sdk_args);
+        for dir in &self.include_dirs {
+            cmd.arg(format!("-I{}", dir.display()));
+        }
     }
 
     /// Check Zig compiler version
@@ -73,10 +481,11 @@ impl ZigCompiler {
         let mut cmd = Command::new(&self.zig_path);
         cmd.arg("build-lib")
             .arg(source)
-            .arg("-static")
+            .arg(self.artifact.flag())
             .arg(format!("-femit-bin={}", output_lib.display()))
             .arg("-target")
             .arg(target);
+        self.target_args(&mut cmd);
 
         if is_wasm {
             // WASM 特殊配置
@@ -87,36 +496,41 @@ impl ZigCompiler {
 
             // 🚀 启用 WASM SIMD128 支持（关键性能优化！）
             // 这将允许使用 v128.load, v128.sub, v128.store 等 SIMD 指令
-            cmd.arg("-mcpu=mvp+simd128");
+            cmd.arg(format!("-mcpu={}", self.codegen.wasm_cpu()));
 
-            // WASM 优化：改用 ReleaseFast 以获得最佳性能
+            // WASM 优化：默认使用 ReleaseFast 以获得最佳性能
             // (ReleaseSmall 会禁用某些 SIMD 优化)
-            cmd.arg("-O").arg("ReleaseFast");
+            cmd.args(self.codegen.args());
 
             // 不链接 libc（freestanding 环境）
-            // WASM 环境下没有标准的 libc
+            // WASM 环境下没有标准的 libc；WASI 链接 wasi-libc
+            if target.contains("wasi") {
+                cmd.args(self.codegen.wasi_args());
+            }
         } else {
             // 非 WASM 目标的标准配置
             // Generate Position Independent Code (required for PIE executables)
-            cmd.arg("-fPIC");
-
-            // Link with libc (required for c_allocator and other libc functions)
-            cmd.arg("-lc");
+            // and link with libc (required for c_allocator and other libc
+            // functions), unless freestanding
+            cmd.args(self.codegen.native_args());
+            self.library_args(&mut cmd);
 
             // Optimize for release builds
-            cmd.arg("-O").arg("ReleaseFast");
+            cmd.args(self.codegen.args());
         }
 
+        let source_dir = source.parent().unwrap_or(Path::new("."));
+        cmd.args(self.codegen.c_args(&[source_dir]));
+
         // 添加所有 C 源文件到编译命令（WASM 也支持 C 文件）
         for c_file in &c_sources {
             cmd.arg(c_file);
         }
 
-        let status = cmd.status().context("Failed to execute zig build-lib")?;
-
-        if !status.success() {
-            anyhow::bail!("Zig compilation failed");
-        }
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        check_compile_output(&cmd, &output, "Zig compilation failed")?;
+        self.normalize_archive(output_lib)?;
 
         println!("cargo:warning=Zig compilation successful");
         println!("cargo:warning=Library: {}", output_lib.display());
@@ -161,33 +575,38 @@ impl ZigCompiler {
         let mut cmd = Command::new(&self.zig_path);
         cmd.arg("build-lib")
             .arg(source)
-            .arg("-static")
+            .arg(self.artifact.flag())
             .arg(format!("-femit-bin={}", output_lib.display()))
             .arg("-target")
             .arg(target);
+        self.target_args(&mut cmd);
 
         if is_wasm {
             // WASM 特殊配置
             cmd.arg("-fno-stack-protector")
                 // 🚀 启用 WASM SIMD128 支持
-                .arg("-mcpu=mvp+simd128")
-                .arg("-O")
-                .arg("ReleaseFast");
+                .arg(format!("-mcpu={}", self.codegen.wasm_cpu()));
+            if target.contains("wasi") {
+                cmd.args(self.codegen.wasi_args());
+            }
         } else {
             // 非 WASM 目标的标准配置
-            cmd.arg("-fPIC").arg("-lc").arg("-O").arg("ReleaseFast");
+            cmd.args(self.codegen.native_args());
+            self.library_args(&mut cmd);
         }
+        cmd.args(self.codegen.args());
+        let source_dir = source.parent().unwrap_or(Path::new("."));
+        cmd.args(self.codegen.c_args(&[source_dir, src_dir]));
 
         // 添加所有 C 源文件到编译命令
         for c_file in &c_sources {
             cmd.arg(c_file);
         }
 
-        let status = cmd.status().context("Failed to execute zig build-lib")?;
-
-        if !status.success() {
-            anyhow::bail!("Zig compilation failed");
-        }
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        check_compile_output(&cmd, &output, "Zig compilation failed")?;
+        self.normalize_archive(output_lib)?;
 
         println!("cargo:warning=Zig compilation successful");
         println!("cargo:warning=Library: {}", output_lib.display());
@@ -256,21 +675,19 @@ impl ZigCompiler {
         println!("cargo:warning=Compiling Zig tests: {} for target: {}", source.display(), target);
 
         // zig test source.zig -femit-bin=output_exe -target <target>
-        let status = Command::new(&self.zig_path)
-            .arg("test")
+        let mut cmd = Command::new(&self.zig_path);
+        cmd.arg("test")
             .arg(source)
             .arg(format!("-femit-bin={}", output_exe.display()))
             .arg("-target")
             .arg(target)
             // Optimize for release builds
             .arg("-O")
-            .arg("ReleaseFast")
-            .status()
-            .context("Failed to execute zig test")?;
+            .arg("ReleaseFast");
 
-        if !status.success() {
-            anyhow::bail!("Zig test compilation failed");
-        }
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        check_compile_output(&cmd, &output, "Zig test compilation failed")?;
 
         println!("cargo:warning=Zig test compilation successful");
         println!("cargo:warning=Test executable: {}", output_exe.display());
@@ -297,6 +714,73 @@ impl ZigCompiler {
         Ok(format!("Stdout: {}\nStderr: {}", stdout, stderr))
     }
 
+    /// Collect stack usage diagnostics for a Zig source file
+    ///
+    /// Builds an object file with `-fstack-report` using the same optimization
+    /// level as the static library, and returns the compiler's diagnostic
+    /// output for [`crate::stack_report::StackReport::parse`].
+    pub fn stack_usage(&self, source: &Path, output_obj: &Path, target: &str) -> Result<String> {
+        let mut cmd = Command::new(&self.zig_path);
+        cmd.arg("build-obj")
+            .arg(source)
+            .arg(format!("-femit-bin={}", output_obj.display()))
+            .arg("-target")
+            .arg(target)
+            .arg("-O")
+            .arg("ReleaseFast")
+            .arg("-fstack-report");
+        self.target_args(&mut cmd);
+
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        if !output.status.success() {
+            return Err(failure(&cmd, &output, "Zig stack usage analysis failed"));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        Ok(format!("{}\n{}", stdout, stderr))
+    }
+
+    /// Build a Zig source file into an object file for `target`
+    pub fn build_object(&self, source: &Path, output_obj: &Path, target: &str) -> Result<()> {
+        let mut cmd = Command::new(&self.zig_path);
+        cmd.arg("build-obj")
+            .arg(source)
+            .arg(format!("-femit-bin={}", output_obj.display()))
+            .arg("-target")
+            .arg(target);
+        self.target_args(&mut cmd);
+
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        if !output.status.success() {
+            return Err(failure(&cmd, &output, "Zig object compilation failed"));
+        }
+        Ok(())
+    }
+
+    /// Compile the compute kernel in a Zig source file into a SPIR-V module
+    ///
+    /// GPU code has no safety checks or sanitizers: it is built `ReleaseFast`
+    /// unless the optimization mode is set explicitly.
+    pub fn compile_spirv(&self, source: &Path, output: &Path, target: &str) -> Result<()> {
+        let optimize = self.codegen.optimize.unwrap_or(ZigOptimize::ReleaseFast);
+        let mut cmd = Command::new(&self.zig_path);
+        cmd.arg("build-obj")
+            .arg(source)
+            .arg(format!("-femit-bin={}", output.display()))
+            .arg("-target")
+            .arg(target)
+            .arg("-O")
+            .arg(optimize.as_str());
+        self.target_args(&mut cmd);
+
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
+        check_compile_output(&cmd, &output, "SPIR-V kernel compilation failed")
+    }
+
     /// Compile using build.zig file
     ///
     /// # Arguments
@@ -318,24 +802,30 @@ impl ZigCompiler {
             .arg(build_file)
             .arg("--prefix")
             .arg(build_dir)
+            .args(&self.sdk_args)
             .current_dir(build_dir);
 
         println!("cargo:warning=Running: {:?}", cmd);
 
-        let output = cmd.output().context("Failed to execute zig build")?;
-
+        self.cache_args(&mut cmd);
+        let output = run_zig(&mut cmd)?;
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            anyhow::bail!("Zig build failed:\nStdout: {}\nStderr: {}", stdout, stderr);
+            return Err(failure(&cmd, &output, "Zig build failed"));
         }
 
         // The output library should be in build_dir/zig-out/lib/libautozig.a (Zig
-        // 0.15.2+) Try multiple possible locations in order
+        // 0.15.2+) Try multiple possible locations in order. Windows DLLs are
+        // installed to bin/.
+        let built_name = match output_lib.extension().and_then(|ext| ext.to_str()) {
+            Some("dll") => "autozig.dll".to_string(),
+            Some(ext) => format!("libautozig.{ext}"),
+            None => "libautozig.a".to_string(),
+        };
         let possible_paths = vec![
-            build_dir.join("zig-out").join("lib").join("libautozig.a"), // Zig 0.15.2+
-            build_dir.join("lib").join("libautozig.a"),                 // Older Zig
-            build_dir.join("libautozig.a"),                             // Direct output
+            build_dir.join("zig-out").join("lib").join(&built_name), // Zig 0.15.2+
+            build_dir.join("zig-out").join("bin").join(&built_name), // DLLs
+            build_dir.join("lib").join(&built_name),                 // Older Zig
+            build_dir.join(&built_name),                             // Direct output
         ];
 
         let mut found = false;
@@ -362,6 +852,7 @@ impl ZigCompiler {
             );
         }
 
+        self.normalize_archive(output_lib)?;
         println!("cargo:warning=Build.zig compilation successful");
         println!("cargo:warning=Library: {}", output_lib.display());
 
@@ -369,6 +860,137 @@ impl ZigCompiler {
     }
 }
 
+/// Attempts for a Zig invocation failing with a transient cache error
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled for each further one
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Run a Zig command, retrying failures caused by cache contention
+///
+/// Parallel builds of a workspace share Zig's global cache, and concurrent
+/// writers occasionally trip over each other's files.
+fn run_zig(cmd: &mut Command) -> Result<Output> {
+    let mut attempt = 1;
+    loop {
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to execute {}", command_line(cmd)))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() || attempt == MAX_ATTEMPTS || !is_transient_failure(&stderr) {
+            return Ok(output);
+        }
+
+        let reason = stderr
+            .lines()
+            .find(|line| line.contains("error"))
+            .unwrap_or_default();
+        println!(
+            "cargo:warning=Transient Zig failure (attempt {}/{}), retrying: {}",
+            attempt, MAX_ATTEMPTS, reason
+        );
+        thread::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1));
+        attempt += 1;
+    }
+}
+
+/// Whether a failed Zig run looks like cache contention rather than an
+/// error in the sources
+fn is_transient_failure(stderr: &str) -> bool {
+    const ALWAYS: &[&str] = &[
+        "FileBusy",
+        "PathAlreadyExists",
+        "SharingViolation",
+        "LockViolation",
+        "WouldBlock",
+    ];
+    const IN_CACHE: &[&str] = &["FileNotFound", "AccessDenied", "Unexpected", "FileTooBig"];
+
+    stderr.lines().any(|line| {
+        ALWAYS.iter().any(|error| line.contains(error))
+            || (line.contains("cache") && IN_CACHE.iter().any(|error| line.contains(error)))
+    })
+}
+
+/// Shell-like rendering of a command line
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("'{}'", arg)
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Environment of a command relative to the build script's: variables it
+/// sets (`+`) or removes (`-`), then inherited `ZIG_*` variables
+fn environment_diff(cmd: &Command) -> Vec<String> {
+    let mut lines: Vec<String> = cmd
+        .get_envs()
+        .map(|(key, value)| match value {
+            Some(value) => format!("+{}={}", key.to_string_lossy(), value.to_string_lossy()),
+            None => format!("-{}", key.to_string_lossy()),
+        })
+        .collect();
+    let mut inherited: Vec<String> = std::env::vars()
+        .filter(|(key, _)| key.starts_with("ZIG_"))
+        .filter(|(key, _)| !cmd.get_envs().any(|(set, _)| set == key.as_str()))
+        .map(|(key, value)| format!(" {}={}", key, value))
+        .collect();
+    inherited.sort();
+    lines.extend(inherited);
+    lines
+}
+
+/// Error for a failed Zig run: command line, environment, working directory
+/// and the complete output, with diagnostics mapped back to Rust files
+fn failure(cmd: &Command, output: &Output, message: &str) -> anyhow::Error {
+    let mut report = format!("{} ({})\ncommand: {}\n", message, output.status, command_line(cmd));
+    if let Some(dir) = cmd.get_current_dir() {
+        report.push_str(&format!("working directory: {}\n", dir.display()));
+    }
+    let env = environment_diff(cmd);
+    if !env.is_empty() {
+        report.push_str("environment:\n");
+        for line in env {
+            report.push_str(&format!("  {}\n", line));
+        }
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        report.push_str(&format!("stdout:\n{}\n", stdout.trim_end()));
+    }
+    report.push_str(&format!(
+        "stderr:\n{}",
+        remap_zig_errors(&String::from_utf8_lossy(&output.stderr))
+    ));
+    anyhow::anyhow!(report)
+}
+
+/// Forward compiler output and fail with errors mapped back to Rust files
+fn check_compile_output(cmd: &Command, output: &Output, message: &str) -> Result<()> {
+    if output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        return Ok(());
+    }
+    Err(failure(cmd, output, message))
+}
+
+/// Map embedded-code diagnostics to their Rust locations, surfacing each
+/// error as a cargo warning so it shows up in `cargo build` output
+fn remap_zig_errors(stderr: &str) -> String {
+    let remapped = diagnostics::remap_diagnostics(stderr);
+    for line in remapped.lines().filter(|line| line.contains(": error: ")) {
+        println!("cargo:warning={}", line);
+    }
+    remapped
+}
+
 impl Default for ZigCompiler {
     fn default() -> Self {
         Self::new()
@@ -385,13 +1007,106 @@ mod tests {
         assert!(!compiler.zig_path.is_empty());
     }
 
+    #[test]
+    fn test_codegen_args() {
+        assert_eq!(CodegenFlags::default().args(), ["-O", "ReleaseFast"]);
+        let codegen = CodegenFlags {
+            optimize: Some(ZigOptimize::ReleaseSmall),
+            strip: true,
+            single_threaded: true,
+            wasi_exec_model: WasiExecModel::Reactor,
+            wasm_threads: true,
+            sanitizer: None,
+            stack_traces: false,
+            reproducible: false,
+            freestanding: false,
+        };
+        assert_eq!(codegen.args(), ["-O", "ReleaseSmall", "-fstrip", "-fsingle-threaded"]);
+        assert_eq!(codegen.wasi_args(), ["-lc", "-mexec-model=reactor"]);
+        assert_eq!(codegen.wasm_cpu(), "mvp+simd128+atomics+bulk_memory");
+        assert_eq!(CodegenFlags::default().wasm_cpu(), "mvp+simd128");
+
+        let asan = CodegenFlags {
+            sanitizer: Some(Sanitizer::Address),
+            ..Default::default()
+        };
+        assert_eq!(asan.args(), ["-O", "ReleaseSafe", "-fno-omit-frame-pointer", "-fsanitize-c"]);
+        assert_eq!(asan.c_args(&[]), ["-cflags", "-fsanitize=address", "--"]);
+        assert!(CodegenFlags::default()
+            .c_args(&[Path::new("/out")])
+            .is_empty());
+        let reproducible = CodegenFlags { reproducible: true, ..Default::default() };
+        assert_eq!(reproducible.args(), ["-O", "ReleaseFast", "-fstrip"]);
+        assert_eq!(
+            reproducible.c_args(&[Path::new("/out")]),
+            ["-cflags", "-ffile-prefix-map=/out=.", "--"]
+        );
+        let traced = CodegenFlags { stack_traces: true, ..Default::default() };
+        assert_eq!(
+            traced.args(),
+            ["-O", "ReleaseSafe", "-fno-omit-frame-pointer", "-ferror-tracing"]
+        );
+        assert_eq!(CodegenFlags::default().native_args(), ["-fPIC", "-lc"]);
+        let freestanding = CodegenFlags { freestanding: true, ..Default::default() };
+        assert_eq!(freestanding.native_args(), ["-fno-stack-protector"]);
+        assert_eq!(Sanitizer::from_rustflags("-Zsanitizer=address"), Some(Sanitizer::Address));
+        assert_eq!(
+            Sanitizer::from_rustflags("-Copt-level=1\x1f-Z\x1fsanitizer=thread"),
+            Some(Sanitizer::Thread)
+        );
+        assert_eq!(Sanitizer::from_rustflags("-Copt-level=1"), None);
+    }
+
+    #[test]
+    fn test_artifact_kind() {
+        assert_eq!(ArtifactKind::Staticlib.file_name("demo", "windows"), "libdemo.a");
+        assert_eq!(ArtifactKind::Cdylib.file_name("demo", "linux"), "libdemo.so");
+        assert_eq!(ArtifactKind::Cdylib.file_name("demo", "macos"), "libdemo.dylib");
+        assert_eq!(ArtifactKind::Cdylib.file_name("demo", "windows"), "demo.dll");
+        assert_eq!(ArtifactKind::Cdylib.flag(), "-dynamic");
+        assert_eq!(ArtifactKind::Cdylib.linkage(), "dynamic");
+    }
+
+    #[test]
+    fn test_transient_failures() {
+        assert!(is_transient_failure(
+            "error: unable to update file '/home/u/.cache/zig/o/ab12/x.o': FileBusy"
+        ));
+        assert!(is_transient_failure(
+            "error: unable to open '/tmp/zig-cache/h/cd34.txt': FileNotFound"
+        ));
+        assert!(!is_transient_failure("src/lib.rs:3:5: error: expected ';' after statement"));
+        assert!(!is_transient_failure("error: FileNotFound: src/missing.zig"));
+    }
+
+    #[test]
+    fn test_failure_report() {
+        let mut cmd = Command::new("zig");
+        cmd.arg("build-lib")
+            .arg("my file.zig")
+            .env("ZIG_GLOBAL_CACHE_DIR", "/tmp/zc")
+            .env_remove("ZIG_LIB_DIR");
+        assert_eq!(command_line(&cmd), "zig build-lib 'my file.zig'");
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg("echo partial; echo 'error: boom' >&2; exit 1")
+            .output()
+            .unwrap();
+        let report = failure(&cmd, &output, "Zig compilation failed").to_string();
+        assert!(report.starts_with("Zig compilation failed (exit status: 1)\n"));
+        assert!(report.contains("command: zig build-lib 'my file.zig'\n"));
+        assert!(report.contains("  +ZIG_GLOBAL_CACHE_DIR=/tmp/zc\n  -ZIG_LIB_DIR\n"));
+        assert!(report.contains("stdout:\npartial\n"));
+        assert!(report.ends_with("stderr:\nerror: boom\n"));
+    }
+
     #[test]
     #[ignore] // Only run if Zig is installed
     fn test_check_version() {
         let compiler = ZigCompiler::new();
-        let version = compiler.check_version();
-        if version.is_ok() {
-            println!("Zig version: {}", version.unwrap());
+        if let Ok(version) = compiler.check_version() {
+            println!("Zig version: {}", version);
         }
     }
 }
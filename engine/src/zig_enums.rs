@@ -0,0 +1,250 @@
+//! Zig enums generated from the Rust enums of an `autozig!` block
+//!
+//! A fieldless enum with an integer `#[repr]` declared next to the Rust
+//! signatures is the single source of truth for both languages: the engine
+//! adds the matching Zig declaration to the block's Zig code, so variants and
+//! discriminants cannot drift apart.
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     export fn next_status(current: Status) Status { ... }
+//!     ---
+//!     #[repr(u8)]
+//!     pub enum Status { Idle = 0, Running, Stopped = 10 }
+//!     fn next_status(current: Status) -> Status;
+//! }
+//! ```
+//!
+//! becomes, on the Zig side, `pub const Status = enum(u8) { Idle = 0,
+//! Running, Stopped = 10 };` (Zig numbers implicit tags like Rust does).
+//! Enums the Zig code already declares are left alone.
+
+use syn::ItemEnum;
+
+/// Rust `#[repr]` integer types, all spelled the same in Zig
+const INT_REPRS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+const ZIG_KEYWORDS: &[&str] = &[
+    "addrspace",
+    "align",
+    "allowzero",
+    "and",
+    "anyframe",
+    "anytype",
+    "asm",
+    "break",
+    "callconv",
+    "catch",
+    "comptime",
+    "const",
+    "continue",
+    "defer",
+    "else",
+    "enum",
+    "errdefer",
+    "error",
+    "export",
+    "extern",
+    "fn",
+    "for",
+    "if",
+    "inline",
+    "linksection",
+    "noalias",
+    "noinline",
+    "nosuspend",
+    "opaque",
+    "or",
+    "orelse",
+    "packed",
+    "pub",
+    "resume",
+    "return",
+    "struct",
+    "suspend",
+    "switch",
+    "test",
+    "threadlocal",
+    "try",
+    "union",
+    "unreachable",
+    "var",
+    "volatile",
+    "while",
+];
+
+/// Zig declarations for the `enums` that `zig_code` does not declare itself
+pub fn inject(zig_code: &str, enums: &[ItemEnum]) -> String {
+    let mut declarations = String::new();
+    for item in enums {
+        if declares(zig_code, &item.ident.to_string()) {
+            continue;
+        }
+        match zig_enum(item) {
+            Ok(Some(declaration)) => declarations.push_str(&declaration),
+            Ok(None) => {},
+            Err(message) => declarations
+                .push_str(&format!("comptime {{\n    @compileError(\"{message}\");\n}}\n")),
+        }
+    }
+    if declarations.is_empty() {
+        return zig_code.to_string();
+    }
+    format!("{zig_code}\n\n// Enums generated from their Rust definitions\n{declarations}")
+}
+
+/// Zig declaration of a fieldless Rust enum with an integer `#[repr]`, or
+/// `None` for enums that have no fixed C layout
+fn zig_enum(item: &ItemEnum) -> Result<Option<String>, String> {
+    let name = &item.ident;
+    let Some(tag) = int_repr(item) else {
+        return Ok(None);
+    };
+    if item
+        .variants
+        .iter()
+        .any(|variant| !matches!(variant.fields, syn::Fields::Unit))
+    {
+        return Ok(None);
+    }
+
+    let mut variants = String::new();
+    for variant in &item.variants {
+        let ident = zig_ident(&variant.ident.to_string());
+        match &variant.discriminant {
+            None => variants.push_str(&format!("    {ident},\n")),
+            Some((_, expr)) => {
+                let Some(value) = int_literal(expr) else {
+                    return Err(format!(
+                        "the discriminant of {name}::{} must be an integer literal to generate \
+                         the Zig enum",
+                        variant.ident
+                    ));
+                };
+                variants.push_str(&format!("    {ident} = {value},\n"));
+            },
+        }
+    }
+    let visibility = if matches!(item.vis, syn::Visibility::Inherited) {
+        ""
+    } else {
+        "pub "
+    };
+    Ok(Some(format!("{visibility}const {name} = enum({tag}) {{\n{variants}}};\n")))
+}
+
+/// Zig tag type for the enum's `#[repr]`: the integer itself, `c_int` for
+/// `#[repr(C)]`
+fn int_repr(item: &ItemEnum) -> Option<String> {
+    let mut tag = None;
+    for attr in item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        let _ = attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                let ident = ident.to_string();
+                if INT_REPRS.contains(&ident.as_str()) {
+                    tag = Some(ident);
+                } else if ident == "C" && tag.is_none() {
+                    tag = Some("c_int".to_string());
+                }
+            }
+            Ok(())
+        });
+    }
+    tag
+}
+
+/// `-1`, `0x10` or `7u8` as a Zig integer literal
+fn int_literal(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(int), .. }) => {
+            Some(int.base10_digits().to_string())
+        },
+        syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) => {
+            int_literal(expr).map(|value| format!("-{value}"))
+        },
+        syn::Expr::Paren(paren) => int_literal(&paren.expr),
+        _ => None,
+    }
+}
+
+pub(crate) fn zig_ident(ident: &str) -> String {
+    if ZIG_KEYWORDS.contains(&ident) {
+        format!("@\"{ident}\"")
+    } else {
+        ident.to_string()
+    }
+}
+
+/// Whether `zig_code` declares `const <name> =`
+pub(crate) fn declares(zig_code: &str, name: &str) -> bool {
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find("const") {
+        let after = &rest[pos + "const".len()..];
+        let boundary = !rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if boundary && after.starts_with(char::is_whitespace) {
+            if let Some(tail) = after.trim_start().strip_prefix(name) {
+                if tail.trim_start().starts_with('=') {
+                    return true;
+                }
+            }
+        }
+        rest = after;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enums(source: &str) -> Vec<ItemEnum> {
+        let file: syn::File = syn::parse_str(source).unwrap();
+        file.items
+            .into_iter()
+            .filter_map(|item| match item {
+                syn::Item::Enum(item) => Some(item),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inject_zig_enums() {
+        let rust = enums(
+            r#"
+            #[repr(u8)]
+            #[derive(Clone, Copy)]
+            pub enum Status { Idle = 0, Running, Stopped = 0x0a }
+            #[repr(C)]
+            enum Level { Low = -1, High }
+            #[repr(i32)]
+            enum Keyword { error, Ok }
+            enum Plain { A, B }
+            #[repr(u8)]
+            enum Shape { Circle(f32), Empty }
+            #[repr(u8)]
+            enum Declared { A }
+            "#,
+        );
+        let zig = inject("const Declared = enum(u8) { A };", &rust);
+        assert_eq!(
+            zig,
+            "const Declared = enum(u8) { A };\n\n// Enums generated from their Rust \
+             definitions\npub const Status = enum(u8) {\n    Idle = 0,\n    Running,\n    Stopped \
+             = 10,\n};\nconst Level = enum(c_int) {\n    Low = -1,\n    High,\n};\nconst Keyword \
+             = enum(i32) {\n    @\"error\",\n    Ok,\n};\n"
+        );
+
+        let computed = enums("#[repr(u8)] enum Flags { A = 1 << 2 }");
+        assert!(inject("", &computed).contains("@compileError(\"the discriminant of Flags::A"));
+    }
+}
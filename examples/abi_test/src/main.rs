@@ -4,25 +4,8 @@ autozig! {
     // Zig code defining struct types and functions
     const std = @import("std");
 
-    // Color struct - 3 floats (RGB)
-    pub const Color = struct {
-        r: f32,
-        g: f32,
-        b: f32,
-    };
-
-    // Vec3 struct - 3D vector
-    pub const Vec3 = struct {
-        x: f32,
-        y: f32,
-        z: f32,
-    };
-
-    // Complex struct with nested data
-    pub const Transform = struct {
-        position: Vec3,
-        scale: f32,
-    };
+    // Color, Vec3 and Transform are generated from the bridged Rust
+    // definitions below
 
     // Function returning Color (needs ABI lowering)
     export fn create_color(r: f32, g: f32, b: f32) Color {
@@ -56,7 +39,8 @@ autozig! {
 
     ---
 
-    // Rust struct definitions matching Zig
+    // Single source of truth for the Zig extern structs
+    #[autozig(bridge)]
     #[repr(C)]
     #[derive(Debug, Clone, Copy, PartialEq)]
     pub struct Color {
@@ -65,6 +49,7 @@ autozig! {
         pub b: f32,
     }
 
+    #[autozig(bridge)]
     #[repr(C)]
     #[derive(Debug, Clone, Copy, PartialEq)]
     pub struct Vec3 {
@@ -73,6 +58,7 @@ autozig! {
         pub z: f32,
     }
 
+    #[autozig(bridge)]
     #[repr(C)]
     #[derive(Debug, Clone, Copy, PartialEq)]
     pub struct Transform {
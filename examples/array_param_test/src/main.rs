@@ -99,7 +99,7 @@ fn main() {
     assert!((trans.z - 15.0).abs() < 0.001);
 
     let mut scaled: [f32; 16] = [0.0; 16];
-    mat4_scale(translation_matrix, 2.0, &mut scaled);
+    unsafe { mat4_scale(translation_matrix, 2.0, &mut scaled) };
     println!("mat4_scale(m, 2.0) first 4 values = {:?}", &scaled[0..4]);
     assert!((scaled[0] - 2.0).abs() < 0.001);
 
@@ -3,36 +3,21 @@ use autozig::autozig;
 autozig! {
 const std = @import("std");
 
-// 定义 Result 类型的 C 表示（使用 tagged union）
-pub const ResultTag = enum(u8) {
-    Ok = 0,
-    Err = 1,
-};
+// ResultTag、OptionTag 和 Status 只在下方的 Rust 部分定义一次，
+// 对应的 Zig enum 由 autozig 根据 #[repr(u8)] 定义自动生成
 
+// 定义 Result 类型的 C 表示（使用 tagged union）
 pub const ResultInt = extern struct {
     tag: ResultTag,
     value: i32,  // Ok 时是结果值，Err 时是错误码
 };
 
 // 定义 Option 类型的 C 表示
-pub const OptionTag = enum(u8) {
-    None = 0,
-    Some = 1,
-};
-
 pub const OptionInt = extern struct {
     tag: OptionTag,
     value: i32,  // Some 时有效
 };
 
-// 定义自定义枚举：Status
-pub const Status = enum(u8) {
-    Idle = 0,
-    Running = 1,
-    Paused = 2,
-    Stopped = 3,
-};
-
 // 返回 Result<i32, i32>：除法操作
 pub export fn divide(a: i32, b: i32) ResultInt {
     if (b == 0) {
@@ -47,6 +32,14 @@ pub export fn divide(a: i32, b: i32) ResultInt {
     };
 }
 
+// 返回 Result<i32, i32>：使用自动生成的 AutoZigResult 辅助类型
+pub export fn checked_divide(a: i32, b: i32) AutoZigResult(i32, i32) {
+    if (b == 0) {
+        return AutoZigResult(i32, i32).err(-1);
+    }
+    return AutoZigResult(i32, i32).ok(@divTrunc(a, b));
+}
+
 // 返回 Option<i32>：查找数组中的最大值
 pub export fn find_max(arr: [*]const i32, len: usize) OptionInt {
     if (len == 0) {
@@ -70,6 +63,17 @@ pub export fn find_max(arr: [*]const i32, len: usize) OptionInt {
     };
 }
 
+// 返回 Option<i32>：使用自动生成的 AutoZigOption 辅助类型
+pub export fn first_positive(arr: [*]const i32, len: usize) AutoZigOption(i32) {
+    var i: usize = 0;
+    while (i < len) : (i += 1) {
+        if (arr[i] > 0) {
+            return AutoZigOption(i32).some(arr[i]);
+        }
+    }
+    return AutoZigOption(i32).none();
+}
+
 // 处理自定义枚举：根据 Status 返回描述
 pub export fn status_to_code(status: Status) u8 {
     return @intFromEnum(status);
@@ -128,7 +132,9 @@ pub enum Status {
 }
 
 fn divide(a: i32, b: i32) -> ResultInt;
+fn checked_divide(a: i32, b: i32) -> Result<i32, i32>;
 fn find_max(arr: *const i32, len: usize) -> OptionInt;
+fn first_positive(arr: &[i32]) -> Option<i32>;
 fn status_to_code(status: Status) -> u8;
 fn code_to_status(code: u8) -> Status;
 fn next_status(current: Status) -> Status;
@@ -152,23 +158,34 @@ fn main() {
         ResultTag::Err => println!("   10 / 0 = 错误（除零），错误码: {}", result2.value),
     }
 
+    // 自动降级的 Result<i32, i32>
+    match checked_divide(10, 0) {
+        Ok(value) => println!("   checked_divide(10, 0) = {} (成功)", value),
+        Err(code) => println!("   checked_divide(10, 0) = 错误码: {}", code),
+    }
+    assert_eq!(checked_divide(10, 2), Ok(5));
+
     // 测试 Option<i32>
     println!("\n2. 测试 Option 类型（查找最大值）:");
 
     let arr = vec![3, 7, 2, 9, 4];
-    let option1 = find_max(arr.as_ptr(), arr.len());
+    let option1 = unsafe { find_max(arr.as_ptr(), arr.len()) };
     match option1.tag {
         OptionTag::Some => println!("   数组 {:?} 的最大值: {}", arr, option1.value),
         OptionTag::None => println!("   数组为空，无最大值"),
     }
 
     let empty: Vec<i32> = vec![];
-    let option2 = find_max(empty.as_ptr(), empty.len());
+    let option2 = unsafe { find_max(empty.as_ptr(), empty.len()) };
     match option2.tag {
         OptionTag::Some => println!("   最大值: {}", option2.value),
         OptionTag::None => println!("   空数组，返回 None"),
     }
 
+    // 自动降级的 Option<i32>
+    println!("   首个正数: {:?}", first_positive(&[-3, 0, 5, 7]));
+    assert_eq!(first_positive(&[-1, -2]), None);
+
     // 测试自定义枚举 Status
     println!("\n3. 测试自定义枚举（Status 状态机）:");
 
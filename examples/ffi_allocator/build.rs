@@ -18,8 +18,6 @@ fn main() {
         let _ = std::fs::remove_file(&lib_path);
     }
 
-    // Use modular_buildzig mode (recommended)
-    std::env::set_var("AUTOZIG_MODE", "modular_buildzig");
-
-    autozig_build::build("src").expect("Failed to build Zig code");
+    autozig_build::build_with_mode("src", autozig_build::CompilationMode::ModularBuildZig)
+        .expect("Failed to build Zig code");
 }
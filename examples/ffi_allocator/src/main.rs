@@ -35,29 +35,29 @@ impl Counter {
     }
 
     pub fn get(&self) -> u32 {
-        counter_get(self.inner)
+        unsafe { counter_get(self.inner) }
     }
 
     pub fn increment(&mut self) {
-        counter_increment(self.inner);
+        unsafe { counter_increment(self.inner) };
     }
 
     pub fn decrement(&mut self) {
-        counter_decrement(self.inner);
+        unsafe { counter_decrement(self.inner) };
     }
 
     pub fn reset(&mut self) {
-        counter_reset(self.inner);
+        unsafe { counter_reset(self.inner) };
     }
 
     pub fn increment_count(&self) -> u32 {
-        counter_get_increment_count(self.inner)
+        unsafe { counter_get_increment_count(self.inner) }
     }
 }
 
 impl Drop for Counter {
     fn drop(&mut self) {
-        counter_destroy(self.inner);
+        unsafe { counter_destroy(self.inner) };
     }
 }
 
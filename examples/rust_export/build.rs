@@ -9,13 +9,10 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    // 强制使用 MODULAR_BUILDZIG 模式避免文件重复
-    std::env::set_var("AUTOZIG_MODE", "modular_buildzig");
-    
     // 🎯 一行搞定！对于 WASM 目标，build() 会自动：
     // 1. 编译 Zig 代码（如果有 autozig! 宏）
     // 2. 生成 TypeScript 绑定（对于 #[autozig_export] 函数）
-    autozig_build::build("src")?;
+    autozig_build::build_with_mode("src", autozig_build::CompilationMode::ModularBuildZig)?;
     
     Ok(())
 }
\ No newline at end of file
@@ -1,5 +1,13 @@
+use autozig_build::Sanitizer;
+
 fn main() -> anyhow::Result<()> {
     // 扫描 src 目录中的 autozig! 宏
-    autozig_build::build("src")?;
+    let mut builder = autozig_build::Builder::new("src");
+    // 与 RUSTFLAGS 中的 -Z sanitizer 保持一致，让 Zig 代码也被插桩
+    let rustflags = std::env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    if let Some(sanitizer) = Sanitizer::from_rustflags(&rustflags) {
+        builder = builder.sanitizer(sanitizer);
+    }
+    builder.build()?;
     Ok(())
 }
@@ -50,9 +50,10 @@ fn display_compile_info() {
         option_env!("AUTOZIG_SIMD_CONFIG").unwrap_or("Not detected (rebuild required)")
     );
     println!(
-        "  Zig CPU Flag: {}\n",
+        "  Zig CPU Flag: {}",
         option_env!("AUTOZIG_ZIG_CPU_FLAG").unwrap_or("Not detected (rebuild required)")
     );
+    println!("  Zig Build:    {}\n", autozig::build_info!());
 }
 
 fn display_runtime_features() {
@@ -95,7 +96,7 @@ fn demo_vector_add() {
     let mut result = vec![0.0f32; size];
 
     let start = Instant::now();
-    vector_add_f32(a.as_ptr(), b.as_ptr(), result.as_mut_ptr(), size);
+    unsafe { vector_add_f32(a.as_ptr(), b.as_ptr(), result.as_mut_ptr(), size) };
     let elapsed = start.elapsed();
 
     println!("  Added {} elements in {:?}", size, elapsed);
@@ -118,7 +119,7 @@ fn demo_dot_product() {
     let b: Vec<f32> = (0..size).map(|i| (i + 1) as f32).collect();
 
     let start = Instant::now();
-    let result = dot_product_f32(a.as_ptr(), b.as_ptr(), size);
+    let result = unsafe { dot_product_f32(a.as_ptr(), b.as_ptr(), size) };
     let elapsed = start.elapsed();
 
     // Expected: sum of i * (i + 1) for i in 0..1000
@@ -160,7 +161,7 @@ fn demo_matrix_mul() {
     let mut result = [0.0f32; 16];
 
     let start = Instant::now();
-    matrix_mul_4x4(a.as_ptr(), b.as_ptr(), result.as_mut_ptr());
+    unsafe { matrix_mul_4x4(a.as_ptr(), b.as_ptr(), result.as_mut_ptr()) };
     let elapsed = start.elapsed();
 
     println!("  Multiplied 4x4 matrices in {:?}", elapsed);
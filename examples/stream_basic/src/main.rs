@@ -79,7 +79,7 @@ async fn test_zig_sequence_stream() {
         let mut buffer = vec![0u32; count as usize];
 
         // Call Zig to generate sequence
-        generate_sequence(0, count, buffer.as_mut_ptr());
+        unsafe { generate_sequence(0, count, buffer.as_mut_ptr()) };
 
         // Stream the generated data
         for value in buffer {
@@ -114,7 +114,7 @@ async fn test_zig_sequence_stream() {
     }
 
     // Use Zig to compute sum
-    let sum = sum_array(values.as_ptr(), values.len());
+    let sum = unsafe { sum_array(values.as_ptr(), values.len()) };
     println!("  ✓ Generated {} values, sum = {} (computed by Zig)", values.len(), sum);
 }
 
@@ -127,7 +127,7 @@ async fn test_fibonacci_stream() {
         let mut fib_buffer = vec![0u64; count as usize];
 
         // Generate fibonacci using Zig
-        generate_fibonacci(count, fib_buffer.as_mut_ptr());
+        unsafe { generate_fibonacci(count, fib_buffer.as_mut_ptr()) };
 
         // Stream the fibonacci numbers
         for value in fib_buffer {
@@ -204,11 +204,11 @@ async fn test_stream_with_zig_processing() {
         println!("  Original data: {:?}", data);
 
         // Process with Zig: double all values
-        double_values(data.as_mut_ptr(), data.len());
+        unsafe { double_values(data.as_mut_ptr(), data.len()) };
         println!("  After doubling (Zig): {:?}", data);
 
         // Filter even numbers using Zig
-        let new_len = filter_even(data.as_mut_ptr(), data.len());
+        let new_len = unsafe { filter_even(data.as_mut_ptr(), data.len()) };
         data.truncate(new_len);
         println!("  After filtering even (Zig): {:?}", data);
 
@@ -255,7 +255,7 @@ async fn test_concurrent_streams() {
                 // Use Zig to generate sequence for each stream
                 let count = 3u32;
                 let mut buffer = vec![0u32; count as usize];
-                generate_sequence(stream_id * 100, count, buffer.as_mut_ptr());
+                unsafe { generate_sequence(stream_id * 100, count, buffer.as_mut_ptr()) };
 
                 for value in buffer {
                     let bytes = value.to_le_bytes().to_vec();
@@ -3,25 +3,8 @@ use autozig::autozig;
 autozig! {
 const std = @import("std");
 
-// 定义一个简单的 Point 结构体
-pub const Point = extern struct {
-    x: i32,
-    y: i32,
-};
-
-// 定义一个包含多种类型的 Person 结构体
-pub const Person = extern struct {
-    age: u8,
-    height: f32,
-    is_student: bool,
-};
-
-// 定义一个嵌套结构体 Rectangle
-pub const Rectangle = extern struct {
-    top_left: Point,
-    width: i32,
-    height: i32,
-};
+// Point、Person 和 Rectangle 标记了 #[autozig(bridge)]，
+// 对应的 extern struct 由下方的 Rust 定义自动生成
 
 // 计算点到原点的距离
 pub export fn point_distance(p: Point) f64 {
@@ -42,7 +25,7 @@ pub export fn point_move(p: *Point, dx: i32, dy: i32) void {
 }
 
 // 创建一个新的 Person
-pub export fn person_new(age: u8, height: f32, is_student: bool) Person {
+pub export fn person_new(age: u8, height: f32, is_student: u8) Person {
     return Person{
         .age = age,
         .height = height,
@@ -64,6 +47,7 @@ pub export fn rect_new(x: i32, y: i32, w: i32, h: i32) Rectangle {
     };
 }
 ---
+#[autozig(bridge)]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct Point {
@@ -71,6 +55,7 @@ pub struct Point {
     pub y: i32,
 }
 
+#[autozig(bridge)]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct Person {
@@ -79,6 +64,7 @@ pub struct Person {
     pub is_student: u8,
 }
 
+#[autozig(bridge)]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct Rectangle {
@@ -108,7 +94,7 @@ fn main() {
 
     let mut p2 = point_new(10, 20);
     println!("   移动前: Point {{ x: {}, y: {} }}", p2.x, p2.y);
-    point_move(&mut p2, 5, -3);
+    unsafe { point_move(&mut p2, 5, -3) };
     println!("   移动后: Point {{ x: {}, y: {} }}", p2.x, p2.y);
 
     // 测试 Person
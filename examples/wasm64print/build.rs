@@ -9,11 +9,8 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    // 强制使用 MODULAR_BUILDZIG 模式避免文件重复
-    std::env::set_var("AUTOZIG_MODE", "modular_buildzig");
-
     // 🎯 编译 Zig 代码并生成 TypeScript 绑定
-    autozig_build::build("src")?;
+    autozig_build::build_with_mode("src", autozig_build::CompilationMode::ModularBuildZig)?;
 
     Ok(())
 }
@@ -25,7 +25,7 @@ macro_rules! console_log {
     ($($t:tt)*) => {
         {
             let s = format!($($t)*);
-            console_log(s.as_ptr(), s.len());
+            unsafe { console_log(s.as_ptr(), s.len()) };
         }
     }
 }
@@ -35,7 +35,7 @@ macro_rules! console_error {
     ($($t:tt)*) => {
         {
             let s = format!($($t)*);
-            console_error(s.as_ptr(), s.len());
+            unsafe { console_error(s.as_ptr(), s.len()) };
         }
     }
 }
@@ -0,0 +1,38 @@
+//! Zig filters against their Rust-native equivalents
+//!
+//! Run with `cargo bench -p autozig-wasm-filter`.
+
+use autozig::criterion::{
+    criterion_group,
+    criterion_main,
+};
+use autozig_wasm_filter::*;
+
+/// One 1080p RGBA frame
+fn frame() -> Vec<u8> {
+    (0..4 * 1920 * 1080).map(|i| (i % 251) as u8).collect()
+}
+
+autozig::bench! {
+    invert {
+        zig: apply_invert,
+        rust: apply_invert_rust,
+        input: frame,
+        bytes: Vec::len,
+    }
+    grayscale {
+        zig: apply_grayscale,
+        rust: apply_grayscale_rust,
+        input: frame,
+        bytes: Vec::len,
+    }
+    brightness {
+        zig: |data| apply_brightness(data, 40),
+        rust: |data| apply_brightness_rust(data, 40),
+        input: frame,
+        bytes: Vec::len,
+    }
+}
+
+criterion_group!(benches, invert, grayscale, brightness);
+criterion_main!(benches);
@@ -5,6 +5,7 @@
 use autozig::{
     autozig,
     autozig_export,
+    image::ImageBuffer,
 };
 
 // 使用 autozig! 宏嵌入 Zig 代码
@@ -31,17 +32,13 @@ autozig! {
         }
     }
 
-    // 灰度滤镜 - 标量版本（SIMD 优化需要复杂的像素重排）
-    export fn grayscale_raw(ptr: [*]u8, len: usize) void {
-        var i: usize = 0;
-        while (i < len) : (i += 4) {
-            const r = @as(u32, ptr[i]);
-            const g = @as(u32, ptr[i + 1]);
-            const b = @as(u32, ptr[i + 2]);
-            const gray = @as(u8, @intCast((r * 299 + g * 587 + b * 114) / 1000));
-            ptr[i] = gray;
-            ptr[i + 1] = gray;
-            ptr[i + 2] = gray;
+    // 灰度滤镜 - 通过 AutoZigImage 逐像素处理，不假定 RGBA 布局
+    export fn grayscale(image: AutoZigImage) void {
+        for (0..image.height) |y| {
+            for (0..image.width) |x| {
+                const gray = image.luma(x, y);
+                image.setRgba(x, y, .{ gray, gray, gray, image.getRgba(x, y)[3] });
+            }
         }
     }
 
@@ -87,7 +84,7 @@ autozig! {
     // Zig: fn(ptr: [*]u8, len: usize, extra_params...)
     // Rust: fn(ptr: *mut u8, len: usize, extra_params...)
     fn invert_colors_raw(ptr: *mut u8, len: usize);
-    fn grayscale_raw(ptr: *mut u8, len: usize);
+    fn grayscale(image: ImageBuffer);
     fn adjust_brightness_raw(ptr: *mut u8, len: usize, delta: i32);
 }
 
@@ -96,21 +93,23 @@ autozig! {
 /// 反色滤镜
 #[autozig_export]
 pub fn apply_invert(mut data: Vec<u8>) -> Vec<u8> {
-    invert_colors_raw(data.as_mut_ptr(), data.len());
+    unsafe { invert_colors_raw(data.as_mut_ptr(), data.len()) };
     data
 }
 
 /// 灰度滤镜
 #[autozig_export]
 pub fn apply_grayscale(mut data: Vec<u8>) -> Vec<u8> {
-    grayscale_raw(data.as_mut_ptr(), data.len());
+    // 逐像素滤镜，把 ImageData 视为一行 RGBA 像素即可
+    let width = (data.len() / 4) as u32;
+    grayscale(ImageBuffer::rgba(&mut data, width, 1));
     data
 }
 
 /// 亮度调整
 #[autozig_export]
 pub fn apply_brightness(mut data: Vec<u8>, delta: i32) -> Vec<u8> {
-    adjust_brightness_raw(data.as_mut_ptr(), data.len(), delta);
+    unsafe { adjust_brightness_raw(data.as_mut_ptr(), data.len(), delta) };
     data
 }
 
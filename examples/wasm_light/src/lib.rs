@@ -60,7 +60,7 @@ pub fn wasm_render_lights_simd(
     lights_ptr: *const f32,
     num_lights: u32,
 ) {
-    render_lights_simd_raw(pixel_ptr, width, height, lights_ptr, num_lights);
+    unsafe { render_lights_simd_raw(pixel_ptr, width, height, lights_ptr, num_lights) };
 }
 
 /// Zig Scalar 标量渲染（对比基准）
@@ -72,7 +72,7 @@ pub fn wasm_render_lights_scalar(
     lights_ptr: *const f32,
     num_lights: u32,
 ) {
-    render_lights_scalar_raw(pixel_ptr, width, height, lights_ptr, num_lights);
+    unsafe { render_lights_scalar_raw(pixel_ptr, width, height, lights_ptr, num_lights) };
 }
 
 /// 获取版本信息
@@ -89,7 +89,7 @@ fn demo_large_data() {
     println!("   ✓ Data integrity verified\n");
 
     // Compute statistics using Zig
-    let stats = compute_stats(slice.as_ptr(), slice.len());
+    let stats = unsafe { compute_stats(slice.as_ptr(), slice.len()) };
     println!("   Statistics computed by Zig:");
     println!(
         "     Min: {}, Max: {}, Sum: {}, Count: {}",
@@ -144,7 +144,7 @@ fn demo_mutation() {
     println!("   Original: {:?}", vec);
 
     // Pass to Zig for mutation
-    double_values(vec.as_mut_ptr(), vec.len());
+    unsafe { double_values(vec.as_mut_ptr(), vec.len()) };
     println!("   After doubling (by Zig): {:?}", vec);
     println!("   ✓ Values doubled in-place, no copy\n");
 }
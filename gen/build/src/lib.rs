@@ -12,13 +12,24 @@ use std::{
 use anyhow::Result;
 use autozig_engine::{
     AutoZigEngine,
+    BuildOptions,
     BuildOutput,
+    TypeLowering,
 };
 
 pub mod simd;
 
-// Re-export CompilationMode for user convenience
-pub use autozig_engine::CompilationMode;
+// Re-export engine settings for user convenience
+pub use autozig_engine::{
+    ArtifactKind,
+    CompilationMode,
+    FloatMode,
+    Sanitizer,
+    SymbolVisibility,
+    UnusedExports,
+    WasiExecModel,
+    ZigOptimize,
+};
 pub use simd::{
     detect_and_report,
     SimdConfig,
@@ -27,7 +38,8 @@ pub use simd::{
 /// Builder for autozig in build.rs
 pub struct Builder {
     src_dir: PathBuf,
-    mode: CompilationMode,
+    mode: Option<CompilationMode>,
+    options: BuildOptions,
 }
 
 impl Builder {
@@ -39,7 +51,8 @@ impl Builder {
     pub fn new(src_dir: impl Into<PathBuf>) -> Self {
         Self {
             src_dir: src_dir.into(),
-            mode: CompilationMode::default(),
+            mode: None,
+            options: BuildOptions::default(),
         }
     }
 
@@ -52,6 +65,10 @@ impl Builder {
     ///   - `CompilationMode::ModularBuildZig` - Modular with build.zig
     ///     (recommended, default)
     ///
+    /// Without it, the `#![autozig(mode = ...)]` header of the crate's blocks
+    /// decides, else the deprecated `AUTOZIG_MODE` environment variable. The
+    /// mode used is recorded in `OUT_DIR` (see `autozig_engine::MODE_FILE`).
+    ///
     /// # Example
     /// ```rust,no_run
     /// use autozig_build::{
@@ -65,7 +82,588 @@ impl Builder {
     ///     .expect("Build failed");
     /// ```
     pub fn mode(mut self, mode: CompilationMode) -> Self {
-        self.mode = mode;
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Write a stack usage report for exported Zig functions
+    ///
+    /// The report is written to `OUT_DIR/autozig_stack_report.txt` and
+    /// echoed as cargo warnings.
+    pub fn stack_report(mut self, enabled: bool) -> Self {
+        self.options.stack_report = enabled;
+        self
+    }
+
+    /// Fail the build when an exported Zig function uses more than `bytes` of
+    /// stack, or when its stack frame is not statically bounded
+    ///
+    /// Useful when Zig code runs on small fibers or embedded stacks.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .max_stack(16 * 1024)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn max_stack(mut self, bytes: u64) -> Self {
+        self.options.max_stack = Some(bytes);
+        self
+    }
+
+    /// Use deterministic `autozig_runtime` shims for reproducible tests
+    ///
+    /// Zig code using `autozig_runtime.allocator()` gets a fixed-buffer
+    /// allocator and `autozig_runtime.random()` a PRNG seeded through
+    /// `autozig::testing::init_rng`. Also enabled by `AUTOZIG_TEST_SHIMS=1`.
+    pub fn test_shims(mut self, enabled: bool) -> Self {
+        self.options.test_shims = enabled;
+        self
+    }
+
+    /// Set the visibility of Zig exports on native targets
+    ///
+    /// Defaults to [`SymbolVisibility::Hidden`]: exports link into the Rust
+    /// crate as usual but are not re-exported from shared libraries. WASM
+    /// targets always keep their exports public.
+    pub fn symbol_visibility(mut self, visibility: SymbolVisibility) -> Self {
+        self.options.visibility = visibility;
+        self
+    }
+
+    /// Keep a Zig export publicly visible when exports are hidden
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .public_symbol("plugin_entry")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn public_symbol(mut self, name: impl Into<String>) -> Self {
+        self.options.public_symbols.push(name.into());
+        self
+    }
+
+    /// Choose what happens to Zig exports no Rust declaration binds
+    /// (default: [`UnusedExports::Warn`])
+    ///
+    /// [`UnusedExports::Strip`] removes their `export` before compiling, so
+    /// they no longer take space in the static library or WASM binary.
+    /// Exports registered with [`Builder::public_symbol`] are always kept.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::UnusedExports;
+    ///
+    /// autozig_build::Builder::new("src")
+    ///     .unused_exports(UnusedExports::Strip)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn unused_exports(mut self, mode: UnusedExports) -> Self {
+        self.options.unused_exports = mode;
+        self
+    }
+
+    /// Keep Zig's local cache in `path`, relative to the crate's manifest
+    /// directory
+    ///
+    /// By default every build script of the project shares
+    /// `target/zig-cache/` (or `ZIG_LOCAL_CACHE_DIR`), rather than each
+    /// `OUT_DIR` compiling into a cache of its own. Zig's global cache
+    /// (`ZIG_GLOBAL_CACHE_DIR`, else `~/.cache/zig`) is always reused.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .zig_cache_dir("../.zig-cache")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn zig_cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.zig_cache_dir = Some(path.into());
+        self
+    }
+
+    /// Link a system library the Zig or C code calls into (e.g. `"z"` for
+    /// zlib)
+    ///
+    /// The library is linked by Zig (`-lz`, or `linkSystemLibrary` in the
+    /// generated build.zig) and by the final Rust link. Ignored on WASM
+    /// targets.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .link_system_lib("png")
+    ///     .link_system_lib("z")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn link_system_lib(mut self, name: impl Into<String>) -> Self {
+        self.options.system_libs.push(name.into());
+        self
+    }
+
+    /// Search `path` for the headers of C sources and `@cImport`, relative to
+    /// the crate's manifest directory
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .include_dir("/usr/include/libpng16")
+    ///     .link_system_lib("png16")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn include_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.include_dirs.push(path.into());
+        self
+    }
+
+    /// Export Zig functions under crate-unique symbol names
+    ///
+    /// `export fn add` is emitted as `az_<hash>__add` and the macros link the
+    /// `add` wrapper against it, so two crates in one binary can both export
+    /// an `add`. Symbols registered with [`Builder::public_symbol`] (e.g.
+    /// functions called from C) keep their names, and WASM targets are never
+    /// mangled.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .mangle_symbols(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn mangle_symbols(mut self, enabled: bool) -> Self {
+        self.options.mangle_symbols = enabled;
+        self
+    }
+
+    /// Make the license/provenance manifest available to the crate
+    ///
+    /// The manifest is always written to `OUT_DIR/autozig_manifest.json`;
+    /// with this enabled it can also be embedded in the binary with
+    /// `autozig::source_manifest!()`.
+    pub fn embed_manifest(mut self, enabled: bool) -> Self {
+        self.options.embed_manifest = enabled;
+        self
+    }
+
+    /// Share compiled Zig libraries between crates (enabled by default)
+    ///
+    /// Libraries are cached under `target/autozig-cache/` (or
+    /// `AUTOZIG_CACHE_DIR`), keyed by the generated sources, the Zig target
+    /// and the Zig version.
+    pub fn artifact_cache(mut self, enabled: bool) -> Self {
+        self.options.artifact_cache = enabled;
+        self
+    }
+
+    /// Warn about misuse of generated bindings (default: enabled)
+    ///
+    /// Flags slices of a `ZeroCopyBuffer` held across an `.await` and
+    /// references to temporaries passed to `#[autozig(retains)]` functions.
+    pub fn safety_lints(mut self, enabled: bool) -> Self {
+        self.options.safety_lints = enabled;
+        self
+    }
+
+    /// Set the floating-point mode of all Zig modules (default:
+    /// [`FloatMode::Strict`])
+    ///
+    /// [`FloatMode::Optimized`] enables fast-math: results may change with
+    /// the optimizer, the target and its FMA support. Only the Zig code is
+    /// affected; Rust code keeps IEEE semantics.
+    pub fn float_mode(mut self, mode: FloatMode) -> Self {
+        self.options.float_mode = mode;
+        self
+    }
+
+    /// Set the floating-point mode of a single `include_zig!` module
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::FloatMode;
+    ///
+    /// autozig_build::Builder::new("src")
+    ///     .module_float_mode("dsp.zig", FloatMode::Optimized)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn module_float_mode(mut self, module: impl Into<String>, mode: FloatMode) -> Self {
+        self.options.module_float_modes.push((module.into(), mode));
+        self
+    }
+
+    /// Pass extra compiler flags to a C or C++ source file in `src`
+    ///
+    /// C and C++ files are compiled by the generated build.zig
+    /// (`ModularBuildZig` mode); C++ files default to `-std=c++17`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .source_flags("dsp.cpp", ["-std=c++20", "-DUSE_SIMD=1"])
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn source_flags(
+        mut self,
+        file: impl Into<String>,
+        flags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let flags = flags.into_iter().map(Into::into).collect();
+        self.options.source_flags.push((file.into(), flags));
+        self
+    }
+
+    /// Guarantee bit-identical float results across targets (e.g. for
+    /// lockstep simulations)
+    ///
+    /// Fails the build if any module uses fast-math and turns
+    /// `autozig_fenv.enterFlushToZero` into a compile error, since WASM cannot
+    /// flush denormals. Zig's `std.math` functions are implemented in Zig and
+    /// stay deterministic; calls into the C math library do not.
+    pub fn strict_float_determinism(mut self, enabled: bool) -> Self {
+        self.options.strict_float_determinism = enabled;
+        self
+    }
+
+    /// Snapshot the lowered `extern "C"` declarations and `#[repr]` layouts
+    /// for golden-file tests
+    ///
+    /// The snapshot of the current target is written to
+    /// `tests/abi_snapshots/<target>.txt` when missing (or with
+    /// `AUTOZIG_UPDATE_ABI_SNAPSHOTS=1`) and compared by
+    /// `autozig::abi_snapshot_test!()`.
+    pub fn abi_snapshot(mut self, enabled: bool) -> Self {
+        self.options.abi_snapshot = enabled;
+        self
+    }
+
+    /// Check `#[repr(C)]` structs against the Zig `extern struct` of the same
+    /// name (enabled by default)
+    ///
+    /// The build compiles a probe reporting `@sizeOf`/`@alignOf`/`@offsetOf`
+    /// for the target, and the macros fail compilation if the Rust layout
+    /// differs, e.g. after reordering fields on only one side.
+    pub fn layout_check(mut self, enabled: bool) -> Self {
+        self.options.layout_check = enabled;
+        self
+    }
+
+    /// Compile identical Zig modules once for the whole workspace
+    ///
+    /// Self-contained `include_zig!` files (importing only `std`/`builtin`)
+    /// are built on their own into the shared `target/autozig-cache/`, and
+    /// every crate including the same file links that one library. Not
+    /// applied on WASM targets.
+    pub fn share_modules(mut self, enabled: bool) -> Self {
+        self.options.share_modules = enabled;
+        self
+    }
+
+    /// Write a C header for the Zig exports, so C or C++ code can link the
+    /// same library
+    ///
+    /// `path` is relative to the crate's manifest directory, e.g.
+    /// `"include/myzig.h"`. The header declares every `export fn` and
+    /// `extern struct` after autozig's ABI lowering; exports using types
+    /// without a C equivalent are listed in a comment.
+    pub fn emit_c_header(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.c_header = Some(path.into());
+        self
+    }
+
+    /// Describe the crate's functions as a WIT world and export them to the
+    /// WASM component model
+    ///
+    /// `path` is relative to the crate's manifest directory, e.g.
+    /// `"wit/world.wit"`. On `wasm32`, every wrapper taking and returning
+    /// scalars, strings (`&str`, `String`) and lists of scalars (`&[T]`,
+    /// `Vec<T>`) gets an export following the canonical ABI; other functions
+    /// are listed in a comment of the world. Requires the `component`
+    /// feature of `autozig`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// // then: wasm-tools component new target/wasm32-wasip1/release/my_crate.wasm
+    /// //           --adapt wasi_snapshot_preview1.reactor.wasm -o my_crate.component.wasm
+    /// autozig_build::Builder::new("src")
+    ///     .emit_wit("wit/world.wit")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn emit_wit(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.wit = Some(path.into());
+        self
+    }
+
+    /// Describe the crate's items in a UniFFI UDL file, for Kotlin and Swift
+    /// bindings
+    ///
+    /// `path` is relative to the crate's manifest directory, e.g.
+    /// `"src/my_crate.udl"`. Wrappers become namespace functions, structs
+    /// with public scalar fields dictionaries, fieldless enums enums, and
+    /// opaque types declared `send` interfaces; items with other types are
+    /// listed in a comment of the file. The Rust side UniFFI expects is
+    /// written to `OUT_DIR/autozig_uniffi.rs`, to be included at the crate
+    /// root with `include!(concat!(env!("OUT_DIR"), "/autozig_uniffi.rs"));`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .emit_udl("src/my_crate.udl")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// // then: uniffi::generate_scaffolding("src/my_crate.udl")
+    /// ```
+    pub fn emit_udl(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.udl = Some(path.into());
+        self
+    }
+
+    /// Let `rust_type` cross the boundary as `ffi_type`
+    ///
+    /// By-value parameters of that type are converted with `to_ffi` (a path
+    /// to `fn(RustType) -> FfiType`) before the call, and return values with
+    /// `from_ffi` (`fn(FfiType) -> RustType`) after it. The Zig side sees
+    /// only `ffi_type`:
+    ///
+    /// ```rust,ignore
+    /// Builder::new("src")
+    ///     .lower_type("Decimal", "i64", "crate::decimal::to_cents", "crate::decimal::from_cents")
+    ///     .build()?;
+    /// ```
+    pub fn lower_type(
+        mut self,
+        rust_type: impl Into<String>,
+        ffi_type: impl Into<String>,
+        to_ffi: impl Into<String>,
+        from_ffi: impl Into<String>,
+    ) -> Self {
+        self.options.type_lowerings.push(TypeLowering {
+            rust_type: rust_type.into(),
+            ffi_type: ffi_type.into(),
+            to_ffi: to_ffi.into(),
+            from_ffi: from_ffi.into(),
+        });
+        self
+    }
+
+    /// Produce byte-identical Zig libraries from the same sources, for
+    /// supply-chain verification
+    ///
+    /// The Zig code is built without debug information, since Zig has no
+    /// prefix map for the absolute paths it embeds; C sources get
+    /// `-ffile-prefix-map=<build dir>=.`. The static archive is then
+    /// normalized: member timestamps and owners are zeroed, the mode is
+    /// `644`, and members are sorted by name with the symbol table rewritten
+    /// to match. Cannot be combined with [`Builder::zig_stack_traces`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .reproducible(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn reproducible(mut self, enabled: bool) -> Self {
+        self.options.codegen.reproducible = enabled;
+        self
+    }
+
+    /// Build the Zig code for a `#![no_std]` crate, which turns off autozig's
+    /// default `std` feature
+    ///
+    /// Native targets are built without libc, PIC or stack protector. Zig
+    /// buffers returned as `String`/`Vec<u8>` and `g_allocator` allocate
+    /// through Rust's global allocator, so call `autozig::allocator::install()`
+    /// before the first Zig call; allocations fail until then. Cannot be
+    /// combined with [`Builder::zig_stack_traces`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .no_std(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn no_std(mut self, enabled: bool) -> Self {
+        self.options.codegen.freestanding = enabled;
+        self
+    }
+
+    /// Set the Zig optimization mode
+    ///
+    /// Defaults to `ReleaseFast`; build.zig builds for native targets
+    /// (`CompilationMode::ModularBuildZig`) otherwise use Debug. Debug WASM
+    /// builds cannot use Zig's std containers. Takes precedence over the
+    /// `#![autozig(optimize = ...)]` header of a block.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::ZigOptimize;
+    ///
+    /// // Size-critical WASM build
+    /// autozig_build::Builder::new("src")
+    ///     .optimize(ZigOptimize::ReleaseSmall)
+    ///     .strip(true)
+    ///     .single_threaded(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn optimize(mut self, optimize: ZigOptimize) -> Self {
+        self.options.codegen.optimize = Some(optimize);
+        self
+    }
+
+    /// Omit debug information from the Zig library
+    pub fn strip(mut self, enabled: bool) -> Self {
+        self.options.codegen.strip = enabled;
+        self
+    }
+
+    /// Compile the Zig library for a single thread
+    ///
+    /// Removes atomics and locking from Zig's standard library. Only sound
+    /// when the Zig code is never called from more than one thread.
+    pub fn single_threaded(mut self, enabled: bool) -> Self {
+        self.options.codegen.single_threaded = enabled;
+        self
+    }
+
+    /// Instrument the Zig and C code with a sanitizer, to match Rust code
+    /// built with `-Zsanitizer`
+    ///
+    /// The sanitizer runtime is linked by rustc, so the Rust code must use
+    /// the same sanitizer; the build warns when `RUSTFLAGS` names another
+    /// one. Unless [`optimize`](Self::optimize) is set, Zig code is built
+    /// `ReleaseSafe`. Not supported on WASM targets.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::Sanitizer;
+    ///
+    /// // RUSTFLAGS="-Zsanitizer=address" cargo +nightly test --target x86_64-unknown-linux-gnu
+    /// autozig_build::Builder::new("src")
+    ///     .sanitizer(Sanitizer::Address)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.options.codegen.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Report Zig panics and errors to Rust with their Zig stack traces
+    ///
+    /// The Zig code keeps frame pointers and error return traces and gets a
+    /// panic handler that runs Rust's panic hook with the message and the Zig
+    /// frames before aborting. Error unions returned as
+    /// `Result<T, autozig::zig_trace::Traced<E>>` carry the error return
+    /// trace. Unless [`optimize`](Self::optimize) is set, Zig code is built
+    /// `ReleaseSafe`. Frames are symbolized from the final binary's debug
+    /// information, so it must not be stripped. Not supported on WASM
+    /// targets.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .zig_stack_traces(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn zig_stack_traces(mut self, enabled: bool) -> Self {
+        self.options.codegen.stack_traces = enabled;
+        self
+    }
+
+    /// Require an exact Zig version
+    ///
+    /// Takes precedence over `zig-version` in `[package.metadata.autozig]`.
+    /// If the installed `zig` (or `ZIG_PATH`) has another version, the build
+    /// uses a copy cached in `~/.cache/autozig/`, downloading it first when
+    /// the `download` feature is enabled, and fails otherwise.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .zig_version("0.15.2")
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn zig_version(mut self, version: impl Into<String>) -> Self {
+        self.options.zig_version = Some(version.into());
+        self
+    }
+
+    /// WASI execution model of the final module (default: command)
+    ///
+    /// A reactor is a library the host initializes once and then calls into
+    /// repeatedly, as wasmtime and wasmer embedders usually do. Rust links
+    /// `cdylib` crates for WASI as reactors; set this to match so Zig code
+    /// sees the same `builtin.wasi_exec_model`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::WasiExecModel;
+    ///
+    /// autozig_build::Builder::new("src")
+    ///     .wasi_exec_model(WasiExecModel::Reactor)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn wasi_exec_model(mut self, model: WasiExecModel) -> Self {
+        self.options.codegen.wasi_exec_model = model;
+        self
+    }
+
+    /// Compile WASM Zig code with atomics and bulk memory
+    ///
+    /// Needed for Zig functions running on a shared memory across Web
+    /// Workers. The Rust side must be built for shared memory as well
+    /// (`-C target-feature=+atomics,+bulk-memory` and the
+    /// `--shared-memory --import-memory` link args); see
+    /// `autozig::wasm_threads` for passing chunks of a shared buffer.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// autozig_build::Builder::new("src")
+    ///     .wasm_threads(true)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// ```
+    pub fn wasm_threads(mut self, enabled: bool) -> Self {
+        self.options.codegen.wasm_threads = enabled;
+        self
+    }
+
+    /// Kind of library to compile the Zig code into (default: a static
+    /// archive linked into the crate)
+    ///
+    /// `ArtifactKind::Cdylib` builds a shared library (`.so`, `.dylib`,
+    /// `.dll`) that is not linked; its path is returned in
+    /// [`BuildOutput::lib_path`] and exposed to the crate as
+    /// `AUTOZIG_CDYLIB`, e.g. for a plugin host to load with `libloading`.
+    /// Its exports keep default visibility. Not supported on WASM.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autozig_build::ArtifactKind;
+    ///
+    /// let output = autozig_build::Builder::new("src")
+    ///     .artifact(ArtifactKind::Cdylib)
+    ///     .build()
+    ///     .expect("Build failed");
+    /// println!("cargo:warning=plugin at {:?}", output.lib_path);
+    /// ```
+    pub fn artifact(mut self, kind: ArtifactKind) -> Self {
+        self.options.artifact = kind;
         self
     }
 
@@ -83,9 +681,33 @@ impl Builder {
             .unwrap_or_else(|_| PathBuf::from("target/debug/build"));
 
         // Create and run engine with specified mode
-        let engine = AutoZigEngine::with_mode(&self.src_dir, &out_dir, self.mode);
+        let engine = match self.mode {
+            Some(mode) => AutoZigEngine::with_mode(&self.src_dir, &out_dir, mode),
+            None => AutoZigEngine::new(&self.src_dir, &out_dir),
+        }
+        .with_options(self.options.clone());
         engine.build()
     }
+
+    /// Build like [`Builder::build`], compiling the shared Zig modules of
+    /// every workspace member using autozig first
+    ///
+    /// Identical self-contained `include_zig!` modules across the workspace
+    /// are compiled once, in parallel, into `target/autozig-cache/`; each
+    /// crate links the ones it includes (see [`Builder::share_modules`],
+    /// which this implies). See [`workspace`] for the usual setup.
+    pub fn build_workspace(&self) -> Result<BuildOutput> {
+        let out_dir = env::var("OUT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("target/debug/build"));
+
+        let engine = match self.mode {
+            Some(mode) => AutoZigEngine::with_mode(&self.src_dir, &out_dir, mode),
+            None => AutoZigEngine::new(&self.src_dir, &out_dir),
+        }
+        .with_options(self.options.clone());
+        engine.build_workspace()
+    }
 }
 
 /// Convenience function for simple build scripts (uses default ModularBuildZig
@@ -104,6 +726,30 @@ pub fn build(src_dir: impl Into<PathBuf>) -> Result<BuildOutput> {
     Builder::new(src_dir).build()
 }
 
+/// Build the crate's Zig code as part of a workspace-wide pass
+///
+/// Call it from the build script of every workspace member using autozig.
+/// The first one to run compiles the self-contained `include_zig!` modules of
+/// all members, each distinct module once and in parallel, into
+/// `target/autozig-cache/`; every member then links the modules it includes
+/// instead of compiling its own copies. Uses `ModularBuildZig` mode, since
+/// merged code cannot share modules.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// // In build.rs of each member:
+/// fn main() -> anyhow::Result<()> {
+///     autozig_build::workspace()?;
+///     Ok(())
+/// }
+/// ```
+pub fn workspace() -> Result<BuildOutput> {
+    Builder::new("src")
+        .mode(CompilationMode::ModularBuildZig)
+        .build_workspace()
+}
+
 /// Build with specific compilation mode
 ///
 /// # Example
@@ -127,6 +773,9 @@ pub fn build_with_mode(src_dir: impl Into<PathBuf>, mode: CompilationMode) -> Re
 /// tests. Test executables will be placed in OUT_DIR with the naming pattern:
 /// test_{filename}
 ///
+/// Register the tests with cargo test through `autozig::zig_tests!` using the
+/// same directory.
+///
 /// # Arguments
 /// * `zig_dir` - Directory containing .zig files with test blocks
 ///
@@ -150,7 +799,10 @@ pub fn build_tests(zig_dir: impl Into<PathBuf>) -> Result<Vec<PathBuf>> {
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("target/debug/build"));
 
-    let compiler = ZigCompiler::new();
+    let compiler = match autozig_engine::cache::zig_cache_dir(&out_dir) {
+        Some(dir) => ZigCompiler::new().with_cache_dir(dir),
+        None => ZigCompiler::new(),
+    };
     let mut test_executables = Vec::new();
 
     // Find all .zig files
@@ -209,7 +861,7 @@ pub fn generate_typescript_bindings_for_rust_exports(src_dir: impl Into<PathBuf>
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("target/debug/build"));
 
-    let engine = AutoZigEngine::with_mode(&src_dir, &out_dir, CompilationMode::default());
+    let engine = AutoZigEngine::new(&src_dir, &out_dir);
     engine.generate_typescript_bindings_for_rust_exports()?;
 
     Ok(())
@@ -1,10 +1,15 @@
 //! Procedural macro implementation for autozig!
 //!
 //! This macro processes mixed Zig/Rust code and generates safe bindings
-//! using IDL-driven FFI generation (no bindgen required)
+//! using IDL-driven FFI generation (no bindgen required). The expansion
+//! itself is implemented in `autozig-codegen`.
 
 #![forbid(unsafe_code)]
 
+use autozig_codegen::{
+    expand_autozig,
+    expand_external,
+};
 use autozig_parser::{
     AutoZigConfig,
     IncludeZigConfig,
@@ -33,6 +38,10 @@ use syn::parse_macro_input;
 /// }
 /// ```
 ///
+/// `autozig!(mod = img, { ... })` generates the bindings in `pub mod img`
+/// and links them against `img__`-prefixed Zig exports, so blocks binding
+/// functions of the same name do not collide.
+///
 /// The macro will:
 /// 1. Extract Zig code to be compiled by build.rs (via Scanner)
 /// 2. Generate extern "C" FFI bindings directly from Rust signatures
@@ -42,642 +51,7 @@ use syn::parse_macro_input;
 #[proc_macro]
 pub fn autozig(input: TokenStream) -> TokenStream {
     let config = parse_macro_input!(input as AutoZigConfig);
-
-    // Generate code with IDL-driven FFI
-    // No bindgen needed - we generate extern "C" directly from user signatures
-    let mod_name = syn::Ident::new(config.get_mod_name(), proc_macro2::Span::call_site());
-
-    let output = if config.has_rust_signatures()
-        || !config.rust_structs.is_empty()
-        || !config.rust_enums.is_empty()
-        || !config.rust_trait_impls.is_empty()
-    {
-        // Generate enum definitions (must come before struct definitions)
-        let enum_defs = generate_enum_definitions(&config);
-
-        // Generate struct definitions (must come before FFI declarations that use them)
-        let struct_defs = generate_struct_definitions(&config);
-
-        // Generate trait impl target types (ZST structs for Phase 1)
-        let trait_impl_types = generate_trait_impl_types(&config);
-
-        // Phase 3: Generate FFI declarations and wrappers with monomorphization and
-        // async support
-        let (ffi_decls, wrappers) = generate_with_monomorphization(&config);
-
-        // Generate trait FFI declarations
-        let trait_ffi_decls = generate_trait_ffi_declarations(&config);
-
-        // Generate trait implementations
-        let trait_impls = generate_trait_implementations(&config);
-
-        quote! {
-            // Enum definitions (visible at module level)
-            #enum_defs
-
-            // Struct definitions (visible at module level)
-            #struct_defs
-
-            // Trait impl target types (ZST structs)
-            #trait_impl_types
-
-            // Raw FFI module with extern "C" declarations
-            mod #mod_name {
-                use super::*;  // Import enums and structs from parent scope
-                #ffi_decls
-                #trait_ffi_decls
-            }
-
-            // Safe wrappers
-            #wrappers
-
-            // Trait implementations
-            #trait_impls
-        }
-    } else {
-        // No signatures provided - user must write their own FFI declarations
-        quote! {
-            // Note: No Rust signatures provided in autozig! macro
-            // You must manually declare extern "C" functions or provide signatures after ---
-            compile_error!("autozig! macro requires Rust function signatures after --- separator");
-        }
-    };
-
-    TokenStream::from(output)
-}
-
-/// Generate enum definitions from IDL
-fn generate_enum_definitions(config: &AutoZigConfig) -> proc_macro2::TokenStream {
-    let enums: Vec<_> = config.rust_enums.iter().map(|e| &e.item).collect();
-
-    quote! {
-        #(#enums)*
-    }
-}
-
-/// Generate struct definitions from IDL
-fn generate_struct_definitions(config: &AutoZigConfig) -> proc_macro2::TokenStream {
-    let structs: Vec<_> = config.rust_structs.iter().map(|s| &s.item).collect();
-
-    quote! {
-        #(#structs)*
-    }
-}
-
-/// Check if a type is a reference to a slice or str
-fn is_slice_or_str_ref(ty: &syn::Type) -> Option<(bool, Option<syn::Type>)> {
-    if let syn::Type::Reference(type_ref) = ty {
-        let is_mut = type_ref.mutability.is_some();
-
-        // Check for &str or &mut str
-        if let syn::Type::Path(type_path) = &*type_ref.elem {
-            if type_path.path.is_ident("str") {
-                return Some((is_mut, None)); // str has no element type
-            }
-        }
-
-        // Check for &[T] or &mut [T]
-        if let syn::Type::Slice(type_slice) = &*type_ref.elem {
-            return Some((is_mut, Some((*type_slice.elem).clone())));
-        }
-    }
-    None
-}
-
-/// Check if a type is a fixed-size array [T; N]
-/// Returns Some((element_type, array_size_expr)) if it matches
-/// This enables automatic conversion of [T; N] to *const [N]T in FFI
-fn is_fixed_array(ty: &syn::Type) -> Option<(syn::Type, syn::Expr)> {
-    if let syn::Type::Array(type_array) = ty {
-        return Some(((*type_array.elem).clone(), type_array.len.clone()));
-    }
-    None
-}
-
-/// Check if a type is a mutable reference to a fixed-size array &mut [T; N]
-/// Returns Some((element_type, array_size_expr)) if it matches
-fn is_mut_fixed_array_ref(ty: &syn::Type) -> Option<(syn::Type, syn::Expr)> {
-    if let syn::Type::Reference(type_ref) = ty {
-        if type_ref.mutability.is_some() {
-            if let syn::Type::Array(type_array) = &*type_ref.elem {
-                return Some(((*type_array.elem).clone(), type_array.len.clone()));
-            }
-        }
-    }
-    None
-}
-
-/// Check if return type is a fixed-size array [T; N]
-/// Returns Some((element_type, array_size_expr)) if it matches
-fn is_array_return_type(output: &syn::ReturnType) -> Option<(syn::Type, syn::Expr)> {
-    if let syn::ReturnType::Type(_, ty) = output {
-        return is_fixed_array(ty);
-    }
-    None
-}
-
-/// Check if a type is a struct type (non-primitive) that needs ABI-safe pointer
-/// passing Returns true for struct types, false for primitives
-fn is_struct_type(ty: &syn::Type) -> bool {
-    if let syn::Type::Path(type_path) = ty {
-        if let Some(ident) = type_path.path.get_ident() {
-            let ident_str = ident.to_string();
-            // Whitelist: Rust primitive types - these don't need pointer conversion
-            return !matches!(
-                ident_str.as_str(),
-                "i8" | "i16"
-                    | "i32"
-                    | "i64"
-                    | "i128"
-                    | "isize"
-                    | "u8"
-                    | "u16"
-                    | "u32"
-                    | "u64"
-                    | "u128"
-                    | "usize"
-                    | "f32"
-                    | "f64"
-                    | "bool"
-                    | "char"
-                    | "()"
-            );
-        }
-        // Complex path types (like generic types) - treat as struct type
-        true
-    } else if matches!(ty, syn::Type::Tuple(tuple) if tuple.elems.is_empty()) {
-        // Unit type () - not a struct
-        false
-    } else {
-        // Other types (arrays, references, etc.) - not struct types
-        // Arrays and references are already handled by other functions
-        false
-    }
-}
-
-
-/// Generate ZST struct types for trait implementations (Phase 1)
-/// Generate Opaque Pointer struct types for stateful trait implementations
-/// (Phase 2)
-fn generate_trait_impl_types(config: &AutoZigConfig) -> proc_macro2::TokenStream {
-    let mut type_defs = Vec::new();
-    let mut generated_types = std::collections::HashSet::new();
-
-    for trait_impl in &config.rust_trait_impls {
-        // Skip if we've already generated this type
-        if generated_types.contains(&trait_impl.target_type) {
-            continue;
-        }
-        generated_types.insert(trait_impl.target_type.clone());
-
-        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
-
-        if trait_impl.is_opaque {
-            // Phase 2: Generate opaque pointer struct
-            type_defs.push(generate_opaque_struct(&type_name));
-        } else if trait_impl.is_zst {
-            // Phase 1: Generate zero-sized type with Default derive
-            type_defs.push(quote! {
-                #[derive(Default, Debug, Clone, Copy)]
-                pub struct #type_name;
-            });
-        }
-    }
-
-    quote! {
-        #(#type_defs)*
-    }
-}
-
-/// Generate an opaque pointer struct (Phase 2)
-fn generate_opaque_struct(type_name: &syn::Ident) -> proc_macro2::TokenStream {
-    quote! {
-        pub struct #type_name {
-            inner: std::ptr::NonNull<std::ffi::c_void>,
-            _marker: std::marker::PhantomData<*mut ()>,
-        }
-
-        // Opaque types are !Send and !Sync by default (via PhantomData<*mut ()>)
-        // Users can manually implement Send/Sync if their Zig code is thread-safe
-
-        // Implement Default by calling the constructor (if available)
-        impl Default for #type_name {
-            fn default() -> Self {
-                Self::new()
-            }
-        }
-    }
-}
-
-/// Generate trait implementations (Phase 1 & 2)
-fn generate_trait_implementations(config: &AutoZigConfig) -> proc_macro2::TokenStream {
-    let mut impls = Vec::new();
-    let mod_name = syn::Ident::new(config.get_mod_name(), proc_macro2::Span::call_site());
-
-    for trait_impl in &config.rust_trait_impls {
-        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
-
-        // Phase 2: Generate constructor if present
-        if let Some(constructor) = &trait_impl.constructor {
-            impls.push(generate_constructor(&type_name, constructor, &mod_name));
-        }
-
-        // Phase 2: Generate Drop implementation if destructor present
-        if let Some(destructor) = &trait_impl.destructor {
-            impls.push(generate_drop_impl(&type_name, destructor, &mod_name));
-        }
-
-        // Skip trait impl generation if this is an inherent impl (empty trait name)
-        if trait_impl.trait_name.is_empty() {
-            continue;
-        }
-
-        let trait_name = syn::Ident::new(&trait_impl.trait_name, proc_macro2::Span::call_site());
-
-        // Generate methods for the trait implementation
-        let mut methods = Vec::new();
-        for method in &trait_impl.methods {
-            let method_sig = &method.sig;
-            let method_name = &method_sig.ident;
-            let inputs = &method_sig.inputs;
-            let return_type = &method_sig.output;
-
-            // Phase 2: For opaque types, always generate FFI call (ignore user's simplified
-            // body) Phase 1: Use original method body if available (preserves
-            // user logic like Option wrapping)
-            let should_generate_ffi_call = trait_impl.is_opaque || method.body.is_none();
-
-            if !should_generate_ffi_call {
-                // Phase 1: Use the original body with unsafe wrapper (for ZST with complex
-                // logic)
-                if let Some(original_body) = &method.body {
-                    methods.push(quote! {
-                        fn #method_name(#inputs) #return_type {
-                            unsafe #original_body
-                        }
-                    });
-                }
-            } else {
-                // Fallback: generate simple FFI call
-                let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
-
-                let mut ffi_args = Vec::new();
-
-                // Phase 2: Inject self pointer for opaque types
-                if trait_impl.is_opaque {
-                    ffi_args.push(inject_self_pointer(method_sig));
-                }
-
-                for input in &method_sig.inputs {
-                    if let syn::FnArg::Receiver(_) = input {
-                        // Skip self/&self/&mut self - already handled above
-                        continue;
-                    }
-
-                    if let syn::FnArg::Typed(pat_type) = input {
-                        if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                            let param_name = &ident.ident;
-
-                            if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(&pat_type.ty) {
-                                if is_mut {
-                                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                                } else {
-                                    ffi_args.push(quote! { #param_name.as_ptr() });
-                                }
-                                ffi_args.push(quote! { #param_name.len() });
-                            } else if is_mut_fixed_array_ref(&pat_type.ty).is_some() {
-                                // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
-                                ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                            } else if is_fixed_array(&pat_type.ty).is_some() {
-                                // Fixed array [T; N] -> pass &param
-                                ffi_args.push(quote! { &#param_name });
-                            } else {
-                                ffi_args.push(quote! { #param_name });
-                            }
-                        }
-                    }
-                }
-
-                methods.push(quote! {
-                    fn #method_name(#inputs) #return_type {
-                        unsafe {
-                            #mod_name::#zig_fn(#(#ffi_args),*)
-                        }
-                    }
-                });
-            }
-        }
-
-        // Generate the complete impl block
-        impls.push(quote! {
-            impl #trait_name for #type_name {
-                #(#methods)*
-            }
-        });
-    }
-
-    quote! {
-        #(#impls)*
-    }
-}
-
-/// Generate constructor for opaque types (Phase 2)
-fn generate_constructor(
-    type_name: &syn::Ident,
-    constructor: &autozig_parser::TraitMethod,
-    mod_name: &syn::Ident,
-) -> proc_macro2::TokenStream {
-    let zig_fn = syn::Ident::new(&constructor.zig_function, proc_macro2::Span::call_site());
-    let method_name = syn::Ident::new(&constructor.name, proc_macro2::Span::call_site());
-
-    // Get parameters (excluding self)
-    let params: Vec<_> = constructor
-        .sig
-        .inputs
-        .iter()
-        .filter_map(|input| {
-            if let syn::FnArg::Typed(pat_type) = input {
-                Some(pat_type)
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    let param_names: Vec<_> = params
-        .iter()
-        .filter_map(|pat_type| {
-            if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                Some(&ident.ident)
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    let inputs = &constructor.sig.inputs;
-
-    quote! {
-        impl #type_name {
-            pub fn #method_name(#inputs) -> Self {
-                unsafe {
-                    let ptr = #mod_name::#zig_fn(#(#param_names),*);
-                    std::ptr::NonNull::new(ptr as *mut std::ffi::c_void)
-                        .map(|inner| Self {
-                            inner,
-                            _marker: std::marker::PhantomData,
-                        })
-                        .expect("Zig allocation failed (OOM)")
-                }
-            }
-        }
-    }
-}
-
-/// Generate Drop implementation for opaque types (Phase 2)
-fn generate_drop_impl(
-    type_name: &syn::Ident,
-    destructor: &autozig_parser::TraitMethod,
-    mod_name: &syn::Ident,
-) -> proc_macro2::TokenStream {
-    let zig_fn = syn::Ident::new(&destructor.zig_function, proc_macro2::Span::call_site());
-
-    quote! {
-        impl Drop for #type_name {
-            fn drop(&mut self) {
-                unsafe {
-                    #mod_name::#zig_fn(self.inner.as_ptr());
-                }
-            }
-        }
-    }
-}
-
-/// Inject self pointer as first argument for opaque types (Phase 2)
-fn inject_self_pointer(sig: &syn::Signature) -> proc_macro2::TokenStream {
-    // Check receiver type: &self or &mut self
-    for input in &sig.inputs {
-        if let syn::FnArg::Receiver(receiver) = input {
-            if receiver.mutability.is_some() {
-                // &mut self -> *mut c_void
-                return quote! { self.inner.as_ptr() };
-            } else {
-                // &self -> *const c_void
-                return quote! { self.inner.as_ptr() as *const std::ffi::c_void };
-            }
-        }
-    }
-
-    // No receiver, shouldn't happen for trait methods
-    quote! {}
-}
-
-/// Generate FFI declarations for Zig functions used in trait implementations
-/// (Phase 1 & 2)
-fn generate_trait_ffi_declarations(config: &AutoZigConfig) -> proc_macro2::TokenStream {
-    let mut decls = Vec::new();
-
-    for trait_impl in &config.rust_trait_impls {
-        // Phase 2: Generate constructor FFI declaration
-        if let Some(constructor) = &trait_impl.constructor {
-            let zig_fn = syn::Ident::new(&constructor.zig_function, proc_macro2::Span::call_site());
-            let params: Vec<_> = constructor
-                .sig
-                .inputs
-                .iter()
-                .filter_map(|input| {
-                    if let syn::FnArg::Typed(pat_type) = input {
-                        let param_name = &pat_type.pat;
-                        let param_type = &pat_type.ty;
-                        Some(quote! { #param_name: #param_type })
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            decls.push(quote! {
-                extern "C" {
-                    pub fn #zig_fn(#(#params),*) -> *mut std::ffi::c_void;
-                }
-            });
-        }
-
-        // Phase 2: Generate destructor FFI declaration
-        if let Some(destructor) = &trait_impl.destructor {
-            let zig_fn = syn::Ident::new(&destructor.zig_function, proc_macro2::Span::call_site());
-
-            decls.push(quote! {
-                extern "C" {
-                    pub fn #zig_fn(ptr: *mut std::ffi::c_void);
-                }
-            });
-        }
-
-        for method in &trait_impl.methods {
-            let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
-            let method_sig = &method.sig;
-
-            // Build FFI parameter list
-            let mut ffi_params = Vec::new();
-
-            // Phase 2: Add self pointer parameter for opaque types
-            if trait_impl.is_opaque {
-                let self_param = handle_receiver_type(method_sig);
-                if !self_param.is_empty() {
-                    ffi_params.push(self_param);
-                }
-            }
-
-            for input in &method_sig.inputs {
-                if let syn::FnArg::Receiver(_) = input {
-                    // Skip &self / &mut self for ZST (Phase 1)
-                    // For opaque types, already handled above
-                    continue;
-                }
-
-                if let syn::FnArg::Typed(pat_type) = input {
-                    let param_name = &pat_type.pat;
-                    let param_type = &pat_type.ty;
-
-                    // Check if this is a slice or str reference
-                    if let Some((is_mut, elem_type)) = is_slice_or_str_ref(param_type) {
-                        // Extract parameter name as string
-                        let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                            ident.ident.to_string()
-                        } else {
-                            continue;
-                        };
-
-                        // Lower to ptr + len
-                        let ptr_type = if let Some(elem) = elem_type {
-                            if is_mut {
-                                quote! { *mut #elem }
-                            } else {
-                                quote! { *const #elem }
-                            }
-                        } else if is_mut {
-                            quote! { *mut u8 }
-                        } else {
-                            quote! { *const u8 }
-                        };
-
-                        let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
-                        let len_name = quote::format_ident!("{}_len", param_name_str);
-
-                        ffi_params.push(quote! { #ptr_name: #ptr_type });
-                        ffi_params.push(quote! { #len_name: usize });
-                    } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type)
-                    {
-                        // NEW: Mutable array &mut [T; N] -> *mut T
-                        let ptr_type = quote! { *mut #elem_type };
-                        ffi_params.push(quote! { #param_name: #ptr_type });
-                    } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
-                        // Fixed array [T; N] -> *const [N]T
-                        let ptr_type = quote! { *const #param_type };
-                        ffi_params.push(quote! { #param_name: #ptr_type });
-                    } else {
-                        ffi_params.push(quote! { #param_name: #param_type });
-                    }
-                }
-            }
-
-            // Extract Zig function return type from Zig code
-            let zig_return_type = extract_zig_return_type(&config.zig_code, &method.zig_function);
-            let return_type = if let Some(zig_ret) = zig_return_type {
-                zig_ret
-            } else {
-                // Fallback: use method signature for most cases, but we need special handling
-                // If the method returns Option but doesn't have the Option in its body,
-                // it likely means the Zig function returns the unwrapped type
-                method_sig.output.clone()
-            };
-
-            decls.push(quote! {
-                extern "C" {
-                    pub fn #zig_fn(#(#ffi_params),*) #return_type;
-                }
-            });
-        }
-    }
-
-    quote! {
-        #(#decls)*
-    }
-}
-
-/// Extract return type from Zig function definition
-/// Looks for patterns like: `export fn function_name(...) TYPE {`
-fn extract_zig_return_type(zig_code: &str, fn_name: &str) -> Option<syn::ReturnType> {
-    // Simple string-based extraction
-    // Find "export fn function_name" - handle possible newline before function name
-    let search_pattern1 = format!("export fn {}", fn_name);
-    let search_pattern2 = format!("export fn\n{}", fn_name);
-
-    let start_pos = if let Some(pos) = zig_code.find(&search_pattern1) {
-        pos
-    } else if let Some(pos) = zig_code.find(&search_pattern2) {
-        pos
-    } else {
-        return None;
-    };
-
-    // Find the closing parenthesis of parameters
-    let after_fn = &zig_code[start_pos..];
-    let paren_start = after_fn.find('(')?;
-    let mut paren_count = 1;
-    let mut paren_end = paren_start + 1;
-
-    for (i, ch) in after_fn[paren_start + 1..].chars().enumerate() {
-        match ch {
-            '(' => paren_count += 1,
-            ')' => {
-                paren_count -= 1;
-                if paren_count == 0 {
-                    paren_end = paren_start + 1 + i;
-                    break;
-                }
-            },
-            _ => {},
-        }
-    }
-
-    // Extract return type between ')' and '{'
-    let after_paren = &after_fn[paren_end + 1..];
-    let brace_pos = after_paren.find('{')?;
-    let return_type_str = after_paren[..brace_pos].trim();
-
-    // Map Zig types to Rust types
-    let rust_type = match return_type_str {
-        "i32" => quote! { -> i32 },
-        "u32" => quote! { -> u32 },
-        "i64" => quote! { -> i64 },
-        "u64" => quote! { -> u64 },
-        "f32" => quote! { -> f32 },
-        "f64" => quote! { -> f64 },
-        "bool" => quote! { -> bool },
-        "void" => quote! {},
-        _ => return None, // Unknown type, fall back to method signature
-    };
-
-    syn::parse2(rust_type).ok()
-}
-
-/// Handle receiver type for FFI parameter list (Phase 2)
-/// Returns the self pointer parameter for opaque types
-fn handle_receiver_type(sig: &syn::Signature) -> proc_macro2::TokenStream {
-    for input in &sig.inputs {
-        if let syn::FnArg::Receiver(receiver) = input {
-            if receiver.mutability.is_some() {
-                // &mut self -> *mut c_void
-                return quote! { self_ptr: *mut std::ffi::c_void };
-            } else {
-                // &self -> *const c_void
-                return quote! { self_ptr: *const std::ffi::c_void };
-            }
-        }
-    }
-    quote! {}
+    TokenStream::from(expand_autozig(&config))
 }
 
 /// include_zig! macro for referencing external Zig files
@@ -697,1040 +71,133 @@ fn handle_receiver_type(sig: &syn::Signature) -> proc_macro2::TokenStream {
 pub fn include_zig(input: TokenStream) -> TokenStream {
     let config = parse_macro_input!(input as IncludeZigConfig);
 
-    // Generate unique module name based on file path
-    // Convert "zig/math.zig" to "ffi_zig_math"
-    let mod_name = config.get_unique_mod_name();
-    let mod_name_ident = syn::Ident::new(&mod_name, proc_macro2::Span::call_site());
-    let file_path = &config.file_path;
-
     // Generate a marker that build.rs can detect
     // We use a const string that scanner will find
-    let marker_code = format!("// @autozig:include:{}", file_path);
-
-    let output = if config.has_rust_signatures()
-        || !config.rust_structs.is_empty()
-        || !config.rust_enums.is_empty()
-    {
-        // Generate enum definitions
-        let enum_defs = generate_enum_definitions_for_include(&config);
+    let marker_code = format!("// @autozig:include:{}", config.file_path);
 
-        // Generate struct definitions
-        let struct_defs = generate_struct_definitions_for_include(&config);
-
-        // Phase 3: Use monomorphization-aware generation for include_zig! too
-        let (ffi_decls, wrappers) = generate_with_monomorphization_for_include(&config);
-
-        quote! {
-            // Marker for scanner (will be removed in final output)
-            #[doc = #marker_code]
-
-            // Enum definitions (visible at module level)
-            #enum_defs
-
-            // Struct definitions (visible at module level)
-            #struct_defs
-
-            // Raw FFI module with extern "C" declarations (unique name per file)
-            mod #mod_name_ident {
-                use super::*;
-                #ffi_decls
-            }
-
-            // Safe wrappers
-            #wrappers
-        }
-    } else {
-        quote! {
-            #[doc = #marker_code]
-            compile_error!("include_zig! macro requires Rust function signatures");
-        }
-    };
-
-    TokenStream::from(output)
+    TokenStream::from(expand_external(&config, &marker_code, "include_zig!"))
 }
 
-/// Helper functions for include_zig! - reuse the same logic as autozig!
-fn generate_enum_definitions_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
-    let enums: Vec<_> = config.rust_enums.iter().map(|e| &e.item).collect();
-    quote! {
-        #(#enums)*
-    }
-}
-
-fn generate_struct_definitions_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
-    let structs: Vec<_> = config.rust_structs.iter().map(|s| &s.item).collect();
-    quote! {
-        #(#structs)*
-    }
-}
-
-#[allow(dead_code)]
-fn generate_ffi_declarations_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
-    let mut decls = Vec::new();
-
-    for rust_sig in &config.rust_signatures {
-        let sig = &rust_sig.sig;
-        let fn_name = &sig.ident;
-        let output = &sig.output;
-
-        let mut ffi_params = Vec::new();
-
-        for input in &sig.inputs {
-            if let syn::FnArg::Typed(pat_type) = input {
-                let param_type = &pat_type.ty;
-                let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                    ident.ident.to_string()
-                } else {
-                    continue;
-                };
-
-                if let Some((is_mut, elem_type)) = is_slice_or_str_ref(param_type) {
-                    let ptr_type = if let Some(elem) = elem_type {
-                        if is_mut {
-                            quote! { *mut #elem }
-                        } else {
-                            quote! { *const #elem }
-                        }
-                    } else if is_mut {
-                        quote! { *mut u8 }
-                    } else {
-                        quote! { *const u8 }
-                    };
-
-                    let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
-                    let len_name = quote::format_ident!("{}_len", param_name_str);
-
-                    ffi_params.push(quote! { #ptr_name: #ptr_type });
-                    ffi_params.push(quote! { #len_name: usize });
-                } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type) {
-                    // NEW: Mutable array &mut [T; N] -> *mut T
-                    let param_name = &pat_type.pat;
-                    let ptr_type = quote! { *mut #elem_type };
-                    ffi_params.push(quote! { #param_name: #ptr_type });
-                } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
-                    // NEW: Fixed array [T; N] -> *const [N]T
-                    let param_name = &pat_type.pat;
-                    let ptr_type = quote! { *const #param_type };
-                    ffi_params.push(quote! { #param_name: #ptr_type });
-                } else {
-                    let param_name = &pat_type.pat;
-                    ffi_params.push(quote! { #param_name: #param_type });
-                }
-            }
-        }
-
-        decls.push(quote! {
-            #[link(name = "autozig")]
-            extern "C" {
-                pub fn #fn_name(#(#ffi_params),*) #output;
-            }
-        });
-    }
-
-    quote! {
-        #(#decls)*
-    }
-}
-
-#[allow(dead_code)]
-fn generate_safe_wrappers_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
-    let mut wrappers = Vec::new();
-    let mod_name_str = config.get_unique_mod_name();
-    let mod_name = syn::Ident::new(&mod_name_str, proc_macro2::Span::call_site());
-
-    for rust_sig in &config.rust_signatures {
-        let sig = &rust_sig.sig;
-        let fn_name = &sig.ident;
-        let inputs = &sig.inputs;
-        let output = &sig.output;
-
-        let mut ffi_args = Vec::new();
-
-        for input in &sig.inputs {
-            if let syn::FnArg::Typed(pat_type) = input {
-                if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                    let param_name = &ident.ident;
-                    let param_type = &pat_type.ty;
-
-                    if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                        if is_mut {
-                            ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                        } else {
-                            ffi_args.push(quote! { #param_name.as_ptr() });
-                        }
-                        ffi_args.push(quote! { #param_name.len() });
-                    } else if is_mut_fixed_array_ref(param_type).is_some() {
-                        // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
-                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                    } else if is_fixed_array(param_type).is_some() {
-                        // NEW: Fixed array [T; N] -> pass &param
-                        ffi_args.push(quote! { &#param_name });
-                    } else {
-                        ffi_args.push(quote! { #param_name });
-                    }
-                }
-            }
-        }
-
-        let wrapper = quote! {
-            pub fn #fn_name(#inputs) #output {
-                unsafe {
-                    #mod_name::#fn_name(#(#ffi_args),*)
-                }
-            }
-        };
-
-        wrappers.push(wrapper);
-    }
-
-    quote! {
-        #(#wrappers)*
-    }
-}
-
-#[allow(dead_code)]
-fn generate_trait_impl_types_for_include(config: &IncludeZigConfig) -> proc_macro2::TokenStream {
-    let mut type_defs = Vec::new();
-
-    for trait_impl in &config.rust_trait_impls {
-        if trait_impl.is_zst {
-            let type_name =
-                syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
-
-            type_defs.push(quote! {
-                #[derive(Default, Debug, Clone, Copy)]
-                pub struct #type_name;
-            });
-        }
-    }
-
-    quote! {
-        #(#type_defs)*
-    }
-}
-
-#[allow(dead_code)]
-fn generate_trait_implementations_for_include(
-    config: &IncludeZigConfig,
-) -> proc_macro2::TokenStream {
-    let mut impls = Vec::new();
-    let mod_name_str = config.get_unique_mod_name();
-    let mod_name = syn::Ident::new(&mod_name_str, proc_macro2::Span::call_site());
-
-    for trait_impl in &config.rust_trait_impls {
-        let trait_name = syn::Ident::new(&trait_impl.trait_name, proc_macro2::Span::call_site());
-        let type_name = syn::Ident::new(&trait_impl.target_type, proc_macro2::Span::call_site());
-
-        let mut methods = Vec::new();
-        for method in &trait_impl.methods {
-            let method_sig = &method.sig;
-            let method_name = &method_sig.ident;
-            let zig_fn = syn::Ident::new(&method.zig_function, proc_macro2::Span::call_site());
-
-            let mut ffi_args = Vec::new();
-            for input in &method_sig.inputs {
-                if let syn::FnArg::Typed(pat_type) = input {
-                    if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                        let param_name = &ident.ident;
-
-                        if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(&pat_type.ty) {
-                            if is_mut {
-                                ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                            } else {
-                                ffi_args.push(quote! { #param_name.as_ptr() });
-                            }
-                            ffi_args.push(quote! { #param_name.len() });
-                        } else {
-                            ffi_args.push(quote! { #param_name });
-                        }
-                    }
-                }
-            }
-
-            let return_type = &method_sig.output;
-
-            methods.push(quote! {
-                fn #method_name(#method_sig) #return_type {
-                    unsafe {
-                        #mod_name::#zig_fn(#(#ffi_args),*)
-                    }
-                }
-            });
-        }
-
-        impls.push(quote! {
-            impl #trait_name for #type_name {
-                #(#methods)*
-            }
-        });
-    }
-
-    quote! {
-        #(#impls)*
-    }
-}
-
-// ============================================================================
-// Phase 3: Generics and Async Support
-// ============================================================================
-
-/// Phase 3: Generate FFI declarations and wrappers with monomorphization
-/// support
-fn generate_with_monomorphization(
-    config: &AutoZigConfig,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let mut all_ffi_decls = Vec::new();
-    let mut all_wrappers = Vec::new();
-
-    for rust_sig in &config.rust_signatures {
-        if !rust_sig.generic_params.is_empty() && !rust_sig.monomorphize_types.is_empty() {
-            // Generic function with monomorphization attribute
-            let (mono_ffi, mono_wrappers) =
-                generate_monomorphized_versions(rust_sig, config.get_mod_name());
-            all_ffi_decls.push(mono_ffi);
-            all_wrappers.push(mono_wrappers);
-        } else if rust_sig.is_async {
-            // Async function
-            let (async_ffi, async_wrapper) =
-                generate_async_ffi_and_wrapper(rust_sig, config.get_mod_name());
-            all_ffi_decls.push(async_ffi);
-            all_wrappers.push(async_wrapper);
-        } else {
-            // Regular function (non-generic, non-async)
-            let ffi_decl = generate_single_ffi_declaration(rust_sig);
-            let wrapper = generate_single_safe_wrapper(rust_sig, config.get_mod_name());
-            all_ffi_decls.push(ffi_decl);
-            all_wrappers.push(wrapper);
-        }
-    }
-
-    let ffi_decls = quote! { #(#all_ffi_decls)* };
-    let wrappers = quote! { #(#all_wrappers)* };
-
-    (ffi_decls, wrappers)
-}
-
-/// Generate single FFI declaration for regular (non-generic) function
-fn generate_single_ffi_declaration(
-    rust_sig: &autozig_parser::RustFunctionSignature,
-) -> proc_macro2::TokenStream {
-    let sig = &rust_sig.sig;
-    let fn_name = &sig.ident;
-    let output = &sig.output;
-
-    // Check if this function returns an array - if so, parameters need pointer
-    // conversion
-    let has_array_return = is_array_return_type(output).is_some();
-
-    let mut ffi_params = Vec::new();
-
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            let param_type = &pat_type.ty;
-            let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                ident.ident.to_string()
-            } else {
-                continue;
-            };
-
-            if let Some((is_mut, elem_type)) = is_slice_or_str_ref(param_type) {
-                let ptr_type = if let Some(elem) = elem_type {
-                    if is_mut {
-                        quote! { *mut #elem }
-                    } else {
-                        quote! { *const #elem }
-                    }
-                } else if is_mut {
-                    quote! { *mut u8 }
-                } else {
-                    quote! { *const u8 }
-                };
-
-                let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
-                let len_name = quote::format_ident!("{}_len", param_name_str);
-
-                ffi_params.push(quote! { #ptr_name: #ptr_type });
-                ffi_params.push(quote! { #len_name: usize });
-            } else if let Some((elem_type, _size_expr)) = is_mut_fixed_array_ref(param_type) {
-                // NEW: Mutable array &mut [T; N] -> *mut T
-                let param_name = &pat_type.pat;
-                let ptr_type = quote! { *mut #elem_type };
-                ffi_params.push(quote! { #param_name: #ptr_type });
-            } else if let Some((_elem_type, _size_expr)) = is_fixed_array(param_type) {
-                // NEW: Fixed array [T; N] -> *const [N]T
-                // This is backward compatible - only triggers for [T; N] types
-                let param_name = &pat_type.pat;
-                let ptr_type = quote! { *const #param_type };
-                ffi_params.push(quote! { #param_name: #ptr_type });
-            } else if has_array_return && is_struct_type(param_type) {
-                // CRITICAL FIX: For array returns, Engine converts struct params to pointers
-                // This matches Engine's convert_params_to_ptrs behavior
-                let param_name = &pat_type.pat;
-                let ptr_type = quote! { *const #param_type };
-                ffi_params.push(quote! { #param_name: #ptr_type });
-            } else {
-                let param_name = &pat_type.pat;
-                ffi_params.push(quote! { #param_name: #param_type });
-            }
-        }
-    }
-
-    // Check if this function needs ABI lowering
-    if rust_sig.needs_abi_lowering {
-        // Generate FFI declaration for pointer-based version
-        let ptr_fn_name =
-            syn::Ident::new(&format!("{}__autozig_ptr", fn_name), proc_macro2::Span::call_site());
-
-        // Rebuild FFI params with struct types converted to pointers
-        let mut abi_ffi_params = Vec::new();
-        for input in &sig.inputs {
-            if let syn::FnArg::Typed(pat_type) = input {
-                let param_name = &pat_type.pat;
-                let param_type = &pat_type.ty;
-
-                // Convert struct types to *const StructType
-                if is_struct_type(param_type) {
-                    abi_ffi_params.push(quote! { #param_name: *const #param_type });
-                } else {
-                    abi_ffi_params.push(quote! { #param_name: #param_type });
-                }
-            }
-        }
-
-        // Return type becomes *const ReturnType
-        let ptr_output = if let syn::ReturnType::Type(arrow, ty) = output {
-            syn::ReturnType::Type(
-                *arrow,
-                Box::new(syn::Type::Ptr(syn::TypePtr {
-                    star_token: syn::Token![*](proc_macro2::Span::call_site()),
-                    const_token: Some(syn::Token![const](proc_macro2::Span::call_site())),
-                    mutability: None,
-                    elem: ty.clone(),
-                })),
-            )
-        } else {
-            output.clone()
-        };
-
-        return quote! {
-            extern "C" {
-                pub fn #ptr_fn_name(#(#abi_ffi_params),*) #ptr_output;
-            }
-        };
-    }
+/// bind_zig! macro for linking a prebuilt Zig (or C) static library
+///
+/// # Syntax
+///
+/// ```rust,ignore
+/// bind_zig!("vendor/libfoo.a", {
+///     // Rust function signatures
+///     fn foo_checksum(data: &[u8]) -> u32;
+/// });
+/// ```
+///
+/// Nothing is compiled: the build script links the archive as-is and fails
+/// if it does not define every declared function. The path is relative to
+/// the Cargo manifest directory.
+#[proc_macro_error]
+#[proc_macro]
+pub fn bind_zig(input: TokenStream) -> TokenStream {
+    let config = parse_macro_input!(input as IncludeZigConfig);
 
-    // Check if return type is an array - FFI should return pointer
-    let ffi_output = if let Some((_elem_type, _size_expr)) = is_array_return_type(output) {
-        // Array return: FFI returns *const [T; N]
-        if let syn::ReturnType::Type(arrow, ty) = output {
-            syn::ReturnType::Type(
-                *arrow,
-                Box::new(syn::Type::Ptr(syn::TypePtr {
-                    star_token: syn::Token![*](proc_macro2::Span::call_site()),
-                    const_token: Some(syn::Token![const](proc_macro2::Span::call_site())),
-                    mutability: None,
-                    elem: ty.clone(),
-                })),
-            )
-        } else {
-            output.clone()
-        }
-    } else {
-        output.clone()
-    };
+    let marker_code = format!("// @autozig:bind:{}", config.file_path);
 
-    quote! {
-        extern "C" {
-            pub fn #fn_name(#(#ffi_params),*) #ffi_output;
-        }
-    }
+    TokenStream::from(expand_external(&config, &marker_code, "bind_zig!"))
 }
 
-/// Generate single safe wrapper for regular (non-generic) function
-fn generate_single_safe_wrapper(
-    rust_sig: &autozig_parser::RustFunctionSignature,
-    mod_name: &str,
-) -> proc_macro2::TokenStream {
-    // Check if this function has AutoZig binding configuration
-    let config = &rust_sig.binding_config;
-
-    // If there's binding config, generate dual exports (wasm-bindgen + C-style)
-    if config.strategy.is_some() {
-        return generate_dual_binding_wrappers(rust_sig, mod_name);
-    }
-
-    // Otherwise, use original single wrapper generation
-    let sig = &rust_sig.sig;
-    let fn_name = &sig.ident;
-    let inputs = &sig.inputs;
-    let output = &sig.output;
-    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
-
-    // Check if this function returns an array - if so, struct params need pointer
-    // conversion
-    let has_array_return = is_array_return_type(output).is_some();
-
-    let mut ffi_args = Vec::new();
-
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                let param_name = &ident.ident;
-                let param_type = &pat_type.ty;
-
-                if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                    if is_mut {
-                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                    } else {
-                        ffi_args.push(quote! { #param_name.as_ptr() });
-                    }
-                    ffi_args.push(quote! { #param_name.len() });
-                } else if is_mut_fixed_array_ref(param_type).is_some() {
-                    // NEW: Mutable array &mut [T; N] -> pass as_mut_ptr()
-                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                } else if is_fixed_array(param_type).is_some() {
-                    // NEW: Fixed array [T; N] -> pass &param
-                    // This is backward compatible - only triggers for [T; N] types
-                    ffi_args.push(quote! { &#param_name });
-                } else if has_array_return && is_struct_type(param_type) {
-                    // CRITICAL FIX: For array returns, pass struct params as pointers
-                    // This matches Engine's behavior where struct params become *const StructType
-                    ffi_args.push(quote! { &#param_name });
-                } else {
-                    ffi_args.push(quote! { #param_name });
-                }
-            }
-        }
-    }
-
-    // Check if this function needs ABI lowering (struct return)
-    if rust_sig.needs_abi_lowering {
-        // Rebuild ffi_args with ONLY struct types converted to pointers
-        // Other types (arrays, slices, primitives) keep their original handling
-        let mut abi_ffi_args = Vec::new();
-        for input in &sig.inputs {
-            if let syn::FnArg::Typed(pat_type) = input {
-                if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                    let param_name = &ident.ident;
-                    let param_type = &pat_type.ty;
-
-                    // Only struct types get pointer conversion
-                    // Arrays, slices, and primitives use original handling
-                    if is_struct_type(param_type) && is_fixed_array(param_type).is_none() {
-                        // Pass struct by pointer: &param
-                        abi_ffi_args.push(quote! { &#param_name });
-                    } else if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                        if is_mut {
-                            abi_ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                        } else {
-                            abi_ffi_args.push(quote! { #param_name.as_ptr() });
-                        }
-                        abi_ffi_args.push(quote! { #param_name.len() });
-                    } else if is_mut_fixed_array_ref(param_type).is_some() {
-                        abi_ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                    } else if is_fixed_array(param_type).is_some() {
-                        // Fixed arrays: pass as pointer reference for FFI (same as normal path)
-                        abi_ffi_args.push(quote! { &#param_name });
-                    } else {
-                        abi_ffi_args.push(quote! { #param_name });
-                    }
-                }
-            }
-        }
-        // Generate ABI-safe wrapper using pointer-based call
-        return generate_abi_lowered_wrapper(fn_name, inputs, output, &abi_ffi_args, &mod_ident);
-    }
-
-    // Check if return type is an array
-    let wrapper_body = if let Some((_elem_type, _size_expr)) = is_array_return_type(output) {
-        // Array return: need to dereference pointer and read value
-        quote! {
-            pub fn #fn_name(#inputs) #output {
-                unsafe {
-                    let ptr = #mod_ident::#fn_name(#(#ffi_args),*);
-                    // Dereference the pointer to get the array value
-                    *ptr
-                }
-            }
-        }
-    } else {
-        // Normal return
-        quote! {
-            pub fn #fn_name(#inputs) #output {
-                unsafe {
-                    #mod_ident::#fn_name(#(#ffi_args),*)
-                }
-            }
-        }
+/// zig_tests! macro registering Zig `test` blocks as Rust tests
+///
+/// # Syntax
+///
+/// ```rust,ignore
+/// // tests/zig_tests.rs
+/// autozig::zig_tests!("zig/");
+/// ```
+///
+/// Generates one module per `.zig` file in the directory (relative to the
+/// Cargo manifest directory) with one `#[test]` per Zig test block, e.g.
+/// `math::factorial_basic_cases`. Each runs the executable built by
+/// `autozig_build::build_tests` for that file and reports that block's result.
+#[proc_macro_error]
+#[proc_macro]
+pub fn zig_tests(input: TokenStream) -> TokenStream {
+    let dir_lit = parse_macro_input!(input as syn::LitStr);
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let dir = std::path::Path::new(&manifest_dir).join(dir_lit.value());
+    let mut files: Vec<_> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "zig"))
+            .collect(),
+        Err(e) => {
+            let message = format!("zig_tests!: cannot read {}: {}", dir.display(), e);
+            return syn::Error::new(dir_lit.span(), message)
+                .to_compile_error()
+                .into();
+        },
     };
-
-    wrapper_body
-}
-
-/// Generate dual binding wrappers (wasm-bindgen + C-style export)
-/// This is the core of the AutoZig dual export feature
-fn generate_dual_binding_wrappers(
-    rust_sig: &autozig_parser::RustFunctionSignature,
-    mod_name: &str,
-) -> proc_macro2::TokenStream {
-    let config = &rust_sig.binding_config;
-    let sig = &rust_sig.sig;
-    let fn_name = &sig.ident;
-    let inputs = &sig.inputs;
-    let output = &sig.output;
-    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
-
-    // Get strategy (default: "dual")
-    let strategy = config.strategy.as_deref().unwrap_or("dual");
-
-    // Get prefixes
-    let prefix_bindgen = config.prefix_bindgen.as_deref().unwrap_or("wasm_");
-    let prefix_c = config.prefix_c.as_deref().unwrap_or("wasm64_");
-
-    // Build FFI call arguments
-    let mut ffi_args = Vec::new();
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                let param_name = &ident.ident;
-                let param_type = &pat_type.ty;
-
-                if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                    if is_mut {
-                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                    } else {
-                        ffi_args.push(quote! { #param_name.as_ptr() });
-                    }
-                    ffi_args.push(quote! { #param_name.len() });
-                } else if is_mut_fixed_array_ref(param_type).is_some() {
-                    ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                } else if is_fixed_array(param_type).is_some() {
-                    ffi_args.push(quote! { &#param_name });
-                } else {
-                    ffi_args.push(quote! { #param_name });
+    files.sort();
+
+    let modules = files.iter().filter_map(|path| {
+        let source = std::fs::read_to_string(path).ok()?;
+        let stem = path.file_stem()?.to_str()?;
+        let mod_ident = syn::Ident::new(&rust_test_ident(stem), proc_macro2::Span::call_site());
+        let exe_suffix = format!("/test_{}", stem);
+        let path_str = path.display().to_string();
+
+        let mut used = std::collections::HashSet::new();
+        let tests = autozig_parser::zig_test_names(&source)
+            .into_iter()
+            .map(|label| {
+                let name = label
+                    .split_once('.')
+                    .map_or(label.as_str(), |(_, name)| name);
+                let mut fn_name = rust_test_ident(name);
+                let mut n = 2;
+                while !used.insert(fn_name.clone()) {
+                    fn_name = format!("{}_{}", rust_test_ident(name), n);
+                    n += 1;
                 }
-            }
-        }
-    }
-
-    let mut wrappers = Vec::new();
-
-    // Generate wasm-bindgen wrapper
-    if strategy == "dual" || strategy == "bindgen" {
-        let export_name = quote::format_ident!("{}{}", prefix_bindgen, fn_name);
-        wrappers.push(quote! {
-            #[cfg(not(target_family = "wasm"))]
-            #[wasm_bindgen::prelude::wasm_bindgen]
-            pub fn #export_name(#inputs) #output {
-                unsafe {
-                    #mod_ident::#fn_name(#(#ffi_args),*)
-                }
-            }
-        });
-    }
-
-    // Generate C-style export wrapper
-    if strategy == "dual" || strategy == "c_only" {
-        let export_name = quote::format_ident!("{}{}", prefix_c, fn_name);
-
-        // Handle return type conversion if specified
-        let (ret_type, body) = if let (Some(c_ret), Some(map_fn)) = (&config.c_ret, &config.map_fn)
-        {
-            // With type mapping
-            (
-                quote! { -> #c_ret },
-                quote! {
-                    let res = unsafe { #mod_ident::#fn_name(#(#ffi_args),*) };
-                    let mapper = #map_fn;
-                    mapper(res)
-                },
-            )
-        } else {
-            // Direct pass-through
-            (
-                quote! { #output },
+                let fn_ident = syn::Ident::new(&fn_name, proc_macro2::Span::call_site());
                 quote! {
-                    unsafe { #mod_ident::#fn_name(#(#ffi_args),*) }
-                },
-            )
-        };
-
-        wrappers.push(quote! {
-            #[cfg(not(target_family = "wasm"))]
-            #[no_mangle]
-            pub extern "C" fn #export_name(#inputs) #ret_type {
-                #body
-            }
-        });
-    }
-
-    quote! {
-        #(#wrappers)*
-    }
-}
-
-/// Generate ABI-lowered wrapper using MaybeUninit + pointer call
-/// This ensures cross-platform ABI compatibility for struct returns
-fn generate_abi_lowered_wrapper(
-    fn_name: &syn::Ident,
-    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
-    output: &syn::ReturnType,
-    ffi_args: &[proc_macro2::TokenStream],
-    mod_ident: &syn::Ident,
-) -> proc_macro2::TokenStream {
-    // Extract return type
-    let return_type = match output {
-        syn::ReturnType::Type(_, ty) => ty,
-        syn::ReturnType::Default => {
-            // Should not happen for needs_abi_lowering, but fallback
-            return quote! {
-                pub fn #fn_name(#inputs) #output {
-                    unsafe {
-                        #mod_ident::#fn_name(#(#ffi_args),*)
+                    #[test]
+                    fn #fn_ident() {
+                        ::autozig::testing::run_zig_test(
+                            concat!(env!("OUT_DIR"), #exe_suffix),
+                            #label,
+                        );
                     }
                 }
-            };
-        },
-    };
-
-    // Generate wrapper name for pointer version
-    let ptr_fn_name =
-        syn::Ident::new(&format!("{}__autozig_ptr", fn_name), proc_macro2::Span::call_site());
-
-    quote! {
-        pub fn #fn_name(#inputs) #output {
-            unsafe {
-                // Use MaybeUninit for uninitialized stack allocation
-                let mut result = std::mem::MaybeUninit::<#return_type>::uninit();
-
-                // Call pointer-based FFI function
-                let result_ptr = #mod_ident::#ptr_fn_name(#(#ffi_args),*);
-
-                // Copy result from pointer to our stack allocation
-                std::ptr::copy_nonoverlapping(
-                    result_ptr,
-                    result.as_mut_ptr(),
-                    1
-                );
-
-                // Assume initialized and return
-                result.assume_init()
-            }
-        }
-    }
-}
-
-/// Phase 3: Generate monomorphized versions for a generic function
-fn generate_monomorphized_versions(
-    rust_sig: &autozig_parser::RustFunctionSignature,
-    mod_name: &str,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let mut ffi_decls = Vec::new();
-    let mut wrappers = Vec::new();
-
-    let base_name = &rust_sig.sig.ident;
-
-    for mono_type in &rust_sig.monomorphize_types {
-        // Generate mangled name: process<T> + i32 -> process_i32
-        let mono_name = syn::Ident::new(
-            &format!("{}_{}", base_name, mono_type.replace("::", "_")),
-            proc_macro2::Span::call_site(),
-        );
-
-        // Substitute generic type T with concrete type
-        let mono_sig = substitute_generic_type(&rust_sig.sig, mono_type);
-
-        // Generate FFI declaration for this monomorphized version
-        let ffi_decl = generate_ffi_declaration_from_sig(&mono_name, &mono_sig);
-        ffi_decls.push(ffi_decl);
-
-        // Generate safe wrapper for this monomorphized version
-        let wrapper = generate_wrapper_from_sig(&mono_name, &mono_sig, mod_name);
-        wrappers.push(wrapper);
-    }
-
-    let ffi_output = quote! { #(#ffi_decls)* };
-    let wrapper_output = quote! { #(#wrappers)* };
-
-    (ffi_output, wrapper_output)
-}
-
-/// Substitute generic type parameter with concrete type
-fn substitute_generic_type(sig: &syn::Signature, concrete_type: &str) -> syn::Signature {
-    let mut new_sig = sig.clone();
-
-    // Parse concrete type
-    let concrete_ty: syn::Type =
-        syn::parse_str(concrete_type).unwrap_or_else(|_| panic!("Invalid type: {}", concrete_type));
-
-    // Get generic parameter name (e.g., "T")
-    let generic_name =
-        if let Some(syn::GenericParam::Type(type_param)) = sig.generics.params.first() {
-            type_param.ident.to_string()
-        } else {
-            return new_sig; // No generics
-        };
-
-    // Remove generics from signature
-    new_sig.generics = syn::Generics::default();
-
-    // Substitute type in parameters
-    for input in &mut new_sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            *pat_type.ty = substitute_type_recursive(&pat_type.ty, &generic_name, &concrete_ty);
-        }
-    }
-
-    // Substitute type in return type
-    if let syn::ReturnType::Type(_, ret_ty) = &mut new_sig.output {
-        **ret_ty = substitute_type_recursive(ret_ty, &generic_name, &concrete_ty);
-    }
-
-    new_sig
-}
-
-/// Recursively substitute generic type in a type expression
-fn substitute_type_recursive(
-    ty: &syn::Type,
-    generic_name: &str,
-    concrete_ty: &syn::Type,
-) -> syn::Type {
-    match ty {
-        syn::Type::Path(type_path) => {
-            // Check if this is the generic parameter
-            if type_path.path.is_ident(generic_name) {
-                concrete_ty.clone()
-            } else {
-                ty.clone()
-            }
-        },
-        syn::Type::Reference(type_ref) => {
-            let mut new_ref = type_ref.clone();
-            *new_ref.elem = substitute_type_recursive(&type_ref.elem, generic_name, concrete_ty);
-            syn::Type::Reference(new_ref)
-        },
-        syn::Type::Slice(type_slice) => {
-            let mut new_slice = type_slice.clone();
-            *new_slice.elem =
-                substitute_type_recursive(&type_slice.elem, generic_name, concrete_ty);
-            syn::Type::Slice(new_slice)
-        },
-        _ => ty.clone(),
-    }
-}
-
-/// Generate FFI declaration from signature with specific name
-fn generate_ffi_declaration_from_sig(
-    fn_name: &syn::Ident,
-    sig: &syn::Signature,
-) -> proc_macro2::TokenStream {
-    let output = &sig.output;
-
-    let mut ffi_params = Vec::new();
-
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            let param_type = &pat_type.ty;
-            let param_name_str = if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                ident.ident.to_string()
-            } else {
-                continue;
-            };
-
-            if let Some((is_mut, elem_type)) = is_slice_or_str_ref(param_type) {
-                let ptr_type = if let Some(elem) = elem_type {
-                    if is_mut {
-                        quote! { *mut #elem }
-                    } else {
-                        quote! { *const #elem }
-                    }
-                } else if is_mut {
-                    quote! { *mut u8 }
-                } else {
-                    quote! { *const u8 }
-                };
-
-                let ptr_name = quote::format_ident!("{}_ptr", param_name_str);
-                let len_name = quote::format_ident!("{}_len", param_name_str);
-
-                ffi_params.push(quote! { #ptr_name: #ptr_type });
-                ffi_params.push(quote! { #len_name: usize });
-            } else {
-                let param_name = &pat_type.pat;
-                ffi_params.push(quote! { #param_name: #param_type });
-            }
-        }
-    }
-
-    quote! {
-        extern "C" {
-            pub fn #fn_name(#(#ffi_params),*) #output;
-        }
-    }
-}
-
-/// Generate safe wrapper from signature with specific name
-fn generate_wrapper_from_sig(
-    fn_name: &syn::Ident,
-    sig: &syn::Signature,
-    mod_name: &str,
-) -> proc_macro2::TokenStream {
-    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
-    let inputs = &sig.inputs;
-    let output = &sig.output;
-
-    let mut ffi_args = Vec::new();
+            });
 
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                let param_name = &ident.ident;
-                let param_type = &pat_type.ty;
+        Some(quote! {
+            mod #mod_ident {
+                // Recompile the test module when the Zig file changes
+                const _: &str = include_str!(#path_str);
 
-                if let Some((is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                    if is_mut {
-                        ffi_args.push(quote! { #param_name.as_mut_ptr() });
-                    } else {
-                        ffi_args.push(quote! { #param_name.as_ptr() });
-                    }
-                    ffi_args.push(quote! { #param_name.len() });
-                } else {
-                    ffi_args.push(quote! { #param_name });
-                }
+                #(#tests)*
             }
-        }
-    }
+        })
+    });
 
-    quote! {
-        /// Monomorphized wrapper (generated by autozig)
-        pub fn #fn_name(#inputs) #output {
-            unsafe {
-                #mod_ident::#fn_name(#(#ffi_args),*)
-            }
-        }
-    }
+    TokenStream::from(quote! { #(#modules)* })
 }
 
-/// Phase 3.2: Generate async FFI and wrapper using spawn_blocking pattern
-/// Architecture: "Rust Async Wrapper, Zig Sync Execution"
-/// - Zig writes normal synchronous code (no async/await needed in Zig)
-/// - Rust async fn automatically uses tokio::task::spawn_blocking
-/// - This prevents blocking the async runtime while maintaining async interface
-fn generate_async_ffi_and_wrapper(
-    rust_sig: &autozig_parser::RustFunctionSignature,
-    mod_name: &str,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let fn_name = &rust_sig.sig.ident;
-    let sig = &rust_sig.sig;
-
-    // Generate standard synchronous FFI declaration
-    // Zig side is always synchronous - no async/await needed!
-    let ffi_decl = generate_ffi_declaration_from_sig(fn_name, sig);
-
-    // Build wrapper parameters and FFI call arguments
-    let inputs = &sig.inputs;
-    let output = &sig.output;
-    let mod_ident = syn::Ident::new(mod_name, proc_macro2::Span::call_site());
-
-    let mut ffi_args = Vec::new();
-    let mut param_captures = Vec::new();
-
-    for input in &sig.inputs {
-        if let syn::FnArg::Typed(pat_type) = input {
-            if let syn::Pat::Ident(ident) = &*pat_type.pat {
-                let param_name = &ident.ident;
-                let param_type = &pat_type.ty;
-
-                // For async, we need to move parameters into the closure
-                // For slices/strings, we need to convert to owned data
-                if let Some((_is_mut, _elem_type)) = is_slice_or_str_ref(param_type) {
-                    // Convert slice to Vec to own the data
-                    param_captures.push(quote! {
-                        let #param_name = #param_name.to_vec();
-                    });
-
-                    ffi_args.push(quote! { #param_name.as_ptr() });
-                    ffi_args.push(quote! { #param_name.len() });
-                } else {
-                    // For Copy types, just capture them
-                    ffi_args.push(quote! { #param_name });
-                }
-            }
+/// Turn a Zig test name into a snake_case Rust identifier
+fn rust_test_ident(name: &str) -> String {
+    let mut ident = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            ident.push(c.to_ascii_lowercase());
+        } else if !ident.ends_with('_') {
+            ident.push('_');
         }
     }
-
-    // Generate async wrapper using spawn_blocking
-    let wrapper = quote! {
-        /// Async wrapper (auto-generated by AutoZig Phase 3.2)
-        ///
-        /// This function uses tokio::task::spawn_blocking to offload the
-        /// synchronous Zig FFI call to a dedicated thread pool, preventing
-        /// blocking of the async runtime.
-        ///
-        /// Zig side: Write normal synchronous code, no async/await needed!
-        pub async fn #fn_name(#inputs) #output {
-            // Capture parameters (convert slices to owned Vec)
-            #(#param_captures)*
-
-            // Offload to blocking thread pool
-            tokio::task::spawn_blocking(move || {
-                unsafe {
-                    #mod_ident::#fn_name(#(#ffi_args),*)
-                }
-            })
-            .await
-            .expect("Zig task panicked or was cancelled")
-        }
-    };
-
-    (ffi_decl, wrapper)
-}
-
-/// Phase 3: Generate FFI declarations and wrappers with monomorphization
-/// support for include_zig!
-///
-/// NOTE: For include_zig!, we DISABLE ABI lowering because external Zig files
-/// are expected to use `extern struct` declarations which are already
-/// ABI-compatible. The engine generates wrappers only for autozig! embedded
-/// code.
-fn generate_with_monomorphization_for_include(
-    config: &IncludeZigConfig,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let mut all_ffi_decls = Vec::new();
-    let mut all_wrappers = Vec::new();
-    let mod_name = config.get_unique_mod_name();
-
-    for rust_sig in &config.rust_signatures {
-        // For include_zig!, external Zig files should handle ABI themselves
-        // by using `extern struct`. We disable ABI lowering here.
-        let mut sig_no_abi_lowering = rust_sig.clone();
-        sig_no_abi_lowering.needs_abi_lowering = false;
-
-        if !rust_sig.generic_params.is_empty() && !rust_sig.monomorphize_types.is_empty() {
-            // Generic function with monomorphization attribute
-            let (mono_ffi, mono_wrappers) =
-                generate_monomorphized_versions(&sig_no_abi_lowering, &mod_name);
-            all_ffi_decls.push(mono_ffi);
-            all_wrappers.push(mono_wrappers);
-        } else if rust_sig.is_async {
-            // Async function
-            let (async_ffi, async_wrapper) =
-                generate_async_ffi_and_wrapper(&sig_no_abi_lowering, &mod_name);
-            all_ffi_decls.push(async_ffi);
-            all_wrappers.push(async_wrapper);
-        } else {
-            // Regular function (non-generic, non-async)
-            let ffi_decl = generate_single_ffi_declaration(&sig_no_abi_lowering);
-            let wrapper = generate_single_safe_wrapper(&sig_no_abi_lowering, &mod_name);
-            all_ffi_decls.push(ffi_decl);
-            all_wrappers.push(wrapper);
-        }
+    let ident = ident.trim_matches('_');
+    match ident.chars().next() {
+        None => "unnamed".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("test_{}", ident),
+        Some(_) if syn::parse_str::<syn::Ident>(ident).is_err() => format!("{}_", ident),
+        Some(_) => ident.to_string(),
     }
-
-    let ffi_decls = quote! { #(#all_ffi_decls)* };
-    let wrappers = quote! { #(#all_wrappers)* };
-
-    (ffi_decls, wrappers)
 }
 
 /// `#[autozig_export]` attribute macro for exporting Rust functions to WASM
@@ -0,0 +1,166 @@
+//! Zig error unions returned across the FFI boundary
+//!
+//! An `export fn` cannot return `E!T` directly. The engine renames such a
+//! function and exports a wrapper returning `AutoZigResult(T, u16)`, where
+//! the error code is the 1-based position of the error in the declared error
+//! set. The macros read the same set to generate a Rust enum with matching
+//! codes, so both sides must resolve it from the same source text.
+
+use crate::type_mapper::zig_exports;
+
+/// Return type of an `export fn` returning an error union
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorUnion {
+    /// Payload type (`void` for `E!void`)
+    pub payload: String,
+    /// Error names; the code of each is its index + 1
+    pub errors: Vec<String>,
+}
+
+impl ErrorUnion {
+    /// Code of `error` in the FFI result
+    pub fn code(&self, error: &str) -> Option<u16> {
+        let index = self.errors.iter().position(|e| e == error)?;
+        u16::try_from(index + 1).ok()
+    }
+}
+
+/// Error union returned by the export `fn_name` in `zig_code`
+///
+/// `None` if there is no such export or it does not return an error union;
+/// `Some(Err(..))` if its error set cannot be resolved (e.g. inferred `!T`).
+pub fn export_error_union(zig_code: &str, fn_name: &str) -> Option<Result<ErrorUnion, String>> {
+    let export = zig_exports(zig_code)
+        .into_iter()
+        .find(|export| export.name == fn_name)?;
+    let (set, payload) = split_error_union(&export.return_type)?;
+    Some(
+        resolve_error_set(zig_code, set, 0)
+            .map(|errors| ErrorUnion { payload: payload.to_string(), errors })
+            .map_err(|reason| format!("`{fn_name}` returns an error union, but {reason}")),
+    )
+}
+
+/// Split `E!T` at the `!` outside of braces
+pub fn split_error_union(return_type: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in return_type.char_indices() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            '!' if depth == 0 => {
+                return Some((return_type[..i].trim(), return_type[i + 1..].trim()));
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Members of the error set expression `set`, in declaration order
+fn resolve_error_set(zig_code: &str, set: &str, depth: usize) -> Result<Vec<String>, String> {
+    let set = set.trim();
+    if set.is_empty() || set == "anyerror" {
+        return Err("its error set is inferred; declare it explicitly, e.g. `error{Overflow}!T`"
+            .to_string());
+    }
+    if depth > 8 {
+        return Err(format!("error set `{set}` is defined recursively"));
+    }
+
+    let mut errors: Vec<String> = Vec::new();
+    for part in set.split("||") {
+        let part = part.trim();
+        let members = if let Some(body) = part.strip_prefix("error") {
+            let body = body.trim();
+            let body = body
+                .strip_prefix('{')
+                .and_then(|body| body.strip_suffix('}'))
+                .ok_or_else(|| format!("error set `{part}` cannot be parsed"))?;
+            body.split(',')
+                .map(|member| member.trim().to_string())
+                .filter(|member| !member.is_empty())
+                .collect()
+        } else {
+            let definition = const_value(zig_code, part)
+                .ok_or_else(|| format!("error set `{part}` is not declared in this block"))?;
+            resolve_error_set(zig_code, &definition, depth + 1)?
+        };
+        for member in members {
+            if !errors.contains(&member) {
+                errors.push(member);
+            }
+        }
+    }
+    Ok(errors)
+}
+
+/// Value of the container-level `const name = value;`
+fn const_value(zig_code: &str, name: &str) -> Option<String> {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut search = 0;
+    while let Some(found) = zig_code[search..].find(name) {
+        let start = search + found;
+        let end = start + name.len();
+        search = end;
+        if zig_code[..start]
+            .chars()
+            .next_back()
+            .is_some_and(is_ident_char)
+            || zig_code[end..].chars().next().is_some_and(is_ident_char)
+        {
+            continue;
+        }
+        let Some(before) = zig_code[..start].trim_end().strip_suffix("const") else {
+            continue;
+        };
+        if before.chars().next_back().is_some_and(is_ident_char) {
+            continue;
+        }
+        let Some(value) = zig_code[end..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.split(';').next()?;
+        // Drop line comments inside multi-line sets
+        let value: Vec<&str> = value
+            .lines()
+            .map(|line| line.split("//").next().unwrap_or(""))
+            .collect();
+        return Some(value.join(" "));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_error_union() {
+        let code = r#"
+const IoError = error{ Closed, Timeout };
+pub const ParseError = error{
+    InvalidDigit, // not 0-9
+    Overflow,
+} || IoError;
+
+export fn parse(ptr: [*]const u8, len: usize) ParseError!i32 { return 0; }
+export fn check(x: u8) error { TooBig , Closed } ! void { _ = x; }
+export fn guess() !u8 { return 1; }
+export fn plain() u8 { return 1; }
+"#;
+        let parse = export_error_union(code, "parse").unwrap().unwrap();
+        assert_eq!(parse.payload, "i32");
+        assert_eq!(parse.errors, ["InvalidDigit", "Overflow", "Closed", "Timeout"]);
+        assert_eq!(parse.code("Closed"), Some(3));
+
+        let check = export_error_union(code, "check").unwrap().unwrap();
+        assert_eq!(check.payload, "void");
+        assert_eq!(check.errors, ["TooBig", "Closed"]);
+
+        let err = export_error_union(code, "guess").unwrap().unwrap_err();
+        assert!(err.contains("inferred"));
+        assert!(export_error_union(code, "plain").is_none());
+        assert!(export_error_union(code, "missing").is_none());
+    }
+}
@@ -0,0 +1,91 @@
+//! SPIR-V compute kernels of `#[autozig(gpu)]` functions
+//!
+//! The engine compiles each kernel into a SPIR-V module of its own,
+//! `$OUT_DIR/autozig_gpu/<symbol>.spv`, and the generated binding embeds it
+//! with its descriptor layout instead of calling it. Every argument is a
+//! storage buffer in descriptor set 0, bound in argument order: `&[T]` read
+//! only, `&mut [T]` read-write. The Zig kernel declares each buffer as a
+//! global named after the argument.
+
+/// Invocations per workgroup when `workgroup_size` is not set
+pub const DEFAULT_WORKGROUP_SIZE: u32 = 64;
+
+/// Directory of the SPIR-V modules in `OUT_DIR`
+pub const SPIRV_DIR: &str = "autozig_gpu";
+
+/// Storage buffer bound to a kernel argument
+#[derive(Clone)]
+pub struct StorageBuffer {
+    /// Argument name, also the name of the Zig global
+    pub name: syn::Ident,
+    /// Element type
+    pub element: syn::Type,
+    /// `&[T]` rather than `&mut [T]`
+    pub read_only: bool,
+}
+
+/// Storage buffers of the kernel declared as `sig`
+///
+/// Fails unless every argument is a slice and nothing is returned: kernels
+/// take no scalars and write their results into buffers.
+pub fn storage_buffers(sig: &syn::Signature) -> syn::Result<Vec<StorageBuffer>> {
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "`gpu` kernels return nothing; write results into a `&mut [T]` buffer",
+        ));
+    }
+    sig.inputs
+        .iter()
+        .map(|input| {
+            let slice = match input {
+                syn::FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+                    (syn::Pat::Ident(ident), syn::Type::Reference(reference)) => {
+                        match &*reference.elem {
+                            syn::Type::Slice(slice) => Some((
+                                ident.ident.clone(),
+                                (*slice.elem).clone(),
+                                reference.mutability.is_none(),
+                            )),
+                            _ => None,
+                        }
+                    },
+                    _ => None,
+                },
+                syn::FnArg::Receiver(_) => None,
+            };
+            let (name, element, read_only) = slice.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    input,
+                    "`gpu` kernel arguments must be `&[T]` or `&mut [T]` storage buffers; pass \
+                     scalars in a one-element buffer",
+                )
+            })?;
+            Ok(StorageBuffer { name, element, read_only })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_buffers() {
+        let sig: syn::Signature = syn::parse_quote! {
+            fn saxpy(x: &[f32], y: &mut [f32], params: &[f32])
+        };
+        let buffers = storage_buffers(&sig).unwrap();
+        let names: Vec<_> = buffers
+            .iter()
+            .map(|buffer| buffer.name.to_string())
+            .collect();
+        assert_eq!(names, ["x", "y", "params"]);
+        assert!(buffers[0].read_only && !buffers[1].read_only);
+
+        let scalar: syn::Signature = syn::parse_quote! { fn scale(x: &mut [f32], a: f32) };
+        assert!(storage_buffers(&scalar).is_err());
+        let returns: syn::Signature = syn::parse_quote! { fn sum(x: &[f32]) -> f32 };
+        assert!(storage_buffers(&returns).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Leading inner attributes of an `autozig!` block
+//!
+//! `#![infer]` derives missing signatures from the Zig exports, and
+//! `#![autozig(...)]` configures the block without build.rs changes:
+//!
+//! ```text
+//! autozig! {
+//!     #![autozig(mode = "modular", mod = "ffi_img", optimize = "ReleaseFast")]
+//!     export fn blur(...) void { ... }
+//!     ---
+//!     fn blur(...);
+//! }
+//! ```
+//!
+//! `mod` generates the bindings in a module, like `autozig!(mod = ffi_img,
+//! { ... })`. `mode` and `optimize` apply to the crate's whole Zig library:
+//! the scanner copies them into a [`CONFIG_MARKER`] comment above the
+//! block's Zig code, and the engine uses them unless build.rs or
+//! `AUTOZIG_MODE` chose otherwise.
+
+use syn::{
+    parse::{
+        ParseStream,
+        Parser,
+    },
+    Token,
+};
+
+/// Prefix of the comment line carrying a block's `mode` and `optimize`
+pub const CONFIG_MARKER: &str = "// @autozig:config ";
+
+/// Compilation modes a block can ask for; `modular` is `modular_buildzig`
+pub const MODES: &[&str] = &["merged", "modular", "modular_import", "modular_buildzig"];
+
+/// Zig optimization modes a block can ask for
+pub const OPTIMIZE_MODES: &[&str] = &["Debug", "ReleaseSafe", "ReleaseFast", "ReleaseSmall"];
+
+/// Settings of the leading `#![...]` attributes of a block
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// `#![infer]`
+    pub infer: bool,
+    /// Module of the bindings (`mod = "ffi_img"`)
+    pub namespace: Option<syn::Ident>,
+    /// Compilation mode, as named by `AUTOZIG_MODE` (`modular` normalized
+    /// to `modular_buildzig`)
+    pub mode: Option<String>,
+    /// Zig optimization mode (`ReleaseFast`, ...)
+    pub optimize: Option<String>,
+}
+
+impl BlockHeader {
+    /// Parse the leading `#![...]` attributes of `input`, if any
+    pub fn parse_leading(input: ParseStream) -> syn::Result<BlockHeader> {
+        let mut header = BlockHeader::default();
+        if !(input.peek(Token![#]) && input.peek2(Token![!])) {
+            return Ok(header);
+        }
+        for attr in input.call(syn::Attribute::parse_inner)? {
+            if attr.path().is_ident("infer") {
+                header.infer = true;
+            } else if attr.path().is_ident("autozig") {
+                attr.parse_nested_meta(|meta| header.parse_setting(meta))?;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "unknown autozig! attribute, expected `#![infer]` or `#![autozig(...)]`",
+                ));
+            }
+        }
+        Ok(header)
+    }
+
+    /// Header of a macro's tokens, ignoring the rest of the block
+    pub fn of_tokens(tokens: proc_macro2::TokenStream) -> syn::Result<BlockHeader> {
+        let parser = |input: ParseStream| {
+            let header = BlockHeader::parse_leading(input)?;
+            input.parse::<proc_macro2::TokenStream>()?;
+            Ok(header)
+        };
+        parser.parse2(tokens)
+    }
+
+    fn parse_setting(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        let value = |meta: &syn::meta::ParseNestedMeta| -> syn::Result<syn::LitStr> {
+            meta.value()?.parse()
+        };
+        let choice = |lit: &syn::LitStr, choices: &[&str], what: &str| {
+            if choices.contains(&lit.value().as_str()) {
+                Ok(lit.value())
+            } else {
+                Err(syn::Error::new_spanned(
+                    lit,
+                    format!(
+                        "unknown {what} `{}`, expected one of: {}",
+                        lit.value(),
+                        choices.join(", ")
+                    ),
+                ))
+            }
+        };
+        if meta.path.is_ident("mod") {
+            self.namespace = Some(value(&meta)?.parse()?);
+        } else if meta.path.is_ident("mode") {
+            let mode = choice(&value(&meta)?, MODES, "mode")?;
+            self.mode = Some(match mode.as_str() {
+                "modular" => "modular_buildzig".to_string(),
+                _ => mode,
+            });
+        } else if meta.path.is_ident("optimize") {
+            self.optimize = Some(choice(&value(&meta)?, OPTIMIZE_MODES, "optimize mode")?);
+        } else {
+            return Err(meta.error("unknown setting, expected `mode`, `mod` or `optimize`"));
+        }
+        Ok(())
+    }
+
+    /// [`CONFIG_MARKER`] line carrying `mode` and `optimize`, if either is
+    /// set
+    pub fn marker(&self) -> Option<String> {
+        let mut settings = Vec::new();
+        if let Some(mode) = &self.mode {
+            settings.push(format!("mode={mode}"));
+        }
+        if let Some(optimize) = &self.optimize {
+            settings.push(format!("optimize={optimize}"));
+        }
+        (!settings.is_empty()).then(|| format!("{CONFIG_MARKER}{}", settings.join(" ")))
+    }
+
+    /// `mode` and `optimize` of a [`CONFIG_MARKER`] line
+    pub fn from_marker(line: &str) -> Option<BlockHeader> {
+        let settings = line.trim().strip_prefix(CONFIG_MARKER)?;
+        let mut header = BlockHeader::default();
+        for setting in settings.split_whitespace() {
+            match setting.split_once('=') {
+                Some(("mode", mode)) => header.mode = Some(mode.to_string()),
+                Some(("optimize", optimize)) => header.optimize = Some(optimize.to_string()),
+                _ => {},
+            }
+        }
+        Some(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn test_block_header() {
+        let header = BlockHeader::of_tokens(quote! {
+            #![autozig(mode = "modular", mod = "ffi_img", optimize = "ReleaseFast")]
+            #![infer]
+            export fn f() void {}
+        })
+        .unwrap();
+        assert!(header.infer);
+        assert_eq!(header.namespace.as_ref().unwrap(), "ffi_img");
+        let marker = header.marker().unwrap();
+        assert_eq!(marker, "// @autozig:config mode=modular_buildzig optimize=ReleaseFast");
+        assert_eq!(
+            BlockHeader::from_marker(&marker).unwrap(),
+            BlockHeader {
+                mode: header.mode.clone(),
+                optimize: header.optimize.clone(),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            BlockHeader::of_tokens(quote! { export fn f() void {} }).unwrap(),
+            BlockHeader::default()
+        );
+        let err = BlockHeader::of_tokens(quote! { #![autozig(optimize = "Fast")] }).unwrap_err();
+        assert!(err.to_string().contains("unknown optimize mode `Fast`"));
+        assert!(BlockHeader::of_tokens(quote! { #![autozig(threads = 4)] }).is_err());
+    }
+}
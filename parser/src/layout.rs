@@ -0,0 +1,83 @@
+//! Struct layouts measured from compiled Zig code
+//!
+//! The engine compiles a probe for every `extern struct` and records the
+//! layouts Zig chose in `OUT_DIR/autozig_layouts.txt` (path exported as
+//! `AUTOZIG_LAYOUTS`). The macros read it back to assert at compile time
+//! that the matching `#[repr(C)]` Rust structs agree.
+//!
+//! One struct per line: `Point size=8 align=4 x=0 y=4`.
+
+/// Size, alignment and field offsets of a Zig `extern struct`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub name: String,
+    pub size: u64,
+    pub align: u64,
+    /// Field names with their byte offsets, in declaration order
+    pub fields: Vec<(String, u64)>,
+}
+
+impl StructLayout {
+    /// Offset of `field`, if the Zig struct has it
+    pub fn offset_of(&self, field: &str) -> Option<u64> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Render as one line of the layouts file
+    pub fn render(&self) -> String {
+        let mut line = format!("{} size={} align={}", self.name, self.size, self.align);
+        for (field, offset) in &self.fields {
+            line.push_str(&format!(" {field}={offset}"));
+        }
+        line
+    }
+
+    /// Parse one line of the layouts file
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let name = parts.next()?.to_string();
+        let mut size = None;
+        let mut align = None;
+        let mut fields = Vec::new();
+        for part in parts {
+            let (key, value) = part.split_once('=')?;
+            let value = value.parse().ok()?;
+            match key {
+                "size" if size.is_none() => size = Some(value),
+                "align" if align.is_none() => align = Some(value),
+                _ => fields.push((key.to_string(), value)),
+            }
+        }
+        Some(Self { name, size: size?, align: align?, fields })
+    }
+}
+
+/// Parse a layouts file, skipping malformed lines
+pub fn parse_layouts(text: &str) -> Vec<StructLayout> {
+    text.lines().filter_map(StructLayout::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_roundtrip() {
+        let layout = StructLayout {
+            name: "Point".to_string(),
+            size: 8,
+            align: 4,
+            fields: vec![("x".to_string(), 0), ("y".to_string(), 4)],
+        };
+        assert_eq!(layout.render(), "Point size=8 align=4 x=0 y=4");
+        assert_eq!(
+            parse_layouts(&format!("{}\n\nbroken\n", layout.render())),
+            vec![layout.clone()]
+        );
+        assert_eq!(layout.offset_of("y"), Some(4));
+        assert_eq!(layout.offset_of("z"), None);
+    }
+}
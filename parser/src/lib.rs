@@ -4,6 +4,14 @@
 
 #![forbid(unsafe_code)]
 
+pub mod error_union;
+pub mod gpu;
+pub mod header;
+pub mod layout;
+pub mod lowering;
+pub mod multiversion;
+pub mod type_mapper;
+
 use proc_macro2::TokenStream;
 use syn::{
     parse::{
@@ -15,7 +23,9 @@ use syn::{
     ItemStruct,
     Result as ParseResult,
     Signature,
+    Token,
 };
+use type_mapper::TypeMapper;
 
 /// Configuration parsed from autozig! macro
 #[derive(Debug, Clone)]
@@ -32,6 +42,11 @@ pub struct AutoZigConfig {
     pub rust_enums: Vec<RustEnumDefinition>,
     /// Rust trait implementations (Phase 1: stateless traits)
     pub rust_trait_impls: Vec<RustTraitImpl>,
+    /// Zig data bound as Rust statics (`static CRC_TABLE: [u32; 256];`)
+    pub rust_statics: Vec<RustStaticDefinition>,
+    /// Module the bindings are generated in (`autozig!(mod = img, { ... })`);
+    /// the block's Zig exports get a `img__` prefix
+    pub namespace: Option<syn::Ident>,
 }
 
 /// Generic parameter definition (Phase 3)
@@ -56,6 +71,46 @@ pub struct AutoZigBindingConfig {
     pub c_ret: Option<syn::Type>,
     /// Mapping function for return value conversion
     pub map_fn: Option<syn::Expr>,
+    /// Zig keeps pointers passed to this function after it returns
+    /// (`#[autozig(retains)]`)
+    pub retains: bool,
+    /// Keep the wrapper safe even though it takes raw pointers
+    /// (`#[autozig(assume_safe)]`)
+    pub assume_safe: bool,
+    /// Iterations per step of a time-sliced function
+    /// (`#[autozig(time_sliced = N)]`)
+    pub time_sliced: Option<u32>,
+    /// Inputs shorter than this run the Rust body instead of the Zig kernel
+    /// (`#[autozig(fallback_below = N)]`)
+    pub fallback_below: Option<usize>,
+    /// Run the call in a forked child process (`#[autozig(isolated)]`)
+    pub isolated: bool,
+    /// Also compile the function for newer CPUs and pick the best variant
+    /// at runtime (`#[autozig(multiversion)]`)
+    pub multiversion: bool,
+    /// Executor offloading an async function's blocking call:
+    /// `"tokio"` (default), `"autozig"` (`autozig::runtime`'s pool),
+    /// `"async-std"`, `"smol"` or the path of a `spawn_blocking` function
+    /// (`#[autozig(runtime = "...")]`)
+    pub runtime: Option<String>,
+    /// Give up on an async function's blocking call after this many
+    /// milliseconds (`#[autozig(timeout_ms = N)]`, tokio only)
+    pub timeout_ms: Option<u64>,
+    /// Export the function to Python (`#[autozig(python)]`, `python`
+    /// feature)
+    pub python: bool,
+    /// The wrapper neither allocates nor blocks, so interrupt handlers can
+    /// call it (`#[autozig(isr_safe)]`)
+    pub isr_safe: bool,
+    /// Section of an `isr_safe` wrapper, e.g. `.ramfunc`
+    /// (`#[autozig(isr_safe, link_section = "...")]`)
+    pub link_section: Option<String>,
+    /// Compile the function to a SPIR-V compute kernel instead of binding
+    /// it (`#[autozig(gpu)]`)
+    pub gpu: bool,
+    /// Invocations per workgroup of a `gpu` kernel
+    /// (`#[autozig(gpu, workgroup_size = N)]`, default 64)
+    pub workgroup_size: Option<u32>,
 }
 
 impl std::fmt::Debug for AutoZigBindingConfig {
@@ -66,6 +121,19 @@ impl std::fmt::Debug for AutoZigBindingConfig {
             .field("prefix_c", &self.prefix_c)
             .field("c_ret", &self.c_ret.as_ref().map(|_| "<Type>"))
             .field("map_fn", &self.map_fn.as_ref().map(|_| "<Expr>"))
+            .field("retains", &self.retains)
+            .field("assume_safe", &self.assume_safe)
+            .field("time_sliced", &self.time_sliced)
+            .field("fallback_below", &self.fallback_below)
+            .field("isolated", &self.isolated)
+            .field("multiversion", &self.multiversion)
+            .field("runtime", &self.runtime)
+            .field("timeout_ms", &self.timeout_ms)
+            .field("python", &self.python)
+            .field("isr_safe", &self.isr_safe)
+            .field("link_section", &self.link_section)
+            .field("gpu", &self.gpu)
+            .field("workgroup_size", &self.workgroup_size)
             .finish()
     }
 }
@@ -78,18 +146,36 @@ pub struct RustFunctionSignature {
     pub generic_params: Vec<GenericParam>,
     /// Whether this is an async function (Phase 3: Async support)
     pub is_async: bool,
-    /// Monomorphization attribute types (e.g., #[monomorphize(i32, f64)])
+    /// Monomorphization attribute types (e.g., #[monomorphize(i32, f64)]);
+    /// with several type parameters each entry is a tuple, e.g. `(u8, f32)`
     pub monomorphize_types: Vec<String>,
+    /// Values bound for the const generic parameter (e.g.,
+    /// #[monomorphize_const(1, 3, 5)])
+    pub monomorphize_const: Vec<String>,
     /// Whether this function needs ABI lowering (struct return -> pointer)
     pub needs_abi_lowering: bool,
     /// AutoZig binding configuration for dual export support
     pub binding_config: AutoZigBindingConfig,
+    /// Pure Rust implementation given as the function body, used for small
+    /// inputs with `#[autozig(fallback_below = N)]`
+    pub rust_fallback: Option<Box<syn::Block>>,
+    /// `#[cfg(...)]` attributes, copied onto the generated bindings; the
+    /// engine drops the Zig export on targets they exclude
+    pub cfg_attrs: Vec<syn::Attribute>,
+    /// Doc comments, copied onto the generated safe wrapper
+    pub doc_attrs: Vec<syn::Attribute>,
 }
 
 /// A Rust struct definition for FFI types
 #[derive(Clone)]
 pub struct RustStructDefinition {
     pub item: ItemStruct,
+    /// `#[autozig(bridge)]`: the Zig `extern struct` is generated from this
+    /// definition (the marker itself is removed from `item`)
+    pub bridge: bool,
+    /// `#[monomorphize(f32, f64)]` on a generic struct: the instances named
+    /// in Rust and Zig as `Matrix_f32`, ... (removed from `item`)
+    pub monomorphize_types: Vec<String>,
 }
 
 /// A Rust enum definition for FFI types
@@ -103,6 +189,9 @@ pub struct RustEnumDefinition {
 pub struct RustTraitImpl {
     /// The trait being implemented (e.g., "Calculator")
     pub trait_name: String,
+    /// The trait path as written, including generic arguments (e.g.
+    /// `digest::Digest<32>`); `None` for inherent impls
+    pub trait_path: Option<syn::Path>,
     /// The type implementing the trait (e.g., "ZigCalculator")
     pub target_type: String,
     /// Methods in this trait implementation
@@ -115,6 +204,33 @@ pub struct RustTraitImpl {
     pub constructor: Option<TraitMethod>,
     /// Destructor method for opaque types - Phase 2
     pub destructor: Option<TraitMethod>,
+    /// Skip the generated `Default` impl (`#[autozig(no_default)]`)
+    pub no_default: bool,
+    /// Skip the generated `Drop` impl (`#[autozig(no_drop)]`)
+    pub no_drop: bool,
+    /// Opaque type asserted thread-safe to move across threads
+    /// (`struct T(opaque, send);`)
+    pub send: bool,
+    /// Opaque type asserted thread-safe to share across threads
+    /// (`struct T(opaque, sync);`)
+    pub sync: bool,
+    /// Serialize method calls behind a mutex in debug builds
+    /// (`struct T(opaque, debug_lock);`)
+    pub debug_lock: bool,
+    /// Export the opaque type to Python as a class (`#[autozig(python)]` on
+    /// its declaration or an impl, `python` feature)
+    pub python: bool,
+    /// Associated types and consts of the impl (e.g. `type Item = u32;`),
+    /// copied verbatim
+    pub assoc_items: Vec<syn::ImplItem>,
+    /// Methods that do not call into Zig, copied verbatim
+    pub rust_methods: Vec<syn::ImplItemFn>,
+}
+
+/// A `static NAME: T;` bound to data the Zig code exports
+#[derive(Clone)]
+pub struct RustStaticDefinition {
+    pub item: syn::ForeignItemStatic,
 }
 
 /// A method within a trait implementation
@@ -134,12 +250,16 @@ pub struct TraitMethod {
     pub is_constructor: bool,
     /// Whether this is a destructor (#[destructor]) - Phase 2
     pub is_destructor: bool,
+    /// Doc comments, copied onto the generated method
+    pub doc_attrs: Vec<syn::Attribute>,
 }
 
 impl std::fmt::Debug for RustStructDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RustStructDefinition")
             .field("ident", &self.item.ident.to_string())
+            .field("bridge", &self.bridge)
+            .field("monomorphize_types", &self.monomorphize_types)
             .finish()
     }
 }
@@ -160,6 +280,22 @@ impl std::fmt::Debug for RustTraitImpl {
             .field("methods", &self.methods.len())
             .field("is_zst", &self.is_zst)
             .field("is_opaque", &self.is_opaque)
+            .field("no_default", &self.no_default)
+            .field("no_drop", &self.no_drop)
+            .field("send", &self.send)
+            .field("sync", &self.sync)
+            .field("debug_lock", &self.debug_lock)
+            .field("python", &self.python)
+            .field("assoc_items", &self.assoc_items.len())
+            .field("rust_methods", &self.rust_methods.len())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for RustStaticDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RustStaticDefinition")
+            .field("ident", &self.item.ident.to_string())
             .finish()
     }
 }
@@ -183,6 +319,34 @@ impl std::fmt::Debug for RustFunctionSignature {
 
 impl Parse for AutoZigConfig {
     fn parse(input: ParseStream) -> ParseResult<Self> {
+        // `mod = img, { ... }`: bindings in `pub mod img`
+        if input.peek(Token![mod]) && input.peek2(Token![=]) {
+            input.parse::<Token![mod]>()?;
+            input.parse::<Token![=]>()?;
+            let namespace = if input.peek(syn::LitStr) {
+                input.parse::<syn::LitStr>()?.parse::<syn::Ident>()?
+            } else {
+                input.parse::<syn::Ident>()?
+            };
+            input.parse::<Token![,]>()?;
+            let content;
+            syn::braced!(content in input);
+            let mut config: AutoZigConfig = content.parse()?;
+            input.parse::<Option<Token![,]>>()?;
+            if config.namespace.is_some() {
+                return Err(syn::Error::new_spanned(
+                    namespace,
+                    "module given twice, by `mod = ...` and by `#![autozig(mod = ...)]`",
+                ));
+            }
+            config.namespace = Some(namespace);
+            return Ok(config);
+        }
+
+        // `#![infer]` and `#![autozig(...)]` (see `header`)
+        let span = input.span();
+        let header = header::BlockHeader::parse_leading(input)?;
+
         // Strategy: Parse everything as a token stream, then split by "---" separator
         let tokens: TokenStream = input.parse()?;
         let token_str = tokens.to_string();
@@ -201,16 +365,18 @@ impl Parse for AutoZigConfig {
         }
 
 
-        if parts.len() == 1 {
+        let mut config = if parts.len() == 1 {
             // No separator, treat entire input as Zig code
-            Ok(AutoZigConfig {
+            AutoZigConfig {
                 zig_code: parts[0].trim().to_string(),
                 external_file: None,
                 rust_signatures: Vec::new(),
                 rust_structs: Vec::new(),
                 rust_enums: Vec::new(),
                 rust_trait_impls: Vec::new(),
-            })
+                rust_statics: Vec::new(),
+                namespace: None,
+            }
         } else if parts.len() >= 2 {
             // Has separator: first part is Zig, second is Rust definitions
             let zig_code = parts[0].trim().to_string();
@@ -218,26 +384,36 @@ impl Parse for AutoZigConfig {
 
             // Parse Rust definitions (enums, structs, function signatures, and trait impls)
             // from second part
-            let (rust_enums, rust_structs, rust_signatures, rust_trait_impls) =
+            let (rust_enums, rust_structs, rust_signatures, rust_trait_impls, rust_statics) =
                 parse_rust_definitions(parts[1])?;
 
 
-            Ok(AutoZigConfig {
+            AutoZigConfig {
                 zig_code,
                 external_file: None,
                 rust_signatures,
                 rust_structs,
                 rust_enums,
                 rust_trait_impls,
-            })
+                rust_statics,
+                namespace: None,
+            }
         } else {
-            Err(syn::Error::new(input.span(), "autozig! macro parsing error"))
+            return Err(syn::Error::new(input.span(), "autozig! macro parsing error"));
+        };
+
+        config.namespace = header.namespace;
+        if header.infer {
+            config
+                .infer_signatures()
+                .map_err(|message| syn::Error::new(span, message))?;
         }
+        Ok(config)
     }
 }
 
-/// Parse Rust definitions (enums, structs, function signatures, and trait
-/// impls) from a string
+/// Parse Rust definitions (enums, structs, function signatures, trait impls
+/// and statics) from a string
 fn parse_rust_definitions(
     input: &str,
 ) -> ParseResult<(
@@ -245,11 +421,13 @@ fn parse_rust_definitions(
     Vec<RustStructDefinition>,
     Vec<RustFunctionSignature>,
     Vec<RustTraitImpl>,
+    Vec<RustStaticDefinition>,
 )> {
     let mut enums = Vec::new();
     let mut structs = Vec::new();
     let mut signatures = Vec::new();
     let mut trait_impls = Vec::new();
+    let mut statics = Vec::new();
     let mut trait_impl_types = std::collections::HashSet::new();
 
 
@@ -280,14 +458,45 @@ fn parse_rust_definitions(
             if let syn::Item::Mod(item_mod) = item {
                 if let Some((_, items)) = item_mod.content {
                     eprintln!("Parser: Module has {} items", items.len());
-                    // First pass: collect opaque struct definitions
+                    // First pass: collect opaque struct definitions and generated
+                    // impl opt-outs
                     let mut opaque_types = std::collections::HashSet::new();
+                    let mut opaque_markers = std::collections::HashMap::new();
+                    let mut opt_outs = std::collections::HashMap::new();
+                    let mut python_types = std::collections::HashSet::new();
+                    for inner_item in &items {
+                        let (type_name, attrs) = match inner_item {
+                            syn::Item::Struct(item_struct) => {
+                                (item_struct.ident.to_string(), &item_struct.attrs)
+                            },
+                            syn::Item::Impl(item_impl) => match &*item_impl.self_ty {
+                                syn::Type::Path(type_path) => {
+                                    match type_path.path.segments.last() {
+                                        Some(segment) => {
+                                            (segment.ident.to_string(), &item_impl.attrs)
+                                        },
+                                        None => continue,
+                                    }
+                                },
+                                _ => continue,
+                            },
+                            _ => continue,
+                        };
+                        if has_python_marker(attrs) {
+                            python_types.insert(type_name.clone());
+                        }
+                        let (no_default, no_drop) = extract_impl_opt_outs(attrs);
+                        let entry = opt_outs.entry(type_name).or_insert((false, false));
+                        entry.0 |= no_default;
+                        entry.1 |= no_drop;
+                    }
                     for inner_item in &items {
                         if let syn::Item::Struct(item_struct) = inner_item {
                             eprintln!("Parser: Found struct: {}", item_struct.ident);
-                            if is_opaque_struct(item_struct) {
+                            if let Some(markers) = opaque_struct_markers(item_struct) {
                                 eprintln!("Parser:   -> Marked as OPAQUE");
                                 opaque_types.insert(item_struct.ident.to_string());
+                                opaque_markers.insert(item_struct.ident.to_string(), markers);
                             }
                         }
                     }
@@ -331,6 +540,21 @@ fn parse_rust_definitions(
                     }
                     eprintln!("Parser: Total trait impls collected: {}", trait_impls.len());
 
+                    // Opt-outs and markers apply to every impl block of the type
+                    for trait_impl in &mut trait_impls {
+                        trait_impl.python = python_types.contains(&trait_impl.target_type);
+                        if let Some(&(no_default, no_drop)) = opt_outs.get(&trait_impl.target_type)
+                        {
+                            trait_impl.no_default = no_default;
+                            trait_impl.no_drop = no_drop;
+                        }
+                        if let Some(markers) = opaque_markers.get(&trait_impl.target_type) {
+                            trait_impl.send = markers.send;
+                            trait_impl.sync = markers.sync;
+                            trait_impl.debug_lock = markers.debug_lock;
+                        }
+                    }
+
                     // Third pass: collect everything else, skipping structs that will be generated
                     for inner_item in items {
                         // Debug: log what type of item this is
@@ -350,7 +574,7 @@ fn parse_rust_definitions(
                                 eprintln!("Parser:   -> Collecting Enum");
                                 enums.push(RustEnumDefinition { item: item_enum });
                             },
-                            syn::Item::Struct(item_struct) => {
+                            syn::Item::Struct(mut item_struct) => {
                                 eprintln!("Parser:   -> Checking Struct");
                                 // Skip opaque struct declarations (they will be generated by macro)
                                 // Skip structs that will be generated by trait impl
@@ -358,12 +582,24 @@ fn parse_rust_definitions(
                                 if !trait_impl_types.contains(&struct_name)
                                     && !is_opaque_struct(&item_struct)
                                 {
-                                    structs.push(RustStructDefinition { item: item_struct });
+                                    let bridge = take_bridge_marker(&mut item_struct.attrs);
+                                    let monomorphize_types =
+                                        extract_monomorphize_types(&item_struct.attrs);
+                                    item_struct
+                                        .attrs
+                                        .retain(|attr| !attr.path().is_ident("monomorphize"));
+                                    structs.push(RustStructDefinition {
+                                        item: item_struct,
+                                        bridge,
+                                        monomorphize_types,
+                                    });
                                 }
                             },
                             syn::Item::Fn(item_fn) => {
-                                signatures
-                                    .push(parse_function_signature(item_fn.sig, &item_fn.attrs));
+                                let mut signature =
+                                    parse_function_signature(item_fn.sig, &item_fn.attrs);
+                                signature.rust_fallback = Some(item_fn.block);
+                                signatures.push(signature);
                             },
                             syn::Item::Impl(_) => {
                                 // Already processed in first pass
@@ -380,6 +616,14 @@ fn parse_rust_definitions(
                             },
                             syn::Item::Verbatim(tokens) => {
                                 // Verbatim items are unparsed token streams
+                                // A `static` without initializer binds Zig data
+                                if let Ok(item_static) =
+                                    syn::parse2::<syn::ForeignItemStatic>(tokens.clone())
+                                {
+                                    statics.push(RustStaticDefinition { item: item_static });
+                                    continue;
+                                }
+
                                 // Try to parse as a function signature
                                 let tokens_str = tokens.to_string();
 
@@ -439,12 +683,12 @@ fn parse_rust_definitions(
         }
     }
 
-    Ok((enums, structs, signatures, trait_impls))
+    Ok((enums, structs, signatures, trait_impls, statics))
 }
 
 /// Check if a type is a safe primitive (whitelist mechanism for ABI)
 /// Only these types can be safely returned by value across FFI boundaries
-fn is_safe_primitive(ty: &syn::Type) -> bool {
+pub fn is_safe_primitive(ty: &syn::Type) -> bool {
     if let syn::Type::Path(type_path) = ty {
         if let Some(ident) = type_path.path.get_ident() {
             let ident_str = ident.to_string();
@@ -479,6 +723,63 @@ fn is_safe_primitive(ty: &syn::Type) -> bool {
     }
 }
 
+/// Check if a type is `Option<*const T>` / `Option<*mut T>`
+/// These are returned as a plain nullable pointer (null = None)
+fn is_nullable_pointer(ty: &syn::Type) -> bool {
+    let syn::Type::Path(type_path) = ty else {
+        return false;
+    };
+    let Some(segment) = type_path.path.segments.last() else {
+        return false;
+    };
+    if segment.ident != "Option" {
+        return false;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => {
+            matches!(args.args.first(), Some(syn::GenericArgument::Type(syn::Type::Ptr(_))))
+        },
+        _ => false,
+    }
+}
+
+/// Check if a type is `MapBuilder<K, V>`
+/// Zig fills these through an out pointer and returns nothing
+pub fn is_map_builder(ty: &syn::Type) -> bool {
+    let syn::Type::Path(type_path) = ty else {
+        return false;
+    };
+    type_path.path.segments.last().is_some_and(|segment| {
+        segment.ident == "MapBuilder"
+            && matches!(segment.arguments, syn::PathArguments::AngleBracketed(_))
+    })
+}
+
+/// Check if a type is a refcounted byte buffer: `Bytes` (from the `bytes`
+/// crate) or `Arc<[u8]>`
+/// Zig receives its pointer and length, and the wrapper keeps the owner alive
+/// for the call instead of copying it
+pub fn is_shared_bytes(ty: &syn::Type) -> bool {
+    let syn::Type::Path(type_path) = ty else {
+        return false;
+    };
+    let Some(segment) = type_path.path.segments.last() else {
+        return false;
+    };
+    match &segment.arguments {
+        syn::PathArguments::None => segment.ident == "Bytes",
+        syn::PathArguments::AngleBracketed(args) if segment.ident == "Arc" => {
+            matches!(
+                args.args.first(),
+                Some(syn::GenericArgument::Type(syn::Type::Slice(slice)))
+                    if args.args.len() == 1
+                        && matches!(&*slice.elem, syn::Type::Path(elem) if elem.path.is_ident("u8"))
+            )
+        },
+        _ => false,
+    }
+}
+
 /// Parse a function signature with generics and async support (Phase 3)
 fn parse_function_signature(sig: Signature, attrs: &[syn::Attribute]) -> RustFunctionSignature {
     // Extract generic parameters
@@ -523,7 +824,9 @@ fn parse_function_signature(sig: Signature, attrs: &[syn::Attribute]) -> RustFun
     // ABI lowering
     let needs_abi_lowering = match &sig.output {
         syn::ReturnType::Default => false, // void return, no lowering needed
-        syn::ReturnType::Type(_, ty) => !is_safe_primitive(ty),
+        syn::ReturnType::Type(_, ty) => {
+            !is_safe_primitive(ty) && !is_nullable_pointer(ty) && !is_map_builder(ty)
+        },
     };
 
     // Extract AutoZig binding configuration from attributes
@@ -534,33 +837,120 @@ fn parse_function_signature(sig: Signature, attrs: &[syn::Attribute]) -> RustFun
         generic_params,
         is_async,
         monomorphize_types,
+        monomorphize_const: extract_attribute_list(attrs, "monomorphize_const"),
         needs_abi_lowering,
         binding_config,
+        rust_fallback: None,
+        cfg_attrs: attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .cloned()
+            .collect(),
+        doc_attrs: doc_attrs(attrs),
     }
 }
 
+/// `#[doc]` attributes (doc comments) among `attrs`
+fn doc_attrs(attrs: &[syn::Attribute]) -> Vec<syn::Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .cloned()
+        .collect()
+}
+
 /// Extract types from #[monomorphize(T1, T2, ...)] attribute
 fn extract_monomorphize_types(attrs: &[syn::Attribute]) -> Vec<String> {
+    extract_attribute_list(attrs, "monomorphize")
+}
+
+/// Comma-separated arguments of the `#[<name>(...)]` attribute
+fn extract_attribute_list(attrs: &[syn::Attribute], name: &str) -> Vec<String> {
     for attr in attrs {
         if let syn::Meta::List(meta_list) = &attr.meta {
-            if meta_list.path.is_ident("monomorphize") {
+            if meta_list.path.is_ident(name) {
                 // Parse the token stream: (i32, f64, u8)
-                let tokens = &meta_list.tokens;
-                let tokens_str = tokens.to_string();
-                // Simple comma-separated parsing
-                return tokens_str
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+                return split_top_level(&meta_list.tokens.to_string());
             }
         }
     }
     Vec::new()
 }
 
+/// Comma-separated items of `list`, keeping commas nested in `()`, `[]` and
+/// `<>` (e.g. `(u8, f32), Pair<i32, i32>` has two items)
+fn split_top_level(list: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut item = String::new();
+    for c in list.chars() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(std::mem::take(&mut item));
+                continue;
+            },
+            _ => {},
+        }
+        item.push(c);
+    }
+    items.push(item);
+    items
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Symbol-safe spelling of a Rust type for monomorphized names:
+/// `geometry::Vec3` becomes `geometry_Vec3` and `Matrix<f32>` `Matrix_f32`
+pub fn mangle_type(ty: &str) -> String {
+    let mut mangled = String::new();
+    for c in ty.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            mangled.push(c);
+        } else if !mangled.is_empty() && !mangled.ends_with('_') {
+            mangled.push('_');
+        }
+    }
+    mangled.trim_end_matches('_').to_string()
+}
+
+impl RustFunctionSignature {
+    /// The concrete types of each `#[monomorphize]` instance, one per type
+    /// parameter: `#[monomorphize((u8, f32), (i16, f32))]` on
+    /// `convert<Src, Dst>` gives `[["u8", "f32"], ["i16", "f32"]]`
+    pub fn monomorphize_instances(&self) -> Vec<Vec<String>> {
+        self.monomorphize_types
+            .iter()
+            .map(|entry| {
+                let tuple = entry
+                    .strip_prefix('(')
+                    .and_then(|inner| inner.strip_suffix(')'));
+                match tuple {
+                    Some(inner) if self.generic_params.len() > 1 => split_top_level(inner),
+                    _ => vec![entry.clone()],
+                }
+            })
+            .collect()
+    }
+
+    /// Name of the binding and Zig export for one instance: `convert_u8_f32`
+    pub fn monomorphized_name(&self, instance: &[String]) -> String {
+        let mut name = self.sig.ident.to_string();
+        for ty in instance {
+            name.push('_');
+            name.push_str(&mangle_type(ty));
+        }
+        name
+    }
+}
+
 /// Extract AutoZig binding configuration from #[autozig(...)] attribute
-/// Supports: strategy, prefix_bindgen, prefix_c, c_ret, map_fn
+/// Supports: strategy, prefix_bindgen, prefix_c, c_ret, map_fn, retains,
+/// assume_safe, time_sliced, fallback_below, isolated, multiversion, runtime,
+/// timeout_ms, python, isr_safe, link_section, gpu, workgroup_size
 fn extract_autozig_binding_config(attrs: &[syn::Attribute]) -> AutoZigBindingConfig {
     let mut config = AutoZigBindingConfig::default();
 
@@ -594,6 +984,56 @@ fn extract_autozig_binding_config(attrs: &[syn::Attribute]) -> AutoZigBindingCon
                             }
                         }
                     }
+                } else if meta.path.is_ident("retains") {
+                    config.retains = true;
+                } else if meta.path.is_ident("assume_safe") {
+                    config.assume_safe = true;
+                } else if meta.path.is_ident("isolated") {
+                    config.isolated = true;
+                } else if meta.path.is_ident("multiversion") {
+                    config.multiversion = true;
+                } else if meta.path.is_ident("python") {
+                    config.python = true;
+                } else if meta.path.is_ident("isr_safe") {
+                    config.isr_safe = true;
+                } else if meta.path.is_ident("gpu") {
+                    config.gpu = true;
+                } else if meta.path.is_ident("workgroup_size") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitInt>() {
+                            config.workgroup_size = lit.base10_parse().ok();
+                        }
+                    }
+                } else if meta.path.is_ident("link_section") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitStr>() {
+                            config.link_section = Some(lit.value());
+                        }
+                    }
+                } else if meta.path.is_ident("runtime") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitStr>() {
+                            config.runtime = Some(lit.value());
+                        }
+                    }
+                } else if meta.path.is_ident("time_sliced") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitInt>() {
+                            config.time_sliced = lit.base10_parse().ok();
+                        }
+                    }
+                } else if meta.path.is_ident("timeout_ms") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitInt>() {
+                            config.timeout_ms = lit.base10_parse().ok();
+                        }
+                    }
+                } else if meta.path.is_ident("fallback_below") {
+                    if let Ok(value) = meta.value() {
+                        if let Ok(lit) = value.parse::<syn::LitInt>() {
+                            config.fallback_below = lit.base10_parse().ok();
+                        }
+                    }
                 } else if meta.path.is_ident("map_fn") {
                     if let Ok(value) = meta.value() {
                         if let Ok(lit) = value.parse::<syn::LitStr>() {
@@ -611,11 +1051,63 @@ fn extract_autozig_binding_config(attrs: &[syn::Attribute]) -> AutoZigBindingCon
     config
 }
 
+/// Extract generated impl opt-outs from `#[autozig(no_default, no_drop)]`
+fn extract_impl_opt_outs(attrs: &[syn::Attribute]) -> (bool, bool) {
+    let mut no_default = false;
+    let mut no_drop = false;
+
+    for attr in attrs {
+        if attr.path().is_ident("autozig") {
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("no_default") {
+                    no_default = true;
+                } else if meta.path.is_ident("no_drop") {
+                    no_drop = true;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    (no_default, no_drop)
+}
+
+/// Whether `#[autozig(python)]` is among the attributes
+fn has_python_marker(attrs: &[syn::Attribute]) -> bool {
+    let mut python = false;
+    for attr in attrs {
+        if attr.path().is_ident("autozig") {
+            let _ = attr.parse_nested_meta(|meta| {
+                python |= meta.path.is_ident("python");
+                Ok(())
+            });
+        }
+    }
+    python
+}
+
+/// Remove `#[autozig(bridge)]` from a struct's attributes, returning whether
+/// it was present
+fn take_bridge_marker(attrs: &mut Vec<syn::Attribute>) -> bool {
+    let before = attrs.len();
+    attrs.retain(|attr| {
+        let mut bridge = false;
+        if attr.path().is_ident("autozig") {
+            let _ = attr.parse_nested_meta(|meta| {
+                bridge |= meta.path.is_ident("bridge");
+                Ok(())
+            });
+        }
+        !bridge
+    });
+    attrs.len() != before
+}
+
 /// Parse a trait implementation (impl Trait for Type)
 fn parse_trait_impl(item_impl: ItemImpl) -> Option<RustTraitImpl> {
     // Check if this is a trait implementation (has a trait path)
-    let trait_path = item_impl.trait_.as_ref()?;
-    let trait_name = trait_path.1.segments.last()?.ident.to_string();
+    let trait_path = item_impl.trait_.as_ref()?.1.clone();
+    let trait_name = trait_path.segments.last()?.ident.to_string();
 
     // Get the target type name
     let target_type = if let syn::Type::Path(type_path) = &*item_impl.self_ty {
@@ -635,8 +1127,13 @@ fn parse_trait_impl(item_impl: ItemImpl) -> Option<RustTraitImpl> {
     let mut methods = Vec::new();
     let mut constructor = None;
     let mut destructor = None;
+    let mut assoc_items = Vec::new();
+    let mut rust_methods = Vec::new();
 
     for impl_item in &item_impl.items {
+        if matches!(impl_item, syn::ImplItem::Type(_) | syn::ImplItem::Const(_)) {
+            assoc_items.push(impl_item.clone());
+        }
         if let syn::ImplItem::Fn(method) = impl_item {
             // Check for #[constructor] or #[destructor] attributes
             let is_constructor_attr = has_attribute(&method.attrs, "constructor");
@@ -652,6 +1149,7 @@ fn parse_trait_impl(item_impl: ItemImpl) -> Option<RustTraitImpl> {
                     zig_return_type: None, // Will be filled by macro with Zig code analysis
                     is_constructor: is_constructor_attr,
                     is_destructor: is_destructor_attr,
+                    doc_attrs: doc_attrs(&method.attrs),
                 };
 
                 if is_constructor_attr {
@@ -661,6 +1159,8 @@ fn parse_trait_impl(item_impl: ItemImpl) -> Option<RustTraitImpl> {
                 } else {
                     methods.push(trait_method);
                 }
+            } else {
+                rust_methods.push(method.clone());
             }
         }
     }
@@ -671,12 +1171,21 @@ fn parse_trait_impl(item_impl: ItemImpl) -> Option<RustTraitImpl> {
 
     Some(RustTraitImpl {
         trait_name,
+        trait_path: Some(trait_path),
         target_type,
         methods,
         is_zst,
         is_opaque,
         constructor,
         destructor,
+        no_default: false,
+        no_drop: false,
+        send: false,
+        sync: false,
+        debug_lock: false,
+        python: false,
+        assoc_items,
+        rust_methods,
     })
 }
 
@@ -715,6 +1224,10 @@ fn extract_zig_function_from_expr(expr: &syn::Expr) -> Option<String> {
         syn::Expr::Call(call) => {
             if let syn::Expr::Path(path) = &*call.func {
                 let fn_name = path.path.segments.last()?.ident.to_string();
+                // Wrapping the result (`Some(zig_next(..))`) is not the call
+                if matches!(fn_name.as_str(), "Some" | "Ok" | "Err") {
+                    return call.args.iter().find_map(extract_zig_function_from_expr);
+                }
                 // Accept any function call (not just zig_* prefix)
                 // This allows for Phase 2 naming like hasher_new, hasher_free, etc.
                 return Some(fn_name);
@@ -764,7 +1277,7 @@ fn has_attribute(attrs: &[syn::Attribute], name: &str) -> bool {
     })
 }
 
-/// Parse an inherent impl block (impl Type { ... }) for constructor/destructor
+/// Parse an inherent impl block (impl Type { ... }) of an opaque type
 fn parse_inherent_impl(
     item_impl: ItemImpl,
     opaque_types: &std::collections::HashSet<String>,
@@ -786,7 +1299,9 @@ fn parse_inherent_impl(
         return None;
     }
 
-    // Parse methods from impl block looking for constructor/destructor
+    // Parse methods from impl block: constructor/destructor plus regular
+    // methods taking `&self` / `&mut self`
+    let mut methods = Vec::new();
     let mut constructor = None;
     let mut destructor = None;
 
@@ -817,6 +1332,7 @@ fn parse_inherent_impl(
                         zig_return_type: None,
                         is_constructor: is_constructor_attr,
                         is_destructor: is_destructor_attr,
+                        doc_attrs: doc_attrs(&method.attrs),
                     };
 
                     if is_constructor_attr {
@@ -825,12 +1341,24 @@ fn parse_inherent_impl(
                         destructor = Some(trait_method);
                     }
                 }
+            } else if method.sig.receiver().is_some() {
+                if let Some(zig_function) = extract_zig_function_call(&method.block) {
+                    methods.push(TraitMethod {
+                        name: method.sig.ident.to_string(),
+                        sig: method.sig.clone(),
+                        zig_function,
+                        body: Some(method.block.clone()),
+                        zig_return_type: None,
+                        is_constructor: false,
+                        is_destructor: false,
+                        doc_attrs: doc_attrs(&method.attrs),
+                    });
+                }
             }
         }
     }
 
-    // Must have at least constructor or destructor
-    if constructor.is_none() && destructor.is_none() {
+    if methods.is_empty() && constructor.is_none() && destructor.is_none() {
         return None;
     }
 
@@ -838,30 +1366,70 @@ fn parse_inherent_impl(
     // This allows us to generate the constructor/destructor without a real trait
     Some(RustTraitImpl {
         trait_name: String::new(), // No trait for inherent impl
+        trait_path: None,
         target_type,
-        methods: Vec::new(), // No regular methods in inherent impl
+        methods,
         is_zst: false,
         is_opaque: true,
         constructor,
         destructor,
+        no_default: false,
+        no_drop: false,
+        send: false,
+        sync: false,
+        debug_lock: false,
+        python: false,
+        assoc_items: Vec::new(),
+        rust_methods: Vec::new(),
     })
 }
 
+/// Thread-safety markers of an opaque struct declaration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct OpaqueMarkers {
+    send: bool,
+    sync: bool,
+    debug_lock: bool,
+}
+
 /// Check if a struct is marked as opaque: struct Name(opaque);
 fn is_opaque_struct(item: &ItemStruct) -> bool {
-    // Check for tuple struct with single field named "opaque"
-    if let syn::Fields::Unnamed(fields) = &item.fields {
-        if fields.unnamed.len() == 1 {
-            if let Some(field) = fields.unnamed.first() {
-                if let syn::Type::Path(type_path) = &field.ty {
-                    if let Some(ident) = type_path.path.get_ident() {
-                        return ident == "opaque";
-                    }
-                }
-            }
+    opaque_struct_markers(item).is_some()
+}
+
+/// Markers of an opaque struct: `struct Name(opaque, send, sync);`
+///
+/// `send` / `sync` may also be given as `#[send]` / `#[sync]` attributes.
+/// Returns `None` if the struct is not opaque or has an unknown marker.
+fn opaque_struct_markers(item: &ItemStruct) -> Option<OpaqueMarkers> {
+    let syn::Fields::Unnamed(fields) = &item.fields else {
+        return None;
+    };
+    let mut idents = fields.unnamed.iter().map(|field| match &field.ty {
+        syn::Type::Path(type_path) => type_path.path.get_ident(),
+        _ => None,
+    });
+    if idents.next()?? != "opaque" {
+        return None;
+    }
+
+    let mut markers = OpaqueMarkers::default();
+    for ident in idents {
+        match ident?.to_string().as_str() {
+            "send" => markers.send = true,
+            "sync" => markers.sync = true,
+            "debug_lock" => markers.debug_lock = true,
+            _ => return None,
+        }
+    }
+    for attr in &item.attrs {
+        if attr.path().is_ident("send") {
+            markers.send = true;
+        } else if attr.path().is_ident("sync") {
+            markers.sync = true;
         }
     }
-    false
+    Some(markers)
 }
 
 /// Configuration for include_zig! macro (external file mode)
@@ -877,6 +1445,8 @@ pub struct IncludeZigConfig {
     pub rust_enums: Vec<RustEnumDefinition>,
     /// Rust trait implementations
     pub rust_trait_impls: Vec<RustTraitImpl>,
+    /// Zig data bound as Rust statics
+    pub rust_statics: Vec<RustStaticDefinition>,
 }
 
 impl Parse for IncludeZigConfig {
@@ -896,7 +1466,7 @@ impl Parse for IncludeZigConfig {
             let tokens: TokenStream = input.parse()?;
             let token_str = tokens.to_string();
 
-            let (rust_enums, rust_structs, rust_signatures, rust_trait_impls) =
+            let (rust_enums, rust_structs, rust_signatures, rust_trait_impls, rust_statics) =
                 parse_rust_definitions(&token_str)?;
 
             Ok(IncludeZigConfig {
@@ -905,6 +1475,7 @@ impl Parse for IncludeZigConfig {
                 rust_structs,
                 rust_enums,
                 rust_trait_impls,
+                rust_statics,
             })
         } else {
             // No signatures provided
@@ -914,6 +1485,7 @@ impl Parse for IncludeZigConfig {
                 rust_structs: Vec::new(),
                 rust_enums: Vec::new(),
                 rust_trait_impls: Vec::new(),
+                rust_statics: Vec::new(),
             })
         }
     }
@@ -940,9 +1512,167 @@ impl IncludeZigConfig {
     pub fn has_rust_signatures(&self) -> bool {
         !self.rust_signatures.is_empty()
     }
+
+    /// Link-time symbol names the declared signatures resolve to
+    ///
+    /// Monomorphized generics expand to one `name_Type` symbol per type, and
+    /// const generics to one `name_value` symbol per value.
+    pub fn symbol_names(&self) -> Vec<String> {
+        symbol_names(&self.rust_signatures, &self.rust_trait_impls, &self.rust_statics)
+    }
+}
+
+/// Zig symbols the generated bindings link against: one per signature
+/// (per type or value when monomorphized, `_begin`/`_step`/`_finish` when
+/// time-sliced), the functions trait impls call and the bound statics
+fn symbol_names(
+    signatures: &[RustFunctionSignature],
+    trait_impls: &[RustTraitImpl],
+    statics: &[RustStaticDefinition],
+) -> Vec<String> {
+    let mut names = Vec::new();
+    for rust_sig in signatures {
+        let base_name = rust_sig.sig.ident.to_string();
+        if rust_sig.binding_config.time_sliced.is_some() {
+            for suffix in ["begin", "step", "finish"] {
+                names.push(format!("{base_name}_{suffix}"));
+            }
+        } else if !rust_sig.generic_params.is_empty() && !rust_sig.monomorphize_types.is_empty() {
+            for instance in rust_sig.monomorphize_instances() {
+                names.push(rust_sig.monomorphized_name(&instance));
+            }
+        } else if !rust_sig.monomorphize_const.is_empty() {
+            for value in &rust_sig.monomorphize_const {
+                names.push(format!("{base_name}_{value}"));
+            }
+        } else {
+            names.push(base_name);
+        }
+    }
+    for trait_impl in trait_impls {
+        let methods = trait_impl
+            .methods
+            .iter()
+            .chain(&trait_impl.constructor)
+            .chain(&trait_impl.destructor);
+        for method in methods {
+            if !names.contains(&method.zig_function) {
+                names.push(method.zig_function.clone());
+            }
+        }
+    }
+    names.extend(statics.iter().map(|s| s.item.ident.to_string()));
+    names
+}
+
+/// Symbol of the Zig export `name` in an `autozig!(mod = namespace, ...)`
+/// block: `namespace__name`
+pub fn namespaced_symbol(namespace: &str, name: &str) -> String {
+    format!("{namespace}__{name}")
+}
+
+/// Names of the `test` blocks declared in a Zig source file, in the form the
+/// Zig test runner reports them: `test.<name>` for `test "name" {}` and
+/// `decltest.<decl>` for `test decl {}`
+///
+/// Anonymous `test {}` blocks are skipped.
+pub fn zig_test_names(source: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for line in source.lines() {
+        let Some(rest) = line.trim_start().strip_prefix("test") else {
+            continue;
+        };
+        // `testing.expect(...)`, `test_data = ...`
+        if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        let rest = rest.trim_start();
+        if let Some(literal) = rest.strip_prefix('"') {
+            if let Some(name) = parse_zig_string(literal) {
+                names.push(format!("test.{}", name));
+            }
+        } else {
+            let ident: String = rest
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            let after = rest[ident.len()..].trim_start();
+            if !ident.is_empty() && after.starts_with('{') {
+                names.push(format!("decltest.{}", ident));
+            }
+        }
+    }
+    names
+}
+
+/// Decode a Zig string literal body up to its closing quote
+fn parse_zig_string(literal: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
 }
 
 impl AutoZigConfig {
+    /// Add a signature for every Zig export that has no explicit one
+    /// (`#![infer]`)
+    ///
+    /// Exports called by trait impls and the `_begin`/`_step`/`_finish`
+    /// exports of time-sliced functions are bound already and skipped.
+    /// Fails on the first export whose types are ambiguous, asking for an
+    /// explicit signature.
+    fn infer_signatures(&mut self) -> Result<(), String> {
+        let mut bound: std::collections::HashSet<String> = self
+            .rust_signatures
+            .iter()
+            .map(|sig| sig.sig.ident.to_string())
+            .collect();
+        for sig in &self.rust_signatures {
+            if sig.binding_config.time_sliced.is_some() {
+                for suffix in ["begin", "step", "finish"] {
+                    bound.insert(format!("{}_{}", sig.sig.ident, suffix));
+                }
+            }
+        }
+        for trait_impl in &self.rust_trait_impls {
+            let methods = trait_impl
+                .methods
+                .iter()
+                .chain(&trait_impl.constructor)
+                .chain(&trait_impl.destructor);
+            bound.extend(methods.map(|method| method.zig_function.clone()));
+        }
+
+        let mapper = TypeMapper::new();
+        for export in type_mapper::zig_exports(&self.zig_code) {
+            if bound.contains(&export.name) {
+                continue;
+            }
+            let decl = mapper.infer_signature(&export).map_err(|reason| {
+                format!(
+                    "#![infer] cannot derive a Rust signature for {}; declare `{}` explicitly \
+                     after `---`",
+                    reason, export.name
+                )
+            })?;
+            let sig: Signature = syn::parse_str(&decl).map_err(|e| e.to_string())?;
+            self.rust_signatures
+                .push(parse_function_signature(sig, &[]));
+        }
+        Ok(())
+    }
+
     /// Get the module name for generated bindings
     pub fn get_mod_name(&self) -> &str {
         "ffi"
@@ -957,11 +1687,31 @@ impl AutoZigConfig {
     pub fn is_external_mode(&self) -> bool {
         self.external_file.is_some()
     }
+
+    /// Link-time symbol names the declared signatures resolve to (see
+    /// [`IncludeZigConfig::symbol_names`]), prefixed by the namespace
+    pub fn symbol_names(&self) -> Vec<String> {
+        symbol_names(&self.rust_signatures, &self.rust_trait_impls, &self.rust_statics)
+            .into_iter()
+            .map(|name| self.link_name(&name))
+            .collect()
+    }
+
+    /// Symbol the Zig export `name` of this block is linked as
+    pub fn link_name(&self, name: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => namespaced_symbol(&namespace.to_string(), name),
+            None => name.to_string(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use quote::quote;
+    use quote::{
+        quote,
+        ToTokens,
+    };
 
     use super::*;
 
@@ -1014,6 +1764,51 @@ mod tests {
         assert_eq!(sig.monomorphize_types, vec!["i32", "f64"]);
     }
 
+    #[test]
+    fn test_parse_multi_param_monomorphize() {
+        let input = quote! {
+            export fn convert_u8_f32(ptr: [*]const u8, len: usize) void {}
+            ---
+            #[repr(C)]
+            #[monomorphize(f32, f64)]
+            struct Matrix<T> { data: [T; 4] }
+
+            #[monomorphize((u8, f32), (geometry::Vec3, Matrix<f32>))]
+            fn convert<Src, Dst>(input: &[Src], output: &mut [Dst]);
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let matrix = &config.rust_structs[0];
+        assert_eq!(matrix.monomorphize_types, vec!["f32", "f64"]);
+        assert!(matrix
+            .item
+            .attrs
+            .iter()
+            .all(|attr| !attr.path().is_ident("monomorphize")));
+
+        let sig = &config.rust_signatures[0];
+        let instances = sig.monomorphize_instances();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0], vec!["u8", "f32"]);
+        assert_eq!(sig.monomorphized_name(&instances[0]), "convert_u8_f32");
+        assert_eq!(sig.monomorphized_name(&instances[1]), "convert_geometry_Vec3_Matrix_f32");
+    }
+
+    #[test]
+    fn test_parse_const_generic_function() {
+        let input = quote! {
+            fn blur(comptime RADIUS: usize, ptr: [*]u8, len: usize) void {}
+            ---
+            #[monomorphize_const(1, 3, 5)]
+            fn blur<const RADIUS: usize>(img: &mut [u8]);
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let sig = &config.rust_signatures[0];
+        assert!(sig.generic_params.is_empty());
+        assert_eq!(sig.monomorphize_const, vec!["1", "3", "5"]);
+    }
+
     #[test]
     fn test_parse_async_function() {
         let input = quote! {
@@ -1027,4 +1822,547 @@ mod tests {
         let sig = &config.rust_signatures[0];
         assert!(sig.is_async);
     }
+
+    #[test]
+    fn test_parse_callback_parameter() {
+        let input = quote! {
+            export fn for_each(ctx: ?*anyopaque, cb: *const fn (?*anyopaque, u32) callconv(.C) void) void {}
+            ---
+            fn for_each(cb: impl FnMut(u32));
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.rust_signatures.len(), 1);
+        let sig = &config.rust_signatures[0];
+        assert!(!sig.needs_abi_lowering);
+        assert_eq!(sig.sig.inputs.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_result_return() {
+        let input = quote! {
+            export fn divide(a: i32, b: i32) AutoZigResult(i32, u8) {}
+            ---
+            fn divide(a: i32, b: i32) -> Result<i32, u8>;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let sig = &config.rust_signatures[0];
+        assert!(sig.needs_abi_lowering);
+        assert!(!sig.is_async);
+    }
+
+    #[test]
+    fn test_parse_option_returns() {
+        let input = quote! {
+            export fn find(id: u32) ?*Foo {}
+            export fn find_max(ptr: [*]const i32, len: usize) AutoZigOption(i32) {}
+            ---
+            fn find(id: u32) -> Option<*mut Foo>;
+            fn find_max(data: &[i32]) -> Option<i32>;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(!config.rust_signatures[0].needs_abi_lowering);
+        assert!(config.rust_signatures[1].needs_abi_lowering);
+    }
+
+    #[test]
+    fn test_parse_impl_opt_outs() {
+        let input = quote! {
+            export fn hasher_new() ?*State {}
+            ---
+            #[autozig(no_default, no_drop)]
+            struct ZigHasher(opaque);
+
+            impl ZigHasher {
+                #[constructor]
+                fn new() -> Self { hasher_new() }
+            }
+
+            impl std::hash::Hasher for ZigHasher {
+                fn finish(&self) -> u64 { hasher_finish() }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.rust_trait_impls.len(), 2);
+        assert!(config
+            .rust_trait_impls
+            .iter()
+            .all(|t| t.no_default && t.no_drop));
+    }
+
+    #[test]
+    fn test_parse_bridge_struct() {
+        let input = quote! {
+            export fn length(v: Vec3) f32 { return @sqrt(v.x * v.x + v.y * v.y + v.z * v.z); }
+            ---
+            #[autozig(bridge)]
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            pub struct Vec3 { pub x: f32, pub y: f32, pub z: f32 }
+            #[repr(C)]
+            pub struct Pair { pub a: i32, pub b: i32 }
+            fn length(v: Vec3) -> f32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let [vec3, pair] = &config.rust_structs[..] else {
+            panic!("expected two structs");
+        };
+        assert!(vec3.bridge);
+        assert_eq!(vec3.item.attrs.len(), 2);
+        assert!(!pair.bridge);
+    }
+
+    #[test]
+    fn test_parse_thread_safety_markers() {
+        let input = quote! {
+            export fn hasher_new() ?*State {}
+            export fn buf_new() ?*Buf {}
+            ---
+            struct ZigHasher(opaque, send, sync, debug_lock);
+            #[send]
+            struct ZigBuf(opaque);
+
+            impl ZigHasher {
+                #[constructor]
+                fn new() -> Self { hasher_new() }
+            }
+
+            impl ZigBuf {
+                #[constructor]
+                fn new() -> Self { buf_new() }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let hasher = &config.rust_trait_impls[0];
+        assert!(hasher.is_opaque && hasher.send && hasher.sync && hasher.debug_lock);
+        let buf = &config.rust_trait_impls[1];
+        assert!(buf.is_opaque && buf.send && !buf.sync && !buf.debug_lock);
+    }
+
+    #[test]
+    fn test_parse_inherent_methods() {
+        let input = quote! {
+            export fn buf_new() ?*Buf {}
+            ---
+            struct ZigBuf(opaque);
+
+            impl ZigBuf {
+                #[constructor]
+                fn new() -> Self { buf_new() }
+
+                fn as_bytes(&self) -> &[u8] { buf_bytes() }
+
+                fn helper() -> u8 { 0 }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let inherent = &config.rust_trait_impls[0];
+        assert!(inherent.trait_name.is_empty());
+        assert!(inherent.constructor.is_some());
+        assert_eq!(inherent.methods.len(), 1);
+        assert_eq!(inherent.methods[0].zig_function, "buf_bytes");
+    }
+
+    #[test]
+    fn test_parse_trait_path() {
+        let input = quote! {
+            export fn sha_new() ?*State {}
+            ---
+            struct ZigSha256(opaque);
+
+            impl digest::Digest<32> for ZigSha256 {
+                fn update(&mut self, data: &[u8]) { sha_update(data) }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let digest = &config.rust_trait_impls[0];
+        assert_eq!(digest.trait_name, "Digest");
+        let path = digest.trait_path.as_ref().unwrap();
+        assert_eq!(quote!(#path).to_string(), "digest :: Digest < 32 >");
+    }
+
+    #[test]
+    fn test_parse_assoc_items_and_rust_methods() {
+        let input = quote! {
+            export fn counter_next() u32 {}
+            ---
+            struct Counter;
+
+            impl std::iter::Iterator for Counter {
+                type Item = u32;
+                fn next(&mut self) -> Option<u32> { Some(counter_next()) }
+                fn size_hint(&self) -> (usize, Option<usize>) { (usize::MAX, None) }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let iterator = &config.rust_trait_impls[0];
+        let path = iterator.trait_path.as_ref().unwrap();
+        assert_eq!(quote!(#path).to_string(), "std :: iter :: Iterator");
+        assert_eq!(iterator.methods[0].zig_function, "counter_next");
+        assert!(matches!(iterator.assoc_items[..], [syn::ImplItem::Type(_)]));
+        assert_eq!(iterator.rust_methods[0].sig.ident, "size_hint");
+    }
+
+    #[test]
+    fn test_zig_test_names() {
+        let source = r#"
+const std = @import("std");
+
+test "factorial basic cases" {
+    try std.testing.expect(true);
+}
+test "quoted \"edge\" case" {}
+test factorial {}
+test {
+    std.testing.refAllDecls(@This());
+}
+// test "commented out" {}
+const testing = std.testing;
+"#;
+        assert_eq!(
+            zig_test_names(source),
+            vec!["test.factorial basic cases", "test.quoted \"edge\" case", "decltest.factorial"]
+        );
+    }
+
+    #[test]
+    fn test_parse_retains() {
+        let input = quote! {
+            export fn set_name(ptr: [*]const u8, len: usize) void {}
+            ---
+            #[autozig(retains)]
+            fn set_name(name: &str);
+            fn name_len() -> usize;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(config.rust_signatures[0].binding_config.retains);
+        assert!(!config.rust_signatures[1].binding_config.retains);
+    }
+
+    #[test]
+    fn test_parse_assume_safe() {
+        let input = quote! {
+            export fn fill(ptr: [*]u8, len: usize) void {}
+            ---
+            #[autozig(assume_safe)]
+            fn fill(ptr: *mut u8, len: usize);
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(config.rust_signatures[0].binding_config.assume_safe);
+    }
+
+    #[test]
+    fn test_parse_infer() {
+        let input = quote! {
+            #![infer]
+            export fn add(a: i32, b: i32) i32 { return a + b; }
+            export fn sum(data_ptr: [*]const i32, data_len: usize) i64 { return 0; }
+            export fn origin(p: *const Point) f64 { return 0; }
+            ---
+            fn origin(p: &Point) -> f64;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(!config.zig_code.contains("infer"));
+        let names: Vec<_> = config
+            .rust_signatures
+            .iter()
+            .map(|sig| sig.sig.ident.to_string())
+            .collect();
+        assert_eq!(names, ["origin", "add", "sum"]);
+
+        let ambiguous = quote! {
+            #![infer]
+            export fn origin(p: *const Point) f64 { return 0; }
+        };
+        let err = syn::parse2::<AutoZigConfig>(ambiguous).err().unwrap();
+        assert!(err.to_string().contains("declare `origin` explicitly"));
+    }
+
+    #[test]
+    fn test_parse_time_sliced() {
+        let input = quote! {
+            export fn slow_sum_begin(n: u64) ?*anyopaque { return null; }
+            ---
+            #[autozig(time_sliced = 10_000)]
+            fn slow_sum(n: u64) -> u64;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.rust_signatures[0].binding_config.time_sliced, Some(10_000));
+    }
+
+    #[test]
+    fn test_parse_fallback_below() {
+        let input = quote! {
+            export fn sum(ptr: [*]const i32, len: usize) i32 { return 0; }
+            ---
+            #[autozig(fallback_below = 64)]
+            fn sum(data: &[i32]) -> i32 {
+                data.iter().sum()
+            }
+            fn other(x: i32) -> i32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let sum = &config.rust_signatures[0];
+        assert_eq!(sum.binding_config.fallback_below, Some(64));
+        assert!(sum.rust_fallback.is_some());
+        assert!(config.rust_signatures[1].rust_fallback.is_none());
+    }
+
+    #[test]
+    fn test_parse_cfg_attrs() {
+        let input = quote! {
+            export fn dot_avx(ptr: [*]const f32, len: usize) f32 { return 0; }
+            ---
+            #[cfg(target_arch = "x86_64")]
+            #[autozig(isolated)]
+            fn dot_avx(data: &[f32]) -> f32;
+            fn other(x: i32) -> i32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let cfg_attrs = &config.rust_signatures[0].cfg_attrs;
+        assert_eq!(cfg_attrs.len(), 1);
+        assert_eq!(
+            cfg_attrs[0].meta.to_token_stream().to_string(),
+            "cfg (target_arch = \"x86_64\")"
+        );
+        assert!(config.rust_signatures[1].cfg_attrs.is_empty());
+    }
+
+    #[test]
+    fn test_map_builder_return_is_not_lowered() {
+        let input = quote! {
+            export fn histogram(ptr: [*]const u8, len: usize, out: *AutoZigMapBuilder(u8, u32)) void {}
+            ---
+            fn histogram(data: &[u8]) -> MapBuilder<u8, u32>;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(!config.rust_signatures[0].needs_abi_lowering);
+    }
+
+    #[test]
+    fn test_parse_isolated() {
+        let input = quote! {
+            export fn decode(ptr: [*]const u8, len: usize) u32 { return 0; }
+            ---
+            #[autozig(isolated)]
+            fn decode(data: &[u8]) -> u32;
+            fn other(x: i32) -> i32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(config.rust_signatures[0].binding_config.isolated);
+        assert!(!config.rust_signatures[1].binding_config.isolated);
+    }
+
+    #[test]
+    fn test_parse_python() {
+        let input = quote! {
+            export fn count(ptr: [*]const u8, len: usize) usize { return len; }
+            ---
+            #[autozig(python)]
+            fn count(s: &str) -> usize;
+            fn other(x: i32) -> i32;
+
+            #[autozig(python)]
+            struct ZigBuf(opaque);
+
+            impl ZigBuf {
+                #[constructor]
+                fn new() -> Self {
+                    zig_buf_new()
+                }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(config.rust_signatures[0].binding_config.python);
+        assert!(!config.rust_signatures[1].binding_config.python);
+        assert!(config.rust_trait_impls.iter().all(|t| t.python));
+    }
+
+    #[test]
+    fn test_parse_multiversion() {
+        let input = quote! {
+            export fn dot(a: [*]const f32, b: [*]const f32, len: usize) f32 { return 0; }
+            ---
+            #[autozig(multiversion)]
+            fn dot(a: &[f32], b: &[f32]) -> f32;
+            fn other(x: i32) -> i32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert!(config.rust_signatures[0].binding_config.multiversion);
+        assert!(!config.rust_signatures[1].binding_config.multiversion);
+    }
+
+    #[test]
+    fn test_parse_statics() {
+        let input = quote! {
+            export const CRC_TABLE: [256]u32 = .{0} ** 256;
+            ---
+            /// CRC-32 lookup table
+            pub static CRC_TABLE: [u32; 256];
+            fn crc32(data: &[u8]) -> u32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.rust_statics.len(), 1);
+        assert_eq!(config.rust_statics[0].item.ident, "CRC_TABLE");
+        assert_eq!(config.rust_signatures.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        let input = quote! {
+            export fn add(a: i32, b: i32) i32 { return a + b; }
+            ---
+            /// Adds two numbers
+            #[cfg(feature = "math")]
+            fn add(a: i32, b: i32) -> i32;
+            struct Calc;
+            impl Calculator for Calc {
+                /// Adds on the calculator
+                fn add(&self, a: i32, b: i32) -> i32 { add(a, b) }
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let sig = &config.rust_signatures[0];
+        assert_eq!(sig.doc_attrs.len(), 1);
+        assert!(sig.doc_attrs[0].path().is_ident("doc"));
+        assert_eq!(sig.cfg_attrs.len(), 1);
+        assert_eq!(config.rust_trait_impls[0].methods[0].doc_attrs.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_async_runtime() {
+        let input = quote! {
+            export fn work(x: u32) u32 { return x; }
+            ---
+            #[autozig(runtime = "smol")]
+            async fn work(x: u32) -> u32;
+            #[autozig(timeout_ms = 5000)]
+            async fn other(x: u32) -> u32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.rust_signatures[0].binding_config.runtime.as_deref(), Some("smol"));
+        assert_eq!(config.rust_signatures[1].binding_config.runtime, None);
+        assert_eq!(config.rust_signatures[0].binding_config.timeout_ms, None);
+        assert_eq!(config.rust_signatures[1].binding_config.timeout_ms, Some(5000));
+    }
+
+    #[test]
+    fn test_parse_isr_safe() {
+        let input = quote! {
+            export fn filter(x: i32) i32 { return x; }
+            ---
+            #[autozig(isr_safe, link_section = ".ramfunc")]
+            fn filter(x: i32) -> i32;
+            #[autozig(isr_safe)]
+            fn other(x: i32) -> i32;
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let filter = &config.rust_signatures[0].binding_config;
+        assert!(filter.isr_safe);
+        assert_eq!(filter.link_section.as_deref(), Some(".ramfunc"));
+        let other = &config.rust_signatures[1].binding_config;
+        assert!(other.isr_safe && other.link_section.is_none());
+    }
+
+    #[test]
+    fn test_parse_gpu() {
+        let input = quote! {
+            export fn saxpy() callconv(.spirv_kernel) void {}
+            ---
+            #[autozig(gpu, workgroup_size = 256)]
+            fn saxpy(x: &[f32], y: &mut [f32]);
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        let saxpy = &config.rust_signatures[0].binding_config;
+        assert!(saxpy.gpu);
+        assert_eq!(saxpy.workgroup_size, Some(256));
+    }
+
+    #[test]
+    fn test_symbol_names() {
+        let input = quote! {
+            export fn sum_begin(n: u64) u64 { return n; }
+            ---
+            #[autozig(time_sliced = 1000)]
+            fn sum(n: u64) -> u64;
+            fn reset();
+            static TABLE: [u8; 4];
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(
+            config.symbol_names(),
+            vec!["sum_begin", "sum_step", "sum_finish", "reset", "TABLE"]
+        );
+    }
+
+    #[test]
+    fn test_parse_namespace() {
+        let input = quote! {
+            mod = img, {
+                export fn resize(w: u32) u32 { return w; }
+                ---
+                fn resize(w: u32) -> u32;
+            }
+        };
+
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.namespace.as_ref().unwrap(), "img");
+        assert_eq!(config.symbol_names(), vec!["img__resize"]);
+
+        let input = quote! { mod = "audio", { export fn gain() void {} } };
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.namespace.as_ref().unwrap(), "audio");
+
+        let input = quote! {
+            #![autozig(mod = "img", optimize = "ReleaseFast")]
+            export fn resize(w: u32) u32 { return w; }
+            ---
+            fn resize(w: u32) -> u32;
+        };
+        let config: AutoZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.symbol_names(), vec!["img__resize"]);
+        assert!(!config.zig_code.contains("autozig"));
+
+        let input = quote! { mod = img, { #![autozig(mod = "audio")] export fn gain() void {} } };
+        assert!(syn::parse2::<AutoZigConfig>(input).is_err());
+    }
+
+    #[test]
+    fn test_include_symbol_names() {
+        let input = quote! {
+            "vendor/libfoo.a", {
+                fn foo_init() -> i32;
+                #[monomorphize(i32, f64)]
+                fn foo_sum<T>(data: &[T]) -> T;
+            }
+        };
+
+        let config: IncludeZigConfig = syn::parse2(input).unwrap();
+        assert_eq!(config.file_path, "vendor/libfoo.a");
+        assert_eq!(config.symbol_names(), vec!["foo_init", "foo_sum_i32", "foo_sum_f64"]);
+    }
 }
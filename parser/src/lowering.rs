@@ -0,0 +1,75 @@
+//! User-registered lowering rules for domain types
+//!
+//! A build script can teach autozig how a crate-local type crosses the
+//! boundary: `Decimal` travels as `i64`, converted with `to_ffi` before the
+//! call and `from_ffi` after it. The engine writes the rules registered on
+//! the builder to `OUT_DIR/autozig_lowerings.txt` (path exported as
+//! `AUTOZIG_LOWERINGS`), and the macros apply them to by-value parameters
+//! and return types.
+//!
+//! One rule per line, the Rust type, FFI type, `to_ffi` and `from_ffi`
+//! separated by tabs (types may contain spaces, e.g. `[u8; 4]`).
+
+/// "Rust type X lowers to FFI type Y with conversions to_ffi / from_ffi"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeLowering {
+    /// Type as written in the signatures, e.g. `Decimal`
+    pub rust_type: String,
+    /// FFI-safe type it crosses as, e.g. `i64`
+    pub ffi_type: String,
+    /// Path of `fn(RustType) -> FfiType`
+    pub to_ffi: String,
+    /// Path of `fn(FfiType) -> RustType`
+    pub from_ffi: String,
+}
+
+impl TypeLowering {
+    /// Render as one line of the lowerings file
+    pub fn render(&self) -> String {
+        format!("{}\t{}\t{}\t{}", self.rust_type, self.ffi_type, self.to_ffi, self.from_ffi)
+    }
+
+    /// Parse one line of the lowerings file
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split('\t').map(str::trim);
+        let lowering = Self {
+            rust_type: parts.next()?.to_string(),
+            ffi_type: parts.next()?.to_string(),
+            to_ffi: parts.next()?.to_string(),
+            from_ffi: parts.next()?.to_string(),
+        };
+        let complete =
+            [&lowering.rust_type, &lowering.ffi_type, &lowering.to_ffi, &lowering.from_ffi]
+                .iter()
+                .all(|part| !part.is_empty());
+        (complete && parts.next().is_none()).then_some(lowering)
+    }
+}
+
+/// Parse a lowerings file, skipping malformed lines
+pub fn parse_lowerings(text: &str) -> Vec<TypeLowering> {
+    text.lines().filter_map(TypeLowering::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowering_roundtrip() {
+        let lowering = TypeLowering {
+            rust_type: "Decimal".to_string(),
+            ffi_type: "i64".to_string(),
+            to_ffi: "crate::decimal::to_cents".to_string(),
+            from_ffi: "crate::decimal::from_cents".to_string(),
+        };
+        assert_eq!(
+            lowering.render(),
+            "Decimal\ti64\tcrate::decimal::to_cents\tcrate::decimal::from_cents"
+        );
+        assert_eq!(
+            parse_lowerings(&format!("{}\n\nUuid\tu128\n", lowering.render())),
+            vec![lowering]
+        );
+    }
+}
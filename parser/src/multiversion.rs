@@ -0,0 +1,55 @@
+//! CPU variants of `#[autozig(multiversion)]` functions
+//!
+//! The engine compiles such a function once more for every variant of the
+//! target architecture, exported as `<name>__<suffix>`, and the generated
+//! binding picks the best variant the running CPU supports on its first
+//! call. The baseline build (SSE2 on x86_64, NEON on aarch64) is the
+//! fallback.
+
+/// One extra build of a multiversioned function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuVariant {
+    /// Suffix of the variant's symbol
+    pub suffix: &'static str,
+    /// Rust `target_arch` the variant exists on
+    pub arch: &'static str,
+    /// Zig `-mcpu` of the variant
+    pub zig_cpu: &'static str,
+    /// Features the CPU must have, as named by `is_x86_feature_detected!`
+    /// or `is_aarch64_feature_detected!`
+    pub features: &'static [&'static str],
+}
+
+/// All variants, best first within each architecture
+pub const CPU_VARIANTS: &[CpuVariant] = &[
+    CpuVariant {
+        suffix: "avx512",
+        arch: "x86_64",
+        zig_cpu: "x86_64_v4",
+        features: &["avx512f", "avx512bw", "avx512cd", "avx512dq", "avx512vl", "avx2", "fma"],
+    },
+    CpuVariant {
+        suffix: "avx2",
+        arch: "x86_64",
+        zig_cpu: "x86_64_v3",
+        features: &["avx2", "fma", "bmi1", "bmi2", "f16c", "lzcnt", "movbe"],
+    },
+    CpuVariant {
+        suffix: "sve",
+        arch: "aarch64",
+        zig_cpu: "generic+sve",
+        features: &["sve"],
+    },
+];
+
+/// Variants built for the Rust `target_arch` `arch`
+pub fn variants_for(arch: &str) -> impl Iterator<Item = &'static CpuVariant> + '_ {
+    CPU_VARIANTS
+        .iter()
+        .filter(move |variant| variant.arch == arch)
+}
+
+/// Symbol of the `variant` of the Zig function `name`
+pub fn symbol(name: &str, variant: &CpuVariant) -> String {
+    format!("{name}__{}", variant.suffix)
+}
@@ -0,0 +1,419 @@
+//! Type mapping between Zig and Rust types
+
+use std::collections::HashMap;
+
+/// Maps Zig types to Rust types for FFI
+pub struct TypeMapper {
+    mappings: HashMap<&'static str, &'static str>,
+}
+
+impl TypeMapper {
+    pub fn new() -> Self {
+        let mut mappings = HashMap::new();
+
+        // Integer types
+        mappings.insert("i8", "i8");
+        mappings.insert("i16", "i16");
+        mappings.insert("i32", "i32");
+        mappings.insert("i64", "i64");
+        mappings.insert("i128", "i128");
+        mappings.insert("isize", "isize");
+
+        mappings.insert("u8", "u8");
+        mappings.insert("u16", "u16");
+        mappings.insert("u32", "u32");
+        mappings.insert("u64", "u64");
+        mappings.insert("u128", "u128");
+        mappings.insert("usize", "usize");
+
+        // Floating point
+        mappings.insert("f32", "f32");
+        mappings.insert("f64", "f64");
+
+        // Boolean (Zig bool is u8 in C ABI)
+        mappings.insert("bool", "u8");
+
+        // Void
+        mappings.insert("void", "()");
+
+        // Pointer types
+        mappings.insert("[*]const u8", "*const u8");
+        mappings.insert("[*]u8", "*mut u8");
+
+        Self { mappings }
+    }
+
+    /// Map a Zig type to its Rust equivalent
+    pub fn map_type(&self, zig_type: &str) -> Option<&str> {
+        self.mappings.get(zig_type).copied()
+    }
+
+    /// Check if a type is a pointer that needs special handling
+    pub fn is_slice_type(&self, zig_type: &str) -> bool {
+        zig_type.starts_with("[*]")
+    }
+
+    /// Rust signature for a Zig `export fn`, e.g. `fn sum(data: &[i32]) ->
+    /// i64` for `export fn sum(data_ptr: [*]const i32, data_len: usize) i64`
+    ///
+    /// Only scalars and `[*]T` + `usize` length pairs (lowered from slices)
+    /// have an unambiguous Rust type; anything else (single-item pointers,
+    /// structs, optionals) is an error naming the offending type.
+    pub fn infer_signature(&self, export: &ZigExport) -> Result<String, String> {
+        let mut params = Vec::new();
+        let mut zig_params = export.params.iter().peekable();
+        while let Some((name, ty)) = zig_params.next() {
+            if let Some(elem) = ty.strip_prefix("[*]") {
+                let (mutability, elem) = match elem.strip_prefix("const ") {
+                    Some(elem) => ("", elem),
+                    None => ("mut ", elem),
+                };
+                let elem = self.infer_scalar(elem).ok_or_else(|| {
+                    format!(
+                        "`{}`: element type of `{}: {}` has no Rust equivalent",
+                        export.name, name, ty
+                    )
+                })?;
+                if zig_params
+                    .next_if(|(_, len_ty)| len_ty == "usize")
+                    .is_none()
+                {
+                    return Err(format!(
+                        "`{}`: `{}: {}` is not followed by a `usize` length, so its size is \
+                         unknown",
+                        export.name, name, ty
+                    ));
+                }
+                let name = name.strip_suffix("_ptr").unwrap_or(name);
+                params.push(format!("{}: &{}[{}]", name, mutability, elem));
+            } else {
+                let rust = self.infer_scalar(ty).ok_or_else(|| {
+                    format!(
+                        "`{}`: parameter `{}: {}` has no unambiguous Rust type",
+                        export.name, name, ty
+                    )
+                })?;
+                params.push(format!("{}: {}", name, rust));
+            }
+        }
+
+        let ret = match export.return_type.as_str() {
+            "void" => String::new(),
+            ty => {
+                let rust = self.infer_scalar(ty).ok_or_else(|| {
+                    format!("`{}`: return type `{}` has no unambiguous Rust type", export.name, ty)
+                })?;
+                format!(" -> {}", rust)
+            },
+        };
+
+        Ok(format!("fn {}({}){}", export.name, params.join(", "), ret))
+    }
+
+    /// Rust type of a Zig scalar passed by value
+    fn infer_scalar(&self, zig_type: &str) -> Option<&str> {
+        match zig_type {
+            // Zig `bool` has the C `_Bool` ABI, same as Rust `bool`
+            "bool" => Some("bool"),
+            "void" => None,
+            ty if ty.contains('*') => None,
+            ty => self.map_type(ty),
+        }
+    }
+}
+
+/// A Zig `export fn` declaration, with whitespace in types normalized
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZigExport {
+    pub name: String,
+    /// (name, Zig type) per parameter
+    pub params: Vec<(String, String)>,
+    pub return_type: String,
+}
+
+/// `export fn` declarations in Zig source
+///
+/// Works on both source text and stringified token streams, where `[*]const
+/// u8` may appear as `[* ] const u8`.
+pub fn zig_exports(zig_code: &str) -> Vec<ZigExport> {
+    let mut exports = Vec::new();
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find("export") {
+        let preceded_by_ident = rest[..pos].chars().next_back().is_some_and(is_ident_char);
+        let after = &rest[pos + "export".len()..];
+        rest = after;
+        if preceded_by_ident || !after.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let Some(after_fn) = after.trim_start().strip_prefix("fn") else {
+            continue;
+        };
+        if !after_fn.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if let Some(export) = parse_export(after_fn.trim_start()) {
+            exports.push(export);
+        }
+    }
+    exports
+}
+
+/// Declaration of the function `name` in Zig source, exported or not
+///
+/// `comptime` parameters are listed like the others.
+pub fn zig_function(zig_code: &str, name: &str) -> Option<ZigExport> {
+    let mut rest = zig_code;
+    while let Some(pos) = rest.find("fn") {
+        let preceded_by_ident = rest[..pos].chars().next_back().is_some_and(is_ident_char);
+        let after = &rest[pos + "fn".len()..];
+        rest = after;
+        if preceded_by_ident || !after.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let decl = after.trim_start();
+        let named = decl
+            .strip_prefix(name)
+            .is_some_and(|tail| tail.trim_start().starts_with('('));
+        if named {
+            return parse_export(decl);
+        }
+    }
+    None
+}
+
+/// Parse `name(params) ret {` (the text after `export fn`)
+fn parse_export(decl: &str) -> Option<ZigExport> {
+    let name: String = decl.chars().take_while(|c| is_ident_char(*c)).collect();
+    let params_start = decl[name.len()..].trim_start().strip_prefix('(')?;
+
+    let mut depth = 1;
+    let close = params_start.char_indices().find_map(|(i, c)| {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {},
+        }
+        (depth == 0).then_some(i)
+    })?;
+
+    let params = split_top_level(&params_start[..close])
+        .into_iter()
+        .filter_map(|param| {
+            let (name, ty) = param.split_once(':')?;
+            let name = name
+                .trim()
+                .trim_start_matches("noalias")
+                .trim_start_matches("comptime");
+            Some((name.trim().to_string(), normalize_zig_type(ty)))
+        })
+        .collect();
+
+    let after_params = &params_start[close + 1..];
+    let mut return_type = after_params[..body_start(after_params)?].trim();
+    if let Some(after_callconv) = return_type.strip_prefix("callconv") {
+        return_type = after_callconv
+            .split_once(')')
+            .map_or("", |(_, ret)| ret)
+            .trim();
+    }
+
+    Some(ZigExport {
+        name,
+        params,
+        return_type: normalize_zig_type(return_type),
+    })
+}
+
+/// Offset of the `{` opening a function body, skipping the braces of an
+/// inline error set (`error{A, B}!T`)
+fn body_start(after_params: &str) -> Option<usize> {
+    let mut search = 0;
+    loop {
+        let open = search + after_params[search..].find('{')?;
+        let before = after_params[..open].trim_end();
+        let is_error_set = before
+            .strip_suffix("error")
+            .is_some_and(|prefix| !prefix.chars().next_back().is_some_and(is_ident_char));
+        if !is_error_set {
+            return Some(open);
+        }
+        search = open + after_params[open..].find('}')? + 1;
+    }
+}
+
+/// Split on commas outside of brackets
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&list[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect()
+}
+
+/// Collapse whitespace, keeping it only between identifiers (`[* ] const
+/// u8` -> `[*]const u8`)
+fn normalize_zig_type(ty: &str) -> String {
+    let mut normalized = String::new();
+    let mut pending_space = false;
+    for c in ty.chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space
+            && normalized.chars().next_back().is_some_and(is_ident_char)
+            && is_ident_char(c)
+        {
+            normalized.push(' ');
+        }
+        pending_space = false;
+        normalized.push(c);
+    }
+    normalized
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Default for TypeMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Conversion strategy for function parameters
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamConversion {
+    /// Direct pass-through (primitive types)
+    Direct,
+    /// Convert &[T] to (ptr, len)
+    SliceToPtrLen,
+    /// Convert &str to (ptr, len)
+    StrToPtrLen,
+    /// Pass `Bytes` / `Arc<[u8]>` as (ptr, len), keeping the owner alive
+    SharedBytesToPtrLen,
+}
+
+/// Analyze a Rust type and determine conversion strategy
+pub fn analyze_param_type(ty: &syn::Type) -> ParamConversion {
+    match ty {
+        syn::Type::Reference(type_ref) => {
+            // Check if it's &[T] or &str
+            match &*type_ref.elem {
+                syn::Type::Slice(_) => ParamConversion::SliceToPtrLen,
+                syn::Type::Path(type_path) => {
+                    if let Some(segment) = type_path.path.segments.last() {
+                        if segment.ident == "str" {
+                            return ParamConversion::StrToPtrLen;
+                        }
+                    }
+                    ParamConversion::Direct
+                },
+                _ => ParamConversion::Direct,
+            }
+        },
+        _ if crate::is_shared_bytes(ty) => ParamConversion::SharedBytesToPtrLen,
+        _ => ParamConversion::Direct,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_mapper() {
+        let mapper = TypeMapper::new();
+
+        assert_eq!(mapper.map_type("i32"), Some("i32"));
+        assert_eq!(mapper.map_type("u64"), Some("u64"));
+        assert_eq!(mapper.map_type("f32"), Some("f32"));
+        assert_eq!(mapper.map_type("bool"), Some("u8"));
+        assert_eq!(mapper.map_type("void"), Some("()"));
+    }
+
+    #[test]
+    fn test_zig_exports() {
+        let code = "const std = @import(\"std\"); export fn sum (data_ptr : [* ] const i32 , \
+                    data_len : usize) i64 { return 0; } fn private() void {} export fn tick() \
+                    callconv(.c) void {}";
+        let exports = zig_exports(code);
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[0].name, "sum");
+        assert_eq!(exports[0].params[0], ("data_ptr".to_string(), "[*]const i32".to_string()));
+        assert_eq!(exports[0].return_type, "i64");
+        assert_eq!(exports[1].return_type, "void");
+
+        let exports =
+            zig_exports("export fn parse(x: u8) error { Bad , Worse } ! u8 { return x; }");
+        assert_eq!(exports[0].return_type, "error{Bad,Worse}!u8");
+    }
+
+    #[test]
+    fn test_zig_function() {
+        let code = "fn blur_h(x: u8) void {} fn blur(comptime RADIUS: usize, ptr: [*]u8, len: \
+                    usize) void {}";
+        let blur = zig_function(code, "blur").unwrap();
+        assert_eq!(blur.params[0], ("RADIUS".to_string(), "usize".to_string()));
+        assert_eq!(blur.params.len(), 3);
+        assert_eq!(blur.return_type, "void");
+        assert!(zig_function(code, "sharpen").is_none());
+    }
+
+    #[test]
+    fn test_infer_signature() {
+        let mapper = TypeMapper::new();
+        let infer = |code: &str| mapper.infer_signature(&zig_exports(code)[0]);
+
+        assert_eq!(
+            infer("export fn sum(data_ptr: [*]const i32, data_len: usize) i64 {}").unwrap(),
+            "fn sum(data: &[i32]) -> i64"
+        );
+        assert_eq!(
+            infer("export fn fill(buf: [*]u8, len: usize, v: u8) void {}").unwrap(),
+            "fn fill(buf: &mut [u8], v: u8)"
+        );
+        assert_eq!(infer("export fn ok(x: f64) bool {}").unwrap(), "fn ok(x: f64) -> bool");
+
+        assert!(infer("export fn get(p: *Point) i32 {}")
+            .unwrap_err()
+            .contains("`p: *Point`"));
+        assert!(infer("export fn raw(p: [*]const u8) void {}").is_err());
+    }
+
+    #[test]
+    fn test_slice_detection() {
+        let mapper = TypeMapper::new();
+
+        assert!(mapper.is_slice_type("[*]const u8"));
+        assert!(mapper.is_slice_type("[*]u8"));
+        assert!(!mapper.is_slice_type("i32"));
+    }
+
+    #[test]
+    fn test_param_conversion() {
+        let conversion = |ty: &str| analyze_param_type(&syn::parse_str(ty).unwrap());
+
+        assert_eq!(conversion("&[f32]"), ParamConversion::SliceToPtrLen);
+        assert_eq!(conversion("&str"), ParamConversion::StrToPtrLen);
+        assert_eq!(conversion("bytes::Bytes"), ParamConversion::SharedBytesToPtrLen);
+        assert_eq!(conversion("std::sync::Arc<[u8]>"), ParamConversion::SharedBytesToPtrLen);
+        assert_eq!(conversion("Arc<[i32]>"), ParamConversion::Direct);
+        assert_eq!(conversion("BytesMut"), ParamConversion::Direct);
+    }
+}
@@ -0,0 +1,112 @@
+#![allow(unsafe_code)]
+//! Zig allocations through Rust's global allocator
+//!
+//! Zig code that allocates with `g_allocator` gets an allocator injected by
+//! the engine. Once [`install`] has run, it forwards every allocation to the
+//! shims below, which use Rust's `#[global_allocator]`, so Zig memory shows
+//! up in jemalloc/mimalloc statistics and heap profilers like any other:
+//!
+//! ```zig
+//! export fn make_buffer(len: usize) ?[*]u8 {
+//!     const buf = g_allocator.alloc(u8, len) catch return null;
+//!     return buf.ptr;
+//! }
+//! ```
+//!
+//! ```rust,ignore
+//! fn main() {
+//!     assert!(autozig::allocator::install());
+//!     // ...
+//! }
+//! ```
+//!
+//! Until then `g_allocator` uses `std.heap.c_allocator`, or fails every
+//! allocation in freestanding builds (`autozig_build::Builder::no_std`),
+//! where it also backs the `String`/`Vec<u8>` buffers Zig returns. Calling
+//! [`install`] in a crate whose Zig code never uses `g_allocator` fails at
+//! link time.
+
+use ::alloc::alloc::{
+    self,
+    Layout,
+};
+
+type AllocFn = extern "C" fn(usize, usize) -> *mut u8;
+type ReallocFn = extern "C" fn(*mut u8, usize, usize, usize) -> *mut u8;
+type FreeFn = extern "C" fn(*mut u8, usize, usize);
+
+extern "C" {
+    fn autozig_install_allocator(alloc: AllocFn, realloc: ReallocFn, free: FreeFn) -> bool;
+}
+
+/// Route `g_allocator` in Zig through Rust's global allocator
+///
+/// Call it once at startup, before other threads run Zig code. Returns
+/// `false` if Zig already allocated through the C allocator fallback; memory
+/// from both allocators must not be mixed, so the fallback stays in use.
+pub fn install() -> bool {
+    unsafe { autozig_install_allocator(rust_alloc, rust_realloc, rust_free) }
+}
+
+/// Non-null pointer for zero-sized allocations, which Rust's allocator does
+/// not serve
+fn dangling(align: usize) -> *mut u8 {
+    align as *mut u8
+}
+
+extern "C" fn rust_alloc(size: usize, align: usize) -> *mut u8 {
+    match Layout::from_size_align(size, align) {
+        Ok(_) if size == 0 => dangling(align),
+        Ok(layout) => unsafe { alloc::alloc(layout) },
+        Err(_) => core::ptr::null_mut(),
+    }
+}
+
+extern "C" fn rust_realloc(ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8 {
+    if size == 0 {
+        return rust_alloc(new_size, align);
+    }
+    if new_size == 0 {
+        rust_free(ptr, size, align);
+        return dangling(align);
+    }
+    match Layout::from_size_align(size, align) {
+        Ok(layout) => unsafe { alloc::realloc(ptr, layout, new_size) },
+        Err(_) => core::ptr::null_mut(),
+    }
+}
+
+extern "C" fn rust_free(ptr: *mut u8, size: usize, align: usize) {
+    if size == 0 {
+        return;
+    }
+    if let Ok(layout) = Layout::from_size_align(size, align) {
+        unsafe {
+            crate::contracts::poison(ptr, size);
+            alloc::dealloc(ptr, layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shims_roundtrip() {
+        let ptr = rust_alloc(16, 8);
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 8, 0);
+        unsafe { ptr.write_bytes(7, 16) };
+
+        let ptr = rust_realloc(ptr, 16, 8, 64);
+        assert!(!ptr.is_null());
+        assert_eq!(unsafe { *ptr.add(15) }, 7);
+        rust_free(ptr, 64, 8);
+
+        let empty = rust_alloc(0, 4);
+        assert_eq!(empty as usize, 4);
+        rust_free(empty, 0, 4);
+        assert!(rust_alloc(8, 3).is_null());
+    }
+}
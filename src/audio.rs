@@ -0,0 +1,285 @@
+#![allow(unsafe_code)]
+//! Lock-free audio ring buffer shared between a Rust thread and Zig
+//!
+//! [`RingBuffer::with_capacity`] returns the two ends of a single-producer,
+//! single-consumer ring of samples. Neither end locks or allocates, so both
+//! can be used on real-time threads, e.g. a cpal input callback writing into
+//! a ring that a Zig effect reads from in the output callback:
+//!
+//! ```rust,ignore
+//! use autozig::audio::{Consumer, RingBuffer};
+//!
+//! autozig! {
+//!     export fn reverb(input: *AutoZigRingReader(f32), out_ptr: [*]f32, out_len: usize) usize {
+//!         const out = out_ptr[0..out_len];
+//!         const n = input.readBlock(out);
+//!         for (out[0..n]) |*sample| sample.* = process(sample.*);
+//!         return n;
+//!     }
+//!     ---
+//!     fn reverb(input: &mut Consumer<f32>, out: &mut [f32]) -> usize;
+//! }
+//!
+//! let (mut producer, mut consumer) = RingBuffer::<f32>::with_capacity(4096);
+//! // input callback:  producer.write_block(data);
+//! // output callback: let n = reverb(&mut consumer, data); data[n..].fill(0.0);
+//! ```
+//!
+//! A `&mut Consumer<T>` reaches Zig as `*AutoZigRingReader(T)` and a
+//! `&mut Producer<T>` as `*AutoZigRingWriter(T)`; the engine injects both,
+//! with `readBlock`/`available` and `writeBlock`/`freeSpace`.
+//!
+//! # Memory ordering
+//!
+//! The ring holds two free-running indices, `write` and `read`, that wrap at
+//! `usize::MAX`; a slot is `index & (capacity - 1)`. Each index has a single
+//! writer, in Rust or in Zig:
+//!
+//! - the producer copies samples into free slots, then stores `write` with
+//!   `Release`; the consumer loads `write` with `Acquire` before copying them
+//!   out, so it sees the samples;
+//! - the consumer copies samples out, then stores `read` with `Release`; the
+//!   producer loads `read` with `Acquire` before reusing the slots;
+//! - each side loads its own index `Relaxed`.
+//!
+//! Zig code must follow the same contract, which the injected helpers do;
+//! an end must not be used from two threads at once.
+
+use alloc::boxed::Box;
+use core::{
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr::{
+        self,
+        NonNull,
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+/// State shared by the [`Producer`] and [`Consumer`] of a ring, with the
+/// layout of Zig's `AutoZigRing(T)`
+#[repr(C)]
+pub struct RingBuffer<T> {
+    /// Samples written so far (wrapping), stored by the producer
+    write: AtomicUsize,
+    /// Samples read so far (wrapping), stored by the consumer
+    read: AtomicUsize,
+    /// Number of slots, a power of two
+    capacity: usize,
+    data: *mut T,
+    /// Ends not dropped yet
+    handles: AtomicUsize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    /// A ring of at least `capacity` samples, rounded up to a power of two
+    pub fn with_capacity(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        let capacity = capacity.max(1).next_power_of_two();
+        let data: Box<[MaybeUninit<T>]> = (0..capacity).map(|_| MaybeUninit::uninit()).collect();
+        let ring = Box::new(RingBuffer {
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            capacity,
+            data: Box::into_raw(data) as *mut T,
+            handles: AtomicUsize::new(2),
+        });
+        let ring = NonNull::from(Box::leak(ring));
+        (Producer { ring, _marker: PhantomData }, Consumer { ring, _marker: PhantomData })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Copy `len` samples from `src` into the slots from `index` on
+    ///
+    /// # Safety
+    ///
+    /// The slots must be free and `len` at most `capacity`.
+    unsafe fn copy_in(&self, index: usize, src: *const T, len: usize) {
+        let start = index & (self.capacity - 1);
+        let first = len.min(self.capacity - start);
+        ptr::copy_nonoverlapping(src, self.data.add(start), first);
+        ptr::copy_nonoverlapping(src.add(first), self.data, len - first);
+    }
+
+    /// Copy `len` samples from the slots from `index` on into `dst`
+    ///
+    /// # Safety
+    ///
+    /// The slots must hold written samples and `len` be at most `capacity`.
+    unsafe fn copy_out(&self, index: usize, dst: *mut T, len: usize) {
+        let start = index & (self.capacity - 1);
+        let first = len.min(self.capacity - start);
+        ptr::copy_nonoverlapping(self.data.add(start), dst, first);
+        ptr::copy_nonoverlapping(self.data, dst.add(first), len - first);
+    }
+}
+
+/// Drop one end of the ring, freeing it with the last one
+///
+/// # Safety
+///
+/// `ring` must come from `with_capacity` and each end release it once.
+unsafe fn release<T>(ring: NonNull<RingBuffer<T>>) {
+    if ring.as_ref().handles.fetch_sub(1, Ordering::AcqRel) != 1 {
+        return;
+    }
+    let ring = Box::from_raw(ring.as_ptr());
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        ring.data as *mut MaybeUninit<T>,
+        ring.capacity,
+    )));
+}
+
+/// Writing end of a [`RingBuffer`]
+#[repr(transparent)]
+pub struct Producer<T: Copy> {
+    ring: NonNull<RingBuffer<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> Producer<T> {
+    fn ring(&self) -> &RingBuffer<T> {
+        // SAFETY: the ring lives until both ends are dropped
+        unsafe { self.ring.as_ref() }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring().capacity
+    }
+
+    /// Slots free for writing
+    pub fn free_space(&self) -> usize {
+        let ring = self.ring();
+        let write = ring.write.load(Ordering::Relaxed);
+        ring.capacity - write.wrapping_sub(ring.read.load(Ordering::Acquire))
+    }
+
+    /// Append as many of `samples` as fit, returning how many were written
+    pub fn write_block(&mut self, samples: &[T]) -> usize {
+        let ring = self.ring();
+        let write = ring.write.load(Ordering::Relaxed);
+        let free = ring.capacity - write.wrapping_sub(ring.read.load(Ordering::Acquire));
+        let len = samples.len().min(free);
+        // SAFETY: the consumer released these slots before storing `read`
+        unsafe { ring.copy_in(write, samples.as_ptr(), len) };
+        ring.write.store(write.wrapping_add(len), Ordering::Release);
+        len
+    }
+}
+
+impl<T: Copy> Drop for Producer<T> {
+    fn drop(&mut self) {
+        // SAFETY: each end releases the ring once, on drop
+        unsafe { release(self.ring) };
+    }
+}
+
+// SAFETY: the producer only writes free slots and its own index
+unsafe impl<T: Copy + Send> Send for Producer<T> {}
+
+/// Reading end of a [`RingBuffer`]
+#[repr(transparent)]
+pub struct Consumer<T: Copy> {
+    ring: NonNull<RingBuffer<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> Consumer<T> {
+    fn ring(&self) -> &RingBuffer<T> {
+        // SAFETY: the ring lives until both ends are dropped
+        unsafe { self.ring.as_ref() }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring().capacity
+    }
+
+    /// Samples ready to read
+    pub fn available(&self) -> usize {
+        let ring = self.ring();
+        ring.write
+            .load(Ordering::Acquire)
+            .wrapping_sub(ring.read.load(Ordering::Relaxed))
+    }
+
+    /// Move up to `out.len()` samples into `out`, returning how many were
+    /// read
+    pub fn read_block(&mut self, out: &mut [T]) -> usize {
+        let ring = self.ring();
+        let read = ring.read.load(Ordering::Relaxed);
+        let ready = ring.write.load(Ordering::Acquire).wrapping_sub(read);
+        let len = out.len().min(ready);
+        // SAFETY: the producer wrote these slots before storing `write`
+        unsafe { ring.copy_out(read, out.as_mut_ptr(), len) };
+        ring.read.store(read.wrapping_add(len), Ordering::Release);
+        len
+    }
+}
+
+impl<T: Copy> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        // SAFETY: each end releases the ring once, on drop
+        unsafe { release(self.ring) };
+    }
+}
+
+// SAFETY: the consumer only reads written slots and stores its own index
+unsafe impl<T: Copy + Send> Send for Consumer<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_wraps() {
+        let (mut producer, mut consumer) = RingBuffer::<f32>::with_capacity(6);
+        assert_eq!(producer.capacity(), 8);
+        assert_eq!(producer.write_block(&[1.0, 2.0, 3.0, 4.0, 5.0]), 5);
+        let mut out = [0.0; 4];
+        assert_eq!(consumer.read_block(&mut out), 4);
+        assert_eq!(out, [1.0, 2.0, 3.0, 4.0]);
+
+        // Crosses the end of the slots, and stops when full
+        assert_eq!(producer.write_block(&[6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0]), 7);
+        assert_eq!(producer.free_space(), 0);
+        assert_eq!(consumer.available(), 8);
+        let mut out = [0.0; 10];
+        assert_eq!(consumer.read_block(&mut out), 8);
+        assert_eq!(out[..8], [5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0]);
+        assert_eq!(consumer.read_block(&mut out), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_ring_across_threads() {
+        let (mut producer, mut consumer) = RingBuffer::<u32>::with_capacity(64);
+        let writer = std::thread::spawn(move || {
+            let samples: Vec<u32> = (0..10_000).collect();
+            let mut written = 0;
+            while written < samples.len() {
+                let block = &samples[written..(written + 48).min(samples.len())];
+                match producer.write_block(block) {
+                    0 => std::thread::yield_now(),
+                    n => written += n,
+                }
+            }
+        });
+
+        let mut received = Vec::with_capacity(10_000);
+        let mut block = [0; 32];
+        while received.len() < 10_000 {
+            let n = consumer.read_block(&mut block);
+            if n == 0 {
+                std::thread::yield_now();
+            }
+            received.extend_from_slice(&block[..n]);
+        }
+        writer.join().unwrap();
+        assert!(received.iter().copied().eq(0..10_000));
+    }
+}
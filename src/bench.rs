@@ -0,0 +1,101 @@
+//! Criterion benchmarks comparing a Zig kernel with its Rust counterpart
+//!
+//! `autozig::bench!` turns pairs of functions with the same signature into
+//! criterion benchmark functions. Both implementations run on fresh copies of
+//! the same input in one benchmark group, so the report shows them side by
+//! side, with throughput when the input size is given:
+//!
+//! ```rust,ignore
+//! // benches/filters.rs (with `harness = false`)
+//! use autozig::criterion::{criterion_group, criterion_main};
+//! use my_filters::{apply_invert, apply_invert_rust};
+//!
+//! autozig::bench! {
+//!     invert {
+//!         zig: apply_invert,
+//!         rust: apply_invert_rust,
+//!         input: || vec![128u8; 4 * 1920 * 1080],
+//!         bytes: |image: &Vec<u8>| image.len(),
+//!     }
+//! }
+//!
+//! criterion_group!(benches, invert);
+//! criterion_main!(benches);
+//! ```
+//!
+//! Each implementation receives the input by value; wrap functions taking
+//! references in a closure (`zig: |data: Vec<f32>| sum(&data)`).
+
+/// Generate criterion benchmark functions comparing Zig and Rust
+/// implementations
+///
+/// See the [module documentation](crate::bench) for an example.
+#[macro_export]
+macro_rules! bench {
+    ($(
+        $name:ident {
+            zig: $zig:expr,
+            rust: $rust:expr,
+            input: $input:expr
+            $(, bytes: $bytes:expr)?
+            $(,)?
+        }
+    )+) => {
+        $(
+            pub fn $name(c: &mut $crate::criterion::Criterion) {
+                let input = $input;
+                let mut group = c.benchmark_group(stringify!($name));
+                $(
+                    let bytes = ($bytes)(&input());
+                    group.throughput($crate::criterion::Throughput::Bytes(bytes as u64));
+                )?
+                group.bench_function("zig", |b| {
+                    b.iter_batched(
+                        &input,
+                        |input| ($zig)(input),
+                        $crate::criterion::BatchSize::LargeInput,
+                    )
+                });
+                group.bench_function("rust", |b| {
+                    b.iter_batched(
+                        &input,
+                        |input| ($rust)(input),
+                        $crate::criterion::BatchSize::LargeInput,
+                    )
+                });
+                group.finish();
+            }
+        )+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use criterion::Criterion;
+
+    fn double(mut data: Vec<u32>) -> Vec<u32> {
+        data.iter_mut().for_each(|x| *x *= 2);
+        data
+    }
+
+    crate::bench! {
+        double_vs_shift {
+            zig: double,
+            rust: |data: Vec<u32>| data.into_iter().map(|x| x << 1).collect::<Vec<_>>(),
+            input: || vec![3u32; 1024],
+            bytes: |data: &Vec<u32>| data.len() * 4,
+        }
+    }
+
+    #[test]
+    fn test_bench_runs_both_implementations() {
+        let mut c = Criterion::default()
+            .sample_size(10)
+            .warm_up_time(Duration::from_millis(1))
+            .measurement_time(Duration::from_millis(10))
+            .without_plots();
+        double_vs_shift(&mut c);
+    }
+}
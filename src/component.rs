@@ -0,0 +1,122 @@
+#![allow(unsafe_code)]
+//! Runtime of the WASM component model exports (`component` feature)
+//!
+//! With `autozig_build::Builder::emit_wit`, the macros export every wrapper
+//! the WIT world describes with the canonical ABI. The host allocates the
+//! strings and lists it passes in through [`cabi_realloc`], and the exports
+//! take ownership of them; strings and lists they return are left in a
+//! return area until the host calls the matching `cabi_post_<name>`.
+
+use core::cell::UnsafeCell;
+
+use ::alloc::{
+    alloc::{
+        self,
+        Layout,
+    },
+    boxed::Box,
+    string::String,
+    vec::Vec,
+};
+
+/// Pointer and length of the last returned string or list
+struct ReturnArea(UnsafeCell<[usize; 2]>);
+
+// Component instances are single-threaded; the host reads the area before
+// the next call
+unsafe impl Sync for ReturnArea {}
+
+static RETURN_AREA: ReturnArea = ReturnArea(UnsafeCell::new([0; 2]));
+
+/// Allocator the host uses for arguments, as required by the canonical ABI
+///
+/// # Safety
+///
+/// `old_ptr` must have been allocated by this function with `old_len` and
+/// `align`, or `old_len` must be 0; `align` must be a power of two.
+#[no_mangle]
+pub unsafe extern "C" fn cabi_realloc(
+    old_ptr: *mut u8,
+    old_len: usize,
+    align: usize,
+    new_len: usize,
+) -> *mut u8 {
+    let ptr = if old_len == 0 {
+        if new_len == 0 {
+            return align as *mut u8;
+        }
+        alloc::alloc(Layout::from_size_align_unchecked(new_len, align))
+    } else {
+        alloc::realloc(old_ptr, Layout::from_size_align_unchecked(old_len, align), new_len)
+    };
+    if ptr.is_null() {
+        alloc::handle_alloc_error(Layout::from_size_align_unchecked(new_len, align));
+    }
+    ptr
+}
+
+/// Take ownership of a list argument allocated with [`cabi_realloc`]
+///
+/// # Safety
+///
+/// `ptr` must hold `len` initialized elements allocated by [`cabi_realloc`],
+/// and not be used afterwards.
+pub unsafe fn take_list<T>(ptr: *mut T, len: usize) -> Vec<T> {
+    if len == 0 {
+        return Vec::new();
+    }
+    Vec::from_raw_parts(ptr, len, len)
+}
+
+/// Take ownership of a string argument allocated with [`cabi_realloc`]
+///
+/// The canonical ABI guarantees UTF-8; invalid bytes are replaced rather
+/// than trusted.
+///
+/// # Safety
+///
+/// As for [`take_list`].
+pub unsafe fn take_string(ptr: *mut u8, len: usize) -> String {
+    let bytes = take_list(ptr, len);
+    String::from_utf8(bytes).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into())
+}
+
+/// Hand a returned list to the host, returning the return area
+pub fn return_list<T>(list: Vec<T>) -> *mut usize {
+    let len = list.len();
+    let ptr = Box::into_raw(list.into_boxed_slice()) as *mut T;
+    let area = RETURN_AREA.0.get();
+    unsafe {
+        *area = [ptr as usize, len];
+    }
+    area.cast()
+}
+
+/// Free a list returned with [`return_list`] (called by `cabi_post_<name>`)
+///
+/// # Safety
+///
+/// `area` must be the return area of a list of `T` the host is done with.
+pub unsafe fn free_list<T>(area: *mut usize) {
+    let [ptr, len] = *area.cast::<[usize; 2]>();
+    drop(Box::from_raw(core::ptr::slice_from_raw_parts_mut(ptr as *mut T, len)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_abi_roundtrip() {
+        let text = "héllo";
+        let ptr = unsafe { cabi_realloc(std::ptr::null_mut(), 0, 1, text.len()) };
+        unsafe { std::ptr::copy_nonoverlapping(text.as_ptr(), ptr, text.len()) };
+        assert_eq!(unsafe { take_string(ptr, text.len()) }, text);
+        assert!(unsafe { take_list::<u32>(4 as *mut u32, 0) }.is_empty());
+
+        let area = return_list(vec![1.5f32, 2.5]);
+        let [ptr, len] = unsafe { *area.cast::<[usize; 2]>() };
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr as *const f32, len) }, [1.5, 2.5]);
+        unsafe { free_list::<f32>(area) };
+    }
+}
@@ -0,0 +1,128 @@
+#![allow(unsafe_code)]
+//! Debug-build checks of the FFI contract, a sanitizer-lite without ASan
+//!
+//! With the `ffi-contracts` feature, debug builds check what crosses the
+//! boundary and panic on the first violation, naming the function and
+//! parameter:
+//!
+//! - raw pointers passed to Zig are aligned for their type;
+//! - pointers Zig hands back as slices (callback arguments, borrowed returns)
+//!   are non-null and aligned, and span at most `isize::MAX` bytes;
+//! - strings Zig returns are valid UTF-8, instead of being repaired or assumed;
+//! - with [`set_poisoning`], buffers whose ownership ends at the boundary
+//!   (dropped [`ZigBox`](crate::ffi_types::ZigBox)es, memory Zig frees through
+//!   `g_allocator`) are overwritten with [`POISON`] before they are freed, so a
+//!   stale pointer reads garbage instead of plausible data.
+//!
+//! Without the feature, or in release builds, the checks compile to nothing.
+
+use core::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+
+/// Byte written over poisoned buffers
+pub const POISON: u8 = 0xDD;
+
+static POISONING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the checks are compiled in
+pub const fn enabled() -> bool {
+    cfg!(all(feature = "ffi-contracts", debug_assertions))
+}
+
+/// Overwrite freed buffers with [`POISON`] (off by default; has no effect
+/// unless [`enabled`])
+pub fn set_poisoning(poison: bool) {
+    POISONING.store(poison, Ordering::Relaxed);
+}
+
+/// Check a raw pointer argument passed to Zig (called by generated wrappers)
+#[inline(always)]
+pub fn check_arg_ptr<T>(context: &'static str, ptr: *const T) {
+    if enabled() && !ptr.is_null() && misaligned(ptr) {
+        panic!("FFI contract violated: {context} is misaligned ({ptr:p})");
+    }
+}
+
+/// Check a pointer and length Zig passes or returns before Rust views them
+/// as a `&[T]`
+#[inline(always)]
+pub fn check_slice_parts<T>(context: &'static str, ptr: *const T, len: usize) {
+    if !enabled() {
+        return;
+    }
+    if ptr.is_null() {
+        panic!("FFI contract violated: {context} is a null pointer (len {len})");
+    }
+    if misaligned(ptr) {
+        panic!("FFI contract violated: {context} is misaligned ({ptr:p})");
+    }
+    if len.saturating_mul(core::mem::size_of::<T>()) > isize::MAX as usize {
+        panic!("FFI contract violated: {context} has length {len}, over isize::MAX bytes");
+    }
+}
+
+/// Check that bytes Zig returns as a string are UTF-8
+#[inline(always)]
+pub fn check_utf8(context: &'static str, bytes: &[u8]) {
+    if enabled() {
+        if let Err(err) = core::str::from_utf8(bytes) {
+            panic!("FFI contract violated: {context} is not valid UTF-8 ({err})");
+        }
+    }
+}
+
+/// Poison `len` bytes at `ptr` before they are freed, if poisoning is on
+///
+/// # Safety
+///
+/// `ptr` must be valid for writes of `len` bytes, or null.
+#[inline(always)]
+pub unsafe fn poison(ptr: *mut u8, len: usize) {
+    if enabled() && POISONING.load(Ordering::Relaxed) && !ptr.is_null() {
+        core::ptr::write_bytes(ptr, POISON, len);
+    }
+}
+
+fn misaligned<T>(ptr: *const T) -> bool {
+    ptr as usize % core::mem::align_of::<T>() != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_checks() {
+        let value = 7u32;
+        check_arg_ptr("`f` argument `p`", &value as *const u32);
+        check_arg_ptr("`f` argument `p`", core::ptr::null::<u32>());
+        check_slice_parts("`f` return", &value as *const u32, 1);
+        check_utf8("`f` return", "ok".as_bytes());
+
+        let mut buffer = [1u8; 4];
+        set_poisoning(true);
+        unsafe { poison(buffer.as_mut_ptr(), 2) };
+        set_poisoning(false);
+        let expected = if enabled() {
+            [POISON, POISON, 1, 1]
+        } else {
+            [1; 4]
+        };
+        assert_eq!(buffer, expected);
+
+        #[cfg(feature = "std")]
+        if enabled() {
+            let misaligned = (&value as *const u32 as *const u8).wrapping_add(1) as *const u32;
+            assert!(
+                std::panic::catch_unwind(|| check_arg_ptr("`f` argument `p`", misaligned)).is_err()
+            );
+            assert!(std::panic::catch_unwind(|| {
+                check_slice_parts("`f` return", core::ptr::null::<u8>(), 0)
+            })
+            .is_err());
+            assert!(std::panic::catch_unwind(|| check_utf8("`f` return", &[0xff])).is_err());
+        }
+    }
+}
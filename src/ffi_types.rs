@@ -1,5 +1,21 @@
 #![allow(unsafe_code)]
-use std::marker::PhantomData;
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec,
+};
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{
+        Add,
+        Div,
+        Mul,
+        Neg,
+        Sub,
+    },
+};
 
 /// standard exchange format for moving memory from Zig to Rust
 #[repr(C)]
@@ -15,6 +31,33 @@ pub struct ZigBuffer {
     pub free_fn: Option<unsafe extern "C" fn(*mut u8, usize, usize)>,
 }
 
+impl ZigBuffer {
+    /// Copy the bytes into a Rust `Vec<u8>` and release the Zig buffer.
+    ///
+    /// Used by generated wrappers for functions returning `Vec<u8>`.
+    pub fn into_bytes(self) -> Vec<u8> {
+        ZigBox::<u8>::new(self).as_slice().to_vec()
+    }
+
+    /// Copy the bytes into a Rust `String` and release the Zig buffer.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`, or panic with the
+    /// `ffi-contracts` feature.
+    pub fn into_string(self) -> String {
+        let bytes = ZigBox::<u8>::new(self);
+        crate::contracts::check_utf8("String returned by Zig", bytes.as_slice());
+        String::from_utf8_lossy(bytes.as_slice()).into_owned()
+    }
+
+    /// Keep the bytes where Zig allocated them, as a [`ZigString`].
+    ///
+    /// Used by generated wrappers for functions returning `ZigString`.
+    /// Panics if the bytes are not valid UTF-8.
+    pub fn into_zig_string(self) -> ZigString {
+        ZigString::new(self)
+    }
+}
+
 /// Helper function to free Rust vectors passed to Zig.
 /// Use this when manually constructing ZigBuffer or as the backend for
 /// `From<Vec<T>>`.
@@ -30,7 +73,7 @@ pub unsafe extern "C" fn rust_free_vec<T>(ptr: *mut u8, len: usize, cap: usize)
 
 impl<T> From<Vec<T>> for ZigBuffer {
     fn from(data: Vec<T>) -> Self {
-        let mut manual = std::mem::ManuallyDrop::new(data);
+        let mut manual = core::mem::ManuallyDrop::new(data);
         ZigBuffer {
             ptr: manual.as_mut_ptr() as *mut u8,
             len: manual.len(),
@@ -114,20 +157,27 @@ impl<T> ZigBox<T> {
 
     /// Access the data as a Rust slice.
     pub fn as_slice(&self) -> &[T] {
+        // Empty buffers may carry a null pointer, which from_raw_parts rejects
+        if self.inner.ptr.is_null() {
+            return &[];
+        }
         unsafe {
-            std::slice::from_raw_parts(
+            core::slice::from_raw_parts(
                 self.inner.ptr as *const T,
-                self.inner.len / std::mem::size_of::<T>(),
+                self.inner.len / core::mem::size_of::<T>(),
             )
         }
     }
 
     /// Access the data as a mutable Rust slice.
     pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.inner.ptr.is_null() {
+            return &mut [];
+        }
         unsafe {
-            std::slice::from_raw_parts_mut(
+            core::slice::from_raw_parts_mut(
                 self.inner.ptr as *mut T,
-                self.inner.len / std::mem::size_of::<T>(),
+                self.inner.len / core::mem::size_of::<T>(),
             )
         }
     }
@@ -137,26 +187,894 @@ impl<T> Drop for ZigBox<T> {
     fn drop(&mut self) {
         if let Some(free_fn) = self.inner.free_fn {
             unsafe {
+                crate::contracts::poison(self.inner.ptr, self.inner.len);
                 free_fn(self.inner.ptr, self.inner.len, self.inner.cap);
             }
         }
     }
 }
 
+/// An owned UTF-8 string allocated by Zig, used without copying.
+///
+/// The buffer is freed through its `free_fn` on drop; clones are allocated
+/// by Rust.
+pub struct ZigString {
+    inner: ZigBox<u8>,
+}
+
+impl ZigString {
+    /// Wrap a Zig buffer holding UTF-8 text.
+    ///
+    /// Panics if the buffer is invalid or not UTF-8. For a non-panicking
+    /// version, use `try_new`.
+    pub fn new(raw: ZigBuffer) -> Self {
+        match Self::try_new(raw) {
+            Ok(s) => s,
+            Err(e) => panic!("ZigString::new failed: {}", e),
+        }
+    }
+
+    /// Try to wrap a Zig buffer, validating it and its UTF-8 encoding.
+    ///
+    /// An invalid buffer is still freed.
+    pub fn try_new(raw: ZigBuffer) -> Result<Self, &'static str> {
+        let inner = ZigBox::try_new(raw)?;
+        if core::str::from_utf8(inner.as_slice()).is_err() {
+            return Err("Invalid UTF-8");
+        }
+        Ok(Self { inner })
+    }
+
+    /// Access the string.
+    pub fn as_str(&self) -> &str {
+        // SAFETY: validated as UTF-8 on construction and never mutated
+        unsafe { core::str::from_utf8_unchecked(self.inner.as_slice()) }
+    }
+}
+
+impl core::ops::Deref for ZigString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ZigString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for ZigString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for ZigString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Clone for ZigString {
+    fn clone(&self) -> Self {
+        Self::from(String::from(self.as_str()))
+    }
+}
+
+impl From<String> for ZigString {
+    fn from(s: String) -> Self {
+        Self {
+            inner: ZigBox::new(ZigBuffer::from(s.into_bytes())),
+        }
+    }
+}
+
+impl From<ZigString> for String {
+    fn from(s: ZigString) -> Self {
+        String::from(s.as_str())
+    }
+}
+
+impl PartialEq for ZigString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ZigString {}
+
+impl PartialEq<str> for ZigString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ZigString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl core::hash::Hash for ZigString {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+/// FFI representation of `Result<T, E>` returned from Zig.
+///
+/// Mirrors the `AutoZigResult(T, E)` extern struct that the build engine
+/// injects into the generated Zig code. Only the field selected by `is_ok`
+/// is initialized.
+#[repr(C)]
+pub struct ZigResult<T, E> {
+    /// Non-zero when `ok_value` holds the result
+    pub is_ok: u8,
+    pub ok_value: MaybeUninit<T>,
+    pub err_value: MaybeUninit<E>,
+}
+
+impl<T, E> ZigResult<T, E> {
+    /// Convert into a Rust `Result`, reading only the initialized field.
+    pub fn into_result(self) -> Result<T, E> {
+        // SAFETY: the Zig side always initializes the field selected by `is_ok`
+        unsafe {
+            if self.is_ok != 0 {
+                Ok(self.ok_value.assume_init())
+            } else {
+                Err(self.err_value.assume_init())
+            }
+        }
+    }
+}
+
+impl<T, E> From<ZigResult<T, E>> for Result<T, E> {
+    fn from(result: ZigResult<T, E>) -> Self {
+        result.into_result()
+    }
+}
+
+/// FFI representation of `Option<T>` for value types returned from Zig.
+///
+/// Mirrors the `AutoZigOption(T)` extern struct injected into the generated
+/// Zig code. Pointer options (`Option<*mut T>`) don't use this type; they are
+/// returned as nullable pointers.
+#[repr(C)]
+pub struct ZigOption<T> {
+    /// Non-zero when `value` is initialized
+    pub is_some: u8,
+    pub value: MaybeUninit<T>,
+}
+
+impl<T> ZigOption<T> {
+    /// Convert into a Rust `Option`, reading `value` only when present.
+    pub fn into_option(self) -> Option<T> {
+        if self.is_some != 0 {
+            // SAFETY: the Zig side initializes `value` whenever `is_some` is set
+            Some(unsafe { self.value.assume_init() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> From<ZigOption<T>> for Option<T> {
+    fn from(option: ZigOption<T>) -> Self {
+        option.into_option()
+    }
+}
+
+/// FFI representation of a slice borrowed from Zig-owned state.
+///
+/// Mirrors the `AutoZigSlice(T)` extern struct injected into the generated
+/// Zig code. Opaque type methods declared as returning `&[T]` or `&str`
+/// receive this and tie the borrow to `&self`, so the buffer can't be used
+/// after the object is mutated or dropped.
+#[repr(C)]
+pub struct ZigSliceRef<T> {
+    pub ptr: *const T,
+    pub len: usize,
+}
+
+impl<T> ZigSliceRef<T> {
+    /// View the Zig buffer as a Rust slice.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` initialized items that stay valid and
+    /// unmodified for the chosen lifetime `'a`.
+    pub unsafe fn as_slice<'a>(&self) -> &'a [T] {
+        if self.len != 0 {
+            crate::contracts::check_slice_parts("slice returned by Zig", self.ptr, self.len);
+        }
+        // Empty slices may carry a null pointer, which from_raw_parts rejects
+        if self.ptr.is_null() || self.len == 0 {
+            return &[];
+        }
+        core::slice::from_raw_parts(self.ptr, self.len)
+    }
+}
+
+impl ZigSliceRef<u8> {
+    /// View static Zig data (string literals, `@tagName`) as a `&'static
+    /// str`.
+    ///
+    /// Functions declared as returning `&'static str` receive this. Debug
+    /// builds check that the bytes are valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` bytes of UTF-8 that are never freed or
+    /// modified.
+    pub unsafe fn into_static_str(self) -> &'static str {
+        let bytes = self.as_slice();
+        debug_assert!(
+            core::str::from_utf8(bytes).is_ok(),
+            "Zig returned a `&'static str` that is not valid UTF-8: {bytes:?}"
+        );
+        core::str::from_utf8_unchecked(bytes)
+    }
+}
+
+/// FFI view of a Rust `Vec<T>` that Zig can append to.
+///
+/// Generated wrappers lower a `&mut Vec<T>` parameter to `*mut ZigGrowable<T>`,
+/// which mirrors the `AutoZigGrowable(T)` extern struct injected into the
+/// generated Zig code. Zig writes into `ptr[len..cap]`, bumps `len`, and calls
+/// `grow_fn` to have Rust's allocator make room when `cap` runs out. The new
+/// length is committed to the vector when the view is dropped.
+#[repr(C)]
+pub struct ZigGrowable<'a, T: Copy> {
+    pub ptr: *mut T,
+    pub len: usize,
+    pub cap: usize,
+    /// The borrowed `Vec<T>`; opaque to Zig
+    pub ctx: *mut core::ffi::c_void,
+    /// Ensure room for at least `min_cap` items; returns false on allocation
+    /// failure, leaving the buffer untouched.
+    pub grow_fn: unsafe extern "C" fn(*mut ZigGrowable<'a, T>, usize) -> bool,
+    _marker: PhantomData<&'a mut Vec<T>>,
+}
+
+impl<'a, T: Copy> ZigGrowable<'a, T> {
+    /// Borrow `vec` for the duration of a Zig call.
+    pub fn new(vec: &'a mut Vec<T>) -> Self {
+        Self {
+            ptr: vec.as_mut_ptr(),
+            len: vec.len(),
+            cap: vec.capacity(),
+            ctx: vec as *mut Vec<T> as *mut core::ffi::c_void,
+            grow_fn: Self::grow,
+            _marker: PhantomData,
+        }
+    }
+
+    unsafe extern "C" fn grow(this: *mut ZigGrowable<'a, T>, min_cap: usize) -> bool {
+        let Some(this) = this.as_mut() else {
+            return false;
+        };
+        if this.len > this.cap {
+            return false;
+        }
+        let vec = &mut *(this.ctx as *mut Vec<T>);
+        // Items Zig appended so far must survive the reallocation
+        vec.set_len(this.len);
+        if vec.try_reserve(min_cap.saturating_sub(this.len)).is_err() {
+            return false;
+        }
+        this.ptr = vec.as_mut_ptr();
+        this.cap = vec.capacity();
+        true
+    }
+}
+
+impl<T: Copy> Drop for ZigGrowable<'_, T> {
+    fn drop(&mut self) {
+        assert!(
+            self.len <= self.cap,
+            "Zig set ZigGrowable length {} past its capacity {}",
+            self.len,
+            self.cap
+        );
+        // SAFETY: `ctx` is the vector borrowed in `new`, and Zig initialized
+        // every item below `len` inside its (possibly grown) allocation
+        unsafe {
+            let vec = &mut *(self.ctx as *mut Vec<T>);
+            vec.set_len(self.len);
+        }
+    }
+}
+
+/// Entries of the map behind a [`MapView`]
+type MapEntries<'a, K, V> = Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>;
+
+/// FFI view of a Rust map that Zig walks entry by entry.
+///
+/// A `MapView<'_, K, V>` parameter is passed as `*mut MapView<K, V>`, which
+/// mirrors the `AutoZigMapView(K, V)` extern struct injected into the
+/// generated Zig code. Zig calls `next_fn` until it returns false; the entries
+/// come in the map's iteration order and can be walked once.
+#[repr(C)]
+pub struct MapView<'a, K: Copy, V: Copy> {
+    /// Number of entries
+    pub len: usize,
+    /// The map's iterator; opaque to Zig
+    pub ctx: *mut core::ffi::c_void,
+    /// Write the next entry to `key` and `value`; returns false when done.
+    pub next_fn: unsafe extern "C" fn(*mut MapView<'a, K, V>, *mut K, *mut V) -> bool,
+    _marker: PhantomData<&'a (K, V)>,
+}
+
+impl<'a, K: Copy + 'a, V: Copy + 'a> MapView<'a, K, V> {
+    /// Borrow the entries of `map` (a `HashMap`, `BTreeMap`, ...) for the
+    /// duration of a Zig call.
+    pub fn new<M>(map: &'a M) -> Self
+    where
+        M: ?Sized,
+        &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        <&'a M as IntoIterator>::IntoIter: ExactSizeIterator + 'a,
+    {
+        let entries = map.into_iter();
+        let len = entries.len();
+        let entries: MapEntries<'a, K, V> = Box::new(entries);
+        Self {
+            len,
+            ctx: Box::into_raw(Box::new(entries)) as *mut core::ffi::c_void,
+            next_fn: Self::next,
+            _marker: PhantomData,
+        }
+    }
+
+    unsafe extern "C" fn next(this: *mut MapView<'a, K, V>, key: *mut K, value: *mut V) -> bool {
+        let Some(this) = this.as_mut() else {
+            return false;
+        };
+        let entries = &mut *(this.ctx as *mut MapEntries<'a, K, V>);
+        let Some((k, v)) = entries.next() else {
+            return false;
+        };
+        if !key.is_null() {
+            key.write(*k);
+        }
+        if !value.is_null() {
+            value.write(*v);
+        }
+        true
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: Copy, V: Copy, S> From<&'a std::collections::HashMap<K, V, S>> for MapView<'a, K, V> {
+    fn from(map: &'a std::collections::HashMap<K, V, S>) -> Self {
+        Self::new(map)
+    }
+}
+
+impl<'a, K: Copy, V: Copy> From<&'a alloc::collections::BTreeMap<K, V>> for MapView<'a, K, V> {
+    fn from(map: &'a alloc::collections::BTreeMap<K, V>) -> Self {
+        Self::new(map)
+    }
+}
+
+impl<K: Copy, V: Copy> Drop for MapView<'_, K, V> {
+    fn drop(&mut self) {
+        // SAFETY: `ctx` is the boxed iterator created in `new`
+        unsafe { drop(Box::from_raw(self.ctx as *mut MapEntries<'_, K, V>)) };
+    }
+}
+
+/// Map entries that Zig inserts one by one, returned to Rust.
+///
+/// A function declared as returning `MapBuilder<K, V>` receives a trailing
+/// `*mut MapBuilder<K, V>` out parameter, which mirrors the
+/// `AutoZigMapBuilder(K, V)` extern struct injected into the generated Zig
+/// code. Zig calls `insert_fn` (`put` on the Zig side) for every entry; the
+/// caller turns the builder into any map with [`MapBuilder::collect`].
+#[repr(C)]
+pub struct MapBuilder<K: Copy, V: Copy> {
+    /// The entries inserted so far (a boxed `Vec<(K, V)>`); opaque to Zig
+    pub ctx: *mut core::ffi::c_void,
+    /// Insert an entry; returns false on allocation failure.
+    pub insert_fn: unsafe extern "C" fn(*mut MapBuilder<K, V>, *const K, *const V) -> bool,
+}
+
+impl<K: Copy, V: Copy> MapBuilder<K, V> {
+    /// Empty builder, ready to be passed to Zig.
+    pub fn new() -> Self {
+        Self {
+            ctx: Box::into_raw(Box::new(Vec::<(K, V)>::new())) as *mut core::ffi::c_void,
+            insert_fn: Self::insert,
+        }
+    }
+
+    unsafe extern "C" fn insert(
+        this: *mut MapBuilder<K, V>,
+        key: *const K,
+        value: *const V,
+    ) -> bool {
+        let Some(this) = this.as_mut() else {
+            return false;
+        };
+        if key.is_null() || value.is_null() {
+            return false;
+        }
+        let entries = this.entries_mut();
+        if entries.try_reserve(1).is_err() {
+            return false;
+        }
+        entries.push((key.read(), value.read()));
+        true
+    }
+
+    fn entries_mut(&mut self) -> &mut Vec<(K, V)> {
+        // SAFETY: `ctx` is the boxed vector created in `new`
+        unsafe { &mut *(self.ctx as *mut Vec<(K, V)>) }
+    }
+
+    /// Number of entries inserted, counting duplicate keys.
+    pub fn len(&self) -> usize {
+        // SAFETY: `ctx` is the boxed vector created in `new`
+        unsafe { (*(self.ctx as *const Vec<(K, V)>)).len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entries in insertion order.
+    pub fn into_entries(mut self) -> Vec<(K, V)> {
+        core::mem::take(self.entries_mut())
+    }
+
+    /// Collect into a map (or any collection of pairs); for duplicate keys
+    /// the last insertion wins in `HashMap` and `BTreeMap`.
+    pub fn collect<M: FromIterator<(K, V)>>(self) -> M {
+        self.into_entries().into_iter().collect()
+    }
+}
+
+impl<K: Copy, V: Copy> Default for MapBuilder<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Copy + Eq + core::hash::Hash, V: Copy> From<MapBuilder<K, V>>
+    for std::collections::HashMap<K, V>
+{
+    fn from(builder: MapBuilder<K, V>) -> Self {
+        builder.collect()
+    }
+}
+
+impl<K: Copy + Ord, V: Copy> From<MapBuilder<K, V>> for alloc::collections::BTreeMap<K, V> {
+    fn from(builder: MapBuilder<K, V>) -> Self {
+        builder.collect()
+    }
+}
+
+impl<K: Copy, V: Copy> Drop for MapBuilder<K, V> {
+    fn drop(&mut self) {
+        // SAFETY: `ctx` is the boxed vector created in `new`
+        unsafe { drop(Box::from_raw(self.ctx as *mut Vec<(K, V)>)) };
+    }
+}
+
+/// Elements a `rows` x `cols` matrix with rows `row_stride` apart spans
+///
+/// # Panics
+///
+/// If `cols` exceeds `row_stride` or `data_len` is too short.
+fn check_matrix(data_len: usize, rows: usize, cols: usize, row_stride: usize) {
+    assert!(cols <= row_stride, "matrix rows overlap: {cols} columns, stride {row_stride}");
+    let needed = match rows {
+        0 => 0,
+        rows => (rows - 1)
+            .checked_mul(row_stride)
+            .and_then(|start| start.checked_add(cols))
+            .expect("matrix size overflows usize"),
+    };
+    assert!(
+        data_len >= needed,
+        "{rows}x{cols} matrix with stride {row_stride} needs {needed} elements, got {data_len}"
+    );
+}
+
+/// FFI view of a row-major matrix (an image plane, a 2D slice of a tensor)
+/// borrowed from Rust.
+///
+/// Passed by value; mirrors the `AutoZigMatView(T)` extern struct injected
+/// into the generated Zig code, whose `at` and `row` helpers check bounds.
+/// Rows may be padded: element `(r, c)` is at `ptr[r * row_stride + c]`.
+#[repr(C)]
+pub struct MatView<'a, T> {
+    ptr: *const T,
+    rows: usize,
+    cols: usize,
+    row_stride: usize,
+    _marker: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> MatView<'a, T> {
+    /// View `data` as `rows` x `cols` elements, rows `row_stride` elements
+    /// apart
+    ///
+    /// # Panics
+    ///
+    /// If `cols` exceeds `row_stride` or `data` is too short.
+    pub fn new(data: &'a [T], rows: usize, cols: usize, row_stride: usize) -> Self {
+        check_matrix(data.len(), rows, cols, row_stride);
+        Self {
+            ptr: data.as_ptr(),
+            rows,
+            cols,
+            row_stride,
+            _marker: PhantomData,
+        }
+    }
+
+    /// View `data` as `rows` x `cols` densely packed elements
+    pub fn packed(data: &'a [T], rows: usize, cols: usize) -> Self {
+        Self::new(data, rows, cols, cols)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Elements from the start of one row to the start of the next
+    pub fn row_stride(&self) -> usize {
+        self.row_stride
+    }
+
+    /// Row `r`, or `None` past the last row
+    pub fn row(&self, r: usize) -> Option<&'a [T]> {
+        // SAFETY: `new` checked that every row is within the borrowed slice
+        (r < self.rows).then(|| unsafe {
+            core::slice::from_raw_parts(self.ptr.add(r * self.row_stride), self.cols)
+        })
+    }
+
+    /// Element `(r, c)`, or `None` out of bounds
+    pub fn get(&self, r: usize, c: usize) -> Option<&'a T> {
+        self.row(r)?.get(c)
+    }
+
+    /// The `rows` x `cols` block starting at `(row, col)`, sharing the
+    /// stride, or `None` if it does not fit
+    pub fn submatrix(&self, row: usize, col: usize, rows: usize, cols: usize) -> Option<Self> {
+        if row.checked_add(rows)? > self.rows || col.checked_add(cols)? > self.cols {
+            return None;
+        }
+        Some(Self {
+            // SAFETY: the block is within this view (an empty block may start
+            // one past its end)
+            ptr: unsafe { self.ptr.add((row * self.row_stride + col).min(self.span())) },
+            rows,
+            cols,
+            row_stride: self.row_stride,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Elements from the first to one past the last
+    fn span(&self) -> usize {
+        match self.rows {
+            0 => 0,
+            rows => (rows - 1) * self.row_stride + self.cols,
+        }
+    }
+}
+
+impl<T> Clone for MatView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MatView<'_, T> {}
+
+impl<T> fmt::Debug for MatView<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MatView")
+            .field("rows", &self.rows)
+            .field("cols", &self.cols)
+            .field("row_stride", &self.row_stride)
+            .finish()
+    }
+}
+
+// SAFETY: a shared borrow of `[T]`
+unsafe impl<T: Sync> Send for MatView<'_, T> {}
+unsafe impl<T: Sync> Sync for MatView<'_, T> {}
+
+/// Mutable FFI view of a row-major matrix borrowed from Rust, for Zig to
+/// write into.
+///
+/// Passed by value; mirrors the `AutoZigMatViewMut(T)` extern struct, which
+/// adds `set` and `ptrAt` to the helpers of [`MatView`].
+#[repr(C)]
+pub struct MatViewMut<'a, T> {
+    ptr: *mut T,
+    rows: usize,
+    cols: usize,
+    row_stride: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> MatViewMut<'a, T> {
+    /// View `data` as `rows` x `cols` elements, rows `row_stride` elements
+    /// apart
+    ///
+    /// # Panics
+    ///
+    /// If `cols` exceeds `row_stride` or `data` is too short.
+    pub fn new(data: &'a mut [T], rows: usize, cols: usize, row_stride: usize) -> Self {
+        check_matrix(data.len(), rows, cols, row_stride);
+        Self {
+            ptr: data.as_mut_ptr(),
+            rows,
+            cols,
+            row_stride,
+            _marker: PhantomData,
+        }
+    }
+
+    /// View `data` as `rows` x `cols` densely packed elements
+    pub fn packed(data: &'a mut [T], rows: usize, cols: usize) -> Self {
+        Self::new(data, rows, cols, cols)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Elements from the start of one row to the start of the next
+    pub fn row_stride(&self) -> usize {
+        self.row_stride
+    }
+
+    /// Read-only view of the same matrix
+    pub fn as_view(&self) -> MatView<'_, T> {
+        MatView {
+            ptr: self.ptr,
+            rows: self.rows,
+            cols: self.cols,
+            row_stride: self.row_stride,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Row `r`, or `None` past the last row
+    pub fn row_mut(&mut self, r: usize) -> Option<&mut [T]> {
+        // SAFETY: `new` checked that every row is within the borrowed slice,
+        // and rows do not overlap
+        (r < self.rows).then(|| unsafe {
+            core::slice::from_raw_parts_mut(self.ptr.add(r * self.row_stride), self.cols)
+        })
+    }
+
+    /// Element `(r, c)`, or `None` out of bounds
+    pub fn get_mut(&mut self, r: usize, c: usize) -> Option<&mut T> {
+        self.row_mut(r)?.get_mut(c)
+    }
+}
+
+impl<T> fmt::Debug for MatViewMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MatViewMut")
+            .field("rows", &self.rows)
+            .field("cols", &self.cols)
+            .field("row_stride", &self.row_stride)
+            .finish()
+    }
+}
+
+// SAFETY: an exclusive borrow of `[T]`
+unsafe impl<T: Send> Send for MatViewMut<'_, T> {}
+unsafe impl<T: Sync> Sync for MatViewMut<'_, T> {}
+
+/// Fixed-point number with `F` fractional bits stored in the integer `I`
+///
+/// `#[repr(transparent)]`, so it crosses the FFI boundary as the bare
+/// integer: `Fixed<i32, 16>` on the Rust side is an `i32` in the Zig
+/// signature, where `AutoZigFixed(i32, 16)` provides the same conversions
+/// and arithmetic.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed<I, const F: u32>(I);
+
+/// Integer storage of a [`Fixed`] number
+pub trait FixedBits: Copy {
+    /// Nearest integer, saturating at the integer range
+    fn from_f64(value: f64) -> Self;
+
+    fn to_f64(self) -> f64;
+}
+
+impl<I, const F: u32> Fixed<I, F> {
+    /// Wrap the raw integer representation
+    pub const fn from_bits(bits: I) -> Self {
+        Self(bits)
+    }
+
+    /// Raw integer representation
+    pub fn to_bits(self) -> I {
+        self.0
+    }
+}
+
+impl<I: FixedBits, const F: u32> Fixed<I, F> {
+    const SCALE: f64 = (1u128 << F) as f64;
+
+    /// Nearest fixed-point value, saturating at the integer range
+    pub fn from_f64(value: f64) -> Self {
+        Self(I::from_f64(value * Self::SCALE))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0.to_f64() / Self::SCALE
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(value as f64)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+}
+
+impl<I: FixedBits, const F: u32> fmt::Display for Fixed<I, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+/// `f64::round` (half away from zero), which `core` lacks
+fn round(value: f64) -> f64 {
+    #[cfg(feature = "std")]
+    return value.round();
+    #[cfg(not(feature = "std"))]
+    {
+        // Exact for the magnitudes fixed-point storage can hold
+        let whole = value as i128 as f64;
+        match value - whole {
+            frac if frac >= 0.5 => whole + 1.0,
+            frac if frac <= -0.5 => whole - 1.0,
+            _ => whole,
+        }
+    }
+}
+
+macro_rules! impl_fixed {
+    ($($int:ty => $wide:ty),* $(,)?) => {$(
+        impl FixedBits for $int {
+            fn from_f64(value: f64) -> Self {
+                round(value) as $int
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+
+        impl<const F: u32> Add for Fixed<$int, F> {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self(self.0.wrapping_add(rhs.0))
+            }
+        }
+
+        impl<const F: u32> Sub for Fixed<$int, F> {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0.wrapping_sub(rhs.0))
+            }
+        }
+
+        /// Product computed at double width, truncated like Zig's
+        /// `AutoZigFixed.mul`
+        impl<const F: u32> Mul for Fixed<$int, F> {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                Self(((self.0 as $wide * rhs.0 as $wide) >> F) as $int)
+            }
+        }
+
+        impl<const F: u32> Div for Fixed<$int, F> {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self {
+                Self((((self.0 as $wide) << F) / rhs.0 as $wide) as $int)
+            }
+        }
+    )*};
+}
+
+impl_fixed!(
+    i8 => i16, i16 => i32, i32 => i64, i64 => i128,
+    u8 => u16, u16 => u32, u32 => u64, u64 => u128,
+);
+
+macro_rules! impl_fixed_neg {
+    ($($int:ty),*) => {$(
+        impl<const F: u32> Neg for Fixed<$int, F> {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(self.0.wrapping_neg())
+            }
+        }
+    )*};
+}
+
+impl_fixed_neg!(i8, i16, i32, i64);
+
+/// Plain data that Zig receives by value or through a pointer
+///
+/// `#[monomorphize]` accepts primitives and the `#[repr(C)]` types declared
+/// in the same block. Other types, e.g. `#[repr(C)]` structs defined
+/// elsewhere, are accepted when the type parameter is bounded by `ZigPod`
+/// (`fn sum<T: ZigPod>(data: &[T]) -> T`) and they implement it.
+///
+/// # Safety
+///
+/// The type must have the layout of the Zig type it is passed as
+/// (`#[repr(C)]`, `#[repr(transparent)]` or a primitive), and every bit
+/// pattern Zig can produce for it must be valid.
+pub unsafe trait ZigPod {}
+
+macro_rules! impl_zig_pod {
+    ($($ty:ty),*) => {
+        // SAFETY: primitives have the layout of the Zig type of the same name
+        $(unsafe impl ZigPod for $ty {})*
+    };
+}
+
+impl_zig_pod!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+// SAFETY: a fixed-point number is its integer
+unsafe impl<I: ZigPod, const F: u32> ZigPod for Fixed<I, F> {}
+
 #[cfg(test)]
 mod tests {
+    use alloc::{
+        format,
+        string::{
+            String,
+            ToString,
+        },
+        vec,
+    };
+
     use super::*;
 
     #[test]
     fn test_zig_box_drop_calls_free() {
-        use std::sync::atomic::{
+        use core::sync::atomic::{
             AtomicPtr,
             AtomicUsize,
             Ordering,
         };
 
         // Static atomics to track calls safely
-        static FREED_PTR: AtomicPtr<u8> = AtomicPtr::new(std::ptr::null_mut());
+        static FREED_PTR: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
         static FREED_LEN: AtomicUsize = AtomicUsize::new(0);
         static FREED_CAP: AtomicUsize = AtomicUsize::new(0);
         static CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -189,4 +1107,181 @@ mod tests {
         assert_eq!(FREED_LEN.load(Ordering::SeqCst), 3);
         assert_eq!(FREED_CAP.load(Ordering::SeqCst), 3);
     }
+
+    #[test]
+    fn test_zig_result_into_result() {
+        let ok = ZigResult::<i32, u8> {
+            is_ok: 1,
+            ok_value: MaybeUninit::new(42),
+            err_value: MaybeUninit::uninit(),
+        };
+        assert_eq!(ok.into_result(), Ok(42));
+
+        let err = ZigResult::<i32, u8> {
+            is_ok: 0,
+            ok_value: MaybeUninit::uninit(),
+            err_value: MaybeUninit::new(7),
+        };
+        assert_eq!(Result::from(err), Err(7));
+    }
+
+    #[test]
+    fn test_zig_option_into_option() {
+        let some = ZigOption::<i32> { is_some: 1, value: MaybeUninit::new(9) };
+        assert_eq!(some.into_option(), Some(9));
+
+        let none = ZigOption::<i32> { is_some: 0, value: MaybeUninit::uninit() };
+        assert_eq!(Option::<i32>::from(none), None);
+    }
+
+    #[test]
+    fn test_zig_buffer_into_string_frees() {
+        let buf = ZigBuffer::from(b"hello".to_vec());
+        assert_eq!(buf.into_string(), "hello");
+
+        let empty = ZigBuffer {
+            ptr: core::ptr::null_mut(),
+            len: 0,
+            cap: 0,
+            free_fn: None,
+        };
+        assert!(empty.into_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_zig_slice_ref_as_slice() {
+        let data = [1u32, 2, 3];
+        let view = ZigSliceRef { ptr: data.as_ptr(), len: data.len() };
+        assert_eq!(unsafe { view.as_slice() }, &data);
+
+        let empty = ZigSliceRef::<u32> { ptr: core::ptr::null(), len: 0 };
+        assert!(unsafe { empty.as_slice() }.is_empty());
+    }
+
+    #[test]
+    fn test_zig_slice_ref_into_static_str() {
+        static VERSION: &[u8] = b"1.4.0";
+        let view = ZigSliceRef {
+            ptr: VERSION.as_ptr(),
+            len: VERSION.len(),
+        };
+        assert_eq!(unsafe { view.into_static_str() }, "1.4.0");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not valid UTF-8")]
+    fn test_zig_slice_ref_into_static_str_rejects_invalid_utf8() {
+        static BYTES: &[u8] = &[0xff, 0xfe];
+        let view = ZigSliceRef { ptr: BYTES.as_ptr(), len: BYTES.len() };
+        let _ = unsafe { view.into_static_str() };
+    }
+
+    #[test]
+    fn test_zig_growable_grow_and_commit() {
+        let mut out = vec![1u8, 2];
+        {
+            let mut growable = ZigGrowable::new(&mut out);
+            // What Zig's `appendSlice` does once capacity runs out
+            let extra = [3u8; 100];
+            let needed = growable.len + extra.len();
+            unsafe {
+                assert!((growable.grow_fn)(&mut growable, needed));
+                assert!(growable.cap >= 102);
+                core::ptr::copy_nonoverlapping(
+                    extra.as_ptr(),
+                    growable.ptr.add(growable.len),
+                    extra.len(),
+                );
+            }
+            growable.len += extra.len();
+        }
+        assert_eq!(out.len(), 102);
+        assert_eq!(&out[..3], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_zig_string() {
+        let s = ZigBuffer::from(b"h\xc3\xa9llo".to_vec()).into_zig_string();
+        assert_eq!(s, "héllo");
+        assert_eq!(s.len(), 6);
+        assert_eq!(format!("{s}|{s:?}"), "héllo|\"héllo\"");
+
+        let copy = s.clone();
+        drop(s);
+        assert_eq!(String::from(copy), "héllo");
+
+        let invalid = ZigString::try_new(ZigBuffer::from(vec![0xffu8, 0xfe]));
+        assert_eq!(invalid.unwrap_err(), "Invalid UTF-8");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_map_view_and_builder() {
+        use std::collections::{
+            BTreeMap,
+            HashMap,
+        };
+
+        let prices = BTreeMap::from([(1u32, 2.5f64), (7, 4.0)]);
+        let mut view = MapView::from(&prices);
+        assert_eq!(view.len, 2);
+        let (mut key, mut value) = (0u32, 0f64);
+        let mut seen = Vec::new();
+        // SAFETY: called the way the Zig side calls it
+        while unsafe { (view.next_fn)(&mut view, &mut key, &mut value) } {
+            seen.push((key, value));
+        }
+        assert_eq!(seen, [(1, 2.5), (7, 4.0)]);
+
+        let mut builder = MapBuilder::<u8, u32>::new();
+        for (k, v) in [(b'a', 1u32), (b'b', 2), (b'a', 3)] {
+            // SAFETY: called the way the Zig side calls it
+            assert!(unsafe { (builder.insert_fn)(&mut builder, &k, &v) });
+        }
+        assert_eq!(builder.len(), 3);
+        let counts: HashMap<u8, u32> = builder.into();
+        assert_eq!(counts, HashMap::from([(b'a', 3), (b'b', 2)]));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_mat_view() {
+        // 2x3 matrix in rows of 4, the last one unpadded
+        let mut data: Vec<u8> = (0..7).collect();
+        let view = MatView::new(&data, 2, 3, 4);
+        assert_eq!(view.row(1), Some(&[4u8, 5, 6][..]));
+        assert_eq!(view.get(0, 2), Some(&2));
+        assert_eq!(view.get(0, 3), None);
+        assert_eq!(view.row(2), None);
+        let block = view.submatrix(1, 1, 1, 2).unwrap();
+        assert_eq!(block.row(0), Some(&[5u8, 6][..]));
+        assert!(view.submatrix(1, 2, 1, 2).is_none());
+        assert_eq!(view.submatrix(2, 3, 0, 0).unwrap().rows(), 0);
+
+        let mut out = MatViewMut::new(&mut data, 2, 3, 4);
+        *out.get_mut(1, 0).unwrap() = 40;
+        out.row_mut(0).unwrap().fill(9);
+        assert_eq!(out.as_view().get(1, 0), Some(&40));
+        assert_eq!(data, [9, 9, 9, 3, 40, 5, 6]);
+
+        assert_eq!(core::mem::size_of::<MatView<'_, f32>>(), 4 * core::mem::size_of::<usize>());
+        assert!(std::panic::catch_unwind(|| MatView::packed(&[0u8; 5], 2, 3)).is_err());
+        assert!(std::panic::catch_unwind(|| MatView::new(&[0u8; 8], 2, 4, 3)).is_err());
+    }
+
+    #[test]
+    fn test_fixed_arithmetic() {
+        type Q16 = Fixed<i32, 16>;
+        let a = Q16::from_f64(1.5);
+        let b = Q16::from_f64(-2.25);
+        assert_eq!(a.to_bits(), 0x18000);
+        assert_eq!((a + b).to_f64(), -0.75);
+        assert_eq!((a - b).to_f64(), 3.75);
+        assert_eq!((a * b).to_f64(), -3.375);
+        assert_eq!((b / a).to_f64(), -1.5);
+        assert_eq!((-a).to_f32(), -1.5);
+        assert_eq!(Fixed::<u8, 4>::from_bits(0x28).to_string(), "2.5");
+        assert_eq!(core::mem::size_of::<Q16>(), 4);
+    }
 }
@@ -0,0 +1,98 @@
+//! SPIR-V compute kernels written in Zig (`#[autozig(gpu)]`)
+//!
+//! Instead of a wrapper, each `gpu` function becomes a [`ComputeKernel`]
+//! constant named after it in upper case: the SPIR-V module compiled by the
+//! build script plus what a pipeline needs to run it. Every argument is a
+//! storage buffer in descriptor set 0, bound in argument order.
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     const gpu = @import("std").gpu;
+//!     extern var x: [1024]f32 addrspace(.storage_buffer);
+//!     extern var y: [1024]f32 addrspace(.storage_buffer);
+//!
+//!     export fn saxpy() callconv(.kernel) void {
+//!         const i = gpu.global_invocation_id[0];
+//!         y[i] += 2.0 * x[i];
+//!     }
+//!     ---
+//!     #[autozig(gpu, workgroup_size = 256)]
+//!     fn saxpy(x: &[f32], y: &mut [f32]);
+//! }
+//!
+//! let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+//!     label: Some(SAXPY.entry_point),
+//!     source: wgpu::util::make_spirv(SAXPY.spirv),
+//! });
+//! // ... one storage buffer per entry of SAXPY.bindings, then
+//! pass.dispatch_workgroups(SAXPY.workgroups(1024), 1, 1);
+//! ```
+
+/// SPIR-V module of a Zig compute kernel with its descriptor layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeKernel {
+    /// Name of the entry point in the module
+    pub entry_point: &'static str,
+    /// The SPIR-V module, little-endian words
+    pub spirv: &'static [u8],
+    /// Invocations per workgroup (`LocalSize`)
+    pub workgroup_size: [u32; 3],
+    /// Storage buffers, in argument order
+    pub bindings: &'static [StorageBinding],
+}
+
+/// Storage buffer of a [`ComputeKernel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageBinding {
+    /// Argument name, also the name of the Zig global
+    pub name: &'static str,
+    /// Descriptor set
+    pub group: u32,
+    /// Binding within the descriptor set
+    pub binding: u32,
+    /// Declared as `&[T]` rather than `&mut [T]`
+    pub read_only: bool,
+    /// Size in bytes of one element
+    pub element_size: usize,
+}
+
+impl ComputeKernel {
+    /// Workgroups to dispatch along x to cover `invocations`
+    pub const fn workgroups(&self, invocations: u32) -> u32 {
+        invocations.div_ceil(self.workgroup_size[0])
+    }
+
+    /// The module as 32-bit words, for APIs such as ash that take `&[u32]`
+    pub fn spirv_words(&self) -> alloc::vec::Vec<u32> {
+        self.spirv
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_kernel() {
+        const SPIRV: [u8; 8] = [0x03, 0x02, 0x23, 0x07, 0x00, 0x05, 0x01, 0x00];
+        let kernel = ComputeKernel {
+            entry_point: "saxpy",
+            spirv: &SPIRV,
+            workgroup_size: [64, 1, 1],
+            bindings: &[StorageBinding {
+                name: "x",
+                group: 0,
+                binding: 0,
+                read_only: true,
+                element_size: 4,
+            }],
+        };
+        assert_eq!(kernel.workgroups(1), 1);
+        assert_eq!(kernel.workgroups(128), 2);
+        assert_eq!(kernel.workgroups(129), 3);
+        assert_eq!(kernel.spirv_words(), [0x0723_0203, 0x0001_0500]);
+    }
+}
@@ -0,0 +1,190 @@
+#![allow(unsafe_code)]
+//! Pixel buffers passed to Zig image filters
+//!
+//! An [`ImageBuffer`] borrows the pixels of an image with its size and
+//! [`PixelFormat`], so a filter no longer takes a raw pointer and length and
+//! assumes RGBA. It is passed by value and mirrors the `AutoZigImage` extern
+//! struct injected into the generated Zig code, whose helpers check bounds
+//! and convert between formats:
+//!
+//! ```rust,ignore
+//! use autozig::image::ImageBuffer;
+//!
+//! autozig! {
+//!     export fn sepia(image: AutoZigImage) void {
+//!         for (0..image.height) |y| {
+//!             for (0..image.width) |x| {
+//!                 const p = image.getRgba(x, y);
+//!                 const l = image.luma(x, y);
+//!                 image.setRgba(x, y, .{ l +| 40, l +| 20, l, p[3] });
+//!             }
+//!         }
+//!     }
+//!     ---
+//!     fn sepia(image: ImageBuffer);
+//! }
+//!
+//! // Canvas `ImageData` is RGBA
+//! let mut data = image_data.data().0;
+//! sepia(ImageBuffer::rgba(&mut data, image_data.width(), image_data.height()));
+//! ```
+
+use core::marker::PhantomData;
+
+/// Byte layout of the pixels of an [`ImageBuffer`], one byte per channel
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Red, green, blue, alpha (canvas `ImageData`, PNG)
+    Rgba8 = 0,
+    /// Blue, green, red, alpha (Windows bitmaps, many video frames)
+    Bgra8 = 1,
+    /// Red, green, blue
+    Rgb8 = 2,
+    /// Luma only
+    Gray8 = 3,
+}
+
+impl PixelFormat {
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+
+    pub const fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::Rgba8 | PixelFormat::Bgra8)
+    }
+}
+
+/// Pixels of an image borrowed for a Zig call, rows packed top to bottom
+#[repr(C)]
+pub struct ImageBuffer<'a> {
+    ptr: *mut u8,
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> ImageBuffer<'a> {
+    /// Borrow `data` as a `width` x `height` image, or `None` unless it holds
+    /// exactly that many pixels of `format`
+    pub fn try_new(
+        data: &'a mut [u8],
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+    ) -> Option<Self> {
+        let len = (width as usize)
+            .checked_mul(height as usize)?
+            .checked_mul(format.bytes_per_pixel())?;
+        if data.len() != len {
+            return None;
+        }
+        Some(Self {
+            ptr: data.as_mut_ptr(),
+            width,
+            height,
+            format,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Borrow `data` as a `width` x `height` image
+    ///
+    /// # Panics
+    ///
+    /// Unless `data` holds exactly `width * height` pixels of `format`.
+    pub fn new(data: &'a mut [u8], width: u32, height: u32, format: PixelFormat) -> Self {
+        let len = data.len();
+        Self::try_new(data, width, height, format)
+            .unwrap_or_else(|| panic!("{len} bytes are not a {width}x{height} {format:?} image"))
+    }
+
+    /// Borrow RGBA pixels, the layout of canvas `ImageData`
+    ///
+    /// # Panics
+    ///
+    /// Unless `data` holds exactly `width * height * 4` bytes.
+    pub fn rgba(data: &'a mut [u8], width: u32, height: u32) -> Self {
+        Self::new(data, width, height, PixelFormat::Rgba8)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `try_new` borrowed exactly this many bytes
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len()) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `try_new` borrowed exactly this many bytes
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len()) }
+    }
+
+    /// Bytes of the pixel at column `x`, row `y`, or `None` out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let size = self.format.bytes_per_pixel();
+        let start = (y as usize * self.width as usize + x as usize) * size;
+        Some(&self.as_bytes()[start..start + size])
+    }
+
+    fn len(&self) -> usize {
+        self.width as usize * self.height as usize * self.format.bytes_per_pixel()
+    }
+}
+
+impl core::fmt::Debug for ImageBuffer<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ImageBuffer")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+// SAFETY: an exclusive borrow of `[u8]`
+unsafe impl Send for ImageBuffer<'_> {}
+unsafe impl Sync for ImageBuffer<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_image_buffer() {
+        let mut data: Vec<u8> = (0..24).collect();
+        let image = ImageBuffer::rgba(&mut data, 3, 2);
+        assert_eq!(image.pixel(1, 1), Some(&[16u8, 17, 18, 19][..]));
+        assert_eq!(image.pixel(3, 0), None);
+
+        let mut image = ImageBuffer::new(&mut data, 4, 2, PixelFormat::Rgb8);
+        image.as_bytes_mut()[3] = 99;
+        assert_eq!(image.pixel(1, 0), Some(&[99u8, 4, 5][..]));
+        assert_eq!(data[3], 99);
+
+        assert!(ImageBuffer::try_new(&mut data, 5, 5, PixelFormat::Gray8).is_none());
+        assert!(ImageBuffer::try_new(&mut data, u32::MAX, u32::MAX, PixelFormat::Rgba8).is_none());
+        assert_eq!(PixelFormat::Bgra8.bytes_per_pixel(), 4);
+        assert!(!PixelFormat::Gray8.has_alpha());
+    }
+}
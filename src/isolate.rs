@@ -0,0 +1,212 @@
+#![allow(unsafe_code)]
+//! Crash isolation for untrusted or crash-prone Zig kernels
+//!
+//! Functions declared with `#[autozig(isolated)]` run in a forked child
+//! process. The result travels back through a pipe; if the child dies from a
+//! signal (segfault, Zig `@panic`, `unreachable` in safe builds) or exits
+//! abnormally, the wrapper returns `Err(Crashed)` and the host keeps running:
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     // ...
+//!     ---
+//!     #[autozig(isolated)]
+//!     fn decode_header(data: &[u8]) -> u32;
+//! }
+//!
+//! match decode_header(untrusted) {
+//!     Ok(value) => println!("{value}"),
+//!     Err(crash) => eprintln!("decoder crashed: {crash}"),
+//! }
+//! ```
+//!
+//! The child sees a copy of the parent's memory, so borrowed inputs are
+//! readable but writes through `&mut` cannot reach the caller; isolated
+//! functions take inputs by value or shared reference and return `Copy`
+//! data without references or pointers. Each call costs a `fork()`. Only the
+//! forking thread exists in the child, so the Zig code must not wait on
+//! locks held by other threads; the wrapper runs its `pointer-log`,
+//! `trace-ffi` and `metrics` hooks in the parent for the same reason.
+//!
+//! On non-Unix targets the call runs in-process.
+
+use std::fmt;
+
+/// Why an isolated call produced no result
+#[derive(Debug)]
+pub enum Crashed {
+    /// The child was killed by this signal
+    Signal(i32),
+    /// The child exited with this non-zero status (101 for a Rust panic)
+    Exit(i32),
+    /// The child process could not be started
+    Spawn(std::io::Error),
+}
+
+impl fmt::Display for Crashed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crashed::Signal(signal) => write!(f, "Zig call killed by signal {signal}"),
+            Crashed::Exit(code) => write!(f, "Zig call exited with status {code}"),
+            Crashed::Spawn(err) => write!(f, "failed to start isolated Zig call: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Crashed {}
+
+/// Run `f` in a forked child process and return its result
+///
+/// Called by the wrappers of `#[autozig(isolated)]` functions.
+///
+/// # Safety
+///
+/// The bytes of the child's `T` are copied into the parent, so `T` must not
+/// hold references, pointers or handles to memory the child may have
+/// allocated. `f` runs with only the calling thread, so it must not take
+/// locks that other threads of the parent may hold, e.g. those of the
+/// autozig hooks.
+#[cfg(unix)]
+pub unsafe fn run<T: Copy, F: FnOnce() -> T>(f: F) -> Result<T, Crashed> {
+    use std::mem::MaybeUninit;
+
+    /// Leaves the child with status 101 if `f` unwinds, so the unwind never
+    /// reaches the parent's frames
+    struct ExitOnUnwind;
+
+    impl Drop for ExitOnUnwind {
+        fn drop(&mut self) {
+            unsafe { libc::_exit(101) }
+        }
+    }
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(Crashed::Spawn(std::io::Error::last_os_error()));
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+
+    match unsafe { libc::fork() } {
+        -1 => {
+            let err = std::io::Error::last_os_error();
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+            }
+            Err(Crashed::Spawn(err))
+        },
+        0 => {
+            // Child: compute, send the bytes, and leave through `_exit` only,
+            // without allocating or running destructors or atexit handlers
+            // of the parent's state
+            unsafe { libc::close(read_fd) };
+            let guard = ExitOnUnwind;
+            let value = f();
+            std::mem::forget(guard);
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    (&value as *const T).cast::<u8>(),
+                    std::mem::size_of::<T>(),
+                )
+            };
+            let status = if write_all(write_fd, bytes) { 0 } else { 1 };
+            unsafe { libc::_exit(status) }
+        },
+        pid => {
+            unsafe { libc::close(write_fd) };
+            // Read before waiting, so a result larger than the pipe buffer
+            // cannot block the child
+            let mut value = MaybeUninit::<T>::uninit();
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(
+                    value.as_mut_ptr().cast::<u8>(),
+                    std::mem::size_of::<T>(),
+                )
+            };
+            let received = read_all(read_fd, bytes);
+            unsafe { libc::close(read_fd) };
+
+            let mut status = 0;
+            while unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(Crashed::Spawn(err));
+                }
+            }
+            if libc::WIFSIGNALED(status) {
+                return Err(Crashed::Signal(libc::WTERMSIG(status)));
+            }
+            match libc::WEXITSTATUS(status) {
+                0 if received => Ok(unsafe { value.assume_init() }),
+                0 => Err(Crashed::Exit(1)),
+                code => Err(Crashed::Exit(code)),
+            }
+        },
+    }
+}
+
+/// Run `f` in-process; crash isolation needs `fork()`
+///
+/// # Safety
+///
+/// None needed in-process; `unsafe` to match the Unix version.
+#[cfg(not(unix))]
+pub unsafe fn run<T: Copy, F: FnOnce() -> T>(f: F) -> Result<T, Crashed> {
+    Ok(f())
+}
+
+#[cfg(unix)]
+fn write_all(fd: libc::c_int, mut bytes: &[u8]) -> bool {
+    while !bytes.is_empty() {
+        let written = unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+        if written <= 0 {
+            if written == -1
+                && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            return false;
+        }
+        bytes = &bytes[written as usize..];
+    }
+    true
+}
+
+#[cfg(unix)]
+fn read_all(fd: libc::c_int, mut bytes: &mut [u8]) -> bool {
+    while !bytes.is_empty() {
+        let read = unsafe { libc::read(fd, bytes.as_mut_ptr().cast(), bytes.len()) };
+        if read <= 0 {
+            if read == -1
+                && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            return false;
+        }
+        bytes = &mut bytes[read as usize..];
+    }
+    true
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_isolated() {
+        let data = [1u64, 2, 3];
+        // SAFETY: the results are plain data and the closures take no locks
+        unsafe {
+            assert_eq!(run(|| data.iter().sum::<u64>()).unwrap(), 6);
+            assert_eq!(run(|| [7u8; 100_000]).unwrap()[99_999], 7);
+
+            match run(|| -> u32 { std::process::abort() }) {
+                Err(Crashed::Signal(signal)) => assert_eq!(signal, libc::SIGABRT),
+                other => panic!("expected a signal, got {other:?}"),
+            }
+            assert!(matches!(run(|| libc::_exit(3)), Err::<(), _>(Crashed::Exit(3))));
+            assert!(matches!(run(|| -> u8 { panic!("in the child") }), Err(Crashed::Exit(101))));
+        }
+    }
+}
@@ -43,29 +43,201 @@
 // Note: We cannot use #![forbid(unsafe_code)] because the zero_copy module
 // requires unsafe for FFI and raw pointer manipulation.
 #![warn(unsafe_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 /// Re-export the procedural macros
 pub use autozig_macro::autozig;
 pub use autozig_macro::{
     autozig_export,
+    bind_zig,
     include_zig,
+    zig_tests,
 };
+/// Benchmark framework driving `autozig::bench!`
+#[cfg(feature = "bench")]
+pub use criterion;
+/// RustCrypto `digest` traits implemented by generated hash adapters
+#[cfg(feature = "digest")]
+pub use digest;
+/// Half-precision float type matching Zig's `f16`
+#[cfg(feature = "half")]
+pub use half;
 
 /// Stream support for async Zig FFI
 #[cfg(feature = "stream")]
 pub mod stream;
 
 /// Zero-copy buffer passing between Zig and Rust (Phase 4.2)
+#[cfg(feature = "std")]
 pub mod zero_copy;
 
+/// Passing chunks of a shared buffer to Zig on threaded WASM
+pub mod wasm_threads;
+
 /// Safe memory bridging types (ffi protocol)
 pub mod ffi_types;
+pub use ffi_types::ZigPod;
+
+/// Debug log of pointers passed to and returned by Zig (`pointer-log`
+/// feature)
+pub mod pointer_log;
+
+/// Debug-build checks at the FFI boundary (`ffi-contracts` feature)
+pub mod contracts;
+
+/// Tracing spans around Zig calls (`trace-ffi` feature)
+pub mod trace_ffi;
+
+/// Per-function call counts and latencies of Zig calls (`metrics` feature)
+pub mod metrics;
+
+/// Zig stack traces of panics and errors (`Builder::zig_stack_traces`)
+#[cfg(feature = "std")]
+pub mod zig_trace;
+
+/// Dedicated thread pool for the blocking calls of async wrappers
+#[cfg(feature = "std")]
+pub mod runtime;
+
+/// Time limits on async Zig calls (`#[autozig(timeout_ms = N)]`)
+#[cfg(feature = "std")]
+pub mod timeout;
+#[cfg(feature = "std")]
+pub use timeout::AutoZigTimeout;
+
+/// Lock-free ring buffer of audio samples shared with Zig
+#[cfg(target_has_atomic = "ptr")]
+pub mod audio;
+
+/// Pixel buffers with a size and format for Zig image filters
+pub mod image;
+
+/// SPIR-V compute kernels of `#[autozig(gpu)]` functions
+pub mod gpu;
+
+/// Canonical ABI support for WASM component exports (`component` feature)
+#[cfg(feature = "component")]
+pub mod component;
+
+/// Registry of the pyo3 bindings of `#[autozig(python)]` items (`python`
+/// feature)
+#[cfg(feature = "python")]
+pub mod python;
+
+/// Zig allocations through Rust's global allocator (`g_allocator`)
+pub mod allocator;
+
+/// Zig log records routed to `log` or `tracing` (`log`/`tracing` features)
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod logging;
+
+/// Running crash-prone Zig calls in a forked process (`isolation` feature)
+#[cfg(feature = "isolation")]
+pub mod isolate;
+
+/// Zig shared libraries loaded at runtime, with hot reload (`plugin`
+/// feature)
+#[cfg(feature = "plugin")]
+pub mod plugin;
+
+/// Criterion benchmarks of Zig kernels against Rust (`bench` feature)
+#[cfg(feature = "bench")]
+pub mod bench;
+
+/// Zig test harness support and deterministic allocator/RNG controls for tests
+#[cfg(feature = "std")]
+pub mod testing;
+
+/// Embed the license/provenance manifest of linked Zig/C sources as a
+/// `&'static str` (JSON)
+///
+/// Requires `autozig_build::Builder::embed_manifest(true)` in build.rs.
+///
+/// ```rust,ignore
+/// static FOREIGN_SOURCES: &str = autozig::source_manifest!();
+/// ```
+#[macro_export]
+macro_rules! source_manifest {
+    () => {
+        include_str!(env!("AUTOZIG_SOURCE_MANIFEST"))
+    };
+}
+
+/// Generate a `#[test]` comparing the crate's ABI with the committed
+/// snapshot for the current target
+///
+/// Requires `autozig_build::Builder::abi_snapshot(true)` in build.rs, which
+/// writes `tests/abi_snapshots/<target>.txt` on the first build. Rebuild with
+/// `AUTOZIG_UPDATE_ABI_SNAPSHOTS=1` to accept an intended change.
+///
+/// ```rust,ignore
+/// #[cfg(test)]
+/// mod abi {
+///     autozig::abi_snapshot_test!();
+/// }
+/// ```
+#[macro_export]
+macro_rules! abi_snapshot_test {
+    () => {
+        #[test]
+        fn abi_snapshot() {
+            $crate::testing::assert_abi_snapshot(
+                env!("AUTOZIG_ABI_GOLDEN"),
+                include_str!(env!("AUTOZIG_ABI_SNAPSHOT")),
+            );
+        }
+    };
+}
+
+/// How the crate's Zig code was compiled, returned by
+/// [`build_info!`](crate::build_info)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Output of `zig version`, or `unknown`
+    pub zig_version: &'static str,
+    /// Rust target triple
+    pub target: &'static str,
+    /// Zig optimization mode (`Debug`, `ReleaseFast`, ...)
+    pub opt_level: &'static str,
+    /// The full record as JSON, adding the autozig version, Zig target,
+    /// code generation flags, compilation mode and library kind
+    pub json: &'static str,
+}
+
+impl core::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Zig {} for {} ({})", self.zig_version, self.target, self.opt_level)
+    }
+}
+
+/// Metadata of the crate's Zig build, for logs and bug reports
+///
+/// The build script sets it up whenever it runs the autozig engine.
+///
+/// ```rust,ignore
+/// log::info!("{}", autozig::build_info!()); // Zig 0.15.2 for x86_64-unknown-linux-gnu (ReleaseFast)
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo {
+            zig_version: env!("AUTOZIG_ZIG_VERSION"),
+            target: env!("AUTOZIG_TARGET"),
+            opt_level: env!("AUTOZIG_OPT_LEVEL"),
+            json: include_str!(env!("AUTOZIG_BUILD_INFO")),
+        }
+    };
+}
 
 /// Common imports for using AutoZig
 pub mod prelude {
     pub use crate::{
         autozig,
+        bind_zig,
         include_zig,
+        ZigPod,
     };
 }
 
@@ -0,0 +1,132 @@
+#![allow(unsafe_code)]
+//! Zig log records in the host's `log`/`tracing` stack
+//!
+//! Zig code calling `az_log` gets the extern declaration injected by the
+//! engine, and every build writes `autozig_log.zig` next to the generated
+//! sources, with formatting wrappers and a `std.log` backend:
+//!
+//! ```zig
+//! const zlog = @import("autozig_log.zig");
+//!
+//! export fn decode(p: [*]const u8, n: usize) usize {
+//!     zlog.debug("decoding {d} bytes", .{n});
+//!     // ...
+//! }
+//! ```
+//!
+//! This module implements `az_log`: records become `log` records (`log`
+//! feature) or `tracing` events (`tracing` feature, preferred when both are
+//! enabled) with the target `zig`, so filters like `RUST_LOG=zig=debug`
+//! apply to them. Zig code logging in a crate with neither feature fails at
+//! link time.
+
+/// Level of a record; Zig passes 1 (error) to 5 (trace), anything else is
+/// trace
+#[derive(Clone, Copy)]
+enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn from_zig(level: u8) -> Self {
+        match level {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+}
+
+/// Target of the records and events of Zig code
+pub const TARGET: &str = "zig";
+
+/// Hook called by Zig for every record; invalid UTF-8 is replaced
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn az_log(level: u8, ptr: *const u8, len: usize) {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    };
+    emit(Level::from_zig(level), &String::from_utf8_lossy(bytes));
+}
+
+#[cfg(feature = "tracing")]
+fn emit(level: Level, message: &str) {
+    match level {
+        Level::Error => tracing::event!(target: TARGET, tracing::Level::ERROR, "{message}"),
+        Level::Warn => tracing::event!(target: TARGET, tracing::Level::WARN, "{message}"),
+        Level::Info => tracing::event!(target: TARGET, tracing::Level::INFO, "{message}"),
+        Level::Debug => tracing::event!(target: TARGET, tracing::Level::DEBUG, "{message}"),
+        Level::Trace => tracing::event!(target: TARGET, tracing::Level::TRACE, "{message}"),
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+fn emit(level: Level, message: &str) {
+    let level = match level {
+        Level::Error => log::Level::Error,
+        Level::Warn => log::Level::Warn,
+        Level::Info => log::Level::Info,
+        Level::Debug => log::Level::Debug,
+        Level::Trace => log::Level::Trace,
+    };
+    log::log!(target: TARGET, level, "{message}");
+}
+
+#[cfg(all(test, feature = "log", not(feature = "tracing")))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    static RECORDS: Mutex<Vec<(log::Level, String, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            RECORDS.lock().unwrap().push((
+                record.level(),
+                record.target().to_string(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_az_log_routes_to_log() {
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+        let message = b"decoded 3 frames";
+        unsafe {
+            az_log(2, message.as_ptr(), message.len());
+            az_log(9, [0xff].as_ptr(), 1);
+            az_log(1, std::ptr::null(), 0);
+        }
+        assert_eq!(
+            *RECORDS.lock().unwrap(),
+            [
+                (log::Level::Warn, "zig".to_string(), "decoded 3 frames".to_string()),
+                (log::Level::Trace, "zig".to_string(), "\u{fffd}".to_string()),
+                (log::Level::Error, "zig".to_string(), String::new()),
+            ]
+        );
+    }
+}
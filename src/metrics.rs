@@ -0,0 +1,147 @@
+//! Call counts and latencies of Zig functions through the `metrics` facade
+//!
+//! With the `metrics` feature enabled, every generated wrapper times its FFI
+//! call and reports it to the installed `metrics` recorder (Prometheus,
+//! StatsD, ...), labelled with `function`, the Rust name of the Zig
+//! function:
+//!
+//! - [`CALLS`], a counter incremented once per call;
+//! - [`DURATION`], a histogram of call durations in seconds.
+//!
+//! Async wrappers time the call on the blocking thread, without the wait for
+//! a worker. Without the feature the timers are empty structs and compile to
+//! nothing.
+
+/// Counter of calls into Zig
+pub const CALLS: &str = "autozig_ffi_calls_total";
+
+/// Histogram of the durations of calls into Zig, in seconds
+pub const DURATION: &str = "autozig_ffi_call_duration_seconds";
+
+/// Timer of one FFI call, reporting it when dropped
+#[must_use]
+pub struct CallTimer {
+    #[cfg(feature = "metrics")]
+    function: &'static str,
+    #[cfg(feature = "metrics")]
+    start: std::time::Instant,
+}
+
+/// Start timing a call of `function` (called by generated wrappers)
+#[inline(always)]
+pub fn start(function: &'static str) -> CallTimer {
+    #[cfg(feature = "metrics")]
+    return CallTimer {
+        function,
+        start: std::time::Instant::now(),
+    };
+    #[cfg(not(feature = "metrics"))]
+    {
+        let _ = function;
+        CallTimer {}
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for CallTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        ::metrics::counter!(CALLS, "function" => self.function).increment(1);
+        ::metrics::histogram!(DURATION, "function" => self.function).record(elapsed);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    use ::metrics::{
+        Counter,
+        CounterFn,
+        Gauge,
+        Histogram,
+        HistogramFn,
+        Key,
+        KeyName,
+        Metadata,
+        Recorder,
+        SharedString,
+        Unit,
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorded {
+        calls: Mutex<Vec<(String, u64)>>,
+        durations: Mutex<Vec<(String, f64)>>,
+    }
+
+    struct Handle(Key, Arc<Recorded>);
+
+    impl Handle {
+        fn label(&self) -> String {
+            self.0
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect()
+        }
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            self.1.calls.lock().unwrap().push((self.label(), value));
+        }
+
+        fn absolute(&self, _: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            self.1.durations.lock().unwrap().push((self.label(), value));
+        }
+    }
+
+    #[derive(Default)]
+    struct Capture(Arc<Recorded>);
+
+    impl Recorder for Capture {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            assert_eq!(key.name(), CALLS);
+            Counter::from_arc(Arc::new(Handle(key.clone(), self.0.clone())))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            assert_eq!(key.name(), DURATION);
+            Histogram::from_arc(Arc::new(Handle(key.clone(), self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn test_call_timer() {
+        let recorder = Capture::default();
+        ::metrics::with_local_recorder(&recorder, || {
+            let _timer = start("checksum");
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        });
+        let recorded = &recorder.0;
+        assert_eq!(*recorded.calls.lock().unwrap(), [("function=checksum".to_string(), 1)]);
+        let durations = recorded.durations.lock().unwrap();
+        assert_eq!(durations.len(), 1);
+        assert_eq!(durations[0].0, "function=checksum");
+        assert!(durations[0].1 >= 0.002);
+    }
+}
@@ -0,0 +1,346 @@
+#![allow(unsafe_code)]
+//! Zig shared libraries loaded at runtime, with hot reload
+//!
+//! A plugin crate builds its Zig code with
+//! `Builder::artifact(ArtifactKind::Cdylib)`. The host declares the exports it
+//! calls with [`plugin_api!`](crate::plugin_api), which generates a table of
+//! function pointers and safe methods calling them, and loads the library
+//! into a [`PluginHost`]:
+//!
+//! ```rust,ignore
+//! autozig::plugin_api! {
+//!     pub struct GameLogic {
+//!         fn update(dt: f32) -> u32;
+//!         fn score() -> u64;
+//!     }
+//! }
+//!
+//! let mut logic = PluginHost::<GameLogic>::load("target/debug/libgame_logic.so")?;
+//! loop {
+//!     logic.reload_if_changed()?;
+//!     logic.update(1.0 / 60.0);
+//! }
+//! ```
+//!
+//! In debug builds, [`PluginHost::reload_if_changed`] loads the library again
+//! when its file changed, so Zig logic can be tweaked without restarting the
+//! host. Each load works on a private copy of the file, which a rebuild can
+//! overwrite safely. A failed reload leaves the previous library in place.
+//!
+//! Zig state lives in the library, so it starts over on every reload; keep
+//! what must survive on the Rust side.
+
+use std::{
+    env,
+    fmt,
+    fs,
+    ops::Deref,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+    time::SystemTime,
+};
+
+use libloading::Library;
+
+/// A set of Zig exports resolved from a shared library, usually generated by
+/// [`plugin_api!`](crate::plugin_api)
+///
+/// # Safety
+///
+/// `resolve` must only produce function pointers whose types match the
+/// signatures the library exports.
+pub unsafe trait ZigApi: Sized {
+    /// Names of the exports the API calls
+    const SYMBOLS: &'static [&'static str];
+
+    /// Resolve every export from `symbols`
+    fn resolve(symbols: &Symbols<'_>) -> Result<Self, PluginError>;
+}
+
+/// Exports of a loaded library, for [`ZigApi::resolve`]
+pub struct Symbols<'lib> {
+    library: &'lib Library,
+    path: &'lib Path,
+}
+
+impl Symbols<'_> {
+    /// The export `name` as the function pointer type `F`
+    ///
+    /// # Safety
+    ///
+    /// `F` must be an `extern "C" fn` type matching the export.
+    pub unsafe fn get<F: Copy>(&self, name: &str) -> Result<F, PluginError> {
+        let mut symbol = name.as_bytes().to_vec();
+        symbol.push(0);
+        self.library
+            .get::<F>(&symbol)
+            .map(|function| *function)
+            .map_err(|_| PluginError::MissingSymbols {
+                path: self.path.to_path_buf(),
+                symbols: vec![name.to_string()],
+            })
+    }
+}
+
+/// Why a plugin could not be loaded
+#[derive(Debug)]
+pub enum PluginError {
+    /// The library could not be copied or loaded
+    Load { path: PathBuf, message: String },
+    /// The library does not export these symbols of the API
+    MissingSymbols { path: PathBuf, symbols: Vec<String> },
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load { path, message } => {
+                write!(f, "failed to load Zig plugin {}: {message}", path.display())
+            },
+            PluginError::MissingSymbols { path, symbols } => {
+                write!(f, "Zig plugin {} does not export {}", path.display(), symbols.join(", "))
+            },
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Distinguishes the copies of libraries loaded by this process
+static COPIES: AtomicU64 = AtomicU64::new(0);
+
+/// A Zig shared library and the API `T` resolved from it
+///
+/// Dereferences to `T`, so the API's methods are called on the host. They
+/// borrow the host, which keeps a reload from unloading code in use.
+pub struct PluginHost<T: ZigApi> {
+    path: PathBuf,
+    api: T,
+    library: Option<Library>,
+    /// Private copy the library was loaded from
+    copy: PathBuf,
+    modified: Option<SystemTime>,
+    generation: u64,
+}
+
+impl<T: ZigApi> PluginHost<T> {
+    /// Load the library at `path` and resolve `T` from it
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, PluginError> {
+        let path = path.into();
+        let modified = modified(&path);
+        let (library, copy, api) = open::<T>(&path)?;
+        Ok(Self {
+            path,
+            api,
+            library: Some(library),
+            copy,
+            modified,
+            generation: 0,
+        })
+    }
+
+    /// The resolved API
+    pub fn api(&self) -> &T {
+        &self.api
+    }
+
+    /// Path of the library
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of successful reloads
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Load the library again and resolve `T` from the new code
+    ///
+    /// On error the previous library stays loaded.
+    pub fn reload(&mut self) -> Result<(), PluginError> {
+        let modified = modified(&self.path);
+        let (library, copy, api) = open::<T>(&self.path)?;
+        // The old function pointers go before the code they point to
+        self.api = api;
+        drop(self.library.replace(library));
+        let _ = fs::remove_file(std::mem::replace(&mut self.copy, copy));
+        self.modified = modified;
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// In debug builds, [`reload`](Self::reload) if the library changed
+    /// since it was loaded, returning whether it did; release builds never
+    /// reload
+    ///
+    /// A library still being written fails to load; the next call tries
+    /// again.
+    pub fn reload_if_changed(&mut self) -> Result<bool, PluginError> {
+        if !cfg!(debug_assertions) || modified(&self.path) == self.modified {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+}
+
+impl<T: ZigApi> Deref for PluginHost<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.api
+    }
+}
+
+impl<T: ZigApi> Drop for PluginHost<T> {
+    fn drop(&mut self) {
+        drop(self.library.take());
+        let _ = fs::remove_file(&self.copy);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Load a private copy of the library at `path` and resolve `T` from it
+fn open<T: ZigApi>(path: &Path) -> Result<(Library, PathBuf, T), PluginError> {
+    let load_error = |message: String| PluginError::Load { path: path.to_path_buf(), message };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| load_error("not a file".to_string()))?
+        .to_string_lossy();
+    let copy = env::temp_dir().join(format!(
+        "autozig-plugin-{}-{}-{file_name}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::copy(path, &copy).map_err(|err| load_error(err.to_string()))?;
+
+    let loaded = unsafe { Library::new(&copy) }
+        .map_err(|err| load_error(err.to_string()))
+        .and_then(|library| {
+            let symbols = Symbols { library: &library, path };
+            let missing: Vec<String> = T::SYMBOLS
+                .iter()
+                .filter(|name| unsafe { symbols.get::<unsafe extern "C" fn()>(name) }.is_err())
+                .map(|name| name.to_string())
+                .collect();
+            if !missing.is_empty() {
+                return Err(PluginError::MissingSymbols {
+                    path: path.to_path_buf(),
+                    symbols: missing,
+                });
+            }
+            let api = T::resolve(&symbols)?;
+            Ok((library, api))
+        });
+    match loaded {
+        Ok((library, api)) => Ok((library, copy, api)),
+        Err(err) => {
+            let _ = fs::remove_file(&copy);
+            Err(err)
+        },
+    }
+}
+
+/// Declare the exports of a Zig plugin as a [`ZigApi`]
+///
+/// Generates a struct of function pointers, resolved by name, and a method
+/// calling each of them. Parameters and return values must be FFI-safe.
+/// See the [module documentation](crate::plugin) for an example.
+#[macro_export]
+macro_rules! plugin_api {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$fn_attr:meta])*
+                fn $fn_name:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($fn_name: unsafe extern "C" fn($($arg_ty),*) $(-> $ret)?,)*
+        }
+
+        unsafe impl $crate::plugin::ZigApi for $name {
+            const SYMBOLS: &'static [&'static str] = &[$(stringify!($fn_name)),*];
+
+            fn resolve(
+                symbols: &$crate::plugin::Symbols<'_>,
+            ) -> ::core::result::Result<Self, $crate::plugin::PluginError> {
+                ::core::result::Result::Ok(Self {
+                    $($fn_name: unsafe { symbols.get(stringify!($fn_name))? },)*
+                })
+            }
+        }
+
+        impl $name {
+            $(
+                $(#[$fn_attr])*
+                $vis fn $fn_name(&self, $($arg: $arg_ty),*) $(-> $ret)? {
+                    unsafe { (self.$fn_name)($($arg),*) }
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::plugin_api! {
+        struct Libm {
+            fn cos(x: f64) -> f64;
+            fn pow(x: f64, y: f64) -> f64;
+        }
+    }
+
+    mod missing {
+        #![allow(dead_code)]
+
+        crate::plugin_api! {
+            pub struct Missing {
+                fn cos(x: f64) -> f64;
+                fn autozig_no_such_export();
+            }
+        }
+    }
+    use missing::Missing;
+
+    #[test]
+    fn test_plugin_host_loads_and_reloads() {
+        // Any shared library will do; the C math library is everywhere
+        let Some(libm) =
+            ["/lib/x86_64-linux-gnu/libm.so.6", "/usr/lib/libm.so.6", "/lib64/libm.so.6"]
+                .iter()
+                .map(Path::new)
+                .find(|path| path.exists())
+        else {
+            return;
+        };
+
+        let mut host = PluginHost::<Libm>::load(libm).unwrap();
+        assert_eq!(host.cos(0.0), 1.0);
+        assert_eq!(host.pow(2.0, 10.0), 1024.0);
+        assert!(!host.reload_if_changed().unwrap());
+        host.reload().unwrap();
+        assert_eq!(host.generation(), 1);
+        assert_eq!(host.api().pow(3.0, 2.0), 9.0);
+
+        let Err(PluginError::MissingSymbols { symbols, .. }) = PluginHost::<Missing>::load(libm)
+        else {
+            panic!("expected a missing symbol");
+        };
+        assert_eq!(symbols, ["autozig_no_such_export"]);
+    }
+}
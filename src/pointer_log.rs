@@ -0,0 +1,230 @@
+//! Log of pointers crossing into and out of Zig, for debugging crashes
+//!
+//! With the `pointer-log` feature enabled, every generated wrapper records
+//! the pointer and length of each borrowed argument it passes to Zig and
+//! each raw pointer Zig returns, tagged with the function name and the time
+//! since the first record. The last [`CAPACITY`] records are kept in a ring
+//! buffer, so a crash inside Zig can be traced back to the calls that handed
+//! it the offending memory:
+//!
+//! ```rust,ignore
+//! autozig::pointer_log::install_panic_hook();
+//! // ... or from a custom crash handler:
+//! eprintln!("{}", autozig::pointer_log::dump());
+//! ```
+//!
+//! Without the feature the recording functions compile to nothing and the
+//! log stays empty.
+
+use alloc::{
+    string::{
+        String,
+        ToString,
+    },
+    vec::Vec,
+};
+use core::{
+    fmt,
+    time::Duration,
+};
+
+/// Number of records kept; older ones are overwritten
+pub const CAPACITY: usize = 4096;
+
+/// Whether a pointer was passed to Zig or returned by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Argument passed to Zig
+    Arg,
+    /// Pointer returned by Zig
+    Return,
+}
+
+/// One pointer crossing the FFI boundary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crossing {
+    /// Time since the first record
+    pub timestamp: Duration,
+    /// Rust name of the Zig function
+    pub function: &'static str,
+    /// Parameter name (`"return"` for returned pointers)
+    pub name: &'static str,
+    pub direction: Direction,
+    /// Address of the first element
+    pub addr: usize,
+    /// Number of elements (1 for raw pointers)
+    pub len: usize,
+    /// Size of one element in bytes
+    pub elem_size: usize,
+}
+
+impl fmt::Display for Crossing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            Direction::Arg => "->",
+            Direction::Return => "<-",
+        };
+        write!(
+            f,
+            "[{:>10.6}s] {} {}({}) {:#x}..{:#x} ({} x {} bytes)",
+            self.timestamp.as_secs_f64(),
+            arrow,
+            self.function,
+            self.name,
+            self.addr,
+            self.addr
+                .wrapping_add(self.len.wrapping_mul(self.elem_size)),
+            self.len,
+            self.elem_size
+        )
+    }
+}
+
+/// Record a pointer argument (called by generated wrappers)
+#[inline(always)]
+pub fn record_arg<T>(function: &'static str, name: &'static str, ptr: *const T, len: usize) {
+    #[cfg(feature = "pointer-log")]
+    imp::push(function, name, Direction::Arg, ptr as usize, len, core::mem::size_of::<T>());
+    #[cfg(not(feature = "pointer-log"))]
+    let _ = (function, name, ptr, len);
+}
+
+/// Record a pointer returned by Zig (called by generated wrappers)
+#[inline(always)]
+pub fn record_return<T>(function: &'static str, ptr: *const T) {
+    #[cfg(feature = "pointer-log")]
+    imp::push(
+        function,
+        "return",
+        Direction::Return,
+        ptr as usize,
+        1,
+        core::mem::size_of::<T>(),
+    );
+    #[cfg(not(feature = "pointer-log"))]
+    let _ = (function, ptr);
+}
+
+/// Recorded crossings, oldest first
+pub fn snapshot() -> Vec<Crossing> {
+    #[cfg(feature = "pointer-log")]
+    return imp::snapshot();
+    #[cfg(not(feature = "pointer-log"))]
+    Vec::new()
+}
+
+/// Recorded crossings, one per line, oldest first
+pub fn dump() -> String {
+    let mut out = String::new();
+    for crossing in snapshot() {
+        out.push_str(&crossing.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// Forget all recorded crossings
+pub fn clear() {
+    #[cfg(feature = "pointer-log")]
+    imp::clear();
+}
+
+/// Print the log to stderr on panic, then run the previously installed hook
+#[cfg(feature = "std")]
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        eprintln!("autozig pointer log (oldest first):\n{}", dump());
+        previous(info);
+    }));
+}
+
+#[cfg(feature = "pointer-log")]
+mod imp {
+    use std::{
+        collections::VecDeque,
+        sync::{
+            Mutex,
+            OnceLock,
+        },
+        time::Instant,
+    };
+
+    use super::*;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    static LOG: Mutex<VecDeque<Crossing>> = Mutex::new(VecDeque::new());
+
+    pub(super) fn push(
+        function: &'static str,
+        name: &'static str,
+        direction: Direction,
+        addr: usize,
+        len: usize,
+        elem_size: usize,
+    ) {
+        let timestamp = START.get_or_init(Instant::now).elapsed();
+        let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+        if log.len() == CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(Crossing {
+            timestamp,
+            function,
+            name,
+            direction,
+            addr,
+            len,
+            elem_size,
+        });
+    }
+
+    pub(super) fn snapshot() -> Vec<Crossing> {
+        LOG.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub(super) fn clear() {
+        LOG.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossing_display() {
+        let crossing = Crossing {
+            timestamp: Duration::from_millis(1500),
+            function: "checksum",
+            name: "data",
+            direction: Direction::Arg,
+            addr: 0x1000,
+            len: 4,
+            elem_size: 2,
+        };
+        assert_eq!(
+            crossing.to_string(),
+            "[  1.500000s] -> checksum(data) 0x1000..0x1008 (4 x 2 bytes)"
+        );
+    }
+
+    #[cfg(feature = "pointer-log")]
+    #[test]
+    fn test_record_and_dump() {
+        let data = [1u32, 2, 3];
+        clear();
+        record_arg("sum", "data", data.as_ptr(), data.len());
+        record_return("sum", data.as_ptr());
+        let log = snapshot();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].addr, data.as_ptr() as usize);
+        assert_eq!((log[0].len, log[0].elem_size), (3, 4));
+        assert_eq!(log[1].direction, Direction::Return);
+        assert!(dump().contains("<- sum(return)"));
+    }
+}
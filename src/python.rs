@@ -0,0 +1,87 @@
+#![allow(unsafe_code)]
+//! Registry of the Python bindings (`python` feature)
+//!
+//! Every `autozig!` block with `#[autozig(python)]` items submits a
+//! [`Registration`] adding its functions and classes to a module, and
+//! [`python_module!`](crate::python_module) defines the extension module
+//! adding all of them, so a crate built with maturin needs no other glue:
+//!
+//! ```rust,ignore
+//! use autozig::autozig;
+//!
+//! autozig! {
+//!     // ...
+//!     ---
+//!     #[autozig(python)]
+//!     fn checksum(data: &[u8]) -> u32;
+//! }
+//!
+//! autozig::python_module!(fast_img);
+//! ```
+//!
+//! autozig does not depend on pyo3 itself; the generated code uses the
+//! crate's own `pyo3` dependency, so registrations pass the module as a
+//! pointer.
+
+pub use inventory;
+
+/// Adds one block's Python functions and classes to a module
+pub struct Registration {
+    /// Called with a pointer to the `pyo3::Bound<PyModule>` being
+    /// initialized; the error is the boxed `pyo3::PyErr`
+    pub register:
+        unsafe fn(module: *const std::ffi::c_void) -> Result<(), Box<dyn std::any::Any + Send>>,
+}
+
+inventory::collect!(Registration);
+
+/// Registrations of all blocks linked into the extension
+pub fn registrations() -> impl Iterator<Item = &'static Registration> {
+    inventory::iter::<Registration>.into_iter()
+}
+
+/// Define the `#[pymodule]` named `$name` exporting every
+/// `#[autozig(python)]` function and opaque type of the crate
+///
+/// `$name` must match the `lib.name` the extension is imported as.
+#[macro_export]
+macro_rules! python_module {
+    ($name:ident) => {
+        #[::pyo3::pymodule]
+        fn $name(module: &::pyo3::Bound<'_, ::pyo3::types::PyModule>) -> ::pyo3::PyResult<()> {
+            for registration in $crate::python::registrations() {
+                let module = module as *const ::pyo3::Bound<'_, ::pyo3::types::PyModule>;
+                // SAFETY: registrations are generated for this pyo3 version
+                unsafe { (registration.register)(module.cast()) }.map_err(|err| {
+                    *err.downcast::<::pyo3::PyErr>()
+                        .expect("autozig Python registration returned a foreign error")
+                })?;
+            }
+            Ok(())
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn count(module: *const std::ffi::c_void) -> Result<(), Box<dyn std::any::Any + Send>> {
+        *(module as *mut usize) += 1;
+        Ok(())
+    }
+
+    inventory::submit! {
+        Registration { register: count }
+    }
+
+    #[test]
+    fn test_registrations_are_collected() {
+        let mut calls = 0usize;
+        for registration in registrations() {
+            let module = std::ptr::addr_of_mut!(calls) as *const std::ffi::c_void;
+            unsafe { (registration.register)(module) }.unwrap();
+        }
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,273 @@
+//! Dedicated thread pool for blocking Zig calls
+//!
+//! Async wrappers normally offload their Zig call to tokio's blocking pool,
+//! which also serves file I/O and DNS lookups; heavy Zig compute can starve
+//! it. After [`configure`], the wrappers of tokio functions (the default
+//! runtime) run their calls on autozig's own workers instead:
+//!
+//! ```rust,ignore
+//! #[tokio::main]
+//! async fn main() {
+//!     autozig::runtime::configure(4);
+//!     let frame = render(scene).await; // runs on an `autozig-worker-N` thread
+//!     println!("{:?}", autozig::runtime::stats());
+//! }
+//! ```
+//!
+//! Functions declared with `#[autozig(runtime = "autozig")]` always use the
+//! pool, starting it with one worker per CPU if it was not configured, and
+//! work with any executor. Panics in Zig calls resume on the awaiting task.
+//!
+//! [`stats`] reports the queue depth; with the `metrics` feature it is also
+//! published as the [`QUEUE_DEPTH`] gauge.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    panic::{
+        self,
+        AssertUnwindSafe,
+    },
+    pin::Pin,
+    sync::{
+        atomic::{
+            AtomicU64,
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        Condvar,
+        Mutex,
+        OnceLock,
+    },
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+    thread,
+};
+
+/// Gauge of the calls waiting for a worker
+pub const QUEUE_DEPTH: &str = "autozig_pool_queue_depth";
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    threads: usize,
+    queue: Mutex<VecDeque<Job>>,
+    queued: Condvar,
+    active: AtomicUsize,
+    completed: AtomicU64,
+}
+
+static POOL: OnceLock<&'static Pool> = OnceLock::new();
+
+impl Pool {
+    fn start(threads: usize) -> &'static Pool {
+        let pool: &'static Pool = Box::leak(Box::new(Pool {
+            threads,
+            queue: Mutex::new(VecDeque::new()),
+            queued: Condvar::new(),
+            active: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+        }));
+        for index in 0..threads {
+            thread::Builder::new()
+                .name(format!("autozig-worker-{index}"))
+                .spawn(move || pool.work())
+                .expect("failed to start autozig worker thread");
+        }
+        pool
+    }
+
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(job) = queue.pop_front() {
+                        report_depth(queue.len());
+                        break job;
+                    }
+                    queue = self.queued.wait(queue).unwrap();
+                }
+            };
+            self.active.fetch_add(1, Ordering::Relaxed);
+            job();
+            self.active.fetch_sub(1, Ordering::Relaxed);
+            self.completed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn submit<T, F>(&self, f: F) -> Task<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot { result: None, waker: None }));
+        let filled = Arc::clone(&slot);
+        let job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            let mut slot = filled.lock().unwrap();
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+        let mut queue = self.queue.lock().unwrap();
+        queue.push_back(job);
+        report_depth(queue.len());
+        self.queued.notify_one();
+        Task { slot }
+    }
+}
+
+#[inline(always)]
+fn report_depth(depth: usize) {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!(QUEUE_DEPTH).set(depth as f64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = depth;
+}
+
+struct Slot<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Result of a call running on the pool
+#[must_use = "the call runs anyway, but its result is lost"]
+pub struct Task<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Future for Task<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Start the pool with `threads` workers
+///
+/// Returns `false` (and changes nothing) if the pool is already running.
+///
+/// # Panics
+///
+/// If `threads` is zero.
+pub fn configure(threads: usize) -> bool {
+    assert!(threads > 0, "the autozig thread pool needs at least one thread");
+    let mut started = false;
+    POOL.get_or_init(|| {
+        started = true;
+        Pool::start(threads)
+    });
+    started
+}
+
+/// Run `f` on the pool, starting it with one worker per CPU if needed
+/// (used by `#[autozig(runtime = "autozig")]` wrappers)
+pub fn spawn<T, F>(f: F) -> Task<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = POOL.get_or_init(|| {
+        Pool::start(thread::available_parallelism().map_or(4, |threads| threads.get()))
+    });
+    pool.submit(f)
+}
+
+/// Run `f` on the pool if [`configure`] started it, or hand it back (used
+/// by tokio wrappers)
+pub fn try_spawn<T, F>(f: F) -> Result<Task<T>, F>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match POOL.get() {
+        Some(pool) => Ok(pool.submit(f)),
+        None => Err(f),
+    }
+}
+
+/// State of the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of workers
+    pub threads: usize,
+    /// Calls waiting for a worker
+    pub queued: usize,
+    /// Calls running
+    pub active: usize,
+    /// Calls finished since the pool started
+    pub completed: u64,
+}
+
+/// State of the pool, if it was started
+pub fn stats() -> Option<PoolStats> {
+    let pool = POOL.get()?;
+    Some(PoolStats {
+        threads: pool.threads,
+        queued: pool.queue.lock().unwrap().len(),
+        active: pool.active.load(Ordering::Relaxed),
+        completed: pool.completed.load(Ordering::Relaxed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool() {
+        assert!(try_spawn(|| 1).is_err());
+        assert!(stats().is_none());
+
+        assert!(configure(2));
+        assert!(!configure(8));
+        let tasks: Vec<_> = (0..16)
+            .map(|i| try_spawn(move || (i, thread::current().name().map(str::to_string))))
+            .map(|task| task.ok().unwrap())
+            .collect();
+        for (i, task) in tasks.into_iter().enumerate() {
+            let (value, name) = futures::executor::block_on(task);
+            assert_eq!(value, i);
+            assert!(name.unwrap().starts_with("autozig-worker-"));
+        }
+
+        let panicked = panic::catch_unwind(|| {
+            futures::executor::block_on(spawn(|| -> u32 { panic!("zig call failed") }))
+        });
+        assert!(panicked.is_err());
+
+        // Workers count a call after waking its task
+        let idle = (0..100).find_map(|_| {
+            let stats = stats().unwrap();
+            if stats.completed < 17 {
+                thread::sleep(std::time::Duration::from_millis(10));
+                return None;
+            }
+            Some(stats)
+        });
+        assert_eq!(
+            idle,
+            Some(PoolStats {
+                threads: 2,
+                queued: 0,
+                active: 0,
+                completed: 17
+            })
+        );
+    }
+}
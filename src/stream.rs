@@ -198,6 +198,56 @@ pub fn create_stream<T>(
     (tx, ZigStream::new(rx))
 }
 
+/// Stream type returned by generated `-> impl Stream<Item = T>` wrappers
+pub use futures::Stream;
+
+/// Items kept in flight between a blocking producer and its consumer
+pub const PRODUCER_CAPACITY: usize = 16;
+
+/// Stream of the items a blocking producer emits
+///
+/// `produce` runs on tokio's blocking thread pool once the stream is first
+/// polled and hands each item to the `emit` callback it receives. `emit`
+/// waits while [`PRODUCER_CAPACITY`] items are unconsumed and returns
+/// `false` once the stream has been dropped, so the producer can stop early.
+/// Used by the wrappers of Zig functions declared as returning
+/// `impl Stream<Item = T>`.
+///
+/// ```rust,ignore
+/// let numbers = autozig::stream::from_producer(|emit| {
+///     for n in 0..10u32 {
+///         if !emit(n) {
+///             break;
+///         }
+///     }
+/// });
+/// ```
+pub fn from_producer<T, P>(produce: P) -> impl Stream<Item = T> + Send
+where
+    T: Send + 'static,
+    P: FnOnce(&mut dyn FnMut(T) -> bool) + Send + 'static,
+{
+    enum Producer<P, T> {
+        Idle(P),
+        Running(tokio::sync::mpsc::Receiver<T>),
+    }
+
+    futures::stream::unfold(Producer::Idle(produce), |producer| async move {
+        let mut receiver = match producer {
+            Producer::Idle(produce) => {
+                let (tx, rx) = tokio::sync::mpsc::channel(PRODUCER_CAPACITY);
+                tokio::task::spawn_blocking(move || {
+                    produce(&mut |item| tx.blocking_send(item).is_ok());
+                });
+                rx
+            },
+            Producer::Running(receiver) => receiver,
+        };
+        let item = receiver.recv().await?;
+        Some((item, Producer::Running(receiver)))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use futures::StreamExt;
@@ -303,6 +353,33 @@ mod tests {
         assert!(tx.send(Ok(vec![5, 6, 7, 8])).is_err());
     }
 
+    #[tokio::test]
+    async fn test_from_producer() {
+        let numbers = from_producer(|emit| {
+            for n in 0..100u32 {
+                if !emit(n) {
+                    break;
+                }
+            }
+        });
+        let collected: Vec<u32> = numbers.collect().await;
+        assert_eq!(collected, (0..100).collect::<Vec<_>>());
+
+        // Dropping the stream stops an endless producer
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let endless = from_producer(move |emit| {
+            let mut n = 0u64;
+            while emit(n) {
+                n += 1;
+            }
+            let _ = done_tx.send(());
+        });
+        let mut endless = Box::pin(endless);
+        assert_eq!(endless.next().await, Some(0));
+        drop(endless);
+        done_rx.await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_multiple_consumers() {
         let (tx, stream) = create_stream::<TestU32>();
@@ -0,0 +1,212 @@
+#![allow(unsafe_code)]
+//! Zig test integration and reproducible Zig behavior for tests
+//!
+//! `autozig::zig_tests!("zig/")` registers every `test` block of the Zig
+//! files in a directory as a Rust `#[test]`, running the executables built by
+//! `autozig_build::build_tests`:
+//!
+//! ```rust,ignore
+//! // tests/zig_tests.rs
+//! autozig::zig_tests!("zig/");
+//! ```
+//!
+//! Zig kernels that draw memory and randomness from `autozig_runtime`
+//! (`autozig_runtime.allocator()`, `autozig_runtime.random()`) can be made
+//! deterministic by building with the test shims enabled, either via
+//! `autozig_build::Builder::test_shims(true)` or `AUTOZIG_TEST_SHIMS=1`.
+//! The shims back the allocator with a fixed buffer and the random source
+//! with a seedable PRNG, which the functions below control.
+//!
+//! Calling the shim controls without the shims enabled fails at link time.
+//!
+//! ```rust,ignore
+//! #[test]
+//! fn golden_output() {
+//!     autozig::testing::reset(42);
+//!     assert_eq!(shuffle_and_sum(&[1, 2, 3]), 6);
+//! }
+//! ```
+
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    process::Command,
+    sync::{
+        Arc,
+        Mutex,
+        OnceLock,
+    },
+};
+
+extern "C" {
+    fn autozig_init_rng(seed: u64);
+    fn autozig_reset_allocator();
+}
+
+/// Re-seed the PRNG behind `autozig_runtime.random()`
+pub fn init_rng(seed: u64) {
+    unsafe { autozig_init_rng(seed) }
+}
+
+/// Release every allocation made through `autozig_runtime.allocator()`
+///
+/// Subsequent allocations start again from the beginning of the buffer, so
+/// their addresses repeat across runs.
+pub fn reset_allocator() {
+    unsafe { autozig_reset_allocator() }
+}
+
+/// Reset both the allocator and the PRNG (seeded with `seed`)
+pub fn reset(seed: u64) {
+    reset_allocator();
+    init_rng(seed);
+}
+
+/// Outcome of one run of a Zig test executable
+#[derive(Debug, Clone)]
+pub struct ZigTestRun {
+    /// Whether the executable exited successfully
+    pub success: bool,
+    /// Combined stdout and stderr
+    pub output: String,
+}
+
+impl ZigTestRun {
+    /// Result of the test reported as `label` (`test.<name>` or
+    /// `decltest.<decl>`)
+    ///
+    /// The Zig test runner only prints tests that fail or are skipped, so any
+    /// other test passed, unless the executable aborted (e.g. on a panic)
+    /// before printing its summary.
+    pub fn outcome(&self, label: &str) -> Result<(), String> {
+        let marker = format!("{label}...");
+        if let Some(line) = self.output.lines().find(|line| line.contains(&marker)) {
+            let status = &line[line.find(&marker).unwrap_or(0) + marker.len()..];
+            if !status.starts_with("SKIP") {
+                return Err(self.output.trim_end().to_string());
+            }
+        }
+        if !self.success && !self.output.contains("passed") {
+            return Err(format!("test executable aborted\n{}", self.output.trim_end()));
+        }
+        Ok(())
+    }
+}
+
+/// Run the Zig test reported as `label` from the test executable at `exe`
+/// (built by `autozig_build::build_tests`), panicking if it failed
+///
+/// The executable runs once per process; its result is shared by every test
+/// registered from it. Usually called from code generated by
+/// [`zig_tests!`](crate::zig_tests).
+pub fn run_zig_test(exe: &str, label: &str) {
+    if let Err(message) = zig_test_run(exe).outcome(label) {
+        panic!("Zig test `{label}` failed:\n{message}");
+    }
+}
+
+fn zig_test_run(exe: &str) -> Arc<ZigTestRun> {
+    static RUNS: Mutex<BTreeMap<String, Arc<OnceLock<Arc<ZigTestRun>>>>> =
+        Mutex::new(BTreeMap::new());
+
+    let slot = RUNS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(exe.to_string())
+        .or_default()
+        .clone();
+    slot.get_or_init(|| {
+        let output = Command::new(exe)
+            .output()
+            .unwrap_or_else(|e| panic!("Failed to run Zig test executable {exe}: {e}"));
+        Arc::new(ZigTestRun {
+            success: output.status.success(),
+            output: format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        })
+    })
+    .clone()
+}
+
+/// Compare the ABI snapshot of the current build with the committed copy at
+/// `golden_path`, panicking with the changed lines if they differ
+///
+/// Usually called from the test generated by
+/// [`abi_snapshot_test!`](crate::abi_snapshot_test).
+pub fn assert_abi_snapshot(golden_path: &str, current: &str) {
+    let golden = std::fs::read_to_string(golden_path)
+        .unwrap_or_else(|e| panic!("Failed to read ABI snapshot {golden_path}: {e}"));
+    if let Some(diff) = abi_diff(&golden, current) {
+        panic!(
+            "ABI changed compared to {golden_path}:\n{diff}\n\nIf the change is intended, rebuild \
+             with AUTOZIG_UPDATE_ABI_SNAPSHOTS=1 and commit the updated snapshot"
+        );
+    }
+}
+
+/// Lines removed from (`-`) and added to (`+`) the `golden` ABI snapshot, or
+/// `None` if it matches `current`
+fn abi_diff(golden: &str, current: &str) -> Option<String> {
+    let golden = golden.replace("\r\n", "\n");
+    if golden == current {
+        return None;
+    }
+    let old: BTreeSet<&str> = golden.lines().collect();
+    let new: BTreeSet<&str> = current.lines().collect();
+    let mut diff: Vec<String> = old
+        .difference(&new)
+        .map(|line| format!("- {line}"))
+        .collect();
+    diff.extend(new.difference(&old).map(|line| format!("+ {line}")));
+    if diff.is_empty() {
+        return Some("(same declarations in a different order)".to_string());
+    }
+    Some(diff.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zig_test_outcome() {
+        let run = ZigTestRun {
+            success: false,
+            output: concat!(
+                "2/3 math.test.factorial overflow...FAIL (TestUnexpectedResult)\n",
+                "3/3 math.decltest.fib...SKIP\n",
+                "1 passed; 1 skipped; 1 failed.\n",
+            )
+            .to_string(),
+        };
+        assert!(run.outcome("test.factorial basic cases").is_ok());
+        assert!(run.outcome("decltest.fib").is_ok());
+        let err = run.outcome("test.factorial overflow").unwrap_err();
+        assert!(err.contains("2/3 math.test.factorial overflow...FAIL"));
+
+        let aborted = ZigTestRun {
+            success: false,
+            output: "thread 1 panic: index out of bounds\n".to_string(),
+        };
+        assert!(aborted.outcome("test.factorial basic cases").is_err());
+    }
+
+    #[test]
+    fn test_abi_diff() {
+        let golden = "extern \"C\" {\n    fn add(a: i32, b: i32) -> i32;\n}\n";
+        assert_eq!(abi_diff(golden, golden), None);
+        assert_eq!(abi_diff(&golden.replace('\n', "\r\n"), golden), None);
+
+        let changed = golden.replace("b: i32", "b: i64");
+        let diff = abi_diff(golden, &changed).unwrap();
+        assert_eq!(
+            diff,
+            "-     fn add(a: i32, b: i32) -> i32;\n+     fn add(a: i32, b: i64) -> i32;"
+        );
+    }
+}
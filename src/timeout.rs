@@ -0,0 +1,50 @@
+//! Time limits on async Zig calls
+//!
+//! Async functions declared with `#[autozig(timeout_ms = N)]` stop waiting
+//! for their blocking call after `N` milliseconds and return
+//! `Err(AutoZigTimeout)`:
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     // ...
+//!     ---
+//!     #[autozig(timeout_ms = 5000)]
+//!     async fn render(scene: Vec<u8>) -> u32;
+//! }
+//!
+//! match render(scene).await {
+//!     Ok((frame, _scene)) => println!("{frame}"),
+//!     Err(timeout) => eprintln!("{timeout}"),
+//! }
+//! ```
+//!
+//! The timer is `tokio::time::timeout`, so the crate's tokio needs the `time`
+//! feature. Zig code cannot be interrupted: the call keeps its blocking
+//! thread until it returns, and its result is then dropped.
+
+use std::{
+    fmt,
+    time::Duration,
+};
+
+/// An async Zig call did not finish within its `timeout_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoZigTimeout {
+    /// Name of the Zig function
+    pub function: &'static str,
+    /// The exceeded time limit
+    pub timeout: Duration,
+}
+
+impl fmt::Display for AutoZigTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Zig call `{}` timed out after {} ms",
+            self.function,
+            self.timeout.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for AutoZigTimeout {}
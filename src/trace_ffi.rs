@@ -0,0 +1,156 @@
+//! Tracing spans around Zig calls, for performance debugging
+//!
+//! With the `trace-ffi` feature enabled, every generated wrapper makes its
+//! FFI call inside a `zig_ffi` span (target `autozig::ffi`, level `TRACE`)
+//! whose fields are the Rust function name and the byte sizes of its slice
+//! and string arguments. Async wrappers enter the span on the blocking
+//! thread, so it covers the Zig call and not the wait for a worker. Any
+//! subscriber that times spans then shows where Zig time goes:
+//!
+//! ```text
+//! RUST_LOG=autozig::ffi=trace
+//! zig_ffi{function="blur" args=pixels=4194304 radius_lut=64}: close time.busy=3.1ms
+//! ```
+//!
+//! Without the feature the spans are empty structs and compile to nothing.
+
+/// Span of one FFI call, created by generated wrappers before the call
+#[must_use]
+pub struct FfiSpan {
+    #[cfg(feature = "trace-ffi")]
+    span: tracing::Span,
+}
+
+/// Guard of an entered [`FfiSpan`]; the span is exited when it is dropped
+pub struct Entered {
+    #[cfg(feature = "trace-ffi")]
+    _entered: tracing::span::EnteredSpan,
+}
+
+/// Span of a call of `function` passing `args` (parameter names and byte
+/// sizes), if a subscriber is interested in it
+#[inline(always)]
+pub fn span(function: &'static str, args: &[(&'static str, usize)]) -> FfiSpan {
+    #[cfg(feature = "trace-ffi")]
+    return FfiSpan {
+        span: tracing::trace_span!(
+            target: "autozig::ffi",
+            "zig_ffi",
+            function,
+            args = %imp::ArgSizes(args)
+        ),
+    };
+    #[cfg(not(feature = "trace-ffi"))]
+    {
+        let _ = (function, args);
+        FfiSpan {}
+    }
+}
+
+impl FfiSpan {
+    /// Enter the span until the returned guard is dropped
+    #[inline(always)]
+    pub fn entered(self) -> Entered {
+        Entered {
+            #[cfg(feature = "trace-ffi")]
+            _entered: self.span.entered(),
+        }
+    }
+}
+
+#[cfg(feature = "trace-ffi")]
+mod imp {
+    use std::fmt;
+
+    /// `name=bytes` pairs separated by spaces, formatted only if recorded
+    pub(super) struct ArgSizes<'a>(pub(super) &'a [(&'static str, usize)]);
+
+    impl fmt::Display for ArgSizes<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for (i, (name, bytes)) in self.0.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{name}={bytes}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "trace-ffi"))]
+mod tests {
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    use tracing::{
+        field::{
+            Field,
+            Visit,
+        },
+        span,
+        subscriber::with_default,
+        Event,
+        Metadata,
+        Subscriber,
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}={value:?}", field.name()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+            self.0
+                .lock()
+                .unwrap()
+                .push(attrs.metadata().name().to_string());
+            attrs.record(&mut self.clone());
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &span::Id) {
+            self.0.lock().unwrap().push("enter".to_string());
+        }
+
+        fn exit(&self, _: &span::Id) {
+            self.0.lock().unwrap().push("exit".to_string());
+        }
+    }
+
+    #[test]
+    fn test_ffi_span() {
+        let recorder = Recorder::default();
+        with_default(recorder.clone(), || {
+            let data = [0u32; 4];
+            let _entered =
+                span("checksum", &[("data", std::mem::size_of_val(&data)), ("key", 3)]).entered();
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            ["zig_ffi", "function=\"checksum\"", "args=data=16 key=3", "enter", "exit"]
+        );
+    }
+}
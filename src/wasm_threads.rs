@@ -0,0 +1,106 @@
+//! # Threaded WASM on a shared memory
+//!
+//! This module hands out raw pointers into Rust buffers.
+#![allow(unsafe_code)]
+
+//! With `Builder::wasm_threads(true)` the Zig code is compiled with atomics
+//! and bulk memory, so a module built for shared memory can be instantiated
+//! once per Web Worker on the same `SharedArrayBuffer`. Each worker then runs
+//! a Zig function on its own part of a buffer owned by Rust:
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     export fn invert_chunk(ptr: [*]u8, len: usize) void {
+//!         for (ptr[0..len]) |*byte| byte.* = 255 - byte.*;
+//!     }
+//!     ---
+//!     fn invert_chunk(data: &mut [u8]);
+//! }
+//!
+//! #[wasm_bindgen]
+//! pub fn split_image(pixels: &mut [u8], workers: usize) -> Vec<usize> {
+//!     SharedChunk::split(pixels, workers)
+//!         .iter()
+//!         .flat_map(|chunk| [chunk.ptr, chunk.len])
+//!         .collect()
+//! }
+//! ```
+//!
+//! and on the JavaScript side every worker calls
+//! `instance.exports.invert_chunk(ptr, len)` with one `(ptr, len)` pair.
+//! The Rust code must be built for shared memory too:
+//! `RUSTFLAGS="-C target-feature=+atomics,+bulk-memory -C
+//! link-arg=--shared-memory -C link-arg=--import-memory"` with
+//! `-Z build-std=std,panic_abort`.
+
+use alloc::vec::Vec;
+
+/// Whether this module was compiled for a shared WASM memory
+pub const fn is_shared_memory() -> bool {
+    cfg!(all(target_family = "wasm", target_feature = "atomics"))
+}
+
+/// A part of a buffer, as an address in linear memory and a length in
+/// elements
+///
+/// Chunks from one [`SharedChunk::split`] never overlap, so each can be given
+/// to a different worker.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedChunk {
+    /// Address of the first element
+    pub ptr: usize,
+    /// Number of elements
+    pub len: usize,
+}
+
+impl SharedChunk {
+    /// Split `data` into at most `parts` disjoint chunks of nearly equal size
+    ///
+    /// The buffer stays borrowed by the caller: the chunks are only valid
+    /// while it is neither moved, resized nor accessed through `data`.
+    pub fn split<T>(data: &mut [T], parts: usize) -> Vec<SharedChunk> {
+        let size = data.len().div_ceil(parts.max(1)).max(1);
+        data.chunks_mut(size)
+            .map(|chunk| SharedChunk {
+                ptr: chunk.as_mut_ptr() as usize,
+                len: chunk.len(),
+            })
+            .collect()
+    }
+
+    /// View the chunk as a slice, e.g. on the worker thread that owns it
+    ///
+    /// # Safety
+    /// The chunk must come from [`SharedChunk::split`] over a `[T]` that is
+    /// still alive and not otherwise accessed for `'a`, and no other view of
+    /// the same chunk may exist.
+    pub unsafe fn as_mut_slice<'a, T>(self) -> &'a mut [T] {
+        // SAFETY: guaranteed by the caller
+        unsafe { core::slice::from_raw_parts_mut(self.ptr as *mut T, self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_split_into_disjoint_chunks() {
+        let mut data: Vec<u16> = (0..10).collect();
+        let chunks = SharedChunk::split(&mut data, 3);
+        assert_eq!(chunks.iter().map(|chunk| chunk.len).collect::<Vec<_>>(), [4, 4, 2]);
+        assert_eq!(chunks[1].ptr, chunks[0].ptr + 4 * core::mem::size_of::<u16>());
+
+        for chunk in &chunks {
+            // SAFETY: the chunks are disjoint and `data` is untouched meanwhile
+            let slice = unsafe { chunk.as_mut_slice::<u16>() };
+            slice.iter_mut().for_each(|value| *value *= 2);
+        }
+        assert_eq!(data, (0..10).map(|value| value * 2).collect::<Vec<_>>());
+        assert!(SharedChunk::split::<u8>(&mut [], 4).is_empty());
+        assert!(!is_shared_memory());
+    }
+}
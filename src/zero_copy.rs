@@ -263,6 +263,182 @@ impl<T> AsRef<[T]> for ZeroCopyBuffer<T> {
     }
 }
 
+/// Memory-mapped file or anonymous region handed to Zig without copying
+///
+/// The mapping derefs to `&[u8]`, so it is passed to Zig functions taking
+/// `&[u8]` (`[*]const u8, usize` in Zig) like any other slice; the kernel
+/// pages the file in on demand, so multi-GB inputs never touch the heap. Zig
+/// can wrap the pair in the injected `AutoZigMapped` for bounds-checked
+/// reads:
+///
+/// ```rust,ignore
+/// use autozig::zero_copy::MappedBuffer;
+///
+/// autozig! {
+///     export fn count_records(ptr: [*]const u8, len: usize) u64 {
+///         const data = AutoZigMapped.init(ptr, len);
+///         var offset: usize = 0;
+///         var count: u64 = 0;
+///         while (data.readInt(u32, offset, .little)) |size| : (count += 1) {
+///             offset += 4 + size;
+///         }
+///         return count;
+///     }
+///     ---
+///     fn count_records(data: &[u8]) -> u64;
+/// }
+///
+/// let log = MappedBuffer::open("events.bin")?;
+/// println!("{} records", count_records(&log));
+/// ```
+///
+/// Requires the `mmap` feature and a Unix target.
+#[cfg(all(unix, feature = "mmap"))]
+pub struct MappedBuffer {
+    ptr: std::ptr::NonNull<u8>,
+    len: usize,
+    writable: bool,
+}
+
+// The mapping is plain memory owned by this value
+#[cfg(all(unix, feature = "mmap"))]
+unsafe impl Send for MappedBuffer {}
+#[cfg(all(unix, feature = "mmap"))]
+unsafe impl Sync for MappedBuffer {}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl MappedBuffer {
+    /// Map `path` read-only
+    ///
+    /// The file must not be truncated while mapped: accessing pages past
+    /// its new end raises `SIGBUS`.
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::map_file(&file, libc::PROT_READ, libc::MAP_PRIVATE)
+    }
+
+    /// Map `path` for reading and writing; writes go to the file (see
+    /// [`flush`](Self::flush))
+    pub fn open_mut(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        Self::map_file(&file, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)
+    }
+
+    /// Map `len` zeroed bytes not backed by a file
+    pub fn anonymous(len: usize) -> std::io::Result<Self> {
+        Self::map(
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+        )
+    }
+
+    fn map_file(file: &std::fs::File, prot: i32, flags: i32) -> std::io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "file too large to map")
+        })?;
+        // The mapping stays valid after the descriptor is closed
+        Self::map(len, prot, flags, file.as_raw_fd())
+    }
+
+    fn map(len: usize, prot: i32, flags: i32, fd: i32) -> std::io::Result<Self> {
+        let writable = prot & libc::PROT_WRITE != 0;
+        // mmap rejects empty mappings
+        if len == 0 {
+            return Ok(Self {
+                ptr: std::ptr::NonNull::dangling(),
+                len,
+                writable,
+            });
+        }
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), len, prot, flags, fd, 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        let ptr = std::ptr::NonNull::new(ptr.cast()).expect("mmap returned null");
+        Ok(Self { ptr, len, writable })
+    }
+
+    /// Number of mapped bytes
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the mapping is empty
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The mapped bytes
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The mapped bytes, or `None` for a read-only mapping
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> Option<&mut [u8]> {
+        self.writable
+            .then(|| unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) })
+    }
+
+    /// Write modified pages of a file mapping back to the file
+    pub fn flush(&self) -> std::io::Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        if unsafe { libc::msync(self.ptr.as_ptr().cast(), self.len, libc::MS_SYNC) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl std::ops::Deref for MappedBuffer {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl AsRef<[u8]> for MappedBuffer {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl std::fmt::Debug for MappedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedBuffer")
+            .field("len", &self.len)
+            .field("writable", &self.writable)
+            .finish()
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl Drop for MappedBuffer {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
+
 // Ensure RawVec has the same layout as Vec
 #[cfg(test)]
 mod layout_tests {
@@ -370,3 +546,36 @@ mod tests {
         assert_eq!(recovered[size as usize - 1], (size - 1));
     }
 }
+
+#[cfg(all(test, unix, feature = "mmap"))]
+mod mapped_tests {
+    use std::io::Write;
+
+    use super::MappedBuffer;
+
+    #[test]
+    fn test_mapped_buffer() {
+        let path = std::env::temp_dir().join(format!("autozig-mmap-{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"hello zig")
+            .unwrap();
+
+        let mut read_only = MappedBuffer::open(&path).unwrap();
+        assert_eq!(&read_only[..], b"hello zig");
+        assert!(read_only.as_mut_slice().is_none());
+
+        let mut shared = MappedBuffer::open_mut(&path).unwrap();
+        shared.as_mut_slice().unwrap()[..5].copy_from_slice(b"HELLO");
+        shared.flush().unwrap();
+        drop(shared);
+        assert_eq!(std::fs::read(&path).unwrap(), b"HELLO zig");
+        std::fs::remove_file(&path).unwrap();
+
+        let mut anonymous = MappedBuffer::anonymous(4096).unwrap();
+        assert!(anonymous.iter().all(|&b| b == 0));
+        anonymous.as_mut_slice().unwrap()[4095] = 1;
+        assert_eq!(anonymous[4095], 1);
+        assert!(MappedBuffer::anonymous(0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+#![allow(unsafe_code)]
+//! Zig stack traces of panics and errors
+//!
+//! With `autozig_build::Builder::zig_stack_traces(true)`, the engine builds
+//! the Zig code with frame pointers and error return traces and injects a
+//! panic handler and an error hook that format the Zig stack trace and hand
+//! it to the functions below:
+//!
+//! - a Zig panic runs Rust's panic hook with the message and the Zig frames (so
+//!   crash reporters see them), then aborts, since Rust cannot unwind through
+//!   Zig frames;
+//! - an error returned from a Zig error union leaves its error return trace for
+//!   [`take_error_trace`], and wrappers declared as returning `Result<T,
+//!   Traced<E>>` attach it to the error:
+//!
+//! ```rust,ignore
+//! autozig! {
+//!     // ...
+//!     ---
+//!     fn parse(s: &str) -> Result<i32, autozig::zig_trace::Traced<ParseError>>;
+//! }
+//!
+//! if let Err(err) = parse("12x") {
+//!     eprintln!("{err}"); // error.InvalidDigit, then the Zig frames
+//! }
+//! ```
+//!
+//! Frames are symbolized from the debug information of the final binary;
+//! without it (e.g. `strip = true`) they are plain addresses.
+
+use std::{
+    cell::RefCell,
+    fmt,
+};
+
+thread_local! {
+    static ERROR_TRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Zig stack trace of the last error returned to this thread, if not taken
+/// yet
+pub fn take_error_trace() -> Option<String> {
+    ERROR_TRACE.with(|trace| trace.borrow_mut().take())
+}
+
+/// `ptr[..len]` as text; invalid UTF-8 is replaced
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes.
+unsafe fn text(ptr: *const u8, len: usize) -> String {
+    if len == 0 {
+        return String::new();
+    }
+    String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).into_owned()
+}
+
+/// Error hook called by Zig before it returns an error to Rust
+///
+/// # Safety
+///
+/// `trace` must point to `trace_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn autozig_zig_error_trace(trace: *const u8, trace_len: usize) {
+    let trace = text(trace, trace_len);
+    ERROR_TRACE.with(|last| *last.borrow_mut() = Some(trace));
+}
+
+/// Panic handler called by Zig: runs the panic hook with the Zig message and
+/// stack trace, then aborts
+///
+/// # Safety
+///
+/// `msg` and `trace` must point to `msg_len` and `trace_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn autozig_zig_panic(
+    msg: *const u8,
+    msg_len: usize,
+    trace: *const u8,
+    trace_len: usize,
+) -> ! {
+    let report = panic_report(&text(msg, msg_len), &text(trace, trace_len));
+    // The panic cannot unwind into Zig; catching it still runs the hook
+    let _ = std::panic::catch_unwind(|| panic!("{report}"));
+    std::process::abort()
+}
+
+fn panic_report(msg: &str, trace: &str) -> String {
+    format!("Zig panicked: {msg}\n\nZig stack trace:\n{}", trace.trim_end())
+}
+
+/// Error of a Zig function together with the Zig stack trace that led to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Traced<E> {
+    error: E,
+    trace: Option<String>,
+}
+
+impl<E> Traced<E> {
+    /// `error`, with the trace Zig left for it on this thread (see
+    /// [`take_error_trace`])
+    pub fn new(error: E) -> Self {
+        Traced { error, trace: take_error_trace() }
+    }
+
+    /// The Zig error
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// The Zig error, without the trace
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Formatted Zig stack trace, if the build recorded one
+    pub fn trace(&self) -> Option<&str> {
+        self.trace.as_deref()
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Traced<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(trace) = &self.trace {
+            write!(f, "\n\nZig stack trace:\n{}", trace.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for Traced<E> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traced_error() {
+        let trace = "src/lib.zig:12:9: 0x1234 in parse (lib)\n";
+        unsafe { autozig_zig_error_trace(trace.as_ptr(), trace.len()) };
+        let err = Traced::new("error.InvalidDigit");
+        assert_eq!(err.trace(), Some(trace));
+        assert_eq!(
+            err.to_string(),
+            "error.InvalidDigit\n\nZig stack trace:\nsrc/lib.zig:12:9: 0x1234 in parse (lib)"
+        );
+        assert_eq!(take_error_trace(), None);
+        assert_eq!(Traced::new("error.Overflow").to_string(), "error.Overflow");
+        assert_eq!(
+            panic_report("index out of bounds", trace),
+            "Zig panicked: index out of bounds\n\nZig stack trace:\nsrc/lib.zig:12:9: 0x1234 in \
+             parse (lib)"
+        );
+    }
+}